@@ -123,6 +123,12 @@ mod tests {
       private_key: None,
       public_key: None,
       last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
     };
 
     let inserted_creator = User_::create(&conn, &creator_form).unwrap();
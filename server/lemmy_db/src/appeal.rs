@@ -0,0 +1,291 @@
+use crate::{limit_and_offset, naive_now, schema::appeal, Crud};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The mod action an appeal is against, which says what table its mod_action_id points into.
+/// Stored as an i16.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+pub enum AppealKind {
+  PostRemoval,
+  CommentRemoval,
+  CommunityBan,
+  SiteBan,
+}
+
+impl AppealKind {
+  pub fn from_i16(value: i16) -> Option<Self> {
+    match value {
+      0 => Some(AppealKind::PostRemoval),
+      1 => Some(AppealKind::CommentRemoval),
+      2 => Some(AppealKind::CommunityBan),
+      3 => Some(AppealKind::SiteBan),
+      _ => None,
+    }
+  }
+}
+
+/// Stored as an i16.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum AppealStatus {
+  Pending,
+  Approved,
+  Denied,
+}
+
+impl AppealStatus {
+  pub fn from_i16(value: i16) -> Option<Self> {
+    match value {
+      0 => Some(AppealStatus::Pending),
+      1 => Some(AppealStatus::Approved),
+      2 => Some(AppealStatus::Denied),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "appeal"]
+pub struct Appeal {
+  pub id: i32,
+  pub kind: i16,
+  pub mod_action_id: i32,
+  pub creator_id: i32,
+  pub community_id: Option<i32>,
+  pub reason: String,
+  pub status: i16,
+  pub resolver_id: Option<i32>,
+  pub published: chrono::NaiveDateTime,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "appeal"]
+pub struct AppealForm {
+  pub kind: i16,
+  pub mod_action_id: i32,
+  pub creator_id: i32,
+  pub community_id: Option<i32>,
+  pub reason: String,
+}
+
+impl Crud<AppealForm> for Appeal {
+  fn read(conn: &PgConnection, appeal_id: i32) -> Result<Self, Error> {
+    use crate::schema::appeal::dsl::*;
+    appeal.find(appeal_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, appeal_id: i32) -> Result<usize, Error> {
+    use crate::schema::appeal::dsl::*;
+    diesel::delete(appeal.find(appeal_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &AppealForm) -> Result<Self, Error> {
+    use crate::schema::appeal::dsl::*;
+    insert_into(appeal).values(form).get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, appeal_id: i32, form: &AppealForm) -> Result<Self, Error> {
+    use crate::schema::appeal::dsl::*;
+    diesel::update(appeal.find(appeal_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl Appeal {
+  /// The appeals against actions in the given communities, oldest first. Passing no communities
+  /// gives every appeal, including those against site bans.
+  pub fn list(
+    conn: &PgConnection,
+    for_community_ids: Option<Vec<i32>>,
+    pending_only: bool,
+    page: Option<i64>,
+    limit: Option<i64>,
+  ) -> Result<Vec<Self>, Error> {
+    use crate::schema::appeal::dsl::*;
+    let mut query = appeal.into_boxed();
+
+    if let Some(for_community_ids) = for_community_ids {
+      query = query.filter(community_id.eq_any(for_community_ids));
+    };
+
+    if pending_only {
+      query = query.filter(status.eq(AppealStatus::Pending as i16));
+    }
+
+    let (limit, offset) = limit_and_offset(page, limit);
+
+    query
+      .limit(limit)
+      .offset(offset)
+      .order_by(published.asc())
+      .load::<Self>(conn)
+  }
+
+  pub fn for_creator(conn: &PgConnection, for_creator_id: i32) -> Result<Vec<Self>, Error> {
+    use crate::schema::appeal::dsl::*;
+    appeal
+      .filter(creator_id.eq(for_creator_id))
+      .order_by(published.desc())
+      .load::<Self>(conn)
+  }
+
+  /// Records the decision on a pending appeal. Fails if the appeal was already resolved.
+  pub fn resolve(
+    conn: &PgConnection,
+    appeal_id: i32,
+    for_resolver_id: i32,
+    new_status: AppealStatus,
+  ) -> Result<Self, Error> {
+    use crate::schema::appeal::dsl::*;
+    diesel::update(
+      appeal
+        .find(appeal_id)
+        .filter(status.eq(AppealStatus::Pending as i16)),
+    )
+    .set((
+      status.eq(new_status as i16),
+      resolver_id.eq(Some(for_resolver_id)),
+      updated.eq(naive_now()),
+    ))
+    .get_result::<Self>(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{community::*, tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_mod = UserForm {
+      name: "appeal_judge".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_mod = User_::create(&conn, &new_mod).unwrap();
+
+    let new_user = UserForm {
+      name: "appealing_user".into(),
+      ..new_mod
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_appeal".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_mod.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_appeal = AppealForm {
+      kind: AppealKind::CommunityBan as i16,
+      mod_action_id: 1,
+      creator_id: inserted_user.id,
+      community_id: Some(inserted_community.id),
+      reason: "I was only joking".into(),
+    };
+
+    let inserted_appeal = Appeal::create(&conn, &new_appeal).unwrap();
+
+    let expected_appeal = Appeal {
+      id: inserted_appeal.id,
+      kind: AppealKind::CommunityBan as i16,
+      mod_action_id: 1,
+      creator_id: inserted_user.id,
+      community_id: Some(inserted_community.id),
+      reason: "I was only joking".into(),
+      status: AppealStatus::Pending as i16,
+      resolver_id: None,
+      published: inserted_appeal.published,
+      updated: None,
+    };
+
+    let duplicate_appeal = Appeal::create(&conn, &new_appeal);
+    let pending_appeals =
+      Appeal::list(&conn, Some(vec![inserted_community.id]), true, None, None).unwrap();
+    let resolved_appeal = Appeal::resolve(
+      &conn,
+      inserted_appeal.id,
+      inserted_mod.id,
+      AppealStatus::Denied,
+    )
+    .unwrap();
+    let re_resolved_appeal = Appeal::resolve(
+      &conn,
+      inserted_appeal.id,
+      inserted_mod.id,
+      AppealStatus::Approved,
+    );
+    let pending_after_resolve =
+      Appeal::list(&conn, Some(vec![inserted_community.id]), true, None, None).unwrap();
+    let creator_appeals = Appeal::for_creator(&conn, inserted_user.id).unwrap();
+    let num_deleted = Appeal::delete(&conn, inserted_appeal.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+    User_::delete(&conn, inserted_mod.id).unwrap();
+
+    assert_eq!(expected_appeal, inserted_appeal);
+    assert!(duplicate_appeal.is_err());
+    assert_eq!(vec![expected_appeal], pending_appeals);
+    assert_eq!(AppealStatus::Denied as i16, resolved_appeal.status);
+    assert_eq!(Some(inserted_mod.id), resolved_appeal.resolver_id);
+    assert!(re_resolved_appeal.is_err());
+    assert!(pending_after_resolve.is_empty());
+    assert_eq!(vec![resolved_appeal], creator_appeals);
+    assert_eq!(1, num_deleted);
+  }
+}
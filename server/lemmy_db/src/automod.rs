@@ -0,0 +1,252 @@
+use crate::{schema::automod_rule, Crud};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// What an automod rule checks. Stored as an i16.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum AutomodCondition {
+  Keyword,
+  Regex,
+  Domain,
+  AccountAge,
+  AuthorScore,
+}
+
+impl AutomodCondition {
+  pub fn from_i16(value: i16) -> Option<Self> {
+    match value {
+      0 => Some(AutomodCondition::Keyword),
+      1 => Some(AutomodCondition::Regex),
+      2 => Some(AutomodCondition::Domain),
+      3 => Some(AutomodCondition::AccountAge),
+      4 => Some(AutomodCondition::AuthorScore),
+      _ => None,
+    }
+  }
+}
+
+/// The text that a keyword or regex rule looks at. Stored as an i16.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum AutomodTarget {
+  Title,
+  Body,
+  Url,
+  Any,
+}
+
+impl AutomodTarget {
+  pub fn from_i16(value: i16) -> Option<Self> {
+    match value {
+      0 => Some(AutomodTarget::Title),
+      1 => Some(AutomodTarget::Body),
+      2 => Some(AutomodTarget::Url),
+      3 => Some(AutomodTarget::Any),
+      _ => None,
+    }
+  }
+}
+
+/// What happens to a post or comment that an automod rule matches. Stored as an i16.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum AutomodAction {
+  Remove,
+  Report,
+  RequireApproval,
+}
+
+impl AutomodAction {
+  pub fn from_i16(value: i16) -> Option<Self> {
+    match value {
+      0 => Some(AutomodAction::Remove),
+      1 => Some(AutomodAction::Report),
+      2 => Some(AutomodAction::RequireApproval),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "automod_rule"]
+pub struct AutomodRule {
+  pub id: i32,
+  pub community_id: i32,
+  pub creator_id: i32,
+  pub name: String,
+  pub condition: i16,
+  pub target: i16,
+  pub pattern: Option<String>,
+  pub threshold: Option<i32>,
+  pub action: i16,
+  pub published: chrono::NaiveDateTime,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "automod_rule"]
+pub struct AutomodRuleForm {
+  pub community_id: i32,
+  pub creator_id: i32,
+  pub name: String,
+  pub condition: i16,
+  pub target: i16,
+  pub pattern: Option<String>,
+  pub threshold: Option<i32>,
+  pub action: i16,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+impl Crud<AutomodRuleForm> for AutomodRule {
+  fn read(conn: &PgConnection, automod_rule_id: i32) -> Result<Self, Error> {
+    use crate::schema::automod_rule::dsl::*;
+    automod_rule.find(automod_rule_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, automod_rule_id: i32) -> Result<usize, Error> {
+    use crate::schema::automod_rule::dsl::*;
+    diesel::delete(automod_rule.find(automod_rule_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &AutomodRuleForm) -> Result<Self, Error> {
+    use crate::schema::automod_rule::dsl::*;
+    insert_into(automod_rule)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    automod_rule_id: i32,
+    form: &AutomodRuleForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::automod_rule::dsl::*;
+    diesel::update(automod_rule.find(automod_rule_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl AutomodRule {
+  pub fn for_community(conn: &PgConnection, for_community_id: i32) -> Result<Vec<Self>, Error> {
+    use crate::schema::automod_rule::dsl::*;
+    automod_rule
+      .filter(community_id.eq(for_community_id))
+      .order_by(id)
+      .load::<Self>(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{community::*, tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "automodder".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_automod".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_user.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_rule = AutomodRuleForm {
+      community_id: inserted_community.id,
+      creator_id: inserted_user.id,
+      name: "No crypto".into(),
+      condition: AutomodCondition::Keyword as i16,
+      target: AutomodTarget::Any as i16,
+      pattern: Some("crypto".into()),
+      threshold: None,
+      action: AutomodAction::Report as i16,
+      updated: None,
+    };
+
+    let inserted_rule = AutomodRule::create(&conn, &new_rule).unwrap();
+
+    let expected_rule = AutomodRule {
+      id: inserted_rule.id,
+      community_id: inserted_community.id,
+      creator_id: inserted_user.id,
+      name: "No crypto".into(),
+      condition: 0,
+      target: 3,
+      pattern: Some("crypto".into()),
+      threshold: None,
+      action: 1,
+      published: inserted_rule.published,
+      updated: None,
+    };
+
+    let read_rules = AutomodRule::for_community(&conn, inserted_community.id).unwrap();
+    let num_deleted = AutomodRule::delete(&conn, inserted_rule.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(expected_rule, inserted_rule);
+    assert_eq!(vec![expected_rule], read_rules);
+    assert_eq!(
+      Some(AutomodAction::RequireApproval),
+      AutomodAction::from_i16(2)
+    );
+    assert_eq!(None, AutomodCondition::from_i16(5));
+    assert_eq!(1, num_deleted);
+  }
+}
@@ -0,0 +1,189 @@
+use crate::{schema::blocked_domain, Crud};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "blocked_domain"]
+pub struct BlockedDomain {
+  pub id: i32,
+  pub community_id: Option<i32>,
+  pub domain: String,
+  pub creator_id: i32,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "blocked_domain"]
+pub struct BlockedDomainForm {
+  pub community_id: Option<i32>,
+  pub domain: String,
+  pub creator_id: i32,
+}
+
+impl Crud<BlockedDomainForm> for BlockedDomain {
+  fn read(conn: &PgConnection, blocked_domain_id: i32) -> Result<Self, Error> {
+    use crate::schema::blocked_domain::dsl::*;
+    blocked_domain.find(blocked_domain_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, blocked_domain_id: i32) -> Result<usize, Error> {
+    use crate::schema::blocked_domain::dsl::*;
+    diesel::delete(blocked_domain.find(blocked_domain_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &BlockedDomainForm) -> Result<Self, Error> {
+    use crate::schema::blocked_domain::dsl::*;
+    insert_into(blocked_domain)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    blocked_domain_id: i32,
+    form: &BlockedDomainForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::blocked_domain::dsl::*;
+    diesel::update(blocked_domain.find(blocked_domain_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl BlockedDomain {
+  /// The site wide blocks when no community is given
+  pub fn list(conn: &PgConnection, for_community_id: Option<i32>) -> Result<Vec<Self>, Error> {
+    use crate::schema::blocked_domain::dsl::*;
+    let query = blocked_domain.into_boxed();
+    let query = match for_community_id {
+      Some(for_community_id) => query.filter(community_id.eq(for_community_id)),
+      None => query.filter(community_id.is_null()),
+    };
+    query.order_by(domain).load::<Self>(conn)
+  }
+
+  /// The site wide blocks, plus the ones of the community
+  pub fn applicable(conn: &PgConnection, for_community_id: i32) -> Result<Vec<Self>, Error> {
+    use crate::schema::blocked_domain::dsl::*;
+    blocked_domain
+      .filter(community_id.is_null().or(community_id.eq(for_community_id)))
+      .load::<Self>(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{community::*, tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "domain_blocker".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_domain_block".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_user.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let site_block = BlockedDomain::create(
+      &conn,
+      &BlockedDomainForm {
+        community_id: None,
+        domain: "*.spam.example".into(),
+        creator_id: inserted_user.id,
+      },
+    )
+    .unwrap();
+
+    let community_block_form = BlockedDomainForm {
+      community_id: Some(inserted_community.id),
+      domain: "offtopic.example".into(),
+      creator_id: inserted_user.id,
+    };
+    let community_block = BlockedDomain::create(&conn, &community_block_form).unwrap();
+
+    let expected_community_block = BlockedDomain {
+      id: community_block.id,
+      community_id: Some(inserted_community.id),
+      domain: "offtopic.example".into(),
+      creator_id: inserted_user.id,
+      published: community_block.published,
+    };
+
+    let duplicate_block = BlockedDomain::create(&conn, &community_block_form);
+    let site_blocks = BlockedDomain::list(&conn, None).unwrap();
+    let community_blocks = BlockedDomain::list(&conn, Some(inserted_community.id)).unwrap();
+    let applicable_blocks = BlockedDomain::applicable(&conn, inserted_community.id).unwrap();
+
+    BlockedDomain::delete(&conn, site_block.id).unwrap();
+    let num_deleted = BlockedDomain::delete(&conn, community_block.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(expected_community_block, community_block);
+    assert!(duplicate_block.is_err());
+    assert!(site_blocks.contains(&site_block));
+    assert_eq!(vec![expected_community_block], community_blocks);
+    assert!(applicable_blocks.contains(&site_block));
+    assert!(applicable_blocks.contains(&community_block));
+    assert_eq!(1, num_deleted);
+  }
+}
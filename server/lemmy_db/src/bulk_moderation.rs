@@ -0,0 +1,398 @@
+use crate::{
+  comment::Comment,
+  moderator::{ModLockPostForm, ModRemoveCommentForm, ModRemovePostForm},
+  naive_now,
+  post::Post,
+};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Picks out the posts or comments of a community that a bulk action applies to. Either the ids
+/// or the creator narrow it down, and the time window further. `after` is inclusive and `before`
+/// is exclusive.
+#[derive(Clone, Default)]
+pub struct BulkModerationTarget {
+  pub community_id: i32,
+  pub ids: Option<Vec<i32>>,
+  pub creator_id: Option<i32>,
+  pub after: Option<chrono::NaiveDateTime>,
+  pub before: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+pub enum BulkPostAction {
+  Remove,
+  Restore,
+  Lock,
+  Unlock,
+}
+
+impl BulkModerationTarget {
+  pub fn post_ids(&self, conn: &PgConnection) -> Result<Vec<i32>, Error> {
+    use crate::schema::post::dsl::*;
+    let mut query = post
+      .select(id)
+      .filter(community_id.eq(self.community_id))
+      .into_boxed();
+
+    if let Some(ids) = self.ids.to_owned() {
+      query = query.filter(id.eq_any(ids));
+    }
+
+    if let Some(for_creator_id) = self.creator_id {
+      query = query.filter(creator_id.eq(for_creator_id));
+    }
+
+    if let Some(after) = self.after {
+      query = query.filter(published.ge(after));
+    }
+
+    if let Some(before) = self.before {
+      query = query.filter(published.lt(before));
+    }
+
+    query.order_by(published.asc()).load::<i32>(conn)
+  }
+
+  pub fn comment_ids(&self, conn: &PgConnection) -> Result<Vec<i32>, Error> {
+    use crate::schema::{comment::dsl::*, post};
+    let mut query = comment
+      .select(id)
+      .filter(
+        post_id.eq_any(
+          post::table
+            .filter(post::community_id.eq(self.community_id))
+            .select(post::id),
+        ),
+      )
+      .into_boxed();
+
+    if let Some(ids) = self.ids.to_owned() {
+      query = query.filter(id.eq_any(ids));
+    }
+
+    if let Some(for_creator_id) = self.creator_id {
+      query = query.filter(creator_id.eq(for_creator_id));
+    }
+
+    if let Some(after) = self.after {
+      query = query.filter(published.ge(after));
+    }
+
+    if let Some(before) = self.before {
+      query = query.filter(published.lt(before));
+    }
+
+    query.order_by(published.asc()).load::<i32>(conn)
+  }
+}
+
+/// Applies the action to all the posts at once, with a modlog entry for each one. Posts that were
+/// already in that state are skipped, and only the changed ones are returned.
+pub fn bulk_moderate_posts(
+  conn: &PgConnection,
+  mod_user_id: i32,
+  post_ids: Vec<i32>,
+  action: BulkPostAction,
+  reason: Option<String>,
+) -> Result<Vec<Post>, Error> {
+  use crate::schema::{mod_lock_post, mod_remove_post, post};
+  conn.transaction::<_, Error, _>(|| {
+    let targets = post::table.filter(post::id.eq_any(post_ids));
+
+    match action {
+      BulkPostAction::Remove | BulkPostAction::Restore => {
+        let new_removed = action == BulkPostAction::Remove;
+        let updated_posts = diesel::update(targets.filter(post::removed.ne(new_removed)))
+          .set((post::removed.eq(new_removed), post::updated.eq(naive_now())))
+          .get_results::<Post>(conn)?;
+
+        let forms: Vec<ModRemovePostForm> = updated_posts
+          .iter()
+          .map(|p| ModRemovePostForm {
+            mod_user_id,
+            post_id: p.id,
+            reason: reason.to_owned(),
+            removed: Some(new_removed),
+          })
+          .collect();
+        insert_into(mod_remove_post::table)
+          .values(&forms)
+          .execute(conn)?;
+
+        Ok(updated_posts)
+      }
+      BulkPostAction::Lock | BulkPostAction::Unlock => {
+        let new_locked = action == BulkPostAction::Lock;
+        let updated_posts = diesel::update(targets.filter(post::locked.ne(new_locked)))
+          .set((post::locked.eq(new_locked), post::updated.eq(naive_now())))
+          .get_results::<Post>(conn)?;
+
+        let forms: Vec<ModLockPostForm> = updated_posts
+          .iter()
+          .map(|p| ModLockPostForm {
+            mod_user_id,
+            post_id: p.id,
+            locked: Some(new_locked),
+          })
+          .collect();
+        insert_into(mod_lock_post::table)
+          .values(&forms)
+          .execute(conn)?;
+
+        Ok(updated_posts)
+      }
+    }
+  })
+}
+
+/// Same as `bulk_moderate_posts`, removing or restoring comments.
+pub fn bulk_remove_comments(
+  conn: &PgConnection,
+  mod_user_id: i32,
+  comment_ids: Vec<i32>,
+  new_removed: bool,
+  reason: Option<String>,
+) -> Result<Vec<Comment>, Error> {
+  use crate::schema::{comment, mod_remove_comment};
+  conn.transaction::<_, Error, _>(|| {
+    let updated_comments = diesel::update(
+      comment::table
+        .filter(comment::id.eq_any(comment_ids))
+        .filter(comment::removed.ne(new_removed)),
+    )
+    .set((
+      comment::removed.eq(new_removed),
+      comment::updated.eq(naive_now()),
+    ))
+    .get_results::<Comment>(conn)?;
+
+    let forms: Vec<ModRemoveCommentForm> = updated_comments
+      .iter()
+      .map(|c| ModRemoveCommentForm {
+        mod_user_id,
+        comment_id: c.id,
+        reason: reason.to_owned(),
+        removed: Some(new_removed),
+      })
+      .collect();
+    insert_into(mod_remove_comment::table)
+      .values(&forms)
+      .execute(conn)?;
+
+    Ok(updated_comments)
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    comment::*,
+    community::*,
+    moderator_views::*,
+    post::*,
+    tests::establish_unpooled_connection,
+    user::*,
+    Crud,
+    ListingType,
+    SortType,
+  };
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "bulk_spammer".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_mod = UserForm {
+      name: "bulk_mod".into(),
+      ..new_user
+    };
+
+    let inserted_mod = User_::create(&conn, &new_mod).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_bulk_community".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_mod.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_post = PostForm {
+      name: "Spam".into(),
+      creator_id: inserted_user.id,
+      url: None,
+      body: None,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      embed_type: None,
+      url_media_type: None,
+      thumbnail_url: None,
+      ap_id: "http://fake.com".into(),
+      local: true,
+      published: None,
+      pending: None,
+    };
+
+    let inserted_post = Post::create(&conn, &new_post).unwrap();
+    let inserted_other_post = Post::create(&conn, &new_post).unwrap();
+
+    let mod_post_form = PostForm {
+      name: "Rules".into(),
+      creator_id: inserted_mod.id,
+      ..new_post
+    };
+
+    let inserted_mod_post = Post::create(&conn, &mod_post_form).unwrap();
+
+    let comment_form = CommentForm {
+      content: "More spam".into(),
+      creator_id: inserted_user.id,
+      post_id: inserted_mod_post.id,
+      removed: None,
+      deleted: None,
+      read: None,
+      parent_id: None,
+      published: None,
+      updated: None,
+      ap_id: "http://fake.com".into(),
+      local: true,
+    };
+
+    let inserted_comment = Comment::create(&conn, &comment_form).unwrap();
+
+    let target = BulkModerationTarget {
+      community_id: inserted_community.id,
+      creator_id: Some(inserted_user.id),
+      ..BulkModerationTarget::default()
+    };
+
+    let post_ids = target.post_ids(&conn).unwrap();
+    let comment_ids = target.comment_ids(&conn).unwrap();
+
+    let removed_posts = bulk_moderate_posts(
+      &conn,
+      inserted_mod.id,
+      post_ids.to_owned(),
+      BulkPostAction::Remove,
+      Some("spam".into()),
+    )
+    .unwrap();
+    let removed_again = bulk_moderate_posts(
+      &conn,
+      inserted_mod.id,
+      post_ids.to_owned(),
+      BulkPostAction::Remove,
+      None,
+    )
+    .unwrap();
+    let locked_posts = bulk_moderate_posts(
+      &conn,
+      inserted_mod.id,
+      post_ids.to_owned(),
+      BulkPostAction::Lock,
+      None,
+    )
+    .unwrap();
+    let removed_comments = bulk_remove_comments(
+      &conn,
+      inserted_mod.id,
+      comment_ids.to_owned(),
+      true,
+      Some("spam".into()),
+    )
+    .unwrap();
+    let read_mod_post = Post::read(&conn, inserted_mod_post.id).unwrap();
+
+    let mod_filter = ModlogFilter {
+      community_id: Some(inserted_community.id),
+      ..ModlogFilter::default()
+    };
+    let removed_posts_log = ModRemovePostView::list(&conn, &mod_filter).unwrap();
+    let removed_comments_log = ModRemoveCommentView::list(&conn, &mod_filter).unwrap();
+
+    Comment::delete(&conn, inserted_comment.id).unwrap();
+    Post::delete(&conn, inserted_post.id).unwrap();
+    Post::delete(&conn, inserted_other_post.id).unwrap();
+    Post::delete(&conn, inserted_mod_post.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+    User_::delete(&conn, inserted_mod.id).unwrap();
+
+    assert_eq!(vec![inserted_post.id, inserted_other_post.id], post_ids);
+    assert_eq!(vec![inserted_comment.id], comment_ids);
+    assert_eq!(2, removed_posts.len());
+    assert!(removed_posts.iter().all(|p| p.removed));
+    assert!(removed_again.is_empty());
+    assert_eq!(2, locked_posts.len());
+    assert!(locked_posts.iter().all(|p| p.locked && p.removed));
+    assert_eq!(1, removed_comments.len());
+    assert!(removed_comments[0].removed);
+    assert!(!read_mod_post.removed);
+    assert_eq!(2, removed_posts_log.len());
+    assert_eq!(1, removed_comments_log.len());
+  }
+}
@@ -0,0 +1,103 @@
+use crate::{
+  schema::{captcha_answer, captcha_answer::dsl::*},
+  Crud,
+};
+use diesel::{dsl::*, result::Error, *};
+
+#[derive(Queryable, Identifiable, PartialEq, Debug)]
+#[table_name = "captcha_answer"]
+pub struct CaptchaAnswer {
+  pub id: i32,
+  pub uuid: String,
+  pub answer: String,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "captcha_answer"]
+pub struct CaptchaAnswerForm {
+  pub uuid: String,
+  pub answer: String,
+}
+
+impl Crud<CaptchaAnswerForm> for CaptchaAnswer {
+  fn read(conn: &PgConnection, captcha_answer_id: i32) -> Result<Self, Error> {
+    use crate::schema::captcha_answer::dsl::*;
+    captcha_answer.find(captcha_answer_id).first::<Self>(conn)
+  }
+  fn delete(conn: &PgConnection, captcha_answer_id: i32) -> Result<usize, Error> {
+    diesel::delete(captcha_answer.find(captcha_answer_id)).execute(conn)
+  }
+  fn create(conn: &PgConnection, form: &CaptchaAnswerForm) -> Result<Self, Error> {
+    insert_into(captcha_answer)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+  fn update(
+    conn: &PgConnection,
+    captcha_answer_id: i32,
+    form: &CaptchaAnswerForm,
+  ) -> Result<Self, Error> {
+    diesel::update(captcha_answer.find(captcha_answer_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl CaptchaAnswer {
+  /// Stores a new answer, clearing out any that have expired along the way.
+  pub fn insert(conn: &PgConnection, form: &CaptchaAnswerForm) -> Result<Self, Error> {
+    diesel::delete(captcha_answer.filter(published.lt(now - 10.minutes()))).execute(conn)?;
+    Self::create(&conn, form)
+  }
+
+  /// Captchas can only be tried once, so the answer is removed whether or not it matched.
+  pub fn check(conn: &PgConnection, for_uuid: &str, try_answer: &str) -> Result<bool, Error> {
+    let found = diesel::delete(
+      captcha_answer
+        .filter(uuid.eq(for_uuid))
+        .filter(published.gt(now - 10.minutes())),
+    )
+    .get_results::<Self>(conn)?;
+
+    Ok(
+      found
+        .iter()
+        .any(|c| c.answer.to_lowercase() == try_answer.to_lowercase()),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tests::establish_unpooled_connection;
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_captcha_answer = CaptchaAnswerForm {
+      uuid: "0d2effc8-8a38-4c22-a5f9-a6dba9e3bce6".into(),
+      answer: "XyZ12".into(),
+    };
+
+    let inserted_captcha_answer = CaptchaAnswer::insert(&conn, &new_captcha_answer).unwrap();
+
+    let expected_captcha_answer = CaptchaAnswer {
+      id: inserted_captcha_answer.id,
+      uuid: "0d2effc8-8a38-4c22-a5f9-a6dba9e3bce6".into(),
+      answer: "XyZ12".into(),
+      published: inserted_captcha_answer.published,
+    };
+
+    let read_captcha_answer = CaptchaAnswer::read(&conn, inserted_captcha_answer.id).unwrap();
+    let correct = CaptchaAnswer::check(&conn, &new_captcha_answer.uuid, "xyz12").unwrap();
+    let retried = CaptchaAnswer::check(&conn, &new_captcha_answer.uuid, "xyz12").unwrap();
+
+    assert_eq!(expected_captcha_answer, read_captcha_answer);
+    assert_eq!(expected_captcha_answer, inserted_captcha_answer);
+    assert!(correct);
+    assert!(!retried);
+  }
+}
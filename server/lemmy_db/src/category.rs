@@ -3,9 +3,10 @@ use crate::{
   Crud,
 };
 use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 #[table_name = "category"]
 pub struct Category {
   pub id: i32,
@@ -1,5 +1,6 @@
 use super::{post::Post, *};
-use crate::schema::{comment, comment_like, comment_saved};
+use crate::schema::{comment, comment_like, comment_revision, comment_saved};
+use schemars::JsonSchema;
 
 // WITH RECURSIVE MyTree AS (
 //     SELECT * FROM comment WHERE parent_id IS NULL
@@ -8,7 +9,7 @@ use crate::schema::{comment, comment_like, comment_saved};
 // )
 // SELECT * FROM MyTree;
 
-#[derive(Clone, Queryable, Associations, Identifiable, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Clone, Queryable, Associations, Identifiable, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 #[belongs_to(Post)]
 #[table_name = "comment"]
 pub struct Comment {
@@ -24,6 +25,7 @@ pub struct Comment {
   pub deleted: bool,
   pub ap_id: String,
   pub local: bool,
+  pub pinned: bool,
 }
 
 #[derive(Insertable, AsChangeset, Clone)]
@@ -98,6 +100,74 @@ impl Comment {
       .get_result::<Self>(conn)
   }
 
+  /// Marks every unread reply to the user as read, in one go.
+  pub fn mark_all_replies_as_read(
+    conn: &PgConnection,
+    for_recipient_id: i32,
+  ) -> Result<Vec<Self>, Error> {
+    use crate::{comment_view::reply_fast_view, schema::comment::dsl::*};
+
+    let unread_reply_ids = reply_fast_view::table
+      .filter(reply_fast_view::user_id.eq(for_recipient_id))
+      .filter(reply_fast_view::recipient_id.eq(for_recipient_id))
+      .filter(reply_fast_view::read.eq(false))
+      .select(reply_fast_view::id)
+      .load::<i32>(conn)?;
+
+    diesel::update(comment.filter(id.eq_any(unread_reply_ids)))
+      .set(read.eq(true))
+      .get_results::<Self>(conn)
+  }
+
+  /// A post has at most one pinned comment, so pinning one unpins the rest.
+  pub fn update_pinned(
+    conn: &PgConnection,
+    comment_id: i32,
+    new_pinned: bool,
+  ) -> Result<Self, Error> {
+    use crate::schema::comment::dsl::*;
+
+    conn.transaction::<_, Error, _>(|| {
+      if new_pinned {
+        let for_post_id = comment
+          .find(comment_id)
+          .select(post_id)
+          .first::<i32>(conn)?;
+        diesel::update(
+          comment
+            .filter(post_id.eq(for_post_id))
+            .filter(pinned.eq(true)),
+        )
+        .set(pinned.eq(false))
+        .execute(conn)?;
+      }
+
+      diesel::update(comment.find(comment_id))
+        .set(pinned.eq(new_pinned))
+        .get_result::<Self>(conn)
+    })
+  }
+
+  pub fn pinned_for_post(conn: &PgConnection, for_post_id: i32) -> Result<Vec<Self>, Error> {
+    use crate::schema::comment::dsl::*;
+    comment
+      .filter(post_id.eq(for_post_id))
+      .filter(pinned.eq(true))
+      .load::<Self>(conn)
+  }
+
+  pub fn update_removed(
+    conn: &PgConnection,
+    comment_id: i32,
+    new_removed: bool,
+  ) -> Result<Self, Error> {
+    use crate::schema::comment::dsl::*;
+
+    diesel::update(comment.find(comment_id))
+      .set((removed.eq(new_removed), updated.eq(naive_now())))
+      .get_result::<Self>(conn)
+  }
+
   pub fn permadelete(conn: &PgConnection, comment_id: i32) -> Result<Self, Error> {
     use crate::schema::comment::dsl::*;
 
@@ -201,6 +271,32 @@ impl Saveable<CommentSavedForm> for CommentSaved {
   }
 }
 
+/// A previous version of an edited comment. These are saved by the database whenever the content of
+/// a comment changes.
+#[derive(
+  Identifiable, Queryable, Associations, PartialEq, Debug, Serialize, Deserialize, JsonSchema,
+)]
+#[belongs_to(Comment)]
+#[table_name = "comment_revision"]
+pub struct CommentRevision {
+  pub id: i32,
+  pub comment_id: i32,
+  pub content: String,
+  pub published: chrono::NaiveDateTime,
+}
+
+impl CommentRevision {
+  /// Newest first
+  pub fn for_comment(conn: &PgConnection, for_comment_id: i32) -> Result<Vec<Self>, Error> {
+    use crate::schema::comment_revision::dsl::*;
+    comment_revision
+      .filter(comment_id.eq(for_comment_id))
+      .order_by(published.desc())
+      .then_order_by(id.desc())
+      .load::<Self>(conn)
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use crate::{comment::*, community::*, post::*, tests::establish_unpooled_connection, user::*};
@@ -232,6 +328,12 @@ mod tests {
       private_key: None,
       public_key: None,
       last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
     };
 
     let inserted_user = User_::create(&conn, &new_user).unwrap();
@@ -252,6 +354,15 @@ mod tests {
       public_key: None,
       last_refreshed_at: None,
       published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
     };
 
     let inserted_community = Community::create(&conn, &new_community).unwrap();
@@ -271,10 +382,13 @@ mod tests {
       embed_title: None,
       embed_description: None,
       embed_html: None,
+      embed_type: None,
+      url_media_type: None,
       thumbnail_url: None,
       ap_id: "http://fake.com".into(),
       local: true,
       published: None,
+      pending: None,
     };
 
     let inserted_post = Post::create(&conn, &new_post).unwrap();
@@ -308,6 +422,7 @@ mod tests {
       updated: None,
       ap_id: "http://fake.com".into(),
       local: true,
+      pinned: false,
     };
 
     let child_comment_form = CommentForm {
@@ -362,6 +477,18 @@ mod tests {
 
     let read_comment = Comment::read(&conn, inserted_comment.id).unwrap();
     let updated_comment = Comment::update(&conn, inserted_comment.id, &comment_form).unwrap();
+    let unedited_revisions = CommentRevision::for_comment(&conn, inserted_comment.id).unwrap();
+    let edited_comment_form = CommentForm {
+      content: "An edited comment".into(),
+      ..comment_form.clone()
+    };
+    Comment::update(&conn, inserted_comment.id, &edited_comment_form).unwrap();
+    let edited_revisions = CommentRevision::for_comment(&conn, inserted_comment.id).unwrap();
+    let pinned_comment = Comment::update_pinned(&conn, inserted_comment.id, true).unwrap();
+    let pinned_child_comment =
+      Comment::update_pinned(&conn, inserted_child_comment.id, true).unwrap();
+    let pinned_comments = Comment::pinned_for_post(&conn, inserted_post.id).unwrap();
+    let unpinned_comment = Comment::read(&conn, inserted_comment.id).unwrap();
     let like_removed = CommentLike::remove(&conn, &comment_like_form).unwrap();
     let saved_removed = CommentSaved::unsave(&conn, &comment_saved_form).unwrap();
     let num_deleted = Comment::delete(&conn, inserted_comment.id).unwrap();
@@ -375,6 +502,13 @@ mod tests {
     assert_eq!(expected_comment, updated_comment);
     assert_eq!(expected_comment_like, inserted_comment_like);
     assert_eq!(expected_comment_saved, inserted_comment_saved);
+    assert!(unedited_revisions.is_empty());
+    assert_eq!(1, edited_revisions.len());
+    assert_eq!(expected_comment.content, edited_revisions[0].content);
+    assert!(pinned_comment.pinned);
+    assert!(pinned_child_comment.pinned);
+    assert_eq!(vec![pinned_child_comment], pinned_comments);
+    assert!(!unpinned_comment.pinned);
     assert_eq!(
       expected_comment.id,
       inserted_child_comment.parent_id.unwrap()
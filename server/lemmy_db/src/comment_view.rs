@@ -1,6 +1,17 @@
 // TODO, remove the cross join here, just join to user directly
-use crate::{fuzzy_search, limit_and_offset, ListingType, MaybeOptional, SortType};
+use crate::{
+  controversy_rank,
+  keyset_filter,
+  limit_and_offset,
+  schema::{user_, user_follower},
+  user_muted_keyword::muted_keyword_sql,
+  ListingCursor,
+  ListingType,
+  MaybeOptional,
+  SortType,
+};
 use diesel::{dsl::*, pg::Pg, result::Error, *};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 // The faked schema since diesel doesn't do views
@@ -19,6 +30,7 @@ table! {
     deleted -> Bool,
     ap_id -> Text,
     local -> Bool,
+    pinned -> Bool,
     community_id -> Int4,
     community_actor_id -> Text,
     community_local -> Bool,
@@ -30,6 +42,8 @@ table! {
     creator_name -> Varchar,
     creator_published -> Timestamp,
     creator_avatar -> Nullable<Text>,
+    creator_bot_account -> Bool,
+    creator_display_name -> Nullable<Varchar>,
     score -> BigInt,
     upvotes -> BigInt,
     downvotes -> BigInt,
@@ -56,6 +70,7 @@ table! {
     deleted -> Bool,
     ap_id -> Text,
     local -> Bool,
+    pinned -> Bool,
     community_id -> Int4,
     community_actor_id -> Text,
     community_local -> Bool,
@@ -67,6 +82,8 @@ table! {
     creator_name -> Varchar,
     creator_published -> Timestamp,
     creator_avatar -> Nullable<Text>,
+    creator_bot_account -> Bool,
+    creator_display_name -> Nullable<Varchar>,
     score -> BigInt,
     upvotes -> BigInt,
     downvotes -> BigInt,
@@ -78,8 +95,19 @@ table! {
   }
 }
 
+allow_tables_to_appear_in_same_query!(comment_fast_view, user_);
+allow_tables_to_appear_in_same_query!(comment_fast_view, user_follower);
+
 #[derive(
-  Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, QueryableByName, Clone,
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
 )]
 #[table_name = "comment_fast_view"]
 pub struct CommentView {
@@ -96,6 +124,7 @@ pub struct CommentView {
   pub deleted: bool,
   pub ap_id: String,
   pub local: bool,
+  pub pinned: bool,
   pub community_id: i32,
   pub community_actor_id: String,
   pub community_local: bool,
@@ -107,8 +136,12 @@ pub struct CommentView {
   pub creator_name: String,
   pub creator_published: chrono::NaiveDateTime,
   pub creator_avatar: Option<String>,
+  pub creator_bot_account: bool,
+  pub creator_display_name: Option<String>,
   pub score: i64,
+  #[serde(default, skip_serializing_if = "crate::vote_counts_hidden")]
   pub upvotes: i64,
+  #[serde(default, skip_serializing_if = "crate::vote_counts_hidden")]
   pub downvotes: i64,
   pub hot_rank: i32,
   pub user_id: Option<i32>,
@@ -125,9 +158,18 @@ pub struct CommentQueryBuilder<'a> {
   for_community_id: Option<i32>,
   for_post_id: Option<i32>,
   for_creator_id: Option<i32>,
+  for_parent_id: Option<i32>,
+  max_depth: Option<i32>,
+  breadth_first: bool,
+  excluded_threads: Vec<i32>,
   search_term: Option<String>,
+  published_after: Option<chrono::NaiveDateTime>,
+  published_before: Option<chrono::NaiveDateTime>,
   my_user_id: Option<i32>,
   saved_only: bool,
+  followed_users_only: bool,
+  hide_bot_accounts: bool,
+  cursor: Option<ListingCursor>,
   page: Option<i64>,
   limit: Option<i64>,
 }
@@ -146,9 +188,18 @@ impl<'a> CommentQueryBuilder<'a> {
       for_community_id: None,
       for_post_id: None,
       for_creator_id: None,
+      for_parent_id: None,
+      max_depth: None,
+      breadth_first: false,
+      excluded_threads: Vec::new(),
       search_term: None,
+      published_after: None,
+      published_before: None,
       my_user_id: None,
       saved_only: false,
+      followed_users_only: false,
+      hide_bot_accounts: false,
+      cursor: None,
       page: None,
       limit: None,
     }
@@ -179,6 +230,31 @@ impl<'a> CommentQueryBuilder<'a> {
     self
   }
 
+  /// The comment and every reply below it
+  pub fn for_parent_id<T: MaybeOptional<i32>>(mut self, for_parent_id: T) -> Self {
+    self.for_parent_id = for_parent_id.get_optional();
+    self
+  }
+
+  /// How many levels of replies below the parent, or below the top level without one
+  pub fn max_depth<T: MaybeOptional<i32>>(mut self, max_depth: T) -> Self {
+    self.max_depth = max_depth.get_optional();
+    self
+  }
+
+  /// A level of the thread after the other, so that a limit never leaves out the comment a listed
+  /// reply answers. The cursor isn't used then.
+  pub fn breadth_first(mut self, breadth_first: bool) -> Self {
+    self.breadth_first = breadth_first;
+    self
+  }
+
+  /// Leaves out these comments and every reply below them
+  pub fn excluded_threads(mut self, excluded_threads: Vec<i32>) -> Self {
+    self.excluded_threads = excluded_threads;
+    self
+  }
+
   pub fn search_term<T: MaybeOptional<String>>(mut self, search_term: T) -> Self {
     self.search_term = search_term.get_optional();
     self
@@ -194,6 +270,39 @@ impl<'a> CommentQueryBuilder<'a> {
     self
   }
 
+  /// Only the comments of the users `my_user_id` follows
+  pub fn followed_users_only(mut self, followed_users_only: bool) -> Self {
+    self.followed_users_only = followed_users_only;
+    self
+  }
+
+  pub fn hide_bot_accounts(mut self, hide_bot_accounts: bool) -> Self {
+    self.hide_bot_accounts = hide_bot_accounts;
+    self
+  }
+
+  pub fn published_after<T: MaybeOptional<chrono::NaiveDateTime>>(
+    mut self,
+    published_after: T,
+  ) -> Self {
+    self.published_after = published_after.get_optional();
+    self
+  }
+
+  pub fn published_before<T: MaybeOptional<chrono::NaiveDateTime>>(
+    mut self,
+    published_before: T,
+  ) -> Self {
+    self.published_before = published_before.get_optional();
+    self
+  }
+
+  /// Same as the cursor of `PostQueryBuilder`.
+  pub fn cursor<T: MaybeOptional<ListingCursor>>(mut self, cursor: T) -> Self {
+    self.cursor = cursor.get_optional();
+    self
+  }
+
   pub fn page<T: MaybeOptional<i64>>(mut self, page: T) -> Self {
     self.page = page.get_optional();
     self
@@ -208,6 +317,12 @@ impl<'a> CommentQueryBuilder<'a> {
     use super::comment_view::comment_fast_view::dsl::*;
 
     let mut query = self.query;
+    let for_post = self.for_post_id.is_some();
+    let cursor = if self.search_term.is_none() && !self.breadth_first {
+      self.cursor
+    } else {
+      None
+    };
 
     // The view lets you pass a null user_id, if you're not logged in
     if let Some(my_user_id) = self.my_user_id {
@@ -216,6 +331,47 @@ impl<'a> CommentQueryBuilder<'a> {
       query = query.filter(user_id.is_null());
     }
 
+    // The comments with a keyword the user muted are left out, except for their own
+    if let Some(my_user_id) = self.my_user_id {
+      query = query.filter(
+        creator_id.eq(my_user_id).or(
+          sql::<sql_types::Bool>(&format!(
+            "not {}",
+            muted_keyword_sql(true, &["comment_fast_view.content"])
+          ))
+          .bind::<sql_types::Integer, _>(my_user_id)
+          .sql(")"),
+        ),
+      );
+
+      if self.followed_users_only {
+        let followed_ids = user_follower::table
+          .filter(user_follower::follower_id.eq(my_user_id))
+          .select(user_follower::user_id);
+        query = query.filter(creator_id.eq_any(followed_ids));
+      }
+    }
+
+    // Shadowbanned users' content is only listed for themselves and the admins
+    let shadow_banned_ids = user_::table
+      .filter(user_::shadow_banned.eq(true))
+      .select(user_::id);
+    query = if let Some(my_user_id) = self.my_user_id {
+      let is_admin = exists(
+        user_::table
+          .filter(user_::id.eq(my_user_id))
+          .filter(user_::admin.eq(true)),
+      );
+      query.filter(
+        creator_id
+          .ne_all(shadow_banned_ids)
+          .or(creator_id.eq(my_user_id))
+          .or(is_admin),
+      )
+    } else {
+      query.filter(creator_id.ne_all(shadow_banned_ids))
+    };
+
     if let Some(for_creator_id) = self.for_creator_id {
       query = query.filter(creator_id.eq(for_creator_id));
     };
@@ -224,14 +380,80 @@ impl<'a> CommentQueryBuilder<'a> {
       query = query.filter(community_id.eq(for_community_id));
     }
 
+    if self.breadth_first {
+      query = query.then_order_by(sql::<sql_types::Integer>(
+        "(select nlevel(c.path) from comment c where c.id = comment_fast_view.id)",
+      ));
+    }
+
+    // The pinned comment stays at the top of its thread, whatever the sort
     if let Some(for_post_id) = self.for_post_id {
       query = query.filter(post_id.eq(for_post_id));
+      query = query.then_order_by(pinned.desc());
     };
 
+    // The paths of the comment table find the thread and its depth with the index
+    if let Some(for_parent_id) = self.for_parent_id {
+      query = query.filter(
+        sql::<sql_types::Bool>(
+          "comment_fast_view.id in (select c.id from comment c, comment p \
+          where c.path <@ p.path and p.id = ",
+        )
+        .bind::<sql_types::Integer, _>(for_parent_id)
+        .sql(" and nlevel(c.path) - nlevel(p.path) <= ")
+        .bind::<sql_types::Integer, _>(self.max_depth.unwrap_or(i32::MAX))
+        .sql(")"),
+      );
+    } else if let Some(max_depth) = self.max_depth {
+      query = query.filter(
+        sql::<sql_types::Bool>(
+          "comment_fast_view.id in (select c.id from comment c \
+          where c.post_id = comment_fast_view.post_id and nlevel(c.path) <= ",
+        )
+        .bind::<sql_types::Integer, _>(max_depth.saturating_add(1))
+        .sql(")"),
+      );
+    }
+
+    if !self.excluded_threads.is_empty() {
+      query = query.filter(
+        sql::<sql_types::Bool>(
+          "comment_fast_view.id not in (select c.id from comment c, comment e \
+          where c.path <@ e.path and e.id = any(",
+        )
+        .bind::<sql_types::Array<sql_types::Integer>, _>(self.excluded_threads)
+        .sql("))"),
+      );
+    }
+
+    // Full text search on the comment table, ranking the best matches first
     if let Some(search_term) = self.search_term {
-      query = query.filter(content.ilike(fuzzy_search(&search_term)));
+      query = query
+        .filter(
+          sql::<sql_types::Bool>(
+            "comment_fast_view.id in (select c.id from comment c \
+            where c.search_vector @@ websearch_to_tsquery('english', ",
+          )
+          .bind::<sql_types::Text, _>(search_term.to_owned())
+          .sql("))"),
+        )
+        .order_by(
+          sql::<sql_types::Float>(
+            "(select ts_rank(c.search_vector, websearch_to_tsquery('english', ",
+          )
+          .bind::<sql_types::Text, _>(search_term)
+          .sql(")) from comment c where c.id = comment_fast_view.id) desc"),
+        );
     };
 
+    if let Some(published_after) = self.published_after {
+      query = query.filter(published.ge(published_after));
+    }
+
+    if let Some(published_before) = self.published_before {
+      query = query.filter(published.lt(published_before));
+    }
+
     if let ListingType::Subscribed = self.listing_type {
       query = query.filter(subscribed.eq(true));
     }
@@ -240,38 +462,134 @@ impl<'a> CommentQueryBuilder<'a> {
       query = query.filter(saved.eq(true));
     }
 
+    if self.hide_bot_accounts {
+      query = query.filter(creator_bot_account.eq(false));
+    }
+
     query = match self.sort {
       SortType::Hot => query
-        .order_by(hot_rank.desc())
+        .then_order_by(hot_rank.desc())
         .then_order_by(published.desc()),
-      SortType::New => query.order_by(published.desc()),
-      SortType::TopAll => query.order_by(score.desc()),
+      SortType::New => query.then_order_by(published.desc()),
+      SortType::TopAll => query.then_order_by(score.desc()),
       SortType::TopYear => query
         .filter(published.gt(now - 1.years()))
-        .order_by(score.desc()),
+        .then_order_by(score.desc()),
       SortType::TopMonth => query
         .filter(published.gt(now - 1.months()))
-        .order_by(score.desc()),
+        .then_order_by(score.desc()),
       SortType::TopWeek => query
         .filter(published.gt(now - 1.weeks()))
-        .order_by(score.desc()),
+        .then_order_by(score.desc()),
       SortType::TopDay => query
         .filter(published.gt(now - 1.days()))
-        .order_by(score.desc()),
+        .then_order_by(score.desc()),
+      SortType::Controversial => query
+        .then_order_by(controversy_rank(upvotes, downvotes).desc())
+        .then_order_by(published.desc()),
+      SortType::TopHour => query
+        .filter(published.gt(now - 1.hours()))
+        .then_order_by(score.desc()),
+      SortType::TopSixHour => query
+        .filter(published.gt(now - 6.hours()))
+        .then_order_by(score.desc()),
+      SortType::TopTwelveHour => query
+        .filter(published.gt(now - 12.hours()))
+        .then_order_by(score.desc()),
+      // Comments don't have comments of their own
+      SortType::MostComments => query.then_order_by(published.desc()),
       // _ => query.order_by(published.desc()),
     };
 
+    // So the cursor always points at the same place
+    query = query.then_order_by(id.desc());
+
     let (limit, offset) = limit_and_offset(self.page, self.limit);
+    query = query.limit(limit);
+
+    if let Some(cursor) = &cursor {
+      let pinned_column = if for_post { Some("pinned") } else { None };
+      let sort = match self.sort {
+        SortType::MostComments => &SortType::New,
+        sort => sort,
+      };
+      query = query.filter(keyset_filter(
+        "comment_fast_view",
+        pinned_column,
+        sort,
+        cursor,
+      ));
+    } else {
+      query = query.offset(offset);
+    }
 
     // Note: deleted and removed comments are done on the front side
-    query
-      .limit(limit)
-      .offset(offset)
-      .load::<CommentView>(self.conn)
+    query.load::<CommentView>(self.conn)
   }
 }
 
+/// The replies to a comment that a limited listing of its thread left out, with the ones below
+/// them. The parent is missing for the top level of the post.
+#[derive(QueryableByName, PartialEq, Debug, Clone)]
+pub struct HiddenReplies {
+  #[sql_type = "sql_types::Nullable<sql_types::Integer>"]
+  pub parent_id: Option<i32>,
+  #[sql_type = "sql_types::BigInt"]
+  pub count: i64,
+}
+
 impl CommentView {
+  /// What's left to load below the `shown` comments of a post, and at its top level with
+  /// `top_level`. The `seen` threads were listed before, so they aren't counted.
+  pub fn hidden_replies(
+    conn: &PgConnection,
+    post_id: i32,
+    shown: Vec<i32>,
+    seen: Vec<i32>,
+    top_level: bool,
+  ) -> Result<Vec<HiddenReplies>, Error> {
+    sql_query(
+      "select h.parent_id, count(*) as count \
+      from comment h join comment d on d.path <@ h.path \
+      where h.post_id = $1 and h.id <> all($2) and h.id <> all($3) \
+      and (h.parent_id = any($2) or ($4 and h.parent_id is null)) \
+      group by h.parent_id",
+    )
+    .bind::<sql_types::Integer, _>(post_id)
+    .bind::<sql_types::Array<sql_types::Integer>, _>(shown)
+    .bind::<sql_types::Array<sql_types::Integer>, _>(seen)
+    .bind::<sql_types::Bool, _>(top_level)
+    .load(conn)
+  }
+
+  /// Same as `PostView::update_hot_ranks`, for comments.
+  pub fn update_hot_ranks(
+    conn: &PgConnection,
+    since: chrono::NaiveDateTime,
+  ) -> Result<usize, Error> {
+    sql_query(
+      "update comment_aggregates_fast \
+      set hot_rank = hot_rank(score, published) \
+      where published > $1",
+    )
+    .bind::<sql_types::Timestamp, _>(since)
+    .execute(conn)
+  }
+
+  /// Where a listing that ends with this comment picks up again.
+  pub fn cursor(&self) -> ListingCursor {
+    ListingCursor {
+      pinned: self.pinned,
+      hot_rank: self.hot_rank,
+      score: self.score,
+      upvotes: self.upvotes,
+      downvotes: self.downvotes,
+      number_of_comments: 0,
+      published: self.published,
+      id: self.id,
+    }
+  }
+
   pub fn read(
     conn: &PgConnection,
     from_comment_id: i32,
@@ -321,6 +639,8 @@ table! {
     creator_local -> Bool,
     creator_name -> Varchar,
     creator_avatar -> Nullable<Text>,
+    creator_bot_account -> Bool,
+    creator_display_name -> Nullable<Varchar>,
     creator_published -> Timestamp,
     score -> BigInt,
     upvotes -> BigInt,
@@ -334,8 +654,18 @@ table! {
   }
 }
 
+allow_tables_to_appear_in_same_query!(reply_fast_view, user_);
+
 #[derive(
-  Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, QueryableByName, Clone,
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
 )]
 #[table_name = "reply_fast_view"]
 pub struct ReplyView {
@@ -362,9 +692,13 @@ pub struct ReplyView {
   pub creator_local: bool,
   pub creator_name: String,
   pub creator_avatar: Option<String>,
+  pub creator_bot_account: bool,
+  pub creator_display_name: Option<String>,
   pub creator_published: chrono::NaiveDateTime,
   pub score: i64,
+  #[serde(default, skip_serializing_if = "crate::vote_counts_hidden")]
   pub upvotes: i64,
+  #[serde(default, skip_serializing_if = "crate::vote_counts_hidden")]
   pub downvotes: i64,
   pub hot_rank: i32,
   pub user_id: Option<i32>,
@@ -378,6 +712,7 @@ pub struct ReplyQueryBuilder<'a> {
   conn: &'a PgConnection,
   query: super::comment_view::reply_fast_view::BoxedQuery<'a, Pg>,
   for_user_id: i32,
+  for_community_id: Option<i32>,
   sort: &'a SortType,
   unread_only: bool,
   page: Option<i64>,
@@ -394,6 +729,7 @@ impl<'a> ReplyQueryBuilder<'a> {
       conn,
       query,
       for_user_id,
+      for_community_id: None,
       sort: &SortType::New,
       unread_only: false,
       page: None,
@@ -406,6 +742,11 @@ impl<'a> ReplyQueryBuilder<'a> {
     self
   }
 
+  pub fn for_community_id<T: MaybeOptional<i32>>(mut self, for_community_id: T) -> Self {
+    self.for_community_id = for_community_id.get_optional();
+    self
+  }
+
   pub fn unread_only(mut self, unread_only: bool) -> Self {
     self.unread_only = unread_only;
     self
@@ -430,12 +771,23 @@ impl<'a> ReplyQueryBuilder<'a> {
       .filter(user_id.eq(self.for_user_id))
       .filter(recipient_id.eq(self.for_user_id))
       .filter(deleted.eq(false))
-      .filter(removed.eq(false));
+      .filter(removed.eq(false))
+      .filter(
+        creator_id.ne_all(
+          user_::table
+            .filter(user_::shadow_banned.eq(true))
+            .select(user_::id),
+        ),
+      );
 
     if self.unread_only {
       query = query.filter(read.eq(false));
     }
 
+    if let Some(for_community_id) = self.for_community_id {
+      query = query.filter(community_id.eq(for_community_id));
+    }
+
     query = match self.sort {
       // SortType::Hot => query.order_by(hot_rank.desc()),
       SortType::New => query.order_by(published.desc()),
@@ -452,6 +804,20 @@ impl<'a> ReplyQueryBuilder<'a> {
       SortType::TopDay => query
         .filter(published.gt(now - 1.days()))
         .order_by(score.desc()),
+      SortType::Controversial => query
+        .order_by(controversy_rank(upvotes, downvotes).desc())
+        .then_order_by(published.desc()),
+      SortType::TopHour => query
+        .filter(published.gt(now - 1.hours()))
+        .order_by(score.desc()),
+      SortType::TopSixHour => query
+        .filter(published.gt(now - 6.hours()))
+        .order_by(score.desc()),
+      SortType::TopTwelveHour => query
+        .filter(published.gt(now - 12.hours()))
+        .order_by(score.desc()),
+      // Comments don't have comments of their own
+      SortType::MostComments => query.order_by(published.desc()),
       _ => query.order_by(published.desc()),
     };
 
@@ -463,6 +829,20 @@ impl<'a> ReplyQueryBuilder<'a> {
   }
 }
 
+impl ReplyView {
+  pub fn count_unread(conn: &PgConnection, for_user_id: i32) -> Result<i64, Error> {
+    use super::comment_view::reply_fast_view::dsl::*;
+    reply_fast_view
+      .filter(user_id.eq(for_user_id))
+      .filter(recipient_id.eq(for_user_id))
+      .filter(deleted.eq(false))
+      .filter(removed.eq(false))
+      .filter(read.eq(false))
+      .count()
+      .get_result::<i64>(conn)
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use crate::{
@@ -504,6 +884,12 @@ mod tests {
       private_key: None,
       public_key: None,
       last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
     };
 
     let inserted_user = User_::create(&conn, &new_user).unwrap();
@@ -524,6 +910,15 @@ mod tests {
       public_key: None,
       last_refreshed_at: None,
       published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
     };
 
     let inserted_community = Community::create(&conn, &new_community).unwrap();
@@ -543,10 +938,13 @@ mod tests {
       embed_title: None,
       embed_description: None,
       embed_html: None,
+      embed_type: None,
+      url_media_type: None,
       thumbnail_url: None,
       ap_id: "http://fake.com".into(),
       local: true,
       published: None,
+      pending: None,
     };
 
     let inserted_post = Post::create(&conn, &new_post).unwrap();
@@ -588,6 +986,7 @@ mod tests {
       removed: false,
       deleted: false,
       read: false,
+      pinned: false,
       banned: false,
       banned_from_community: false,
       published: inserted_comment.published,
@@ -595,6 +994,8 @@ mod tests {
       creator_name: inserted_user.name.to_owned(),
       creator_published: inserted_user.published,
       creator_avatar: None,
+      creator_bot_account: false,
+      creator_display_name: None,
       score: 1,
       downvotes: 0,
       hot_rank: 0,
@@ -623,6 +1024,7 @@ mod tests {
       removed: false,
       deleted: false,
       read: false,
+      pinned: false,
       banned: false,
       banned_from_community: false,
       published: inserted_comment.published,
@@ -630,6 +1032,8 @@ mod tests {
       creator_name: inserted_user.name.to_owned(),
       creator_published: inserted_user.published,
       creator_avatar: None,
+      creator_bot_account: false,
+      creator_display_name: None,
       score: 1,
       downvotes: 0,
       hot_rank: 0,
@@ -659,6 +1063,65 @@ mod tests {
       .unwrap();
     read_comment_views_with_user[0].hot_rank = 0;
 
+    let reply_form = CommentForm {
+      parent_id: Some(inserted_comment.id),
+      ap_id: "http://fake.com/reply".into(),
+      ..comment_form.clone()
+    };
+    let inserted_reply = Comment::create(&conn, &reply_form).unwrap();
+    let nested_reply_form = CommentForm {
+      parent_id: Some(inserted_reply.id),
+      ap_id: "http://fake.com/nested_reply".into(),
+      ..comment_form.clone()
+    };
+    let inserted_nested_reply = Comment::create(&conn, &nested_reply_form).unwrap();
+
+    let thread_ids = |parent_id: Option<i32>, max_depth: Option<i32>| {
+      let mut ids = CommentQueryBuilder::create(&conn)
+        .for_post_id(inserted_post.id)
+        .for_parent_id(parent_id)
+        .max_depth(max_depth)
+        .list()
+        .unwrap()
+        .iter()
+        .map(|c| c.id)
+        .collect::<Vec<i32>>();
+      ids.sort();
+      ids
+    };
+    let reply_thread = thread_ids(Some(inserted_reply.id), None);
+    let top_two_levels = thread_ids(None, Some(1));
+    let whole_thread = thread_ids(Some(inserted_comment.id), None);
+
+    let first_level = CommentQueryBuilder::create(&conn)
+      .for_post_id(inserted_post.id)
+      .breadth_first(true)
+      .limit(2)
+      .list()
+      .unwrap()
+      .iter()
+      .map(|c| c.id)
+      .collect::<Vec<i32>>();
+    let hidden = CommentView::hidden_replies(
+      &conn,
+      inserted_post.id,
+      first_level.clone(),
+      Vec::new(),
+      true,
+    )
+    .unwrap();
+    let without_reply = CommentQueryBuilder::create(&conn)
+      .for_post_id(inserted_post.id)
+      .for_parent_id(inserted_comment.id)
+      .excluded_threads(vec![inserted_reply.id])
+      .list()
+      .unwrap()
+      .iter()
+      .map(|c| c.id)
+      .collect::<Vec<i32>>();
+
+    Comment::delete(&conn, inserted_nested_reply.id).unwrap();
+    Comment::delete(&conn, inserted_reply.id).unwrap();
     let like_removed = CommentLike::remove(&conn, &comment_like_form).unwrap();
     let num_deleted = Comment::delete(&conn, inserted_comment.id).unwrap();
     Post::delete(&conn, inserted_post.id).unwrap();
@@ -670,6 +1133,28 @@ mod tests {
       expected_comment_view_with_user,
       read_comment_views_with_user[0]
     );
+    assert_eq!(
+      vec![inserted_reply.id, inserted_nested_reply.id],
+      reply_thread
+    );
+    assert_eq!(vec![inserted_comment.id, inserted_reply.id], top_two_levels);
+    assert_eq!(
+      vec![
+        inserted_comment.id,
+        inserted_reply.id,
+        inserted_nested_reply.id
+      ],
+      whole_thread
+    );
+    assert_eq!(vec![inserted_comment.id, inserted_reply.id], first_level);
+    assert_eq!(
+      vec![HiddenReplies {
+        parent_id: Some(inserted_reply.id),
+        count: 1,
+      }],
+      hidden
+    );
+    assert_eq!(vec![inserted_comment.id], without_reply);
     assert_eq!(1, num_deleted);
     assert_eq!(1, like_removed);
   }
@@ -1,4 +1,5 @@
 use crate::{
+  naive_now,
   schema::{community, community_follower, community_moderator, community_user_ban},
   Bannable,
   Crud,
@@ -27,6 +28,15 @@ pub struct Community {
   pub private_key: Option<String>,
   pub public_key: Option<String>,
   pub last_refreshed_at: chrono::NaiveDateTime,
+  pub require_flair: bool,
+  pub icon: Option<String>,
+  pub banner: Option<String>,
+  pub archive_after_days: i32,
+  pub post_approval: i16,
+  pub read_only: bool,
+  pub allowed_post_types: i16,
+  pub default_sort_type: i16,
+  pub language: Option<String>,
 }
 
 // TODO add better delete, remove, lock actions here.
@@ -48,6 +58,69 @@ pub struct CommunityForm {
   pub private_key: Option<String>,
   pub public_key: Option<String>,
   pub last_refreshed_at: Option<chrono::NaiveDateTime>,
+  pub require_flair: Option<bool>,
+  pub icon: Option<String>,
+  pub banner: Option<String>,
+  pub archive_after_days: Option<i32>,
+  pub post_approval: Option<i16>,
+  pub read_only: Option<bool>,
+  pub allowed_post_types: Option<i16>,
+  pub default_sort_type: Option<i16>,
+  pub language: Option<String>,
+}
+
+/// Which new posts of a community wait for a mod to approve them. Stored as an i16.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum PostApprovalMode {
+  Off,
+  /// Every new post is held back
+  All,
+  /// Only the posts of users who haven't had a post approved in the community yet
+  NewPosters,
+}
+
+impl PostApprovalMode {
+  pub fn from_i16(value: i16) -> Option<Self> {
+    match value {
+      0 => Some(PostApprovalMode::Off),
+      1 => Some(PostApprovalMode::All),
+      2 => Some(PostApprovalMode::NewPosters),
+      _ => None,
+    }
+  }
+}
+
+/// Which kinds of posts a community takes. Stored as an i16.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum AllowedPostTypes {
+  All,
+  /// Only posts with a url
+  Links,
+  /// Only posts without one
+  Text,
+  /// Only posts with the url of an image
+  Image,
+}
+
+impl AllowedPostTypes {
+  pub fn from_i16(value: i16) -> Option<Self> {
+    match value {
+      0 => Some(AllowedPostTypes::All),
+      1 => Some(AllowedPostTypes::Links),
+      2 => Some(AllowedPostTypes::Text),
+      3 => Some(AllowedPostTypes::Image),
+      _ => None,
+    }
+  }
+
+  pub fn allows(self, has_url: bool, is_image: bool) -> bool {
+    match self {
+      AllowedPostTypes::All => true,
+      AllowedPostTypes::Links => has_url,
+      AllowedPostTypes::Text => !has_url,
+      AllowedPostTypes::Image => has_url && is_image,
+    }
+  }
 }
 
 impl Crud<CommunityForm> for Community {
@@ -158,13 +231,16 @@ pub struct CommunityUserBan {
   pub community_id: i32,
   pub user_id: i32,
   pub published: chrono::NaiveDateTime,
+  pub expires: Option<chrono::NaiveDateTime>,
 }
 
 #[derive(Insertable, AsChangeset, Clone)]
 #[table_name = "community_user_ban"]
+#[changeset_options(treat_none_as_null = "true")]
 pub struct CommunityUserBanForm {
   pub community_id: i32,
   pub user_id: i32,
+  pub expires: Option<chrono::NaiveDateTime>,
 }
 
 impl Bannable<CommunityUserBanForm> for CommunityUserBan {
@@ -173,8 +249,12 @@ impl Bannable<CommunityUserBanForm> for CommunityUserBan {
     community_user_ban_form: &CommunityUserBanForm,
   ) -> Result<Self, Error> {
     use crate::schema::community_user_ban::dsl::*;
+    // Banning again changes when the ban expires
     insert_into(community_user_ban)
       .values(community_user_ban_form)
+      .on_conflict((community_id, user_id))
+      .do_update()
+      .set(community_user_ban_form)
       .get_result::<Self>(conn)
   }
 
@@ -192,6 +272,14 @@ impl Bannable<CommunityUserBanForm> for CommunityUserBan {
   }
 }
 
+impl CommunityUserBan {
+  /// Lifts the community bans that have run out, returning them.
+  pub fn lift_expired(conn: &PgConnection) -> Result<Vec<Self>, Error> {
+    use crate::schema::community_user_ban::dsl::*;
+    diesel::delete(community_user_ban.filter(expires.lt(naive_now()))).get_results::<Self>(conn)
+  }
+}
+
 #[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
 #[belongs_to(Community)]
 #[table_name = "community_follower"]
@@ -264,6 +352,12 @@ mod tests {
       private_key: None,
       public_key: None,
       last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
     };
 
     let inserted_user = User_::create(&conn, &new_user).unwrap();
@@ -284,6 +378,15 @@ mod tests {
       public_key: None,
       last_refreshed_at: None,
       published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
     };
 
     let inserted_community = Community::create(&conn, &new_community).unwrap();
@@ -305,6 +408,15 @@ mod tests {
       private_key: None,
       public_key: None,
       last_refreshed_at: inserted_community.published,
+      require_flair: false,
+      icon: None,
+      banner: None,
+      archive_after_days: 0,
+      post_approval: 0,
+      read_only: false,
+      allowed_post_types: 0,
+      default_sort_type: 0,
+      language: None,
     };
 
     let community_follower_form = CommunityFollowerForm {
@@ -339,6 +451,7 @@ mod tests {
     let community_user_ban_form = CommunityUserBanForm {
       community_id: inserted_community.id,
       user_id: inserted_user.id,
+      expires: None,
     };
 
     let inserted_community_user_ban =
@@ -349,6 +462,7 @@ mod tests {
       community_id: inserted_community.id,
       user_id: inserted_user.id,
       published: inserted_community_user_ban.published,
+      expires: None,
     };
 
     let read_community = Community::read(&conn, inserted_community.id).unwrap();
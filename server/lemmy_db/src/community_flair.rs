@@ -0,0 +1,256 @@
+use crate::{
+  schema::{community_flair, post_flair},
+  Crud,
+};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "community_flair"]
+pub struct CommunityFlair {
+  pub id: i32,
+  pub community_id: i32,
+  pub name: String,
+  pub color: String,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "community_flair"]
+pub struct CommunityFlairForm {
+  pub community_id: i32,
+  pub name: String,
+  pub color: String,
+}
+
+impl Crud<CommunityFlairForm> for CommunityFlair {
+  fn read(conn: &PgConnection, community_flair_id: i32) -> Result<Self, Error> {
+    use crate::schema::community_flair::dsl::*;
+    community_flair.find(community_flair_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, community_flair_id: i32) -> Result<usize, Error> {
+    use crate::schema::community_flair::dsl::*;
+    diesel::delete(community_flair.find(community_flair_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &CommunityFlairForm) -> Result<Self, Error> {
+    use crate::schema::community_flair::dsl::*;
+    insert_into(community_flair)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    community_flair_id: i32,
+    form: &CommunityFlairForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::community_flair::dsl::*;
+    diesel::update(community_flair.find(community_flair_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl CommunityFlair {
+  pub fn for_community(conn: &PgConnection, for_community_id: i32) -> Result<Vec<Self>, Error> {
+    use crate::schema::community_flair::dsl::*;
+    community_flair
+      .filter(community_id.eq(for_community_id))
+      .order_by(name)
+      .load::<Self>(conn)
+  }
+
+  pub fn for_post(conn: &PgConnection, for_post_id: i32) -> Result<Self, Error> {
+    community_flair::table
+      .inner_join(post_flair::table)
+      .filter(post_flair::post_id.eq(for_post_id))
+      .select(community_flair::all_columns)
+      .first::<Self>(conn)
+  }
+}
+
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
+#[belongs_to(CommunityFlair, foreign_key = "flair_id")]
+#[table_name = "post_flair"]
+pub struct PostFlair {
+  pub id: i32,
+  pub post_id: i32,
+  pub flair_id: i32,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "post_flair"]
+pub struct PostFlairForm {
+  pub post_id: i32,
+  pub flair_id: i32,
+}
+
+impl PostFlair {
+  /// Replaces the flair of a post, or clears it if there's no new one.
+  pub fn set(
+    conn: &PgConnection,
+    for_post_id: i32,
+    new_flair_id: Option<i32>,
+  ) -> Result<Option<Self>, Error> {
+    use crate::schema::post_flair::dsl::*;
+    diesel::delete(post_flair.filter(post_id.eq(for_post_id))).execute(conn)?;
+
+    match new_flair_id {
+      Some(new_flair_id) => {
+        let form = PostFlairForm {
+          post_id: for_post_id,
+          flair_id: new_flair_id,
+        };
+        insert_into(post_flair)
+          .values(&form)
+          .get_result::<Self>(conn)
+          .map(Some)
+      }
+      None => Ok(None),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    community::*,
+    post::*,
+    tests::establish_unpooled_connection,
+    user::*,
+    ListingType,
+    SortType,
+  };
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "flairy".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_flair_community".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_user.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: Some(true),
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_post = PostForm {
+      name: "A test flaired post".into(),
+      creator_id: inserted_user.id,
+      url: None,
+      body: None,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      embed_type: None,
+      url_media_type: None,
+      thumbnail_url: None,
+      ap_id: "http://fake.com".into(),
+      local: true,
+      published: None,
+      pending: None,
+    };
+
+    let inserted_post = Post::create(&conn, &new_post).unwrap();
+
+    let new_flair = CommunityFlairForm {
+      community_id: inserted_community.id,
+      name: "Discussion".into(),
+      color: "#ff0000".into(),
+    };
+
+    let inserted_flair = CommunityFlair::create(&conn, &new_flair).unwrap();
+
+    let expected_flair = CommunityFlair {
+      id: inserted_flair.id,
+      community_id: inserted_community.id,
+      name: "Discussion".into(),
+      color: "#ff0000".into(),
+      published: inserted_flair.published,
+    };
+
+    let read_flairs = CommunityFlair::for_community(&conn, inserted_community.id).unwrap();
+    let set_flair = PostFlair::set(&conn, inserted_post.id, Some(inserted_flair.id)).unwrap();
+    let post_flair = CommunityFlair::for_post(&conn, inserted_post.id).unwrap();
+    let cleared_flair = PostFlair::set(&conn, inserted_post.id, None).unwrap();
+    let no_post_flair = CommunityFlair::for_post(&conn, inserted_post.id);
+    let num_deleted = CommunityFlair::delete(&conn, inserted_flair.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert!(inserted_community.require_flair);
+    assert_eq!(expected_flair, inserted_flair);
+    assert_eq!(vec![expected_flair.to_owned()], read_flairs);
+    assert_eq!(inserted_flair.id, set_flair.unwrap().flair_id);
+    assert_eq!(expected_flair, post_flair);
+    assert!(cleared_flair.is_none());
+    assert!(no_post_flair.is_err());
+    assert_eq!(1, num_deleted);
+  }
+}
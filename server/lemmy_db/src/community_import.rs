@@ -0,0 +1,339 @@
+use crate::{
+  naive_now,
+  schema::{comment_aggregates, community_import, post_aggregates, user_aggregates},
+  Crud,
+};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Where the export of an import comes from. Stored by name in the `source` of the import.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportSource {
+  Reddit,
+  Kbin,
+}
+
+impl ImportSource {
+  pub fn name(self) -> &'static str {
+    match self {
+      ImportSource::Reddit => "reddit",
+      ImportSource::Kbin => "kbin",
+    }
+  }
+}
+
+/// A community an admin imported. The export is only kept until the import is done.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "community_import"]
+pub struct CommunityImport {
+  pub id: i32,
+  pub community_id: i32,
+  pub creator_id: i32,
+  pub source: String,
+  #[serde(skip)]
+  pub data: Option<String>,
+  pub posts_total: i32,
+  pub posts_imported: i32,
+  pub comments_total: i32,
+  pub comments_imported: i32,
+  pub error: Option<String>,
+  pub published: chrono::NaiveDateTime,
+  pub completed: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "community_import"]
+pub struct CommunityImportForm {
+  pub community_id: i32,
+  pub creator_id: i32,
+  pub source: String,
+  pub data: Option<String>,
+}
+
+impl Crud<CommunityImportForm> for CommunityImport {
+  fn read(conn: &PgConnection, import_id: i32) -> Result<Self, Error> {
+    use crate::schema::community_import::dsl::*;
+    community_import.find(import_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, import_id: i32) -> Result<usize, Error> {
+    use crate::schema::community_import::dsl::*;
+    diesel::delete(community_import.find(import_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &CommunityImportForm) -> Result<Self, Error> {
+    use crate::schema::community_import::dsl::*;
+    insert_into(community_import)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    import_id: i32,
+    form: &CommunityImportForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::community_import::dsl::*;
+    diesel::update(community_import.find(import_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl CommunityImport {
+  /// How much of the export there is to import, once it's read.
+  pub fn record_totals(
+    conn: &PgConnection,
+    import_id: i32,
+    new_posts_total: i32,
+    new_comments_total: i32,
+  ) -> Result<Self, Error> {
+    use crate::schema::community_import::dsl::*;
+    diesel::update(community_import.find(import_id))
+      .set((
+        posts_total.eq(new_posts_total),
+        comments_total.eq(new_comments_total),
+      ))
+      .get_result::<Self>(conn)
+  }
+
+  pub fn record_progress(
+    conn: &PgConnection,
+    import_id: i32,
+    new_posts_imported: i32,
+    new_comments_imported: i32,
+  ) -> Result<Self, Error> {
+    use crate::schema::community_import::dsl::*;
+    diesel::update(community_import.find(import_id))
+      .set((
+        posts_imported.eq(new_posts_imported),
+        comments_imported.eq(new_comments_imported),
+      ))
+      .get_result::<Self>(conn)
+  }
+
+  /// Marks the import done, or failed with the error, and lets go of the export.
+  pub fn complete(
+    conn: &PgConnection,
+    import_id: i32,
+    failure: Option<String>,
+  ) -> Result<Self, Error> {
+    use crate::schema::community_import::dsl::*;
+    diesel::update(community_import.find(import_id))
+      .set((
+        data.eq(None::<String>),
+        error.eq(failure),
+        completed.eq(naive_now()),
+      ))
+      .get_result::<Self>(conn)
+  }
+
+  /// Counts the score the post had where it came from, as if it had been voted up or down that
+  /// often here.
+  pub fn set_post_score(
+    conn: &PgConnection,
+    post_id: i32,
+    creator_id: i32,
+    imported_score: i64,
+  ) -> Result<(), Error> {
+    diesel::update(post_aggregates::table.find(post_id))
+      .set((
+        post_aggregates::score.eq(imported_score),
+        post_aggregates::upvotes.eq(imported_score.max(0)),
+        post_aggregates::downvotes.eq((-imported_score).max(0)),
+      ))
+      .execute(conn)?;
+    diesel::update(user_aggregates::table.find(creator_id))
+      .set(user_aggregates::post_score.eq(user_aggregates::post_score + imported_score))
+      .execute(conn)?;
+
+    // The listings read the score from the fast table, which only the triggers refresh
+    sql_query("delete from post_aggregates_fast where id = $1")
+      .bind::<sql_types::Integer, _>(post_id)
+      .execute(conn)?;
+    sql_query("insert into post_aggregates_fast select * from post_aggregates_view where id = $1")
+      .bind::<sql_types::Integer, _>(post_id)
+      .execute(conn)?;
+    Ok(())
+  }
+
+  /// Same as `set_post_score`, for a comment.
+  pub fn set_comment_score(
+    conn: &PgConnection,
+    comment_id: i32,
+    creator_id: i32,
+    imported_score: i64,
+  ) -> Result<(), Error> {
+    diesel::update(comment_aggregates::table.find(comment_id))
+      .set((
+        comment_aggregates::score.eq(imported_score),
+        comment_aggregates::upvotes.eq(imported_score.max(0)),
+        comment_aggregates::downvotes.eq((-imported_score).max(0)),
+      ))
+      .execute(conn)?;
+    diesel::update(user_aggregates::table.find(creator_id))
+      .set(user_aggregates::comment_score.eq(user_aggregates::comment_score + imported_score))
+      .execute(conn)?;
+
+    sql_query("delete from comment_aggregates_fast where id = $1")
+      .bind::<sql_types::Integer, _>(comment_id)
+      .execute(conn)?;
+    sql_query(
+      "insert into comment_aggregates_fast select * from comment_aggregates_view where id = $1",
+    )
+    .bind::<sql_types::Integer, _>(comment_id)
+    .execute(conn)?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    community::*,
+    post::*,
+    post_view::*,
+    tests::establish_unpooled_connection,
+    user::*,
+    ListingType,
+    SortType,
+  };
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "importy".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: true,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_import".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_user.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_import = CommunityImportForm {
+      community_id: inserted_community.id,
+      creator_id: inserted_user.id,
+      source: ImportSource::Reddit.name().into(),
+      data: Some("{}".into()),
+    };
+
+    let inserted_import = CommunityImport::create(&conn, &new_import).unwrap();
+
+    let new_post = PostForm {
+      name: "An imported post".into(),
+      creator_id: inserted_user.id,
+      url: None,
+      body: None,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      embed_type: None,
+      url_media_type: None,
+      thumbnail_url: None,
+      ap_id: "http://fake.com".into(),
+      local: true,
+      published: None,
+      pending: None,
+    };
+
+    let inserted_post = Post::create(&conn, &new_post).unwrap();
+    CommunityImport::set_post_score(&conn, inserted_post.id, inserted_user.id, -3).unwrap();
+    let post_view = PostView::read(&conn, inserted_post.id, None).unwrap();
+
+    CommunityImport::record_totals(&conn, inserted_import.id, 1, 0).unwrap();
+    CommunityImport::record_progress(&conn, inserted_import.id, 1, 0).unwrap();
+    let completed_import = CommunityImport::complete(&conn, inserted_import.id, None).unwrap();
+
+    let expected_import = CommunityImport {
+      id: inserted_import.id,
+      community_id: inserted_community.id,
+      creator_id: inserted_user.id,
+      source: "reddit".into(),
+      data: None,
+      posts_total: 1,
+      posts_imported: 1,
+      comments_total: 0,
+      comments_imported: 0,
+      error: None,
+      published: inserted_import.published,
+      completed: completed_import.completed,
+    };
+
+    let num_deleted = CommunityImport::delete(&conn, inserted_import.id).unwrap();
+    Post::delete(&conn, inserted_post.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(-3, post_view.score);
+    assert_eq!(0, post_view.upvotes);
+    assert_eq!(3, post_view.downvotes);
+    assert_eq!(expected_import, completed_import);
+    assert!(completed_import.completed.is_some());
+    assert_eq!(1, num_deleted);
+  }
+}
@@ -0,0 +1,197 @@
+use crate::{schema::community_rule, Crud};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "community_rule"]
+pub struct CommunityRule {
+  pub id: i32,
+  pub community_id: i32,
+  pub position: i32,
+  pub title: String,
+  pub description: Option<String>,
+  pub published: chrono::NaiveDateTime,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "community_rule"]
+pub struct CommunityRuleForm {
+  pub community_id: i32,
+  pub position: i32,
+  pub title: String,
+  pub description: Option<String>,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+impl Crud<CommunityRuleForm> for CommunityRule {
+  fn read(conn: &PgConnection, community_rule_id: i32) -> Result<Self, Error> {
+    use crate::schema::community_rule::dsl::*;
+    community_rule.find(community_rule_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, community_rule_id: i32) -> Result<usize, Error> {
+    use crate::schema::community_rule::dsl::*;
+    diesel::delete(community_rule.find(community_rule_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &CommunityRuleForm) -> Result<Self, Error> {
+    use crate::schema::community_rule::dsl::*;
+    insert_into(community_rule)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    community_rule_id: i32,
+    form: &CommunityRuleForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::community_rule::dsl::*;
+    diesel::update(community_rule.find(community_rule_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl CommunityRule {
+  pub fn for_community(conn: &PgConnection, for_community_id: i32) -> Result<Vec<Self>, Error> {
+    use crate::schema::community_rule::dsl::*;
+    community_rule
+      .filter(community_id.eq(for_community_id))
+      .order_by(position)
+      .then_order_by(id)
+      .load::<Self>(conn)
+  }
+
+  /// The position for a rule added after all the existing ones
+  pub fn next_position(conn: &PgConnection, for_community_id: i32) -> Result<i32, Error> {
+    use crate::schema::community_rule::dsl::*;
+    let last = community_rule
+      .filter(community_id.eq(for_community_id))
+      .select(max(position))
+      .first::<Option<i32>>(conn)?;
+    Ok(last.map(|p| p + 1).unwrap_or(0))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{community::*, tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "rule_maker".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_rule_community".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_user.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let first_position = CommunityRule::next_position(&conn, inserted_community.id).unwrap();
+
+    let new_rule = CommunityRuleForm {
+      community_id: inserted_community.id,
+      position: first_position,
+      title: "No spam".into(),
+      description: None,
+      updated: None,
+    };
+
+    let inserted_rule = CommunityRule::create(&conn, &new_rule).unwrap();
+
+    let expected_rule = CommunityRule {
+      id: inserted_rule.id,
+      community_id: inserted_community.id,
+      position: 0,
+      title: "No spam".into(),
+      description: None,
+      published: inserted_rule.published,
+      updated: None,
+    };
+
+    let second_position = CommunityRule::next_position(&conn, inserted_community.id).unwrap();
+    let second_rule = CommunityRule::create(
+      &conn,
+      &CommunityRuleForm {
+        position: second_position,
+        title: "Be civil".into(),
+        ..new_rule.clone()
+      },
+    )
+    .unwrap();
+
+    let read_rule = CommunityRule::read(&conn, inserted_rule.id).unwrap();
+    let read_rules = CommunityRule::for_community(&conn, inserted_community.id).unwrap();
+    let num_deleted = CommunityRule::delete(&conn, inserted_rule.id).unwrap();
+    CommunityRule::delete(&conn, second_rule.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(0, first_position);
+    assert_eq!(1, second_position);
+    assert_eq!(expected_rule, inserted_rule);
+    assert_eq!(expected_rule, read_rule);
+    assert_eq!(vec![expected_rule, second_rule], read_rules);
+    assert_eq!(1, num_deleted);
+  }
+}
@@ -0,0 +1,299 @@
+use crate::{naive_now, schema::community_takeover_request, Crud};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A user asking to take over a community whose mods have all gone inactive. The admins decide.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "community_takeover_request"]
+pub struct CommunityTakeoverRequest {
+  pub id: i32,
+  pub community_id: i32,
+  pub creator_id: i32,
+  pub reason: Option<String>,
+  pub status: i16,
+  pub resolver_id: Option<i32>,
+  pub published: chrono::NaiveDateTime,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "community_takeover_request"]
+pub struct CommunityTakeoverRequestForm {
+  pub community_id: i32,
+  pub creator_id: i32,
+  pub reason: Option<String>,
+}
+
+/// Stored as an i16.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum TakeoverRequestStatus {
+  Pending,
+  Approved,
+  Rejected,
+}
+
+impl TakeoverRequestStatus {
+  pub fn from_i16(value: i16) -> Option<Self> {
+    match value {
+      0 => Some(TakeoverRequestStatus::Pending),
+      1 => Some(TakeoverRequestStatus::Approved),
+      2 => Some(TakeoverRequestStatus::Rejected),
+      _ => None,
+    }
+  }
+}
+
+impl Crud<CommunityTakeoverRequestForm> for CommunityTakeoverRequest {
+  fn read(conn: &PgConnection, request_id: i32) -> Result<Self, Error> {
+    use crate::schema::community_takeover_request::dsl::*;
+    community_takeover_request
+      .find(request_id)
+      .first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, request_id: i32) -> Result<usize, Error> {
+    use crate::schema::community_takeover_request::dsl::*;
+    diesel::delete(community_takeover_request.find(request_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &CommunityTakeoverRequestForm) -> Result<Self, Error> {
+    use crate::schema::community_takeover_request::dsl::*;
+    insert_into(community_takeover_request)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    request_id: i32,
+    form: &CommunityTakeoverRequestForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::community_takeover_request::dsl::*;
+    diesel::update(community_takeover_request.find(request_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl CommunityTakeoverRequest {
+  /// The ones waiting on an admin, oldest first
+  pub fn list_pending(conn: &PgConnection) -> Result<Vec<Self>, Error> {
+    use crate::schema::community_takeover_request::dsl::*;
+    community_takeover_request
+      .filter(status.eq(TakeoverRequestStatus::Pending as i16))
+      .order_by(published.asc())
+      .load::<Self>(conn)
+  }
+
+  pub fn resolve(
+    conn: &PgConnection,
+    request_id: i32,
+    new_status: TakeoverRequestStatus,
+    for_resolver_id: i32,
+  ) -> Result<Self, Error> {
+    use crate::schema::community_takeover_request::dsl::*;
+    diesel::update(community_takeover_request.find(request_id))
+      .set((
+        status.eq(new_status as i16),
+        resolver_id.eq(for_resolver_id),
+        updated.eq(naive_now()),
+      ))
+      .get_result::<Self>(conn)
+  }
+}
+
+/// When any of the community's mods last posted or commented, anywhere on the site. None if they
+/// never did, or if the community has no mods.
+pub fn mods_last_active(
+  conn: &PgConnection,
+  for_community_id: i32,
+) -> Result<Option<chrono::NaiveDateTime>, Error> {
+  use crate::schema::{comment, community_moderator, post};
+  let mod_ids = community_moderator::table
+    .filter(community_moderator::community_id.eq(for_community_id))
+    .select(community_moderator::user_id);
+
+  let last_post = post::table
+    .filter(post::creator_id.eq_any(mod_ids))
+    .select(max(post::published))
+    .first::<Option<chrono::NaiveDateTime>>(conn)?;
+  let last_comment = comment::table
+    .filter(comment::creator_id.eq_any(mod_ids))
+    .select(max(comment::published))
+    .first::<Option<chrono::NaiveDateTime>>(conn)?;
+
+  Ok(last_post.max(last_comment))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    community::*,
+    post::*,
+    tests::establish_unpooled_connection,
+    user::*,
+    Joinable,
+    ListingType,
+    SortType,
+  };
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "takeover_requester".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_mod = UserForm {
+      name: "takeover_absent_mod".into(),
+      ..new_user
+    };
+
+    let inserted_mod = User_::create(&conn, &new_mod).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_takeover".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_mod.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let moderator_form = CommunityModeratorForm {
+      community_id: inserted_community.id,
+      user_id: inserted_mod.id,
+    };
+
+    CommunityModerator::join(&conn, &moderator_form).unwrap();
+    let never_active = mods_last_active(&conn, inserted_community.id).unwrap();
+
+    let new_post = PostForm {
+      name: "Last words".into(),
+      creator_id: inserted_mod.id,
+      url: None,
+      body: None,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      embed_type: None,
+      url_media_type: None,
+      thumbnail_url: None,
+      ap_id: "http://fake.com".into(),
+      local: true,
+      published: None,
+      pending: None,
+    };
+
+    let inserted_post = Post::create(&conn, &new_post).unwrap();
+    let last_active = mods_last_active(&conn, inserted_community.id).unwrap();
+
+    let request_form = CommunityTakeoverRequestForm {
+      community_id: inserted_community.id,
+      creator_id: inserted_user.id,
+      reason: Some("Nobody is around".into()),
+    };
+
+    let inserted_request = CommunityTakeoverRequest::create(&conn, &request_form).unwrap();
+
+    let expected_request = CommunityTakeoverRequest {
+      id: inserted_request.id,
+      community_id: inserted_community.id,
+      creator_id: inserted_user.id,
+      reason: Some("Nobody is around".into()),
+      status: TakeoverRequestStatus::Pending as i16,
+      resolver_id: None,
+      published: inserted_request.published,
+      updated: None,
+    };
+
+    let duplicate = CommunityTakeoverRequest::create(&conn, &request_form);
+    let pending = CommunityTakeoverRequest::list_pending(&conn).unwrap();
+    let resolved_request = CommunityTakeoverRequest::resolve(
+      &conn,
+      inserted_request.id,
+      TakeoverRequestStatus::Rejected,
+      inserted_mod.id,
+    )
+    .unwrap();
+    let pending_after = CommunityTakeoverRequest::list_pending(&conn).unwrap();
+    let num_deleted = CommunityTakeoverRequest::delete(&conn, inserted_request.id).unwrap();
+
+    Post::delete(&conn, inserted_post.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+    User_::delete(&conn, inserted_mod.id).unwrap();
+
+    assert_eq!(None, never_active);
+    assert_eq!(Some(inserted_post.published), last_active);
+    assert_eq!(expected_request, inserted_request);
+    assert!(duplicate.is_err());
+    assert!(pending.contains(&inserted_request));
+    assert_eq!(
+      Some(TakeoverRequestStatus::Rejected),
+      TakeoverRequestStatus::from_i16(resolved_request.status)
+    );
+    assert_eq!(Some(inserted_mod.id), resolved_request.resolver_id);
+    assert!(!pending_after.iter().any(|r| r.id == inserted_request.id));
+    assert_eq!(1, num_deleted);
+  }
+}
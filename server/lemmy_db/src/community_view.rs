@@ -1,6 +1,7 @@
 use super::community_view::community_fast_view::BoxedQuery;
-use crate::{fuzzy_search, limit_and_offset, MaybeOptional, SortType};
+use crate::{fuzzy_search, limit_and_offset, naive_now, MaybeOptional, SortType};
 use diesel::{pg::Pg, result::Error, *};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 table! {
@@ -19,6 +20,15 @@ table! {
     actor_id -> Text,
     local -> Bool,
     last_refreshed_at -> Timestamp,
+    require_flair -> Bool,
+    icon -> Nullable<Text>,
+    banner -> Nullable<Text>,
+    archive_after_days -> Int4,
+    post_approval -> Int2,
+    read_only -> Bool,
+    allowed_post_types -> Int2,
+    default_sort_type -> Int2,
+    language -> Nullable<Varchar>,
     creator_actor_id -> Text,
     creator_local -> Bool,
     creator_name -> Varchar,
@@ -49,6 +59,15 @@ table! {
     actor_id -> Text,
     local -> Bool,
     last_refreshed_at -> Timestamp,
+    require_flair -> Bool,
+    icon -> Nullable<Text>,
+    banner -> Nullable<Text>,
+    archive_after_days -> Int4,
+    post_approval -> Int2,
+    read_only -> Bool,
+    allowed_post_types -> Int2,
+    default_sort_type -> Int2,
+    language -> Nullable<Varchar>,
     creator_actor_id -> Text,
     creator_local -> Bool,
     creator_name -> Varchar,
@@ -101,6 +120,7 @@ table! {
     community_id -> Int4,
     user_id -> Int4,
     published -> Timestamp,
+    expires -> Nullable<Timestamp>,
     user_actor_id -> Text,
     user_local -> Bool,
     user_name -> Varchar,
@@ -112,7 +132,15 @@ table! {
 }
 
 #[derive(
-  Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, QueryableByName, Clone,
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
 )]
 #[table_name = "community_fast_view"]
 pub struct CommunityView {
@@ -130,6 +158,15 @@ pub struct CommunityView {
   pub actor_id: String,
   pub local: bool,
   pub last_refreshed_at: chrono::NaiveDateTime,
+  pub require_flair: bool,
+  pub icon: Option<String>,
+  pub banner: Option<String>,
+  pub archive_after_days: i32,
+  pub post_approval: i16,
+  pub read_only: bool,
+  pub allowed_post_types: i16,
+  pub default_sort_type: i16,
+  pub language: Option<String>,
   pub creator_actor_id: String,
   pub creator_local: bool,
   pub creator_name: String,
@@ -277,7 +314,15 @@ impl CommunityView {
 }
 
 #[derive(
-  Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, QueryableByName, Clone,
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
 )]
 #[table_name = "community_moderator_view"]
 pub struct CommunityModeratorView {
@@ -313,7 +358,15 @@ impl CommunityModeratorView {
 }
 
 #[derive(
-  Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, QueryableByName, Clone,
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
 )]
 #[table_name = "community_follower_view"]
 pub struct CommunityFollowerView {
@@ -355,6 +408,7 @@ pub struct CommunityUserBanView {
   pub community_id: i32,
   pub user_id: i32,
   pub published: chrono::NaiveDateTime,
+  pub expires: Option<chrono::NaiveDateTime>,
   pub user_actor_id: String,
   pub user_local: bool,
   pub user_name: String,
@@ -388,6 +442,7 @@ impl CommunityUserBanView {
     community_user_ban_view
       .filter(user_id.eq(from_user_id))
       .filter(community_id.eq(from_community_id))
+      .filter(expires.is_null().or(expires.gt(naive_now())))
       .first::<Self>(conn)
   }
 }
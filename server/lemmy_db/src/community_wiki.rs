@@ -0,0 +1,263 @@
+use crate::{
+  schema::{community_wiki_page, community_wiki_page_revision},
+  Crud,
+};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Who is allowed to edit a wiki page. Stored as an i16. Mods and admins can always edit.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum WikiEditPermission {
+  Mods,
+  Subscribers,
+  Everyone,
+}
+
+impl WikiEditPermission {
+  pub fn from_i16(value: i16) -> Option<Self> {
+    match value {
+      0 => Some(WikiEditPermission::Mods),
+      1 => Some(WikiEditPermission::Subscribers),
+      2 => Some(WikiEditPermission::Everyone),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "community_wiki_page"]
+pub struct CommunityWikiPage {
+  pub id: i32,
+  pub community_id: i32,
+  pub slug: String,
+  pub title: String,
+  pub content: String,
+  pub edit_permission: i16,
+  pub editor_id: Option<i32>,
+  pub published: chrono::NaiveDateTime,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "community_wiki_page"]
+pub struct CommunityWikiPageForm {
+  pub community_id: i32,
+  pub slug: String,
+  pub title: String,
+  pub content: String,
+  pub edit_permission: i16,
+  pub editor_id: Option<i32>,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+impl Crud<CommunityWikiPageForm> for CommunityWikiPage {
+  fn read(conn: &PgConnection, page_id: i32) -> Result<Self, Error> {
+    use crate::schema::community_wiki_page::dsl::*;
+    community_wiki_page.find(page_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, page_id: i32) -> Result<usize, Error> {
+    use crate::schema::community_wiki_page::dsl::*;
+    diesel::delete(community_wiki_page.find(page_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &CommunityWikiPageForm) -> Result<Self, Error> {
+    use crate::schema::community_wiki_page::dsl::*;
+    insert_into(community_wiki_page)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    page_id: i32,
+    form: &CommunityWikiPageForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::community_wiki_page::dsl::*;
+    diesel::update(community_wiki_page.find(page_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl CommunityWikiPage {
+  pub fn read_from_slug(
+    conn: &PgConnection,
+    for_community_id: i32,
+    for_slug: &str,
+  ) -> Result<Self, Error> {
+    use crate::schema::community_wiki_page::dsl::*;
+    community_wiki_page
+      .filter(community_id.eq(for_community_id))
+      .filter(slug.eq(for_slug))
+      .first::<Self>(conn)
+  }
+
+  pub fn for_community(conn: &PgConnection, for_community_id: i32) -> Result<Vec<Self>, Error> {
+    use crate::schema::community_wiki_page::dsl::*;
+    community_wiki_page
+      .filter(community_id.eq(for_community_id))
+      .order_by(slug)
+      .load::<Self>(conn)
+  }
+}
+
+/// A previous version of an edited wiki page. These are saved by the database whenever the title
+/// or content of a page changes.
+#[derive(
+  Identifiable, Queryable, Associations, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema,
+)]
+#[belongs_to(CommunityWikiPage, foreign_key = "page_id")]
+#[table_name = "community_wiki_page_revision"]
+pub struct CommunityWikiPageRevision {
+  pub id: i32,
+  pub page_id: i32,
+  pub title: String,
+  pub content: String,
+  pub editor_id: Option<i32>,
+  pub published: chrono::NaiveDateTime,
+}
+
+impl CommunityWikiPageRevision {
+  /// Newest first
+  pub fn for_page(conn: &PgConnection, for_page_id: i32) -> Result<Vec<Self>, Error> {
+    use crate::schema::community_wiki_page_revision::dsl::*;
+    community_wiki_page_revision
+      .filter(page_id.eq(for_page_id))
+      .order_by(published.desc())
+      .then_order_by(id.desc())
+      .load::<Self>(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    community::*,
+    naive_now,
+    tests::establish_unpooled_connection,
+    user::*,
+    ListingType,
+    SortType,
+  };
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "wiki_editor".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_wiki_community".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_user.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_page = CommunityWikiPageForm {
+      community_id: inserted_community.id,
+      slug: "rules".into(),
+      title: "Rules".into(),
+      content: "Be nice".into(),
+      edit_permission: WikiEditPermission::Mods as i16,
+      editor_id: Some(inserted_user.id),
+      updated: None,
+    };
+
+    let inserted_page = CommunityWikiPage::create(&conn, &new_page).unwrap();
+
+    let expected_page = CommunityWikiPage {
+      id: inserted_page.id,
+      community_id: inserted_community.id,
+      slug: "rules".into(),
+      title: "Rules".into(),
+      content: "Be nice".into(),
+      edit_permission: 0,
+      editor_id: Some(inserted_user.id),
+      published: inserted_page.published,
+      updated: None,
+    };
+
+    let read_page =
+      CommunityWikiPage::read_from_slug(&conn, inserted_community.id, "rules").unwrap();
+    let read_pages = CommunityWikiPage::for_community(&conn, inserted_community.id).unwrap();
+    let duplicate_page = CommunityWikiPage::create(&conn, &new_page);
+
+    let edit_page = CommunityWikiPageForm {
+      content: "Be very nice".into(),
+      updated: Some(naive_now()),
+      ..new_page.clone()
+    };
+    let updated_page = CommunityWikiPage::update(&conn, inserted_page.id, &edit_page).unwrap();
+    let revisions = CommunityWikiPageRevision::for_page(&conn, inserted_page.id).unwrap();
+
+    let num_deleted = CommunityWikiPage::delete(&conn, inserted_page.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(expected_page, inserted_page);
+    assert_eq!(expected_page, read_page);
+    assert_eq!(vec![expected_page], read_pages);
+    assert!(duplicate_page.is_err());
+    assert_eq!("Be very nice", updated_page.content);
+    assert_eq!(1, revisions.len());
+    assert_eq!("Be nice", revisions[0].content);
+    assert_eq!(inserted_page.published, revisions[0].published);
+    assert_eq!(1, num_deleted);
+  }
+}
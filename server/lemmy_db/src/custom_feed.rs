@@ -0,0 +1,232 @@
+use crate::{
+  schema::{custom_feed, custom_feed_community},
+  Crud,
+};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "custom_feed"]
+pub struct CustomFeed {
+  pub id: i32,
+  pub creator_id: i32,
+  pub name: String,
+  pub published: chrono::NaiveDateTime,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "custom_feed"]
+pub struct CustomFeedForm {
+  pub creator_id: i32,
+  pub name: String,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+impl Crud<CustomFeedForm> for CustomFeed {
+  fn read(conn: &PgConnection, custom_feed_id: i32) -> Result<Self, Error> {
+    use crate::schema::custom_feed::dsl::*;
+    custom_feed.find(custom_feed_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, custom_feed_id: i32) -> Result<usize, Error> {
+    use crate::schema::custom_feed::dsl::*;
+    diesel::delete(custom_feed.find(custom_feed_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &CustomFeedForm) -> Result<Self, Error> {
+    use crate::schema::custom_feed::dsl::*;
+    insert_into(custom_feed)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    custom_feed_id: i32,
+    form: &CustomFeedForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::custom_feed::dsl::*;
+    diesel::update(custom_feed.find(custom_feed_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl CustomFeed {
+  pub fn for_creator(conn: &PgConnection, for_creator_id: i32) -> Result<Vec<Self>, Error> {
+    use crate::schema::custom_feed::dsl::*;
+    custom_feed
+      .filter(creator_id.eq(for_creator_id))
+      .order_by(name)
+      .load::<Self>(conn)
+  }
+}
+
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
+#[belongs_to(CustomFeed)]
+#[table_name = "custom_feed_community"]
+pub struct CustomFeedCommunity {
+  pub id: i32,
+  pub custom_feed_id: i32,
+  pub community_id: i32,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "custom_feed_community"]
+pub struct CustomFeedCommunityForm {
+  pub custom_feed_id: i32,
+  pub community_id: i32,
+}
+
+impl CustomFeedCommunity {
+  /// Replaces all the communities of a feed.
+  pub fn set_communities(
+    conn: &PgConnection,
+    for_custom_feed_id: i32,
+    community_ids: &[i32],
+  ) -> Result<Vec<Self>, Error> {
+    use crate::schema::custom_feed_community::dsl::*;
+    conn.transaction::<_, Error, _>(|| {
+      diesel::delete(custom_feed_community.filter(custom_feed_id.eq(for_custom_feed_id)))
+        .execute(conn)?;
+
+      if community_ids.is_empty() {
+        return Ok(Vec::new());
+      }
+
+      let forms = community_ids
+        .iter()
+        .map(|c| CustomFeedCommunityForm {
+          custom_feed_id: for_custom_feed_id,
+          community_id: *c,
+        })
+        .collect::<Vec<CustomFeedCommunityForm>>();
+
+      insert_into(custom_feed_community)
+        .values(&forms)
+        .on_conflict_do_nothing()
+        .get_results::<Self>(conn)
+    })
+  }
+
+  pub fn community_ids(conn: &PgConnection, for_custom_feed_id: i32) -> Result<Vec<i32>, Error> {
+    use crate::schema::custom_feed_community::dsl::*;
+    custom_feed_community
+      .filter(custom_feed_id.eq(for_custom_feed_id))
+      .select(community_id)
+      .load::<i32>(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{community::*, tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "feedy".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_feed_community".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_user.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_feed = CustomFeedForm {
+      creator_id: inserted_user.id,
+      name: "My feed".into(),
+      updated: None,
+    };
+
+    let inserted_feed = CustomFeed::create(&conn, &new_feed).unwrap();
+
+    let expected_feed = CustomFeed {
+      id: inserted_feed.id,
+      creator_id: inserted_user.id,
+      name: "My feed".into(),
+      published: inserted_feed.published,
+      updated: None,
+    };
+
+    let set_communities = CustomFeedCommunity::set_communities(
+      &conn,
+      inserted_feed.id,
+      &[inserted_community.id, inserted_community.id],
+    )
+    .unwrap();
+    let read_community_ids = CustomFeedCommunity::community_ids(&conn, inserted_feed.id).unwrap();
+    let read_feeds = CustomFeed::for_creator(&conn, inserted_user.id).unwrap();
+    let cleared_communities =
+      CustomFeedCommunity::set_communities(&conn, inserted_feed.id, &[]).unwrap();
+    let num_deleted = CustomFeed::delete(&conn, inserted_feed.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(expected_feed, inserted_feed);
+    assert_eq!(1, set_communities.len());
+    assert_eq!(vec![inserted_community.id], read_community_ids);
+    assert_eq!(vec![expected_feed], read_feeds);
+    assert!(cleared_communities.is_empty());
+    assert_eq!(1, num_deleted);
+  }
+}
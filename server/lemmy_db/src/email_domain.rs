@@ -0,0 +1,145 @@
+use crate::{schema::email_domain, Crud};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// An email domain that registration allows, or denies.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "email_domain"]
+pub struct EmailDomain {
+  pub id: i32,
+  pub domain: String,
+  pub allowed: bool,
+  pub creator_id: i32,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "email_domain"]
+pub struct EmailDomainForm {
+  pub domain: String,
+  pub allowed: bool,
+  pub creator_id: i32,
+}
+
+impl Crud<EmailDomainForm> for EmailDomain {
+  fn read(conn: &PgConnection, email_domain_id: i32) -> Result<Self, Error> {
+    use crate::schema::email_domain::dsl::*;
+    email_domain.find(email_domain_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, email_domain_id: i32) -> Result<usize, Error> {
+    use crate::schema::email_domain::dsl::*;
+    diesel::delete(email_domain.find(email_domain_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &EmailDomainForm) -> Result<Self, Error> {
+    use crate::schema::email_domain::dsl::*;
+    insert_into(email_domain)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    email_domain_id: i32,
+    form: &EmailDomainForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::email_domain::dsl::*;
+    diesel::update(email_domain.find(email_domain_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl EmailDomain {
+  /// The allowed domains first, then the denied ones
+  pub fn list(conn: &PgConnection) -> Result<Vec<Self>, Error> {
+    use crate::schema::email_domain::dsl::*;
+    email_domain
+      .order_by((allowed.desc(), domain))
+      .load::<Self>(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "email_domain_admin".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: true,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let denied_form = EmailDomainForm {
+      domain: "*.spam-inbox.test".into(),
+      allowed: false,
+      creator_id: inserted_user.id,
+    };
+
+    let inserted_denied = EmailDomain::create(&conn, &denied_form).unwrap();
+
+    let allowed_form = EmailDomainForm {
+      domain: "university.test".into(),
+      allowed: true,
+      creator_id: inserted_user.id,
+    };
+
+    let inserted_allowed = EmailDomain::create(&conn, &allowed_form).unwrap();
+    let duplicate = EmailDomain::create(&conn, &allowed_form);
+
+    let expected_denied = EmailDomain {
+      id: inserted_denied.id,
+      domain: "*.spam-inbox.test".into(),
+      allowed: false,
+      creator_id: inserted_user.id,
+      published: inserted_denied.published,
+    };
+
+    let read_denied = EmailDomain::read(&conn, inserted_denied.id).unwrap();
+    let domains = EmailDomain::list(&conn).unwrap();
+    let num_deleted = EmailDomain::delete(&conn, inserted_denied.id).unwrap();
+    EmailDomain::delete(&conn, inserted_allowed.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(expected_denied, read_denied);
+    assert!(duplicate.is_err());
+    let allowed_pos = domains.iter().position(|d| d.id == inserted_allowed.id);
+    let denied_pos = domains.iter().position(|d| d.id == inserted_denied.id);
+    assert!(allowed_pos.unwrap() < denied_pos.unwrap());
+    assert_eq!(1, num_deleted);
+  }
+}
@@ -0,0 +1,159 @@
+use crate::{
+  schema::{email_verification, email_verification::dsl::*},
+  Crud,
+};
+use diesel::{dsl::*, result::Error, *};
+use sha2::{Digest, Sha256};
+
+#[derive(Queryable, Identifiable, PartialEq, Debug)]
+#[table_name = "email_verification"]
+pub struct EmailVerification {
+  pub id: i32,
+  pub user_id: i32,
+  pub email: String,
+  pub token_encrypted: String,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "email_verification"]
+pub struct EmailVerificationForm {
+  pub user_id: i32,
+  pub email: String,
+  pub token_encrypted: String,
+}
+
+impl Crud<EmailVerificationForm> for EmailVerification {
+  fn read(conn: &PgConnection, email_verification_id: i32) -> Result<Self, Error> {
+    use crate::schema::email_verification::dsl::*;
+    email_verification
+      .find(email_verification_id)
+      .first::<Self>(conn)
+  }
+  fn delete(conn: &PgConnection, email_verification_id: i32) -> Result<usize, Error> {
+    diesel::delete(email_verification.find(email_verification_id)).execute(conn)
+  }
+  fn create(conn: &PgConnection, form: &EmailVerificationForm) -> Result<Self, Error> {
+    insert_into(email_verification)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+  fn update(
+    conn: &PgConnection,
+    email_verification_id: i32,
+    form: &EmailVerificationForm,
+  ) -> Result<Self, Error> {
+    diesel::update(email_verification.find(email_verification_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl EmailVerification {
+  pub fn create_token(
+    conn: &PgConnection,
+    from_user_id: i32,
+    for_email: &str,
+    token: &str,
+  ) -> Result<Self, Error> {
+    let form = EmailVerificationForm {
+      user_id: from_user_id,
+      email: for_email.to_string(),
+      token_encrypted: EmailVerification::hash_token(token),
+    };
+
+    Self::create(&conn, &form)
+  }
+
+  pub fn read_from_token(conn: &PgConnection, token: &str) -> Result<Self, Error> {
+    email_verification
+      .filter(token_encrypted.eq(EmailVerification::hash_token(token)))
+      .filter(published.gt(now - 7.days()))
+      .first::<Self>(conn)
+  }
+
+  /// Removes every outstanding token for a user, once their email is verified or changed.
+  pub fn delete_for_user(conn: &PgConnection, for_user_id: i32) -> Result<usize, Error> {
+    diesel::delete(email_verification.filter(user_id.eq(for_user_id))).execute(conn)
+  }
+
+  fn hash_token(token: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(token);
+    let mut str = String::new();
+    for byte in hasher.finalize().to_vec() {
+      str = format!("{}{:02x}", str, byte);
+    }
+    str
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{super::user::*, *};
+  use crate::{tests::establish_unpooled_connection, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "thommy ev".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: Some("thommy@fake.com".into()),
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let token = "nope";
+    let token_encrypted_ = "ca3704aa0b06f5954c79ee837faa152d84d6b2d42838f0637a15eda8337dbdce";
+
+    let inserted_email_verification =
+      EmailVerification::create_token(&conn, inserted_user.id, "thommy@fake.com", token).unwrap();
+
+    let expected_email_verification = EmailVerification {
+      id: inserted_email_verification.id,
+      user_id: inserted_user.id,
+      email: "thommy@fake.com".into(),
+      token_encrypted: token_encrypted_.to_string(),
+      published: inserted_email_verification.published,
+    };
+
+    let read_email_verification = EmailVerification::read_from_token(&conn, token).unwrap();
+    let verified_user = User_::update_email_verified(&conn, inserted_user.id, true).unwrap();
+    let num_tokens_deleted = EmailVerification::delete_for_user(&conn, inserted_user.id).unwrap();
+    let num_deleted = User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(expected_email_verification, read_email_verification);
+    assert_eq!(expected_email_verification, inserted_email_verification);
+    assert!(!inserted_user.email_verified);
+    assert!(verified_user.email_verified);
+    assert_eq!(1, num_tokens_deleted);
+    assert_eq!(1, num_deleted);
+  }
+}
@@ -0,0 +1,110 @@
+use crate::{naive_now, schema::image_proxy};
+use diesel::{dsl::*, result::Error, *};
+use serde::{Deserialize, Serialize};
+
+/// A remote image the proxy downloaded to pictrs, so that it's served from here.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone)]
+#[table_name = "image_proxy"]
+pub struct ImageProxy {
+  pub id: i32,
+  pub url: String,
+  pub file: String,
+  pub delete_token: String,
+  pub published: chrono::NaiveDateTime,
+  pub last_used: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "image_proxy"]
+pub struct ImageProxyForm {
+  pub url: String,
+  pub file: String,
+  pub delete_token: String,
+}
+
+impl ImageProxy {
+  /// Another request downloading the same image at the same time keeps the first file.
+  pub fn create(conn: &PgConnection, form: &ImageProxyForm) -> Result<Self, Error> {
+    use crate::schema::image_proxy::dsl::*;
+    insert_into(image_proxy)
+      .values(form)
+      .on_conflict(url)
+      .do_update()
+      .set(last_used.eq(naive_now()))
+      .get_result::<Self>(conn)
+  }
+
+  pub fn read_for_url(conn: &PgConnection, image_url: &str) -> Result<Option<Self>, Error> {
+    use crate::schema::image_proxy::dsl::*;
+    image_proxy
+      .filter(url.eq(image_url))
+      .first::<Self>(conn)
+      .optional()
+  }
+
+  pub fn mark_used(conn: &PgConnection, image_id: i32) -> Result<usize, Error> {
+    use crate::schema::image_proxy::dsl::*;
+    diesel::update(image_proxy.find(image_id))
+      .set(last_used.eq(naive_now()))
+      .execute(conn)
+  }
+
+  /// The least recently used images beyond the ones to keep.
+  pub fn list_beyond(conn: &PgConnection, keep: i64) -> Result<Vec<Self>, Error> {
+    use crate::schema::image_proxy::dsl::*;
+    image_proxy
+      .order_by(last_used.desc())
+      .offset(keep)
+      .load::<Self>(conn)
+  }
+
+  pub fn delete(conn: &PgConnection, image_id: i32) -> Result<usize, Error> {
+    use crate::schema::image_proxy::dsl::*;
+    diesel::delete(image_proxy.find(image_id)).execute(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tests::establish_unpooled_connection;
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let old_form = ImageProxyForm {
+      url: "https://fake.com/old.png".into(),
+      file: "old.png".into(),
+      delete_token: "old_token".into(),
+    };
+
+    let inserted_old = ImageProxy::create(&conn, &old_form).unwrap();
+
+    let new_form = ImageProxyForm {
+      url: "https://fake.com/new.png".into(),
+      file: "new.png".into(),
+      delete_token: "new_token".into(),
+    };
+
+    let inserted_new = ImageProxy::create(&conn, &new_form).unwrap();
+    let again = ImageProxy::create(&conn, &new_form).unwrap();
+    ImageProxy::mark_used(&conn, inserted_new.id).unwrap();
+
+    let read_image = ImageProxy::read_for_url(&conn, "https://fake.com/new.png").unwrap();
+    let missing = ImageProxy::read_for_url(&conn, "https://fake.com/missing.png").unwrap();
+    let beyond = ImageProxy::list_beyond(&conn, 1).unwrap();
+
+    let num_deleted = ImageProxy::delete(&conn, inserted_old.id).unwrap()
+      + ImageProxy::delete(&conn, inserted_new.id).unwrap();
+
+    assert_eq!(inserted_new.id, again.id);
+    assert_eq!(Some(inserted_new.id), read_image.map(|i| i.id));
+    assert!(missing.is_none());
+    assert_eq!(
+      vec![inserted_old.id],
+      beyond.iter().map(|i| i.id).collect::<Vec<i32>>()
+    );
+    assert_eq!(2, num_deleted);
+  }
+}
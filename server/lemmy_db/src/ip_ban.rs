@@ -0,0 +1,223 @@
+use crate::{
+  naive_now,
+  schema::{ip_ban, user_ip},
+  Crud,
+};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// An IP range that can't use the API. It's lifted once it expires, if it does.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "ip_ban"]
+pub struct IpBan {
+  pub id: i32,
+  pub ip_range: String,
+  pub reason: Option<String>,
+  pub creator_id: i32,
+  pub expires: Option<chrono::NaiveDateTime>,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "ip_ban"]
+pub struct IpBanForm {
+  pub ip_range: String,
+  pub reason: Option<String>,
+  pub creator_id: i32,
+  pub expires: Option<chrono::NaiveDateTime>,
+}
+
+/// An IP that a user registered or logged in from. Only a salted hash of it is kept.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone)]
+#[table_name = "user_ip"]
+pub struct UserIp {
+  pub id: i32,
+  pub user_id: i32,
+  pub ip_hash: String,
+  pub registration: bool,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "user_ip"]
+pub struct UserIpForm {
+  pub user_id: i32,
+  pub ip_hash: String,
+  pub registration: bool,
+}
+
+impl Crud<IpBanForm> for IpBan {
+  fn read(conn: &PgConnection, ip_ban_id: i32) -> Result<Self, Error> {
+    use crate::schema::ip_ban::dsl::*;
+    ip_ban.find(ip_ban_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, ip_ban_id: i32) -> Result<usize, Error> {
+    use crate::schema::ip_ban::dsl::*;
+    diesel::delete(ip_ban.find(ip_ban_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &IpBanForm) -> Result<Self, Error> {
+    use crate::schema::ip_ban::dsl::*;
+    insert_into(ip_ban).values(form).get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, ip_ban_id: i32, form: &IpBanForm) -> Result<Self, Error> {
+    use crate::schema::ip_ban::dsl::*;
+    diesel::update(ip_ban.find(ip_ban_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl IpBan {
+  /// Newest first, including the expired ones
+  pub fn list(conn: &PgConnection) -> Result<Vec<Self>, Error> {
+    use crate::schema::ip_ban::dsl::*;
+    ip_ban.order_by(published.desc()).load::<Self>(conn)
+  }
+
+  pub fn list_active(conn: &PgConnection) -> Result<Vec<Self>, Error> {
+    use crate::schema::ip_ban::dsl::*;
+    ip_ban
+      .filter(expires.is_null().or(expires.gt(naive_now())))
+      .load::<Self>(conn)
+  }
+}
+
+impl UserIp {
+  pub fn record(conn: &PgConnection, form: &UserIpForm) -> Result<Self, Error> {
+    use crate::schema::user_ip::dsl::*;
+    insert_into(user_ip).values(form).get_result::<Self>(conn)
+  }
+
+  pub fn count_registrations_since(
+    conn: &PgConnection,
+    for_ip_hash: &str,
+    since: chrono::NaiveDateTime,
+  ) -> Result<i64, Error> {
+    use crate::schema::user_ip::dsl::*;
+    user_ip
+      .filter(ip_hash.eq(for_ip_hash))
+      .filter(registration.eq(true))
+      .filter(published.gt(since))
+      .count()
+      .get_result::<i64>(conn)
+  }
+
+  /// Drops the IPs that were kept longer than the retention period.
+  pub fn prune_before(conn: &PgConnection, before: chrono::NaiveDateTime) -> Result<usize, Error> {
+    use crate::schema::user_ip::dsl::*;
+    diesel::delete(user_ip.filter(published.lt(before))).execute(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "ip_banner".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: true,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let ban_form = IpBanForm {
+      ip_range: "203.0.113.0/24".into(),
+      reason: Some("Spam".into()),
+      creator_id: inserted_user.id,
+      expires: None,
+    };
+
+    let inserted_ban = IpBan::create(&conn, &ban_form).unwrap();
+
+    let expired_form = IpBanForm {
+      ip_range: "198.51.100.7".into(),
+      reason: None,
+      creator_id: inserted_user.id,
+      expires: Some(naive_now() - chrono::Duration::days(1)),
+    };
+
+    let inserted_expired = IpBan::create(&conn, &expired_form).unwrap();
+
+    let expected_ban = IpBan {
+      id: inserted_ban.id,
+      ip_range: "203.0.113.0/24".into(),
+      reason: Some("Spam".into()),
+      creator_id: inserted_user.id,
+      expires: None,
+      published: inserted_ban.published,
+    };
+
+    let ip_form = UserIpForm {
+      user_id: inserted_user.id,
+      ip_hash: "test_ip_ban_hash".into(),
+      registration: true,
+    };
+
+    UserIp::record(&conn, &ip_form).unwrap();
+    UserIp::record(
+      &conn,
+      &UserIpForm {
+        registration: false,
+        ..ip_form.clone()
+      },
+    )
+    .unwrap();
+
+    let yesterday = naive_now() - chrono::Duration::days(1);
+    let registrations = UserIp::count_registrations_since(&conn, "test_ip_ban_hash", yesterday);
+    UserIp::prune_before(&conn, yesterday).unwrap();
+    let registrations_after_prune =
+      UserIp::count_registrations_since(&conn, "test_ip_ban_hash", yesterday);
+
+    let read_ban = IpBan::read(&conn, inserted_ban.id).unwrap();
+    let all_bans = IpBan::list(&conn).unwrap();
+    let active_bans = IpBan::list_active(&conn).unwrap();
+    let num_deleted = IpBan::delete(&conn, inserted_ban.id).unwrap();
+    IpBan::delete(&conn, inserted_expired.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(expected_ban, read_ban);
+    assert!(all_bans.contains(&inserted_ban));
+    assert!(all_bans.contains(&inserted_expired));
+    assert!(active_bans.contains(&inserted_ban));
+    assert!(!active_bans.contains(&inserted_expired));
+    assert_eq!(1, registrations.unwrap());
+    assert_eq!(1, registrations_after_prune.unwrap());
+    assert_eq!(1, num_deleted);
+  }
+}
@@ -0,0 +1,336 @@
+use crate::{naive_now, schema::job, Crud};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A piece of background work. The payload says what to do, and the kind is only there to tell
+/// the jobs apart in the queue's stats.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "job"]
+pub struct Job {
+  pub id: i32,
+  pub kind: String,
+  pub payload: String,
+  pub status: i16,
+  pub attempts: i32,
+  pub max_attempts: i32,
+  pub run_at: chrono::NaiveDateTime,
+  pub last_error: Option<String>,
+  pub published: chrono::NaiveDateTime,
+  pub updated: Option<chrono::NaiveDateTime>,
+  pub lease_expires: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "job"]
+pub struct JobForm {
+  pub kind: String,
+  pub payload: String,
+  pub max_attempts: i32,
+  pub run_at: chrono::NaiveDateTime,
+}
+
+/// Stored as an i16.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum JobStatus {
+  Queued,
+  Running,
+  Done,
+  /// Out of attempts
+  Failed,
+}
+
+impl JobStatus {
+  pub fn from_i16(value: i16) -> Option<Self> {
+    match value {
+      0 => Some(JobStatus::Queued),
+      1 => Some(JobStatus::Running),
+      2 => Some(JobStatus::Done),
+      3 => Some(JobStatus::Failed),
+      _ => None,
+    }
+  }
+}
+
+/// How many jobs of a kind are in a status.
+#[derive(QueryableByName, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct JobQueueStats {
+  #[sql_type = "sql_types::Text"]
+  pub kind: String,
+  #[sql_type = "sql_types::SmallInt"]
+  pub status: i16,
+  #[sql_type = "sql_types::BigInt"]
+  pub count: i64,
+}
+
+impl Crud<JobForm> for Job {
+  fn read(conn: &PgConnection, job_id: i32) -> Result<Self, Error> {
+    use crate::schema::job::dsl::*;
+    job.find(job_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, job_id: i32) -> Result<usize, Error> {
+    use crate::schema::job::dsl::*;
+    diesel::delete(job.find(job_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &JobForm) -> Result<Self, Error> {
+    use crate::schema::job::dsl::*;
+    insert_into(job).values(form).get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, job_id: i32, form: &JobForm) -> Result<Self, Error> {
+    use crate::schema::job::dsl::*;
+    diesel::update(job.find(job_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl Job {
+  pub fn create_many(conn: &PgConnection, forms: &[JobForm]) -> Result<usize, Error> {
+    use crate::schema::job::dsl::*;
+    insert_into(job).values(forms).execute(conn)
+  }
+
+  /// Marks the job that has been due the longest as running, held until `lease_until`, and returns
+  /// it. A running job whose lease ran out lost its worker, and is taken like a queued one. The
+  /// jobs other workers are claiming at the same time are skipped, so no job runs twice.
+  pub fn claim_next(
+    conn: &PgConnection,
+    lease_until: chrono::NaiveDateTime,
+  ) -> Result<Option<Self>, Error> {
+    use crate::schema::job::dsl::*;
+    conn.transaction::<_, Error, _>(|| {
+      let current_time = naive_now();
+      let due = status
+        .eq(JobStatus::Queued as i16)
+        .and(run_at.le(current_time));
+      let lost = status
+        .eq(JobStatus::Running as i16)
+        .and(lease_expires.lt(current_time));
+      let next_id = job
+        .select(id)
+        .filter(due.or(lost))
+        .order_by(run_at.asc())
+        .limit(1)
+        .for_update()
+        .skip_locked()
+        .first::<i32>(conn)
+        .optional()?;
+
+      match next_id {
+        Some(next_id) => diesel::update(job.find(next_id))
+          .set((
+            status.eq(JobStatus::Running as i16),
+            attempts.eq(attempts + 1),
+            lease_expires.eq(lease_until),
+            updated.eq(current_time),
+          ))
+          .get_result::<Self>(conn)
+          .map(Some),
+        None => Ok(None),
+      }
+    })
+  }
+
+  /// Holds on to a running job for longer, while its worker is still on it.
+  pub fn renew_lease(
+    conn: &PgConnection,
+    job_id: i32,
+    lease_until: chrono::NaiveDateTime,
+  ) -> Result<usize, Error> {
+    use crate::schema::job::dsl::*;
+    diesel::update(
+      job
+        .find(job_id)
+        .filter(status.eq(JobStatus::Running as i16)),
+    )
+    .set(lease_expires.eq(lease_until))
+    .execute(conn)
+  }
+
+  pub fn complete(conn: &PgConnection, job_id: i32) -> Result<Self, Error> {
+    use crate::schema::job::dsl::*;
+    diesel::update(job.find(job_id))
+      .set((
+        status.eq(JobStatus::Done as i16),
+        last_error.eq(None::<String>),
+        lease_expires.eq(None::<chrono::NaiveDateTime>),
+        updated.eq(naive_now()),
+      ))
+      .get_result::<Self>(conn)
+  }
+
+  /// Queues the job again for `retry_at`, or fails it for good once it's out of attempts.
+  pub fn fail(
+    conn: &PgConnection,
+    job_id: i32,
+    error: &str,
+    retry_at: chrono::NaiveDateTime,
+  ) -> Result<Self, Error> {
+    use crate::schema::job::dsl::*;
+    let failed_job = Self::read(conn, job_id)?;
+    let new_status = if failed_job.attempts >= failed_job.max_attempts {
+      JobStatus::Failed
+    } else {
+      JobStatus::Queued
+    };
+
+    diesel::update(job.find(job_id))
+      .set((
+        status.eq(new_status as i16),
+        last_error.eq(error),
+        run_at.eq(retry_at),
+        lease_expires.eq(None::<chrono::NaiveDateTime>),
+        updated.eq(naive_now()),
+      ))
+      .get_result::<Self>(conn)
+  }
+
+  /// Whether a job of the kind is waiting or running.
+  pub fn is_scheduled(conn: &PgConnection, for_kind: &str) -> Result<bool, Error> {
+    use crate::schema::job::dsl::*;
+    select(exists(
+      job.filter(kind.eq(for_kind)).filter(
+        status
+          .eq(JobStatus::Queued as i16)
+          .or(status.eq(JobStatus::Running as i16)),
+      ),
+    ))
+    .get_result::<bool>(conn)
+  }
+
+  /// Diesel 1.4 can't select a count next to the columns it groups by, so this is plain sql.
+  pub fn stats(conn: &PgConnection) -> Result<Vec<JobQueueStats>, Error> {
+    sql_query(
+      "select kind::text as kind, status, count(*) as count from job \
+      group by kind, status \
+      order by kind, status",
+    )
+    .load::<JobQueueStats>(conn)
+  }
+
+  /// When the job that has been due the longest was due, if any is.
+  pub fn oldest_due(conn: &PgConnection) -> Result<Option<chrono::NaiveDateTime>, Error> {
+    use crate::schema::job::dsl::*;
+    job
+      .select(run_at)
+      .filter(status.eq(JobStatus::Queued as i16))
+      .filter(run_at.le(naive_now()))
+      .order_by(run_at.asc())
+      .first::<chrono::NaiveDateTime>(conn)
+      .optional()
+  }
+
+  /// The last jobs that failed for good, newest first.
+  pub fn list_failed(conn: &PgConnection, limit: i64) -> Result<Vec<Self>, Error> {
+    use crate::schema::job::dsl::*;
+    job
+      .filter(status.eq(JobStatus::Failed as i16))
+      .order_by(updated.desc())
+      .limit(limit)
+      .load::<Self>(conn)
+  }
+
+  /// Drops the done and failed jobs last touched before the given time.
+  pub fn prune_finished_before(
+    conn: &PgConnection,
+    before: chrono::NaiveDateTime,
+  ) -> Result<usize, Error> {
+    use crate::schema::job::dsl::*;
+    diesel::delete(
+      job
+        .filter(
+          status
+            .eq(JobStatus::Done as i16)
+            .or(status.eq(JobStatus::Failed as i16)),
+        )
+        .filter(updated.lt(before)),
+    )
+    .execute(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tests::establish_unpooled_connection;
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let job_form = JobForm {
+      kind: "TestJob".into(),
+      payload: "{}".into(),
+      max_attempts: 3,
+      run_at: naive_now() - chrono::Duration::days(365),
+    };
+
+    let inserted_job = Job::create(&conn, &job_form).unwrap();
+
+    let expected_job = Job {
+      id: inserted_job.id,
+      kind: "TestJob".into(),
+      payload: "{}".into(),
+      status: JobStatus::Queued as i16,
+      attempts: 0,
+      max_attempts: 3,
+      run_at: inserted_job.run_at,
+      last_error: None,
+      published: inserted_job.published,
+      updated: None,
+      lease_expires: None,
+    };
+
+    let claimed_job = Job::claim_next(&conn, naive_now() - chrono::Duration::minutes(1))
+      .unwrap()
+      .unwrap();
+    let scheduled = Job::is_scheduled(&conn, "TestJob").unwrap();
+    // Its lease ran out, as if its worker went away
+    let reclaimed_job = Job::claim_next(&conn, naive_now() + chrono::Duration::minutes(1))
+      .unwrap()
+      .unwrap();
+    let renewed = Job::renew_lease(
+      &conn,
+      inserted_job.id,
+      naive_now() + chrono::Duration::minutes(1),
+    )
+    .unwrap();
+    let held_job = Job::claim_next(&conn, naive_now()).unwrap();
+    let retried_job = Job::fail(&conn, inserted_job.id, "unreachable", naive_now()).unwrap();
+    Job::claim_next(&conn, naive_now()).unwrap();
+    let failed_job = Job::fail(&conn, inserted_job.id, "unreachable", naive_now()).unwrap();
+    let stats = Job::stats(&conn).unwrap();
+    let failed_jobs = Job::list_failed(&conn, 10).unwrap();
+    let not_pruned =
+      Job::prune_finished_before(&conn, naive_now() - chrono::Duration::days(1)).unwrap();
+    let pruned = Job::prune_finished_before(&conn, naive_now()).unwrap();
+    let scheduled_after = Job::is_scheduled(&conn, "TestJob").unwrap();
+
+    assert_eq!(expected_job, inserted_job);
+    assert_eq!(inserted_job.id, claimed_job.id);
+    assert_eq!(JobStatus::Running as i16, claimed_job.status);
+    assert_eq!(1, claimed_job.attempts);
+    assert!(scheduled);
+    assert_eq!(inserted_job.id, reclaimed_job.id);
+    assert_eq!(2, reclaimed_job.attempts);
+    assert_eq!(1, renewed);
+    assert!(held_job.is_none());
+    assert_eq!(JobStatus::Queued as i16, retried_job.status);
+    assert_eq!(None, retried_job.lease_expires);
+    assert_eq!(Some("unreachable".to_string()), retried_job.last_error);
+    assert_eq!(JobStatus::Failed as i16, failed_job.status);
+    assert_eq!(3, failed_job.attempts);
+    assert!(stats.contains(&JobQueueStats {
+      kind: "TestJob".into(),
+      status: JobStatus::Failed as i16,
+      count: 1,
+    }));
+    assert_eq!(inserted_job.id, failed_jobs[0].id);
+    assert_eq!(0, not_pruned);
+    assert_eq!(1, pruned);
+    assert!(!scheduled_after);
+  }
+}
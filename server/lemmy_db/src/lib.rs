@@ -13,28 +13,70 @@ pub extern crate strum;
 use chrono::NaiveDateTime;
 use diesel::{dsl::*, result::Error, *};
 use serde::{Deserialize, Serialize};
-use std::{env, env::VarError};
+use std::{
+  env,
+  env::VarError,
+  sync::atomic::{AtomicBool, Ordering},
+};
 
 pub mod activity;
+pub mod appeal;
+pub mod automod;
+pub mod blocked_domain;
+pub mod bulk_moderation;
+pub mod captcha_answer;
 pub mod category;
 pub mod comment;
 pub mod comment_view;
 pub mod community;
+pub mod community_flair;
+pub mod community_import;
+pub mod community_rule;
+pub mod community_takeover;
 pub mod community_view;
+pub mod community_wiki;
+pub mod custom_feed;
+pub mod email_domain;
+pub mod email_verification;
+pub mod image_proxy;
+pub mod ip_ban;
+pub mod job;
+pub mod media;
+pub mod mod_note;
 pub mod moderator;
 pub mod moderator_views;
+pub mod oauth;
 pub mod password_reset_request;
 pub mod post;
 pub mod post_view;
+pub mod posting_requirement;
+pub mod purge;
 pub mod private_message;
+pub mod private_message_conversation_view;
 pub mod private_message_view;
+pub mod rate_limit;
+pub mod report;
+pub mod report_view;
+pub mod scheduled_post;
 pub mod schema;
 pub mod site;
+pub mod site_announcement;
+pub mod site_document;
 pub mod site_view;
+pub mod sitemap;
 pub mod user;
+pub mod user_alias;
+pub mod user_export;
+pub mod user_matrix_room;
 pub mod user_mention;
 pub mod user_mention_view;
+pub mod user_muted_keyword;
+pub mod user_notification_settings;
+pub mod user_push_subscription;
+pub mod user_stats;
 pub mod user_view;
+pub mod webhook;
+pub mod word_filter;
 
 pub trait Crud<T> {
   fn create(conn: &PgConnection, form: &T) -> Result<Self, Error>
@@ -99,6 +141,15 @@ pub trait Saveable<T> {
     Self: Sized;
 }
 
+pub trait Hideable<T> {
+  fn hide(conn: &PgConnection, form: &T) -> Result<Self, Error>
+  where
+    Self: Sized;
+  fn unhide(conn: &PgConnection, form: &T) -> Result<usize, Error>
+  where
+    Self: Sized;
+}
+
 pub trait Readable<T> {
   fn mark_as_read(conn: &PgConnection, form: &T) -> Result<Self, Error>
   where
@@ -137,6 +188,11 @@ pub enum SortType {
   TopMonth,
   TopYear,
   TopAll,
+  Controversial,
+  TopHour,
+  TopSixHour,
+  TopTwelveHour,
+  MostComments,
 }
 
 #[derive(EnumString, ToString, Debug, Serialize, Deserialize)]
@@ -156,11 +212,35 @@ pub enum SearchType {
   Url,
 }
 
+#[derive(EnumString, ToString, Debug, Serialize, Deserialize, PartialEq)]
+pub enum InboxType {
+  All,
+  Replies,
+  Mentions,
+  Messages,
+}
+
+// The postgres function used for the Controversial sort
+sql_function!(
+  fn controversy_rank(upvotes: sql_types::BigInt, downvotes: sql_types::BigInt) -> sql_types::Double
+);
+
+// Names are compared case insensitively, like the lower(name) unique indexes do
+sql_function!(fn lower(x: sql_types::Text) -> sql_types::Text);
+
 pub fn fuzzy_search(q: &str) -> String {
   let replaced = q.replace(" ", "%");
   format!("%{}%", replaced)
 }
 
+/// Strips everything that can't be in a domain name, including the like wildcards
+pub fn clean_url_domain(domain: &str) -> String {
+  domain
+    .trim()
+    .to_lowercase()
+    .replace(|c: char| !(c.is_alphanumeric() || c == '.' || c == '-'), "")
+}
+
 pub fn limit_and_offset(page: Option<i64>, limit: Option<i64>) -> (i64, i64) {
   let page = page.unwrap_or(1);
   let limit = limit.unwrap_or(10);
@@ -168,13 +248,150 @@ pub fn limit_and_offset(page: Option<i64>, limit: Option<i64>) -> (i64, i64) {
   (limit, offset)
 }
 
+/// Whether the site hides how many downvotes the posts and comments got
+static HIDE_DOWNVOTE_COUNTS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_hide_downvote_counts(hide: bool) {
+  HIDE_DOWNVOTE_COUNTS.store(hide, Ordering::Relaxed);
+}
+
+/// For `skip_serializing_if` on the vote counts. The upvotes go too, or the downvotes would be
+/// their difference with the score.
+pub fn vote_counts_hidden<T>(_: &T) -> bool {
+  HIDE_DOWNVOTE_COUNTS.load(Ordering::Relaxed)
+}
+
+/// Where a page of posts or comments ended, so the next one can pick up right after it. Unlike an
+/// offset, items added in the meantime don't push the same ones onto the next page, and deep pages
+/// cost no more than the first. `pinned` is the stickied flag of posts and the pinned one of
+/// comments.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ListingCursor {
+  pub pinned: bool,
+  pub hot_rank: i32,
+  pub score: i64,
+  pub upvotes: i64,
+  pub downvotes: i64,
+  pub number_of_comments: i64,
+  pub published: NaiveDateTime,
+  pub id: i32,
+}
+
+/// Keeps the items that sort after the cursor. The listing has to be ordered by the pinned column
+/// when there is one, then by the sort, and finally by id, all descending.
+pub fn keyset_filter<QS>(
+  table: &str,
+  pinned_column: Option<&str>,
+  sort: &SortType,
+  cursor: &ListingCursor,
+) -> Box<dyn BoxableExpression<QS, pg::Pg, SqlType = sql_types::Bool>> {
+  // Without a pinned column, both sides get the same constant so it never decides
+  let (pinned, cursor_pinned) = match pinned_column {
+    Some(column) => (format!("{}.{}", table, column), cursor.pinned),
+    None => ("false".to_string(), false),
+  };
+
+  match sort {
+    SortType::Hot => Box::new(
+      sql::<sql_types::Bool>(&format!(
+        "({}, {t}.hot_rank, {t}.published, {t}.id) < (",
+        pinned,
+        t = table
+      ))
+      .bind::<sql_types::Bool, _>(cursor_pinned)
+      .sql(", ")
+      .bind::<sql_types::Integer, _>(cursor.hot_rank)
+      .sql(", ")
+      .bind::<sql_types::Timestamp, _>(cursor.published)
+      .sql(", ")
+      .bind::<sql_types::Integer, _>(cursor.id)
+      .sql(")"),
+    ),
+    SortType::New => Box::new(
+      sql::<sql_types::Bool>(&format!(
+        "({}, {t}.published, {t}.id) < (",
+        pinned,
+        t = table
+      ))
+      .bind::<sql_types::Bool, _>(cursor_pinned)
+      .sql(", ")
+      .bind::<sql_types::Timestamp, _>(cursor.published)
+      .sql(", ")
+      .bind::<sql_types::Integer, _>(cursor.id)
+      .sql(")"),
+    ),
+    SortType::Controversial => Box::new(
+      sql::<sql_types::Bool>(&format!(
+        "({}, controversy_rank({t}.upvotes, {t}.downvotes), {t}.published, {t}.id) < (",
+        pinned,
+        t = table
+      ))
+      .bind::<sql_types::Bool, _>(cursor_pinned)
+      .sql(", controversy_rank(")
+      .bind::<sql_types::BigInt, _>(cursor.upvotes)
+      .sql(", ")
+      .bind::<sql_types::BigInt, _>(cursor.downvotes)
+      .sql("), ")
+      .bind::<sql_types::Timestamp, _>(cursor.published)
+      .sql(", ")
+      .bind::<sql_types::Integer, _>(cursor.id)
+      .sql(")"),
+    ),
+    SortType::MostComments => Box::new(
+      sql::<sql_types::Bool>(&format!(
+        "({}, {t}.number_of_comments, {t}.published, {t}.id) < (",
+        pinned,
+        t = table
+      ))
+      .bind::<sql_types::Bool, _>(cursor_pinned)
+      .sql(", ")
+      .bind::<sql_types::BigInt, _>(cursor.number_of_comments)
+      .sql(", ")
+      .bind::<sql_types::Timestamp, _>(cursor.published)
+      .sql(", ")
+      .bind::<sql_types::Integer, _>(cursor.id)
+      .sql(")"),
+    ),
+    // The top sorts only differ in how far back they go
+    SortType::TopAll
+    | SortType::TopYear
+    | SortType::TopMonth
+    | SortType::TopWeek
+    | SortType::TopDay
+    | SortType::TopTwelveHour
+    | SortType::TopSixHour
+    | SortType::TopHour => Box::new(
+      sql::<sql_types::Bool>(&format!("({}, {t}.score, {t}.id) < (", pinned, t = table))
+        .bind::<sql_types::Bool, _>(cursor_pinned)
+        .sql(", ")
+        .bind::<sql_types::BigInt, _>(cursor.score)
+        .sql(", ")
+        .bind::<sql_types::Integer, _>(cursor.id)
+        .sql(")"),
+    ),
+  }
+}
+
 pub fn naive_now() -> NaiveDateTime {
   chrono::prelude::Utc::now().naive_utc()
 }
 
+#[derive(QueryableByName)]
+struct MigrationVersion {
+  #[sql_type = "sql_types::Nullable<sql_types::Text>"]
+  version: Option<String>,
+}
+
+/// The version of the newest migration that was run on the database, if any was.
+pub fn newest_migration(conn: &PgConnection) -> Result<Option<String>, Error> {
+  sql_query("select max(version) as version from __diesel_schema_migrations")
+    .get_result::<MigrationVersion>(conn)
+    .map(|migration| migration.version)
+}
+
 #[cfg(test)]
 mod tests {
-  use super::fuzzy_search;
+  use super::{clean_url_domain, fuzzy_search, newest_migration};
   use crate::get_database_url_from_env;
   use diesel::{Connection, PgConnection};
 
@@ -194,4 +411,16 @@ mod tests {
     let test = "This is a fuzzy search";
     assert_eq!(fuzzy_search(test), "%This%is%a%fuzzy%search%".to_string());
   }
+
+  #[test]
+  fn test_clean_url_domain() {
+    assert_eq!(clean_url_domain(" Example.com"), "example.com".to_string());
+    assert_eq!(clean_url_domain("a%b_c.org"), "abc.org".to_string());
+  }
+
+  #[test]
+  fn test_newest_migration() {
+    let conn = establish_unpooled_connection();
+    assert!(newest_migration(&conn).unwrap().is_some());
+  }
 }
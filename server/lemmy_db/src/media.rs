@@ -0,0 +1,168 @@
+use crate::schema::media;
+use diesel::{dsl::*, result::Error, *};
+use serde::{Deserialize, Serialize};
+
+/// An image a user uploaded to pictrs, with the token that deletes it again.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone)]
+#[table_name = "media"]
+pub struct Media {
+  pub id: i32,
+  pub user_id: i32,
+  pub file: String,
+  pub delete_token: String,
+  pub published: chrono::NaiveDateTime,
+  /// What it was sniffed as on upload. Unknown for the older images
+  pub content_type: Option<String>,
+  /// What the NSFW classifier made of it, from 0 to 1
+  pub nsfw_score: Option<f32>,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "media"]
+pub struct MediaForm {
+  pub user_id: i32,
+  pub file: String,
+  pub delete_token: String,
+  pub content_type: Option<String>,
+  pub nsfw_score: Option<f32>,
+}
+
+impl Media {
+  pub fn create(conn: &PgConnection, form: &MediaForm) -> Result<Self, Error> {
+    use crate::schema::media::dsl::*;
+    insert_into(media).values(form).get_result::<Self>(conn)
+  }
+
+  pub fn read_for_file(conn: &PgConnection, media_file: &str) -> Result<Self, Error> {
+    use crate::schema::media::dsl::*;
+    media.filter(file.eq(media_file)).first::<Self>(conn)
+  }
+
+  pub fn for_user(conn: &PgConnection, for_user_id: i32) -> Result<Vec<Self>, Error> {
+    use crate::schema::media::dsl::*;
+    media
+      .filter(user_id.eq(for_user_id))
+      .order_by(published.desc())
+      .load::<Self>(conn)
+  }
+
+  /// How many images the user uploaded since then, for the daily quota
+  pub fn count_for_user_since(
+    conn: &PgConnection,
+    for_user_id: i32,
+    since: chrono::NaiveDateTime,
+  ) -> Result<i64, Error> {
+    use crate::schema::media::dsl::*;
+    media
+      .filter(user_id.eq(for_user_id))
+      .filter(published.gt(since))
+      .count()
+      .get_result(conn)
+  }
+
+  /// The ones of these files the user uploaded. Others' images can be linked, but stay theirs.
+  pub fn for_user_files(
+    conn: &PgConnection,
+    for_user_id: i32,
+    files: Vec<String>,
+  ) -> Result<Vec<Self>, Error> {
+    use crate::schema::media::dsl::*;
+    media
+      .filter(user_id.eq(for_user_id))
+      .filter(file.eq_any(files))
+      .load::<Self>(conn)
+  }
+
+  pub fn delete(conn: &PgConnection, media_id: i32) -> Result<usize, Error> {
+    use crate::schema::media::dsl::*;
+    diesel::delete(media.find(media_id)).execute(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{tests::establish_unpooled_connection, user::*, Crud, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "uploader".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let media_form = MediaForm {
+      user_id: inserted_user.id,
+      file: "upload.png".into(),
+      delete_token: "token".into(),
+      content_type: Some("video/mp4".into()),
+      nsfw_score: Some(0.25),
+    };
+
+    let inserted_media = Media::create(&conn, &media_form).unwrap();
+
+    let expected_media = Media {
+      id: inserted_media.id,
+      user_id: inserted_user.id,
+      file: "upload.png".into(),
+      delete_token: "token".into(),
+      published: inserted_media.published,
+      content_type: Some("video/mp4".into()),
+      nsfw_score: Some(0.25),
+    };
+
+    let read_media = Media::read_for_file(&conn, "upload.png").unwrap();
+    let user_media = Media::for_user(&conn, inserted_user.id).unwrap();
+    let uploaded_today = Media::count_for_user_since(
+      &conn,
+      inserted_user.id,
+      inserted_media.published - chrono::Duration::days(1),
+    )
+    .unwrap();
+    let linked_media = Media::for_user_files(
+      &conn,
+      inserted_user.id,
+      vec!["upload.png".into(), "someone_elses.png".into()],
+    )
+    .unwrap();
+    let num_deleted = Media::delete(&conn, inserted_media.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(expected_media, inserted_media);
+    assert_eq!(expected_media, read_media);
+    assert_eq!(vec![expected_media.clone()], user_media);
+    assert_eq!(1, uploaded_today);
+    assert_eq!(vec![expected_media], linked_media);
+    assert_eq!(1, num_deleted);
+  }
+}
@@ -0,0 +1,195 @@
+use crate::{limit_and_offset, schema::mod_note, Crud};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "mod_note"]
+pub struct ModNote {
+  pub id: i32,
+  pub community_id: i32,
+  pub user_id: i32,
+  pub creator_id: i32,
+  pub content: String,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "mod_note"]
+pub struct ModNoteForm {
+  pub community_id: i32,
+  pub user_id: i32,
+  pub creator_id: i32,
+  pub content: String,
+}
+
+impl Crud<ModNoteForm> for ModNote {
+  fn read(conn: &PgConnection, mod_note_id: i32) -> Result<Self, Error> {
+    use crate::schema::mod_note::dsl::*;
+    mod_note.find(mod_note_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, mod_note_id: i32) -> Result<usize, Error> {
+    use crate::schema::mod_note::dsl::*;
+    diesel::delete(mod_note.find(mod_note_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &ModNoteForm) -> Result<Self, Error> {
+    use crate::schema::mod_note::dsl::*;
+    insert_into(mod_note).values(form).get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, mod_note_id: i32, form: &ModNoteForm) -> Result<Self, Error> {
+    use crate::schema::mod_note::dsl::*;
+    diesel::update(mod_note.find(mod_note_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl ModNote {
+  /// The notes about a user, newest first. Passing no communities gives the notes of every
+  /// community.
+  pub fn for_user(
+    conn: &PgConnection,
+    for_user_id: i32,
+    for_community_ids: Option<Vec<i32>>,
+    page: Option<i64>,
+    limit: Option<i64>,
+  ) -> Result<Vec<Self>, Error> {
+    use crate::schema::mod_note::dsl::*;
+    let mut query = mod_note.filter(user_id.eq(for_user_id)).into_boxed();
+
+    if let Some(for_community_ids) = for_community_ids {
+      query = query.filter(community_id.eq_any(for_community_ids));
+    };
+
+    let (limit, offset) = limit_and_offset(page, limit);
+
+    query
+      .limit(limit)
+      .offset(offset)
+      .order_by(published.desc())
+      .load::<Self>(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{community::*, tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_mod = UserForm {
+      name: "note_taker".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_mod = User_::create(&conn, &new_mod).unwrap();
+
+    let new_user = UserForm {
+      name: "noted_user".into(),
+      ..new_mod
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_note_community".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_mod.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_note = ModNoteForm {
+      community_id: inserted_community.id,
+      user_id: inserted_user.id,
+      creator_id: inserted_mod.id,
+      content: "Warned about spamming links".into(),
+    };
+
+    let inserted_note = ModNote::create(&conn, &new_note).unwrap();
+
+    let expected_note = ModNote {
+      id: inserted_note.id,
+      community_id: inserted_community.id,
+      user_id: inserted_user.id,
+      creator_id: inserted_mod.id,
+      content: "Warned about spamming links".into(),
+      published: inserted_note.published,
+    };
+
+    let read_note = ModNote::read(&conn, inserted_note.id).unwrap();
+    let community_notes = ModNote::for_user(
+      &conn,
+      inserted_user.id,
+      Some(vec![inserted_community.id]),
+      None,
+      None,
+    )
+    .unwrap();
+    let other_community_notes =
+      ModNote::for_user(&conn, inserted_user.id, Some(vec![]), None, None).unwrap();
+    let num_deleted = ModNote::delete(&conn, inserted_note.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+    User_::delete(&conn, inserted_mod.id).unwrap();
+
+    assert_eq!(expected_note, read_note);
+    assert_eq!(expected_note, inserted_note);
+    assert_eq!(vec![expected_note], community_notes);
+    assert!(other_community_notes.is_empty());
+    assert_eq!(1, num_deleted);
+  }
+}
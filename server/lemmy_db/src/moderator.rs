@@ -5,10 +5,16 @@ use crate::{
     mod_ban,
     mod_ban_from_community,
     mod_lock_post,
+    mod_nsfw_post,
+    mod_purge,
     mod_remove_comment,
     mod_remove_community,
     mod_remove_post,
+    mod_resolve_comment_report,
+    mod_resolve_post_report,
+    mod_shadow_ban,
     mod_sticky_post,
+    mod_transfer_community,
   },
   Crud,
 };
@@ -435,6 +441,306 @@ impl Crud<ModAddForm> for ModAdd {
   }
 }
 
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize)]
+#[table_name = "mod_resolve_post_report"]
+pub struct ModResolvePostReport {
+  pub id: i32,
+  pub mod_user_id: i32,
+  pub report_id: i32,
+  pub resolved: Option<bool>,
+  pub when_: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone, Serialize, Deserialize)]
+#[table_name = "mod_resolve_post_report"]
+pub struct ModResolvePostReportForm {
+  pub mod_user_id: i32,
+  pub report_id: i32,
+  pub resolved: Option<bool>,
+}
+
+impl Crud<ModResolvePostReportForm> for ModResolvePostReport {
+  fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
+    use crate::schema::mod_resolve_post_report::dsl::*;
+    mod_resolve_post_report.find(from_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, from_id: i32) -> Result<usize, Error> {
+    use crate::schema::mod_resolve_post_report::dsl::*;
+    diesel::delete(mod_resolve_post_report.find(from_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &ModResolvePostReportForm) -> Result<Self, Error> {
+    use crate::schema::mod_resolve_post_report::dsl::*;
+    insert_into(mod_resolve_post_report)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    from_id: i32,
+    form: &ModResolvePostReportForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::mod_resolve_post_report::dsl::*;
+    diesel::update(mod_resolve_post_report.find(from_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize)]
+#[table_name = "mod_resolve_comment_report"]
+pub struct ModResolveCommentReport {
+  pub id: i32,
+  pub mod_user_id: i32,
+  pub report_id: i32,
+  pub resolved: Option<bool>,
+  pub when_: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone, Serialize, Deserialize)]
+#[table_name = "mod_resolve_comment_report"]
+pub struct ModResolveCommentReportForm {
+  pub mod_user_id: i32,
+  pub report_id: i32,
+  pub resolved: Option<bool>,
+}
+
+impl Crud<ModResolveCommentReportForm> for ModResolveCommentReport {
+  fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
+    use crate::schema::mod_resolve_comment_report::dsl::*;
+    mod_resolve_comment_report.find(from_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, from_id: i32) -> Result<usize, Error> {
+    use crate::schema::mod_resolve_comment_report::dsl::*;
+    diesel::delete(mod_resolve_comment_report.find(from_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &ModResolveCommentReportForm) -> Result<Self, Error> {
+    use crate::schema::mod_resolve_comment_report::dsl::*;
+    insert_into(mod_resolve_comment_report)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    from_id: i32,
+    form: &ModResolveCommentReportForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::mod_resolve_comment_report::dsl::*;
+    diesel::update(mod_resolve_comment_report.find(from_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize)]
+#[table_name = "mod_shadow_ban"]
+pub struct ModShadowBan {
+  pub id: i32,
+  pub mod_user_id: i32,
+  pub other_user_id: i32,
+  pub reason: Option<String>,
+  pub shadow_banned: Option<bool>,
+  pub when_: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone, Serialize, Deserialize)]
+#[table_name = "mod_shadow_ban"]
+pub struct ModShadowBanForm {
+  pub mod_user_id: i32,
+  pub other_user_id: i32,
+  pub reason: Option<String>,
+  pub shadow_banned: Option<bool>,
+}
+
+impl Crud<ModShadowBanForm> for ModShadowBan {
+  fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
+    use crate::schema::mod_shadow_ban::dsl::*;
+    mod_shadow_ban.find(from_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, from_id: i32) -> Result<usize, Error> {
+    use crate::schema::mod_shadow_ban::dsl::*;
+    diesel::delete(mod_shadow_ban.find(from_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &ModShadowBanForm) -> Result<Self, Error> {
+    use crate::schema::mod_shadow_ban::dsl::*;
+    insert_into(mod_shadow_ban)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, from_id: i32, form: &ModShadowBanForm) -> Result<Self, Error> {
+    use crate::schema::mod_shadow_ban::dsl::*;
+    diesel::update(mod_shadow_ban.find(from_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+/// What a purge hard deleted. Nothing else about it is kept.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum PurgeTargetType {
+  User,
+  Post,
+  Comment,
+  Community,
+}
+
+impl PurgeTargetType {
+  pub fn from_i16(target_type: i16) -> Option<Self> {
+    match target_type {
+      0 => Some(PurgeTargetType::User),
+      1 => Some(PurgeTargetType::Post),
+      2 => Some(PurgeTargetType::Comment),
+      3 => Some(PurgeTargetType::Community),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize)]
+#[table_name = "mod_purge"]
+pub struct ModPurge {
+  pub id: i32,
+  pub mod_user_id: i32,
+  pub target_type: i16,
+  pub community_id: Option<i32>,
+  pub reason: Option<String>,
+  pub when_: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone, Serialize, Deserialize)]
+#[table_name = "mod_purge"]
+pub struct ModPurgeForm {
+  pub mod_user_id: i32,
+  pub target_type: i16,
+  pub community_id: Option<i32>,
+  pub reason: Option<String>,
+}
+
+impl Crud<ModPurgeForm> for ModPurge {
+  fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
+    use crate::schema::mod_purge::dsl::*;
+    mod_purge.find(from_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, from_id: i32) -> Result<usize, Error> {
+    use crate::schema::mod_purge::dsl::*;
+    diesel::delete(mod_purge.find(from_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &ModPurgeForm) -> Result<Self, Error> {
+    use crate::schema::mod_purge::dsl::*;
+    insert_into(mod_purge).values(form).get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, from_id: i32, form: &ModPurgeForm) -> Result<Self, Error> {
+    use crate::schema::mod_purge::dsl::*;
+    diesel::update(mod_purge.find(from_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize)]
+#[table_name = "mod_transfer_community"]
+pub struct ModTransferCommunity {
+  pub id: i32,
+  pub mod_user_id: i32,
+  pub other_user_id: i32,
+  pub community_id: i32,
+  pub when_: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone, Serialize, Deserialize)]
+#[table_name = "mod_transfer_community"]
+pub struct ModTransferCommunityForm {
+  pub mod_user_id: i32,
+  pub other_user_id: i32,
+  pub community_id: i32,
+}
+
+impl Crud<ModTransferCommunityForm> for ModTransferCommunity {
+  fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
+    use crate::schema::mod_transfer_community::dsl::*;
+    mod_transfer_community.find(from_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, from_id: i32) -> Result<usize, Error> {
+    use crate::schema::mod_transfer_community::dsl::*;
+    diesel::delete(mod_transfer_community.find(from_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &ModTransferCommunityForm) -> Result<Self, Error> {
+    use crate::schema::mod_transfer_community::dsl::*;
+    insert_into(mod_transfer_community)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    from_id: i32,
+    form: &ModTransferCommunityForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::mod_transfer_community::dsl::*;
+    diesel::update(mod_transfer_community.find(from_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+/// A mod marking a post NSFW, or safe against what the classifier made of its image.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize)]
+#[table_name = "mod_nsfw_post"]
+pub struct ModNsfwPost {
+  pub id: i32,
+  pub mod_user_id: i32,
+  pub post_id: i32,
+  pub nsfw: Option<bool>,
+  pub when_: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone, Serialize, Deserialize)]
+#[table_name = "mod_nsfw_post"]
+pub struct ModNsfwPostForm {
+  pub mod_user_id: i32,
+  pub post_id: i32,
+  pub nsfw: Option<bool>,
+}
+
+impl Crud<ModNsfwPostForm> for ModNsfwPost {
+  fn read(conn: &PgConnection, from_id: i32) -> Result<Self, Error> {
+    use crate::schema::mod_nsfw_post::dsl::*;
+    mod_nsfw_post.find(from_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, from_id: i32) -> Result<usize, Error> {
+    use crate::schema::mod_nsfw_post::dsl::*;
+    diesel::delete(mod_nsfw_post.find(from_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &ModNsfwPostForm) -> Result<Self, Error> {
+    use crate::schema::mod_nsfw_post::dsl::*;
+    insert_into(mod_nsfw_post)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, from_id: i32, form: &ModNsfwPostForm) -> Result<Self, Error> {
+    use crate::schema::mod_nsfw_post::dsl::*;
+    diesel::update(mod_nsfw_post.find(from_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use crate::{
@@ -476,6 +782,12 @@ mod tests {
       private_key: None,
       public_key: None,
       last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
     };
 
     let inserted_mod = User_::create(&conn, &new_mod).unwrap();
@@ -503,6 +815,12 @@ mod tests {
       private_key: None,
       public_key: None,
       last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
     };
 
     let inserted_user = User_::create(&conn, &new_user).unwrap();
@@ -523,6 +841,15 @@ mod tests {
       public_key: None,
       last_refreshed_at: None,
       published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
     };
 
     let inserted_community = Community::create(&conn, &new_community).unwrap();
@@ -542,10 +869,13 @@ mod tests {
       embed_title: None,
       embed_description: None,
       embed_html: None,
+      embed_type: None,
+      url_media_type: None,
       thumbnail_url: None,
       ap_id: "http://fake.com".into(),
       local: true,
       published: None,
+      pending: None,
     };
 
     let inserted_post = Post::create(&conn, &new_post).unwrap();
@@ -748,6 +1078,80 @@ mod tests {
       when_: inserted_mod_add.when_,
     };
 
+    // shadow ban
+
+    let mod_shadow_ban_form = ModShadowBanForm {
+      mod_user_id: inserted_mod.id,
+      other_user_id: inserted_user.id,
+      reason: None,
+      shadow_banned: None,
+    };
+    let inserted_mod_shadow_ban = ModShadowBan::create(&conn, &mod_shadow_ban_form).unwrap();
+    let read_mod_shadow_ban = ModShadowBan::read(&conn, inserted_mod_shadow_ban.id).unwrap();
+    let expected_mod_shadow_ban = ModShadowBan {
+      id: inserted_mod_shadow_ban.id,
+      mod_user_id: inserted_mod.id,
+      other_user_id: inserted_user.id,
+      reason: None,
+      shadow_banned: Some(true),
+      when_: inserted_mod_shadow_ban.when_,
+    };
+
+    // purge
+
+    let mod_purge_form = ModPurgeForm {
+      mod_user_id: inserted_mod.id,
+      target_type: PurgeTargetType::Post as i16,
+      community_id: Some(inserted_community.id),
+      reason: None,
+    };
+    let inserted_mod_purge = ModPurge::create(&conn, &mod_purge_form).unwrap();
+    let read_mod_purge = ModPurge::read(&conn, inserted_mod_purge.id).unwrap();
+    let expected_mod_purge = ModPurge {
+      id: inserted_mod_purge.id,
+      mod_user_id: inserted_mod.id,
+      target_type: PurgeTargetType::Post as i16,
+      community_id: Some(inserted_community.id),
+      reason: None,
+      when_: inserted_mod_purge.when_,
+    };
+
+    // transfer community
+
+    let mod_transfer_community_form = ModTransferCommunityForm {
+      mod_user_id: inserted_mod.id,
+      other_user_id: inserted_user.id,
+      community_id: inserted_community.id,
+    };
+    let inserted_mod_transfer_community =
+      ModTransferCommunity::create(&conn, &mod_transfer_community_form).unwrap();
+    let read_mod_transfer_community =
+      ModTransferCommunity::read(&conn, inserted_mod_transfer_community.id).unwrap();
+    let expected_mod_transfer_community = ModTransferCommunity {
+      id: inserted_mod_transfer_community.id,
+      mod_user_id: inserted_mod.id,
+      other_user_id: inserted_user.id,
+      community_id: inserted_community.id,
+      when_: inserted_mod_transfer_community.when_,
+    };
+
+    // nsfw post
+
+    let mod_nsfw_post_form = ModNsfwPostForm {
+      mod_user_id: inserted_mod.id,
+      post_id: inserted_post.id,
+      nsfw: Some(false),
+    };
+    let inserted_mod_nsfw_post = ModNsfwPost::create(&conn, &mod_nsfw_post_form).unwrap();
+    let read_mod_nsfw_post = ModNsfwPost::read(&conn, inserted_mod_nsfw_post.id).unwrap();
+    let expected_mod_nsfw_post = ModNsfwPost {
+      id: inserted_mod_nsfw_post.id,
+      mod_user_id: inserted_mod.id,
+      post_id: inserted_post.id,
+      nsfw: Some(false),
+      when_: inserted_mod_nsfw_post.when_,
+    };
+
     ModRemovePost::delete(&conn, inserted_mod_remove_post.id).unwrap();
     ModLockPost::delete(&conn, inserted_mod_lock_post.id).unwrap();
     ModStickyPost::delete(&conn, inserted_mod_sticky_post.id).unwrap();
@@ -757,6 +1161,10 @@ mod tests {
     ModBan::delete(&conn, inserted_mod_ban.id).unwrap();
     ModAddCommunity::delete(&conn, inserted_mod_add_community.id).unwrap();
     ModAdd::delete(&conn, inserted_mod_add.id).unwrap();
+    ModShadowBan::delete(&conn, inserted_mod_shadow_ban.id).unwrap();
+    ModPurge::delete(&conn, inserted_mod_purge.id).unwrap();
+    ModTransferCommunity::delete(&conn, inserted_mod_transfer_community.id).unwrap();
+    ModNsfwPost::delete(&conn, inserted_mod_nsfw_post.id).unwrap();
 
     Comment::delete(&conn, inserted_comment.id).unwrap();
     Post::delete(&conn, inserted_post.id).unwrap();
@@ -773,5 +1181,9 @@ mod tests {
     assert_eq!(expected_mod_ban, read_mod_ban);
     assert_eq!(expected_mod_add_community, read_mod_add_community);
     assert_eq!(expected_mod_add, read_mod_add);
+    assert_eq!(expected_mod_shadow_ban, read_mod_shadow_ban);
+    assert_eq!(expected_mod_purge, read_mod_purge);
+    assert_eq!(expected_mod_transfer_community, read_mod_transfer_community);
+    assert_eq!(expected_mod_nsfw_post, read_mod_nsfw_post);
   }
 }
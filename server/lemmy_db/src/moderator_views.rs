@@ -1,7 +1,22 @@
 use crate::limit_and_offset;
 use diesel::{result::Error, *};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+/// The filters that every modlog list takes. The site wide lists, like bans, ignore the
+/// community, and the lists of actions without a target user ignore that. `after` is inclusive
+/// and `before` is exclusive, so that it can be the cursor for the next page.
+#[derive(Clone, Default)]
+pub struct ModlogFilter {
+  pub community_id: Option<i32>,
+  pub mod_user_id: Option<i32>,
+  pub other_user_id: Option<i32>,
+  pub after: Option<chrono::NaiveDateTime>,
+  pub before: Option<chrono::NaiveDateTime>,
+  pub page: Option<i64>,
+  pub limit: Option<i64>,
+}
+
 table! {
   mod_remove_post_view (id) {
     id -> Int4,
@@ -12,13 +27,22 @@ table! {
     when_ -> Timestamp,
     mod_user_name -> Varchar,
     post_name -> Varchar,
+    post_creator_id -> Int4,
     community_id -> Int4,
     community_name -> Varchar,
   }
 }
 
 #[derive(
-  Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, QueryableByName, Clone,
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
 )]
 #[table_name = "mod_remove_post_view"]
 pub struct ModRemovePostView {
@@ -30,31 +54,38 @@ pub struct ModRemovePostView {
   pub when_: chrono::NaiveDateTime,
   pub mod_user_name: String,
   pub post_name: String,
+  pub post_creator_id: i32,
   pub community_id: i32,
   pub community_name: String,
 }
 
 impl ModRemovePostView {
-  pub fn list(
-    conn: &PgConnection,
-    from_community_id: Option<i32>,
-    from_mod_user_id: Option<i32>,
-    page: Option<i64>,
-    limit: Option<i64>,
-  ) -> Result<Vec<Self>, Error> {
+  pub fn list(conn: &PgConnection, filter: &ModlogFilter) -> Result<Vec<Self>, Error> {
     use super::moderator_views::mod_remove_post_view::dsl::*;
     let mut query = mod_remove_post_view.into_boxed();
 
-    let (limit, offset) = limit_and_offset(page, limit);
+    let (limit, offset) = limit_and_offset(filter.page, filter.limit);
 
-    if let Some(from_community_id) = from_community_id {
+    if let Some(from_community_id) = filter.community_id {
       query = query.filter(community_id.eq(from_community_id));
     };
 
-    if let Some(from_mod_user_id) = from_mod_user_id {
+    if let Some(from_mod_user_id) = filter.mod_user_id {
       query = query.filter(mod_user_id.eq(from_mod_user_id));
     };
 
+    if let Some(from_other_user_id) = filter.other_user_id {
+      query = query.filter(post_creator_id.eq(from_other_user_id));
+    };
+
+    if let Some(after) = filter.after {
+      query = query.filter(when_.ge(after));
+    };
+
+    if let Some(before) = filter.before {
+      query = query.filter(when_.lt(before));
+    };
+
     query
       .limit(limit)
       .offset(offset)
@@ -78,7 +109,15 @@ table! {
 }
 
 #[derive(
-  Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, QueryableByName, Clone,
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
 )]
 #[table_name = "mod_lock_post_view"]
 pub struct ModLockPostView {
@@ -94,26 +133,28 @@ pub struct ModLockPostView {
 }
 
 impl ModLockPostView {
-  pub fn list(
-    conn: &PgConnection,
-    from_community_id: Option<i32>,
-    from_mod_user_id: Option<i32>,
-    page: Option<i64>,
-    limit: Option<i64>,
-  ) -> Result<Vec<Self>, Error> {
+  pub fn list(conn: &PgConnection, filter: &ModlogFilter) -> Result<Vec<Self>, Error> {
     use super::moderator_views::mod_lock_post_view::dsl::*;
     let mut query = mod_lock_post_view.into_boxed();
 
-    let (limit, offset) = limit_and_offset(page, limit);
+    let (limit, offset) = limit_and_offset(filter.page, filter.limit);
 
-    if let Some(from_community_id) = from_community_id {
+    if let Some(from_community_id) = filter.community_id {
       query = query.filter(community_id.eq(from_community_id));
     };
 
-    if let Some(from_mod_user_id) = from_mod_user_id {
+    if let Some(from_mod_user_id) = filter.mod_user_id {
       query = query.filter(mod_user_id.eq(from_mod_user_id));
     };
 
+    if let Some(after) = filter.after {
+      query = query.filter(when_.ge(after));
+    };
+
+    if let Some(before) = filter.before {
+      query = query.filter(when_.lt(before));
+    };
+
     query
       .limit(limit)
       .offset(offset)
@@ -137,7 +178,15 @@ table! {
 }
 
 #[derive(
-  Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, QueryableByName, Clone,
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
 )]
 #[table_name = "mod_sticky_post_view"]
 pub struct ModStickyPostView {
@@ -153,26 +202,28 @@ pub struct ModStickyPostView {
 }
 
 impl ModStickyPostView {
-  pub fn list(
-    conn: &PgConnection,
-    from_community_id: Option<i32>,
-    from_mod_user_id: Option<i32>,
-    page: Option<i64>,
-    limit: Option<i64>,
-  ) -> Result<Vec<Self>, Error> {
+  pub fn list(conn: &PgConnection, filter: &ModlogFilter) -> Result<Vec<Self>, Error> {
     use super::moderator_views::mod_sticky_post_view::dsl::*;
     let mut query = mod_sticky_post_view.into_boxed();
 
-    let (limit, offset) = limit_and_offset(page, limit);
+    let (limit, offset) = limit_and_offset(filter.page, filter.limit);
 
-    if let Some(from_community_id) = from_community_id {
+    if let Some(from_community_id) = filter.community_id {
       query = query.filter(community_id.eq(from_community_id));
     };
 
-    if let Some(from_mod_user_id) = from_mod_user_id {
+    if let Some(from_mod_user_id) = filter.mod_user_id {
       query = query.filter(mod_user_id.eq(from_mod_user_id));
     };
 
+    if let Some(after) = filter.after {
+      query = query.filter(when_.ge(after));
+    };
+
+    if let Some(before) = filter.before {
+      query = query.filter(when_.lt(before));
+    };
+
     query
       .limit(limit)
       .offset(offset)
@@ -201,7 +252,15 @@ table! {
 }
 
 #[derive(
-  Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, QueryableByName, Clone,
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
 )]
 #[table_name = "mod_remove_comment_view"]
 pub struct ModRemoveCommentView {
@@ -222,26 +281,32 @@ pub struct ModRemoveCommentView {
 }
 
 impl ModRemoveCommentView {
-  pub fn list(
-    conn: &PgConnection,
-    from_community_id: Option<i32>,
-    from_mod_user_id: Option<i32>,
-    page: Option<i64>,
-    limit: Option<i64>,
-  ) -> Result<Vec<Self>, Error> {
+  pub fn list(conn: &PgConnection, filter: &ModlogFilter) -> Result<Vec<Self>, Error> {
     use super::moderator_views::mod_remove_comment_view::dsl::*;
     let mut query = mod_remove_comment_view.into_boxed();
 
-    let (limit, offset) = limit_and_offset(page, limit);
+    let (limit, offset) = limit_and_offset(filter.page, filter.limit);
 
-    if let Some(from_community_id) = from_community_id {
+    if let Some(from_community_id) = filter.community_id {
       query = query.filter(community_id.eq(from_community_id));
     };
 
-    if let Some(from_mod_user_id) = from_mod_user_id {
+    if let Some(from_mod_user_id) = filter.mod_user_id {
       query = query.filter(mod_user_id.eq(from_mod_user_id));
     };
 
+    if let Some(from_other_user_id) = filter.other_user_id {
+      query = query.filter(comment_user_id.eq(from_other_user_id));
+    };
+
+    if let Some(after) = filter.after {
+      query = query.filter(when_.ge(after));
+    };
+
+    if let Some(before) = filter.before {
+      query = query.filter(when_.lt(before));
+    };
+
     query
       .limit(limit)
       .offset(offset)
@@ -265,7 +330,15 @@ table! {
 }
 
 #[derive(
-  Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, QueryableByName, Clone,
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
 )]
 #[table_name = "mod_remove_community_view"]
 pub struct ModRemoveCommunityView {
@@ -281,21 +354,24 @@ pub struct ModRemoveCommunityView {
 }
 
 impl ModRemoveCommunityView {
-  pub fn list(
-    conn: &PgConnection,
-    from_mod_user_id: Option<i32>,
-    page: Option<i64>,
-    limit: Option<i64>,
-  ) -> Result<Vec<Self>, Error> {
+  pub fn list(conn: &PgConnection, filter: &ModlogFilter) -> Result<Vec<Self>, Error> {
     use super::moderator_views::mod_remove_community_view::dsl::*;
     let mut query = mod_remove_community_view.into_boxed();
 
-    let (limit, offset) = limit_and_offset(page, limit);
+    let (limit, offset) = limit_and_offset(filter.page, filter.limit);
 
-    if let Some(from_mod_user_id) = from_mod_user_id {
+    if let Some(from_mod_user_id) = filter.mod_user_id {
       query = query.filter(mod_user_id.eq(from_mod_user_id));
     };
 
+    if let Some(after) = filter.after {
+      query = query.filter(when_.ge(after));
+    };
+
+    if let Some(before) = filter.before {
+      query = query.filter(when_.lt(before));
+    };
+
     query
       .limit(limit)
       .offset(offset)
@@ -321,7 +397,15 @@ table! {
 }
 
 #[derive(
-  Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, QueryableByName, Clone,
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
 )]
 #[table_name = "mod_ban_from_community_view"]
 pub struct ModBanFromCommunityView {
@@ -339,26 +423,32 @@ pub struct ModBanFromCommunityView {
 }
 
 impl ModBanFromCommunityView {
-  pub fn list(
-    conn: &PgConnection,
-    from_community_id: Option<i32>,
-    from_mod_user_id: Option<i32>,
-    page: Option<i64>,
-    limit: Option<i64>,
-  ) -> Result<Vec<Self>, Error> {
+  pub fn list(conn: &PgConnection, filter: &ModlogFilter) -> Result<Vec<Self>, Error> {
     use super::moderator_views::mod_ban_from_community_view::dsl::*;
     let mut query = mod_ban_from_community_view.into_boxed();
 
-    let (limit, offset) = limit_and_offset(page, limit);
+    let (limit, offset) = limit_and_offset(filter.page, filter.limit);
 
-    if let Some(from_community_id) = from_community_id {
+    if let Some(from_community_id) = filter.community_id {
       query = query.filter(community_id.eq(from_community_id));
     };
 
-    if let Some(from_mod_user_id) = from_mod_user_id {
+    if let Some(from_mod_user_id) = filter.mod_user_id {
       query = query.filter(mod_user_id.eq(from_mod_user_id));
     };
 
+    if let Some(from_other_user_id) = filter.other_user_id {
+      query = query.filter(other_user_id.eq(from_other_user_id));
+    };
+
+    if let Some(after) = filter.after {
+      query = query.filter(when_.ge(after));
+    };
+
+    if let Some(before) = filter.before {
+      query = query.filter(when_.lt(before));
+    };
+
     query
       .limit(limit)
       .offset(offset)
@@ -382,7 +472,15 @@ table! {
 }
 
 #[derive(
-  Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, QueryableByName, Clone,
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
 )]
 #[table_name = "mod_ban_view"]
 pub struct ModBanView {
@@ -398,21 +496,28 @@ pub struct ModBanView {
 }
 
 impl ModBanView {
-  pub fn list(
-    conn: &PgConnection,
-    from_mod_user_id: Option<i32>,
-    page: Option<i64>,
-    limit: Option<i64>,
-  ) -> Result<Vec<Self>, Error> {
+  pub fn list(conn: &PgConnection, filter: &ModlogFilter) -> Result<Vec<Self>, Error> {
     use super::moderator_views::mod_ban_view::dsl::*;
     let mut query = mod_ban_view.into_boxed();
 
-    let (limit, offset) = limit_and_offset(page, limit);
+    let (limit, offset) = limit_and_offset(filter.page, filter.limit);
 
-    if let Some(from_mod_user_id) = from_mod_user_id {
+    if let Some(from_mod_user_id) = filter.mod_user_id {
       query = query.filter(mod_user_id.eq(from_mod_user_id));
     };
 
+    if let Some(from_other_user_id) = filter.other_user_id {
+      query = query.filter(other_user_id.eq(from_other_user_id));
+    };
+
+    if let Some(after) = filter.after {
+      query = query.filter(when_.ge(after));
+    };
+
+    if let Some(before) = filter.before {
+      query = query.filter(when_.lt(before));
+    };
+
     query
       .limit(limit)
       .offset(offset)
@@ -436,7 +541,15 @@ table! {
 }
 
 #[derive(
-  Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, QueryableByName, Clone,
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
 )]
 #[table_name = "mod_add_community_view"]
 pub struct ModAddCommunityView {
@@ -452,26 +565,32 @@ pub struct ModAddCommunityView {
 }
 
 impl ModAddCommunityView {
-  pub fn list(
-    conn: &PgConnection,
-    from_community_id: Option<i32>,
-    from_mod_user_id: Option<i32>,
-    page: Option<i64>,
-    limit: Option<i64>,
-  ) -> Result<Vec<Self>, Error> {
+  pub fn list(conn: &PgConnection, filter: &ModlogFilter) -> Result<Vec<Self>, Error> {
     use super::moderator_views::mod_add_community_view::dsl::*;
     let mut query = mod_add_community_view.into_boxed();
 
-    let (limit, offset) = limit_and_offset(page, limit);
+    let (limit, offset) = limit_and_offset(filter.page, filter.limit);
 
-    if let Some(from_community_id) = from_community_id {
+    if let Some(from_community_id) = filter.community_id {
       query = query.filter(community_id.eq(from_community_id));
     };
 
-    if let Some(from_mod_user_id) = from_mod_user_id {
+    if let Some(from_mod_user_id) = filter.mod_user_id {
       query = query.filter(mod_user_id.eq(from_mod_user_id));
     };
 
+    if let Some(from_other_user_id) = filter.other_user_id {
+      query = query.filter(other_user_id.eq(from_other_user_id));
+    };
+
+    if let Some(after) = filter.after {
+      query = query.filter(when_.ge(after));
+    };
+
+    if let Some(before) = filter.before {
+      query = query.filter(when_.lt(before));
+    };
+
     query
       .limit(limit)
       .offset(offset)
@@ -493,7 +612,15 @@ table! {
 }
 
 #[derive(
-  Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, QueryableByName, Clone,
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
 )]
 #[table_name = "mod_add_view"]
 pub struct ModAddView {
@@ -507,21 +634,452 @@ pub struct ModAddView {
 }
 
 impl ModAddView {
-  pub fn list(
-    conn: &PgConnection,
-    from_mod_user_id: Option<i32>,
-    page: Option<i64>,
-    limit: Option<i64>,
-  ) -> Result<Vec<Self>, Error> {
+  pub fn list(conn: &PgConnection, filter: &ModlogFilter) -> Result<Vec<Self>, Error> {
     use super::moderator_views::mod_add_view::dsl::*;
     let mut query = mod_add_view.into_boxed();
 
-    let (limit, offset) = limit_and_offset(page, limit);
+    let (limit, offset) = limit_and_offset(filter.page, filter.limit);
+
+    if let Some(from_mod_user_id) = filter.mod_user_id {
+      query = query.filter(mod_user_id.eq(from_mod_user_id));
+    };
+
+    if let Some(from_other_user_id) = filter.other_user_id {
+      query = query.filter(other_user_id.eq(from_other_user_id));
+    };
+
+    if let Some(after) = filter.after {
+      query = query.filter(when_.ge(after));
+    };
+
+    if let Some(before) = filter.before {
+      query = query.filter(when_.lt(before));
+    };
+
+    query
+      .limit(limit)
+      .offset(offset)
+      .order_by(when_.desc())
+      .load::<Self>(conn)
+  }
+}
+
+table! {
+  mod_resolve_post_report_view (id) {
+    id -> Int4,
+    mod_user_id -> Int4,
+    report_id -> Int4,
+    resolved -> Nullable<Bool>,
+    when_ -> Timestamp,
+    mod_user_name -> Varchar,
+    post_id -> Int4,
+    post_name -> Varchar,
+    community_id -> Int4,
+    community_name -> Varchar,
+  }
+}
+
+#[derive(
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
+)]
+#[table_name = "mod_resolve_post_report_view"]
+pub struct ModResolvePostReportView {
+  pub id: i32,
+  pub mod_user_id: i32,
+  pub report_id: i32,
+  pub resolved: Option<bool>,
+  pub when_: chrono::NaiveDateTime,
+  pub mod_user_name: String,
+  pub post_id: i32,
+  pub post_name: String,
+  pub community_id: i32,
+  pub community_name: String,
+}
+
+impl ModResolvePostReportView {
+  pub fn list(conn: &PgConnection, filter: &ModlogFilter) -> Result<Vec<Self>, Error> {
+    use super::moderator_views::mod_resolve_post_report_view::dsl::*;
+    let mut query = mod_resolve_post_report_view.into_boxed();
+
+    let (limit, offset) = limit_and_offset(filter.page, filter.limit);
+
+    if let Some(from_community_id) = filter.community_id {
+      query = query.filter(community_id.eq(from_community_id));
+    };
+
+    if let Some(from_mod_user_id) = filter.mod_user_id {
+      query = query.filter(mod_user_id.eq(from_mod_user_id));
+    };
+
+    if let Some(after) = filter.after {
+      query = query.filter(when_.ge(after));
+    };
+
+    if let Some(before) = filter.before {
+      query = query.filter(when_.lt(before));
+    };
+
+    query
+      .limit(limit)
+      .offset(offset)
+      .order_by(when_.desc())
+      .load::<Self>(conn)
+  }
+}
+
+table! {
+  mod_resolve_comment_report_view (id) {
+    id -> Int4,
+    mod_user_id -> Int4,
+    report_id -> Int4,
+    resolved -> Nullable<Bool>,
+    when_ -> Timestamp,
+    mod_user_name -> Varchar,
+    comment_id -> Int4,
+    post_id -> Int4,
+    post_name -> Varchar,
+    community_id -> Int4,
+    community_name -> Varchar,
+  }
+}
+
+#[derive(
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
+)]
+#[table_name = "mod_resolve_comment_report_view"]
+pub struct ModResolveCommentReportView {
+  pub id: i32,
+  pub mod_user_id: i32,
+  pub report_id: i32,
+  pub resolved: Option<bool>,
+  pub when_: chrono::NaiveDateTime,
+  pub mod_user_name: String,
+  pub comment_id: i32,
+  pub post_id: i32,
+  pub post_name: String,
+  pub community_id: i32,
+  pub community_name: String,
+}
+
+impl ModResolveCommentReportView {
+  pub fn list(conn: &PgConnection, filter: &ModlogFilter) -> Result<Vec<Self>, Error> {
+    use super::moderator_views::mod_resolve_comment_report_view::dsl::*;
+    let mut query = mod_resolve_comment_report_view.into_boxed();
 
-    if let Some(from_mod_user_id) = from_mod_user_id {
+    let (limit, offset) = limit_and_offset(filter.page, filter.limit);
+
+    if let Some(from_community_id) = filter.community_id {
+      query = query.filter(community_id.eq(from_community_id));
+    };
+
+    if let Some(from_mod_user_id) = filter.mod_user_id {
       query = query.filter(mod_user_id.eq(from_mod_user_id));
     };
 
+    if let Some(after) = filter.after {
+      query = query.filter(when_.ge(after));
+    };
+
+    if let Some(before) = filter.before {
+      query = query.filter(when_.lt(before));
+    };
+
+    query
+      .limit(limit)
+      .offset(offset)
+      .order_by(when_.desc())
+      .load::<Self>(conn)
+  }
+}
+
+table! {
+  mod_shadow_ban_view (id) {
+    id -> Int4,
+    mod_user_id -> Int4,
+    other_user_id -> Int4,
+    reason -> Nullable<Text>,
+    shadow_banned -> Nullable<Bool>,
+    when_ -> Timestamp,
+    mod_user_name -> Varchar,
+    other_user_name -> Varchar,
+  }
+}
+
+#[derive(
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
+)]
+#[table_name = "mod_shadow_ban_view"]
+pub struct ModShadowBanView {
+  pub id: i32,
+  pub mod_user_id: i32,
+  pub other_user_id: i32,
+  pub reason: Option<String>,
+  pub shadow_banned: Option<bool>,
+  pub when_: chrono::NaiveDateTime,
+  pub mod_user_name: String,
+  pub other_user_name: String,
+}
+
+impl ModShadowBanView {
+  pub fn list(conn: &PgConnection, filter: &ModlogFilter) -> Result<Vec<Self>, Error> {
+    use super::moderator_views::mod_shadow_ban_view::dsl::*;
+    let mut query = mod_shadow_ban_view.into_boxed();
+
+    let (limit, offset) = limit_and_offset(filter.page, filter.limit);
+
+    if let Some(from_mod_user_id) = filter.mod_user_id {
+      query = query.filter(mod_user_id.eq(from_mod_user_id));
+    };
+
+    if let Some(from_other_user_id) = filter.other_user_id {
+      query = query.filter(other_user_id.eq(from_other_user_id));
+    };
+
+    if let Some(after) = filter.after {
+      query = query.filter(when_.ge(after));
+    };
+
+    if let Some(before) = filter.before {
+      query = query.filter(when_.lt(before));
+    };
+
+    query
+      .limit(limit)
+      .offset(offset)
+      .order_by(when_.desc())
+      .load::<Self>(conn)
+  }
+}
+
+table! {
+  mod_purge_view (id) {
+    id -> Int4,
+    mod_user_id -> Int4,
+    target_type -> Int2,
+    community_id -> Nullable<Int4>,
+    reason -> Nullable<Text>,
+    when_ -> Timestamp,
+    mod_user_name -> Varchar,
+    community_name -> Nullable<Varchar>,
+  }
+}
+
+#[derive(
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
+)]
+#[table_name = "mod_purge_view"]
+pub struct ModPurgeView {
+  pub id: i32,
+  pub mod_user_id: i32,
+  pub target_type: i16,
+  pub community_id: Option<i32>,
+  pub reason: Option<String>,
+  pub when_: chrono::NaiveDateTime,
+  pub mod_user_name: String,
+  pub community_name: Option<String>,
+}
+
+impl ModPurgeView {
+  pub fn list(conn: &PgConnection, filter: &ModlogFilter) -> Result<Vec<Self>, Error> {
+    use super::moderator_views::mod_purge_view::dsl::*;
+    let mut query = mod_purge_view.into_boxed();
+
+    let (limit, offset) = limit_and_offset(filter.page, filter.limit);
+
+    if let Some(from_community_id) = filter.community_id {
+      query = query.filter(community_id.eq(from_community_id));
+    };
+
+    if let Some(from_mod_user_id) = filter.mod_user_id {
+      query = query.filter(mod_user_id.eq(from_mod_user_id));
+    };
+
+    if let Some(after) = filter.after {
+      query = query.filter(when_.ge(after));
+    };
+
+    if let Some(before) = filter.before {
+      query = query.filter(when_.lt(before));
+    };
+
+    query
+      .limit(limit)
+      .offset(offset)
+      .order_by(when_.desc())
+      .load::<Self>(conn)
+  }
+}
+
+table! {
+  mod_transfer_community_view (id) {
+    id -> Int4,
+    mod_user_id -> Int4,
+    other_user_id -> Int4,
+    community_id -> Int4,
+    when_ -> Timestamp,
+    mod_user_name -> Varchar,
+    other_user_name -> Varchar,
+    community_name -> Varchar,
+  }
+}
+
+#[derive(
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
+)]
+#[table_name = "mod_transfer_community_view"]
+pub struct ModTransferCommunityView {
+  pub id: i32,
+  pub mod_user_id: i32,
+  pub other_user_id: i32,
+  pub community_id: i32,
+  pub when_: chrono::NaiveDateTime,
+  pub mod_user_name: String,
+  pub other_user_name: String,
+  pub community_name: String,
+}
+
+impl ModTransferCommunityView {
+  pub fn list(conn: &PgConnection, filter: &ModlogFilter) -> Result<Vec<Self>, Error> {
+    use super::moderator_views::mod_transfer_community_view::dsl::*;
+    let mut query = mod_transfer_community_view.into_boxed();
+
+    let (limit, offset) = limit_and_offset(filter.page, filter.limit);
+
+    if let Some(from_community_id) = filter.community_id {
+      query = query.filter(community_id.eq(from_community_id));
+    };
+
+    if let Some(from_mod_user_id) = filter.mod_user_id {
+      query = query.filter(mod_user_id.eq(from_mod_user_id));
+    };
+
+    if let Some(from_other_user_id) = filter.other_user_id {
+      query = query.filter(other_user_id.eq(from_other_user_id));
+    };
+
+    if let Some(after) = filter.after {
+      query = query.filter(when_.ge(after));
+    };
+
+    if let Some(before) = filter.before {
+      query = query.filter(when_.lt(before));
+    };
+
+    query
+      .limit(limit)
+      .offset(offset)
+      .order_by(when_.desc())
+      .load::<Self>(conn)
+  }
+}
+
+table! {
+  mod_nsfw_post_view (id) {
+    id -> Int4,
+    mod_user_id -> Int4,
+    post_id -> Int4,
+    nsfw -> Nullable<Bool>,
+    when_ -> Timestamp,
+    mod_user_name -> Varchar,
+    post_name -> Varchar,
+    post_creator_id -> Int4,
+    community_id -> Int4,
+    community_name -> Varchar,
+  }
+}
+
+#[derive(
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
+)]
+#[table_name = "mod_nsfw_post_view"]
+pub struct ModNsfwPostView {
+  pub id: i32,
+  pub mod_user_id: i32,
+  pub post_id: i32,
+  pub nsfw: Option<bool>,
+  pub when_: chrono::NaiveDateTime,
+  pub mod_user_name: String,
+  pub post_name: String,
+  pub post_creator_id: i32,
+  pub community_id: i32,
+  pub community_name: String,
+}
+
+impl ModNsfwPostView {
+  pub fn list(conn: &PgConnection, filter: &ModlogFilter) -> Result<Vec<Self>, Error> {
+    use super::moderator_views::mod_nsfw_post_view::dsl::*;
+    let mut query = mod_nsfw_post_view.into_boxed();
+
+    let (limit, offset) = limit_and_offset(filter.page, filter.limit);
+
+    if let Some(from_community_id) = filter.community_id {
+      query = query.filter(community_id.eq(from_community_id));
+    };
+
+    if let Some(from_mod_user_id) = filter.mod_user_id {
+      query = query.filter(mod_user_id.eq(from_mod_user_id));
+    };
+
+    if let Some(from_other_user_id) = filter.other_user_id {
+      query = query.filter(post_creator_id.eq(from_other_user_id));
+    };
+
+    if let Some(after) = filter.after {
+      query = query.filter(when_.ge(after));
+    };
+
+    if let Some(before) = filter.before {
+      query = query.filter(when_.lt(before));
+    };
+
     query
       .limit(limit)
       .offset(offset)
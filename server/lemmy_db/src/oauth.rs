@@ -0,0 +1,296 @@
+use crate::{
+  schema::{oauth_app, oauth_authorization, oauth_token},
+  Crud,
+};
+use diesel::{dsl::*, result::Error, *};
+use sha2::{Digest, Sha256};
+
+/// How long the app has to exchange the code for a token
+const CODE_MINUTES: i32 = 10;
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Clone)]
+#[table_name = "oauth_app"]
+pub struct OAuthApp {
+  pub id: i32,
+  pub client_id: String,
+  pub client_secret: String,
+  pub name: String,
+  pub website: Option<String>,
+  pub redirect_uris: String,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "oauth_app"]
+pub struct OAuthAppForm {
+  pub client_id: String,
+  pub client_secret: String,
+  pub name: String,
+  pub website: Option<String>,
+  pub redirect_uris: String,
+}
+
+impl Crud<OAuthAppForm> for OAuthApp {
+  fn read(conn: &PgConnection, oauth_app_id: i32) -> Result<Self, Error> {
+    oauth_app::table.find(oauth_app_id).first::<Self>(conn)
+  }
+  fn delete(conn: &PgConnection, oauth_app_id: i32) -> Result<usize, Error> {
+    diesel::delete(oauth_app::table.find(oauth_app_id)).execute(conn)
+  }
+  fn create(conn: &PgConnection, form: &OAuthAppForm) -> Result<Self, Error> {
+    insert_into(oauth_app::table)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+  fn update(conn: &PgConnection, oauth_app_id: i32, form: &OAuthAppForm) -> Result<Self, Error> {
+    diesel::update(oauth_app::table.find(oauth_app_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl OAuthApp {
+  pub fn read_from_client_id(conn: &PgConnection, for_client_id: &str) -> Result<Self, Error> {
+    oauth_app::table
+      .filter(oauth_app::client_id.eq(for_client_id))
+      .first::<Self>(conn)
+  }
+
+  /// The app, if the secret is the one it was registered with
+  pub fn read_from_credentials(
+    conn: &PgConnection,
+    for_client_id: &str,
+    for_client_secret: &str,
+  ) -> Result<Self, Error> {
+    oauth_app::table
+      .filter(oauth_app::client_id.eq(for_client_id))
+      .filter(oauth_app::client_secret.eq(for_client_secret))
+      .first::<Self>(conn)
+  }
+
+  /// Whether the app registered the redirect uri. They're one per line.
+  pub fn allows_redirect(&self, redirect_uri: &str) -> bool {
+    self
+      .redirect_uris
+      .split_whitespace()
+      .any(|uri| uri == redirect_uri)
+  }
+}
+
+#[derive(Queryable, Identifiable, PartialEq, Debug)]
+#[table_name = "oauth_authorization"]
+pub struct OAuthAuthorization {
+  pub id: i32,
+  pub app_id: i32,
+  pub user_id: i32,
+  pub redirect_uri: String,
+  pub code_encrypted: String,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Clone)]
+#[table_name = "oauth_authorization"]
+pub struct OAuthAuthorizationForm {
+  pub app_id: i32,
+  pub user_id: i32,
+  pub redirect_uri: String,
+  pub code_encrypted: String,
+}
+
+impl OAuthAuthorization {
+  /// Keeps the hash of the code, and clears out the ones that weren't exchanged in time
+  pub fn create_code(
+    conn: &PgConnection,
+    for_app_id: i32,
+    for_user_id: i32,
+    for_redirect_uri: &str,
+    code: &str,
+  ) -> Result<Self, Error> {
+    diesel::delete(
+      oauth_authorization::table
+        .filter(oauth_authorization::published.lt(now - CODE_MINUTES.minutes())),
+    )
+    .execute(conn)?;
+
+    let form = OAuthAuthorizationForm {
+      app_id: for_app_id,
+      user_id: for_user_id,
+      redirect_uri: for_redirect_uri.to_owned(),
+      code_encrypted: hash_token(code),
+    };
+    insert_into(oauth_authorization::table)
+      .values(&form)
+      .get_result::<Self>(conn)
+  }
+
+  /// Takes the code out as it's read, so it can only be exchanged once, by the app it was given to,
+  /// with the redirect uri it was sent to.
+  pub fn consume_code(
+    conn: &PgConnection,
+    for_app_id: i32,
+    for_redirect_uri: &str,
+    code: &str,
+  ) -> Result<Self, Error> {
+    diesel::delete(
+      oauth_authorization::table
+        .filter(oauth_authorization::code_encrypted.eq(hash_token(code)))
+        .filter(oauth_authorization::app_id.eq(for_app_id))
+        .filter(oauth_authorization::redirect_uri.eq(for_redirect_uri))
+        .filter(oauth_authorization::published.gt(now - CODE_MINUTES.minutes())),
+    )
+    .get_result::<Self>(conn)
+  }
+}
+
+#[derive(Queryable, Identifiable, PartialEq, Debug)]
+#[table_name = "oauth_token"]
+pub struct OAuthToken {
+  pub id: i32,
+  pub app_id: i32,
+  pub user_id: i32,
+  pub token_encrypted: String,
+  pub scope: String,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Clone)]
+#[table_name = "oauth_token"]
+pub struct OAuthTokenForm {
+  pub app_id: i32,
+  pub user_id: i32,
+  pub token_encrypted: String,
+  pub scope: String,
+}
+
+impl OAuthToken {
+  pub fn create_token(
+    conn: &PgConnection,
+    for_app_id: i32,
+    for_user_id: i32,
+    for_scope: &str,
+    token: &str,
+  ) -> Result<Self, Error> {
+    let form = OAuthTokenForm {
+      app_id: for_app_id,
+      user_id: for_user_id,
+      token_encrypted: hash_token(token),
+      scope: for_scope.to_owned(),
+    };
+    insert_into(oauth_token::table)
+      .values(&form)
+      .get_result::<Self>(conn)
+  }
+
+  pub fn read_from_token(conn: &PgConnection, token: &str) -> Result<Self, Error> {
+    oauth_token::table
+      .filter(oauth_token::token_encrypted.eq(hash_token(token)))
+      .first::<Self>(conn)
+  }
+
+  /// Only the app the token was given to can revoke it
+  pub fn revoke(conn: &PgConnection, for_app_id: i32, token: &str) -> Result<usize, Error> {
+    diesel::delete(
+      oauth_token::table
+        .filter(oauth_token::token_encrypted.eq(hash_token(token)))
+        .filter(oauth_token::app_id.eq(for_app_id)),
+    )
+    .execute(conn)
+  }
+}
+
+fn hash_token(token: &str) -> String {
+  let mut hasher = Sha256::new();
+  hasher.update(token);
+  hasher
+    .finalize()
+    .iter()
+    .map(|byte| format!("{:02x}", byte))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "thommy_oauth".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_app = OAuthAppForm {
+      client_id: "oauth_test_client".into(),
+      client_secret: "secret".into(),
+      name: "Test app".into(),
+      website: None,
+      redirect_uris: "https://app.example/callback\nurn:ietf:wg:oauth:2.0:oob".into(),
+    };
+
+    let inserted_app = OAuthApp::create(&conn, &new_app).unwrap();
+    let read_app = OAuthApp::read_from_credentials(&conn, "oauth_test_client", "secret").unwrap();
+    let wrong_secret = OAuthApp::read_from_credentials(&conn, "oauth_test_client", "nope");
+
+    let redirect = "https://app.example/callback";
+    OAuthAuthorization::create_code(&conn, inserted_app.id, inserted_user.id, redirect, "code")
+      .unwrap();
+    let other_redirect =
+      OAuthAuthorization::consume_code(&conn, inserted_app.id, "https://evil.example", "code");
+    let consumed = OAuthAuthorization::consume_code(&conn, inserted_app.id, redirect, "code");
+    let consumed_again = OAuthAuthorization::consume_code(&conn, inserted_app.id, redirect, "code");
+
+    let inserted_token =
+      OAuthToken::create_token(&conn, inserted_app.id, inserted_user.id, "read", "token").unwrap();
+    let read_token = OAuthToken::read_from_token(&conn, "token").unwrap();
+    let num_revoked = OAuthToken::revoke(&conn, inserted_app.id, "token").unwrap();
+    let revoked_token = OAuthToken::read_from_token(&conn, "token");
+
+    let num_deleted = OAuthApp::delete(&conn, inserted_app.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(inserted_app, read_app);
+    assert!(wrong_secret.is_err());
+    assert!(read_app.allows_redirect(redirect));
+    assert!(read_app.allows_redirect("urn:ietf:wg:oauth:2.0:oob"));
+    assert!(!read_app.allows_redirect("https://app.example/callback/evil"));
+    assert!(other_redirect.is_err());
+    assert_eq!(inserted_user.id, consumed.unwrap().user_id);
+    assert!(consumed_again.is_err());
+    assert_eq!(inserted_token, read_token);
+    assert_ne!("token", read_token.token_encrypted);
+    assert_eq!(1, num_revoked);
+    assert!(revoked_token.is_err());
+    assert_eq!(1, num_deleted);
+  }
+}
@@ -1,15 +1,18 @@
 use crate::{
   naive_now,
-  schema::{post, post_like, post_read, post_saved},
+  schema::{post, post_hide, post_like, post_read, post_revision, post_saved, post_subscription},
   Crud,
+  Followable,
+  Hideable,
   Likeable,
   Readable,
   Saveable,
 };
 use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Queryable, Identifiable, PartialEq, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[table_name = "post"]
 pub struct Post {
   pub id: i32,
@@ -31,6 +34,10 @@ pub struct Post {
   pub thumbnail_url: Option<String>,
   pub ap_id: String,
   pub local: bool,
+  pub archived: bool,
+  pub pending: bool,
+  pub embed_type: Option<String>,
+  pub url_media_type: Option<String>,
 }
 
 #[derive(Insertable, AsChangeset, Clone, Debug)]
@@ -51,9 +58,23 @@ pub struct PostForm {
   pub embed_title: Option<String>,
   pub embed_description: Option<String>,
   pub embed_html: Option<String>,
+  pub embed_type: Option<String>,
+  pub url_media_type: Option<String>,
   pub thumbnail_url: Option<String>,
   pub ap_id: String,
   pub local: bool,
+  pub pending: Option<bool>,
+}
+
+/// The link preview of a post. What the refetch didn't find is left as it was.
+#[derive(AsChangeset, Clone, Debug)]
+#[table_name = "post"]
+pub struct PostEmbedForm {
+  pub embed_title: Option<String>,
+  pub embed_description: Option<String>,
+  pub embed_html: Option<String>,
+  pub embed_type: Option<String>,
+  pub thumbnail_url: Option<String>,
 }
 
 impl Post {
@@ -85,6 +106,52 @@ impl Post {
       .get_result::<Self>(conn)
   }
 
+  pub fn update_removed(
+    conn: &PgConnection,
+    post_id: i32,
+    new_removed: bool,
+  ) -> Result<Self, Error> {
+    use crate::schema::post::dsl::*;
+
+    diesel::update(post.find(post_id))
+      .set((removed.eq(new_removed), updated.eq(naive_now())))
+      .get_result::<Self>(conn)
+  }
+
+  /// Takes a post out of the approval queue. A rejected post is removed.
+  pub fn resolve_pending(conn: &PgConnection, post_id: i32, approved: bool) -> Result<Self, Error> {
+    use crate::schema::post::dsl::*;
+
+    let target = post.find(post_id).filter(pending.eq(true));
+    if approved {
+      diesel::update(target)
+        .set(pending.eq(false))
+        .get_result::<Self>(conn)
+    } else {
+      diesel::update(target)
+        .set((pending.eq(false), removed.eq(true), updated.eq(naive_now())))
+        .get_result::<Self>(conn)
+    }
+  }
+
+  /// Whether the user has a post in the community that made it through, which counts as being
+  /// approved there.
+  pub fn has_approved_post(
+    conn: &PgConnection,
+    for_community_id: i32,
+    for_creator_id: i32,
+  ) -> Result<bool, Error> {
+    use crate::schema::post::dsl::*;
+    select(exists(
+      post
+        .filter(community_id.eq(for_community_id))
+        .filter(creator_id.eq(for_creator_id))
+        .filter(pending.eq(false))
+        .filter(removed.eq(false)),
+    ))
+    .get_result(conn)
+  }
+
   pub fn permadelete(conn: &PgConnection, post_id: i32) -> Result<Self, Error> {
     use crate::schema::post::dsl::*;
 
@@ -101,6 +168,57 @@ impl Post {
       ))
       .get_result::<Self>(conn)
   }
+
+  pub fn update_embed(
+    conn: &PgConnection,
+    post_id: i32,
+    form: &PostEmbedForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::post::dsl::*;
+    diesel::update(post.find(post_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+
+  /// The local link posts published since then that got no preview, newest first.
+  pub fn missing_embed_since(
+    conn: &PgConnection,
+    since: chrono::NaiveDateTime,
+    limit: i64,
+  ) -> Result<Vec<Self>, Error> {
+    use crate::schema::post::dsl::*;
+    post
+      .filter(url.is_not_null())
+      .filter(url.ne(""))
+      .filter(embed_title.is_null())
+      .filter(thumbnail_url.is_null())
+      .filter(local.eq(true))
+      .filter(deleted.eq(false))
+      .filter(removed.eq(false))
+      .filter(published.gt(since))
+      .order_by(published.desc())
+      .limit(limit)
+      .load::<Self>(conn)
+  }
+
+  /// Locks and archives the posts of a community published before the given time. Posts that
+  /// were already archived are left alone, so a mod can unlock one for good.
+  pub fn archive_published_before(
+    conn: &PgConnection,
+    for_community_id: i32,
+    before: chrono::NaiveDateTime,
+  ) -> Result<Vec<Self>, Error> {
+    use crate::schema::post::dsl::*;
+
+    diesel::update(
+      post
+        .filter(community_id.eq(for_community_id))
+        .filter(archived.eq(false))
+        .filter(published.lt(before)),
+    )
+    .set((archived.eq(true), locked.eq(true)))
+    .get_results::<Self>(conn)
+  }
 }
 
 impl Crud<PostForm> for Post {
@@ -227,6 +345,9 @@ impl Readable<PostReadForm> for PostRead {
     use crate::schema::post_read::dsl::*;
     insert_into(post_read)
       .values(post_read_form)
+      .on_conflict((post_id, user_id))
+      .do_update()
+      .set(post_read_form)
       .get_result::<Self>(conn)
   }
   fn mark_as_unread(conn: &PgConnection, post_read_form: &PostReadForm) -> Result<usize, Error> {
@@ -240,6 +361,135 @@ impl Readable<PostReadForm> for PostRead {
   }
 }
 
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
+#[belongs_to(Post)]
+#[table_name = "post_hide"]
+pub struct PostHide {
+  pub id: i32,
+  pub post_id: i32,
+  pub user_id: i32,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "post_hide"]
+pub struct PostHideForm {
+  pub post_id: i32,
+  pub user_id: i32,
+}
+
+impl Hideable<PostHideForm> for PostHide {
+  fn hide(conn: &PgConnection, post_hide_form: &PostHideForm) -> Result<Self, Error> {
+    use crate::schema::post_hide::dsl::*;
+    insert_into(post_hide)
+      .values(post_hide_form)
+      .get_result::<Self>(conn)
+  }
+  fn unhide(conn: &PgConnection, post_hide_form: &PostHideForm) -> Result<usize, Error> {
+    use crate::schema::post_hide::dsl::*;
+    diesel::delete(
+      post_hide
+        .filter(post_id.eq(post_hide_form.post_id))
+        .filter(user_id.eq(post_hide_form.user_id)),
+    )
+    .execute(conn)
+  }
+}
+
+/// A post a user watches, to hear about every new comment in it.
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
+#[belongs_to(Post)]
+#[table_name = "post_subscription"]
+pub struct PostSubscription {
+  pub id: i32,
+  pub post_id: i32,
+  pub user_id: i32,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "post_subscription"]
+pub struct PostSubscriptionForm {
+  pub post_id: i32,
+  pub user_id: i32,
+}
+
+impl Followable<PostSubscriptionForm> for PostSubscription {
+  fn follow(conn: &PgConnection, subscription_form: &PostSubscriptionForm) -> Result<Self, Error> {
+    use crate::schema::post_subscription::dsl::*;
+    insert_into(post_subscription)
+      .values(subscription_form)
+      .on_conflict((post_id, user_id))
+      .do_update()
+      .set(subscription_form)
+      .get_result::<Self>(conn)
+  }
+  fn unfollow(
+    conn: &PgConnection,
+    subscription_form: &PostSubscriptionForm,
+  ) -> Result<usize, Error> {
+    use crate::schema::post_subscription::dsl::*;
+    diesel::delete(
+      post_subscription
+        .filter(post_id.eq(subscription_form.post_id))
+        .filter(user_id.eq(subscription_form.user_id)),
+    )
+    .execute(conn)
+  }
+}
+
+impl PostSubscription {
+  pub fn subscriber_ids(conn: &PgConnection, for_post_id: i32) -> Result<Vec<i32>, Error> {
+    use crate::schema::post_subscription::dsl::*;
+    post_subscription
+      .filter(post_id.eq(for_post_id))
+      .select(user_id)
+      .load::<i32>(conn)
+  }
+
+  pub fn is_subscribed(
+    conn: &PgConnection,
+    for_post_id: i32,
+    for_user_id: i32,
+  ) -> Result<bool, Error> {
+    use crate::schema::post_subscription::dsl::*;
+    select(exists(
+      post_subscription
+        .filter(post_id.eq(for_post_id))
+        .filter(user_id.eq(for_user_id)),
+    ))
+    .get_result(conn)
+  }
+}
+
+/// A previous version of an edited post. These are saved by the database whenever the text of a
+/// post changes.
+#[derive(
+  Identifiable, Queryable, Associations, PartialEq, Debug, Serialize, Deserialize, JsonSchema,
+)]
+#[belongs_to(Post)]
+#[table_name = "post_revision"]
+pub struct PostRevision {
+  pub id: i32,
+  pub post_id: i32,
+  pub name: String,
+  pub url: Option<String>,
+  pub body: Option<String>,
+  pub published: chrono::NaiveDateTime,
+}
+
+impl PostRevision {
+  /// Newest first
+  pub fn for_post(conn: &PgConnection, for_post_id: i32) -> Result<Vec<Self>, Error> {
+    use crate::schema::post_revision::dsl::*;
+    post_revision
+      .filter(post_id.eq(for_post_id))
+      .order_by(published.desc())
+      .then_order_by(id.desc())
+      .load::<Self>(conn)
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use crate::{
@@ -278,6 +528,12 @@ mod tests {
       private_key: None,
       public_key: None,
       last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
     };
 
     let inserted_user = User_::create(&conn, &new_user).unwrap();
@@ -298,6 +554,15 @@ mod tests {
       public_key: None,
       last_refreshed_at: None,
       published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
     };
 
     let inserted_community = Community::create(&conn, &new_community).unwrap();
@@ -317,10 +582,13 @@ mod tests {
       embed_title: None,
       embed_description: None,
       embed_html: None,
+      embed_type: None,
+      url_media_type: None,
       thumbnail_url: None,
       ap_id: "http://fake.com".into(),
       local: true,
       published: None,
+      pending: None,
     };
 
     let inserted_post = Post::create(&conn, &new_post).unwrap();
@@ -342,9 +610,13 @@ mod tests {
       embed_title: None,
       embed_description: None,
       embed_html: None,
+      embed_type: None,
+      url_media_type: None,
       thumbnail_url: None,
       ap_id: "http://fake.com".into(),
       local: true,
+      archived: false,
+      pending: false,
     };
 
     // Post Like
@@ -394,11 +666,83 @@ mod tests {
       published: inserted_post_read.published,
     };
 
+    // Post Hide
+    let post_hide_form = PostHideForm {
+      post_id: inserted_post.id,
+      user_id: inserted_user.id,
+    };
+
+    let inserted_post_hide = PostHide::hide(&conn, &post_hide_form).unwrap();
+
+    let expected_post_hide = PostHide {
+      id: inserted_post_hide.id,
+      post_id: inserted_post.id,
+      user_id: inserted_user.id,
+      published: inserted_post_hide.published,
+    };
+
+    // Post Subscription
+    let post_subscription_form = PostSubscriptionForm {
+      post_id: inserted_post.id,
+      user_id: inserted_user.id,
+    };
+
+    let inserted_post_subscription =
+      PostSubscription::follow(&conn, &post_subscription_form).unwrap();
+    let resubscribed = PostSubscription::follow(&conn, &post_subscription_form).unwrap();
+    let subscriber_ids = PostSubscription::subscriber_ids(&conn, inserted_post.id).unwrap();
+    let is_subscribed =
+      PostSubscription::is_subscribed(&conn, inserted_post.id, inserted_user.id).unwrap();
+
     let read_post = Post::read(&conn, inserted_post.id).unwrap();
     let updated_post = Post::update(&conn, inserted_post.id, &new_post).unwrap();
+    let unedited_revisions = PostRevision::for_post(&conn, inserted_post.id).unwrap();
+    let edited_post_form = PostForm {
+      body: Some("An edited body".into()),
+      ..new_post.clone()
+    };
+    Post::update(&conn, inserted_post.id, &edited_post_form).unwrap();
+    let edited_revisions = PostRevision::for_post(&conn, inserted_post.id).unwrap();
+    let not_yet_archived =
+      Post::archive_published_before(&conn, inserted_community.id, inserted_post.published)
+        .unwrap();
+    let archive_before = inserted_post.published + chrono::Duration::seconds(1);
+    let archived_posts =
+      Post::archive_published_before(&conn, inserted_community.id, archive_before).unwrap();
+    let rearchived_posts =
+      Post::archive_published_before(&conn, inserted_community.id, archive_before).unwrap();
+    let pending_post_form = PostForm {
+      pending: Some(true),
+      ..new_post.clone()
+    };
+    let inserted_pending_post = Post::create(&conn, &pending_post_form).unwrap();
+    let rejected_post = Post::resolve_pending(&conn, inserted_pending_post.id, false).unwrap();
+    let resolved_again = Post::resolve_pending(&conn, inserted_pending_post.id, true);
+    let has_approved_post =
+      Post::has_approved_post(&conn, inserted_community.id, inserted_user.id).unwrap();
+    Post::delete(&conn, inserted_pending_post.id).unwrap();
+    let link_post_form = PostForm {
+      url: Some("https://example.com".into()),
+      ..new_post.clone()
+    };
+    let inserted_link_post = Post::create(&conn, &link_post_form).unwrap();
+    let since = inserted_link_post.published - chrono::Duration::minutes(1);
+    let missing_embeds = Post::missing_embed_since(&conn, since, 100).unwrap();
+    let embed_form = PostEmbedForm {
+      embed_title: Some("Example".into()),
+      embed_description: None,
+      embed_html: None,
+      embed_type: None,
+      thumbnail_url: None,
+    };
+    let embedded_post = Post::update_embed(&conn, inserted_link_post.id, &embed_form).unwrap();
+    let missing_after_embed = Post::missing_embed_since(&conn, since, 100).unwrap();
+    Post::delete(&conn, inserted_link_post.id).unwrap();
     let like_removed = PostLike::remove(&conn, &post_like_form).unwrap();
     let saved_removed = PostSaved::unsave(&conn, &post_saved_form).unwrap();
     let read_removed = PostRead::mark_as_unread(&conn, &post_read_form).unwrap();
+    let hide_removed = PostHide::unhide(&conn, &post_hide_form).unwrap();
+    let subscription_removed = PostSubscription::unfollow(&conn, &post_subscription_form).unwrap();
     let num_deleted = Post::delete(&conn, inserted_post.id).unwrap();
     Community::delete(&conn, inserted_community.id).unwrap();
     User_::delete(&conn, inserted_user.id).unwrap();
@@ -409,9 +753,35 @@ mod tests {
     assert_eq!(expected_post_like, inserted_post_like);
     assert_eq!(expected_post_saved, inserted_post_saved);
     assert_eq!(expected_post_read, inserted_post_read);
+    assert_eq!(expected_post_hide, inserted_post_hide);
+    assert_eq!(inserted_post_subscription.id, resubscribed.id);
+    assert_eq!(vec![inserted_user.id], subscriber_ids);
+    assert!(is_subscribed);
+    assert!(unedited_revisions.is_empty());
+    assert_eq!(1, edited_revisions.len());
+    assert_eq!(expected_post.name, edited_revisions[0].name);
+    assert_eq!(expected_post.body, edited_revisions[0].body);
+    assert!(not_yet_archived.is_empty());
+    assert_eq!(1, archived_posts.len());
+    assert!(archived_posts[0].archived);
+    assert!(archived_posts[0].locked);
+    assert!(rearchived_posts.is_empty());
+    assert!(inserted_pending_post.pending);
+    assert!(!rejected_post.pending);
+    assert!(rejected_post.removed);
+    assert!(resolved_again.is_err());
+    assert!(has_approved_post);
+    assert!(missing_embeds.iter().any(|p| p.id == inserted_link_post.id));
+    assert!(missing_embeds.iter().all(|p| p.id != inserted_post.id));
+    assert_eq!(Some("Example".into()), embedded_post.embed_title);
+    assert!(missing_after_embed
+      .iter()
+      .all(|p| p.id != inserted_link_post.id));
     assert_eq!(1, like_removed);
     assert_eq!(1, saved_removed);
     assert_eq!(1, read_removed);
+    assert_eq!(1, hide_removed);
+    assert_eq!(1, subscription_removed);
     assert_eq!(1, num_deleted);
   }
 }
@@ -1,6 +1,18 @@
 use super::post_view::post_fast_view::BoxedQuery;
-use crate::{fuzzy_search, limit_and_offset, ListingType, MaybeOptional, SortType};
+use crate::{
+  clean_url_domain,
+  controversy_rank,
+  keyset_filter,
+  limit_and_offset,
+  schema::{custom_feed_community, post_flair, post_hide, user_, user_follower},
+  user_muted_keyword::muted_keyword_sql,
+  ListingCursor,
+  ListingType,
+  MaybeOptional,
+  SortType,
+};
 use diesel::{dsl::*, pg::Pg, result::Error, *};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 // The faked schema since diesel doesn't do views
@@ -25,11 +37,17 @@ table! {
     thumbnail_url -> Nullable<Text>,
     ap_id -> Text,
     local -> Bool,
+    archived -> Bool,
+    pending -> Bool,
+    embed_type -> Nullable<Text>,
+    url_media_type -> Nullable<Text>,
     creator_actor_id -> Text,
     creator_local -> Bool,
     creator_name -> Varchar,
     creator_published -> Timestamp,
     creator_avatar -> Nullable<Text>,
+    creator_bot_account -> Bool,
+    creator_display_name -> Nullable<Varchar>,
     banned -> Bool,
     banned_from_community -> Bool,
     community_actor_id -> Text,
@@ -73,11 +91,17 @@ table! {
     thumbnail_url -> Nullable<Text>,
     ap_id -> Text,
     local -> Bool,
+    archived -> Bool,
+    pending -> Bool,
+    embed_type -> Nullable<Text>,
+    url_media_type -> Nullable<Text>,
     creator_actor_id -> Text,
     creator_local -> Bool,
     creator_name -> Varchar,
     creator_published -> Timestamp,
     creator_avatar -> Nullable<Text>,
+    creator_bot_account -> Bool,
+    creator_display_name -> Nullable<Varchar>,
     banned -> Bool,
     banned_from_community -> Bool,
     community_actor_id -> Text,
@@ -100,8 +124,23 @@ table! {
   }
 }
 
+// Needed to filter the view by flair, or by custom feed
+allow_tables_to_appear_in_same_query!(post_fast_view, post_flair);
+allow_tables_to_appear_in_same_query!(post_fast_view, custom_feed_community);
+allow_tables_to_appear_in_same_query!(post_fast_view, post_hide);
+allow_tables_to_appear_in_same_query!(post_fast_view, user_);
+allow_tables_to_appear_in_same_query!(post_fast_view, user_follower);
+
 #[derive(
-  Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, QueryableByName, Clone,
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
 )]
 #[table_name = "post_fast_view"]
 pub struct PostView {
@@ -124,11 +163,17 @@ pub struct PostView {
   pub thumbnail_url: Option<String>,
   pub ap_id: String,
   pub local: bool,
+  pub archived: bool,
+  pub pending: bool,
+  pub embed_type: Option<String>,
+  pub url_media_type: Option<String>,
   pub creator_actor_id: String,
   pub creator_local: bool,
   pub creator_name: String,
   pub creator_published: chrono::NaiveDateTime,
   pub creator_avatar: Option<String>,
+  pub creator_bot_account: bool,
+  pub creator_display_name: Option<String>,
   pub banned: bool,
   pub banned_from_community: bool,
   pub community_actor_id: String,
@@ -139,7 +184,9 @@ pub struct PostView {
   pub community_nsfw: bool,
   pub number_of_comments: i64,
   pub score: i64,
+  #[serde(default, skip_serializing_if = "crate::vote_counts_hidden")]
   pub upvotes: i64,
+  #[serde(default, skip_serializing_if = "crate::vote_counts_hidden")]
   pub downvotes: i64,
   pub hot_rank: i32,
   pub newest_activity_time: chrono::NaiveDateTime,
@@ -159,11 +206,21 @@ pub struct PostQueryBuilder<'a> {
   for_creator_id: Option<i32>,
   for_community_id: Option<i32>,
   for_community_name: Option<String>,
+  for_flair_id: Option<i32>,
+  for_custom_feed_id: Option<i32>,
   search_term: Option<String>,
   url_search: Option<String>,
+  url_domain: Option<String>,
+  published_after: Option<chrono::NaiveDateTime>,
+  published_before: Option<chrono::NaiveDateTime>,
   show_nsfw: bool,
   saved_only: bool,
   unread_only: bool,
+  hidden_only: bool,
+  followed_users_only: bool,
+  hide_bot_accounts: bool,
+  pending_only: bool,
+  cursor: Option<ListingCursor>,
   page: Option<i64>,
   limit: Option<i64>,
 }
@@ -183,11 +240,21 @@ impl<'a> PostQueryBuilder<'a> {
       for_creator_id: None,
       for_community_id: None,
       for_community_name: None,
+      for_flair_id: None,
+      for_custom_feed_id: None,
       search_term: None,
       url_search: None,
+      url_domain: None,
+      published_after: None,
+      published_before: None,
       show_nsfw: true,
       saved_only: false,
       unread_only: false,
+      hidden_only: false,
+      followed_users_only: false,
+      hide_bot_accounts: false,
+      pending_only: false,
+      cursor: None,
       page: None,
       limit: None,
     }
@@ -213,6 +280,16 @@ impl<'a> PostQueryBuilder<'a> {
     self
   }
 
+  pub fn for_flair_id<T: MaybeOptional<i32>>(mut self, for_flair_id: T) -> Self {
+    self.for_flair_id = for_flair_id.get_optional();
+    self
+  }
+
+  pub fn for_custom_feed_id<T: MaybeOptional<i32>>(mut self, for_custom_feed_id: T) -> Self {
+    self.for_custom_feed_id = for_custom_feed_id.get_optional();
+    self
+  }
+
   pub fn for_creator_id<T: MaybeOptional<i32>>(mut self, for_creator_id: T) -> Self {
     self.for_creator_id = for_creator_id.get_optional();
     self
@@ -228,6 +305,27 @@ impl<'a> PostQueryBuilder<'a> {
     self
   }
 
+  pub fn url_domain<T: MaybeOptional<String>>(mut self, url_domain: T) -> Self {
+    self.url_domain = url_domain.get_optional();
+    self
+  }
+
+  pub fn published_after<T: MaybeOptional<chrono::NaiveDateTime>>(
+    mut self,
+    published_after: T,
+  ) -> Self {
+    self.published_after = published_after.get_optional();
+    self
+  }
+
+  pub fn published_before<T: MaybeOptional<chrono::NaiveDateTime>>(
+    mut self,
+    published_before: T,
+  ) -> Self {
+    self.published_before = published_before.get_optional();
+    self
+  }
+
   pub fn my_user_id<T: MaybeOptional<i32>>(mut self, my_user_id: T) -> Self {
     self.my_user_id = my_user_id.get_optional();
     self
@@ -248,6 +346,34 @@ impl<'a> PostQueryBuilder<'a> {
     self
   }
 
+  pub fn hidden_only(mut self, hidden_only: bool) -> Self {
+    self.hidden_only = hidden_only;
+    self
+  }
+
+  /// Only the posts of the users `my_user_id` follows
+  pub fn followed_users_only(mut self, followed_users_only: bool) -> Self {
+    self.followed_users_only = followed_users_only;
+    self
+  }
+
+  pub fn hide_bot_accounts(mut self, hide_bot_accounts: bool) -> Self {
+    self.hide_bot_accounts = hide_bot_accounts;
+    self
+  }
+
+  pub fn pending_only(mut self, pending_only: bool) -> Self {
+    self.pending_only = pending_only;
+    self
+  }
+
+  /// Lists the posts after the cursor instead of a page. Not used with a search term, which
+  /// orders by how well they match.
+  pub fn cursor<T: MaybeOptional<ListingCursor>>(mut self, cursor: T) -> Self {
+    self.cursor = cursor.get_optional();
+    self
+  }
+
   pub fn page<T: MaybeOptional<i64>>(mut self, page: T) -> Self {
     self.page = page.get_optional();
     self
@@ -262,6 +388,32 @@ impl<'a> PostQueryBuilder<'a> {
     use super::post_view::post_fast_view::dsl::*;
 
     let mut query = self.query;
+    let for_community = self.for_community_id.is_some() || self.for_community_name.is_some();
+    let cursor = if self.search_term.is_none() {
+      self.cursor
+    } else {
+      None
+    };
+
+    // Full text search on the post table, ranking the best matches first
+    if let Some(search_term) = self.search_term {
+      query = query
+        .filter(
+          sql::<sql_types::Bool>(
+            "post_fast_view.id in (select p.id from post p \
+            where p.search_vector @@ websearch_to_tsquery('english', ",
+          )
+          .bind::<sql_types::Text, _>(search_term.to_owned())
+          .sql("))"),
+        )
+        .then_order_by(
+          sql::<sql_types::Float>(
+            "(select ts_rank(p.search_vector, websearch_to_tsquery('english', ",
+          )
+          .bind::<sql_types::Text, _>(search_term)
+          .sql(")) from post p where p.id = post_fast_view.id) desc"),
+        );
+    }
 
     if let ListingType::Subscribed = self.listing_type {
       query = query.filter(subscribed.eq(true));
@@ -277,15 +429,48 @@ impl<'a> PostQueryBuilder<'a> {
       query = query.then_order_by(stickied.desc());
     }
 
+    if let Some(for_flair_id) = self.for_flair_id {
+      query = query.filter(
+        id.eq_any(
+          post_flair::table
+            .filter(post_flair::flair_id.eq(for_flair_id))
+            .select(post_flair::post_id),
+        ),
+      );
+    }
+
+    if let Some(for_custom_feed_id) = self.for_custom_feed_id {
+      query = query.filter(
+        community_id.eq_any(
+          custom_feed_community::table
+            .filter(custom_feed_community::custom_feed_id.eq(for_custom_feed_id))
+            .select(custom_feed_community::community_id),
+        ),
+      );
+    }
+
     if let Some(url_search) = self.url_search {
       query = query.filter(url.eq(url_search));
     }
 
-    if let Some(search_term) = self.search_term {
-      let searcher = fuzzy_search(&search_term);
-      query = query
-        .filter(name.ilike(searcher.to_owned()))
-        .or_filter(body.ilike(searcher));
+    // Matches the host of the url against the domain, or any of its subdomains
+    if let Some(url_domain) = self.url_domain {
+      let url_domain = clean_url_domain(&url_domain);
+      query = query.filter(
+        sql::<sql_types::Bool>("(lower(substring(url from '://([^/:?#]+)')) = ")
+          .bind::<sql_types::Text, _>(url_domain.to_owned())
+          .sql(" or lower(substring(url from '://([^/:?#]+)')) like ")
+          .bind::<sql_types::Text, _>(format!("%.{}", url_domain))
+          .sql(")"),
+      );
+    }
+
+    if let Some(published_after) = self.published_after {
+      query = query.filter(published.ge(published_after));
+    }
+
+    if let Some(published_before) = self.published_before {
+      query = query.filter(published.lt(published_before));
     }
 
     query = match self.sort {
@@ -306,8 +491,71 @@ impl<'a> PostQueryBuilder<'a> {
       SortType::TopDay => query
         .filter(published.gt(now - 1.days()))
         .then_order_by(score.desc()),
+      SortType::Controversial => query
+        .then_order_by(controversy_rank(upvotes, downvotes).desc())
+        .then_order_by(published.desc()),
+      SortType::TopHour => query
+        .filter(published.gt(now - 1.hours()))
+        .then_order_by(score.desc()),
+      SortType::TopSixHour => query
+        .filter(published.gt(now - 6.hours()))
+        .then_order_by(score.desc()),
+      SortType::TopTwelveHour => query
+        .filter(published.gt(now - 12.hours()))
+        .then_order_by(score.desc()),
+      SortType::MostComments => query
+        .then_order_by(number_of_comments.desc())
+        .then_order_by(published.desc()),
     };
 
+    // So the cursor always points at the same place
+    query = query.then_order_by(id.desc());
+
+    if let Some(cursor) = &cursor {
+      let pinned_column = if for_community {
+        Some("stickied")
+      } else {
+        None
+      };
+      query = query.filter(keyset_filter(
+        "post_fast_view",
+        pinned_column,
+        self.sort,
+        cursor,
+      ));
+    }
+
+    // Hidden posts are left out of every listing, unless they're the ones being reviewed
+    if let Some(my_user_id) = self.my_user_id {
+      let hidden_post_ids = post_hide::table
+        .filter(post_hide::user_id.eq(my_user_id))
+        .select(post_hide::post_id);
+      query = if self.hidden_only {
+        query.filter(id.eq_any(hidden_post_ids))
+      } else {
+        query.filter(id.ne_all(hidden_post_ids))
+      };
+
+      // So are the ones with a keyword the user muted, except for their own
+      query = query.filter(
+        creator_id.eq(my_user_id).or(
+          sql::<sql_types::Bool>(&format!(
+            "not {}",
+            muted_keyword_sql(true, &["post_fast_view.name", "post_fast_view.body"])
+          ))
+          .bind::<sql_types::Integer, _>(my_user_id)
+          .sql(")"),
+        ),
+      );
+
+      if self.followed_users_only {
+        let followed_ids = user_follower::table
+          .filter(user_follower::follower_id.eq(my_user_id))
+          .select(user_follower::user_id);
+        query = query.filter(creator_id.eq_any(followed_ids));
+      }
+    }
+
     // The view lets you pass a null user_id, if you're not logged in
     query = if let Some(my_user_id) = self.my_user_id {
       query.filter(user_id.eq(my_user_id))
@@ -315,6 +563,35 @@ impl<'a> PostQueryBuilder<'a> {
       query.filter(user_id.is_null())
     };
 
+    // Shadowbanned users' content is only listed for themselves and the admins
+    let shadow_banned_ids = user_::table
+      .filter(user_::shadow_banned.eq(true))
+      .select(user_::id);
+    query = if let Some(my_user_id) = self.my_user_id {
+      let is_admin = exists(
+        user_::table
+          .filter(user_::id.eq(my_user_id))
+          .filter(user_::admin.eq(true)),
+      );
+      query.filter(
+        creator_id
+          .ne_all(shadow_banned_ids)
+          .or(creator_id.eq(my_user_id))
+          .or(is_admin),
+      )
+    } else {
+      query.filter(creator_id.ne_all(shadow_banned_ids))
+    };
+
+    // Posts waiting for approval are only listed for their creator, and in the mod queue
+    query = if self.pending_only {
+      query.filter(pending.eq(true))
+    } else if let Some(my_user_id) = self.my_user_id {
+      query.filter(pending.eq(false).or(creator_id.eq(my_user_id)))
+    } else {
+      query.filter(pending.eq(false))
+    };
+
     // If its for a specific user, show the removed / deleted
     if let Some(for_creator_id) = self.for_creator_id {
       query = query.filter(creator_id.eq(for_creator_id));
@@ -341,10 +618,16 @@ impl<'a> PostQueryBuilder<'a> {
       query = query.filter(read.eq(false));
     };
 
+    if self.hide_bot_accounts {
+      query = query.filter(creator_bot_account.eq(false));
+    };
+
     let (limit, offset) = limit_and_offset(self.page, self.limit);
+    query = query.limit(limit);
+    if cursor.is_none() {
+      query = query.offset(offset);
+    }
     query = query
-      .limit(limit)
-      .offset(offset)
       .filter(removed.eq(false))
       .filter(deleted.eq(false))
       .filter(community_removed.eq(false))
@@ -355,6 +638,35 @@ impl<'a> PostQueryBuilder<'a> {
 }
 
 impl PostView {
+  /// Recalculates the hot ranks of the posts published since then. Older posts have decayed so far
+  /// that their rank doesn't change anymore.
+  pub fn update_hot_ranks(
+    conn: &PgConnection,
+    since: chrono::NaiveDateTime,
+  ) -> Result<usize, Error> {
+    sql_query(
+      "update post_aggregates_fast \
+      set hot_rank = hot_rank(score, newest_activity_time) \
+      where published > $1",
+    )
+    .bind::<sql_types::Timestamp, _>(since)
+    .execute(conn)
+  }
+
+  /// Where a listing that ends with this post picks up again.
+  pub fn cursor(&self) -> ListingCursor {
+    ListingCursor {
+      pinned: self.stickied,
+      hot_rank: self.hot_rank,
+      score: self.score,
+      upvotes: self.upvotes,
+      downvotes: self.downvotes,
+      number_of_comments: self.number_of_comments,
+      published: self.published,
+      id: self.id,
+    }
+  }
+
   pub fn read(
     conn: &PgConnection,
     from_post_id: i32,
@@ -421,6 +733,12 @@ mod tests {
       private_key: None,
       public_key: None,
       last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
     };
 
     let inserted_user = User_::create(&conn, &new_user).unwrap();
@@ -441,6 +759,15 @@ mod tests {
       public_key: None,
       last_refreshed_at: None,
       published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
     };
 
     let inserted_community = Community::create(&conn, &new_community).unwrap();
@@ -460,10 +787,13 @@ mod tests {
       embed_title: None,
       embed_description: None,
       embed_html: None,
+      embed_type: None,
+      url_media_type: None,
       thumbnail_url: None,
       ap_id: "http://fake.com".into(),
       local: true,
       published: None,
+      pending: None,
     };
 
     let inserted_post = Post::create(&conn, &new_post).unwrap();
@@ -505,10 +835,22 @@ mod tests {
       .list()
       .unwrap();
 
+    // The only post is on the first page, so there's nothing after it
+    let read_post_listings_after = PostQueryBuilder::create(&conn)
+      .listing_type(ListingType::Community)
+      .sort(&SortType::Hot)
+      .for_community_id(inserted_community.id)
+      .cursor(read_post_listings_no_user[0].cursor())
+      .list()
+      .unwrap();
+
     let read_post_listing_no_user = PostView::read(&conn, inserted_post.id, None).unwrap();
     let read_post_listing_with_user =
       PostView::read(&conn, inserted_post.id, Some(inserted_user.id)).unwrap();
 
+    let updated_hot_ranks =
+      PostView::update_hot_ranks(&conn, naive_now() - chrono::Duration::days(1)).unwrap();
+
     // the non user version
     let expected_post_listing_no_user = PostView {
       user_id: None,
@@ -521,6 +863,8 @@ mod tests {
       creator_name: user_name.to_owned(),
       creator_published: inserted_user.published,
       creator_avatar: None,
+      creator_bot_account: false,
+      creator_display_name: None,
       banned: false,
       banned_from_community: false,
       community_id: inserted_community.id,
@@ -547,9 +891,13 @@ mod tests {
       embed_title: None,
       embed_description: None,
       embed_html: None,
+      embed_type: None,
+      url_media_type: None,
       thumbnail_url: None,
       ap_id: "http://fake.com".to_string(),
       local: true,
+      archived: false,
+      pending: false,
       creator_actor_id: inserted_user.actor_id.to_owned(),
       creator_local: true,
       community_actor_id: inserted_community.actor_id.to_owned(),
@@ -571,6 +919,8 @@ mod tests {
       creator_name: user_name,
       creator_published: inserted_user.published,
       creator_avatar: None,
+      creator_bot_account: false,
+      creator_display_name: None,
       banned: false,
       banned_from_community: false,
       community_id: inserted_community.id,
@@ -593,9 +943,13 @@ mod tests {
       embed_title: None,
       embed_description: None,
       embed_html: None,
+      embed_type: None,
+      url_media_type: None,
       thumbnail_url: None,
       ap_id: "http://fake.com".to_string(),
       local: true,
+      archived: false,
+      pending: false,
       creator_actor_id: inserted_user.actor_id.to_owned(),
       creator_local: true,
       community_actor_id: inserted_community.actor_id.to_owned(),
@@ -619,6 +973,8 @@ mod tests {
     assert_eq!(expected_post_listing_no_user, read_post_listings_no_user[0]);
     assert_eq!(expected_post_listing_no_user, read_post_listing_no_user);
     assert_eq!(1, read_post_listings_no_user.len());
+    assert!(read_post_listings_after.is_empty());
+    assert!(updated_hot_ranks >= 1);
 
     // assert_eq!(expected_post, inserted_post);
     // assert_eq!(expected_post, updated_post);
@@ -626,4 +982,162 @@ mod tests {
     assert_eq!(1, like_removed);
     assert_eq!(1, num_deleted);
   }
+
+  #[test]
+  fn test_controversial_sort() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "controversy_creator".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      updated: None,
+      admin: false,
+      banned: false,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+    let voters = (0..4)
+      .map(|i| {
+        let voter_form = UserForm {
+          name: format!("controversy_voter_{}", i),
+          ..new_user.clone()
+        };
+        User_::create(&conn, &voter_form).unwrap()
+      })
+      .collect::<Vec<User_>>();
+
+    let new_community = CommunityForm {
+      name: "test_controversial".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      creator_id: inserted_user.id,
+      category_id: 1,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_post = PostForm {
+      name: "A settled post".into(),
+      url: None,
+      body: None,
+      creator_id: inserted_user.id,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      embed_type: None,
+      url_media_type: None,
+      thumbnail_url: None,
+      ap_id: "http://fake.com".into(),
+      local: true,
+      published: None,
+      pending: None,
+    };
+
+    // The votes of each post, by the voters in turn
+    let votes: Vec<(&str, Vec<i16>)> = vec![
+      ("A settled post", vec![1, 1, 1, 1]),
+      ("A split post", vec![1, 1, -1, -1]),
+      ("A leaning post", vec![1, 1, -1]),
+      ("A post nobody voted on", vec![]),
+    ];
+    let inserted_posts = votes
+      .iter()
+      .map(|(name, scores)| {
+        let post_form = PostForm {
+          name: (*name).to_owned(),
+          ..new_post.clone()
+        };
+        let inserted_post = Post::create(&conn, &post_form).unwrap();
+        for (voter, score) in voters.iter().zip(scores) {
+          let post_like_form = PostLikeForm {
+            post_id: inserted_post.id,
+            user_id: voter.id,
+            score: *score,
+          };
+          PostLike::like(&conn, &post_like_form).unwrap();
+        }
+        inserted_post
+      })
+      .collect::<Vec<Post>>();
+
+    let controversial_names = PostQueryBuilder::create(&conn)
+      .listing_type(ListingType::Community)
+      .sort(&SortType::Controversial)
+      .for_community_id(inserted_community.id)
+      .list()
+      .unwrap()
+      .into_iter()
+      .map(|post| post.name)
+      .collect::<Vec<String>>();
+
+    for inserted_post in &inserted_posts {
+      Post::delete(&conn, inserted_post.id).unwrap();
+    }
+    Community::delete(&conn, inserted_community.id).unwrap();
+    for voter in &voters {
+      User_::delete(&conn, voter.id).unwrap();
+    }
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    // The most votes, split the most evenly, first. The ones without votes against are as
+    // controversial as each other, and the newest of them comes first.
+    assert_eq!(
+      vec![
+        "A split post",
+        "A leaning post",
+        "A post nobody voted on",
+        "A settled post",
+      ],
+      controversial_names
+    );
+  }
 }
@@ -0,0 +1,218 @@
+use crate::{naive_now, schema::posting_requirement, Crud};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// What an account needs before it can post, either site wide or in a community. With links_only,
+/// text posts are let through, and only links have to wait.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "posting_requirement"]
+pub struct PostingRequirement {
+  pub id: i32,
+  pub community_id: Option<i32>,
+  pub min_account_age_days: i32,
+  pub min_comments: i32,
+  pub min_comment_score: i32,
+  pub links_only: bool,
+  pub published: chrono::NaiveDateTime,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "posting_requirement"]
+pub struct PostingRequirementForm {
+  pub community_id: Option<i32>,
+  pub min_account_age_days: i32,
+  pub min_comments: i32,
+  pub min_comment_score: i32,
+  pub links_only: bool,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+impl Crud<PostingRequirementForm> for PostingRequirement {
+  fn read(conn: &PgConnection, posting_requirement_id: i32) -> Result<Self, Error> {
+    use crate::schema::posting_requirement::dsl::*;
+    posting_requirement
+      .find(posting_requirement_id)
+      .first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, posting_requirement_id: i32) -> Result<usize, Error> {
+    use crate::schema::posting_requirement::dsl::*;
+    diesel::delete(posting_requirement.find(posting_requirement_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &PostingRequirementForm) -> Result<Self, Error> {
+    use crate::schema::posting_requirement::dsl::*;
+    insert_into(posting_requirement)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    posting_requirement_id: i32,
+    form: &PostingRequirementForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::posting_requirement::dsl::*;
+    diesel::update(posting_requirement.find(posting_requirement_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl PostingRequirement {
+  /// The site wide requirements when no community is given
+  pub fn read_for(conn: &PgConnection, for_community_id: Option<i32>) -> Result<Self, Error> {
+    use crate::schema::posting_requirement::dsl::*;
+    let query = posting_requirement.into_boxed();
+    let query = match for_community_id {
+      Some(for_community_id) => query.filter(community_id.eq(for_community_id)),
+      None => query.filter(community_id.is_null()),
+    };
+    query.first::<Self>(conn)
+  }
+
+  /// The site wide requirements, plus the ones of the community
+  pub fn applicable(conn: &PgConnection, for_community_id: i32) -> Result<Vec<Self>, Error> {
+    use crate::schema::posting_requirement::dsl::*;
+    posting_requirement
+      .filter(community_id.is_null().or(community_id.eq(for_community_id)))
+      .load::<Self>(conn)
+  }
+
+  /// Replaces the requirements of the site or the community, creating them the first time.
+  pub fn set(conn: &PgConnection, form: &PostingRequirementForm) -> Result<Self, Error> {
+    conn.transaction::<_, Error, _>(|| match Self::read_for(conn, form.community_id) {
+      Ok(existing) => Self::update(
+        conn,
+        existing.id,
+        &PostingRequirementForm {
+          updated: Some(naive_now()),
+          ..form.to_owned()
+        },
+      ),
+      Err(Error::NotFound) => Self::create(conn, form),
+      Err(e) => Err(e),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{community::*, tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "requirement_setter".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_requirement".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_user.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let requirement_form = PostingRequirementForm {
+      community_id: Some(inserted_community.id),
+      min_account_age_days: 3,
+      min_comments: 5,
+      min_comment_score: 0,
+      links_only: true,
+      updated: None,
+    };
+
+    let inserted_requirement = PostingRequirement::set(&conn, &requirement_form).unwrap();
+
+    let expected_requirement = PostingRequirement {
+      id: inserted_requirement.id,
+      community_id: Some(inserted_community.id),
+      min_account_age_days: 3,
+      min_comments: 5,
+      min_comment_score: 0,
+      links_only: true,
+      published: inserted_requirement.published,
+      updated: None,
+    };
+
+    let updated_requirement = PostingRequirement::set(
+      &conn,
+      &PostingRequirementForm {
+        min_comments: 10,
+        ..requirement_form
+      },
+    )
+    .unwrap();
+    let read_requirement =
+      PostingRequirement::read_for(&conn, Some(inserted_community.id)).unwrap();
+    let applicable_requirements =
+      PostingRequirement::applicable(&conn, inserted_community.id).unwrap();
+    let num_deleted = PostingRequirement::delete(&conn, inserted_requirement.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(expected_requirement, inserted_requirement);
+    assert_eq!(inserted_requirement.id, updated_requirement.id);
+    assert_eq!(10, updated_requirement.min_comments);
+    assert!(updated_requirement.updated.is_some());
+    assert_eq!(updated_requirement, read_requirement);
+    assert!(applicable_requirements.contains(&updated_requirement));
+    assert_eq!(1, num_deleted);
+  }
+}
@@ -1,8 +1,12 @@
-use crate::{schema::private_message, Crud};
+use crate::{
+  schema::{private_message, private_message_conversation},
+  Crud,
+};
 use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 #[table_name = "private_message"]
 pub struct PrivateMessage {
   pub id: i32,
@@ -15,6 +19,7 @@ pub struct PrivateMessage {
   pub updated: Option<chrono::NaiveDateTime>,
   pub ap_id: String,
   pub local: bool,
+  pub conversation_id: i32,
 }
 
 #[derive(Insertable, AsChangeset, Clone)]
@@ -80,6 +85,42 @@ impl PrivateMessage {
       .filter(ap_id.eq(object_id))
       .first::<Self>(conn)
   }
+
+  pub fn mark_all_as_read(conn: &PgConnection, for_recipient_id: i32) -> Result<Vec<Self>, Error> {
+    use crate::schema::private_message::dsl::*;
+    diesel::update(
+      private_message
+        .filter(recipient_id.eq(for_recipient_id))
+        .filter(read.eq(false)),
+    )
+    .set(read.eq(true))
+    .get_results::<Self>(conn)
+  }
+}
+
+/// Every message between two users belongs to one conversation. They're created by the database
+/// when the first message between the pair is inserted.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone)]
+#[table_name = "private_message_conversation"]
+pub struct PrivateMessageConversation {
+  pub id: i32,
+  pub user_a_id: i32,
+  pub user_b_id: i32,
+  pub published: chrono::NaiveDateTime,
+  pub last_message_at: chrono::NaiveDateTime,
+}
+
+impl PrivateMessageConversation {
+  pub fn read(conn: &PgConnection, conversation_id: i32) -> Result<Self, Error> {
+    use crate::schema::private_message_conversation::dsl::*;
+    private_message_conversation
+      .find(conversation_id)
+      .first::<Self>(conn)
+  }
+
+  pub fn is_participant(&self, user_id: i32) -> bool {
+    self.user_a_id == user_id || self.user_b_id == user_id
+  }
 }
 
 #[cfg(test)]
@@ -119,6 +160,12 @@ mod tests {
       private_key: None,
       public_key: None,
       last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
     };
 
     let inserted_creator = User_::create(&conn, &creator_form).unwrap();
@@ -146,6 +193,12 @@ mod tests {
       private_key: None,
       public_key: None,
       last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
     };
 
     let inserted_recipient = User_::create(&conn, &recipient_form).unwrap();
@@ -175,8 +228,14 @@ mod tests {
       published: inserted_private_message.published,
       ap_id: "http://fake.com".into(),
       local: true,
+      conversation_id: inserted_private_message.conversation_id,
     };
 
+    let read_conversation =
+      PrivateMessageConversation::read(&conn, inserted_private_message.conversation_id).unwrap();
+    let second_private_message = PrivateMessage::create(&conn, &private_message_form).unwrap();
+    PrivateMessage::delete(&conn, second_private_message.id).unwrap();
+
     let read_private_message = PrivateMessage::read(&conn, inserted_private_message.id).unwrap();
     let updated_private_message =
       PrivateMessage::update(&conn, inserted_private_message.id, &private_message_form).unwrap();
@@ -187,6 +246,15 @@ mod tests {
     assert_eq!(expected_private_message, read_private_message);
     assert_eq!(expected_private_message, updated_private_message);
     assert_eq!(expected_private_message, inserted_private_message);
+    assert_eq!(
+      inserted_creator.id.min(inserted_recipient.id),
+      read_conversation.user_a_id
+    );
+    assert!(read_conversation.is_participant(inserted_recipient.id));
+    assert_eq!(
+      inserted_private_message.conversation_id,
+      second_private_message.conversation_id
+    );
     assert_eq!(1, num_deleted);
   }
 }
@@ -0,0 +1,121 @@
+use crate::{limit_and_offset, MaybeOptional};
+use diesel::{pg::Pg, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// The faked schema since diesel doesn't do views
+table! {
+  private_message_conversation_view (id, user_id) {
+    id -> Int4,
+    published -> Timestamp,
+    last_message_at -> Timestamp,
+    user_id -> Int4,
+    other_user_id -> Int4,
+    other_user_name -> Varchar,
+    other_user_avatar -> Nullable<Text>,
+    other_user_actor_id -> Text,
+    other_user_local -> Bool,
+    unread_count -> BigInt,
+  }
+}
+
+#[derive(
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
+)]
+#[table_name = "private_message_conversation_view"]
+#[primary_key(id, user_id)]
+pub struct PrivateMessageConversationView {
+  pub id: i32,
+  pub published: chrono::NaiveDateTime,
+  pub last_message_at: chrono::NaiveDateTime,
+  pub user_id: i32,
+  pub other_user_id: i32,
+  pub other_user_name: String,
+  pub other_user_avatar: Option<String>,
+  pub other_user_actor_id: String,
+  pub other_user_local: bool,
+  pub unread_count: i64,
+}
+
+pub struct PrivateMessageConversationQueryBuilder<'a> {
+  conn: &'a PgConnection,
+  query:
+    super::private_message_conversation_view::private_message_conversation_view::BoxedQuery<'a, Pg>,
+  for_user_id: i32,
+  unread_only: bool,
+  page: Option<i64>,
+  limit: Option<i64>,
+}
+
+impl<'a> PrivateMessageConversationQueryBuilder<'a> {
+  pub fn create(conn: &'a PgConnection, for_user_id: i32) -> Self {
+    use super::private_message_conversation_view::private_message_conversation_view::dsl::*;
+
+    let query = private_message_conversation_view.into_boxed();
+
+    PrivateMessageConversationQueryBuilder {
+      conn,
+      query,
+      for_user_id,
+      unread_only: false,
+      page: None,
+      limit: None,
+    }
+  }
+
+  pub fn unread_only(mut self, unread_only: bool) -> Self {
+    self.unread_only = unread_only;
+    self
+  }
+
+  pub fn page<T: MaybeOptional<i64>>(mut self, page: T) -> Self {
+    self.page = page.get_optional();
+    self
+  }
+
+  pub fn limit<T: MaybeOptional<i64>>(mut self, limit: T) -> Self {
+    self.limit = limit.get_optional();
+    self
+  }
+
+  pub fn list(self) -> Result<Vec<PrivateMessageConversationView>, Error> {
+    use super::private_message_conversation_view::private_message_conversation_view::dsl::*;
+
+    let mut query = self.query.filter(user_id.eq(self.for_user_id));
+
+    if self.unread_only {
+      query = query.filter(unread_count.gt(0));
+    }
+
+    let (limit, offset) = limit_and_offset(self.page, self.limit);
+
+    query
+      .limit(limit)
+      .offset(offset)
+      .order_by(last_message_at.desc())
+      .load::<PrivateMessageConversationView>(self.conn)
+  }
+}
+
+impl PrivateMessageConversationView {
+  pub fn read(
+    conn: &PgConnection,
+    from_conversation_id: i32,
+    for_user_id: i32,
+  ) -> Result<Self, Error> {
+    use super::private_message_conversation_view::private_message_conversation_view::dsl::*;
+
+    private_message_conversation_view
+      .filter(id.eq(from_conversation_id))
+      .filter(user_id.eq(for_user_id))
+      .first::<Self>(conn)
+  }
+}
@@ -1,5 +1,6 @@
 use crate::{limit_and_offset, MaybeOptional};
 use diesel::{pg::Pg, result::Error, *};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 // The faked schema since diesel doesn't do views
@@ -15,6 +16,7 @@ table! {
     updated -> Nullable<Timestamp>,
     ap_id -> Text,
     local -> Bool,
+    conversation_id -> Int4,
     creator_name -> Varchar,
     creator_avatar -> Nullable<Text>,
     creator_actor_id -> Text,
@@ -27,7 +29,15 @@ table! {
 }
 
 #[derive(
-  Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, QueryableByName, Clone,
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
 )]
 #[table_name = "private_message_view"]
 pub struct PrivateMessageView {
@@ -41,6 +51,7 @@ pub struct PrivateMessageView {
   pub updated: Option<chrono::NaiveDateTime>,
   pub ap_id: String,
   pub local: bool,
+  pub conversation_id: i32,
   pub creator_name: String,
   pub creator_avatar: Option<String>,
   pub creator_actor_id: String,
@@ -55,6 +66,7 @@ pub struct PrivateMessageQueryBuilder<'a> {
   conn: &'a PgConnection,
   query: super::private_message_view::private_message_view::BoxedQuery<'a, Pg>,
   for_recipient_id: i32,
+  for_conversation_id: Option<i32>,
   unread_only: bool,
   page: Option<i64>,
   limit: Option<i64>,
@@ -70,6 +82,7 @@ impl<'a> PrivateMessageQueryBuilder<'a> {
       conn,
       query,
       for_recipient_id,
+      for_conversation_id: None,
       unread_only: false,
       page: None,
       limit: None,
@@ -81,6 +94,11 @@ impl<'a> PrivateMessageQueryBuilder<'a> {
     self
   }
 
+  pub fn for_conversation_id<T: MaybeOptional<i32>>(mut self, for_conversation_id: T) -> Self {
+    self.for_conversation_id = for_conversation_id.get_optional();
+    self
+  }
+
   pub fn page<T: MaybeOptional<i64>>(mut self, page: T) -> Self {
     self.page = page.get_optional();
     self
@@ -96,6 +114,10 @@ impl<'a> PrivateMessageQueryBuilder<'a> {
 
     let mut query = self.query.filter(deleted.eq(false));
 
+    if let Some(for_conversation_id) = self.for_conversation_id {
+      query = query.filter(conversation_id.eq(for_conversation_id));
+    }
+
     // If its unread, I only want the ones to me
     if self.unread_only {
       query = query
@@ -133,4 +155,14 @@ impl PrivateMessageView {
 
     query.first::<Self>(conn)
   }
+
+  pub fn count_unread(conn: &PgConnection, for_recipient_id: i32) -> Result<i64, Error> {
+    use super::private_message_view::private_message_view::dsl::*;
+    private_message_view
+      .filter(recipient_id.eq(for_recipient_id))
+      .filter(read.eq(false))
+      .filter(deleted.eq(false))
+      .count()
+      .get_result::<i64>(conn)
+  }
 }
@@ -0,0 +1,374 @@
+use crate::{
+  comment::Comment,
+  moderator::{ModPurgeForm, PurgeTargetType},
+  post::Post,
+  schema::pictrs_thumbnail,
+  Crud,
+};
+use diesel::{dsl::*, result::Error, *};
+use serde::{Deserialize, Serialize};
+
+/// A thumbnail lemmy had pictrs make, with the token that deletes it again.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone)]
+#[table_name = "pictrs_thumbnail"]
+pub struct PictrsThumbnail {
+  pub id: i32,
+  pub file: String,
+  pub delete_token: String,
+  pub published: chrono::NaiveDateTime,
+  pub nsfw_score: Option<f32>,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "pictrs_thumbnail"]
+pub struct PictrsThumbnailForm {
+  pub file: String,
+  pub delete_token: String,
+  pub nsfw_score: Option<f32>,
+}
+
+impl PictrsThumbnail {
+  pub fn create(conn: &PgConnection, form: &PictrsThumbnailForm) -> Result<Self, Error> {
+    use crate::schema::pictrs_thumbnail::dsl::*;
+    insert_into(pictrs_thumbnail)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  /// Only the thumbnails made since lemmy started keeping the tokens are found.
+  pub fn for_files(conn: &PgConnection, files: Vec<String>) -> Result<Vec<Self>, Error> {
+    use crate::schema::pictrs_thumbnail::dsl::*;
+    pictrs_thumbnail
+      .filter(file.eq_any(files))
+      .load::<Self>(conn)
+  }
+
+  pub fn delete(conn: &PgConnection, thumbnail_id: i32) -> Result<usize, Error> {
+    use crate::schema::pictrs_thumbnail::dsl::*;
+    diesel::delete(pictrs_thumbnail.find(thumbnail_id)).execute(conn)
+  }
+}
+
+/// The local posts and comments of the user, which the other instances have to be told about
+/// before they're gone.
+pub fn local_content_of_user(
+  conn: &PgConnection,
+  user_id: i32,
+) -> Result<(Vec<Post>, Vec<Comment>), Error> {
+  use crate::schema::{comment, post};
+  let posts = post::table
+    .filter(post::creator_id.eq(user_id))
+    .filter(post::local.eq(true))
+    .load::<Post>(conn)?;
+  let comments = comment::table
+    .filter(comment::creator_id.eq(user_id))
+    .filter(comment::local.eq(true))
+    .load::<Comment>(conn)?;
+  Ok((posts, comments))
+}
+
+/// Hard deletes the post, along with its comments, votes and everything else that belongs to it.
+/// Returns the thumbnail it had, which is left for the caller to delete from pictrs.
+pub fn purge_post(
+  conn: &PgConnection,
+  mod_user_id: i32,
+  post_id: i32,
+  reason: Option<String>,
+) -> Result<Vec<String>, Error> {
+  use crate::schema::{mod_purge, post};
+  conn.transaction::<_, Error, _>(|| {
+    let read_post = Post::read(conn, post_id)?;
+    diesel::delete(post::table.find(post_id)).execute(conn)?;
+
+    insert_into(mod_purge::table)
+      .values(&ModPurgeForm {
+        mod_user_id,
+        target_type: PurgeTargetType::Post as i16,
+        community_id: Some(read_post.community_id),
+        reason,
+      })
+      .execute(conn)?;
+
+    Ok(read_post.thumbnail_url.into_iter().collect())
+  })
+}
+
+/// Hard deletes the comment. Its replies go with it.
+pub fn purge_comment(
+  conn: &PgConnection,
+  mod_user_id: i32,
+  comment_id: i32,
+  reason: Option<String>,
+) -> Result<(), Error> {
+  use crate::schema::{comment, mod_purge};
+  conn.transaction::<_, Error, _>(|| {
+    let read_comment = Comment::read(conn, comment_id)?;
+    let read_post = Post::read(conn, read_comment.post_id)?;
+    diesel::delete(comment::table.find(comment_id)).execute(conn)?;
+
+    insert_into(mod_purge::table)
+      .values(&ModPurgeForm {
+        mod_user_id,
+        target_type: PurgeTargetType::Comment as i16,
+        community_id: Some(read_post.community_id),
+        reason,
+      })
+      .execute(conn)?;
+
+    Ok(())
+  })
+}
+
+/// Hard deletes the community with all of its posts. Returns their thumbnails.
+pub fn purge_community(
+  conn: &PgConnection,
+  mod_user_id: i32,
+  community_id: i32,
+  reason: Option<String>,
+) -> Result<Vec<String>, Error> {
+  use crate::schema::{community, mod_purge, post};
+  conn.transaction::<_, Error, _>(|| {
+    let thumbnails = post::table
+      .select(post::thumbnail_url)
+      .filter(post::community_id.eq(community_id))
+      .load::<Option<String>>(conn)?;
+    diesel::delete(community::table.find(community_id)).execute(conn)?;
+
+    insert_into(mod_purge::table)
+      .values(&ModPurgeForm {
+        mod_user_id,
+        target_type: PurgeTargetType::Community as i16,
+        community_id: None,
+        reason,
+      })
+      .execute(conn)?;
+
+    Ok(thumbnails.into_iter().flatten().collect())
+  })
+}
+
+/// Hard deletes the user with everything they posted. The communities they created would go too,
+/// so those are handed to the one purging first. Returns the thumbnails of their posts.
+pub fn purge_user(
+  conn: &PgConnection,
+  mod_user_id: i32,
+  user_id: i32,
+  reason: Option<String>,
+) -> Result<Vec<String>, Error> {
+  use crate::schema::{community, mod_purge, post, user_};
+  conn.transaction::<_, Error, _>(|| {
+    let thumbnails = post::table
+      .select(post::thumbnail_url)
+      .filter(post::creator_id.eq(user_id))
+      .load::<Option<String>>(conn)?;
+
+    diesel::update(community::table.filter(community::creator_id.eq(user_id)))
+      .set(community::creator_id.eq(mod_user_id))
+      .execute(conn)?;
+    diesel::delete(user_::table.find(user_id)).execute(conn)?;
+
+    insert_into(mod_purge::table)
+      .values(&ModPurgeForm {
+        mod_user_id,
+        target_type: PurgeTargetType::User as i16,
+        community_id: None,
+        reason,
+      })
+      .execute(conn)?;
+
+    Ok(thumbnails.into_iter().flatten().collect())
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    comment::*,
+    community::*,
+    moderator_views::*,
+    post::*,
+    tests::establish_unpooled_connection,
+    user::*,
+    ListingType,
+    SortType,
+  };
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "purged_spammer".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_admin = UserForm {
+      name: "purging_admin".into(),
+      admin: true,
+      ..new_user
+    };
+
+    let inserted_admin = User_::create(&conn, &new_admin).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_purge_community".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_user.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_post = PostForm {
+      name: "Spam".into(),
+      creator_id: inserted_user.id,
+      url: None,
+      body: None,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      embed_type: None,
+      url_media_type: None,
+      thumbnail_url: Some("purge_test_thumbnail.png".into()),
+      ap_id: "http://fake.com".into(),
+      local: true,
+      published: None,
+      pending: None,
+    };
+
+    let inserted_post = Post::create(&conn, &new_post).unwrap();
+    let inserted_other_post = Post::create(&conn, &new_post).unwrap();
+
+    let comment_form = CommentForm {
+      content: "More spam".into(),
+      creator_id: inserted_user.id,
+      post_id: inserted_post.id,
+      removed: None,
+      deleted: None,
+      read: None,
+      parent_id: None,
+      published: None,
+      updated: None,
+      ap_id: "http://fake.com".into(),
+      local: true,
+    };
+
+    let inserted_comment = Comment::create(&conn, &comment_form).unwrap();
+
+    let thumbnail_form = PictrsThumbnailForm {
+      file: "purge_test_thumbnail.png".into(),
+      delete_token: "token".into(),
+      nsfw_score: None,
+    };
+
+    let inserted_thumbnail = PictrsThumbnail::create(&conn, &thumbnail_form).unwrap();
+    let found_thumbnails =
+      PictrsThumbnail::for_files(&conn, vec!["purge_test_thumbnail.png".into()]).unwrap();
+
+    purge_comment(&conn, inserted_admin.id, inserted_comment.id, None).unwrap();
+    let read_comment = Comment::read(&conn, inserted_comment.id);
+    let post_thumbnails = purge_post(
+      &conn,
+      inserted_admin.id,
+      inserted_post.id,
+      Some("spam".into()),
+    )
+    .unwrap();
+    let read_post = Post::read(&conn, inserted_post.id);
+    let (user_posts, user_comments) = local_content_of_user(&conn, inserted_user.id).unwrap();
+    let user_thumbnails = purge_user(&conn, inserted_admin.id, inserted_user.id, None).unwrap();
+    let read_user = User_::read(&conn, inserted_user.id);
+    let read_other_post = Post::read(&conn, inserted_other_post.id);
+    let read_community = Community::read(&conn, inserted_community.id).unwrap();
+    let community_thumbnails =
+      purge_community(&conn, inserted_admin.id, inserted_community.id, None).unwrap();
+
+    let mod_filter = ModlogFilter {
+      mod_user_id: Some(inserted_admin.id),
+      ..ModlogFilter::default()
+    };
+    let purged_log = ModPurgeView::list(&conn, &mod_filter).unwrap();
+
+    PictrsThumbnail::delete(&conn, inserted_thumbnail.id).unwrap();
+    User_::delete(&conn, inserted_admin.id).unwrap();
+
+    assert_eq!(vec![inserted_thumbnail], found_thumbnails);
+    assert!(read_comment.is_err());
+    assert!(read_post.is_err());
+    assert_eq!(
+      vec!["purge_test_thumbnail.png".to_string()],
+      post_thumbnails
+    );
+    assert_eq!(
+      vec![inserted_other_post.id],
+      user_posts.iter().map(|p| p.id).collect::<Vec<i32>>()
+    );
+    assert!(user_comments.is_empty());
+    assert!(read_user.is_err());
+    assert_eq!(
+      vec!["purge_test_thumbnail.png".to_string()],
+      user_thumbnails
+    );
+    assert!(read_other_post.is_err());
+    assert_eq!(inserted_admin.id, read_community.creator_id);
+    assert!(community_thumbnails.is_empty());
+    assert_eq!(4, purged_log.len());
+    assert!(purged_log.iter().all(|m| m.community_id.is_none()));
+  }
+}
@@ -0,0 +1,259 @@
+use crate::{
+  schema::{rate_limit_endpoint, rate_limit_exemption},
+  Crud,
+};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A limit for a single endpoint, which then gets a bucket of its own instead of sharing the one
+/// of its rate limit type. The endpoint is a websocket op name, or an HTTP path.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "rate_limit_endpoint"]
+pub struct RateLimitEndpoint {
+  pub id: i32,
+  pub endpoint: String,
+  pub actions: i32,
+  pub per_second: i32,
+  pub creator_id: i32,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "rate_limit_endpoint"]
+pub struct RateLimitEndpointForm {
+  pub endpoint: String,
+  pub actions: i32,
+  pub per_second: i32,
+  pub creator_id: i32,
+}
+
+impl Crud<RateLimitEndpointForm> for RateLimitEndpoint {
+  fn read(conn: &PgConnection, rate_limit_endpoint_id: i32) -> Result<Self, Error> {
+    use crate::schema::rate_limit_endpoint::dsl::*;
+    rate_limit_endpoint
+      .find(rate_limit_endpoint_id)
+      .first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, rate_limit_endpoint_id: i32) -> Result<usize, Error> {
+    use crate::schema::rate_limit_endpoint::dsl::*;
+    diesel::delete(rate_limit_endpoint.find(rate_limit_endpoint_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &RateLimitEndpointForm) -> Result<Self, Error> {
+    use crate::schema::rate_limit_endpoint::dsl::*;
+    insert_into(rate_limit_endpoint)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    rate_limit_endpoint_id: i32,
+    form: &RateLimitEndpointForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::rate_limit_endpoint::dsl::*;
+    diesel::update(rate_limit_endpoint.find(rate_limit_endpoint_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl RateLimitEndpoint {
+  pub fn list(conn: &PgConnection) -> Result<Vec<Self>, Error> {
+    use crate::schema::rate_limit_endpoint::dsl::*;
+    rate_limit_endpoint.order_by(endpoint).load::<Self>(conn)
+  }
+
+  /// Sets the limit of the endpoint, replacing the one it had.
+  pub fn set(conn: &PgConnection, form: &RateLimitEndpointForm) -> Result<Self, Error> {
+    use crate::schema::rate_limit_endpoint::dsl::*;
+    insert_into(rate_limit_endpoint)
+      .values(form)
+      .on_conflict(endpoint)
+      .do_update()
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+
+  pub fn remove(conn: &PgConnection, for_endpoint: &str) -> Result<usize, Error> {
+    use crate::schema::rate_limit_endpoint::dsl::*;
+    diesel::delete(rate_limit_endpoint.filter(endpoint.eq(for_endpoint))).execute(conn)
+  }
+}
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "rate_limit_exemption"]
+pub struct RateLimitExemption {
+  pub id: i32,
+  pub user_id: i32,
+  pub creator_id: i32,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "rate_limit_exemption"]
+pub struct RateLimitExemptionForm {
+  pub user_id: i32,
+  pub creator_id: i32,
+}
+
+impl Crud<RateLimitExemptionForm> for RateLimitExemption {
+  fn read(conn: &PgConnection, rate_limit_exemption_id: i32) -> Result<Self, Error> {
+    use crate::schema::rate_limit_exemption::dsl::*;
+    rate_limit_exemption
+      .find(rate_limit_exemption_id)
+      .first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, rate_limit_exemption_id: i32) -> Result<usize, Error> {
+    use crate::schema::rate_limit_exemption::dsl::*;
+    diesel::delete(rate_limit_exemption.find(rate_limit_exemption_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &RateLimitExemptionForm) -> Result<Self, Error> {
+    use crate::schema::rate_limit_exemption::dsl::*;
+    insert_into(rate_limit_exemption)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    rate_limit_exemption_id: i32,
+    form: &RateLimitExemptionForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::rate_limit_exemption::dsl::*;
+    diesel::update(rate_limit_exemption.find(rate_limit_exemption_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl RateLimitExemption {
+  pub fn list(conn: &PgConnection) -> Result<Vec<Self>, Error> {
+    use crate::schema::rate_limit_exemption::dsl::*;
+    rate_limit_exemption
+      .order_by(published.desc())
+      .load::<Self>(conn)
+  }
+
+  /// Exempting a user that already is just keeps the existing exemption.
+  pub fn exempt(conn: &PgConnection, form: &RateLimitExemptionForm) -> Result<usize, Error> {
+    use crate::schema::rate_limit_exemption::dsl::*;
+    insert_into(rate_limit_exemption)
+      .values(form)
+      .on_conflict_do_nothing()
+      .execute(conn)
+  }
+
+  pub fn unexempt(conn: &PgConnection, for_user_id: i32) -> Result<usize, Error> {
+    use crate::schema::rate_limit_exemption::dsl::*;
+    diesel::delete(rate_limit_exemption.filter(user_id.eq(for_user_id))).execute(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_admin = UserForm {
+      name: "rate_limit_admin".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: true,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_admin = User_::create(&conn, &new_admin).unwrap();
+
+    let new_bot = UserForm {
+      name: "rate_limit_bot".into(),
+      admin: false,
+      bot_account: true,
+      ..new_admin
+    };
+
+    let inserted_bot = User_::create(&conn, &new_bot).unwrap();
+
+    let endpoint_form = RateLimitEndpointForm {
+      endpoint: "CreateComment".into(),
+      actions: 10,
+      per_second: 60,
+      creator_id: inserted_admin.id,
+    };
+
+    let inserted_endpoint = RateLimitEndpoint::set(&conn, &endpoint_form).unwrap();
+
+    let expected_endpoint = RateLimitEndpoint {
+      id: inserted_endpoint.id,
+      endpoint: "CreateComment".into(),
+      actions: 10,
+      per_second: 60,
+      creator_id: inserted_admin.id,
+      published: inserted_endpoint.published,
+    };
+
+    let updated_endpoint = RateLimitEndpoint::set(
+      &conn,
+      &RateLimitEndpointForm {
+        actions: 20,
+        ..endpoint_form
+      },
+    )
+    .unwrap();
+    let endpoints = RateLimitEndpoint::list(&conn).unwrap();
+    let num_endpoints_removed = RateLimitEndpoint::remove(&conn, "CreateComment").unwrap();
+
+    let exemption_form = RateLimitExemptionForm {
+      user_id: inserted_bot.id,
+      creator_id: inserted_admin.id,
+    };
+
+    let num_exempted = RateLimitExemption::exempt(&conn, &exemption_form).unwrap();
+    let num_re_exempted = RateLimitExemption::exempt(&conn, &exemption_form).unwrap();
+    let exemptions = RateLimitExemption::list(&conn).unwrap();
+    let num_unexempted = RateLimitExemption::unexempt(&conn, inserted_bot.id).unwrap();
+    User_::delete(&conn, inserted_bot.id).unwrap();
+    User_::delete(&conn, inserted_admin.id).unwrap();
+
+    assert_eq!(expected_endpoint, inserted_endpoint);
+    assert_eq!(inserted_endpoint.id, updated_endpoint.id);
+    assert_eq!(20, updated_endpoint.actions);
+    assert!(endpoints.contains(&updated_endpoint));
+    assert_eq!(1, num_endpoints_removed);
+    assert_eq!(1, num_exempted);
+    assert_eq!(0, num_re_exempted);
+    assert!(exemptions.iter().any(|e| e.user_id == inserted_bot.id));
+    assert_eq!(1, num_unexempted);
+  }
+}
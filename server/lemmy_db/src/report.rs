@@ -0,0 +1,344 @@
+use crate::{
+  naive_now,
+  schema::{comment_report, post_report},
+  Crud,
+};
+use diesel::{dsl::*, result::Error, *};
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone)]
+#[table_name = "post_report"]
+pub struct PostReport {
+  pub id: i32,
+  pub creator_id: i32,
+  pub post_id: i32,
+  pub reason: String,
+  pub rule_id: Option<i32>,
+  pub resolved: bool,
+  pub resolver_id: Option<i32>,
+  pub published: chrono::NaiveDateTime,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "post_report"]
+pub struct PostReportForm {
+  pub creator_id: i32,
+  pub post_id: i32,
+  pub reason: String,
+  pub rule_id: Option<i32>,
+}
+
+impl Crud<PostReportForm> for PostReport {
+  fn read(conn: &PgConnection, report_id: i32) -> Result<Self, Error> {
+    use crate::schema::post_report::dsl::*;
+    post_report.find(report_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, report_id: i32) -> Result<usize, Error> {
+    use crate::schema::post_report::dsl::*;
+    diesel::delete(post_report.find(report_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &PostReportForm) -> Result<Self, Error> {
+    use crate::schema::post_report::dsl::*;
+    insert_into(post_report)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, report_id: i32, form: &PostReportForm) -> Result<Self, Error> {
+    use crate::schema::post_report::dsl::*;
+    diesel::update(post_report.find(report_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl PostReport {
+  /// Marks the report as resolved by the given mod, or back to new.
+  pub fn resolve(
+    conn: &PgConnection,
+    report_id: i32,
+    for_resolver_id: i32,
+    for_resolved: bool,
+  ) -> Result<Self, Error> {
+    use crate::schema::post_report::dsl::*;
+    diesel::update(post_report.find(report_id))
+      .set((
+        resolved.eq(for_resolved),
+        resolver_id.eq(if for_resolved {
+          Some(for_resolver_id)
+        } else {
+          None
+        }),
+        updated.eq(naive_now()),
+      ))
+      .get_result::<Self>(conn)
+  }
+}
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone)]
+#[table_name = "comment_report"]
+pub struct CommentReport {
+  pub id: i32,
+  pub creator_id: i32,
+  pub comment_id: i32,
+  pub reason: String,
+  pub rule_id: Option<i32>,
+  pub resolved: bool,
+  pub resolver_id: Option<i32>,
+  pub published: chrono::NaiveDateTime,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "comment_report"]
+pub struct CommentReportForm {
+  pub creator_id: i32,
+  pub comment_id: i32,
+  pub reason: String,
+  pub rule_id: Option<i32>,
+}
+
+impl Crud<CommentReportForm> for CommentReport {
+  fn read(conn: &PgConnection, report_id: i32) -> Result<Self, Error> {
+    use crate::schema::comment_report::dsl::*;
+    comment_report.find(report_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, report_id: i32) -> Result<usize, Error> {
+    use crate::schema::comment_report::dsl::*;
+    diesel::delete(comment_report.find(report_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &CommentReportForm) -> Result<Self, Error> {
+    use crate::schema::comment_report::dsl::*;
+    insert_into(comment_report)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, report_id: i32, form: &CommentReportForm) -> Result<Self, Error> {
+    use crate::schema::comment_report::dsl::*;
+    diesel::update(comment_report.find(report_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl CommentReport {
+  /// Marks the report as resolved by the given mod, or back to new.
+  pub fn resolve(
+    conn: &PgConnection,
+    report_id: i32,
+    for_resolver_id: i32,
+    for_resolved: bool,
+  ) -> Result<Self, Error> {
+    use crate::schema::comment_report::dsl::*;
+    diesel::update(comment_report.find(report_id))
+      .set((
+        resolved.eq(for_resolved),
+        resolver_id.eq(if for_resolved {
+          Some(for_resolver_id)
+        } else {
+          None
+        }),
+        updated.eq(naive_now()),
+      ))
+      .get_result::<Self>(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    comment::*,
+    community::*,
+    community_rule::*,
+    post::*,
+    tests::establish_unpooled_connection,
+    user::*,
+    ListingType,
+    SortType,
+  };
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "report_mod".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_report".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_user.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let inserted_rule = CommunityRule::create(
+      &conn,
+      &CommunityRuleForm {
+        community_id: inserted_community.id,
+        position: 0,
+        title: "No spam".into(),
+        description: None,
+        updated: None,
+      },
+    )
+    .unwrap();
+
+    let new_post = PostForm {
+      name: "A test post".into(),
+      creator_id: inserted_user.id,
+      url: None,
+      body: None,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      embed_type: None,
+      url_media_type: None,
+      thumbnail_url: None,
+      ap_id: "http://fake.com".into(),
+      local: true,
+      published: None,
+      pending: None,
+    };
+
+    let inserted_post = Post::create(&conn, &new_post).unwrap();
+
+    let comment_form = CommentForm {
+      content: "A test comment".into(),
+      creator_id: inserted_user.id,
+      post_id: inserted_post.id,
+      removed: None,
+      deleted: None,
+      read: None,
+      parent_id: None,
+      published: None,
+      updated: None,
+      ap_id: "http://fake.com".into(),
+      local: true,
+    };
+
+    let inserted_comment = Comment::create(&conn, &comment_form).unwrap();
+
+    let post_report_form = PostReportForm {
+      creator_id: inserted_user.id,
+      post_id: inserted_post.id,
+      reason: "This is spam".into(),
+      rule_id: Some(inserted_rule.id),
+    };
+
+    let inserted_post_report = PostReport::create(&conn, &post_report_form).unwrap();
+
+    let expected_post_report = PostReport {
+      id: inserted_post_report.id,
+      creator_id: inserted_user.id,
+      post_id: inserted_post.id,
+      reason: "This is spam".into(),
+      rule_id: Some(inserted_rule.id),
+      resolved: false,
+      resolver_id: None,
+      published: inserted_post_report.published,
+      updated: None,
+    };
+
+    let comment_report_form = CommentReportForm {
+      creator_id: inserted_user.id,
+      comment_id: inserted_comment.id,
+      reason: "Rude".into(),
+      rule_id: None,
+    };
+
+    let inserted_comment_report = CommentReport::create(&conn, &comment_report_form).unwrap();
+
+    let resolved_post_report =
+      PostReport::resolve(&conn, inserted_post_report.id, inserted_user.id, true).unwrap();
+    let unresolved_post_report =
+      PostReport::resolve(&conn, inserted_post_report.id, inserted_user.id, false).unwrap();
+    let resolved_comment_report =
+      CommentReport::resolve(&conn, inserted_comment_report.id, inserted_user.id, true).unwrap();
+
+    // Removing the rule keeps the report
+    CommunityRule::delete(&conn, inserted_rule.id).unwrap();
+    let read_post_report = PostReport::read(&conn, inserted_post_report.id).unwrap();
+
+    let num_deleted = PostReport::delete(&conn, inserted_post_report.id).unwrap();
+    CommentReport::delete(&conn, inserted_comment_report.id).unwrap();
+    Comment::delete(&conn, inserted_comment.id).unwrap();
+    Post::delete(&conn, inserted_post.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(expected_post_report, inserted_post_report);
+    assert!(resolved_post_report.resolved);
+    assert_eq!(Some(inserted_user.id), resolved_post_report.resolver_id);
+    assert!(!unresolved_post_report.resolved);
+    assert_eq!(None, unresolved_post_report.resolver_id);
+    assert!(resolved_comment_report.resolved);
+    assert_eq!(None, read_post_report.rule_id);
+    assert_eq!(1, num_deleted);
+  }
+}
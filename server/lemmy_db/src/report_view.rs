@@ -0,0 +1,187 @@
+use crate::limit_and_offset;
+use diesel::{result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// The faked schema since diesel doesn't do views
+table! {
+  post_report_view (id) {
+    id -> Int4,
+    creator_id -> Int4,
+    post_id -> Int4,
+    reason -> Text,
+    rule_id -> Nullable<Int4>,
+    resolved -> Bool,
+    resolver_id -> Nullable<Int4>,
+    published -> Timestamp,
+    updated -> Nullable<Timestamp>,
+    post_name -> Varchar,
+    post_url -> Nullable<Text>,
+    post_body -> Nullable<Text>,
+    post_creator_id -> Int4,
+    community_id -> Int4,
+    creator_name -> Varchar,
+    post_creator_name -> Varchar,
+    resolver_name -> Nullable<Varchar>,
+    rule_title -> Nullable<Varchar>,
+  }
+}
+
+table! {
+  comment_report_view (id) {
+    id -> Int4,
+    creator_id -> Int4,
+    comment_id -> Int4,
+    reason -> Text,
+    rule_id -> Nullable<Int4>,
+    resolved -> Bool,
+    resolver_id -> Nullable<Int4>,
+    published -> Timestamp,
+    updated -> Nullable<Timestamp>,
+    comment_content -> Text,
+    comment_creator_id -> Int4,
+    post_id -> Int4,
+    post_name -> Varchar,
+    community_id -> Int4,
+    creator_name -> Varchar,
+    comment_creator_name -> Varchar,
+    resolver_name -> Nullable<Varchar>,
+    rule_title -> Nullable<Varchar>,
+  }
+}
+
+#[derive(
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
+)]
+#[table_name = "post_report_view"]
+pub struct PostReportView {
+  pub id: i32,
+  pub creator_id: i32,
+  pub post_id: i32,
+  pub reason: String,
+  pub rule_id: Option<i32>,
+  pub resolved: bool,
+  pub resolver_id: Option<i32>,
+  pub published: chrono::NaiveDateTime,
+  pub updated: Option<chrono::NaiveDateTime>,
+  pub post_name: String,
+  pub post_url: Option<String>,
+  pub post_body: Option<String>,
+  pub post_creator_id: i32,
+  pub community_id: i32,
+  pub creator_name: String,
+  pub post_creator_name: String,
+  pub resolver_name: Option<String>,
+  pub rule_title: Option<String>,
+}
+
+impl PostReportView {
+  pub fn read(conn: &PgConnection, from_report_id: i32) -> Result<Self, Error> {
+    use super::report_view::post_report_view::dsl::*;
+    post_report_view.find(from_report_id).first::<Self>(conn)
+  }
+
+  /// Oldest first, so the queue is worked through in order. No community ids means all of them.
+  pub fn list(
+    conn: &PgConnection,
+    for_community_ids: Option<Vec<i32>>,
+    unresolved_only: bool,
+    page: Option<i64>,
+    limit: Option<i64>,
+  ) -> Result<Vec<Self>, Error> {
+    use super::report_view::post_report_view::dsl::*;
+    let mut query = post_report_view.into_boxed();
+
+    let (limit, offset) = limit_and_offset(page, limit);
+
+    if let Some(for_community_ids) = for_community_ids {
+      query = query.filter(community_id.eq_any(for_community_ids));
+    };
+
+    if unresolved_only {
+      query = query.filter(resolved.eq(false));
+    };
+
+    query
+      .limit(limit)
+      .offset(offset)
+      .order_by(published.asc())
+      .load::<Self>(conn)
+  }
+}
+
+#[derive(
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
+)]
+#[table_name = "comment_report_view"]
+pub struct CommentReportView {
+  pub id: i32,
+  pub creator_id: i32,
+  pub comment_id: i32,
+  pub reason: String,
+  pub rule_id: Option<i32>,
+  pub resolved: bool,
+  pub resolver_id: Option<i32>,
+  pub published: chrono::NaiveDateTime,
+  pub updated: Option<chrono::NaiveDateTime>,
+  pub comment_content: String,
+  pub comment_creator_id: i32,
+  pub post_id: i32,
+  pub post_name: String,
+  pub community_id: i32,
+  pub creator_name: String,
+  pub comment_creator_name: String,
+  pub resolver_name: Option<String>,
+  pub rule_title: Option<String>,
+}
+
+impl CommentReportView {
+  pub fn read(conn: &PgConnection, from_report_id: i32) -> Result<Self, Error> {
+    use super::report_view::comment_report_view::dsl::*;
+    comment_report_view.find(from_report_id).first::<Self>(conn)
+  }
+
+  /// Oldest first, so the queue is worked through in order. No community ids means all of them.
+  pub fn list(
+    conn: &PgConnection,
+    for_community_ids: Option<Vec<i32>>,
+    unresolved_only: bool,
+    page: Option<i64>,
+    limit: Option<i64>,
+  ) -> Result<Vec<Self>, Error> {
+    use super::report_view::comment_report_view::dsl::*;
+    let mut query = comment_report_view.into_boxed();
+
+    let (limit, offset) = limit_and_offset(page, limit);
+
+    if let Some(for_community_ids) = for_community_ids {
+      query = query.filter(community_id.eq_any(for_community_ids));
+    };
+
+    if unresolved_only {
+      query = query.filter(resolved.eq(false));
+    };
+
+    query
+      .limit(limit)
+      .offset(offset)
+      .order_by(published.asc())
+      .load::<Self>(conn)
+  }
+}
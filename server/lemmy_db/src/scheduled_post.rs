@@ -0,0 +1,263 @@
+use crate::{
+  post::{Post, PostForm},
+  schema::scheduled_post,
+  Crud,
+};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A post written ahead of time, put up by the job queue at publish_at as if the creator made it
+/// then. It's gone once it's up, or kept with the error if it couldn't be put up.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "scheduled_post"]
+pub struct ScheduledPost {
+  pub id: i32,
+  pub creator_id: i32,
+  pub community_id: i32,
+  pub name: String,
+  pub url: Option<String>,
+  pub body: Option<String>,
+  pub nsfw: bool,
+  pub flair_id: Option<i32>,
+  pub publish_at: chrono::NaiveDateTime,
+  pub published: chrono::NaiveDateTime,
+  pub post_id: Option<i32>,
+  pub error: Option<String>,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "scheduled_post"]
+pub struct ScheduledPostForm {
+  pub creator_id: i32,
+  pub community_id: i32,
+  pub name: String,
+  pub url: Option<String>,
+  pub body: Option<String>,
+  pub nsfw: bool,
+  pub flair_id: Option<i32>,
+  pub publish_at: chrono::NaiveDateTime,
+}
+
+impl Crud<ScheduledPostForm> for ScheduledPost {
+  fn read(conn: &PgConnection, scheduled_post_id: i32) -> Result<Self, Error> {
+    use crate::schema::scheduled_post::dsl::*;
+    scheduled_post.find(scheduled_post_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, scheduled_post_id: i32) -> Result<usize, Error> {
+    use crate::schema::scheduled_post::dsl::*;
+    diesel::delete(scheduled_post.find(scheduled_post_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &ScheduledPostForm) -> Result<Self, Error> {
+    use crate::schema::scheduled_post::dsl::*;
+    insert_into(scheduled_post)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    scheduled_post_id: i32,
+    form: &ScheduledPostForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::scheduled_post::dsl::*;
+    diesel::update(scheduled_post.find(scheduled_post_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl ScheduledPost {
+  /// The soonest first
+  pub fn list_for_creator(conn: &PgConnection, for_creator_id: i32) -> Result<Vec<Self>, Error> {
+    use crate::schema::scheduled_post::dsl::*;
+    scheduled_post
+      .filter(creator_id.eq(for_creator_id))
+      .order_by(publish_at.asc())
+      .load::<Self>(conn)
+  }
+
+  /// Inserts the post for the scheduled post, and records it in the same transaction, so that a
+  /// retry after something later went wrong knows it's up.
+  pub fn insert_post(
+    conn: &PgConnection,
+    scheduled_post_id: i32,
+    form: &PostForm,
+  ) -> Result<Post, Error> {
+    use crate::schema::scheduled_post::dsl::*;
+    conn.transaction(|| {
+      let inserted_post = Post::create(conn, form)?;
+      diesel::update(scheduled_post.find(scheduled_post_id))
+        .set(post_id.eq(inserted_post.id))
+        .execute(conn)?;
+      Ok(inserted_post)
+    })
+  }
+
+  /// Keeps the error of a scheduled post that couldn't be put up. One whose post is up didn't fail.
+  pub fn fail(conn: &PgConnection, scheduled_post_id: i32, failure: &str) -> Result<Self, Error> {
+    use crate::schema::scheduled_post::dsl::*;
+    diesel::update(
+      scheduled_post
+        .find(scheduled_post_id)
+        .filter(post_id.is_null()),
+    )
+    .set(error.eq(failure))
+    .get_result::<Self>(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    community::*,
+    naive_now,
+    tests::establish_unpooled_connection,
+    user::*,
+    ListingType,
+    SortType,
+  };
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "post_scheduler".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_scheduled".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_user.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let tomorrow = (naive_now() + chrono::Duration::days(1))
+      .date()
+      .and_hms(12, 0, 0);
+    let new_scheduled_post = ScheduledPostForm {
+      creator_id: inserted_user.id,
+      community_id: inserted_community.id,
+      name: "A post for tomorrow".into(),
+      url: None,
+      body: None,
+      nsfw: false,
+      flair_id: None,
+      publish_at: tomorrow,
+    };
+
+    let inserted_scheduled_post = ScheduledPost::create(&conn, &new_scheduled_post).unwrap();
+
+    let expected_scheduled_post = ScheduledPost {
+      id: inserted_scheduled_post.id,
+      creator_id: inserted_user.id,
+      community_id: inserted_community.id,
+      name: "A post for tomorrow".into(),
+      url: None,
+      body: None,
+      nsfw: false,
+      flair_id: None,
+      publish_at: tomorrow,
+      published: inserted_scheduled_post.published,
+      post_id: None,
+      error: None,
+    };
+
+    let sooner_scheduled_post = ScheduledPost::create(
+      &conn,
+      &ScheduledPostForm {
+        name: "A post for later today".into(),
+        publish_at: naive_now() + chrono::Duration::hours(1),
+        ..new_scheduled_post.clone()
+      },
+    )
+    .unwrap();
+
+    let read_scheduled_post = ScheduledPost::read(&conn, inserted_scheduled_post.id).unwrap();
+    let listed_ids = ScheduledPost::list_for_creator(&conn, inserted_user.id)
+      .unwrap()
+      .iter()
+      .map(|scheduled_post| scheduled_post.id)
+      .collect::<Vec<i32>>();
+
+    let edit_scheduled_post = ScheduledPostForm {
+      body: Some("Some more".into()),
+      ..new_scheduled_post
+    };
+    let updated_scheduled_post =
+      ScheduledPost::update(&conn, inserted_scheduled_post.id, &edit_scheduled_post).unwrap();
+
+    let failed_scheduled_post =
+      ScheduledPost::fail(&conn, sooner_scheduled_post.id, "community_ban").unwrap();
+
+    let num_deleted = ScheduledPost::delete(&conn, inserted_scheduled_post.id).unwrap();
+    ScheduledPost::delete(&conn, sooner_scheduled_post.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(expected_scheduled_post, inserted_scheduled_post);
+    assert_eq!(expected_scheduled_post, read_scheduled_post);
+    assert_eq!(
+      vec![sooner_scheduled_post.id, inserted_scheduled_post.id],
+      listed_ids
+    );
+    assert_eq!(Some("Some more".into()), updated_scheduled_post.body);
+    assert_eq!(Some("community_ban".into()), failed_scheduled_post.error);
+    assert_eq!(1, num_deleted);
+  }
+}
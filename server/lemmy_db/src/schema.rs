@@ -9,6 +9,56 @@ table! {
     }
 }
 
+table! {
+    appeal (id) {
+        id -> Int4,
+        kind -> Int2,
+        mod_action_id -> Int4,
+        creator_id -> Int4,
+        community_id -> Nullable<Int4>,
+        reason -> Text,
+        status -> Int2,
+        resolver_id -> Nullable<Int4>,
+        published -> Timestamp,
+        updated -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    automod_rule (id) {
+        id -> Int4,
+        community_id -> Int4,
+        creator_id -> Int4,
+        name -> Varchar,
+        condition -> Int2,
+        target -> Int2,
+        pattern -> Nullable<Text>,
+        threshold -> Nullable<Int4>,
+        action -> Int2,
+        published -> Timestamp,
+        updated -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    blocked_domain (id) {
+        id -> Int4,
+        community_id -> Nullable<Int4>,
+        domain -> Varchar,
+        creator_id -> Int4,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    captcha_answer (id) {
+        id -> Int4,
+        uuid -> Text,
+        answer -> Text,
+        published -> Timestamp,
+    }
+}
+
 table! {
     category (id) {
         id -> Int4,
@@ -30,6 +80,16 @@ table! {
         deleted -> Bool,
         ap_id -> Varchar,
         local -> Bool,
+        pinned -> Bool,
+    }
+}
+
+table! {
+    comment_aggregates (comment_id) {
+        comment_id -> Int4,
+        score -> Int8,
+        upvotes -> Int8,
+        downvotes -> Int8,
     }
 }
 
@@ -47,6 +107,7 @@ table! {
         deleted -> Nullable<Bool>,
         ap_id -> Nullable<Varchar>,
         local -> Nullable<Bool>,
+        pinned -> Nullable<Bool>,
         post_name -> Nullable<Varchar>,
         community_id -> Nullable<Int4>,
         community_actor_id -> Nullable<Varchar>,
@@ -59,6 +120,8 @@ table! {
         creator_name -> Nullable<Varchar>,
         creator_published -> Nullable<Timestamp>,
         creator_avatar -> Nullable<Text>,
+        creator_bot_account -> Nullable<Bool>,
+        creator_display_name -> Nullable<Varchar>,
         score -> Nullable<Int8>,
         upvotes -> Nullable<Int8>,
         downvotes -> Nullable<Int8>,
@@ -77,6 +140,29 @@ table! {
     }
 }
 
+table! {
+    comment_report (id) {
+        id -> Int4,
+        creator_id -> Int4,
+        comment_id -> Int4,
+        reason -> Text,
+        rule_id -> Nullable<Int4>,
+        resolved -> Bool,
+        resolver_id -> Nullable<Int4>,
+        published -> Timestamp,
+        updated -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    comment_revision (id) {
+        id -> Int4,
+        comment_id -> Int4,
+        content -> Text,
+        published -> Timestamp,
+    }
+}
+
 table! {
     comment_saved (id) {
         id -> Int4,
@@ -104,6 +190,24 @@ table! {
         private_key -> Nullable<Text>,
         public_key -> Nullable<Text>,
         last_refreshed_at -> Timestamp,
+        require_flair -> Bool,
+        icon -> Nullable<Text>,
+        banner -> Nullable<Text>,
+        archive_after_days -> Int4,
+        post_approval -> Int2,
+        read_only -> Bool,
+        allowed_post_types -> Int2,
+        default_sort_type -> Int2,
+        language -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    community_aggregates (community_id) {
+        community_id -> Int4,
+        subscribers -> Int8,
+        posts -> Int8,
+        comments -> Int8,
     }
 }
 
@@ -123,6 +227,15 @@ table! {
         actor_id -> Nullable<Varchar>,
         local -> Nullable<Bool>,
         last_refreshed_at -> Nullable<Timestamp>,
+        require_flair -> Nullable<Bool>,
+        icon -> Nullable<Text>,
+        banner -> Nullable<Text>,
+        archive_after_days -> Nullable<Int4>,
+        post_approval -> Nullable<Int2>,
+        read_only -> Nullable<Bool>,
+        allowed_post_types -> Nullable<Int2>,
+        default_sort_type -> Nullable<Int2>,
+        language -> Nullable<Varchar>,
         creator_actor_id -> Nullable<Varchar>,
         creator_local -> Nullable<Bool>,
         creator_name -> Nullable<Varchar>,
@@ -135,6 +248,16 @@ table! {
     }
 }
 
+table! {
+    community_flair (id) {
+        id -> Int4,
+        community_id -> Int4,
+        name -> Varchar,
+        color -> Varchar,
+        published -> Timestamp,
+    }
+}
+
 table! {
     community_follower (id) {
         id -> Int4,
@@ -144,6 +267,23 @@ table! {
     }
 }
 
+table! {
+    community_import (id) {
+        id -> Int4,
+        community_id -> Int4,
+        creator_id -> Int4,
+        source -> Text,
+        data -> Nullable<Text>,
+        posts_total -> Int4,
+        posts_imported -> Int4,
+        comments_total -> Int4,
+        comments_imported -> Int4,
+        error -> Nullable<Text>,
+        published -> Timestamp,
+        completed -> Nullable<Timestamp>,
+    }
+}
+
 table! {
     community_moderator (id) {
         id -> Int4,
@@ -153,12 +293,152 @@ table! {
     }
 }
 
+table! {
+    community_rule (id) {
+        id -> Int4,
+        community_id -> Int4,
+        position -> Int4,
+        title -> Varchar,
+        description -> Nullable<Text>,
+        published -> Timestamp,
+        updated -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    community_takeover_request (id) {
+        id -> Int4,
+        community_id -> Int4,
+        creator_id -> Int4,
+        reason -> Nullable<Text>,
+        status -> Int2,
+        resolver_id -> Nullable<Int4>,
+        published -> Timestamp,
+        updated -> Nullable<Timestamp>,
+    }
+}
+
 table! {
     community_user_ban (id) {
         id -> Int4,
         community_id -> Int4,
         user_id -> Int4,
         published -> Timestamp,
+        expires -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    community_wiki_page (id) {
+        id -> Int4,
+        community_id -> Int4,
+        slug -> Varchar,
+        title -> Varchar,
+        content -> Text,
+        edit_permission -> Int2,
+        editor_id -> Nullable<Int4>,
+        published -> Timestamp,
+        updated -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    community_wiki_page_revision (id) {
+        id -> Int4,
+        page_id -> Int4,
+        title -> Varchar,
+        content -> Text,
+        editor_id -> Nullable<Int4>,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    custom_feed (id) {
+        id -> Int4,
+        creator_id -> Int4,
+        name -> Varchar,
+        published -> Timestamp,
+        updated -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    custom_feed_community (id) {
+        id -> Int4,
+        custom_feed_id -> Int4,
+        community_id -> Int4,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    email_domain (id) {
+        id -> Int4,
+        domain -> Varchar,
+        allowed -> Bool,
+        creator_id -> Int4,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    email_verification (id) {
+        id -> Int4,
+        user_id -> Int4,
+        email -> Text,
+        token_encrypted -> Text,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    image_proxy (id) {
+        id -> Int4,
+        url -> Text,
+        file -> Text,
+        delete_token -> Text,
+        published -> Timestamp,
+        last_used -> Timestamp,
+    }
+}
+
+table! {
+    ip_ban (id) {
+        id -> Int4,
+        ip_range -> Varchar,
+        reason -> Nullable<Text>,
+        creator_id -> Int4,
+        expires -> Nullable<Timestamp>,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    job (id) {
+        id -> Int4,
+        kind -> Varchar,
+        payload -> Text,
+        status -> Int2,
+        attempts -> Int4,
+        max_attempts -> Int4,
+        run_at -> Timestamp,
+        last_error -> Nullable<Text>,
+        published -> Timestamp,
+        updated -> Nullable<Timestamp>,
+        lease_expires -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    media (id) {
+        id -> Int4,
+        user_id -> Int4,
+        file -> Text,
+        delete_token -> Text,
+        published -> Timestamp,
+        content_type -> Nullable<Text>,
+        nsfw_score -> Nullable<Float4>,
     }
 }
 
@@ -218,6 +498,38 @@ table! {
     }
 }
 
+table! {
+    mod_note (id) {
+        id -> Int4,
+        community_id -> Int4,
+        user_id -> Int4,
+        creator_id -> Int4,
+        content -> Text,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    mod_nsfw_post (id) {
+        id -> Int4,
+        mod_user_id -> Int4,
+        post_id -> Int4,
+        nsfw -> Nullable<Bool>,
+        when_ -> Timestamp,
+    }
+}
+
+table! {
+    mod_purge (id) {
+        id -> Int4,
+        mod_user_id -> Int4,
+        target_type -> Int2,
+        community_id -> Nullable<Int4>,
+        reason -> Nullable<Text>,
+        when_ -> Timestamp,
+    }
+}
+
 table! {
     mod_remove_comment (id) {
         id -> Int4,
@@ -252,6 +564,37 @@ table! {
     }
 }
 
+table! {
+    mod_resolve_comment_report (id) {
+        id -> Int4,
+        mod_user_id -> Int4,
+        report_id -> Int4,
+        resolved -> Nullable<Bool>,
+        when_ -> Timestamp,
+    }
+}
+
+table! {
+    mod_resolve_post_report (id) {
+        id -> Int4,
+        mod_user_id -> Int4,
+        report_id -> Int4,
+        resolved -> Nullable<Bool>,
+        when_ -> Timestamp,
+    }
+}
+
+table! {
+    mod_shadow_ban (id) {
+        id -> Int4,
+        mod_user_id -> Int4,
+        other_user_id -> Int4,
+        reason -> Nullable<Text>,
+        shadow_banned -> Nullable<Bool>,
+        when_ -> Timestamp,
+    }
+}
+
 table! {
     mod_sticky_post (id) {
         id -> Int4,
@@ -262,6 +605,50 @@ table! {
     }
 }
 
+table! {
+    mod_transfer_community (id) {
+        id -> Int4,
+        mod_user_id -> Int4,
+        other_user_id -> Int4,
+        community_id -> Int4,
+        when_ -> Timestamp,
+    }
+}
+
+table! {
+    oauth_app (id) {
+        id -> Int4,
+        client_id -> Text,
+        client_secret -> Text,
+        name -> Text,
+        website -> Nullable<Text>,
+        redirect_uris -> Text,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    oauth_authorization (id) {
+        id -> Int4,
+        app_id -> Int4,
+        user_id -> Int4,
+        redirect_uri -> Text,
+        code_encrypted -> Text,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    oauth_token (id) {
+        id -> Int4,
+        app_id -> Int4,
+        user_id -> Int4,
+        token_encrypted -> Text,
+        scope -> Text,
+        published -> Timestamp,
+    }
+}
+
 table! {
     password_reset_request (id) {
         id -> Int4,
@@ -271,6 +658,16 @@ table! {
     }
 }
 
+table! {
+    pictrs_thumbnail (id) {
+        id -> Int4,
+        file -> Text,
+        delete_token -> Text,
+        published -> Timestamp,
+        nsfw_score -> Nullable<Float4>,
+    }
+}
+
 table! {
     post (id) {
         id -> Int4,
@@ -292,6 +689,21 @@ table! {
         thumbnail_url -> Nullable<Text>,
         ap_id -> Varchar,
         local -> Bool,
+        archived -> Bool,
+        pending -> Bool,
+        embed_type -> Nullable<Text>,
+        url_media_type -> Nullable<Text>,
+    }
+}
+
+table! {
+    post_aggregates (post_id) {
+        post_id -> Int4,
+        comments -> Int8,
+        score -> Int8,
+        upvotes -> Int8,
+        downvotes -> Int8,
+        newest_comment_time -> Nullable<Timestamp>,
     }
 }
 
@@ -316,11 +728,17 @@ table! {
         thumbnail_url -> Nullable<Text>,
         ap_id -> Nullable<Varchar>,
         local -> Nullable<Bool>,
+        archived -> Nullable<Bool>,
+        pending -> Nullable<Bool>,
+        embed_type -> Nullable<Text>,
+        url_media_type -> Nullable<Text>,
         creator_actor_id -> Nullable<Varchar>,
         creator_local -> Nullable<Bool>,
         creator_name -> Nullable<Varchar>,
         creator_published -> Nullable<Timestamp>,
         creator_avatar -> Nullable<Text>,
+        creator_bot_account -> Nullable<Bool>,
+        creator_display_name -> Nullable<Varchar>,
         banned -> Nullable<Bool>,
         banned_from_community -> Nullable<Bool>,
         community_actor_id -> Nullable<Varchar>,
@@ -338,6 +756,24 @@ table! {
     }
 }
 
+table! {
+    post_flair (id) {
+        id -> Int4,
+        post_id -> Int4,
+        flair_id -> Int4,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    post_hide (id) {
+        id -> Int4,
+        post_id -> Int4,
+        user_id -> Int4,
+        published -> Timestamp,
+    }
+}
+
 table! {
     post_like (id) {
         id -> Int4,
@@ -357,6 +793,31 @@ table! {
     }
 }
 
+table! {
+    post_report (id) {
+        id -> Int4,
+        creator_id -> Int4,
+        post_id -> Int4,
+        reason -> Text,
+        rule_id -> Nullable<Int4>,
+        resolved -> Bool,
+        resolver_id -> Nullable<Int4>,
+        published -> Timestamp,
+        updated -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    post_revision (id) {
+        id -> Int4,
+        post_id -> Int4,
+        name -> Varchar,
+        url -> Nullable<Text>,
+        body -> Nullable<Text>,
+        published -> Timestamp,
+    }
+}
+
 table! {
     post_saved (id) {
         id -> Int4,
@@ -366,6 +827,28 @@ table! {
     }
 }
 
+table! {
+    post_subscription (id) {
+        id -> Int4,
+        post_id -> Int4,
+        user_id -> Int4,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    posting_requirement (id) {
+        id -> Int4,
+        community_id -> Nullable<Int4>,
+        min_account_age_days -> Int4,
+        min_comments -> Int4,
+        min_comment_score -> Int4,
+        links_only -> Bool,
+        published -> Timestamp,
+        updated -> Nullable<Timestamp>,
+    }
+}
+
 table! {
     private_message (id) {
         id -> Int4,
@@ -378,6 +861,54 @@ table! {
         updated -> Nullable<Timestamp>,
         ap_id -> Varchar,
         local -> Bool,
+        conversation_id -> Int4,
+    }
+}
+
+table! {
+    private_message_conversation (id) {
+        id -> Int4,
+        user_a_id -> Int4,
+        user_b_id -> Int4,
+        published -> Timestamp,
+        last_message_at -> Timestamp,
+    }
+}
+
+table! {
+    rate_limit_endpoint (id) {
+        id -> Int4,
+        endpoint -> Varchar,
+        actions -> Int4,
+        per_second -> Int4,
+        creator_id -> Int4,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    rate_limit_exemption (id) {
+        id -> Int4,
+        user_id -> Int4,
+        creator_id -> Int4,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    scheduled_post (id) {
+        id -> Int4,
+        creator_id -> Int4,
+        community_id -> Int4,
+        name -> Varchar,
+        url -> Nullable<Text>,
+        body -> Nullable<Text>,
+        nsfw -> Bool,
+        flair_id -> Nullable<Int4>,
+        publish_at -> Timestamp,
+        published -> Timestamp,
+        post_id -> Nullable<Int4>,
+        error -> Nullable<Text>,
     }
 }
 
@@ -392,6 +923,72 @@ table! {
         enable_downvotes -> Bool,
         open_registration -> Bool,
         enable_nsfw -> Bool,
+        require_email_verification -> Bool,
+        public_edit_history -> Bool,
+        archive_after_days -> Int4,
+        require_removal_reason -> Bool,
+        rate_limit_message -> Nullable<Int4>,
+        rate_limit_message_per_second -> Nullable<Int4>,
+        rate_limit_post -> Nullable<Int4>,
+        rate_limit_post_per_second -> Nullable<Int4>,
+        rate_limit_register -> Nullable<Int4>,
+        rate_limit_register_per_second -> Nullable<Int4>,
+        rate_limit_report -> Nullable<Int4>,
+        rate_limit_report_per_second -> Nullable<Int4>,
+        block_disposable_emails -> Bool,
+        ip_retention_days -> Int4,
+        registrations_per_ip_per_day -> Int4,
+        mod_inactive_days -> Int4,
+        community_default_nsfw -> Bool,
+        community_default_post_types -> Int2,
+        community_default_sort_type -> Int2,
+        community_default_language -> Nullable<Varchar>,
+        federation_enabled -> Nullable<Bool>,
+        federation_allowed_instances -> Nullable<Text>,
+        hide_downvote_counts -> Bool,
+    }
+}
+
+table! {
+    sitemap (id) {
+        id -> Int4,
+        page -> Int4,
+        content -> Text,
+        lastmod -> Timestamp,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    site_announcement (id) {
+        id -> Int4,
+        creator_id -> Int4,
+        content -> Text,
+        severity -> Int2,
+        start_time -> Timestamp,
+        end_time -> Nullable<Timestamp>,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    site_announcement_dismissal (id) {
+        id -> Int4,
+        announcement_id -> Int4,
+        user_id -> Int4,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    site_document (id) {
+        id -> Int4,
+        slug -> Varchar,
+        title -> Text,
+        content -> Text,
+        editor_id -> Nullable<Int4>,
+        published -> Timestamp,
+        updated -> Nullable<Timestamp>,
     }
 }
 
@@ -421,6 +1018,35 @@ table! {
         private_key -> Nullable<Text>,
         public_key -> Nullable<Text>,
         last_refreshed_at -> Timestamp,
+        email_verified -> Bool,
+        banner -> Nullable<Text>,
+        hide_read_posts -> Bool,
+        bot_account -> Bool,
+        hide_bot_accounts -> Bool,
+        display_name -> Nullable<Varchar>,
+        ban_expires -> Nullable<Timestamp>,
+        shadow_banned -> Bool,
+        hide_presence -> Bool,
+        directory_account -> Bool,
+    }
+}
+
+table! {
+    user_aggregates (user_id) {
+        user_id -> Int4,
+        post_count -> Int8,
+        post_score -> Int8,
+        comment_count -> Int8,
+        comment_score -> Int8,
+    }
+}
+
+table! {
+    user_alias (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Varchar,
+        published -> Timestamp,
     }
 }
 
@@ -432,11 +1058,23 @@ table! {
     }
 }
 
+table! {
+    user_export (id) {
+        id -> Int4,
+        user_id -> Int4,
+        data -> Nullable<Jsonb>,
+        published -> Timestamp,
+        completed -> Nullable<Timestamp>,
+        error -> Nullable<Text>,
+    }
+}
+
 table! {
     user_fast (id) {
         id -> Int4,
         actor_id -> Nullable<Varchar>,
         name -> Nullable<Varchar>,
+        display_name -> Nullable<Varchar>,
         avatar -> Nullable<Text>,
         email -> Nullable<Text>,
         matrix_user_id -> Nullable<Text>,
@@ -444,6 +1082,7 @@ table! {
         local -> Nullable<Bool>,
         admin -> Nullable<Bool>,
         banned -> Nullable<Bool>,
+        bot_account -> Nullable<Bool>,
         show_avatars -> Nullable<Bool>,
         send_notifications_to_email -> Nullable<Bool>,
         published -> Nullable<Timestamp>,
@@ -454,6 +1093,35 @@ table! {
     }
 }
 
+table! {
+    user_follower (id) {
+        id -> Int4,
+        user_id -> Int4,
+        follower_id -> Int4,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    user_ip (id) {
+        id -> Int4,
+        user_id -> Int4,
+        ip_hash -> Varchar,
+        registration -> Bool,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    user_matrix_room (id) {
+        id -> Int4,
+        user_id -> Int4,
+        matrix_user_id -> Text,
+        room_id -> Text,
+        published -> Timestamp,
+    }
+}
+
 table! {
     user_mention (id) {
         id -> Int4,
@@ -464,79 +1132,279 @@ table! {
     }
 }
 
+table! {
+    user_muted_keyword (id) {
+        id -> Int4,
+        user_id -> Int4,
+        keyword -> Varchar,
+        is_regex -> Bool,
+        hide -> Bool,
+        expires -> Nullable<Timestamp>,
+        published -> Timestamp,
+        updated -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    user_notification_settings (id) {
+        id -> Int4,
+        user_id -> Int4,
+        replies -> Bool,
+        mentions -> Bool,
+        private_messages -> Bool,
+        mod_actions -> Bool,
+        subscribed_posts -> Bool,
+        updated -> Nullable<Timestamp>,
+        digest_frequency -> Int2,
+        last_digest_sent_at -> Nullable<Timestamp>,
+        digest_token -> Text,
+    }
+}
+
+table! {
+    user_push_subscription (id) {
+        id -> Int4,
+        user_id -> Int4,
+        endpoint -> Text,
+        p256dh -> Text,
+        auth -> Text,
+        published -> Timestamp,
+    }
+}
+
+table! {
+    webhook (id) {
+        id -> Int4,
+        community_id -> Nullable<Int4>,
+        creator_id -> Int4,
+        url -> Text,
+        secret -> Text,
+        events -> Array<Text>,
+        enabled -> Bool,
+        published -> Timestamp,
+        updated -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    word_filter (id) {
+        id -> Int4,
+        community_id -> Nullable<Int4>,
+        pattern -> Text,
+        is_regex -> Bool,
+        action -> Int2,
+        replacement -> Nullable<Text>,
+        creator_id -> Nullable<Int4>,
+        published -> Timestamp,
+        updated -> Nullable<Timestamp>,
+    }
+}
+
 joinable!(activity -> user_ (user_id));
+joinable!(appeal -> community (community_id));
+joinable!(automod_rule -> community (community_id));
+joinable!(automod_rule -> user_ (creator_id));
+joinable!(blocked_domain -> community (community_id));
+joinable!(blocked_domain -> user_ (creator_id));
 joinable!(comment -> post (post_id));
 joinable!(comment -> user_ (creator_id));
+joinable!(comment_aggregates -> comment (comment_id));
 joinable!(comment_like -> comment (comment_id));
 joinable!(comment_like -> post (post_id));
 joinable!(comment_like -> user_ (user_id));
+joinable!(comment_report -> comment (comment_id));
+joinable!(comment_report -> community_rule (rule_id));
+joinable!(comment_revision -> comment (comment_id));
 joinable!(comment_saved -> comment (comment_id));
 joinable!(comment_saved -> user_ (user_id));
 joinable!(community -> category (category_id));
 joinable!(community -> user_ (creator_id));
+joinable!(community_aggregates -> community (community_id));
+joinable!(community_flair -> community (community_id));
 joinable!(community_follower -> community (community_id));
 joinable!(community_follower -> user_ (user_id));
+joinable!(community_import -> community (community_id));
+joinable!(community_import -> user_ (creator_id));
 joinable!(community_moderator -> community (community_id));
 joinable!(community_moderator -> user_ (user_id));
+joinable!(community_rule -> community (community_id));
+joinable!(community_takeover_request -> community (community_id));
 joinable!(community_user_ban -> community (community_id));
 joinable!(community_user_ban -> user_ (user_id));
+joinable!(community_wiki_page -> community (community_id));
+joinable!(community_wiki_page -> user_ (editor_id));
+joinable!(community_wiki_page_revision -> community_wiki_page (page_id));
+joinable!(community_wiki_page_revision -> user_ (editor_id));
+joinable!(custom_feed -> user_ (creator_id));
+joinable!(custom_feed_community -> community (community_id));
+joinable!(custom_feed_community -> custom_feed (custom_feed_id));
+joinable!(email_domain -> user_ (creator_id));
+joinable!(email_verification -> user_ (user_id));
+joinable!(ip_ban -> user_ (creator_id));
+joinable!(media -> user_ (user_id));
 joinable!(mod_add_community -> community (community_id));
 joinable!(mod_ban_from_community -> community (community_id));
 joinable!(mod_lock_post -> post (post_id));
 joinable!(mod_lock_post -> user_ (mod_user_id));
+joinable!(mod_note -> community (community_id));
+joinable!(mod_nsfw_post -> post (post_id));
+joinable!(mod_nsfw_post -> user_ (mod_user_id));
+joinable!(mod_purge -> community (community_id));
+joinable!(mod_purge -> user_ (mod_user_id));
 joinable!(mod_remove_comment -> comment (comment_id));
 joinable!(mod_remove_comment -> user_ (mod_user_id));
 joinable!(mod_remove_community -> community (community_id));
 joinable!(mod_remove_community -> user_ (mod_user_id));
 joinable!(mod_remove_post -> post (post_id));
 joinable!(mod_remove_post -> user_ (mod_user_id));
+joinable!(mod_resolve_comment_report -> comment_report (report_id));
+joinable!(mod_resolve_comment_report -> user_ (mod_user_id));
+joinable!(mod_resolve_post_report -> post_report (report_id));
+joinable!(mod_resolve_post_report -> user_ (mod_user_id));
 joinable!(mod_sticky_post -> post (post_id));
 joinable!(mod_sticky_post -> user_ (mod_user_id));
+joinable!(mod_transfer_community -> community (community_id));
+joinable!(oauth_authorization -> oauth_app (app_id));
+joinable!(oauth_authorization -> user_ (user_id));
+joinable!(oauth_token -> oauth_app (app_id));
+joinable!(oauth_token -> user_ (user_id));
 joinable!(password_reset_request -> user_ (user_id));
 joinable!(post -> community (community_id));
 joinable!(post -> user_ (creator_id));
+joinable!(post_aggregates -> post (post_id));
+joinable!(post_flair -> community_flair (flair_id));
+joinable!(post_flair -> post (post_id));
 joinable!(post_like -> post (post_id));
 joinable!(post_like -> user_ (user_id));
+joinable!(post_hide -> post (post_id));
+joinable!(post_hide -> user_ (user_id));
 joinable!(post_read -> post (post_id));
 joinable!(post_read -> user_ (user_id));
+joinable!(post_report -> community_rule (rule_id));
+joinable!(post_report -> post (post_id));
+joinable!(post_revision -> post (post_id));
 joinable!(post_saved -> post (post_id));
 joinable!(post_saved -> user_ (user_id));
+joinable!(post_subscription -> post (post_id));
+joinable!(post_subscription -> user_ (user_id));
+joinable!(posting_requirement -> community (community_id));
+joinable!(private_message -> private_message_conversation (conversation_id));
+joinable!(rate_limit_endpoint -> user_ (creator_id));
+joinable!(scheduled_post -> community (community_id));
+joinable!(scheduled_post -> community_flair (flair_id));
+joinable!(scheduled_post -> post (post_id));
+joinable!(scheduled_post -> user_ (creator_id));
 joinable!(site -> user_ (creator_id));
+joinable!(site_announcement -> user_ (creator_id));
+joinable!(site_announcement_dismissal -> site_announcement (announcement_id));
+joinable!(site_announcement_dismissal -> user_ (user_id));
+joinable!(site_document -> user_ (editor_id));
+joinable!(user_aggregates -> user_ (user_id));
+joinable!(user_alias -> user_ (user_id));
 joinable!(user_ban -> user_ (user_id));
+joinable!(user_export -> user_ (user_id));
+joinable!(user_ip -> user_ (user_id));
+joinable!(user_matrix_room -> user_ (user_id));
 joinable!(user_mention -> comment (comment_id));
 joinable!(user_mention -> user_ (recipient_id));
+joinable!(user_muted_keyword -> user_ (user_id));
+joinable!(user_notification_settings -> user_ (user_id));
+joinable!(user_push_subscription -> user_ (user_id));
+joinable!(webhook -> community (community_id));
+joinable!(webhook -> user_ (creator_id));
+joinable!(word_filter -> community (community_id));
+joinable!(word_filter -> user_ (creator_id));
 
 allow_tables_to_appear_in_same_query!(
   activity,
+  appeal,
+  automod_rule,
+  blocked_domain,
+  captcha_answer,
   category,
   comment,
+  comment_aggregates,
   comment_aggregates_fast,
   comment_like,
+  comment_report,
+  comment_revision,
   comment_saved,
   community,
+  community_aggregates,
   community_aggregates_fast,
+  community_flair,
   community_follower,
+  community_import,
   community_moderator,
+  community_rule,
+  community_takeover_request,
   community_user_ban,
+  community_wiki_page,
+  community_wiki_page_revision,
+  custom_feed,
+  custom_feed_community,
+  email_domain,
+  email_verification,
+  image_proxy,
+  ip_ban,
+  job,
+  media,
   mod_add,
   mod_add_community,
   mod_ban,
   mod_ban_from_community,
   mod_lock_post,
+  mod_note,
+  mod_nsfw_post,
+  mod_purge,
   mod_remove_comment,
   mod_remove_community,
   mod_remove_post,
+  mod_resolve_comment_report,
+  mod_resolve_post_report,
+  mod_shadow_ban,
   mod_sticky_post,
+  mod_transfer_community,
+  oauth_app,
+  oauth_authorization,
+  oauth_token,
   password_reset_request,
+  pictrs_thumbnail,
   post,
+  post_aggregates,
   post_aggregates_fast,
+  post_flair,
   post_like,
+  post_hide,
   post_read,
+  post_report,
+  post_revision,
   post_saved,
+  post_subscription,
+  posting_requirement,
   private_message,
+  private_message_conversation,
+  rate_limit_endpoint,
+  rate_limit_exemption,
+  scheduled_post,
   site,
+  site_announcement,
+  site_announcement_dismissal,
+  site_document,
+  sitemap,
   user_,
+  user_aggregates,
+  user_alias,
   user_ban,
+  user_export,
   user_fast,
+  user_follower,
+  user_ip,
+  user_matrix_room,
   user_mention,
+  user_muted_keyword,
+  user_notification_settings,
+  user_push_subscription,
+  webhook,
+  word_filter,
 );
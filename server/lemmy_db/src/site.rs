@@ -1,5 +1,6 @@
 use crate::{schema::site, Crud};
 use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize)]
@@ -14,6 +15,29 @@ pub struct Site {
   pub enable_downvotes: bool,
   pub open_registration: bool,
   pub enable_nsfw: bool,
+  pub require_email_verification: bool,
+  pub public_edit_history: bool,
+  pub archive_after_days: i32,
+  pub require_removal_reason: bool,
+  pub rate_limit_message: Option<i32>,
+  pub rate_limit_message_per_second: Option<i32>,
+  pub rate_limit_post: Option<i32>,
+  pub rate_limit_post_per_second: Option<i32>,
+  pub rate_limit_register: Option<i32>,
+  pub rate_limit_register_per_second: Option<i32>,
+  pub rate_limit_report: Option<i32>,
+  pub rate_limit_report_per_second: Option<i32>,
+  pub block_disposable_emails: bool,
+  pub ip_retention_days: i32,
+  pub registrations_per_ip_per_day: i32,
+  pub mod_inactive_days: i32,
+  pub community_default_nsfw: bool,
+  pub community_default_post_types: i16,
+  pub community_default_sort_type: i16,
+  pub community_default_language: Option<String>,
+  pub federation_enabled: Option<bool>,
+  pub federation_allowed_instances: Option<String>,
+  pub hide_downvote_counts: bool,
 }
 
 #[derive(Insertable, AsChangeset, Clone, Serialize, Deserialize)]
@@ -26,6 +50,52 @@ pub struct SiteForm {
   pub enable_downvotes: bool,
   pub open_registration: bool,
   pub enable_nsfw: bool,
+  pub require_email_verification: bool,
+  pub public_edit_history: bool,
+  pub archive_after_days: i32,
+  pub require_removal_reason: bool,
+  pub block_disposable_emails: bool,
+  pub ip_retention_days: i32,
+  pub registrations_per_ip_per_day: i32,
+  pub mod_inactive_days: i32,
+  pub hide_downvote_counts: bool,
+}
+
+/// The rate limits the admins set at runtime. The ones left unset fall back to the config file.
+#[derive(AsChangeset, Clone, Default, Serialize, Deserialize, Debug, PartialEq, JsonSchema)]
+#[table_name = "site"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct SiteRateLimits {
+  pub rate_limit_message: Option<i32>,
+  pub rate_limit_message_per_second: Option<i32>,
+  pub rate_limit_post: Option<i32>,
+  pub rate_limit_post_per_second: Option<i32>,
+  pub rate_limit_register: Option<i32>,
+  pub rate_limit_register_per_second: Option<i32>,
+  pub rate_limit_report: Option<i32>,
+  pub rate_limit_report_per_second: Option<i32>,
+}
+
+/// What a new community starts out with, until its mods change it.
+#[derive(AsChangeset, Clone, Default, Serialize, Deserialize, Debug, PartialEq, JsonSchema)]
+#[table_name = "site"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct SiteCommunityDefaults {
+  pub community_default_nsfw: bool,
+  pub community_default_post_types: i16,
+  pub community_default_sort_type: i16,
+  pub community_default_language: Option<String>,
+}
+
+/// Whether the site federates and with which instances, as the admins set it at runtime. The
+/// ones left unset fall back to the config file.
+#[derive(AsChangeset, Clone, Default, Serialize, Deserialize, Debug, PartialEq, JsonSchema)]
+#[table_name = "site"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct SiteFederation {
+  pub federation_enabled: Option<bool>,
+  /// Comma separated, like in the config file
+  pub federation_allowed_instances: Option<String>,
 }
 
 impl Crud<SiteForm> for Site {
@@ -51,3 +121,67 @@ impl Crud<SiteForm> for Site {
       .get_result::<Self>(conn)
   }
 }
+
+impl Site {
+  pub fn rate_limits(&self) -> SiteRateLimits {
+    SiteRateLimits {
+      rate_limit_message: self.rate_limit_message,
+      rate_limit_message_per_second: self.rate_limit_message_per_second,
+      rate_limit_post: self.rate_limit_post,
+      rate_limit_post_per_second: self.rate_limit_post_per_second,
+      rate_limit_register: self.rate_limit_register,
+      rate_limit_register_per_second: self.rate_limit_register_per_second,
+      rate_limit_report: self.rate_limit_report,
+      rate_limit_report_per_second: self.rate_limit_report_per_second,
+    }
+  }
+
+  pub fn community_defaults(&self) -> SiteCommunityDefaults {
+    SiteCommunityDefaults {
+      community_default_nsfw: self.community_default_nsfw,
+      community_default_post_types: self.community_default_post_types,
+      community_default_sort_type: self.community_default_sort_type,
+      community_default_language: self.community_default_language.to_owned(),
+    }
+  }
+
+  pub fn federation(&self) -> SiteFederation {
+    SiteFederation {
+      federation_enabled: self.federation_enabled,
+      federation_allowed_instances: self.federation_allowed_instances.to_owned(),
+    }
+  }
+
+  pub fn update_community_defaults(
+    conn: &PgConnection,
+    site_id: i32,
+    defaults: &SiteCommunityDefaults,
+  ) -> Result<Self, Error> {
+    use crate::schema::site::dsl::*;
+    diesel::update(site.find(site_id))
+      .set(defaults)
+      .get_result::<Self>(conn)
+  }
+
+  pub fn update_rate_limits(
+    conn: &PgConnection,
+    site_id: i32,
+    limits: &SiteRateLimits,
+  ) -> Result<Self, Error> {
+    use crate::schema::site::dsl::*;
+    diesel::update(site.find(site_id))
+      .set(limits)
+      .get_result::<Self>(conn)
+  }
+
+  pub fn update_federation(
+    conn: &PgConnection,
+    site_id: i32,
+    federation: &SiteFederation,
+  ) -> Result<Self, Error> {
+    use crate::schema::site::dsl::*;
+    diesel::update(site.find(site_id))
+      .set(federation)
+      .get_result::<Self>(conn)
+  }
+}
@@ -0,0 +1,227 @@
+use crate::{
+  schema::{site_announcement, site_announcement_dismissal},
+  Crud,
+};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// How prominently clients should show an announcement. Stored as an i16.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum AnnouncementSeverity {
+  Info,
+  Warning,
+  Critical,
+}
+
+impl AnnouncementSeverity {
+  pub fn from_i16(value: i16) -> Option<Self> {
+    match value {
+      0 => Some(AnnouncementSeverity::Info),
+      1 => Some(AnnouncementSeverity::Warning),
+      2 => Some(AnnouncementSeverity::Critical),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "site_announcement"]
+pub struct SiteAnnouncement {
+  pub id: i32,
+  pub creator_id: i32,
+  pub content: String,
+  pub severity: i16,
+  pub start_time: chrono::NaiveDateTime,
+  pub end_time: Option<chrono::NaiveDateTime>,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "site_announcement"]
+pub struct SiteAnnouncementForm {
+  pub creator_id: i32,
+  pub content: String,
+  pub severity: i16,
+  pub start_time: Option<chrono::NaiveDateTime>,
+  pub end_time: Option<chrono::NaiveDateTime>,
+}
+
+impl Crud<SiteAnnouncementForm> for SiteAnnouncement {
+  fn read(conn: &PgConnection, announcement_id: i32) -> Result<Self, Error> {
+    use crate::schema::site_announcement::dsl::*;
+    site_announcement.find(announcement_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, announcement_id: i32) -> Result<usize, Error> {
+    use crate::schema::site_announcement::dsl::*;
+    diesel::delete(site_announcement.find(announcement_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &SiteAnnouncementForm) -> Result<Self, Error> {
+    use crate::schema::site_announcement::dsl::*;
+    insert_into(site_announcement)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    announcement_id: i32,
+    form: &SiteAnnouncementForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::site_announcement::dsl::*;
+    diesel::update(site_announcement.find(announcement_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl SiteAnnouncement {
+  /// The announcements that have started and not yet ended, leaving out the ones the user
+  /// dismissed.
+  pub fn list_active(conn: &PgConnection, for_user_id: Option<i32>) -> Result<Vec<Self>, Error> {
+    use crate::schema::site_announcement::dsl::*;
+    let mut query = site_announcement
+      .filter(start_time.le(now))
+      .filter(end_time.is_null().or(end_time.gt(now)))
+      .into_boxed();
+
+    if let Some(for_user_id) = for_user_id {
+      query = query.filter(
+        id.ne_all(
+          site_announcement_dismissal::table
+            .filter(site_announcement_dismissal::user_id.eq(for_user_id))
+            .select(site_announcement_dismissal::announcement_id),
+        ),
+      );
+    }
+
+    query.order_by(start_time.desc()).load::<Self>(conn)
+  }
+}
+
+#[derive(Queryable, Identifiable, PartialEq, Debug)]
+#[table_name = "site_announcement_dismissal"]
+pub struct SiteAnnouncementDismissal {
+  pub id: i32,
+  pub announcement_id: i32,
+  pub user_id: i32,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "site_announcement_dismissal"]
+pub struct SiteAnnouncementDismissalForm {
+  pub announcement_id: i32,
+  pub user_id: i32,
+}
+
+impl SiteAnnouncementDismissal {
+  /// Dismissing the same announcement twice is fine.
+  pub fn dismiss(
+    conn: &PgConnection,
+    form: &SiteAnnouncementDismissalForm,
+  ) -> Result<usize, Error> {
+    use crate::schema::site_announcement_dismissal::dsl::*;
+    insert_into(site_announcement_dismissal)
+      .values(form)
+      .on_conflict_do_nothing()
+      .execute(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{naive_now, tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "announcy".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: true,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let announcement_form = SiteAnnouncementForm {
+      creator_id: inserted_user.id,
+      content: "Maintenance tonight".into(),
+      severity: AnnouncementSeverity::Warning as i16,
+      start_time: None,
+      end_time: None,
+    };
+
+    let inserted_announcement = SiteAnnouncement::create(&conn, &announcement_form).unwrap();
+
+    let expected_announcement = SiteAnnouncement {
+      id: inserted_announcement.id,
+      creator_id: inserted_user.id,
+      content: "Maintenance tonight".into(),
+      severity: AnnouncementSeverity::Warning as i16,
+      start_time: inserted_announcement.start_time,
+      end_time: None,
+      published: inserted_announcement.published,
+    };
+
+    let ended_form = SiteAnnouncementForm {
+      end_time: Some(naive_now() - chrono::Duration::hours(1)),
+      start_time: Some(naive_now() - chrono::Duration::hours(2)),
+      ..announcement_form
+    };
+    let ended_announcement = SiteAnnouncement::create(&conn, &ended_form).unwrap();
+
+    let active = SiteAnnouncement::list_active(&conn, Some(inserted_user.id)).unwrap();
+
+    let dismissal_form = SiteAnnouncementDismissalForm {
+      announcement_id: inserted_announcement.id,
+      user_id: inserted_user.id,
+    };
+    SiteAnnouncementDismissal::dismiss(&conn, &dismissal_form).unwrap();
+    let dismissed_again = SiteAnnouncementDismissal::dismiss(&conn, &dismissal_form).unwrap();
+    let active_after_dismissal =
+      SiteAnnouncement::list_active(&conn, Some(inserted_user.id)).unwrap();
+    let active_logged_out = SiteAnnouncement::list_active(&conn, None).unwrap();
+
+    let num_deleted = SiteAnnouncement::delete(&conn, inserted_announcement.id).unwrap();
+    SiteAnnouncement::delete(&conn, ended_announcement.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(expected_announcement, inserted_announcement);
+    assert!(active.contains(&expected_announcement));
+    assert!(!active.contains(&ended_announcement));
+    assert_eq!(0, dismissed_again);
+    assert!(!active_after_dismissal.contains(&expected_announcement));
+    assert!(active_logged_out.contains(&expected_announcement));
+    assert_eq!(1, num_deleted);
+  }
+}
@@ -0,0 +1,171 @@
+use crate::{schema::site_document, Crud};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A markdown document of the instance, like its terms of service, found by its slug
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "site_document"]
+pub struct SiteDocument {
+  pub id: i32,
+  pub slug: String,
+  pub title: String,
+  pub content: String,
+  pub editor_id: Option<i32>,
+  pub published: chrono::NaiveDateTime,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "site_document"]
+pub struct SiteDocumentForm {
+  pub slug: String,
+  pub title: String,
+  pub content: String,
+  pub editor_id: Option<i32>,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+/// A document without its content, for listing the ones there are
+#[derive(Queryable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct SiteDocumentSummary {
+  pub slug: String,
+  pub title: String,
+  pub published: chrono::NaiveDateTime,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+impl Crud<SiteDocumentForm> for SiteDocument {
+  fn read(conn: &PgConnection, document_id: i32) -> Result<Self, Error> {
+    use crate::schema::site_document::dsl::*;
+    site_document.find(document_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, document_id: i32) -> Result<usize, Error> {
+    use crate::schema::site_document::dsl::*;
+    diesel::delete(site_document.find(document_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &SiteDocumentForm) -> Result<Self, Error> {
+    use crate::schema::site_document::dsl::*;
+    insert_into(site_document)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, document_id: i32, form: &SiteDocumentForm) -> Result<Self, Error> {
+    use crate::schema::site_document::dsl::*;
+    diesel::update(site_document.find(document_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl SiteDocument {
+  pub fn read_from_slug(conn: &PgConnection, for_slug: &str) -> Result<Self, Error> {
+    use crate::schema::site_document::dsl::*;
+    site_document.filter(slug.eq(for_slug)).first::<Self>(conn)
+  }
+
+  pub fn list_summaries(conn: &PgConnection) -> Result<Vec<SiteDocumentSummary>, Error> {
+    use crate::schema::site_document::dsl::*;
+    site_document
+      .select((slug, title, published, updated))
+      .order_by(slug)
+      .load::<SiteDocumentSummary>(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{naive_now, tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "document_editor".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: true,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_document = SiteDocumentForm {
+      slug: "test_terms".into(),
+      title: "Terms of Service".into(),
+      content: "Be nice".into(),
+      editor_id: Some(inserted_user.id),
+      updated: None,
+    };
+
+    let inserted_document = SiteDocument::create(&conn, &new_document).unwrap();
+
+    let expected_document = SiteDocument {
+      id: inserted_document.id,
+      slug: "test_terms".into(),
+      title: "Terms of Service".into(),
+      content: "Be nice".into(),
+      editor_id: Some(inserted_user.id),
+      published: inserted_document.published,
+      updated: None,
+    };
+
+    let read_document = SiteDocument::read_from_slug(&conn, "test_terms").unwrap();
+    let summaries = SiteDocument::list_summaries(&conn).unwrap();
+    let duplicate_document = SiteDocument::create(&conn, &new_document);
+
+    let edit_document = SiteDocumentForm {
+      content: "Be very nice".into(),
+      updated: Some(naive_now()),
+      ..new_document.clone()
+    };
+    let updated_document =
+      SiteDocument::update(&conn, inserted_document.id, &edit_document).unwrap();
+
+    User_::delete(&conn, inserted_user.id).unwrap();
+    let orphaned_document = SiteDocument::read(&conn, inserted_document.id).unwrap();
+    let num_deleted = SiteDocument::delete(&conn, inserted_document.id).unwrap();
+
+    assert_eq!(expected_document, inserted_document);
+    assert_eq!(expected_document, read_document);
+    assert!(summaries.contains(&SiteDocumentSummary {
+      slug: "test_terms".into(),
+      title: "Terms of Service".into(),
+      published: inserted_document.published,
+      updated: None,
+    }));
+    assert!(duplicate_document.is_err());
+    assert_eq!("Be very nice", updated_document.content);
+    assert!(updated_document.updated.is_some());
+    assert_eq!(None, orphaned_document.editor_id);
+    assert_eq!(1, num_deleted);
+  }
+}
@@ -1,4 +1,5 @@
 use diesel::{result::Error, *};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 table! {
@@ -12,6 +13,15 @@ table! {
     enable_downvotes -> Bool,
     open_registration -> Bool,
     enable_nsfw -> Bool,
+    require_email_verification -> Bool,
+    public_edit_history -> Bool,
+    archive_after_days -> Int4,
+    require_removal_reason -> Bool,
+    block_disposable_emails -> Bool,
+    ip_retention_days -> Int4,
+    registrations_per_ip_per_day -> Int4,
+    mod_inactive_days -> Int4,
+    hide_downvote_counts -> Bool,
     creator_name -> Varchar,
     creator_avatar -> Nullable<Text>,
     number_of_users -> BigInt,
@@ -22,7 +32,15 @@ table! {
 }
 
 #[derive(
-  Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, QueryableByName, Clone,
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
 )]
 #[table_name = "site_view"]
 pub struct SiteView {
@@ -35,6 +53,15 @@ pub struct SiteView {
   pub enable_downvotes: bool,
   pub open_registration: bool,
   pub enable_nsfw: bool,
+  pub require_email_verification: bool,
+  pub public_edit_history: bool,
+  pub archive_after_days: i32,
+  pub require_removal_reason: bool,
+  pub block_disposable_emails: bool,
+  pub ip_retention_days: i32,
+  pub registrations_per_ip_per_day: i32,
+  pub mod_inactive_days: i32,
+  pub hide_downvote_counts: bool,
   pub creator_name: String,
   pub creator_avatar: Option<String>,
   pub number_of_users: i64,
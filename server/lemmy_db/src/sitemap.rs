@@ -0,0 +1,269 @@
+use crate::{
+  schema::{community, post, post_aggregates, sitemap},
+  Crud,
+};
+use diesel::{dsl::*, result::Error, *};
+use serde::{Deserialize, Serialize};
+
+/// A page of the sitemap, with the urls of up to a few thousand communities and posts
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone)]
+#[table_name = "sitemap"]
+pub struct Sitemap {
+  pub id: i32,
+  pub page: i32,
+  pub content: String,
+  pub lastmod: chrono::NaiveDateTime,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "sitemap"]
+pub struct SitemapForm {
+  pub page: i32,
+  pub content: String,
+  pub lastmod: chrono::NaiveDateTime,
+}
+
+/// The name of a community, when it was created and when it was last edited
+pub type SitemapCommunity = (String, chrono::NaiveDateTime, Option<chrono::NaiveDateTime>);
+
+/// The id of a post, when it was created, last edited and last commented on
+pub type SitemapPost = (
+  i32,
+  chrono::NaiveDateTime,
+  Option<chrono::NaiveDateTime>,
+  Option<chrono::NaiveDateTime>,
+);
+
+impl Crud<SitemapForm> for Sitemap {
+  fn read(conn: &PgConnection, sitemap_id: i32) -> Result<Self, Error> {
+    use crate::schema::sitemap::dsl::*;
+    sitemap.find(sitemap_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, sitemap_id: i32) -> Result<usize, Error> {
+    use crate::schema::sitemap::dsl::*;
+    diesel::delete(sitemap.find(sitemap_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &SitemapForm) -> Result<Self, Error> {
+    use crate::schema::sitemap::dsl::*;
+    insert_into(sitemap).values(form).get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, sitemap_id: i32, form: &SitemapForm) -> Result<Self, Error> {
+    use crate::schema::sitemap::dsl::*;
+    diesel::update(sitemap.find(sitemap_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl Sitemap {
+  pub fn read_page(conn: &PgConnection, for_page: i32) -> Result<Self, Error> {
+    use crate::schema::sitemap::dsl::*;
+    sitemap.filter(page.eq(for_page)).first::<Self>(conn)
+  }
+
+  /// The number and lastmod of every page, for the index.
+  pub fn list_pages(conn: &PgConnection) -> Result<Vec<(i32, chrono::NaiveDateTime)>, Error> {
+    use crate::schema::sitemap::dsl::*;
+    sitemap
+      .select((page, lastmod))
+      .order_by(page)
+      .load::<(i32, chrono::NaiveDateTime)>(conn)
+  }
+
+  /// Swaps the pages for the newly generated ones at once, so no reader gets half of each.
+  pub fn replace_all(conn: &PgConnection, forms: &[SitemapForm]) -> Result<Vec<Self>, Error> {
+    use crate::schema::sitemap::dsl::*;
+    conn.transaction(|| {
+      diesel::delete(sitemap).execute(conn)?;
+      insert_into(sitemap).values(forms).get_results::<Self>(conn)
+    })
+  }
+
+  /// The local communities that are still up, oldest first.
+  pub fn communities(conn: &PgConnection) -> Result<Vec<SitemapCommunity>, Error> {
+    community::table
+      .filter(community::local.eq(true))
+      .filter(community::removed.eq(false))
+      .filter(community::deleted.eq(false))
+      .select((community::name, community::published, community::updated))
+      .order_by(community::id)
+      .load::<SitemapCommunity>(conn)
+  }
+
+  /// The newest posts of those communities that anyone can see, newest first.
+  pub fn posts(conn: &PgConnection, limit: i64) -> Result<Vec<SitemapPost>, Error> {
+    post::table
+      .inner_join(community::table)
+      .inner_join(post_aggregates::table)
+      .filter(community::local.eq(true))
+      .filter(community::removed.eq(false))
+      .filter(community::deleted.eq(false))
+      .filter(post::removed.eq(false))
+      .filter(post::deleted.eq(false))
+      .filter(post::pending.eq(false))
+      .select((
+        post::id,
+        post::published,
+        post::updated,
+        post_aggregates::newest_comment_time,
+      ))
+      .order_by(post::published.desc())
+      .limit(limit)
+      .load::<SitemapPost>(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    community::*,
+    naive_now,
+    post::*,
+    tests::establish_unpooled_connection,
+    user::*,
+    ListingType,
+    SortType,
+  };
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "mappy".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_sitemap".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_user.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_post = PostForm {
+      name: "A mapped post".into(),
+      creator_id: inserted_user.id,
+      url: None,
+      body: None,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      embed_type: None,
+      url_media_type: None,
+      thumbnail_url: None,
+      ap_id: "http://fake.com".into(),
+      local: true,
+      published: None,
+      pending: None,
+    };
+
+    let inserted_post = Post::create(&conn, &new_post).unwrap();
+    let deleted_post_form = PostForm {
+      name: "A deleted post".into(),
+      deleted: Some(true),
+      ..new_post
+    };
+    let deleted_post = Post::create(&conn, &deleted_post_form).unwrap();
+
+    let communities = Sitemap::communities(&conn).unwrap();
+    let posts = Sitemap::posts(&conn, 9999).unwrap();
+
+    let page_form = SitemapForm {
+      page: 1,
+      content: "<urlset></urlset>".into(),
+      lastmod: naive_now(),
+    };
+    let inserted_page = Sitemap::create(&conn, &page_form).unwrap();
+
+    let replaced_pages = Sitemap::replace_all(&conn, &[page_form.clone()]).unwrap();
+    let read_page = Sitemap::read_page(&conn, 1).unwrap();
+    let listed_pages = Sitemap::list_pages(&conn).unwrap();
+    let old_page = Sitemap::read(&conn, inserted_page.id);
+
+    let expected_page = Sitemap {
+      id: replaced_pages[0].id,
+      page: 1,
+      content: "<urlset></urlset>".into(),
+      lastmod: replaced_pages[0].lastmod,
+      published: replaced_pages[0].published,
+    };
+
+    let num_deleted = Sitemap::delete(&conn, read_page.id).unwrap();
+    Post::delete(&conn, inserted_post.id).unwrap();
+    Post::delete(&conn, deleted_post.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert!(communities.iter().any(|c| c.0 == "test_sitemap"));
+    assert!(posts.iter().any(|p| p.0 == inserted_post.id));
+    assert!(!posts.iter().any(|p| p.0 == deleted_post.id));
+    assert_eq!(vec![expected_page.clone()], replaced_pages);
+    assert_eq!(expected_page, read_page);
+    assert_eq!(vec![(1, expected_page.lastmod)], listed_pages);
+    assert!(old_page.is_err());
+    assert_eq!(1, num_deleted);
+  }
+}
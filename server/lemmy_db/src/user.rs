@@ -1,7 +1,8 @@
 use crate::{
   naive_now,
-  schema::{user_, user_::dsl::*},
+  schema::{user_, user_::dsl::*, user_follower},
   Crud,
+  Followable,
 };
 use bcrypt::{hash, DEFAULT_COST};
 use diesel::{dsl::*, result::Error, *};
@@ -33,6 +34,16 @@ pub struct User_ {
   pub private_key: Option<String>,
   pub public_key: Option<String>,
   pub last_refreshed_at: chrono::NaiveDateTime,
+  pub email_verified: bool,
+  pub banner: Option<String>,
+  pub hide_read_posts: bool,
+  pub bot_account: bool,
+  pub hide_bot_accounts: bool,
+  pub display_name: Option<String>,
+  pub ban_expires: Option<chrono::NaiveDateTime>,
+  pub shadow_banned: bool,
+  pub hide_presence: bool,
+  pub directory_account: bool,
 }
 
 #[derive(Insertable, AsChangeset, Clone, Debug)]
@@ -60,6 +71,12 @@ pub struct UserForm {
   pub private_key: Option<String>,
   pub public_key: Option<String>,
   pub last_refreshed_at: Option<chrono::NaiveDateTime>,
+  pub banner: Option<String>,
+  pub hide_read_posts: bool,
+  pub bot_account: bool,
+  pub hide_bot_accounts: bool,
+  pub display_name: Option<String>,
+  pub hide_presence: bool,
 }
 
 impl Crud<UserForm> for User_ {
@@ -115,9 +132,46 @@ impl User_ {
       .get_result::<Self>(conn)
   }
 
-  pub fn ban_user(conn: &PgConnection, user_id: i32, ban: bool) -> Result<Self, Error> {
+  /// Bans with an expiry get lifted after it, otherwise they last until an admin unbans.
+  pub fn ban_user(
+    conn: &PgConnection,
+    user_id: i32,
+    ban: bool,
+    expires: Option<chrono::NaiveDateTime>,
+  ) -> Result<Self, Error> {
+    let expires = if ban { expires } else { None };
+    diesel::update(user_.find(user_id))
+      .set((banned.eq(ban), ban_expires.eq(expires)))
+      .get_result::<Self>(conn)
+  }
+
+  pub fn shadow_ban(conn: &PgConnection, user_id: i32, ban: bool) -> Result<Self, Error> {
+    diesel::update(user_.find(user_id))
+      .set(shadow_banned.eq(ban))
+      .get_result::<Self>(conn)
+  }
+
+  /// Unbans the users whose ban has run out, returning them.
+  pub fn lift_expired_bans(conn: &PgConnection) -> Result<Vec<Self>, Error> {
+    diesel::update(
+      user_
+        .filter(banned.eq(true))
+        .filter(ban_expires.lt(naive_now())),
+    )
+    .set((
+      banned.eq(false),
+      ban_expires.eq(None::<chrono::NaiveDateTime>),
+    ))
+    .get_results::<Self>(conn)
+  }
+
+  pub fn update_email_verified(
+    conn: &PgConnection,
+    user_id: i32,
+    verified: bool,
+  ) -> Result<Self, Error> {
     diesel::update(user_.find(user_id))
-      .set(banned.eq(ban))
+      .set(email_verified.eq(verified))
       .get_result::<Self>(conn)
   }
 
@@ -125,6 +179,16 @@ impl User_ {
     use crate::schema::user_::dsl::*;
     user_.filter(actor_id.eq(object_id)).first::<Self>(conn)
   }
+
+  /// Registers the account of a user of an LDAP directory, which only the directory logs in to.
+  pub fn register_directory_account(conn: &PgConnection, form: &UserForm) -> Result<Self, Error> {
+    conn.transaction(|| {
+      let registered = Self::register(conn, form)?;
+      diesel::update(user_.find(registered.id))
+        .set(directory_account.eq(true))
+        .get_result::<Self>(conn)
+    })
+  }
 }
 
 impl User_ {
@@ -136,11 +200,80 @@ impl User_ {
     user_.filter(email.eq(from_email)).first::<User_>(conn)
   }
 
+  /// Whether the user is banned from the site, not counting a ban that already expired.
+  pub fn is_banned(&self) -> bool {
+    self.banned && self.ban_expires.map_or(true, |e| e > naive_now())
+  }
+
   pub fn get_profile_url(&self, hostname: &str) -> String {
     format!("https://{}/u/{}", hostname, self.name)
   }
 }
 
+/// A user following another, to see what they post and comment
+#[derive(Identifiable, Queryable, PartialEq, Debug)]
+#[table_name = "user_follower"]
+pub struct UserFollower {
+  pub id: i32,
+  pub user_id: i32,
+  pub follower_id: i32,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "user_follower"]
+pub struct UserFollowerForm {
+  pub user_id: i32,
+  pub follower_id: i32,
+}
+
+impl Followable<UserFollowerForm> for UserFollower {
+  fn follow(conn: &PgConnection, user_follower_form: &UserFollowerForm) -> Result<Self, Error> {
+    insert_into(user_follower::table)
+      .values(user_follower_form)
+      .on_conflict((user_follower::user_id, user_follower::follower_id))
+      .do_update()
+      .set(user_follower_form)
+      .get_result::<Self>(conn)
+  }
+  fn unfollow(conn: &PgConnection, user_follower_form: &UserFollowerForm) -> Result<usize, Error> {
+    diesel::delete(
+      user_follower::table
+        .filter(user_follower::user_id.eq(user_follower_form.user_id))
+        .filter(user_follower::follower_id.eq(user_follower_form.follower_id)),
+    )
+    .execute(conn)
+  }
+}
+
+impl UserFollower {
+  pub fn is_following(
+    conn: &PgConnection,
+    for_user_id: i32,
+    for_follower_id: i32,
+  ) -> Result<bool, Error> {
+    select(exists(
+      user_follower::table
+        .filter(user_follower::user_id.eq(for_user_id))
+        .filter(user_follower::follower_id.eq(for_follower_id)),
+    ))
+    .get_result(conn)
+  }
+
+  /// The actor ids of the followers on other instances, which the user's activities go out to.
+  pub fn remote_follower_actor_ids(
+    conn: &PgConnection,
+    for_user_id: i32,
+  ) -> Result<Vec<String>, Error> {
+    user_follower::table
+      .inner_join(user_.on(id.eq(user_follower::follower_id)))
+      .filter(user_follower::user_id.eq(for_user_id))
+      .filter(local.eq(false))
+      .select(actor_id)
+      .load::<String>(conn)
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use crate::{tests::establish_unpooled_connection, user::*, ListingType, SortType};
@@ -172,6 +305,12 @@ mod tests {
       private_key: None,
       public_key: None,
       last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
     };
 
     let inserted_user = User_::create(&conn, &new_user).unwrap();
@@ -201,15 +340,76 @@ mod tests {
       private_key: None,
       public_key: None,
       last_refreshed_at: inserted_user.published,
+      email_verified: false,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+      ban_expires: None,
+      shadow_banned: false,
+      directory_account: false,
     };
 
     let read_user = User_::read(&conn, inserted_user.id).unwrap();
     let updated_user = User_::update(&conn, inserted_user.id, &new_user).unwrap();
+
+    let follower_user_form = UserForm {
+      name: "thommys_fan".into(),
+      actor_id: "http://fake.com/u/thommys_fan".into(),
+      local: false,
+      ..new_user.clone()
+    };
+    let follower_user = User_::create(&conn, &follower_user_form).unwrap();
+
+    let user_follower_form = UserFollowerForm {
+      user_id: inserted_user.id,
+      follower_id: follower_user.id,
+    };
+    let inserted_user_follower = UserFollower::follow(&conn, &user_follower_form).unwrap();
+    let refollowed_user_follower = UserFollower::follow(&conn, &user_follower_form).unwrap();
+
+    let expected_user_follower = UserFollower {
+      id: inserted_user_follower.id,
+      user_id: inserted_user.id,
+      follower_id: follower_user.id,
+      published: inserted_user_follower.published,
+    };
+
+    let is_following =
+      UserFollower::is_following(&conn, inserted_user.id, follower_user.id).unwrap();
+    let remote_follower_actor_ids =
+      UserFollower::remote_follower_actor_ids(&conn, inserted_user.id).unwrap();
+    let num_unfollowed = UserFollower::unfollow(&conn, &user_follower_form).unwrap();
+    let is_still_following =
+      UserFollower::is_following(&conn, inserted_user.id, follower_user.id).unwrap();
+
+    let directory_user_form = UserForm {
+      name: "thommy_ldap".into(),
+      actor_id: "http://fake.com/u/thommy_ldap".into(),
+      ..new_user
+    };
+    let directory_user = User_::register_directory_account(&conn, &directory_user_form).unwrap();
+
+    User_::delete(&conn, directory_user.id).unwrap();
+    User_::delete(&conn, follower_user.id).unwrap();
     let num_deleted = User_::delete(&conn, inserted_user.id).unwrap();
 
     assert_eq!(expected_user, read_user);
     assert_eq!(expected_user, inserted_user);
     assert_eq!(expected_user, updated_user);
+    assert_eq!(expected_user_follower, inserted_user_follower);
+    assert_eq!(expected_user_follower, refollowed_user_follower);
+    assert!(is_following);
+    assert_eq!(
+      vec!["http://fake.com/u/thommys_fan".to_string()],
+      remote_follower_actor_ids
+    );
+    assert_eq!(1, num_unfollowed);
+    assert!(!is_still_following);
+    assert!(directory_user.directory_account);
+    assert!(!inserted_user.directory_account);
     assert_eq!(1, num_deleted);
   }
 }
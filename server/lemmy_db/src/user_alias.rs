@@ -0,0 +1,192 @@
+use crate::{
+  lower,
+  naive_now,
+  schema::{user_, user_alias},
+  user::User_,
+  Crud,
+};
+use diesel::{dsl::*, result::Error, *};
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone)]
+#[table_name = "user_alias"]
+pub struct UserAlias {
+  pub id: i32,
+  pub user_id: i32,
+  pub name: String,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "user_alias"]
+pub struct UserAliasForm {
+  pub user_id: i32,
+  pub name: String,
+}
+
+impl Crud<UserAliasForm> for UserAlias {
+  fn read(conn: &PgConnection, user_alias_id: i32) -> Result<Self, Error> {
+    use crate::schema::user_alias::dsl::*;
+    user_alias.find(user_alias_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, user_alias_id: i32) -> Result<usize, Error> {
+    use crate::schema::user_alias::dsl::*;
+    diesel::delete(user_alias.find(user_alias_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &UserAliasForm) -> Result<Self, Error> {
+    use crate::schema::user_alias::dsl::*;
+    insert_into(user_alias)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, user_alias_id: i32, form: &UserAliasForm) -> Result<Self, Error> {
+    use crate::schema::user_alias::dsl::*;
+    diesel::update(user_alias.find(user_alias_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl UserAlias {
+  pub fn for_user(conn: &PgConnection, for_user_id: i32) -> Result<Vec<Self>, Error> {
+    use crate::schema::user_alias::dsl::*;
+    user_alias
+      .filter(user_id.eq(for_user_id))
+      .order_by(published)
+      .load::<Self>(conn)
+  }
+
+  pub fn read_from_name(conn: &PgConnection, from_name: &str) -> Result<Self, Error> {
+    use crate::schema::user_alias::dsl::*;
+    user_alias
+      .filter(lower(name).eq(from_name.to_lowercase()))
+      .first::<Self>(conn)
+  }
+
+  /// Reads the user who currently has the name, or else the local user who used to have it.
+  pub fn read_user(conn: &PgConnection, from_name: &str) -> Result<User_, Error> {
+    match User_::read_from_name(conn, from_name) {
+      Err(Error::NotFound) => {
+        let alias = Self::read_from_name(conn, from_name)?;
+        User_::read(conn, alias.user_id)
+      }
+      res => res,
+    }
+  }
+
+  /// Whether the name belongs to a different local user, now or in the past.
+  pub fn is_taken(conn: &PgConnection, for_user_id: i32, new_name: &str) -> Result<bool, Error> {
+    let new_name = new_name.to_lowercase();
+    let taken_by_user = select(exists(
+      user_::table
+        .filter(user_::local.eq(true))
+        .filter(user_::id.ne(for_user_id))
+        .filter(lower(user_::name).eq(&new_name)),
+    ))
+    .get_result::<bool>(conn)?;
+    let taken_by_alias = select(exists(
+      user_alias::table
+        .filter(user_alias::user_id.ne(for_user_id))
+        .filter(lower(user_alias::name).eq(&new_name)),
+    ))
+    .get_result::<bool>(conn)?;
+    Ok(taken_by_user || taken_by_alias)
+  }
+
+  /// Renames the user, keeping their current name as an alias. The actor id is left alone, so
+  /// federated instances keep seeing the same actor.
+  pub fn change_name(
+    conn: &PgConnection,
+    for_user_id: i32,
+    new_name: &str,
+  ) -> Result<User_, Error> {
+    conn.transaction::<_, Error, _>(|| {
+      let user = User_::read(conn, for_user_id)?;
+
+      // Going back to one of your old names keeps its alias
+      insert_into(user_alias::table)
+        .values(&UserAliasForm {
+          user_id: for_user_id,
+          name: user.name,
+        })
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+      diesel::update(user_::table.find(for_user_id))
+        .set((user_::name.eq(new_name), user_::updated.eq(naive_now())))
+        .get_result::<User_>(conn)
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "aliasy".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let renamed_user = UserAlias::change_name(&conn, inserted_user.id, "aliasy_renamed").unwrap();
+    let read_aliases = UserAlias::for_user(&conn, inserted_user.id).unwrap();
+    let read_from_alias = UserAlias::read_user(&conn, "Aliasy").unwrap();
+    let read_from_new_name = UserAlias::read_user(&conn, "aliasy_renamed").unwrap();
+    let taken_by_self = UserAlias::is_taken(&conn, inserted_user.id, "aliasy").unwrap();
+    let taken_by_other = UserAlias::is_taken(&conn, inserted_user.id + 1, "aliasy").unwrap();
+    let num_deleted = UserAlias::delete(&conn, read_aliases[0].id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    let expected_alias = UserAlias {
+      id: read_aliases[0].id,
+      user_id: inserted_user.id,
+      name: "aliasy".into(),
+      published: read_aliases[0].published,
+    };
+
+    assert_eq!("aliasy_renamed", renamed_user.name);
+    assert_eq!(inserted_user.actor_id, renamed_user.actor_id);
+    assert_eq!(vec![expected_alias], read_aliases);
+    assert_eq!(inserted_user.id, read_from_alias.id);
+    assert_eq!(inserted_user.id, read_from_new_name.id);
+    assert!(!taken_by_self);
+    assert!(taken_by_other);
+    assert_eq!(1, num_deleted);
+  }
+}
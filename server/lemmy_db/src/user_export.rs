@@ -0,0 +1,327 @@
+use crate::{
+  comment::Comment,
+  naive_now,
+  post::Post,
+  private_message::PrivateMessage,
+  schema::{
+    comment,
+    comment_like,
+    comment_saved,
+    community,
+    community_follower,
+    post,
+    post_like,
+    post_saved,
+    private_message,
+    user_,
+    user_export,
+    user_export::dsl::*,
+  },
+  Crud,
+};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "user_export"]
+pub struct UserExport {
+  pub id: i32,
+  pub user_id: i32,
+  pub data: Option<serde_json::Value>,
+  pub published: chrono::NaiveDateTime,
+  pub completed: Option<chrono::NaiveDateTime>,
+  pub error: Option<String>,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "user_export"]
+pub struct UserExportForm {
+  pub user_id: i32,
+  pub data: Option<serde_json::Value>,
+  pub completed: Option<chrono::NaiveDateTime>,
+}
+
+impl Crud<UserExportForm> for UserExport {
+  fn read(conn: &PgConnection, user_export_id: i32) -> Result<Self, Error> {
+    user_export.find(user_export_id).first::<Self>(conn)
+  }
+  fn delete(conn: &PgConnection, user_export_id: i32) -> Result<usize, Error> {
+    diesel::delete(user_export.find(user_export_id)).execute(conn)
+  }
+  fn create(conn: &PgConnection, form: &UserExportForm) -> Result<Self, Error> {
+    insert_into(user_export)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+  fn update(
+    conn: &PgConnection,
+    user_export_id: i32,
+    form: &UserExportForm,
+  ) -> Result<Self, Error> {
+    diesel::update(user_export.find(user_export_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl UserExport {
+  /// Whether the user already has an export that hasn't finished generating.
+  pub fn in_progress(conn: &PgConnection, for_user_id: i32) -> Result<bool, Error> {
+    select(exists(
+      user_export
+        .filter(user_id.eq(for_user_id))
+        .filter(completed.is_null()),
+    ))
+    .get_result(conn)
+  }
+
+  /// Assembles the archive for the export, and marks it as completed.
+  pub fn generate(conn: &PgConnection, user_export_id: i32) -> Result<Self, Error> {
+    let found = Self::read(conn, user_export_id)?;
+    let archive = UserExportData::read(conn, found.user_id)?;
+
+    diesel::update(user_export.find(user_export_id))
+      .set((
+        data.eq(serde_json::to_value(archive).ok()),
+        completed.eq(naive_now()),
+      ))
+      .get_result::<Self>(conn)
+  }
+
+  /// Marks the export as completed without an archive, once it ran out of attempts.
+  pub fn fail(conn: &PgConnection, user_export_id: i32, failure: &str) -> Result<Self, Error> {
+    diesel::update(user_export.find(user_export_id))
+      .set((error.eq(failure), completed.eq(naive_now())))
+      .get_result::<Self>(conn)
+  }
+}
+
+/// The account settings, without any of the credentials.
+#[derive(Queryable, Serialize, Deserialize, Debug, PartialEq, JsonSchema)]
+pub struct UserExportSettings {
+  pub name: String,
+  pub preferred_username: Option<String>,
+  pub email: Option<String>,
+  pub avatar: Option<String>,
+  pub bio: Option<String>,
+  pub matrix_user_id: Option<String>,
+  pub show_nsfw: bool,
+  pub theme: String,
+  pub default_sort_type: i16,
+  pub default_listing_type: i16,
+  pub lang: String,
+  pub show_avatars: bool,
+  pub send_notifications_to_email: bool,
+  pub actor_id: String,
+  pub published: chrono::NaiveDateTime,
+}
+
+/// A vote, keyed by the activitypub id of the thing voted on, so it can be resolved on other
+/// instances.
+#[derive(Queryable, Serialize, Deserialize, Debug, PartialEq, JsonSchema)]
+pub struct UserExportVote {
+  pub ap_id: String,
+  pub score: i16,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, JsonSchema)]
+pub struct UserExportData {
+  pub settings: UserExportSettings,
+  pub posts: Vec<Post>,
+  pub comments: Vec<Comment>,
+  pub post_votes: Vec<UserExportVote>,
+  pub comment_votes: Vec<UserExportVote>,
+  pub private_messages: Vec<PrivateMessage>,
+  pub followed_communities: Vec<String>,
+  pub saved_posts: Vec<String>,
+  pub saved_comments: Vec<String>,
+}
+
+impl UserExportData {
+  pub fn read(conn: &PgConnection, for_user_id: i32) -> Result<Self, Error> {
+    let settings = user_::table
+      .find(for_user_id)
+      .select((
+        user_::name,
+        user_::preferred_username,
+        user_::email,
+        user_::avatar,
+        user_::bio,
+        user_::matrix_user_id,
+        user_::show_nsfw,
+        user_::theme,
+        user_::default_sort_type,
+        user_::default_listing_type,
+        user_::lang,
+        user_::show_avatars,
+        user_::send_notifications_to_email,
+        user_::actor_id,
+        user_::published,
+      ))
+      .first::<UserExportSettings>(conn)?;
+
+    let posts = post::table
+      .filter(post::creator_id.eq(for_user_id))
+      .order_by(post::published)
+      .load::<Post>(conn)?;
+
+    let comments = comment::table
+      .filter(comment::creator_id.eq(for_user_id))
+      .order_by(comment::published)
+      .load::<Comment>(conn)?;
+
+    let post_votes = post_like::table
+      .inner_join(post::table)
+      .filter(post_like::user_id.eq(for_user_id))
+      .select((post::ap_id, post_like::score))
+      .load::<UserExportVote>(conn)?;
+
+    let comment_votes = comment_like::table
+      .inner_join(comment::table)
+      .filter(comment_like::user_id.eq(for_user_id))
+      .select((comment::ap_id, comment_like::score))
+      .load::<UserExportVote>(conn)?;
+
+    let private_messages = private_message::table
+      .filter(
+        private_message::creator_id
+          .eq(for_user_id)
+          .or(private_message::recipient_id.eq(for_user_id)),
+      )
+      .order_by(private_message::published)
+      .load::<PrivateMessage>(conn)?;
+
+    let followed_communities = community_follower::table
+      .inner_join(community::table)
+      .filter(community_follower::user_id.eq(for_user_id))
+      .select(community::actor_id)
+      .load::<String>(conn)?;
+
+    let saved_posts = post_saved::table
+      .inner_join(post::table)
+      .filter(post_saved::user_id.eq(for_user_id))
+      .select(post::ap_id)
+      .load::<String>(conn)?;
+
+    let saved_comments = comment_saved::table
+      .inner_join(comment::table)
+      .filter(comment_saved::user_id.eq(for_user_id))
+      .select(comment::ap_id)
+      .load::<String>(conn)?;
+
+    Ok(UserExportData {
+      settings,
+      posts,
+      comments,
+      post_votes,
+      comment_votes,
+      private_messages,
+      followed_communities,
+      saved_posts,
+      saved_comments,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "thommy_export".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_export = UserExportForm {
+      user_id: inserted_user.id,
+      data: None,
+      completed: None,
+    };
+
+    let inserted_export = UserExport::create(&conn, &new_export).unwrap();
+    let in_progress = UserExport::in_progress(&conn, inserted_user.id).unwrap();
+    let generated_export = UserExport::generate(&conn, inserted_export.id).unwrap();
+    let finished = !UserExport::in_progress(&conn, inserted_user.id).unwrap();
+
+    let failing_export = UserExport::create(&conn, &new_export).unwrap();
+    let failed_export = UserExport::fail(&conn, failing_export.id, "timed_out").unwrap();
+    let given_up = !UserExport::in_progress(&conn, inserted_user.id).unwrap();
+
+    let expected_data = UserExportData {
+      settings: UserExportSettings {
+        name: "thommy_export".into(),
+        preferred_username: None,
+        email: None,
+        avatar: None,
+        bio: None,
+        matrix_user_id: None,
+        show_nsfw: false,
+        theme: "darkly".into(),
+        default_sort_type: SortType::Hot as i16,
+        default_listing_type: ListingType::Subscribed as i16,
+        lang: "browser".into(),
+        show_avatars: true,
+        send_notifications_to_email: false,
+        actor_id: "http://fake.com".into(),
+        published: inserted_user.published,
+      },
+      posts: Vec::new(),
+      comments: Vec::new(),
+      post_votes: Vec::new(),
+      comment_votes: Vec::new(),
+      private_messages: Vec::new(),
+      followed_communities: Vec::new(),
+      saved_posts: Vec::new(),
+      saved_comments: Vec::new(),
+    };
+
+    let read_data: UserExportData =
+      serde_json::from_value(generated_export.data.to_owned().unwrap()).unwrap();
+    let num_deleted = User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert!(inserted_export.data.is_none());
+    assert!(in_progress);
+    assert!(finished);
+    assert!(generated_export.completed.is_some());
+    assert!(generated_export.error.is_none());
+    assert_eq!(Some("timed_out".into()), failed_export.error);
+    assert!(failed_export.data.is_none());
+    assert!(given_up);
+    assert_eq!(expected_data, read_data);
+    assert_eq!(1, num_deleted);
+  }
+}
@@ -0,0 +1,157 @@
+use crate::{schema::user_matrix_room, Crud};
+use diesel::{dsl::*, result::Error, *};
+use serde::{Deserialize, Serialize};
+
+/// The direct chat the Matrix bot sends a user their notifications in
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone)]
+#[table_name = "user_matrix_room"]
+pub struct UserMatrixRoom {
+  pub id: i32,
+  pub user_id: i32,
+  pub matrix_user_id: String,
+  pub room_id: String,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "user_matrix_room"]
+pub struct UserMatrixRoomForm {
+  pub user_id: i32,
+  pub matrix_user_id: String,
+  pub room_id: String,
+}
+
+impl Crud<UserMatrixRoomForm> for UserMatrixRoom {
+  fn read(conn: &PgConnection, matrix_room_id: i32) -> Result<Self, Error> {
+    use crate::schema::user_matrix_room::dsl::*;
+    user_matrix_room.find(matrix_room_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, matrix_room_id: i32) -> Result<usize, Error> {
+    use crate::schema::user_matrix_room::dsl::*;
+    diesel::delete(user_matrix_room.find(matrix_room_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &UserMatrixRoomForm) -> Result<Self, Error> {
+    use crate::schema::user_matrix_room::dsl::*;
+    insert_into(user_matrix_room)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    matrix_room_id: i32,
+    form: &UserMatrixRoomForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::user_matrix_room::dsl::*;
+    diesel::update(user_matrix_room.find(matrix_room_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl UserMatrixRoom {
+  /// The room of the user, as long as it's with the Matrix ID they linked now.
+  pub fn read_for_user(
+    conn: &PgConnection,
+    for_user_id: i32,
+    for_matrix_user_id: &str,
+  ) -> Result<Self, Error> {
+    use crate::schema::user_matrix_room::dsl::*;
+    user_matrix_room
+      .filter(user_id.eq(for_user_id))
+      .filter(matrix_user_id.eq(for_matrix_user_id))
+      .first::<Self>(conn)
+  }
+
+  /// Keeps the room, in place of the one with the Matrix ID the user had before.
+  pub fn upsert(conn: &PgConnection, form: &UserMatrixRoomForm) -> Result<Self, Error> {
+    use crate::schema::user_matrix_room::dsl::*;
+    insert_into(user_matrix_room)
+      .values(form)
+      .on_conflict(user_id)
+      .do_update()
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "matrixy".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: Some("@matrixy:example.com".into()),
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let room_form = UserMatrixRoomForm {
+      user_id: inserted_user.id,
+      matrix_user_id: "@matrixy:example.com".into(),
+      room_id: "!first:example.com".into(),
+    };
+
+    let inserted_room = UserMatrixRoom::upsert(&conn, &room_form).unwrap();
+
+    // Linking another Matrix ID takes a new room
+    let relinked_form = UserMatrixRoomForm {
+      matrix_user_id: "@matrixy:example.org".into(),
+      room_id: "!second:example.org".into(),
+      ..room_form
+    };
+    let relinked_room = UserMatrixRoom::upsert(&conn, &relinked_form).unwrap();
+
+    let expected_room = UserMatrixRoom {
+      id: inserted_room.id,
+      user_id: inserted_user.id,
+      matrix_user_id: "@matrixy:example.org".into(),
+      room_id: "!second:example.org".into(),
+      published: inserted_room.published,
+    };
+
+    let read_room =
+      UserMatrixRoom::read_for_user(&conn, inserted_user.id, "@matrixy:example.org").unwrap();
+    let old_room = UserMatrixRoom::read_for_user(&conn, inserted_user.id, "@matrixy:example.com");
+    let num_deleted = UserMatrixRoom::delete(&conn, inserted_room.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(expected_room, relinked_room);
+    assert_eq!(expected_room, read_room);
+    assert!(old_room.is_err());
+    assert_eq!(1, num_deleted);
+  }
+}
@@ -1,5 +1,6 @@
-use crate::{limit_and_offset, MaybeOptional, SortType};
+use crate::{controversy_rank, limit_and_offset, schema::user_, MaybeOptional, SortType};
 use diesel::{dsl::*, pg::Pg, result::Error, *};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 // The faked schema since diesel doesn't do views
@@ -27,6 +28,7 @@ table! {
     banned_from_community -> Bool,
     creator_name -> Varchar,
     creator_avatar -> Nullable<Text>,
+    creator_display_name -> Nullable<Varchar>,
     score -> BigInt,
     upvotes -> BigInt,
     downvotes -> BigInt,
@@ -64,6 +66,7 @@ table! {
     banned_from_community -> Bool,
     creator_name -> Varchar,
     creator_avatar -> Nullable<Text>,
+    creator_display_name -> Nullable<Varchar>,
     score -> BigInt,
     upvotes -> BigInt,
     downvotes -> BigInt,
@@ -77,8 +80,18 @@ table! {
   }
 }
 
+allow_tables_to_appear_in_same_query!(user_mention_fast_view, user_);
+
 #[derive(
-  Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, QueryableByName, Clone,
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
 )]
 #[table_name = "user_mention_fast_view"]
 pub struct UserMentionView {
@@ -104,8 +117,11 @@ pub struct UserMentionView {
   pub banned_from_community: bool,
   pub creator_name: String,
   pub creator_avatar: Option<String>,
+  pub creator_display_name: Option<String>,
   pub score: i64,
+  #[serde(default, skip_serializing_if = "crate::vote_counts_hidden")]
   pub upvotes: i64,
+  #[serde(default, skip_serializing_if = "crate::vote_counts_hidden")]
   pub downvotes: i64,
   pub hot_rank: i32,
   pub user_id: Option<i32>,
@@ -120,6 +136,7 @@ pub struct UserMentionQueryBuilder<'a> {
   conn: &'a PgConnection,
   query: super::user_mention_view::user_mention_fast_view::BoxedQuery<'a, Pg>,
   for_user_id: i32,
+  for_community_id: Option<i32>,
   sort: &'a SortType,
   unread_only: bool,
   page: Option<i64>,
@@ -136,6 +153,7 @@ impl<'a> UserMentionQueryBuilder<'a> {
       conn,
       query,
       for_user_id,
+      for_community_id: None,
       sort: &SortType::New,
       unread_only: false,
       page: None,
@@ -148,6 +166,11 @@ impl<'a> UserMentionQueryBuilder<'a> {
     self
   }
 
+  pub fn for_community_id<T: MaybeOptional<i32>>(mut self, for_community_id: T) -> Self {
+    self.for_community_id = for_community_id.get_optional();
+    self
+  }
+
   pub fn unread_only(mut self, unread_only: bool) -> Self {
     self.unread_only = unread_only;
     self
@@ -172,9 +195,20 @@ impl<'a> UserMentionQueryBuilder<'a> {
       query = query.filter(read.eq(false));
     }
 
+    if let Some(for_community_id) = self.for_community_id {
+      query = query.filter(community_id.eq(for_community_id));
+    }
+
     query = query
       .filter(user_id.eq(self.for_user_id))
-      .filter(recipient_id.eq(self.for_user_id));
+      .filter(recipient_id.eq(self.for_user_id))
+      .filter(
+        creator_id.ne_all(
+          user_::table
+            .filter(user_::shadow_banned.eq(true))
+            .select(user_::id),
+        ),
+      );
 
     query = match self.sort {
       SortType::Hot => query
@@ -194,6 +228,20 @@ impl<'a> UserMentionQueryBuilder<'a> {
       SortType::TopDay => query
         .filter(published.gt(now - 1.days()))
         .order_by(score.desc()),
+      SortType::Controversial => query
+        .order_by(controversy_rank(upvotes, downvotes).desc())
+        .then_order_by(published.desc()),
+      SortType::TopHour => query
+        .filter(published.gt(now - 1.hours()))
+        .order_by(score.desc()),
+      SortType::TopSixHour => query
+        .filter(published.gt(now - 6.hours()))
+        .order_by(score.desc()),
+      SortType::TopTwelveHour => query
+        .filter(published.gt(now - 12.hours()))
+        .order_by(score.desc()),
+      // Comments don't have comments of their own
+      SortType::MostComments => query.order_by(published.desc()),
       // _ => query.order_by(published.desc()),
     };
 
@@ -218,4 +266,14 @@ impl UserMentionView {
       .filter(user_id.eq(from_recipient_id))
       .first::<Self>(conn)
   }
+
+  pub fn count_unread(conn: &PgConnection, for_user_id: i32) -> Result<i64, Error> {
+    use super::user_mention_view::user_mention_fast_view::dsl::*;
+    user_mention_fast_view
+      .filter(user_id.eq(for_user_id))
+      .filter(recipient_id.eq(for_user_id))
+      .filter(read.eq(false))
+      .count()
+      .get_result::<i64>(conn)
+  }
 }
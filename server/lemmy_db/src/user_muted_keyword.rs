@@ -0,0 +1,233 @@
+use crate::{schema::user_muted_keyword, Crud};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A word, or a regex, a user doesn't want to see. The posts and comments with it are collapsed
+/// for them, or with hide left out of their listings, until it expires.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "user_muted_keyword"]
+pub struct UserMutedKeyword {
+  pub id: i32,
+  pub user_id: i32,
+  pub keyword: String,
+  pub is_regex: bool,
+  pub hide: bool,
+  pub expires: Option<chrono::NaiveDateTime>,
+  pub published: chrono::NaiveDateTime,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "user_muted_keyword"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct UserMutedKeywordForm {
+  pub user_id: i32,
+  pub keyword: String,
+  pub is_regex: bool,
+  pub hide: bool,
+  pub expires: Option<chrono::NaiveDateTime>,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+impl Crud<UserMutedKeywordForm> for UserMutedKeyword {
+  fn read(conn: &PgConnection, keyword_id: i32) -> Result<Self, Error> {
+    use crate::schema::user_muted_keyword::dsl::*;
+    user_muted_keyword.find(keyword_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, keyword_id: i32) -> Result<usize, Error> {
+    use crate::schema::user_muted_keyword::dsl::*;
+    diesel::delete(user_muted_keyword.find(keyword_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &UserMutedKeywordForm) -> Result<Self, Error> {
+    use crate::schema::user_muted_keyword::dsl::*;
+    insert_into(user_muted_keyword)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    keyword_id: i32,
+    form: &UserMutedKeywordForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::user_muted_keyword::dsl::*;
+    diesel::update(user_muted_keyword.find(keyword_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl UserMutedKeyword {
+  /// The expired ones too, so they can be renewed
+  pub fn list_for_user(conn: &PgConnection, for_user_id: i32) -> Result<Vec<Self>, Error> {
+    use crate::schema::user_muted_keyword::dsl::*;
+    user_muted_keyword
+      .filter(user_id.eq(for_user_id))
+      .order_by(published.asc())
+      .load::<Self>(conn)
+  }
+
+  /// The keywords are matched by postgres, so it's the one that knows if a regex is valid.
+  pub fn is_valid_regex(conn: &PgConnection, pattern: &str) -> bool {
+    select(sql::<sql_types::Bool>("'' ~* ").bind::<sql_types::Text, _>(pattern.to_owned()))
+      .get_result::<bool>(conn)
+      .is_ok()
+  }
+
+  /// Of the posts, the ones to show collapsed. A user's own posts never are.
+  pub fn collapsed_post_ids(
+    conn: &PgConnection,
+    for_user_id: i32,
+    post_ids: Vec<i32>,
+  ) -> Result<Vec<i32>, Error> {
+    use crate::schema::post::dsl::*;
+    post
+      .filter(id.eq_any(post_ids))
+      .filter(creator_id.ne(for_user_id))
+      .filter(
+        sql::<sql_types::Bool>(&muted_keyword_sql(false, &["post.name", "post.body"]))
+          .bind::<sql_types::Integer, _>(for_user_id)
+          .sql(")"),
+      )
+      .select(id)
+      .load::<i32>(conn)
+  }
+
+  /// Same as `collapsed_post_ids`, for comments.
+  pub fn collapsed_comment_ids(
+    conn: &PgConnection,
+    for_user_id: i32,
+    comment_ids: Vec<i32>,
+  ) -> Result<Vec<i32>, Error> {
+    use crate::schema::comment::dsl::*;
+    comment
+      .filter(id.eq_any(comment_ids))
+      .filter(creator_id.ne(for_user_id))
+      .filter(
+        sql::<sql_types::Bool>(&muted_keyword_sql(false, &["comment.content"]))
+          .bind::<sql_types::Integer, _>(for_user_id)
+          .sql(")"),
+      )
+      .select(id)
+      .load::<i32>(conn)
+  }
+}
+
+/// Whether one of the user's unexpired keywords, of those that hide or those that don't, is in
+/// any of the columns. The id of the user needs to be bound right after it, and followed by a
+/// closing parenthesis.
+pub fn muted_keyword_sql(hide: bool, text_columns: &[&str]) -> String {
+  let matches: Vec<String> = text_columns
+    .iter()
+    .map(|column| {
+      format!(
+        "(case when k.is_regex then coalesce({c}, '') ~* k.keyword \
+        else strpos(lower(coalesce({c}, '')), lower(k.keyword)) > 0 end)",
+        c = column
+      )
+    })
+    .collect();
+  format!(
+    "exists (select 1 from user_muted_keyword k where k.hide = {} \
+    and (k.expires is null or k.expires > now()) and ({}) and k.user_id = ",
+    hide,
+    matches.join(" or ")
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{naive_now, tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "keyword_muter".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_keyword = UserMutedKeywordForm {
+      user_id: inserted_user.id,
+      keyword: "spoiler".into(),
+      is_regex: false,
+      hide: false,
+      expires: None,
+      updated: None,
+    };
+
+    let inserted_keyword = UserMutedKeyword::create(&conn, &new_keyword).unwrap();
+
+    let expected_keyword = UserMutedKeyword {
+      id: inserted_keyword.id,
+      user_id: inserted_user.id,
+      keyword: "spoiler".into(),
+      is_regex: false,
+      hide: false,
+      expires: None,
+      published: inserted_keyword.published,
+      updated: None,
+    };
+
+    let read_keyword = UserMutedKeyword::read(&conn, inserted_keyword.id).unwrap();
+    let listed_keywords = UserMutedKeyword::list_for_user(&conn, inserted_user.id).unwrap();
+
+    let edit_keyword = UserMutedKeywordForm {
+      keyword: "spoil(er|s)".into(),
+      is_regex: true,
+      hide: true,
+      updated: Some(naive_now()),
+      ..new_keyword
+    };
+    let updated_keyword =
+      UserMutedKeyword::update(&conn, inserted_keyword.id, &edit_keyword).unwrap();
+
+    let valid_regex = UserMutedKeyword::is_valid_regex(&conn, "spoil(er|s)");
+    let invalid_regex = UserMutedKeyword::is_valid_regex(&conn, "spoil(er");
+
+    let num_deleted = UserMutedKeyword::delete(&conn, inserted_keyword.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(expected_keyword, inserted_keyword);
+    assert_eq!(expected_keyword, read_keyword);
+    assert_eq!(vec![expected_keyword], listed_keywords);
+    assert!(updated_keyword.is_regex);
+    assert!(updated_keyword.hide);
+    assert!(valid_regex);
+    assert!(!invalid_regex);
+    assert_eq!(1, num_deleted);
+  }
+}
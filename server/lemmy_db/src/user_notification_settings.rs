@@ -0,0 +1,263 @@
+use crate::{
+  naive_now,
+  schema::{community_follower, user_notification_settings},
+};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// How often a user gets emailed the top posts of their subscriptions. Stored as an i16.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum DigestFrequency {
+  Never,
+  Daily,
+  Weekly,
+}
+
+impl DigestFrequency {
+  pub fn from_i16(value: i16) -> Option<Self> {
+    match value {
+      0 => Some(DigestFrequency::Never),
+      1 => Some(DigestFrequency::Daily),
+      2 => Some(DigestFrequency::Weekly),
+      _ => None,
+    }
+  }
+}
+
+/// A row is created for every user by the database, with everything but subscribed posts on.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "user_notification_settings"]
+pub struct UserNotificationSettings {
+  pub id: i32,
+  pub user_id: i32,
+  pub replies: bool,
+  pub mentions: bool,
+  pub private_messages: bool,
+  pub mod_actions: bool,
+  pub subscribed_posts: bool,
+  pub updated: Option<chrono::NaiveDateTime>,
+  pub digest_frequency: i16,
+  pub last_digest_sent_at: Option<chrono::NaiveDateTime>,
+  pub digest_token: String,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "user_notification_settings"]
+pub struct UserNotificationSettingsForm {
+  pub user_id: i32,
+  pub replies: bool,
+  pub mentions: bool,
+  pub private_messages: bool,
+  pub mod_actions: bool,
+  pub subscribed_posts: bool,
+  pub updated: Option<chrono::NaiveDateTime>,
+  pub digest_frequency: i16,
+}
+
+impl UserNotificationSettings {
+  pub fn read_for_user(conn: &PgConnection, for_user_id: i32) -> Result<Self, Error> {
+    use crate::schema::user_notification_settings::dsl::*;
+    user_notification_settings
+      .filter(user_id.eq(for_user_id))
+      .first::<Self>(conn)
+  }
+
+  pub fn update_for_user(
+    conn: &PgConnection,
+    form: &UserNotificationSettingsForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::user_notification_settings::dsl::*;
+    diesel::update(user_notification_settings.filter(user_id.eq(form.user_id)))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+
+  /// The users whose daily or weekly digest hasn't been sent for a day or a week.
+  pub fn due_for_digest(conn: &PgConnection) -> Result<Vec<Self>, Error> {
+    use crate::schema::user_notification_settings::dsl::*;
+    user_notification_settings
+      .filter(
+        sql::<sql_types::Bool>("((digest_frequency = ")
+          .bind::<sql_types::SmallInt, _>(DigestFrequency::Daily as i16)
+          .sql(" and (last_digest_sent_at is null or last_digest_sent_at < now() - interval '1 day'))")
+          .sql(" or (digest_frequency = ")
+          .bind::<sql_types::SmallInt, _>(DigestFrequency::Weekly as i16)
+          .sql(" and (last_digest_sent_at is null or last_digest_sent_at < now() - interval '7 days')))"),
+      )
+      .load::<Self>(conn)
+  }
+
+  pub fn mark_digest_sent(conn: &PgConnection, for_user_id: i32) -> Result<Self, Error> {
+    use crate::schema::user_notification_settings::dsl::*;
+    diesel::update(user_notification_settings.filter(user_id.eq(for_user_id)))
+      .set(last_digest_sent_at.eq(naive_now()))
+      .get_result::<Self>(conn)
+  }
+
+  /// Turns off the digest of whoever the unsubscribe link was for.
+  pub fn unsubscribe_digest(conn: &PgConnection, token: &str) -> Result<Self, Error> {
+    use crate::schema::user_notification_settings::dsl::*;
+    diesel::update(user_notification_settings.filter(digest_token.eq(token)))
+      .set(digest_frequency.eq(DigestFrequency::Never as i16))
+      .get_result::<Self>(conn)
+  }
+
+  /// The subscribers of a community that want to hear about its new posts.
+  pub fn subscribed_post_recipients(
+    conn: &PgConnection,
+    for_community_id: i32,
+  ) -> Result<Vec<i32>, Error> {
+    use crate::schema::user_notification_settings::dsl::*;
+    user_notification_settings
+      .filter(subscribed_posts.eq(true))
+      .filter(
+        user_id.eq_any(
+          community_follower::table
+            .filter(community_follower::community_id.eq(for_community_id))
+            .select(community_follower::user_id),
+        ),
+      )
+      .select(user_id)
+      .load::<i32>(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    community::*,
+    tests::establish_unpooled_connection,
+    user::*,
+    Crud,
+    Followable,
+    ListingType,
+    SortType,
+  };
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "notifier".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_notify".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_user.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let follower_form = CommunityFollowerForm {
+      community_id: inserted_community.id,
+      user_id: inserted_user.id,
+    };
+
+    CommunityFollower::follow(&conn, &follower_form).unwrap();
+
+    let default_settings =
+      UserNotificationSettings::read_for_user(&conn, inserted_user.id).unwrap();
+    let recipients_before =
+      UserNotificationSettings::subscribed_post_recipients(&conn, inserted_community.id).unwrap();
+
+    let settings_form = UserNotificationSettingsForm {
+      user_id: inserted_user.id,
+      replies: false,
+      mentions: true,
+      private_messages: true,
+      mod_actions: true,
+      subscribed_posts: true,
+      updated: Some(naive_now()),
+      digest_frequency: DigestFrequency::Daily as i16,
+    };
+
+    let updated_settings =
+      UserNotificationSettings::update_for_user(&conn, &settings_form).unwrap();
+    let recipients_after =
+      UserNotificationSettings::subscribed_post_recipients(&conn, inserted_community.id).unwrap();
+    let due_before = UserNotificationSettings::due_for_digest(&conn).unwrap();
+    UserNotificationSettings::mark_digest_sent(&conn, inserted_user.id).unwrap();
+    let due_after = UserNotificationSettings::due_for_digest(&conn).unwrap();
+    let unsubscribed =
+      UserNotificationSettings::unsubscribe_digest(&conn, &default_settings.digest_token).unwrap();
+
+    Community::delete(&conn, inserted_community.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    let expected_default_settings = UserNotificationSettings {
+      id: default_settings.id,
+      user_id: inserted_user.id,
+      replies: true,
+      mentions: true,
+      private_messages: true,
+      mod_actions: true,
+      subscribed_posts: false,
+      updated: None,
+      digest_frequency: DigestFrequency::Never as i16,
+      last_digest_sent_at: None,
+      digest_token: default_settings.digest_token.to_owned(),
+    };
+
+    assert_eq!(expected_default_settings, default_settings);
+    assert!(recipients_before.is_empty());
+    assert!(!updated_settings.replies);
+    assert_eq!(vec![inserted_user.id], recipients_after);
+    assert!(due_before.iter().any(|s| s.user_id == inserted_user.id));
+    assert!(!due_after.iter().any(|s| s.user_id == inserted_user.id));
+    assert_eq!(DigestFrequency::Never as i16, unsubscribed.digest_frequency);
+  }
+}
@@ -0,0 +1,166 @@
+use crate::{schema::user_push_subscription, Crud};
+use diesel::{dsl::*, result::Error, *};
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone)]
+#[table_name = "user_push_subscription"]
+pub struct UserPushSubscription {
+  pub id: i32,
+  pub user_id: i32,
+  pub endpoint: String,
+  pub p256dh: String,
+  pub auth: String,
+  pub published: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "user_push_subscription"]
+pub struct UserPushSubscriptionForm {
+  pub user_id: i32,
+  pub endpoint: String,
+  pub p256dh: String,
+  pub auth: String,
+}
+
+impl Crud<UserPushSubscriptionForm> for UserPushSubscription {
+  fn read(conn: &PgConnection, subscription_id: i32) -> Result<Self, Error> {
+    use crate::schema::user_push_subscription::dsl::*;
+    user_push_subscription
+      .find(subscription_id)
+      .first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, subscription_id: i32) -> Result<usize, Error> {
+    use crate::schema::user_push_subscription::dsl::*;
+    diesel::delete(user_push_subscription.find(subscription_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &UserPushSubscriptionForm) -> Result<Self, Error> {
+    use crate::schema::user_push_subscription::dsl::*;
+    insert_into(user_push_subscription)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    subscription_id: i32,
+    form: &UserPushSubscriptionForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::user_push_subscription::dsl::*;
+    diesel::update(user_push_subscription.find(subscription_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl UserPushSubscription {
+  /// Registers the endpoint, taking it over if the browser was registered to someone else.
+  pub fn subscribe(conn: &PgConnection, form: &UserPushSubscriptionForm) -> Result<Self, Error> {
+    use crate::schema::user_push_subscription::dsl::*;
+    insert_into(user_push_subscription)
+      .values(form)
+      .on_conflict(endpoint)
+      .do_update()
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+
+  pub fn unsubscribe(
+    conn: &PgConnection,
+    for_user_id: i32,
+    for_endpoint: &str,
+  ) -> Result<usize, Error> {
+    use crate::schema::user_push_subscription::dsl::*;
+    diesel::delete(
+      user_push_subscription
+        .filter(user_id.eq(for_user_id))
+        .filter(endpoint.eq(for_endpoint)),
+    )
+    .execute(conn)
+  }
+
+  pub fn for_user(conn: &PgConnection, for_user_id: i32) -> Result<Vec<Self>, Error> {
+    use crate::schema::user_push_subscription::dsl::*;
+    user_push_subscription
+      .filter(user_id.eq(for_user_id))
+      .load::<Self>(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "pushy".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let subscription_form = UserPushSubscriptionForm {
+      user_id: inserted_user.id,
+      endpoint: "https://push.example.com/abc".into(),
+      p256dh: "p256dh".into(),
+      auth: "auth".into(),
+    };
+
+    let inserted_subscription = UserPushSubscription::subscribe(&conn, &subscription_form).unwrap();
+
+    let expected_subscription = UserPushSubscription {
+      id: inserted_subscription.id,
+      user_id: inserted_user.id,
+      endpoint: "https://push.example.com/abc".into(),
+      p256dh: "p256dh".into(),
+      auth: "new_auth".into(),
+      published: inserted_subscription.published,
+    };
+
+    // Subscribing the same endpoint again replaces the keys
+    let resubscribe_form = UserPushSubscriptionForm {
+      auth: "new_auth".into(),
+      ..subscription_form
+    };
+    let resubscribed = UserPushSubscription::subscribe(&conn, &resubscribe_form).unwrap();
+    let read_subscriptions = UserPushSubscription::for_user(&conn, inserted_user.id).unwrap();
+    let num_deleted =
+      UserPushSubscription::unsubscribe(&conn, inserted_user.id, &resubscribed.endpoint).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(expected_subscription, resubscribed);
+    assert_eq!(vec![expected_subscription], read_subscriptions);
+    assert_eq!(1, num_deleted);
+  }
+}
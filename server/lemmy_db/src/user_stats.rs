@@ -0,0 +1,270 @@
+use crate::schema::user_aggregates;
+use diesel::{result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The counts and scores of a user, which triggers keep up to date
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "user_aggregates"]
+#[primary_key(user_id)]
+pub struct UserAggregates {
+  pub user_id: i32,
+  pub post_count: i64,
+  pub post_score: i64,
+  pub comment_count: i64,
+  pub comment_score: i64,
+}
+
+/// The karma a user got in one community, from their posts and comments there
+#[derive(QueryableByName, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct CommunityKarma {
+  #[sql_type = "sql_types::Integer"]
+  pub community_id: i32,
+  #[sql_type = "sql_types::Text"]
+  pub community_name: String,
+  #[sql_type = "sql_types::BigInt"]
+  pub post_score: i64,
+  #[sql_type = "sql_types::BigInt"]
+  pub comment_score: i64,
+}
+
+/// How much a user posted and commented in a day, week, month or year, starting at `bucket`
+#[derive(QueryableByName, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ActivityBucket {
+  #[sql_type = "sql_types::Timestamp"]
+  pub bucket: chrono::NaiveDateTime,
+  #[sql_type = "sql_types::BigInt"]
+  pub posts: i64,
+  #[sql_type = "sql_types::BigInt"]
+  pub comments: i64,
+}
+
+/// The lengths of time the activity can be bucketed by, as postgres' `date_trunc` names them
+pub const ACTIVITY_BUCKETS: [&str; 4] = ["day", "week", "month", "year"];
+
+impl UserAggregates {
+  pub fn read(conn: &PgConnection, for_user_id: i32) -> Result<Self, Error> {
+    use crate::schema::user_aggregates::dsl::*;
+    user_aggregates.find(for_user_id).first::<Self>(conn)
+  }
+
+  /// Adds up the scores of the post and comment aggregates of the user, by community, the
+  /// communities they got the most karma in first.
+  pub fn karma_by_community(
+    conn: &PgConnection,
+    for_user_id: i32,
+  ) -> Result<Vec<CommunityKarma>, Error> {
+    sql_query(
+      "select c.id as community_id, c.name::text as community_name, \
+      sum(k.post_score)::bigint as post_score, sum(k.comment_score)::bigint as comment_score \
+      from ( \
+        select p.community_id, pa.score as post_score, 0::bigint as comment_score \
+        from post p join post_aggregates pa on pa.post_id = p.id \
+        where p.creator_id = $1 \
+        union all \
+        select p.community_id, 0::bigint as post_score, ca.score as comment_score \
+        from comment ct join comment_aggregates ca on ca.comment_id = ct.id \
+        join post p on p.id = ct.post_id \
+        where ct.creator_id = $1 \
+      ) k join community c on c.id = k.community_id \
+      group by c.id, c.name \
+      order by sum(k.post_score + k.comment_score) desc, c.name",
+    )
+    .bind::<sql_types::Integer, _>(for_user_id)
+    .load(conn)
+  }
+
+  /// The posts and comments of the user counted by `bucket`, one of `ACTIVITY_BUCKETS`, the
+  /// latest `limit` of those they were active in, newest first.
+  pub fn activity_buckets(
+    conn: &PgConnection,
+    for_user_id: i32,
+    bucket: &str,
+    limit: i64,
+  ) -> Result<Vec<ActivityBucket>, Error> {
+    sql_query(
+      "select a.bucket, count(*) filter (where a.is_post) as posts, \
+      count(*) filter (where not a.is_post) as comments \
+      from ( \
+        select date_trunc($2, published) as bucket, true as is_post from post \
+        where creator_id = $1 \
+        union all \
+        select date_trunc($2, published) as bucket, false as is_post from comment \
+        where creator_id = $1 \
+      ) a \
+      group by a.bucket \
+      order by a.bucket desc \
+      limit $3",
+    )
+    .bind::<sql_types::Integer, _>(for_user_id)
+    .bind::<sql_types::Text, _>(bucket.to_owned())
+    .bind::<sql_types::BigInt, _>(limit)
+    .load(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    comment::*,
+    community::*,
+    post::*,
+    tests::establish_unpooled_connection,
+    user::*,
+    Crud,
+    Likeable,
+    ListingType,
+    SortType,
+  };
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "stats_keeper".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_stats".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_user.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let new_post = PostForm {
+      name: "A test post".into(),
+      creator_id: inserted_user.id,
+      url: None,
+      body: None,
+      community_id: inserted_community.id,
+      removed: None,
+      deleted: None,
+      locked: None,
+      stickied: None,
+      updated: None,
+      nsfw: false,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      embed_type: None,
+      url_media_type: None,
+      thumbnail_url: None,
+      ap_id: "http://fake.com".into(),
+      local: true,
+      published: None,
+      pending: None,
+    };
+
+    let inserted_post = Post::create(&conn, &new_post).unwrap();
+
+    let comment_form = CommentForm {
+      content: "A test comment".into(),
+      creator_id: inserted_user.id,
+      post_id: inserted_post.id,
+      removed: None,
+      deleted: None,
+      read: None,
+      parent_id: None,
+      published: None,
+      updated: None,
+      ap_id: "http://fake.com".into(),
+      local: true,
+    };
+
+    let inserted_comment = Comment::create(&conn, &comment_form).unwrap();
+
+    let post_like_form = PostLikeForm {
+      post_id: inserted_post.id,
+      user_id: inserted_user.id,
+      score: 1,
+    };
+
+    PostLike::like(&conn, &post_like_form).unwrap();
+
+    let expected_aggregates = UserAggregates {
+      user_id: inserted_user.id,
+      post_count: 1,
+      post_score: 1,
+      comment_count: 1,
+      comment_score: 0,
+    };
+
+    let expected_karma = CommunityKarma {
+      community_id: inserted_community.id,
+      community_name: "test_stats".into(),
+      post_score: 1,
+      comment_score: 0,
+    };
+
+    let read_aggregates = UserAggregates::read(&conn, inserted_user.id).unwrap();
+    let karma = UserAggregates::karma_by_community(&conn, inserted_user.id).unwrap();
+    let months = UserAggregates::activity_buckets(&conn, inserted_user.id, "month", 12).unwrap();
+    let no_buckets = UserAggregates::activity_buckets(&conn, inserted_user.id, "day", 0).unwrap();
+
+    Comment::delete(&conn, inserted_comment.id).unwrap();
+    Post::delete(&conn, inserted_post.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(expected_aggregates, read_aggregates);
+    assert_eq!(vec![expected_karma], karma);
+    assert_eq!(1, months.len());
+    assert_eq!(1, months[0].posts);
+    assert_eq!(1, months[0].comments);
+    assert!(months[0].bucket <= inserted_post.published);
+    assert!(no_buckets.is_empty());
+  }
+}
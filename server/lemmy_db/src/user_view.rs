@@ -1,6 +1,7 @@
 use super::user_view::user_fast::BoxedQuery;
 use crate::{fuzzy_search, limit_and_offset, MaybeOptional, SortType};
 use diesel::{dsl::*, pg::Pg, result::Error, *};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 table! {
@@ -8,13 +9,17 @@ table! {
     id -> Int4,
     actor_id -> Text,
     name -> Varchar,
+    display_name -> Nullable<Varchar>,
     avatar -> Nullable<Text>,
     email -> Nullable<Text>,
     matrix_user_id -> Nullable<Text>,
     bio -> Nullable<Text>,
+    banner -> Nullable<Text>,
     local -> Bool,
     admin -> Bool,
     banned -> Bool,
+    ban_expires -> Nullable<Timestamp>,
+    bot_account -> Bool,
     show_avatars -> Bool,
     send_notifications_to_email -> Bool,
     published -> Timestamp,
@@ -30,13 +35,17 @@ table! {
     id -> Int4,
     actor_id -> Text,
     name -> Varchar,
+    display_name -> Nullable<Varchar>,
     avatar -> Nullable<Text>,
     email -> Nullable<Text>,
     matrix_user_id -> Nullable<Text>,
     bio -> Nullable<Text>,
+    banner -> Nullable<Text>,
     local -> Bool,
     admin -> Bool,
     banned -> Bool,
+    ban_expires -> Nullable<Timestamp>,
+    bot_account -> Bool,
     show_avatars -> Bool,
     send_notifications_to_email -> Bool,
     published -> Timestamp,
@@ -48,20 +57,32 @@ table! {
 }
 
 #[derive(
-  Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, QueryableByName, Clone,
+  Queryable,
+  Identifiable,
+  PartialEq,
+  Debug,
+  Serialize,
+  Deserialize,
+  QueryableByName,
+  Clone,
+  JsonSchema,
 )]
 #[table_name = "user_fast"]
 pub struct UserView {
   pub id: i32,
   pub actor_id: String,
   pub name: String,
+  pub display_name: Option<String>,
   pub avatar: Option<String>,
   pub email: Option<String>,
   pub matrix_user_id: Option<String>,
   pub bio: Option<String>,
+  pub banner: Option<String>,
   pub local: bool,
   pub admin: bool,
   pub banned: bool,
+  pub ban_expires: Option<chrono::NaiveDateTime>,
+  pub bot_account: bool,
   pub show_avatars: bool,
   pub send_notifications_to_email: bool,
   pub published: chrono::NaiveDateTime,
@@ -102,7 +123,12 @@ impl<'a> UserQueryBuilder<'a> {
   pub fn search_term<T: MaybeOptional<String>>(mut self, search_term: T) -> Self {
     use super::user_view::user_fast::dsl::*;
     if let Some(search_term) = search_term.get_optional() {
-      self.query = self.query.filter(name.ilike(fuzzy_search(&search_term)));
+      let searcher = fuzzy_search(&search_term);
+      self.query = self.query.filter(
+        name
+          .ilike(searcher.to_owned())
+          .or(display_name.ilike(searcher)),
+      );
     }
     self
   }
@@ -140,6 +166,20 @@ impl<'a> UserQueryBuilder<'a> {
       SortType::TopDay => query
         .filter(published.gt(now - 1.days()))
         .order_by(comment_score.desc()),
+      // Users don't have up and downvotes of their own
+      SortType::Controversial => query.order_by(published.desc()),
+      SortType::TopHour => query
+        .filter(published.gt(now - 1.hours()))
+        .order_by(comment_score.desc()),
+      SortType::TopSixHour => query
+        .filter(published.gt(now - 6.hours()))
+        .order_by(comment_score.desc()),
+      SortType::TopTwelveHour => query
+        .filter(published.gt(now - 12.hours()))
+        .order_by(comment_score.desc()),
+      SortType::MostComments => query
+        .order_by(number_of_comments.desc())
+        .then_order_by(published.desc()),
     };
 
     let (limit, offset) = limit_and_offset(self.page, self.limit);
@@ -164,13 +204,17 @@ impl UserView {
         id,
         actor_id,
         name,
+        display_name,
         avatar,
         "".into_sql::<Nullable<Text>>(),
         matrix_user_id,
         bio,
+        banner,
         local,
         admin,
         banned,
+        ban_expires,
+        bot_account,
         show_avatars,
         send_notifications_to_email,
         published,
@@ -192,13 +236,17 @@ impl UserView {
         id,
         actor_id,
         name,
+        display_name,
         avatar,
         "".into_sql::<Nullable<Text>>(),
         matrix_user_id,
         bio,
+        banner,
         local,
         admin,
         banned,
+        ban_expires,
+        bot_account,
         show_avatars,
         send_notifications_to_email,
         published,
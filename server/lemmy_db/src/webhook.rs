@@ -0,0 +1,253 @@
+use crate::{schema::webhook, Crud};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// What a webhook can be posted about. Stored by name in the `events` of the webhook.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+  NewPost,
+  NewReport,
+  NewRegistration,
+  FederationFailure,
+}
+
+impl WebhookEvent {
+  pub fn name(self) -> &'static str {
+    match self {
+      WebhookEvent::NewPost => "new_post",
+      WebhookEvent::NewReport => "new_report",
+      WebhookEvent::NewRegistration => "new_registration",
+      WebhookEvent::FederationFailure => "federation_failure",
+    }
+  }
+
+  /// The events that aren't about a community, which only the site wide webhooks get
+  pub fn is_site_wide(self) -> bool {
+    match self {
+      WebhookEvent::NewRegistration | WebhookEvent::FederationFailure => true,
+      WebhookEvent::NewPost | WebhookEvent::NewReport => false,
+    }
+  }
+}
+
+/// A URL that gets the events of the site, or of a community, as they happen. The deliveries are
+/// signed with the secret, which only the creator is shown.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "webhook"]
+pub struct Webhook {
+  pub id: i32,
+  pub community_id: Option<i32>,
+  pub creator_id: i32,
+  pub url: String,
+  #[serde(skip)]
+  pub secret: String,
+  pub events: Vec<String>,
+  pub enabled: bool,
+  pub published: chrono::NaiveDateTime,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "webhook"]
+pub struct WebhookForm {
+  pub community_id: Option<i32>,
+  pub creator_id: i32,
+  pub url: String,
+  pub secret: String,
+  pub events: Vec<String>,
+  pub enabled: bool,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+impl Crud<WebhookForm> for Webhook {
+  fn read(conn: &PgConnection, webhook_id: i32) -> Result<Self, Error> {
+    use crate::schema::webhook::dsl::*;
+    webhook.find(webhook_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, webhook_id: i32) -> Result<usize, Error> {
+    use crate::schema::webhook::dsl::*;
+    diesel::delete(webhook.find(webhook_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &WebhookForm) -> Result<Self, Error> {
+    use crate::schema::webhook::dsl::*;
+    insert_into(webhook).values(form).get_result::<Self>(conn)
+  }
+
+  fn update(conn: &PgConnection, webhook_id: i32, form: &WebhookForm) -> Result<Self, Error> {
+    use crate::schema::webhook::dsl::*;
+    diesel::update(webhook.find(webhook_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl Webhook {
+  /// The site wide webhooks when no community is given
+  pub fn list(conn: &PgConnection, for_community_id: Option<i32>) -> Result<Vec<Self>, Error> {
+    use crate::schema::webhook::dsl::*;
+    let query = webhook.into_boxed();
+    let query = match for_community_id {
+      Some(for_community_id) => query.filter(community_id.eq(for_community_id)),
+      None => query.filter(community_id.is_null()),
+    };
+    query.order_by(published).load::<Self>(conn)
+  }
+
+  /// The enabled webhooks that want the event: the site wide ones, plus the ones of the community
+  pub fn for_event(
+    conn: &PgConnection,
+    event: WebhookEvent,
+    for_community_id: Option<i32>,
+  ) -> Result<Vec<Self>, Error> {
+    use crate::schema::webhook::dsl::*;
+    let query = webhook
+      .filter(enabled.eq(true))
+      .filter(events.contains(vec![event.name().to_owned()]))
+      .into_boxed();
+    let query = match for_community_id {
+      Some(for_community_id) => {
+        query.filter(community_id.is_null().or(community_id.eq(for_community_id)))
+      }
+      None => query.filter(community_id.is_null()),
+    };
+    query.load::<Self>(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{community::*, tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "webhook_owner".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_webhook".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_user.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let site_webhook_form = WebhookForm {
+      community_id: None,
+      creator_id: inserted_user.id,
+      url: "https://hooks.example/site".into(),
+      secret: "secret".into(),
+      events: vec!["new_registration".into(), "new_post".into()],
+      enabled: true,
+      updated: None,
+    };
+    let site_webhook = Webhook::create(&conn, &site_webhook_form).unwrap();
+
+    let community_webhook_form = WebhookForm {
+      community_id: Some(inserted_community.id),
+      url: "https://hooks.example/community".into(),
+      events: vec!["new_report".into()],
+      ..site_webhook_form.clone()
+    };
+    let community_webhook = Webhook::create(&conn, &community_webhook_form).unwrap();
+
+    let expected_community_webhook = Webhook {
+      id: community_webhook.id,
+      community_id: Some(inserted_community.id),
+      creator_id: inserted_user.id,
+      url: "https://hooks.example/community".into(),
+      secret: "secret".into(),
+      events: vec!["new_report".into()],
+      enabled: true,
+      published: community_webhook.published,
+      updated: None,
+    };
+
+    let site_webhooks = Webhook::list(&conn, None).unwrap();
+    let community_webhooks = Webhook::list(&conn, Some(inserted_community.id)).unwrap();
+    let report_webhooks =
+      Webhook::for_event(&conn, WebhookEvent::NewReport, Some(inserted_community.id)).unwrap();
+    let registration_webhooks =
+      Webhook::for_event(&conn, WebhookEvent::NewRegistration, None).unwrap();
+    let disabled_form = WebhookForm {
+      enabled: false,
+      ..community_webhook_form
+    };
+    Webhook::update(&conn, community_webhook.id, &disabled_form).unwrap();
+    let disabled_webhooks =
+      Webhook::for_event(&conn, WebhookEvent::NewReport, Some(inserted_community.id)).unwrap();
+
+    Webhook::delete(&conn, site_webhook.id).unwrap();
+    let num_deleted = Webhook::delete(&conn, community_webhook.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(expected_community_webhook, community_webhook);
+    assert!(site_webhooks.contains(&site_webhook));
+    assert_eq!(vec![expected_community_webhook], community_webhooks);
+    assert!(report_webhooks.contains(&community_webhook));
+    assert!(!report_webhooks.contains(&site_webhook));
+    assert!(registration_webhooks.contains(&site_webhook));
+    assert!(!disabled_webhooks
+      .iter()
+      .any(|w| w.id == community_webhook.id));
+    assert_eq!(1, num_deleted);
+  }
+}
@@ -0,0 +1,191 @@
+use crate::{schema::word_filter, Crud};
+use diesel::{dsl::*, result::Error, *};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A word, or a regex, that gets blocked, replaced or flagged. The site wide filters have no
+/// community, and the built in one has no creator.
+#[derive(Queryable, Identifiable, PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[table_name = "word_filter"]
+pub struct WordFilter {
+  pub id: i32,
+  pub community_id: Option<i32>,
+  pub pattern: String,
+  pub is_regex: bool,
+  pub action: i16,
+  pub replacement: Option<String>,
+  pub creator_id: Option<i32>,
+  pub published: chrono::NaiveDateTime,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable, AsChangeset, Clone)]
+#[table_name = "word_filter"]
+pub struct WordFilterForm {
+  pub community_id: Option<i32>,
+  pub pattern: String,
+  pub is_regex: bool,
+  pub action: i16,
+  pub replacement: Option<String>,
+  pub creator_id: Option<i32>,
+  pub updated: Option<chrono::NaiveDateTime>,
+}
+
+impl Crud<WordFilterForm> for WordFilter {
+  fn read(conn: &PgConnection, word_filter_id: i32) -> Result<Self, Error> {
+    use crate::schema::word_filter::dsl::*;
+    word_filter.find(word_filter_id).first::<Self>(conn)
+  }
+
+  fn delete(conn: &PgConnection, word_filter_id: i32) -> Result<usize, Error> {
+    use crate::schema::word_filter::dsl::*;
+    diesel::delete(word_filter.find(word_filter_id)).execute(conn)
+  }
+
+  fn create(conn: &PgConnection, form: &WordFilterForm) -> Result<Self, Error> {
+    use crate::schema::word_filter::dsl::*;
+    insert_into(word_filter)
+      .values(form)
+      .get_result::<Self>(conn)
+  }
+
+  fn update(
+    conn: &PgConnection,
+    word_filter_id: i32,
+    form: &WordFilterForm,
+  ) -> Result<Self, Error> {
+    use crate::schema::word_filter::dsl::*;
+    diesel::update(word_filter.find(word_filter_id))
+      .set(form)
+      .get_result::<Self>(conn)
+  }
+}
+
+impl WordFilter {
+  /// The site wide filters when no community is given
+  pub fn list(conn: &PgConnection, for_community_id: Option<i32>) -> Result<Vec<Self>, Error> {
+    use crate::schema::word_filter::dsl::*;
+    let query = word_filter.into_boxed();
+    let query = match for_community_id {
+      Some(for_community_id) => query.filter(community_id.eq(for_community_id)),
+      None => query.filter(community_id.is_null()),
+    };
+    query.order_by(published.asc()).load::<Self>(conn)
+  }
+
+  /// Every filter, of the site and of all communities
+  pub fn list_all(conn: &PgConnection) -> Result<Vec<Self>, Error> {
+    use crate::schema::word_filter::dsl::*;
+    word_filter.load::<Self>(conn)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{community::*, tests::establish_unpooled_connection, user::*, ListingType, SortType};
+
+  #[test]
+  fn test_crud() {
+    let conn = establish_unpooled_connection();
+
+    let new_user = UserForm {
+      name: "word_filterer".into(),
+      preferred_username: None,
+      password_encrypted: "nope".into(),
+      email: None,
+      matrix_user_id: None,
+      avatar: None,
+      admin: false,
+      banned: false,
+      updated: None,
+      show_nsfw: false,
+      theme: "darkly".into(),
+      default_sort_type: SortType::Hot as i16,
+      default_listing_type: ListingType::Subscribed as i16,
+      lang: "browser".into(),
+      show_avatars: true,
+      send_notifications_to_email: false,
+      actor_id: "http://fake.com".into(),
+      bio: None,
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
+    };
+
+    let inserted_user = User_::create(&conn, &new_user).unwrap();
+
+    let new_community = CommunityForm {
+      name: "test_word_filter".to_string(),
+      title: "nada".to_owned(),
+      description: None,
+      category_id: 1,
+      creator_id: inserted_user.id,
+      removed: None,
+      deleted: None,
+      updated: None,
+      nsfw: false,
+      actor_id: "http://fake.com".into(),
+      local: true,
+      private_key: None,
+      public_key: None,
+      last_refreshed_at: None,
+      published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+    };
+
+    let inserted_community = Community::create(&conn, &new_community).unwrap();
+
+    let filter_form = WordFilterForm {
+      community_id: Some(inserted_community.id),
+      pattern: "spoilers".into(),
+      is_regex: false,
+      action: 2,
+      replacement: None,
+      creator_id: Some(inserted_user.id),
+      updated: None,
+    };
+
+    let inserted_filter = WordFilter::create(&conn, &filter_form).unwrap();
+
+    let expected_filter = WordFilter {
+      id: inserted_filter.id,
+      community_id: Some(inserted_community.id),
+      pattern: "spoilers".into(),
+      is_regex: false,
+      action: 2,
+      replacement: None,
+      creator_id: Some(inserted_user.id),
+      published: inserted_filter.published,
+      updated: None,
+    };
+
+    let community_filters = WordFilter::list(&conn, Some(inserted_community.id)).unwrap();
+    let site_filters = WordFilter::list(&conn, None).unwrap();
+    let all_filters = WordFilter::list_all(&conn).unwrap();
+    let num_deleted = WordFilter::delete(&conn, inserted_filter.id).unwrap();
+    Community::delete(&conn, inserted_community.id).unwrap();
+    User_::delete(&conn, inserted_user.id).unwrap();
+
+    assert_eq!(expected_filter, inserted_filter);
+    assert_eq!(vec![expected_filter], community_filters);
+    assert!(!site_filters.contains(&inserted_filter));
+    assert!(all_filters.contains(&inserted_filter));
+    assert_eq!(1, num_deleted);
+  }
+}
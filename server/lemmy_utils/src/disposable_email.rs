@@ -0,0 +1,64 @@
+//! Throwaway inbox providers, which sites can turn away when people sign up. It only covers the
+//! well known ones, the admins can deny any others themselves.
+
+const DISPOSABLE_EMAIL_DOMAINS: &[&str] = &[
+  "0-mail.com",
+  "10minutemail.com",
+  "10minutemail.net",
+  "20minutemail.com",
+  "33mail.com",
+  "anonbox.net",
+  "burnermail.io",
+  "discard.email",
+  "dispostable.com",
+  "dropmail.me",
+  "emailondeck.com",
+  "fakeinbox.com",
+  "getairmail.com",
+  "getnada.com",
+  "guerrillamail.biz",
+  "guerrillamail.com",
+  "guerrillamail.de",
+  "guerrillamail.info",
+  "guerrillamail.net",
+  "guerrillamail.org",
+  "guerrillamailblock.com",
+  "harakirimail.com",
+  "incognitomail.org",
+  "installs.io",
+  "jetable.org",
+  "mailcatch.com",
+  "maildrop.cc",
+  "mailinator.com",
+  "mailinator.net",
+  "mailnesia.com",
+  "mintemail.com",
+  "moakt.com",
+  "mohmal.com",
+  "mytemp.email",
+  "mytrashmail.com",
+  "sharklasers.com",
+  "spam4.me",
+  "spambox.us",
+  "spamgourmet.com",
+  "tempail.com",
+  "temp-mail.org",
+  "tempmail.net",
+  "tempmailo.com",
+  "tempr.email",
+  "throwawaymail.com",
+  "trashmail.com",
+  "trashmail.de",
+  "trashmail.net",
+  "yopmail.com",
+  "yopmail.fr",
+  "yopmail.net",
+];
+
+/// Whether the domain, or one it belongs to, is a known throwaway inbox provider.
+pub fn is_disposable_email_domain(domain: &str) -> bool {
+  let domain = domain.to_lowercase();
+  DISPOSABLE_EMAIL_DOMAINS
+    .iter()
+    .any(|d| domain == *d || domain.ends_with(&format!(".{}", d)))
+}
@@ -0,0 +1,165 @@
+//! The translations of what the server writes itself: the emails it sends and the messages of the
+//! API errors. Every language has a JSON file in the translations folder, read once at startup.
+//! A string a language doesn't have comes from the language it's a variant of, then from English,
+//! and as a last resort the key itself is shown.
+
+use serde_json::Value;
+use std::{collections::HashMap, env, fs, io::Error, path::Path, sync::RwLock};
+
+static TRANSLATIONS_DIR: &str = "translations";
+
+/// The language everything falls back to
+pub const DEFAULT_LANG: &str = "en";
+
+lazy_static! {
+  static ref TRANSLATIONS: RwLock<HashMap<String, HashMap<String, String>>> =
+    RwLock::new(HashMap::new());
+}
+
+/// Reads every `<lang>.json` of the folder, returning the languages that were found. The nested
+/// objects of a file become dotted keys, so `{"emails": {"inbox": ".."}}` is `emails.inbox`.
+pub fn load_translations(dir: &Path) -> Result<Vec<String>, Error> {
+  let mut translations = HashMap::new();
+  for entry in fs::read_dir(dir)? {
+    let path = entry?.path();
+    if path.extension().and_then(|e| e.to_str()) != Some("json") {
+      continue;
+    }
+    let lang = match path.file_stem().and_then(|s| s.to_str()) {
+      Some(stem) => normalize_lang(stem),
+      None => continue,
+    };
+    let json: Value = serde_json::from_str(&fs::read_to_string(&path)?)?;
+    let mut strings = HashMap::new();
+    flatten_strings("", &json, &mut strings);
+    translations.insert(lang, strings);
+  }
+
+  let mut langs: Vec<String> = translations.keys().cloned().collect();
+  langs.sort();
+  set_translations(translations);
+  Ok(langs)
+}
+
+/// Where the translations are, which `LEMMY_TRANSLATIONS_LOCATION` can change.
+pub fn get_translations_location() -> String {
+  env::var("LEMMY_TRANSLATIONS_LOCATION").unwrap_or_else(|_| TRANSLATIONS_DIR.to_string())
+}
+
+/// Swaps in a new set of translations, keyed by language and then by string.
+pub fn set_translations(translations: HashMap<String, HashMap<String, String>>) {
+  *TRANSLATIONS.write().unwrap() = translations;
+}
+
+/// The string in the first of the languages that has it, with the `{{name}}`s filled in.
+pub fn translate(langs: &[String], key: &str, args: &[(&str, &str)]) -> String {
+  let translations = TRANSLATIONS.read().unwrap();
+  let text = fallback_chain(langs)
+    .iter()
+    .find_map(|lang| translations.get(lang).and_then(|t| t.get(key)))
+    .map(|t| t.as_str())
+    .unwrap_or(key);
+
+  fill_in(text, args)
+}
+
+/// The languages to try for someone whose setting is `lang`. The setting is "browser" for those
+/// who never picked one, which a server can't know about, so they get the default.
+pub fn user_langs(lang: &str) -> Vec<String> {
+  if lang == "browser" || lang.trim().is_empty() {
+    Vec::new()
+  } else {
+    vec![lang.to_owned()]
+  }
+}
+
+/// The languages of an Accept-Language header, the most preferred first.
+pub fn accept_language(header: &str) -> Vec<String> {
+  let mut langs: Vec<(String, f32)> = header
+    .split(',')
+    .filter_map(|part| {
+      let mut pieces = part.split(';');
+      let lang = pieces.next()?.trim();
+      if lang.is_empty() || lang == "*" {
+        return None;
+      }
+      let quality = pieces
+        .find_map(|p| p.trim().strip_prefix("q=").map(|q| q.parse::<f32>()))
+        .unwrap_or(Ok(1.0))
+        .ok()?;
+      if quality <= 0.0 {
+        None
+      } else {
+        Some((lang.to_owned(), quality))
+      }
+    })
+    .collect();
+
+  // Stable, so the ones of the same quality keep their order
+  langs.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+  langs.into_iter().map(|(lang, _)| lang).collect()
+}
+
+/// Every language to try in order: each of them, then what it's a variant of, then the default.
+pub fn fallback_chain(langs: &[String]) -> Vec<String> {
+  let mut chain: Vec<String> = Vec::new();
+  for lang in langs {
+    let lang = normalize_lang(lang);
+    let base = lang.split('_').next().unwrap_or_default().to_owned();
+    for l in [lang, base].iter() {
+      if !l.is_empty() && !chain.contains(l) {
+        chain.push(l.to_owned());
+      }
+    }
+  }
+  if !chain.iter().any(|l| l == DEFAULT_LANG) {
+    chain.push(DEFAULT_LANG.to_owned());
+  }
+  chain
+}
+
+/// Swaps each `{{name}}` for its value in one pass, so a value with braces in it stays as it is.
+/// The ones without a value are left in.
+fn fill_in(text: &str, args: &[(&str, &str)]) -> String {
+  let mut filled = String::with_capacity(text.len());
+  let mut rest = text;
+  while let Some(start) = rest.find("{{") {
+    let end = match rest[start..].find("}}") {
+      Some(end) => start + end,
+      None => break,
+    };
+    let name = &rest[start + 2..end];
+    filled.push_str(&rest[..start]);
+    match args.iter().find(|(n, _)| *n == name) {
+      Some((_, value)) => filled.push_str(value),
+      None => filled.push_str(&rest[start..end + 2]),
+    }
+    rest = &rest[end + 2..];
+  }
+  filled.push_str(rest);
+  filled
+}
+
+/// `pt-BR` and `pt_br` are the same language.
+fn normalize_lang(lang: &str) -> String {
+  lang.trim().to_lowercase().replace('-', "_")
+}
+
+fn flatten_strings(prefix: &str, value: &Value, strings: &mut HashMap<String, String>) {
+  match value {
+    Value::Object(map) => {
+      for (key, value) in map {
+        let key = if prefix.is_empty() {
+          key.to_owned()
+        } else {
+          format!("{}.{}", prefix, key)
+        };
+        flatten_strings(&key, value, strings);
+      }
+    }
+    Value::String(text) => {
+      strings.insert(prefix.to_owned(), text.to_owned());
+    }
+    _ => {}
+  }
+}
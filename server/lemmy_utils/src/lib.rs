@@ -9,7 +9,10 @@ pub extern crate regex;
 pub extern crate serde_json;
 pub extern crate url;
 
+pub mod disposable_email;
+pub mod i18n;
 pub mod settings;
+pub mod word_filter;
 
 use crate::settings::Settings;
 use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, Utc};
@@ -25,10 +28,20 @@ use lettre::{
   Transport,
 };
 use lettre_email::Email;
-use openssl::{pkey::PKey, rsa::Rsa};
+use openssl::{
+  base64,
+  bn::BigNumContext,
+  ec::{EcGroup, EcKey, PointConversionForm},
+  nid::Nid,
+  pkey::{PKey, Private},
+  rsa::Rsa,
+};
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
-use regex::{Regex, RegexBuilder};
-use std::io::{Error, ErrorKind};
+use regex::Regex;
+use std::{
+  io::{Error, ErrorKind},
+  net::IpAddr,
+};
 use url::Url;
 
 pub fn to_datetime_utc(ndt: NaiveDateTime) -> DateTime<Utc> {
@@ -48,12 +61,23 @@ pub fn is_email_regex(test: &str) -> bool {
   EMAIL_REGEX.is_match(test)
 }
 
+/// The lowercased part after the last @, if there is one.
+pub fn email_domain(email: &str) -> Option<String> {
+  let email = email.trim();
+  match email.rfind('@') {
+    Some(at) if at > 0 && at + 1 < email.len() => Some(email[at + 1..].to_lowercase()),
+    _ => None,
+  }
+}
+
+/// Where text can't be rejected, the site's word filters only replace.
 pub fn remove_slurs(test: &str) -> String {
-  SLUR_REGEX.replace_all(test, "*removed*").to_string()
+  word_filter::site_replace(test)
 }
 
+/// Where text can't be changed, the site's word filters that would replace block instead.
 pub fn slur_check(test: &str) -> Result<(), Vec<&str>> {
-  let mut matches: Vec<&str> = SLUR_REGEX.find_iter(test).map(|mat| mat.as_str()).collect();
+  let mut matches: Vec<&str> = word_filter::site_matches(test);
 
   // Unique
   matches.sort_unstable();
@@ -66,9 +90,9 @@ pub fn slur_check(test: &str) -> Result<(), Vec<&str>> {
   }
 }
 
-pub fn slurs_vec_to_str(slurs: Vec<&str>) -> String {
+pub fn slurs_vec_to_str<S: AsRef<str>>(slurs: Vec<S>) -> String {
   let start = "No slurs - ";
-  let combined = &slurs.join(", ");
+  let combined = &slurs.iter().map(|s| s.as_ref()).join(", ");
   [start, combined].concat()
 }
 
@@ -162,18 +186,109 @@ pub fn is_valid_post_title(title: &str) -> bool {
   VALID_POST_TITLE_REGEX.is_match(title)
 }
 
+pub fn is_valid_wiki_slug(slug: &str) -> bool {
+  VALID_WIKI_SLUG_REGEX.is_match(slug)
+}
+
+/// A Matrix user ID like `@alice:matrix.org`, with the server name and maybe a port.
+pub fn is_valid_matrix_id(matrix_id: &str) -> bool {
+  VALID_MATRIX_ID_REGEX.is_match(matrix_id)
+}
+
+/// Whether the url points at an image, going by its file extension.
+pub fn is_image_url(url: &str) -> bool {
+  let path = url.split(['?', '#']).next().unwrap_or("");
+  let extension = path.rsplit('.').next().unwrap_or("").to_lowercase();
+  path.contains('.')
+    && ["jpg", "jpeg", "png", "gif", "webp", "svg", "bmp", "avif"].contains(&extension.as_str())
+}
+
+/// A language tag like `en` or `pt-BR`.
+pub fn is_valid_language_code(code: &str) -> bool {
+  code.len() <= 20 && VALID_LANGUAGE_CODE_REGEX.is_match(code)
+}
+
+pub fn is_valid_domain_pattern(pattern: &str) -> bool {
+  pattern.len() <= 255 && VALID_DOMAIN_PATTERN_REGEX.is_match(pattern)
+}
+
+/// A pattern like `*.example.com` covers example.com and all of its subdomains, any other pattern
+/// only covers that exact host.
+pub fn domain_matches(pattern: &str, host: &str) -> bool {
+  let host = host.to_lowercase();
+  match pattern.strip_prefix("*.") {
+    Some(parent) => host == parent || host.ends_with(&format!(".{}", parent)),
+    None => host == pattern,
+  }
+}
+
+/// An IP range is either a single address, or one in CIDR notation like `10.0.0.0/8`.
+fn parse_ip_range(range: &str) -> Option<(IpAddr, u8)> {
+  let mut parts = range.trim().splitn(2, '/');
+  let addr: IpAddr = parts.next()?.parse().ok()?;
+  let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+  let prefix = match parts.next() {
+    Some(prefix) => prefix.parse::<u8>().ok().filter(|p| *p <= max_prefix)?,
+    None => max_prefix,
+  };
+  Some((addr, prefix))
+}
+
+pub fn is_valid_ip_range(range: &str) -> bool {
+  parse_ip_range(range).is_some()
+}
+
+/// Whether the IP lies in the range. IPv4 addresses only ever match IPv4 ranges, and the same for
+/// IPv6.
+pub fn ip_in_range(range: &str, ip: &str) -> bool {
+  let (range_addr, prefix) = match parse_ip_range(range) {
+    Some(range) => range,
+    None => return false,
+  };
+  let ip: IpAddr = match ip.parse() {
+    Ok(ip) => ip,
+    Err(_e) => return false,
+  };
+
+  let (range_bits, ip_bits, width) = match (range_addr, ip) {
+    (IpAddr::V4(r), IpAddr::V4(i)) => (u32::from(r) as u128, u32::from(i) as u128, 32),
+    (IpAddr::V6(r), IpAddr::V6(i)) => (u128::from(r), u128::from(i), 128),
+    _ => return false,
+  };
+  if prefix == 0 {
+    return true;
+  }
+  let shift = width - u32::from(prefix);
+  (range_bits >> shift) == (ip_bits >> shift)
+}
+
 #[cfg(test)]
 mod tests {
   use crate::{
+    disposable_email::is_disposable_email_domain,
+    domain_matches,
+    email_domain,
+    generate_vapid_keypair,
+    i18n::{accept_language, set_translations, translate, user_langs},
+    ip_in_range,
     is_email_regex,
+    is_image_url,
     is_valid_community_name,
+    is_valid_domain_pattern,
+    is_valid_ip_range,
+    is_valid_language_code,
+    is_valid_matrix_id,
     is_valid_post_title,
     is_valid_username,
+    is_valid_wiki_slug,
     remove_slurs,
     scrape_text_for_mentions,
     slur_check,
     slurs_vec_to_str,
+    vapid_public_key,
+    word_filter::*,
   };
+  use std::collections::HashMap;
 
   #[test]
   fn test_mentions_regex() {
@@ -185,12 +300,42 @@ mod tests {
     assert_eq!(mentions[1].domain, "lemmy-alpha:8540".to_string());
   }
 
+  #[test]
+  fn test_vapid_keypair() {
+    let keypair = generate_vapid_keypair().unwrap();
+
+    // An uncompressed P-256 point is 65 bytes, so 87 chars of unpadded base64
+    assert_eq!(87, keypair.public_key.len());
+    assert!(!keypair.public_key.contains(&['+', '/', '='][..]));
+    assert_eq!(
+      keypair.public_key,
+      vapid_public_key(&keypair.private_key).unwrap()
+    );
+  }
+
   #[test]
   fn test_email() {
     assert!(is_email_regex("gush@gmail.com"));
     assert!(!is_email_regex("nada_neutho"));
   }
 
+  #[test]
+  fn test_email_domain() {
+    assert_eq!(Some("gmail.com".into()), email_domain("gush@GMail.com"));
+    assert_eq!(Some("b.com".into()), email_domain("\"a@b\"@b.com"));
+    assert_eq!(None, email_domain("nada_neutho"));
+    assert_eq!(None, email_domain("gush@"));
+    assert_eq!(None, email_domain("@gmail.com"));
+  }
+
+  #[test]
+  fn test_disposable_email_domain() {
+    assert!(is_disposable_email_domain("mailinator.com"));
+    assert!(is_disposable_email_domain("eu.Mailinator.com"));
+    assert!(!is_disposable_email_domain("notmailinator.com"));
+    assert!(!is_disposable_email_domain("gmail.com"));
+  }
+
   #[test]
   fn test_valid_register_username() {
     assert!(is_valid_username("Hello_98"));
@@ -216,8 +361,155 @@ mod tests {
     assert!(!is_valid_post_title("\n \n \n \n    		")); // tabs/spaces/newlines
   }
 
+  #[test]
+  fn test_valid_wiki_slug() {
+    assert!(is_valid_wiki_slug("rules"));
+    assert!(is_valid_wiki_slug("getting-started_2"));
+    assert!(!is_valid_wiki_slug("Rules"));
+    assert!(!is_valid_wiki_slug("two words"));
+    assert!(!is_valid_wiki_slug(""));
+  }
+
+  #[test]
+  fn test_image_url() {
+    assert!(is_image_url("https://example.com/cat.jpg"));
+    assert!(is_image_url(
+      "https://example.com/pictrs/image/cat.PNG?thumbnail=96"
+    ));
+    assert!(!is_image_url("https://example.com/cat.html"));
+    assert!(!is_image_url("https://example.com/jpg"));
+  }
+
+  #[test]
+  fn test_valid_matrix_id() {
+    assert!(is_valid_matrix_id("@alice:matrix.org"));
+    assert!(is_valid_matrix_id("@bob.smith:localhost:8448"));
+    assert!(!is_valid_matrix_id("alice:matrix.org"));
+    assert!(!is_valid_matrix_id("@Alice:matrix.org"));
+    assert!(!is_valid_matrix_id("@alice"));
+    assert!(!is_valid_matrix_id(""));
+  }
+
+  #[test]
+  fn test_valid_language_code() {
+    assert!(is_valid_language_code("en"));
+    assert!(is_valid_language_code("pt-BR"));
+    assert!(is_valid_language_code("zh-Hant-TW"));
+    assert!(!is_valid_language_code("English"));
+    assert!(!is_valid_language_code("e"));
+    assert!(!is_valid_language_code("en_US"));
+    assert!(!is_valid_language_code(""));
+  }
+
+  #[test]
+  fn test_valid_domain_pattern() {
+    assert!(is_valid_domain_pattern("example.com"));
+    assert!(is_valid_domain_pattern("*.example.com"));
+    assert!(is_valid_domain_pattern("*.xyz"));
+    assert!(!is_valid_domain_pattern("Example.com"));
+    assert!(!is_valid_domain_pattern("*"));
+    assert!(!is_valid_domain_pattern("ex*ample.com"));
+    assert!(!is_valid_domain_pattern(""));
+  }
+
+  #[test]
+  fn test_domain_matches() {
+    assert!(domain_matches("example.com", "example.com"));
+    assert!(domain_matches("example.com", "Example.com"));
+    assert!(!domain_matches("example.com", "www.example.com"));
+    assert!(domain_matches("*.example.com", "example.com"));
+    assert!(domain_matches("*.example.com", "cdn.www.example.com"));
+    assert!(!domain_matches("*.example.com", "notexample.com"));
+  }
+
+  #[test]
+  fn test_ip_ranges() {
+    assert!(is_valid_ip_range("192.168.1.7"));
+    assert!(is_valid_ip_range("10.0.0.0/8"));
+    assert!(is_valid_ip_range("2001:db8::/32"));
+    assert!(!is_valid_ip_range("10.0.0.0/33"));
+    assert!(!is_valid_ip_range("example.com"));
+
+    assert!(ip_in_range("192.168.1.7", "192.168.1.7"));
+    assert!(!ip_in_range("192.168.1.7", "192.168.1.8"));
+    assert!(ip_in_range("10.0.0.0/8", "10.200.3.4"));
+    assert!(!ip_in_range("10.0.0.0/8", "11.0.0.1"));
+    assert!(ip_in_range("0.0.0.0/0", "8.8.8.8"));
+    assert!(ip_in_range("2001:db8::/32", "2001:db8:1::5"));
+    assert!(!ip_in_range("2001:db8::/32", "10.0.0.1"));
+    assert!(!ip_in_range("10.0.0.0/8", "blank_ip"));
+  }
+
+  #[test]
+  fn test_translate() {
+    let strings = |pairs: &[(&str, &str)]| {
+      pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect::<HashMap<String, String>>()
+    };
+    let mut translations = HashMap::new();
+    let en = strings(&[("hi", "Hi {{name}}"), ("bye", "Bye")]);
+    translations.insert("en".into(), en);
+    translations.insert("de".into(), strings(&[("hi", "Hallo {{name}}")]));
+    translations.insert("pt_br".into(), strings(&[("hi", "Olá {{name}}")]));
+    set_translations(translations);
+
+    let hi = |langs: &[&str]| {
+      let langs: Vec<String> = langs.iter().map(|l| l.to_string()).collect();
+      translate(&langs, "hi", &[("name", "Ana")])
+    };
+    assert_eq!("Hallo Ana", hi(&["de-AT"]));
+    assert_eq!("Olá Ana", hi(&["pt-BR"]));
+    assert_eq!("Hallo Ana", hi(&["fr", "de"]));
+    assert_eq!("Hi Ana", hi(&[]));
+    assert_eq!("Bye", translate(&["de".to_string()], "bye", &[]));
+    let browser = user_langs("browser");
+    assert_eq!("Hi Ana", translate(&browser, "hi", &[("name", "Ana")]));
+    assert_eq!("nope", translate(&["de".to_string()], "nope", &[]));
+    assert_eq!("Hi {{name}}", translate(&[], "hi", &[("name", "{{name}}")]));
+    assert_eq!("Hi {{name}}", translate(&[], "hi", &[]));
+  }
+
+  #[test]
+  fn test_accept_language() {
+    assert_eq!(
+      vec!["fr-CH", "fr", "en", "de"],
+      accept_language("fr-CH, fr;q=0.9, en;q=0.8, de;q=0.7, *;q=0.5")
+    );
+    assert_eq!(vec!["de", "en"], accept_language("en;q=0.5,de"));
+    assert_eq!(vec!["en"], accept_language("en, es;q=0, it;q=nope"));
+    assert!(accept_language("").is_empty());
+  }
+
   #[test]
   fn test_slur_filter() {
+    // The filter the migration starts the table with
+    let pattern = r"(fag(g|got|tard)?|maricos?|cock\s?sucker(s|ing)?|\bn(i|1)g(\b|g?(a|er)?(s|z)?)\b|dindu(s?)|mudslime?s?|kikes?|mongoloids?|towel\s*heads?|\bspi(c|k)s?\b|\bchinks?|niglets?|beaners?|\bnips?\b|\bcoons?\b|jungle\s*bunn(y|ies?)|jigg?aboo?s?|\bpakis?\b|rag\s*heads?|gooks?|cunts?|bitch(es|ing|y)?|puss(y|ies?)|twats?|feminazis?|whor(es?|ing)|\bslut(s|t?y)?|\btr(a|@)nn?(y|ies?)|ladyboy(s?)|\b(b|re|r)tard(ed)?s?)";
+    set_word_filters(vec![
+      CompiledWordFilter {
+        regex: build_word_filter_regex(pattern, true).unwrap(),
+        action: WordFilterAction::Replace,
+        replacement: "*removed*".into(),
+        community_id: None,
+        creator_id: None,
+      },
+      CompiledWordFilter {
+        regex: build_word_filter_regex("spam", false).unwrap(),
+        action: WordFilterAction::Block,
+        replacement: "*removed*".into(),
+        community_id: Some(1),
+        creator_id: Some(2),
+      },
+      CompiledWordFilter {
+        regex: build_word_filter_regex("scam", false).unwrap(),
+        action: WordFilterAction::Flag,
+        replacement: "*removed*".into(),
+        community_id: None,
+        creator_id: Some(2),
+      },
+    ]);
+
     let test =
       "coons test dindu ladyboy tranny retardeds. Capitalized Niggerz. This is a bunch of other safe text.";
     let slur_free = "No slurs here";
@@ -242,6 +534,23 @@ mod tests {
     if let Err(slur_vec) = slur_check(test) {
       assert_eq!(&slurs_vec_to_str(slur_vec), has_slurs_err_str);
     }
+
+    let spam = "Buy my SPAM, it's no scam. But spammers are fine.";
+    assert_eq!(filter_words(spam, Some(1)), Err(vec!["SPAM".to_string()]));
+    assert_eq!(
+      filter_words("Some coons", Some(1)).map(|f| f.text),
+      Ok("Some *removed*".to_string())
+    );
+    let filtered = filter_words(spam, Some(3)).unwrap();
+    assert_eq!(filtered.text, spam);
+    assert_eq!(
+      filtered.flagged,
+      vec![FlaggedWord {
+        creator_id: Some(2),
+        word: "scam".into()
+      }]
+    );
+    assert_eq!(slur_check(spam), Ok(()));
   }
 
   // These helped with testing
@@ -254,7 +563,6 @@ mod tests {
 
 lazy_static! {
   static ref EMAIL_REGEX: Regex = Regex::new(r"^[a-zA-Z0-9.!#$%&’*+/=?^_`{|}~-]+@[a-zA-Z0-9-]+(?:\.[a-zA-Z0-9-]+)*$").unwrap();
-  static ref SLUR_REGEX: Regex = RegexBuilder::new(r"(fag(g|got|tard)?|maricos?|cock\s?sucker(s|ing)?|\bn(i|1)g(\b|g?(a|er)?(s|z)?)\b|dindu(s?)|mudslime?s?|kikes?|mongoloids?|towel\s*heads?|\bspi(c|k)s?\b|\bchinks?|niglets?|beaners?|\bnips?\b|\bcoons?\b|jungle\s*bunn(y|ies?)|jigg?aboo?s?|\bpakis?\b|rag\s*heads?|gooks?|cunts?|bitch(es|ing|y)?|puss(y|ies?)|twats?|feminazis?|whor(es?|ing)|\bslut(s|t?y)?|\btr(a|@)nn?(y|ies?)|ladyboy(s?)|\b(b|re|r)tard(ed)?s?)").case_insensitive(true).build().unwrap();
   static ref USERNAME_MATCHES_REGEX: Regex = Regex::new(r"/u/[a-zA-Z][0-9a-zA-Z_]*").unwrap();
   // TODO keep this old one, it didn't work with port well tho
   // static ref MENTIONS_REGEX: Regex = Regex::new(r"@(?P<name>[\w.]+)@(?P<domain>[a-zA-Z0-9._-]+\.[a-zA-Z0-9_-]+)").unwrap();
@@ -262,6 +570,13 @@ lazy_static! {
   static ref VALID_USERNAME_REGEX: Regex = Regex::new(r"^[a-zA-Z0-9_]{3,20}$").unwrap();
   static ref VALID_COMMUNITY_NAME_REGEX: Regex = Regex::new(r"^[a-z0-9_]{3,20}$").unwrap();
   static ref VALID_POST_TITLE_REGEX: Regex = Regex::new(r".*\S.*").unwrap();
+  static ref VALID_WIKI_SLUG_REGEX: Regex = Regex::new(r"^[a-z0-9_-]{1,100}$").unwrap();
+  static ref VALID_MATRIX_ID_REGEX: Regex =
+    Regex::new(r"^@[a-z0-9._=/+-]+:[a-zA-Z0-9.-]+(:[0-9]{1,5})?$").unwrap();
+  static ref VALID_LANGUAGE_CODE_REGEX: Regex =
+    Regex::new(r"^[a-z]{2,3}(-[a-zA-Z0-9]{2,8})*$").unwrap();
+  static ref VALID_DOMAIN_PATTERN_REGEX: Regex =
+    Regex::new(r"^(\*\.)?[a-z0-9-]+(\.[a-z0-9-]+)*$").unwrap();
   pub static ref WEBFINGER_COMMUNITY_REGEX: Regex = Regex::new(&format!(
     "^group:([a-z0-9_]{{3, 20}})@{}$",
     Settings::get().hostname
@@ -300,6 +615,47 @@ pub fn generate_actor_keypair() -> Result<Keypair, Error> {
   })
 }
 
+/// Generate the P-256 keypair that signs web push requests (VAPID). The public key is in the url
+/// safe base64 form browsers take as the `applicationServerKey`.
+pub fn generate_vapid_keypair() -> Result<Keypair, Error> {
+  let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+  let key = EcKey::generate(&group)?;
+  let private_key = match String::from_utf8(key.private_key_to_pem()?) {
+    Ok(s) => s,
+    Err(e) => {
+      return Err(Error::new(
+        ErrorKind::Other,
+        format!("Failed converting key to string: {}", e),
+      ))
+    }
+  };
+  Ok(Keypair {
+    private_key,
+    public_key: vapid_public_key_for(&key)?,
+  })
+}
+
+/// The public half of a PEM encoded VAPID private key.
+pub fn vapid_public_key(private_key: &str) -> Result<String, Error> {
+  let key = EcKey::private_key_from_pem(private_key.as_bytes())?;
+  vapid_public_key_for(&key)
+}
+
+fn vapid_public_key_for(key: &EcKey<Private>) -> Result<String, Error> {
+  let mut ctx = BigNumContext::new()?;
+  let bytes =
+    key
+      .public_key()
+      .to_bytes(key.group(), PointConversionForm::UNCOMPRESSED, &mut ctx)?;
+  Ok(
+    base64::encode_block(&bytes)
+      .replace('+', "-")
+      .replace('/', "_")
+      .trim_end_matches('=')
+      .to_string(),
+  )
+}
+
 pub enum EndpointType {
   Community,
   User,
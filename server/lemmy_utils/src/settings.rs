@@ -1,6 +1,8 @@
-use config::{Config, ConfigError, Environment, File};
-use serde::Deserialize;
-use std::{env, fs, io::Error, net::IpAddr, sync::RwLock};
+use crate::{generate_vapid_keypair, vapid_public_key, Keypair};
+use config::{Config, ConfigError, Environment, File, FileFormat};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{env, fs, io::Error, net::IpAddr, path::Path, sync::RwLock};
 
 static CONFIG_FILE_DEFAULTS: &str = "config/defaults.hjson";
 static CONFIG_FILE: &str = "config/config.hjson";
@@ -17,6 +19,19 @@ pub struct Settings {
   pub rate_limit: RateLimitConfig,
   pub email: Option<EmailConfig>,
   pub federation: Federation,
+  pub captcha: CaptchaConfig,
+  pub web_push: WebPushConfig,
+  pub jobs: JobsConfig,
+  pub cache: CacheConfig,
+  pub metrics: MetricsConfig,
+  pub logging: LoggingConfig,
+  pub websocket: WebsocketConfig,
+  pub image_proxy: ImageProxyConfig,
+  pub pictrs: PictrsConfig,
+  pub nsfw_classifier: Option<NsfwClassifierConfig>,
+  pub ldap: Option<LdapConfig>,
+  pub matrix: Option<MatrixConfig>,
+  pub hooks: Option<Vec<HookConfig>>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -35,6 +50,9 @@ pub struct RateLimitConfig {
   pub post_per_second: i32,
   pub register: i32,
   pub register_per_second: i32,
+  pub report: i32,
+  pub report_per_second: i32,
+  pub redis_url: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -46,6 +64,162 @@ pub struct EmailConfig {
   pub use_tls: bool,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct CaptchaConfig {
+  pub enabled: bool,
+  pub difficulty: String, // easy, medium, or hard
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WebPushConfig {
+  pub enabled: bool,
+  pub vapid_key_file: String,
+  pub subject: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct JobsConfig {
+  pub workers: usize,
+  /// How long the workers get to finish their jobs when the server shuts down
+  pub shutdown_timeout_seconds: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CacheConfig {
+  pub enabled: bool,
+  pub post_listing_ttl_seconds: u64,
+  pub site_ttl_seconds: u64,
+  pub max_post_listings: usize,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MetricsConfig {
+  pub enabled: bool,
+  /// Needed to scrape the metrics from the main address
+  pub token: Option<String>,
+  /// Serves the metrics on an address of their own instead, without the token
+  pub bind: Option<IpAddr>,
+  pub port: u16,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct LoggingConfig {
+  /// One json object per line instead of plain text, with the fields of the spans
+  pub json: bool,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ImageProxyConfig {
+  pub enabled: bool,
+  pub max_cached_images: i64,
+  /// The largest thumbnail that can be asked for, in pixels
+  pub max_thumbnail_size: u32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PictrsConfig {
+  /// Where lemmy reaches pictrs, without a trailing slash
+  pub url: String,
+  pub uploads: UploadConfig,
+}
+
+/// What's done to the images users upload before pictrs gets them. Their metadata is always
+/// stripped.
+#[derive(Debug, Deserialize, Clone)]
+pub struct UploadConfig {
+  /// The largest image that's taken, in bytes
+  pub max_size: usize,
+  /// The largest audio or video file for an attachment post, in bytes
+  pub max_attachment_size: usize,
+  /// What the images can be, going by what's in them rather than what the client says
+  pub allowed_types: Vec<String>,
+  /// How many images a user can upload in a day
+  pub max_per_day: Option<i64>,
+  /// Decodes the images and encodes them again, with the limits below
+  pub reencode: bool,
+  /// Larger images are shrunk to this many pixels on their longest side
+  pub max_dimension: u32,
+  /// Encodes them as WebP instead of the format they came in
+  pub webp: bool,
+  /// From 0 to 100
+  pub webp_quality: f32,
+}
+
+/// An outside service that tells how likely an image is NSFW. It's sent the image, and answers
+/// with `{"nsfw": 0.97}`.
+#[derive(Debug, Deserialize, Clone)]
+pub struct NsfwClassifierConfig {
+  pub url: String,
+  /// From 0 to 1. The posts with an image above it are flagged
+  pub threshold: f32,
+  pub action: String, // mark_nsfw or review
+}
+
+/// A directory the users can log in against. The ones in it get an account here the first time they
+/// log in.
+#[derive(Debug, Deserialize, Clone)]
+pub struct LdapConfig {
+  pub url: String,
+  /// The account the users are searched with, or none to search anonymously
+  pub bind_dn: Option<String>,
+  pub bind_password: Option<String>,
+  pub user_base_dn: String,
+  /// Finds the user, with `{username}` for what they log in with
+  pub user_filter: String,
+  /// Where the name of their account here comes from
+  pub username_attribute: String,
+  pub email_attribute: String,
+}
+
+/// The Matrix account of a bot, which sends the users that linked a Matrix ID their inbox
+/// notifications in a direct chat.
+#[derive(Debug, Deserialize, Clone)]
+pub struct MatrixConfig {
+  /// Like https://matrix.example.com
+  pub homeserver_url: String,
+  pub access_token: String,
+  /// How many notifications a user gets per period, the rest wait for the next
+  pub notifications: i32,
+  pub notifications_per_second: i32,
+}
+
+/// Where a hook is called before an action goes through
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+  PrePublishPost,
+  PreRegister,
+  PreFederate,
+}
+
+impl HookEvent {
+  pub fn name(self) -> &'static str {
+    match self {
+      HookEvent::PrePublishPost => "pre_publish_post",
+      HookEvent::PreRegister => "pre_register",
+      HookEvent::PreFederate => "pre_federate",
+    }
+  }
+}
+
+/// A service that's asked before the actions of its events, and can change or reject them.
+#[derive(Debug, Deserialize, Clone)]
+pub struct HookConfig {
+  pub url: String,
+  pub events: Vec<HookEvent>,
+  /// Signs the requests like the webhooks, when set
+  pub secret: Option<String>,
+  pub timeout_seconds: u64,
+  /// Rejects the actions while the hook can't be reached, instead of letting them through
+  pub fail_closed: bool,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WebsocketConfig {
+  /// Where the server processes pass each other the messages for their websocket clients
+  pub redis_url: Option<String>,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Database {
   pub user: String,
@@ -54,6 +228,15 @@ pub struct Database {
   pub port: i32,
   pub database: String,
   pub pool_size: u32,
+  pub read_replicas: Vec<DatabaseReplica>,
+}
+
+/// A copy of the database that is only read from. It takes the same user, password and database
+/// name as the primary.
+#[derive(Debug, Deserialize, Clone)]
+pub struct DatabaseReplica {
+  pub host: String,
+  pub port: i32,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -68,6 +251,16 @@ lazy_static! {
     Ok(c) => c,
     Err(e) => panic!("{}", e),
   });
+  static ref FEDERATION_OVERRIDES: RwLock<FederationOverrides> =
+    RwLock::new(FederationOverrides::default());
+}
+
+/// What the admins changed about the config file's federation, read from the site. The ones left
+/// unset keep the config file's value.
+#[derive(Debug, Clone, Default)]
+pub struct FederationOverrides {
+  pub enabled: Option<bool>,
+  pub allowed_instances: Option<String>,
 }
 
 impl Settings {
@@ -92,12 +285,43 @@ impl Settings {
     // https://github.com/mehcode/config-rs/issues/73
     s.merge(Environment::with_prefix("LEMMY").separator("__"))?;
 
+    // The lists and objects, like `LEMMY_DATABASE__READ_REPLICAS`, are given as JSON, which the
+    // environment source would take for a string
+    for (key, value) in env::vars() {
+      if let Some(json) = env_json_override(&key, &value) {
+        s.merge(File::from_str(&json, FileFormat::Hjson))?;
+      }
+    }
+
     s.try_into()
   }
 
-  /// Returns the config as a struct.
+  /// Reads the config files and environment again, keeping the config as it was if they're
+  /// invalid. Whatever calls `get()` from then on sees the changes, like the rate limits, the
+  /// federation and the email, but what's only read on startup, like the database and the port,
+  /// needs a restart.
+  pub fn reload() -> Result<(), ConfigError> {
+    let settings = Settings::init()?;
+    *SETTINGS.write().unwrap() = settings;
+    Ok(())
+  }
+
+  /// Returns the config as a struct, with the admins' federation overrides applied.
   pub fn get() -> Self {
-    SETTINGS.read().unwrap().to_owned()
+    let mut settings = SETTINGS.read().unwrap().to_owned();
+    let overrides = FEDERATION_OVERRIDES.read().unwrap();
+    if let Some(enabled) = overrides.enabled {
+      settings.federation.enabled = enabled;
+    }
+    if let Some(allowed_instances) = &overrides.allowed_instances {
+      settings.federation.allowed_instances = allowed_instances.to_owned();
+    }
+    settings
+  }
+
+  /// Swaps in the admins' federation overrides, which every `get()` from then on applies.
+  pub fn set_federation_overrides(overrides: FederationOverrides) {
+    *FEDERATION_OVERRIDES.write().unwrap() = overrides;
   }
 
   pub fn get_database_url(&self) -> String {
@@ -111,10 +335,45 @@ impl Settings {
     )
   }
 
+  pub fn get_read_replica_urls(&self) -> Vec<String> {
+    self
+      .database
+      .read_replicas
+      .iter()
+      .map(|replica| {
+        format!(
+          "postgres://{}:{}@{}:{}/{}",
+          self.database.user,
+          self.database.password,
+          replica.host,
+          replica.port,
+          self.database.database
+        )
+      })
+      .collect()
+  }
+
   pub fn api_endpoint(&self) -> String {
     format!("{}/api/v1", self.hostname)
   }
 
+  /// Reads the VAPID keypair for web push, generating and saving it the first time.
+  pub fn get_vapid_keypair(&self) -> Result<Keypair, Error> {
+    let key_file = &self.web_push.vapid_key_file;
+    if Path::new(key_file).exists() {
+      let private_key = fs::read_to_string(key_file)?;
+      let public_key = vapid_public_key(&private_key)?;
+      Ok(Keypair {
+        private_key,
+        public_key,
+      })
+    } else {
+      let keypair = generate_vapid_keypair()?;
+      fs::write(key_file, &keypair.private_key)?;
+      Ok(keypair)
+    }
+  }
+
   pub fn get_config_location() -> String {
     env::var("LEMMY_CONFIG_LOCATION").unwrap_or_else(|_| CONFIG_FILE.to_string())
   }
@@ -127,13 +386,28 @@ impl Settings {
     fs::write(Self::get_config_location(), data)?;
 
     // Reload the new settings
-    // From https://stackoverflow.com/questions/29654927/how-do-i-assign-a-string-to-a-mutable-static-variable/47181804#47181804
-    let mut new_settings = SETTINGS.write().unwrap();
-    *new_settings = match Settings::init() {
-      Ok(c) => c,
-      Err(e) => panic!("{}", e),
-    };
+    if let Err(e) = Self::reload() {
+      panic!("{}", e);
+    }
 
     Self::read_config_file()
   }
 }
+
+/// A config file of nothing but the env var's key, when it's one of ours holding a JSON list or
+/// object. `LEMMY_DATABASE__READ_REPLICAS=[..]` becomes `{"database": {"read_replicas": [..]}}`.
+fn env_json_override(key: &str, value: &str) -> Option<String> {
+  let path = key.strip_prefix("LEMMY_")?.to_lowercase();
+  let value = value.trim();
+  if !value.starts_with('[') && !value.starts_with('{') {
+    return None;
+  }
+
+  let mut json: Value = serde_json::from_str(value).ok()?;
+  for part in path.rsplit("__") {
+    let mut object = Map::new();
+    object.insert(part.to_string(), json);
+    json = Value::Object(object);
+  }
+  Some(json.to_string())
+}
@@ -0,0 +1,141 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+
+/// What happens to text that a word filter matches. Stored as an i16.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum WordFilterAction {
+  /// The text gets rejected
+  Block,
+  /// The match gets swapped for the filter's replacement
+  Replace,
+  /// The text goes through, but gets reported for a mod to look at
+  Flag,
+}
+
+impl WordFilterAction {
+  pub fn from_i16(value: i16) -> Option<Self> {
+    match value {
+      0 => Some(WordFilterAction::Block),
+      1 => Some(WordFilterAction::Replace),
+      2 => Some(WordFilterAction::Flag),
+      _ => None,
+    }
+  }
+}
+
+/// A word filter as it gets applied. The site wide filters have no community.
+#[derive(Debug, Clone)]
+pub struct CompiledWordFilter {
+  pub regex: Regex,
+  pub action: WordFilterAction,
+  pub replacement: String,
+  pub community_id: Option<i32>,
+  /// Who the reports for flagged text are filed as. None for the built in filter.
+  pub creator_id: Option<i32>,
+}
+
+/// A flag filter that matched, and what it matched
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlaggedWord {
+  pub creator_id: Option<i32>,
+  pub word: String,
+}
+
+/// Text that made it through the filters, with the replacements made.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilteredText {
+  pub text: String,
+  pub flagged: Vec<FlaggedWord>,
+}
+
+lazy_static! {
+  static ref WORD_FILTERS: RwLock<Vec<CompiledWordFilter>> = RwLock::new(Vec::new());
+}
+
+/// Turns a stored pattern into a regex. Plain words only match whole words.
+pub fn build_word_filter_regex(pattern: &str, is_regex: bool) -> Result<Regex, regex::Error> {
+  let pattern = if is_regex {
+    pattern.to_owned()
+  } else {
+    format!(r"\b{}\b", regex::escape(pattern.trim()))
+  };
+  RegexBuilder::new(&pattern).case_insensitive(true).build()
+}
+
+/// Swaps in a new set of filters, which apply from the next check on.
+pub fn set_word_filters(filters: Vec<CompiledWordFilter>) {
+  *WORD_FILTERS.write().unwrap() = filters;
+}
+
+/// Runs the site's filters, plus the ones of the community if there is one. Fails with the
+/// blocked words if any block filter matched.
+pub fn filter_words(text: &str, community_id: Option<i32>) -> Result<FilteredText, Vec<String>> {
+  let filters = WORD_FILTERS.read().unwrap();
+  let applicable = filters
+    .iter()
+    .filter(|f| f.community_id.is_none() || f.community_id == community_id);
+
+  let mut blocked: Vec<String> = Vec::new();
+  let mut flagged: Vec<FlaggedWord> = Vec::new();
+  let mut filtered = text.to_owned();
+  for filter in applicable {
+    match filter.action {
+      WordFilterAction::Block => blocked.extend(
+        filter
+          .regex
+          .find_iter(text)
+          .map(|mat| mat.as_str().to_owned()),
+      ),
+      WordFilterAction::Replace => {
+        filtered = filter
+          .regex
+          .replace_all(&filtered, filter.replacement.as_str())
+          .to_string();
+      }
+      WordFilterAction::Flag => {
+        flagged.extend(filter.regex.find_iter(text).map(|mat| FlaggedWord {
+          creator_id: filter.creator_id,
+          word: mat.as_str().to_owned(),
+        }))
+      }
+    }
+  }
+
+  if blocked.is_empty() {
+    Ok(FilteredText {
+      text: filtered,
+      flagged,
+    })
+  } else {
+    blocked.sort_unstable();
+    blocked.dedup();
+    Err(blocked)
+  }
+}
+
+/// The site wide block and replace filters that match, for text that can't be changed.
+pub(crate) fn site_matches(test: &str) -> Vec<&str> {
+  WORD_FILTERS
+    .read()
+    .unwrap()
+    .iter()
+    .filter(|f| f.community_id.is_none() && f.action != WordFilterAction::Flag)
+    .flat_map(|f| f.regex.find_iter(test).map(|mat| mat.as_str()))
+    .collect()
+}
+
+/// Applies the site wide block and replace filters as replacements, for text that can't be
+/// rejected.
+pub(crate) fn site_replace(test: &str) -> String {
+  WORD_FILTERS
+    .read()
+    .unwrap()
+    .iter()
+    .filter(|f| f.community_id.is_none() && f.action != WordFilterAction::Flag)
+    .fold(test.to_owned(), |text, f| {
+      f.regex
+        .replace_all(&text, f.replacement.as_str())
+        .to_string()
+    })
+}
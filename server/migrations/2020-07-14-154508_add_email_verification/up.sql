@@ -0,0 +1,27 @@
+-- Email verification tokens
+create table email_verification (
+  id serial primary key,
+  user_id int references user_ on update cascade on delete cascade not null,
+  email text not null,
+  token_encrypted text not null,
+  published timestamp not null default now()
+);
+
+-- Existing accounts are considered verified
+alter table user_ add column email_verified boolean default false not null;
+update user_ set email_verified = true;
+
+alter table site add column require_email_verification boolean default false not null;
+
+-- Recreate the site_view to pick up the new column
+drop view site_view;
+
+create view site_view as 
+select *,
+(select name from user_ u where s.creator_id = u.id) as creator_name,
+(select avatar from user_ u where s.creator_id = u.id) as creator_avatar,
+(select count(*) from user_) as number_of_users,
+(select count(*) from post) as number_of_posts,
+(select count(*) from comment) as number_of_comments,
+(select count(*) from community) as number_of_communities
+from site s;
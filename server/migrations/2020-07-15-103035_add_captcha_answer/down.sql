@@ -0,0 +1 @@
+drop table captcha_answer;
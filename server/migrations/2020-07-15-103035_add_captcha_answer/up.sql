@@ -0,0 +1,7 @@
+-- Answers to the captchas handed out on the registration form
+create table captcha_answer (
+  id serial primary key,
+  uuid text not null unique,
+  answer text not null,
+  published timestamp not null default now()
+);
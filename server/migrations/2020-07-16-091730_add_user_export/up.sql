@@ -0,0 +1,10 @@
+-- Archives of all of a user's data, generated in the background
+create table user_export (
+  id serial primary key,
+  user_id int references user_ on update cascade on delete cascade not null,
+  data jsonb,
+  published timestamp not null default now(),
+  completed timestamp
+);
+
+create index idx_user_export_user on user_export (user_id);
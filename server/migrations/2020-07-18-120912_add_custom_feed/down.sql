@@ -0,0 +1,2 @@
+drop table custom_feed_community;
+drop table custom_feed;
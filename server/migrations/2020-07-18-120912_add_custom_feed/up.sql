@@ -0,0 +1,17 @@
+-- User defined feeds, grouping any local or remote communities
+create table custom_feed (
+  id serial primary key,
+  creator_id int references user_ on update cascade on delete cascade not null,
+  name varchar(100) not null,
+  published timestamp not null default now(),
+  updated timestamp,
+  unique(creator_id, name)
+);
+
+create table custom_feed_community (
+  id serial primary key,
+  custom_feed_id int references custom_feed on update cascade on delete cascade not null,
+  community_id int references community on update cascade on delete cascade not null,
+  published timestamp not null default now(),
+  unique(custom_feed_id, community_id)
+);
@@ -0,0 +1 @@
+drop function controversy_rank(bigint, bigint);
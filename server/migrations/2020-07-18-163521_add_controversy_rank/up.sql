@@ -0,0 +1,17 @@
+-- Ranks items with lots of votes, where the upvotes and downvotes are close to each other, the highest
+create or replace function controversy_rank(
+  upvotes bigint,
+  downvotes bigint)
+returns float as $$
+begin
+  if upvotes <= 0 or downvotes <= 0 then
+    return 0;
+  else
+    return (upvotes + downvotes) * case when upvotes > downvotes
+      then downvotes::float / upvotes::float
+      else upvotes::float / downvotes::float
+    end;
+  end if;
+end; $$
+LANGUAGE plpgsql
+IMMUTABLE;
@@ -0,0 +1,8 @@
+drop index if exists idx_post_aggregates_fast_hot_rank_published;
+drop index idx_post_aggregates_fast_published;
+drop index idx_post_aggregates_fast_score_published;
+drop index idx_post_aggregates_fast_comments_published;
+
+drop index idx_comment_aggregates_fast_hot_rank_published;
+drop index idx_comment_aggregates_fast_published;
+drop index idx_comment_aggregates_fast_score_published;
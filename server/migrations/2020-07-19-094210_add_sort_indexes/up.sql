@@ -0,0 +1,11 @@
+-- Indexes for the post and comment sorts, so that the time windowed top sorts
+-- and the most comments sort don't scan the whole fast tables.
+-- The hot rank index was lost when the post fast table got recreated.
+create index if not exists idx_post_aggregates_fast_hot_rank_published on post_aggregates_fast (hot_rank desc, published desc);
+create index idx_post_aggregates_fast_published on post_aggregates_fast (published desc);
+create index idx_post_aggregates_fast_score_published on post_aggregates_fast (score desc, published desc);
+create index idx_post_aggregates_fast_comments_published on post_aggregates_fast (number_of_comments desc, published desc);
+
+create index idx_comment_aggregates_fast_hot_rank_published on comment_aggregates_fast (hot_rank desc, published desc);
+create index idx_comment_aggregates_fast_published on comment_aggregates_fast (published desc);
+create index idx_comment_aggregates_fast_score_published on comment_aggregates_fast (score desc, published desc);
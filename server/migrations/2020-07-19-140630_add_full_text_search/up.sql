@@ -0,0 +1,41 @@
+-- Full text search for posts and comments.
+-- The vectors are kept up to date by triggers, and aren't part of the views.
+alter table post add column search_vector tsvector;
+alter table comment add column search_vector tsvector;
+
+update post set search_vector =
+  setweight(to_tsvector('english', name), 'A') ||
+  setweight(to_tsvector('english', coalesce(body, '')), 'B');
+
+update comment set search_vector = to_tsvector('english', content);
+
+create index idx_post_search_vector on post using gin(search_vector);
+create index idx_comment_search_vector on comment using gin(search_vector);
+
+create or replace function post_search_vector_update()
+returns trigger language plpgsql
+as $$
+begin
+  new.search_vector :=
+    setweight(to_tsvector('english', new.name), 'A') ||
+    setweight(to_tsvector('english', coalesce(new.body, '')), 'B');
+  return new;
+end $$;
+
+create trigger post_search_vector_update
+before insert or update of name, body on post
+for each row
+execute procedure post_search_vector_update();
+
+create or replace function comment_search_vector_update()
+returns trigger language plpgsql
+as $$
+begin
+  new.search_vector := to_tsvector('english', new.content);
+  return new;
+end $$;
+
+create trigger comment_search_vector_update
+before insert or update of content on comment
+for each row
+execute procedure comment_search_vector_update();
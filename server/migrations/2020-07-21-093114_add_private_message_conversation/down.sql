@@ -0,0 +1,24 @@
+drop view private_message_conversation_view;
+drop view private_message_view;
+
+drop trigger private_message_conversation_assign on private_message;
+drop function private_message_conversation_assign();
+
+drop index idx_private_message_conversation;
+alter table private_message drop column conversation_id;
+drop table private_message_conversation;
+
+create view private_message_view as 
+select        
+pm.*,
+u.name as creator_name,
+u.avatar as creator_avatar,
+u.actor_id as creator_actor_id,
+u.local as creator_local,
+u2.name as recipient_name,
+u2.avatar as recipient_avatar,
+u2.actor_id as recipient_actor_id,
+u2.local as recipient_local
+from private_message pm
+inner join user_ u on u.id = pm.creator_id
+inner join user_ u2 on u2.id = pm.recipient_id;
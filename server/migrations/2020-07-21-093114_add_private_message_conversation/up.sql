@@ -0,0 +1,92 @@
+-- A conversation groups every private message between two users.
+-- user_a_id is always the lower of the two ids, so a pair only has one row.
+create table private_message_conversation (
+  id serial primary key,
+  user_a_id int references user_ on update cascade on delete cascade not null,
+  user_b_id int references user_ on update cascade on delete cascade not null,
+  published timestamp not null default now(),
+  last_message_at timestamp not null default now(),
+  unique(user_a_id, user_b_id),
+  check (user_a_id <= user_b_id)
+);
+
+-- Fill in the conversations for the existing messages
+insert into private_message_conversation (user_a_id, user_b_id, published, last_message_at)
+select least(creator_id, recipient_id), greatest(creator_id, recipient_id), min(published), max(published)
+from private_message
+group by least(creator_id, recipient_id), greatest(creator_id, recipient_id);
+
+alter table private_message add column conversation_id int references private_message_conversation on update cascade on delete cascade;
+
+update private_message pm
+set conversation_id = c.id
+from private_message_conversation c
+where c.user_a_id = least(pm.creator_id, pm.recipient_id)
+and c.user_b_id = greatest(pm.creator_id, pm.recipient_id);
+
+alter table private_message alter column conversation_id set not null;
+
+create index idx_private_message_conversation on private_message (conversation_id, published desc);
+
+-- New messages get their conversation assigned (and created if needed)
+create or replace function private_message_conversation_assign()
+returns trigger language plpgsql
+as $$
+begin
+  insert into private_message_conversation (user_a_id, user_b_id, last_message_at)
+  values (least(NEW.creator_id, NEW.recipient_id), greatest(NEW.creator_id, NEW.recipient_id), coalesce(NEW.published, now()))
+  on conflict (user_a_id, user_b_id)
+  do update set last_message_at = greatest(private_message_conversation.last_message_at, excluded.last_message_at)
+  returning id into NEW.conversation_id;
+
+  return NEW;
+end $$;
+
+create trigger private_message_conversation_assign
+before insert on private_message
+for each row
+execute procedure private_message_conversation_assign();
+
+-- Recreate the message view with the conversation id
+drop view private_message_view;
+create view private_message_view as 
+select        
+pm.*,
+u.name as creator_name,
+u.avatar as creator_avatar,
+u.actor_id as creator_actor_id,
+u.local as creator_local,
+u2.name as recipient_name,
+u2.avatar as recipient_avatar,
+u2.actor_id as recipient_actor_id,
+u2.local as recipient_local
+from private_message pm
+inner join user_ u on u.id = pm.creator_id
+inner join user_ u2 on u2.id = pm.recipient_id;
+
+-- One row per conversation and participant, with the other participant and the unread count
+create view private_message_conversation_view as
+select
+c.id,
+c.published,
+c.last_message_at,
+s.user_id,
+o.id as other_user_id,
+o.name as other_user_name,
+o.avatar as other_user_avatar,
+o.actor_id as other_user_actor_id,
+o.local as other_user_local,
+(
+  select count(*) from private_message pm
+  where pm.conversation_id = c.id
+  and pm.recipient_id = s.user_id
+  and pm.read = false
+  and pm.deleted = false
+) as unread_count
+from private_message_conversation c
+cross join lateral (
+  values (c.user_a_id, c.user_b_id), (c.user_b_id, c.user_a_id)
+) as s(user_id, other_user_id)
+inner join user_ o on o.id = s.other_user_id
+-- A conversation with yourself only shows up once
+where c.user_a_id <> c.user_b_id or s.user_id = c.user_a_id;
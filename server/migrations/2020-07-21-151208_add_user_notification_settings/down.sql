@@ -0,0 +1,3 @@
+drop trigger user_notification_settings_create on user_;
+drop function user_notification_settings_create();
+drop table user_notification_settings;
@@ -0,0 +1,28 @@
+-- Which kinds of notifications a user wants, over websocket and email
+create table user_notification_settings (
+  id serial primary key,
+  user_id int references user_ on update cascade on delete cascade not null unique,
+  replies boolean default true not null,
+  mentions boolean default true not null,
+  private_messages boolean default true not null,
+  mod_actions boolean default true not null,
+  subscribed_posts boolean default false not null,
+  updated timestamp
+);
+
+insert into user_notification_settings (user_id)
+select id from user_;
+
+-- Every new user gets the defaults
+create or replace function user_notification_settings_create()
+returns trigger language plpgsql
+as $$
+begin
+  insert into user_notification_settings (user_id) values (NEW.id);
+  return null;
+end $$;
+
+create trigger user_notification_settings_create
+after insert on user_
+for each row
+execute procedure user_notification_settings_create();
@@ -0,0 +1,11 @@
+-- The browsers and devices users registered for web push notifications
+create table user_push_subscription (
+  id serial primary key,
+  user_id int references user_ on update cascade on delete cascade not null,
+  endpoint text not null unique,
+  p256dh text not null,
+  auth text not null,
+  published timestamp not null default now()
+);
+
+create index idx_user_push_subscription_user on user_push_subscription (user_id);
@@ -0,0 +1,4 @@
+alter table user_notification_settings
+drop column digest_frequency,
+drop column last_digest_sent_at,
+drop column digest_token;
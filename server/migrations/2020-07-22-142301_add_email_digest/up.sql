@@ -0,0 +1,7 @@
+-- Email digests of the top posts in a user's subscriptions.
+-- digest_frequency is 0 for never, 1 for daily and 2 for weekly.
+-- The token goes in the unsubscribe link, so it works without logging in.
+alter table user_notification_settings
+add column digest_frequency smallint not null default 0,
+add column last_digest_sent_at timestamp,
+add column digest_token text not null unique default md5(random()::text || clock_timestamp()::text);
@@ -0,0 +1,10 @@
+-- Posts a user never wants to see in their listings again
+create table post_hide (
+  id serial primary key,
+  post_id int references post on update cascade on delete cascade not null,
+  user_id int references user_ on update cascade on delete cascade not null,
+  published timestamp not null default now(),
+  unique(post_id, user_id)
+);
+
+create index idx_post_hide_user on post_hide (user_id);
@@ -0,0 +1 @@
+alter table user_ drop column hide_read_posts;
@@ -0,0 +1 @@
+alter table user_ add column hide_read_posts boolean default false not null;
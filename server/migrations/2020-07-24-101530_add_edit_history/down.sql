@@ -0,0 +1,21 @@
+drop view site_view;
+
+alter table site drop column public_edit_history;
+
+create view site_view as 
+select *,
+(select name from user_ u where s.creator_id = u.id) as creator_name,
+(select avatar from user_ u where s.creator_id = u.id) as creator_avatar,
+(select count(*) from user_) as number_of_users,
+(select count(*) from post) as number_of_posts,
+(select count(*) from comment) as number_of_comments,
+(select count(*) from community) as number_of_communities
+from site s;
+
+drop trigger comment_revision_save on comment;
+drop function comment_revision_save();
+drop trigger post_revision_save on post;
+drop function post_revision_save();
+
+drop table comment_revision;
+drop table post_revision;
@@ -0,0 +1,70 @@
+-- The previous versions of edited posts and comments
+create table post_revision (
+  id serial primary key,
+  post_id int references post on update cascade on delete cascade not null,
+  name varchar(200) not null,
+  url text,
+  body text,
+  published timestamp not null default now()
+);
+
+create index idx_post_revision_post on post_revision (post_id);
+
+create table comment_revision (
+  id serial primary key,
+  comment_id int references comment on update cascade on delete cascade not null,
+  content text not null,
+  published timestamp not null default now()
+);
+
+create index idx_comment_revision_comment on comment_revision (comment_id);
+
+-- Keep the old text whenever it changes. This catches local edits and remote Update activities
+-- alike. The revision is dated to when that version was written.
+create or replace function post_revision_save()
+returns trigger language plpgsql
+as $$
+begin
+  insert into post_revision (post_id, name, url, body, published)
+  values (OLD.id, OLD.name, OLD.url, OLD.body, coalesce(OLD.updated, OLD.published));
+  return NULL;
+end $$;
+
+create trigger post_revision_save
+after update of name, url, body on post
+for each row
+when (OLD.name is distinct from NEW.name
+  or OLD.url is distinct from NEW.url
+  or OLD.body is distinct from NEW.body)
+execute procedure post_revision_save();
+
+create or replace function comment_revision_save()
+returns trigger language plpgsql
+as $$
+begin
+  insert into comment_revision (comment_id, content, published)
+  values (OLD.id, OLD.content, coalesce(OLD.updated, OLD.published));
+  return NULL;
+end $$;
+
+create trigger comment_revision_save
+after update of content on comment
+for each row
+when (OLD.content is distinct from NEW.content)
+execute procedure comment_revision_save();
+
+-- Whether everyone can see the edit history, or only the author, mods and admins
+alter table site add column public_edit_history boolean default true not null;
+
+-- Recreate the site_view to pick up the new column
+drop view site_view;
+
+create view site_view as 
+select *,
+(select name from user_ u where s.creator_id = u.id) as creator_name,
+(select avatar from user_ u where s.creator_id = u.id) as creator_avatar,
+(select count(*) from user_) as number_of_users,
+(select count(*) from post) as number_of_posts,
+(select count(*) from comment) as number_of_comments,
+(select count(*) from community) as number_of_communities
+from site s;
@@ -0,0 +1,11 @@
+-- The names local users had before renaming themselves. The actor id keeps the original name,
+-- so these keep old profile links, mentions and webfinger lookups resolving.
+create table user_alias (
+  id serial primary key,
+  user_id int references user_ on update cascade on delete cascade not null,
+  name varchar(20) not null,
+  published timestamp not null default now()
+);
+
+create unique index idx_user_alias_name_lower on user_alias (lower(name));
+create index idx_user_alias_user on user_alias (user_id);
@@ -0,0 +1,2 @@
+drop table site_announcement_dismissal;
+drop table site_announcement;
@@ -0,0 +1,19 @@
+-- Announcements the admins show across the whole site, between a start and an optional end time
+create table site_announcement (
+  id serial primary key,
+  creator_id int references user_ on update cascade on delete cascade not null,
+  content text not null,
+  severity smallint not null default 0,
+  start_time timestamp not null default now(),
+  end_time timestamp,
+  published timestamp not null default now()
+);
+
+-- The announcements users have closed, so they aren't shown to them again
+create table site_announcement_dismissal (
+  id serial primary key,
+  announcement_id int references site_announcement on update cascade on delete cascade not null,
+  user_id int references user_ on update cascade on delete cascade not null,
+  published timestamp not null default now(),
+  unique(announcement_id, user_id)
+);
@@ -0,0 +1,5 @@
+drop trigger community_wiki_page_revision_save on community_wiki_page;
+drop function community_wiki_page_revision_save();
+
+drop table community_wiki_page_revision;
+drop table community_wiki_page;
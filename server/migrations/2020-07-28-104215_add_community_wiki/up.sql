@@ -0,0 +1,43 @@
+-- Wiki pages that a community keeps alongside its sidebar, like FAQs and rules
+create table community_wiki_page (
+  id serial primary key,
+  community_id int references community on update cascade on delete cascade not null,
+  slug varchar(100) not null,
+  title varchar(200) not null,
+  content text not null,
+  -- Who can edit the page: 0 = mods, 1 = subscribers, 2 = everyone
+  edit_permission smallint default 0 not null,
+  editor_id int references user_ on update cascade on delete set null,
+  published timestamp not null default now(),
+  updated timestamp,
+  unique(community_id, slug)
+);
+
+-- The previous versions of edited wiki pages
+create table community_wiki_page_revision (
+  id serial primary key,
+  page_id int references community_wiki_page on update cascade on delete cascade not null,
+  title varchar(200) not null,
+  content text not null,
+  editor_id int references user_ on update cascade on delete set null,
+  published timestamp not null default now()
+);
+
+create index idx_community_wiki_page_revision_page on community_wiki_page_revision (page_id);
+
+-- Same as the post and comment revisions, the old version is dated to when it was written.
+create or replace function community_wiki_page_revision_save()
+returns trigger language plpgsql
+as $$
+begin
+  insert into community_wiki_page_revision (page_id, title, content, editor_id, published)
+  values (OLD.id, OLD.title, OLD.content, OLD.editor_id, coalesce(OLD.updated, OLD.published));
+  return NULL;
+end $$;
+
+create trigger community_wiki_page_revision_save
+after update of title, content on community_wiki_page
+for each row
+when (OLD.title is distinct from NEW.title
+  or OLD.content is distinct from NEW.content)
+execute procedure community_wiki_page_revision_save();
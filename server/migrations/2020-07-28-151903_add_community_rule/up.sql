@@ -0,0 +1,12 @@
+-- The rules of a community, shown in order
+create table community_rule (
+  id serial primary key,
+  community_id int references community on update cascade on delete cascade not null,
+  position int not null,
+  title varchar(100) not null,
+  description text,
+  published timestamp not null default now(),
+  updated timestamp
+);
+
+create index idx_community_rule_community on community_rule (community_id);
@@ -0,0 +1,9 @@
+drop view mod_resolve_comment_report_view;
+drop view mod_resolve_post_report_view;
+drop table mod_resolve_comment_report;
+drop table mod_resolve_post_report;
+
+drop view comment_report_view;
+drop view post_report_view;
+drop table comment_report;
+drop table post_report;
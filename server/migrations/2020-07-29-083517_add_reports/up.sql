@@ -0,0 +1,93 @@
+-- Reports that users file against posts and comments, for the community mods to handle
+create table post_report (
+  id serial primary key,
+  creator_id int references user_ on update cascade on delete cascade not null,
+  post_id int references post on update cascade on delete cascade not null,
+  reason text not null,
+  rule_id int references community_rule on update cascade on delete set null,
+  resolved boolean default false not null,
+  resolver_id int references user_ on update cascade on delete set null,
+  published timestamp not null default now(),
+  updated timestamp
+);
+
+create index idx_post_report_post on post_report (post_id);
+
+create table comment_report (
+  id serial primary key,
+  creator_id int references user_ on update cascade on delete cascade not null,
+  comment_id int references comment on update cascade on delete cascade not null,
+  reason text not null,
+  rule_id int references community_rule on update cascade on delete set null,
+  resolved boolean default false not null,
+  resolver_id int references user_ on update cascade on delete set null,
+  published timestamp not null default now(),
+  updated timestamp
+);
+
+create index idx_comment_report_comment on comment_report (comment_id);
+
+create view post_report_view as
+select r.*,
+p.name as post_name,
+p.url as post_url,
+p.body as post_body,
+p.creator_id as post_creator_id,
+p.community_id,
+(select name from user_ u where r.creator_id = u.id) as creator_name,
+(select name from user_ u where p.creator_id = u.id) as post_creator_name,
+(select name from user_ u where r.resolver_id = u.id) as resolver_name,
+(select title from community_rule cr where r.rule_id = cr.id) as rule_title
+from post_report r
+join post p on r.post_id = p.id;
+
+create view comment_report_view as
+select r.*,
+c.content as comment_content,
+c.creator_id as comment_creator_id,
+c.post_id,
+p.name as post_name,
+p.community_id,
+(select name from user_ u where r.creator_id = u.id) as creator_name,
+(select name from user_ u where c.creator_id = u.id) as comment_creator_name,
+(select name from user_ u where r.resolver_id = u.id) as resolver_name,
+(select title from community_rule cr where r.rule_id = cr.id) as rule_title
+from comment_report r
+join comment c on r.comment_id = c.id
+join post p on c.post_id = p.id;
+
+-- The modlog entries for resolving reports
+create table mod_resolve_post_report (
+  id serial primary key,
+  mod_user_id int references user_ on update cascade on delete cascade not null,
+  report_id int references post_report on update cascade on delete cascade not null,
+  resolved boolean default true,
+  when_ timestamp not null default now()
+);
+
+create table mod_resolve_comment_report (
+  id serial primary key,
+  mod_user_id int references user_ on update cascade on delete cascade not null,
+  report_id int references comment_report on update cascade on delete cascade not null,
+  resolved boolean default true,
+  when_ timestamp not null default now()
+);
+
+create view mod_resolve_post_report_view as
+select mr.*,
+(select name from user_ u where mr.mod_user_id = u.id) as mod_user_name,
+(select p.id from post_report r, post p where mr.report_id = r.id and r.post_id = p.id) as post_id,
+(select p.name from post_report r, post p where mr.report_id = r.id and r.post_id = p.id) as post_name,
+(select c.id from post_report r, post p, community c where mr.report_id = r.id and r.post_id = p.id and p.community_id = c.id) as community_id,
+(select c.name from post_report r, post p, community c where mr.report_id = r.id and r.post_id = p.id and p.community_id = c.id) as community_name
+from mod_resolve_post_report mr;
+
+create view mod_resolve_comment_report_view as
+select mr.*,
+(select name from user_ u where mr.mod_user_id = u.id) as mod_user_name,
+(select c.id from comment_report r, comment c where mr.report_id = r.id and r.comment_id = c.id) as comment_id,
+(select p.id from comment_report r, comment c, post p where mr.report_id = r.id and r.comment_id = c.id and c.post_id = p.id) as post_id,
+(select p.name from comment_report r, comment c, post p where mr.report_id = r.id and r.comment_id = c.id and c.post_id = p.id) as post_name,
+(select co.id from comment_report r, comment c, post p, community co where mr.report_id = r.id and r.comment_id = c.id and c.post_id = p.id and p.community_id = co.id) as community_id,
+(select co.name from comment_report r, comment c, post p, community co where mr.report_id = r.id and r.comment_id = c.id and c.post_id = p.id and p.community_id = co.id) as community_name
+from mod_resolve_comment_report mr;
@@ -0,0 +1,20 @@
+-- Rules that mods set up to act on new posts and comments in their community
+create table automod_rule (
+  id serial primary key,
+  community_id int references community on update cascade on delete cascade not null,
+  creator_id int references user_ on update cascade on delete cascade not null,
+  name varchar(100) not null,
+  -- 0 = keyword, 1 = regex, 2 = domain, 3 = account age, 4 = author score
+  condition smallint not null,
+  -- Which text a keyword or regex looks at: 0 = title, 1 = body, 2 = url, 3 = any
+  target smallint default 3 not null,
+  pattern text,
+  -- Days for the account age, points for the author score
+  threshold int,
+  -- 0 = remove, 1 = report, 2 = require approval
+  action smallint not null,
+  published timestamp not null default now(),
+  updated timestamp
+);
+
+create index idx_automod_rule_community on automod_rule (community_id);
@@ -0,0 +1,11 @@
+-- Domains that link posts can't point to. Site wide when there's no community.
+create table blocked_domain (
+  id serial primary key,
+  community_id int references community on update cascade on delete cascade,
+  -- Either an exact host, or *.example.com for a domain and all of its subdomains
+  domain varchar(255) not null,
+  creator_id int references user_ on update cascade on delete cascade not null,
+  published timestamp not null default now()
+);
+
+create unique index idx_blocked_domain_unique on blocked_domain (coalesce(community_id, 0), domain);
@@ -0,0 +1,11 @@
+-- Private notes that the mods of a community keep about a user
+create table mod_note (
+  id serial primary key,
+  community_id int references community on update cascade on delete cascade not null,
+  user_id int references user_ on update cascade on delete cascade not null,
+  creator_id int references user_ on update cascade on delete cascade not null,
+  content text not null,
+  published timestamp not null default now()
+);
+
+create index idx_mod_note_community_user on mod_note (community_id, user_id);
@@ -0,0 +1,14 @@
+-- Sites can make mods give a reason when they remove content or ban someone
+drop view site_view;
+
+alter table site add column require_removal_reason boolean default false not null;
+
+create view site_view as 
+select *,
+(select name from user_ u where s.creator_id = u.id) as creator_name,
+(select avatar from user_ u where s.creator_id = u.id) as creator_avatar,
+(select count(*) from user_) as number_of_users,
+(select count(*) from post) as number_of_posts,
+(select count(*) from comment) as number_of_comments,
+(select count(*) from community) as number_of_communities
+from site s;
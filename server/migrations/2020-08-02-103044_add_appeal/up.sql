@@ -0,0 +1,17 @@
+-- Appeals against a post or comment removal, or a ban. The kind says which mod log table
+-- mod_action_id points into, and community_id is null for site bans.
+create table appeal (
+  id serial primary key,
+  kind smallint not null,
+  mod_action_id int not null,
+  creator_id int references user_ on update cascade on delete cascade not null,
+  community_id int references community on update cascade on delete cascade,
+  reason text not null,
+  status smallint not null default 0,
+  resolver_id int references user_ on update cascade on delete cascade,
+  published timestamp not null default now(),
+  updated timestamp,
+  unique (kind, mod_action_id)
+);
+
+create index idx_appeal_community_status on appeal (community_id, status);
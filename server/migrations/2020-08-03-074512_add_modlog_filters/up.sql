@@ -0,0 +1,37 @@
+-- The target user filter needs the post creator, and comment_user_id was the comment id
+drop view mod_remove_post_view;
+drop view mod_remove_comment_view;
+
+create view mod_remove_post_view as 
+select mrp.*,
+(select name from user_ u where mrp.mod_user_id = u.id) as mod_user_name,
+(select name from post p where mrp.post_id = p.id) as post_name,
+(select p.creator_id from post p where mrp.post_id = p.id) as post_creator_id,
+(select c.id from post p, community c where mrp.post_id = p.id and p.community_id = c.id) as community_id,
+(select c.name from post p, community c where mrp.post_id = p.id and p.community_id = c.id) as community_name
+from mod_remove_post mrp;
+
+create view mod_remove_comment_view as 
+select mrc.*,
+(select name from user_ u where mrc.mod_user_id = u.id) as mod_user_name,
+(select c.creator_id from comment c where mrc.comment_id = c.id) as comment_user_id,
+(select name from user_ u, comment c where mrc.comment_id = c.id and u.id = c.creator_id) as comment_user_name,
+(select content from comment c where mrc.comment_id = c.id) as comment_content,
+(select p.id from post p, comment c where mrc.comment_id = c.id and c.post_id = p.id) as post_id,
+(select p.name from post p, comment c where mrc.comment_id = c.id and c.post_id = p.id) as post_name,
+(select co.id from comment c, post p, community co where mrc.comment_id = c.id and c.post_id = p.id and p.community_id = co.id) as community_id, 
+(select co.name from comment c, post p, community co where mrc.comment_id = c.id and c.post_id = p.id and p.community_id = co.id) as community_name
+from mod_remove_comment mrc;
+
+-- The modlog is paged by time
+create index idx_mod_remove_post_when on mod_remove_post (when_);
+create index idx_mod_lock_post_when on mod_lock_post (when_);
+create index idx_mod_sticky_post_when on mod_sticky_post (when_);
+create index idx_mod_remove_comment_when on mod_remove_comment (when_);
+create index idx_mod_remove_community_when on mod_remove_community (when_);
+create index idx_mod_ban_from_community_when on mod_ban_from_community (when_);
+create index idx_mod_ban_when on mod_ban (when_);
+create index idx_mod_add_community_when on mod_add_community (when_);
+create index idx_mod_add_when on mod_add (when_);
+create index idx_mod_resolve_post_report_when on mod_resolve_post_report (when_);
+create index idx_mod_resolve_comment_report_when on mod_resolve_comment_report (when_);
@@ -0,0 +1,3 @@
+drop view mod_shadow_ban_view;
+drop table mod_shadow_ban;
+alter table user_ drop column shadow_banned;
@@ -0,0 +1,19 @@
+-- Shadowbanned users can keep posting, but only they and the admins see it
+alter table user_ add column shadow_banned boolean default false not null;
+
+create table mod_shadow_ban (
+  id serial primary key,
+  mod_user_id int references user_ on update cascade on delete cascade not null,
+  other_user_id int references user_ on update cascade on delete cascade not null,
+  reason text,
+  shadow_banned boolean default true,
+  when_ timestamp not null default now()
+);
+
+create index idx_mod_shadow_ban_when on mod_shadow_ban (when_);
+
+create view mod_shadow_ban_view as 
+select msb.*,
+(select name from user_ u where msb.mod_user_id = u.id) as mod_user_name,
+(select name from user_ u where msb.other_user_id = u.id) as other_user_name
+from mod_shadow_ban msb;
@@ -0,0 +1,22 @@
+drop table rate_limit_exemption;
+drop table rate_limit_endpoint;
+drop view site_view;
+
+alter table site drop column rate_limit_message;
+alter table site drop column rate_limit_message_per_second;
+alter table site drop column rate_limit_post;
+alter table site drop column rate_limit_post_per_second;
+alter table site drop column rate_limit_register;
+alter table site drop column rate_limit_register_per_second;
+alter table site drop column rate_limit_report;
+alter table site drop column rate_limit_report_per_second;
+
+create view site_view as 
+select *,
+(select name from user_ u where s.creator_id = u.id) as creator_name,
+(select avatar from user_ u where s.creator_id = u.id) as creator_avatar,
+(select count(*) from user_) as number_of_users,
+(select count(*) from post) as number_of_posts,
+(select count(*) from comment) as number_of_comments,
+(select count(*) from community) as number_of_communities
+from site s;
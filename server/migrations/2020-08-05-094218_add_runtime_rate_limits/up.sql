@@ -0,0 +1,27 @@
+-- Limits the admins set at runtime. Where these are null, the config file's limits apply.
+alter table site add column rate_limit_message int;
+alter table site add column rate_limit_message_per_second int;
+alter table site add column rate_limit_post int;
+alter table site add column rate_limit_post_per_second int;
+alter table site add column rate_limit_register int;
+alter table site add column rate_limit_register_per_second int;
+alter table site add column rate_limit_report int;
+alter table site add column rate_limit_report_per_second int;
+
+-- An endpoint with its own limit gets its own bucket, instead of sharing its type's
+create table rate_limit_endpoint (
+  id serial primary key,
+  endpoint varchar(100) not null unique,
+  actions int not null,
+  per_second int not null,
+  creator_id int references user_ on update cascade on delete cascade not null,
+  published timestamp not null default now()
+);
+
+-- Users that skip rate limiting entirely, like approved bots
+create table rate_limit_exemption (
+  id serial primary key,
+  user_id int references user_ on update cascade on delete cascade not null unique,
+  creator_id int references user_ on update cascade on delete cascade not null,
+  published timestamp not null default now()
+);
@@ -0,0 +1 @@
+drop table posting_requirement;
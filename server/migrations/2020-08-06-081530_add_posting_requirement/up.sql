@@ -0,0 +1,14 @@
+-- What new accounts need before they can post. The site wide requirements have no community.
+create table posting_requirement (
+  id serial primary key,
+  community_id int references community on update cascade on delete cascade unique,
+  min_account_age_days int default 0 not null,
+  min_comments int default 0 not null,
+  min_comment_score int default 0 not null,
+  links_only boolean default false not null,
+  published timestamp not null default now(),
+  updated timestamp
+);
+
+-- Nulls don't collide in the unique constraint, so the site gets its own
+create unique index idx_posting_requirement_site on posting_requirement ((community_id is null)) where community_id is null;
@@ -0,0 +1,19 @@
+-- The words that get blocked, replaced or flagged for review. The site wide filters have no
+-- community. Action is 0 to block, 1 to replace, 2 to flag.
+create table word_filter (
+  id serial primary key,
+  community_id int references community on update cascade on delete cascade,
+  pattern text not null,
+  is_regex boolean default false not null,
+  action smallint not null,
+  replacement text,
+  creator_id int references user_ on update cascade on delete cascade,
+  published timestamp not null default now(),
+  updated timestamp
+);
+
+create index idx_word_filter_community on word_filter (community_id);
+
+-- The slur filter that used to be built in
+insert into word_filter (pattern, is_regex, action) values
+('(fag(g|got|tard)?|maricos?|cock\s?sucker(s|ing)?|\bn(i|1)g(\b|g?(a|er)?(s|z)?)\b|dindu(s?)|mudslime?s?|kikes?|mongoloids?|towel\s*heads?|\bspi(c|k)s?\b|\bchinks?|niglets?|beaners?|\bnips?\b|\bcoons?\b|jungle\s*bunn(y|ies?)|jigg?aboo?s?|\bpakis?\b|rag\s*heads?|gooks?|cunts?|bitch(es|ing|y)?|puss(y|ies?)|twats?|feminazis?|whor(es?|ing)|\bslut(s|t?y)?|\btr(a|@)nn?(y|ies?)|ladyboy(s?)|\b(b|re|r)tard(ed)?s?)', true, 1);
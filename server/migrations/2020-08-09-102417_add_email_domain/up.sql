@@ -0,0 +1,25 @@
+-- Email domains that registration allows or denies. Once there are any allowed ones, only those
+-- can sign up.
+create table email_domain (
+  id serial primary key,
+  -- Either an exact domain, or *.example.com for a domain and all of its subdomains
+  domain varchar(255) not null unique,
+  allowed boolean not null,
+  creator_id int references user_ on update cascade on delete cascade not null,
+  published timestamp not null default now()
+);
+
+-- Sites can turn away the throwaway inbox providers that lemmy knows of
+drop view site_view;
+
+alter table site add column block_disposable_emails boolean default false not null;
+
+create view site_view as 
+select *,
+(select name from user_ u where s.creator_id = u.id) as creator_name,
+(select avatar from user_ u where s.creator_id = u.id) as creator_avatar,
+(select count(*) from user_) as number_of_users,
+(select count(*) from post) as number_of_posts,
+(select count(*) from comment) as number_of_comments,
+(select count(*) from community) as number_of_communities
+from site s;
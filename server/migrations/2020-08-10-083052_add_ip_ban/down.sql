@@ -0,0 +1,17 @@
+drop view site_view;
+
+alter table site drop column ip_retention_days;
+alter table site drop column registrations_per_ip_per_day;
+
+create view site_view as 
+select *,
+(select name from user_ u where s.creator_id = u.id) as creator_name,
+(select avatar from user_ u where s.creator_id = u.id) as creator_avatar,
+(select count(*) from user_) as number_of_users,
+(select count(*) from post) as number_of_posts,
+(select count(*) from comment) as number_of_comments,
+(select count(*) from community) as number_of_communities
+from site s;
+
+drop table user_ip;
+drop table ip_ban;
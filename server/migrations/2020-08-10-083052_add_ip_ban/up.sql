@@ -0,0 +1,39 @@
+-- IP ranges that can't use the API, until they expire if they do
+create table ip_ban (
+  id serial primary key,
+  -- Either a single address, or a range like 10.0.0.0/8
+  ip_range varchar(50) not null unique,
+  reason text,
+  creator_id int references user_ on update cascade on delete cascade not null,
+  expires timestamp,
+  published timestamp not null default now()
+);
+
+-- The IPs that users registered or logged in from, salted and hashed, so that registrations can be
+-- throttled per IP. They're pruned after the site's retention period.
+create table user_ip (
+  id serial primary key,
+  user_id int references user_ on update cascade on delete cascade not null,
+  ip_hash varchar(64) not null,
+  registration boolean not null,
+  published timestamp not null default now()
+);
+
+create index idx_user_ip_hash on user_ip (ip_hash, published);
+create index idx_user_ip_published on user_ip (published);
+
+drop view site_view;
+
+alter table site add column ip_retention_days int default 30 not null;
+-- No limit when it's 0
+alter table site add column registrations_per_ip_per_day int default 0 not null;
+
+create view site_view as 
+select *,
+(select name from user_ u where s.creator_id = u.id) as creator_name,
+(select avatar from user_ u where s.creator_id = u.id) as creator_avatar,
+(select count(*) from user_) as number_of_users,
+(select count(*) from post) as number_of_posts,
+(select count(*) from comment) as number_of_comments,
+(select count(*) from community) as number_of_communities
+from site s;
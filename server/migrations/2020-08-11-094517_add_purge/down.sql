@@ -0,0 +1,3 @@
+drop table pictrs_thumbnail;
+drop view mod_purge_view;
+drop table mod_purge;
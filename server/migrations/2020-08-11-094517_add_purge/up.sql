@@ -0,0 +1,26 @@
+-- Purges hard delete, so all the modlog keeps is what kind of thing it was, and where
+create table mod_purge (
+  id serial primary key,
+  mod_user_id int references user_ on update cascade on delete cascade not null,
+  -- 0 is a user, 1 a post, 2 a comment, 3 a community
+  target_type smallint not null,
+  community_id int references community on update cascade on delete set null,
+  reason text,
+  when_ timestamp not null default now()
+);
+
+create index idx_mod_purge_when on mod_purge (when_);
+
+create view mod_purge_view as 
+select mp.*,
+(select name from user_ u where mp.mod_user_id = u.id) as mod_user_name,
+(select name from community c where mp.community_id = c.id) as community_name
+from mod_purge mp;
+
+-- The delete tokens of the thumbnails lemmy had pictrs make, so that they can be deleted again
+create table pictrs_thumbnail (
+  id serial primary key,
+  file text not null unique,
+  delete_token text not null,
+  published timestamp not null default now()
+);
@@ -0,0 +1,17 @@
+drop view site_view;
+
+alter table site drop column mod_inactive_days;
+
+create view site_view as 
+select *,
+(select name from user_ u where s.creator_id = u.id) as creator_name,
+(select avatar from user_ u where s.creator_id = u.id) as creator_avatar,
+(select count(*) from user_) as number_of_users,
+(select count(*) from post) as number_of_posts,
+(select count(*) from comment) as number_of_comments,
+(select count(*) from community) as number_of_communities
+from site s;
+
+drop table community_takeover_request;
+drop view mod_transfer_community_view;
+drop table mod_transfer_community;
@@ -0,0 +1,46 @@
+-- A community getting a new top mod, by a transfer or by an approved takeover request
+create table mod_transfer_community (
+  id serial primary key,
+  mod_user_id int references user_ on update cascade on delete cascade not null,
+  other_user_id int references user_ on update cascade on delete cascade not null,
+  community_id int references community on update cascade on delete cascade not null,
+  when_ timestamp not null default now()
+);
+
+create index idx_mod_transfer_community_when on mod_transfer_community (when_);
+
+create view mod_transfer_community_view as 
+select mt.*,
+(select name from user_ u where mt.mod_user_id = u.id) as mod_user_name,
+(select name from user_ u where mt.other_user_id = u.id) as other_user_name,
+(select name from community c where mt.community_id = c.id) as community_name
+from mod_transfer_community mt;
+
+-- Users asking the admins for a community whose mods have all gone inactive
+create table community_takeover_request (
+  id serial primary key,
+  community_id int references community on update cascade on delete cascade not null,
+  creator_id int references user_ on update cascade on delete cascade not null,
+  reason text,
+  -- 0 is pending, 1 approved and 2 rejected
+  status smallint not null default 0,
+  resolver_id int references user_ on update cascade on delete set null,
+  published timestamp not null default now(),
+  updated timestamp
+);
+
+create unique index idx_community_takeover_request_pending on community_takeover_request (community_id, creator_id) where status = 0;
+
+drop view site_view;
+
+alter table site add column mod_inactive_days int default 90 not null;
+
+create view site_view as 
+select *,
+(select name from user_ u where s.creator_id = u.id) as creator_name,
+(select avatar from user_ u where s.creator_id = u.id) as creator_avatar,
+(select count(*) from user_) as number_of_users,
+(select count(*) from post) as number_of_posts,
+(select count(*) from comment) as number_of_comments,
+(select count(*) from community) as number_of_communities
+from site s;
@@ -0,0 +1,17 @@
+-- The background work of the server, picked up by the job workers. The status is 0 queued,
+-- 1 running, 2 done and 3 failed for good.
+create table job (
+  id serial primary key,
+  kind varchar(50) not null,
+  payload text not null,
+  status smallint default 0 not null,
+  attempts int default 0 not null,
+  max_attempts int default 5 not null,
+  run_at timestamp not null default now(),
+  last_error text,
+  published timestamp not null default now(),
+  updated timestamp
+);
+
+create index idx_job_queued on job (run_at) where status = 0;
+create index idx_job_kind_status on job (kind, status);
@@ -0,0 +1,502 @@
+-- The counts and scores are kept in tables of their own, which triggers keep up to date as
+-- things get created, liked, followed and deleted. The views join them, instead of counting the
+-- likes and comments again every time a row is read.
+create table post_aggregates (
+  post_id int primary key references post on update cascade on delete cascade,
+  comments bigint default 0 not null,
+  score bigint default 0 not null,
+  upvotes bigint default 0 not null,
+  downvotes bigint default 0 not null,
+  newest_comment_time timestamp
+);
+
+create table comment_aggregates (
+  comment_id int primary key references comment on update cascade on delete cascade,
+  score bigint default 0 not null,
+  upvotes bigint default 0 not null,
+  downvotes bigint default 0 not null
+);
+
+create table community_aggregates (
+  community_id int primary key references community on update cascade on delete cascade,
+  subscribers bigint default 0 not null,
+  posts bigint default 0 not null,
+  comments bigint default 0 not null
+);
+
+create table user_aggregates (
+  user_id int primary key references user_ on update cascade on delete cascade,
+  post_count bigint default 0 not null,
+  post_score bigint default 0 not null,
+  comment_count bigint default 0 not null,
+  comment_score bigint default 0 not null
+);
+
+-- Fill them in from what's there
+insert into post_aggregates (post_id, comments, score, upvotes, downvotes, newest_comment_time)
+select
+  p.id,
+  coalesce(ct.comments, 0),
+  coalesce(pl.score, 0),
+  coalesce(pl.upvotes, 0),
+  coalesce(pl.downvotes, 0),
+  ct.newest_comment_time
+from post p
+left join (
+  select
+    post_id,
+    count(*) as comments,
+    max(published) as newest_comment_time
+  from comment
+  group by post_id
+) ct on ct.post_id = p.id
+left join (
+  select
+    post_id,
+    sum(score) as score,
+    count(*) filter (where score = 1) as upvotes,
+    count(*) filter (where score = -1) as downvotes
+  from post_like
+  group by post_id
+) pl on pl.post_id = p.id;
+
+insert into comment_aggregates (comment_id, score, upvotes, downvotes)
+select
+  c.id,
+  coalesce(cl.score, 0),
+  coalesce(cl.upvotes, 0),
+  coalesce(cl.downvotes, 0)
+from comment c
+left join (
+  select
+    comment_id,
+    sum(score) as score,
+    count(*) filter (where score = 1) as upvotes,
+    count(*) filter (where score = -1) as downvotes
+  from comment_like
+  group by comment_id
+) cl on cl.comment_id = c.id;
+
+insert into community_aggregates (community_id, subscribers, posts, comments)
+select
+  c.id,
+  coalesce(cf.subscribers, 0),
+  coalesce(pd.posts, 0),
+  coalesce(cd.comments, 0)
+from community c
+left join (
+  select community_id, count(*) as subscribers
+  from community_follower
+  group by community_id
+) cf on cf.community_id = c.id
+left join (
+  select community_id, count(*) as posts
+  from post
+  group by community_id
+) pd on pd.community_id = c.id
+left join (
+  select p.community_id, count(*) as comments
+  from comment ct
+  join post p on p.id = ct.post_id
+  group by p.community_id
+) cd on cd.community_id = c.id;
+
+insert into user_aggregates (user_id, post_count, post_score, comment_count, comment_score)
+select
+  u.id,
+  coalesce(pd.posts, 0),
+  coalesce(pd.score, 0),
+  coalesce(cd.comments, 0),
+  coalesce(cd.score, 0)
+from user_ u
+left join (
+  select
+    p.creator_id,
+    count(*) as posts,
+    sum(pa.score) as score
+  from post p
+  join post_aggregates pa on pa.post_id = p.id
+  group by p.creator_id
+) pd on pd.creator_id = u.id
+left join (
+  select
+    c.creator_id,
+    count(*) as comments,
+    sum(ca.score) as score
+  from comment c
+  join comment_aggregates ca on ca.comment_id = c.id
+  group by c.creator_id
+) cd on cd.creator_id = u.id;
+
+-- The triggers are named so they run before the refresh_ ones, which read the views into the
+-- fast tables.
+--
+-- Deleting a post or comment takes its counts off of the ones it belongs to before it's gone.
+-- The likes and comments deleted along with it can't find it anymore after that, so they're
+-- only counted once.
+create or replace function aggregates_user()
+returns trigger language plpgsql
+as $$
+begin
+  insert into user_aggregates (user_id) values (NEW.id);
+  return null;
+end $$;
+
+create trigger aggregates_user
+after insert on user_
+for each row
+execute procedure aggregates_user();
+
+create or replace function aggregates_community()
+returns trigger language plpgsql
+as $$
+begin
+  insert into community_aggregates (community_id) values (NEW.id);
+  return null;
+end $$;
+
+create trigger aggregates_community
+after insert on community
+for each row
+execute procedure aggregates_community();
+
+create or replace function aggregates_post()
+returns trigger language plpgsql
+as $$
+begin
+  IF (TG_OP = 'DELETE') THEN
+    update user_aggregates as ua
+    set post_count = post_count - 1,
+    post_score = post_score - pa.score
+    from post_aggregates as pa
+    where ua.user_id = OLD.creator_id and pa.post_id = OLD.id;
+
+    update community_aggregates as ca
+    set posts = posts - 1,
+    comments = ca.comments - pa.comments
+    from post_aggregates as pa
+    where ca.community_id = OLD.community_id and pa.post_id = OLD.id;
+
+    return OLD;
+  ELSIF (TG_OP = 'UPDATE') THEN
+    -- A post moved to another community takes its comments with it
+    IF (NEW.community_id != OLD.community_id) THEN
+      update community_aggregates as ca
+      set posts = posts - 1,
+      comments = ca.comments - pa.comments
+      from post_aggregates as pa
+      where ca.community_id = OLD.community_id and pa.post_id = NEW.id;
+
+      update community_aggregates as ca
+      set posts = posts + 1,
+      comments = ca.comments + pa.comments
+      from post_aggregates as pa
+      where ca.community_id = NEW.community_id and pa.post_id = NEW.id;
+    END IF;
+  ELSIF (TG_OP = 'INSERT') THEN
+    insert into post_aggregates (post_id) values (NEW.id);
+    update user_aggregates set post_count = post_count + 1 where user_id = NEW.creator_id;
+    update community_aggregates set posts = posts + 1 where community_id = NEW.community_id;
+  END IF;
+
+  return null;
+end $$;
+
+create trigger aggregates_post
+after insert or update on post
+for each row
+execute procedure aggregates_post();
+
+create trigger aggregates_post_delete
+before delete on post
+for each row
+execute procedure aggregates_post();
+
+create or replace function aggregates_comment()
+returns trigger language plpgsql
+as $$
+begin
+  IF (TG_OP = 'DELETE') THEN
+    update user_aggregates as ua
+    set comment_count = comment_count - 1,
+    comment_score = comment_score - ca.score
+    from comment_aggregates as ca
+    where ua.user_id = OLD.creator_id and ca.comment_id = OLD.id;
+
+    update post_aggregates set comments = comments - 1 where post_id = OLD.post_id;
+
+    update community_aggregates as ca
+    set comments = comments - 1
+    from post as p
+    where ca.community_id = p.community_id and p.id = OLD.post_id;
+
+    return OLD;
+  ELSIF (TG_OP = 'INSERT') THEN
+    insert into comment_aggregates (comment_id) values (NEW.id);
+    update user_aggregates set comment_count = comment_count + 1 where user_id = NEW.creator_id;
+
+    update post_aggregates
+    set comments = comments + 1,
+    newest_comment_time = greatest(newest_comment_time, NEW.published)
+    where post_id = NEW.post_id;
+
+    update community_aggregates as ca
+    set comments = comments + 1
+    from post as p
+    where ca.community_id = p.community_id and p.id = NEW.post_id;
+  END IF;
+
+  return null;
+end $$;
+
+create trigger aggregates_comment
+after insert on comment
+for each row
+execute procedure aggregates_comment();
+
+create trigger aggregates_comment_delete
+before delete on comment
+for each row
+execute procedure aggregates_comment();
+
+create or replace function aggregates_post_like()
+returns trigger language plpgsql
+as $$
+begin
+  IF (TG_OP = 'DELETE') THEN
+    update post_aggregates
+    set score = score - OLD.score,
+    upvotes = case when (OLD.score = 1) then upvotes - 1 else upvotes end,
+    downvotes = case when (OLD.score = -1) then downvotes - 1 else downvotes end
+    where post_id = OLD.post_id;
+
+    update user_aggregates as ua
+    set post_score = post_score - OLD.score
+    from post as p
+    where ua.user_id = p.creator_id and p.id = OLD.post_id;
+  ELSIF (TG_OP = 'INSERT') THEN
+    update post_aggregates
+    set score = score + NEW.score,
+    upvotes = case when (NEW.score = 1) then upvotes + 1 else upvotes end,
+    downvotes = case when (NEW.score = -1) then downvotes + 1 else downvotes end
+    where post_id = NEW.post_id;
+
+    update user_aggregates as ua
+    set post_score = post_score + NEW.score
+    from post as p
+    where ua.user_id = p.creator_id and p.id = NEW.post_id;
+  END IF;
+
+  return null;
+end $$;
+
+create trigger aggregates_post_like
+after insert or delete on post_like
+for each row
+execute procedure aggregates_post_like();
+
+create or replace function aggregates_comment_like()
+returns trigger language plpgsql
+as $$
+begin
+  IF (TG_OP = 'DELETE') THEN
+    update comment_aggregates
+    set score = score - OLD.score,
+    upvotes = case when (OLD.score = 1) then upvotes - 1 else upvotes end,
+    downvotes = case when (OLD.score = -1) then downvotes - 1 else downvotes end
+    where comment_id = OLD.comment_id;
+
+    update user_aggregates as ua
+    set comment_score = comment_score - OLD.score
+    from comment as c
+    where ua.user_id = c.creator_id and c.id = OLD.comment_id;
+  ELSIF (TG_OP = 'INSERT') THEN
+    update comment_aggregates
+    set score = score + NEW.score,
+    upvotes = case when (NEW.score = 1) then upvotes + 1 else upvotes end,
+    downvotes = case when (NEW.score = -1) then downvotes + 1 else downvotes end
+    where comment_id = NEW.comment_id;
+
+    update user_aggregates as ua
+    set comment_score = comment_score + NEW.score
+    from comment as c
+    where ua.user_id = c.creator_id and c.id = NEW.comment_id;
+  END IF;
+
+  return null;
+end $$;
+
+create trigger aggregates_comment_like
+after insert or delete on comment_like
+for each row
+execute procedure aggregates_comment_like();
+
+create or replace function aggregates_community_follower()
+returns trigger language plpgsql
+as $$
+begin
+  IF (TG_OP = 'DELETE') THEN
+    update community_aggregates set subscribers = subscribers - 1 where community_id = OLD.community_id;
+  ELSIF (TG_OP = 'INSERT') THEN
+    update community_aggregates set subscribers = subscribers + 1 where community_id = NEW.community_id;
+  END IF;
+
+  return null;
+end $$;
+
+create trigger aggregates_community_follower
+after insert or delete on community_follower
+for each row
+execute procedure aggregates_community_follower();
+
+-- The views keep their columns, so the ones built on them and the fast tables stay as they are
+create or replace view user_view as
+select
+  u.id,
+  u.actor_id,
+  u.name,
+  u.display_name,
+  u.avatar,
+  u.email,
+  u.matrix_user_id,
+  u.bio,
+  u.banner,
+  u.local,
+  u.admin,
+  u.banned,
+  u.ban_expires,
+  u.bot_account,
+  u.show_avatars,
+  u.send_notifications_to_email,
+  u.published,
+  coalesce(ua.post_count, 0) as number_of_posts,
+  coalesce(ua.post_score, 0) as post_score,
+  coalesce(ua.comment_count, 0) as number_of_comments,
+  coalesce(ua.comment_score, 0) as comment_score
+from user_ u
+left join user_aggregates ua on ua.user_id = u.id;
+
+create or replace view community_aggregates_view as
+select
+    c.id,
+    c.name,
+    c.title,
+    c.description,
+    c.category_id,
+    c.creator_id,
+    c.removed,
+    c.published,
+    c.updated,
+    c.deleted,
+    c.nsfw,
+    c.actor_id,
+    c.local,
+    c.last_refreshed_at,
+    c.require_flair,
+    c.icon,
+    c.banner,
+    c.archive_after_days,
+    c.post_approval,
+    c.read_only,
+    c.allowed_post_types,
+    c.default_sort_type,
+    c.language,
+    u.actor_id as creator_actor_id,
+    u.local as creator_local,
+    u.name as creator_name,
+    u.avatar as creator_avatar,
+    cat.name as category_name,
+    coalesce(ca.subscribers, 0) as number_of_subscribers,
+    coalesce(ca.posts, 0) as number_of_posts,
+    coalesce(ca.comments, 0) as number_of_comments,
+    hot_rank(ca.subscribers, c.published) as hot_rank
+from community c
+left join user_ u on c.creator_id = u.id
+left join category cat on c.category_id = cat.id
+left join community_aggregates ca on ca.community_id = c.id;
+
+create or replace view post_aggregates_view as
+select
+	p.*,
+	-- creator details
+	u.actor_id as creator_actor_id,
+	u."local" as creator_local,
+	u."name" as creator_name,
+  u.published as creator_published,
+	u.avatar as creator_avatar,
+	u.bot_account as creator_bot_account,
+	u.display_name as creator_display_name,
+  u.banned as banned,
+  cb.id::bool as banned_from_community,
+	-- community details
+	c.actor_id as community_actor_id,
+	c."local" as community_local,
+	c."name" as community_name,
+	c.removed as community_removed,
+	c.deleted as community_deleted,
+	c.nsfw as community_nsfw,
+	-- post score data/comment count
+	coalesce(pa.comments, 0) as number_of_comments,
+	coalesce(pa.score, 0) as score,
+	coalesce(pa.upvotes, 0) as upvotes,
+	coalesce(pa.downvotes, 0) as downvotes,
+	hot_rank(
+		coalesce(pa.score , 0), (
+			case
+				when (p.published < ('now'::timestamp - '1 month'::interval))
+				then p.published
+				else greatest(pa.newest_comment_time, p.published)
+			end
+		)
+	) as hot_rank,
+	(
+		case
+			when (p.published < ('now'::timestamp - '1 month'::interval))
+			then p.published
+			else greatest(pa.newest_comment_time, p.published)
+		end
+	) as newest_activity_time
+from post p
+left join user_ u on p.creator_id = u.id
+left join community_user_ban cb on p.creator_id = cb.user_id and p.community_id = cb.community_id
+left join community c on p.community_id = c.id
+left join post_aggregates pa on pa.post_id = p.id
+order by p.id;
+
+create or replace view comment_aggregates_view as
+select
+	ct.*,
+	-- post details
+	p."name" as post_name,
+	p.community_id,
+	-- community details
+	c.actor_id as community_actor_id,
+	c."local" as community_local,
+	c."name" as community_name,
+	-- creator details
+	u.banned as banned,
+  coalesce(cb.id, 0)::bool as banned_from_community,
+	u.actor_id as creator_actor_id,
+	u.local as creator_local,
+	u.name as creator_name,
+  u.published as creator_published,
+	u.avatar as creator_avatar,
+	u.bot_account as creator_bot_account,
+	u.display_name as creator_display_name,
+	-- score details
+	coalesce(ca.score, 0) as score,
+	coalesce(ca.upvotes, 0) as upvotes,
+	coalesce(ca.downvotes, 0) as downvotes,
+	hot_rank(coalesce(ca.score, 0), ct.published) as hot_rank
+from comment ct
+left join post p on ct.post_id = p.id
+left join community c on p.community_id = c.id
+left join user_ u on ct.creator_id = u.id
+left join community_user_ban cb on ct.creator_id = cb.user_id and p.id = ct.post_id and p.community_id = cb.community_id
+left join comment_aggregates ca on ca.comment_id = ct.id;
+
+-- The posts without comments or likes didn't count towards the communities and users before
+delete from community_aggregates_fast;
+insert into community_aggregates_fast select * from community_aggregates_view;
+delete from user_fast;
+insert into user_fast select * from user_view;
@@ -0,0 +1,11 @@
+-- The remote images served through the proxy, with the pictrs file each was downloaded to
+create table image_proxy (
+  id serial primary key,
+  url text not null unique,
+  file text not null,
+  delete_token text not null,
+  published timestamp not null default now(),
+  last_used timestamp not null default now()
+);
+
+create index idx_image_proxy_last_used on image_proxy (last_used);
@@ -0,0 +1,4 @@
+drop trigger comment_path on comment;
+drop function comment_path();
+alter table comment drop column path;
+drop extension ltree;
@@ -0,0 +1,49 @@
+-- Every comment knows the ids of the comments above it, ending with its own, so that a whole
+-- thread or its first few levels can be read with one indexed query
+create extension if not exists ltree;
+
+alter table comment add column path ltree;
+
+-- Nothing shown in the views changes, so the refresh can be skipped
+alter table comment disable trigger refresh_comment;
+
+with recursive tree as (
+  select id, text2ltree(id::text) as path
+  from comment
+  where parent_id is null
+  union all
+  select c.id, t.path || c.id::text
+  from comment c
+  join tree t on c.parent_id = t.id
+)
+update comment
+set path = tree.path
+from tree
+where comment.id = tree.id;
+
+alter table comment enable trigger refresh_comment;
+
+alter table comment alter column path set not null;
+
+create function comment_path()
+returns trigger language plpgsql
+as $$
+begin
+  if new.parent_id is null then
+    new.path = text2ltree(new.id::text);
+  else
+    select path || new.id::text
+    into new.path
+    from comment
+    where id = new.parent_id;
+  end if;
+  return new;
+end $$;
+
+create trigger comment_path
+before insert on comment
+for each row
+execute procedure comment_path();
+
+create index idx_comment_path on comment using gist (path);
+create index idx_comment_post_depth on comment (post_id, nlevel(path));
@@ -0,0 +1,2 @@
+-- Users can keep from being counted in the users online of the communities and posts they read
+alter table user_ add column hide_presence boolean default false not null;
@@ -0,0 +1,10 @@
+-- The images users uploaded to pictrs, with the token that deletes each one again
+create table media (
+  id serial primary key,
+  user_id int references user_ on update cascade on delete cascade not null,
+  file text not null unique,
+  delete_token text not null,
+  published timestamp not null default now()
+);
+
+create index idx_media_user on media (user_id);
@@ -0,0 +1,5 @@
+drop view mod_nsfw_post_view;
+drop table mod_nsfw_post;
+
+alter table pictrs_thumbnail drop column nsfw_score;
+alter table media drop column nsfw_score;
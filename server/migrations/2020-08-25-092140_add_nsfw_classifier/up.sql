@@ -0,0 +1,24 @@
+-- How likely the outside classifier thinks the image is NSFW, from 0 to 1. Unknown when there's
+-- no classifier, or it couldn't be reached
+alter table media add column nsfw_score real;
+alter table pictrs_thumbnail add column nsfw_score real;
+
+-- A mod marking a post NSFW or safe, against what the classifier made of its image
+create table mod_nsfw_post (
+  id serial primary key,
+  mod_user_id int references user_ on update cascade on delete cascade not null,
+  post_id int references post on update cascade on delete cascade not null,
+  nsfw boolean default true,
+  when_ timestamp not null default now()
+);
+
+create index idx_mod_nsfw_post_when on mod_nsfw_post (when_);
+
+create view mod_nsfw_post_view as 
+select mnp.*,
+(select name from user_ u where mnp.mod_user_id = u.id) as mod_user_name,
+(select name from post p where mnp.post_id = p.id) as post_name,
+(select p.creator_id from post p where mnp.post_id = p.id) as post_creator_id,
+(select c.id from post p, community c where mnp.post_id = p.id and p.community_id = c.id) as community_id,
+(select c.name from post p, community c where mnp.post_id = p.id and p.community_id = c.id) as community_name
+from mod_nsfw_post mnp;
@@ -0,0 +1,16 @@
+-- Where the events of the instance are posted. The ones without a community are the admins', for
+-- the whole site
+create table webhook (
+  id serial primary key,
+  community_id int references community on update cascade on delete cascade,
+  creator_id int references user_ on update cascade on delete cascade not null,
+  url text not null,
+  -- The key of the HMAC that signs the deliveries
+  secret text not null,
+  events text[] not null,
+  enabled boolean default true not null,
+  published timestamp not null default now(),
+  updated timestamp
+);
+
+create index idx_webhook_community on webhook (community_id);
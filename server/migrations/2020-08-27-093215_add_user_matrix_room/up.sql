@@ -0,0 +1,9 @@
+-- The direct chat the matrix bot sends a user their notifications in, kept for as long as they
+-- don't link another matrix id
+create table user_matrix_room (
+  id serial primary key,
+  user_id int references user_ on update cascade on delete cascade not null unique,
+  matrix_user_id text not null,
+  room_id text not null,
+  published timestamp not null default now()
+);
@@ -0,0 +1 @@
+drop table community_import;
@@ -0,0 +1,18 @@
+-- Communities an admin imported from a subreddit or a kbin magazine. The export is kept until the
+-- import is done, and the counts show how far along it is.
+create table community_import (
+  id serial primary key,
+  community_id int references community on update cascade on delete cascade not null,
+  creator_id int references user_ on update cascade on delete cascade not null,
+  source text not null,
+  data text,
+  posts_total int default 0 not null,
+  posts_imported int default 0 not null,
+  comments_total int default 0 not null,
+  comments_imported int default 0 not null,
+  error text,
+  published timestamp not null default now(),
+  completed timestamp
+);
+
+create index idx_community_import_community on community_import (community_id);
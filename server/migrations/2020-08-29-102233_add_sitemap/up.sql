@@ -0,0 +1,8 @@
+-- The pages of the sitemap, as they were last generated. The index at /sitemap.xml lists them.
+create table sitemap (
+  id serial primary key,
+  page int not null unique,
+  content text not null,
+  lastmod timestamp not null,
+  published timestamp not null default now()
+);
@@ -0,0 +1,10 @@
+-- The documents the admins write for the instance, like its terms of service and privacy policy
+create table site_document (
+  id serial primary key,
+  slug varchar(100) unique not null,
+  title text not null,
+  content text not null,
+  editor_id int references user_ on update cascade on delete set null,
+  published timestamp not null default now(),
+  updated timestamp
+);
@@ -0,0 +1,3 @@
+-- Federation as the admins set it at runtime. Null falls back to the config file.
+alter table site add column federation_enabled boolean;
+alter table site add column federation_allowed_instances text;
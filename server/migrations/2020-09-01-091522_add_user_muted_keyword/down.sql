@@ -0,0 +1 @@
+drop table user_muted_keyword;
@@ -0,0 +1,15 @@
+-- The words, or regexes, a user doesn't want to see. The posts and comments with one are
+-- collapsed for them, or left out of their listings with hide. They're muted for good without an
+-- expiry.
+create table user_muted_keyword (
+  id serial primary key,
+  user_id int references user_ on update cascade on delete cascade not null,
+  keyword varchar(100) not null,
+  is_regex boolean default false not null,
+  hide boolean default false not null,
+  expires timestamp,
+  published timestamp not null default now(),
+  updated timestamp
+);
+
+create index idx_user_muted_keyword_user on user_muted_keyword (user_id);
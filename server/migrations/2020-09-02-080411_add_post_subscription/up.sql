@@ -0,0 +1,8 @@
+-- The posts a user watches, hearing about every new comment in them
+create table post_subscription (
+  id serial primary key,
+  post_id int references post on update cascade on delete cascade not null,
+  user_id int references user_ on update cascade on delete cascade not null,
+  published timestamp not null default now(),
+  unique(post_id, user_id)
+);
@@ -0,0 +1,8 @@
+-- The users a user follows, to see what they post and comment
+create table user_follower (
+  id serial primary key,
+  user_id int references user_ on update cascade on delete cascade not null,
+  follower_id int references user_ on update cascade on delete cascade not null,
+  published timestamp not null default now(),
+  unique(user_id, follower_id)
+);
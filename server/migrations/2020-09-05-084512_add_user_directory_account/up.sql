@@ -0,0 +1,2 @@
+-- The accounts made for the users of an LDAP directory, which only the directory logs in to
+alter table user_ add column directory_account boolean default false not null;
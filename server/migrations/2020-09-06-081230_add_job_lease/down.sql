@@ -0,0 +1,3 @@
+drop index idx_job_lease_expires;
+
+alter table job drop column lease_expires;
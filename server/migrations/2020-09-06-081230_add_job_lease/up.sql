@@ -0,0 +1,5 @@
+-- How long the worker on a running job holds on to it. A job whose lease ran out lost its worker,
+-- and another one takes it up.
+alter table job add column lease_expires timestamp;
+
+create index idx_job_lease_expires on job (lease_expires) where status = 1;
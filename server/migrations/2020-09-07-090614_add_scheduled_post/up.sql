@@ -0,0 +1,16 @@
+-- The posts users wrote ahead of time, each put up by the job queue when it's due. One is gone
+-- once it's up, or if it's cancelled.
+create table scheduled_post (
+  id serial primary key,
+  creator_id int references user_ on update cascade on delete cascade not null,
+  community_id int references community on update cascade on delete cascade not null,
+  name varchar(200) not null,
+  url text,
+  body text,
+  nsfw boolean default false not null,
+  flair_id int references community_flair on update cascade on delete set null,
+  publish_at timestamp not null,
+  published timestamp not null default now()
+);
+
+create index idx_scheduled_post_creator on scheduled_post (creator_id);
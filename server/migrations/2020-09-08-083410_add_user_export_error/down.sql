@@ -0,0 +1 @@
+alter table user_export drop column error;
@@ -0,0 +1,3 @@
+-- Why the export couldn't be generated. It counts as completed then, so the user can ask for
+-- another one.
+alter table user_export add column error text;
@@ -0,0 +1,2 @@
+alter table scheduled_post drop column error;
+alter table scheduled_post drop column post_id;
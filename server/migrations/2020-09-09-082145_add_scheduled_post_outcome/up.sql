@@ -0,0 +1,4 @@
+-- The post put up for it, recorded along with the post so a retry doesn't put it up twice, and
+-- why it couldn't be put up, once its job gave up on it.
+alter table scheduled_post add column post_id int references post on update cascade on delete cascade;
+alter table scheduled_post add column error text;
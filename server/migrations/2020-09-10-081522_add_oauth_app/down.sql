@@ -0,0 +1,3 @@
+drop table oauth_token;
+drop table oauth_authorization;
+drop table oauth_app;
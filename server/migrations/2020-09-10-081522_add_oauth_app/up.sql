@@ -0,0 +1,32 @@
+-- The apps registered through the Mastodon API. They can only send the user back to the redirect
+-- uris they registered with, one per line.
+create table oauth_app (
+  id serial primary key,
+  client_id text not null unique,
+  client_secret text not null,
+  name text not null,
+  website text,
+  redirect_uris text not null,
+  published timestamp not null default now()
+);
+
+-- The codes the app gets back once the user logs in. They're exchanged once for a token, within
+-- a few minutes.
+create table oauth_authorization (
+  id serial primary key,
+  app_id int references oauth_app on update cascade on delete cascade not null,
+  user_id int references user_ on update cascade on delete cascade not null,
+  redirect_uri text not null,
+  code_encrypted text not null unique,
+  published timestamp not null default now()
+);
+
+-- The access tokens of the apps, until they're revoked
+create table oauth_token (
+  id serial primary key,
+  app_id int references oauth_app on update cascade on delete cascade not null,
+  user_id int references user_ on update cascade on delete cascade not null,
+  token_encrypted text not null unique,
+  scope text not null,
+  published timestamp not null default now()
+);
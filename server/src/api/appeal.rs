@@ -0,0 +1,444 @@
+use crate::{
+  api::{
+    claims::Claims,
+    community::check_community_mod_or_admin,
+    user::send_mod_action_notice,
+    APIError,
+    Oper,
+    Perform,
+  },
+  apub::ApubObjectType,
+  blocking,
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::{
+  appeal::*,
+  comment::*,
+  community::*,
+  community_view::*,
+  moderator::*,
+  post::*,
+  user::*,
+  user_view::*,
+  Bannable,
+  Crud,
+};
+use lemmy_utils::settings::Settings;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The longest reason an appeal can give
+const MAX_APPEAL_REASON_LENGTH: usize = 1000;
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CreateAppeal {
+  kind: AppealKind,
+  mod_action_id: i32,
+  reason: String,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct AppealResponse {
+  appeal: Appeal,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ListAppeals {
+  community_id: Option<i32>,
+  pending_only: bool,
+  page: Option<i64>,
+  limit: Option<i64>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ListAppealsResponse {
+  appeals: Vec<Appeal>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetMyAppeals {
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ResolveAppeal {
+  appeal_id: i32,
+  approve: bool,
+  reason: Option<String>,
+  auth: String,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<CreateAppeal> {
+  type Response = AppealResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<AppealResponse, LemmyError> {
+    let data: &CreateAppeal = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_valid_appeal_reason(&data.reason)?;
+
+    let user_id = claims.id;
+
+    // Only the affected user can appeal, and only while the action is still in effect
+    let kind = data.kind;
+    let mod_action_id = data.mod_action_id;
+    let (community_id, appealable) = match blocking(pool, move |conn| {
+      check_appealable(conn, kind, mod_action_id, user_id)
+    })
+    .await?
+    {
+      Ok(checked) => checked,
+      Err(_e) => return Err(APIError::err("couldnt_find_mod_action").into()),
+    };
+
+    if !appealable {
+      return Err(APIError::err("not_appealable").into());
+    }
+
+    let appeal_form = AppealForm {
+      kind: kind as i16,
+      mod_action_id,
+      creator_id: user_id,
+      community_id,
+      reason: data.reason.trim().to_owned(),
+    };
+
+    let appeal = match blocking(pool, move |conn| Appeal::create(conn, &appeal_form)).await? {
+      Ok(appeal) => appeal,
+      Err(_e) => return Err(APIError::err("appeal_already_exists").into()),
+    };
+
+    Ok(AppealResponse { appeal })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ListAppeals> {
+  type Response = ListAppealsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListAppealsResponse, LemmyError> {
+    let data: &ListAppeals = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    // Without a community, admins get the whole site and mods get the communities they moderate
+    let community_ids = match data.community_id {
+      Some(community_id) => {
+        check_community_mod_or_admin(pool, user_id, community_id).await?;
+        Some(vec![community_id])
+      }
+      None => {
+        let user = blocking(pool, move |conn| UserView::read(conn, user_id)).await??;
+        if user.admin {
+          None
+        } else {
+          let moderated = blocking(pool, move |conn| {
+            CommunityModeratorView::for_user(conn, user_id)
+          })
+          .await??;
+          if moderated.is_empty() {
+            return Err(APIError::err("not_a_moderator").into());
+          }
+          Some(moderated.into_iter().map(|m| m.community_id).collect())
+        }
+      }
+    };
+
+    let pending_only = data.pending_only;
+    let page = data.page;
+    let limit = data.limit;
+    let appeals = blocking(pool, move |conn| {
+      Appeal::list(conn, community_ids, pending_only, page, limit)
+    })
+    .await??;
+
+    Ok(ListAppealsResponse { appeals })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetMyAppeals> {
+  type Response = ListAppealsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListAppealsResponse, LemmyError> {
+    let data: &GetMyAppeals = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    let appeals = blocking(pool, move |conn| Appeal::for_creator(conn, user_id)).await??;
+
+    Ok(ListAppealsResponse { appeals })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ResolveAppeal> {
+  type Response = AppealResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<AppealResponse, LemmyError> {
+    let data: &ResolveAppeal = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let appeal_id = data.appeal_id;
+    let appeal = match blocking(pool, move |conn| Appeal::read(conn, appeal_id)).await? {
+      Ok(appeal) => appeal,
+      Err(_e) => return Err(APIError::err("couldnt_find_appeal").into()),
+    };
+
+    let kind = match AppealKind::from_i16(appeal.kind) {
+      Some(kind) => kind,
+      None => return Err(APIError::err("couldnt_find_appeal").into()),
+    };
+
+    // Site bans are for the admins, everything else is for the mods of the community
+    match appeal.community_id {
+      Some(community_id) => check_community_mod_or_admin(pool, user_id, community_id).await?,
+      None => {
+        let is_admin = move |conn: &'_ _| UserView::read(conn, user_id).map(|u| u.admin);
+        if !blocking(pool, is_admin).await?? {
+          return Err(APIError::err("not_an_admin").into());
+        }
+      }
+    }
+
+    let status = if data.approve {
+      AppealStatus::Approved
+    } else {
+      AppealStatus::Denied
+    };
+    let resolve = move |conn: &'_ _| Appeal::resolve(conn, appeal_id, user_id, status);
+    let resolved = match blocking(pool, resolve).await? {
+      Ok(appeal) => appeal,
+      Err(_e) => return Err(APIError::err("couldnt_resolve_appeal").into()),
+    };
+
+    let mod_ = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+    let creator_id = appeal.creator_id;
+    let mod_action_id = appeal.mod_action_id;
+    let reason = data.reason.to_owned();
+
+    let subject = match kind {
+      AppealKind::PostRemoval => {
+        let action = blocking(pool, move |conn| ModRemovePost::read(conn, mod_action_id)).await??;
+        let post_id = action.post_id;
+        let post = if data.approve {
+          let post =
+            blocking(pool, move |conn| Post::update_removed(conn, post_id, false)).await??;
+
+          // Mod tables
+          let form = ModRemovePostForm {
+            mod_user_id: user_id,
+            post_id,
+            removed: Some(false),
+            reason: reason.to_owned(),
+          };
+          blocking(pool, move |conn| ModRemovePost::create(conn, &form)).await??;
+
+          post.send_undo_remove(&mod_, &self.client, pool).await?;
+          post
+        } else {
+          blocking(pool, move |conn| Post::read(conn, post_id)).await??
+        };
+        format!(
+          "the removal of your post [{}](/post/{})",
+          post.name, post.id
+        )
+      }
+      AppealKind::CommentRemoval => {
+        let action = blocking(pool, move |conn| {
+          ModRemoveComment::read(conn, mod_action_id)
+        })
+        .await??;
+        let comment_id = action.comment_id;
+        let comment = if data.approve {
+          let comment = blocking(pool, move |conn| {
+            Comment::update_removed(conn, comment_id, false)
+          })
+          .await??;
+
+          // Mod tables
+          let form = ModRemoveCommentForm {
+            mod_user_id: user_id,
+            comment_id,
+            removed: Some(false),
+            reason: reason.to_owned(),
+          };
+          blocking(pool, move |conn| ModRemoveComment::create(conn, &form)).await??;
+
+          comment.send_undo_remove(&mod_, &self.client, pool).await?;
+          comment
+        } else {
+          blocking(pool, move |conn| Comment::read(conn, comment_id)).await??
+        };
+        format!(
+          "the removal of your [comment](/post/{}/comment/{})",
+          comment.post_id, comment.id
+        )
+      }
+      AppealKind::CommunityBan => {
+        let action = blocking(pool, move |conn| {
+          ModBanFromCommunity::read(conn, mod_action_id)
+        })
+        .await??;
+        let community_id = action.community_id;
+        if data.approve {
+          let community_user_ban_form = CommunityUserBanForm {
+            community_id,
+            user_id: creator_id,
+            expires: None,
+          };
+          let unban = move |conn: &'_ _| CommunityUserBan::unban(conn, &community_user_ban_form);
+          if blocking(pool, unban).await?.is_err() {
+            return Err(APIError::err("community_user_already_banned").into());
+          }
+
+          // Mod tables
+          let form = ModBanFromCommunityForm {
+            mod_user_id: user_id,
+            other_user_id: creator_id,
+            community_id,
+            reason: reason.to_owned(),
+            banned: Some(false),
+            expires: None,
+          };
+          blocking(pool, move |conn| ModBanFromCommunity::create(conn, &form)).await??;
+        }
+        let community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
+        format!("your ban from /c/{}", community.name)
+      }
+      AppealKind::SiteBan => {
+        if data.approve {
+          let unban = move |conn: &'_ _| User_::ban_user(conn, creator_id, false, None);
+          if blocking(pool, unban).await?.is_err() {
+            return Err(APIError::err("couldnt_update_user").into());
+          }
+
+          // Mod tables
+          let form = ModBanForm {
+            mod_user_id: user_id,
+            other_user_id: creator_id,
+            reason: reason.to_owned(),
+            banned: Some(false),
+            expires: None,
+          };
+          blocking(pool, move |conn| ModBan::create(conn, &form)).await??;
+        }
+        format!("your ban from {}", Settings::get().hostname)
+      }
+    };
+
+    let action = format!(
+      "Your appeal against {} was {}.",
+      subject,
+      if data.approve { "approved" } else { "denied" }
+    );
+    send_mod_action_notice(
+      pool,
+      &self.client,
+      websocket_info,
+      &mod_,
+      creator_id,
+      &action,
+      &reason,
+    )
+    .await?;
+
+    Ok(AppealResponse { appeal: resolved })
+  }
+}
+
+fn check_valid_appeal_reason(reason: &str) -> Result<(), LemmyError> {
+  let reason_len = reason.trim().chars().count();
+  if reason_len == 0 || reason_len > MAX_APPEAL_REASON_LENGTH {
+    return Err(APIError::err("invalid_appeal_reason").into());
+  }
+
+  Ok(())
+}
+
+/// Returns the community the mod action was taken in, which is None for site bans, and whether
+/// it removed or banned the given user and is still in effect.
+fn check_appealable(
+  conn: &diesel::PgConnection,
+  kind: AppealKind,
+  mod_action_id: i32,
+  user_id: i32,
+) -> Result<(Option<i32>, bool), diesel::result::Error> {
+  let checked = match kind {
+    AppealKind::PostRemoval => {
+      let action = ModRemovePost::read(conn, mod_action_id)?;
+      let post = Post::read(conn, action.post_id)?;
+      (
+        Some(post.community_id),
+        action.removed == Some(true) && post.removed && post.creator_id == user_id,
+      )
+    }
+    AppealKind::CommentRemoval => {
+      let action = ModRemoveComment::read(conn, mod_action_id)?;
+      let comment = Comment::read(conn, action.comment_id)?;
+      let post = Post::read(conn, comment.post_id)?;
+      (
+        Some(post.community_id),
+        action.removed == Some(true) && comment.removed && comment.creator_id == user_id,
+      )
+    }
+    AppealKind::CommunityBan => {
+      let action = ModBanFromCommunity::read(conn, mod_action_id)?;
+      let still_banned = CommunityUserBanView::get(conn, user_id, action.community_id).is_ok();
+      (
+        Some(action.community_id),
+        action.banned == Some(true) && action.other_user_id == user_id && still_banned,
+      )
+    }
+    AppealKind::SiteBan => {
+      let action = ModBan::read(conn, mod_action_id)?;
+      let user = User_::read(conn, user_id)?;
+      (
+        None,
+        action.banned == Some(true) && action.other_user_id == user_id && user.is_banned(),
+      )
+    }
+  };
+
+  Ok(checked)
+}
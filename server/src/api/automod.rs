@@ -0,0 +1,524 @@
+use crate::{
+  api::{
+    claims::Claims,
+    community::{check_community_mod_or_admin, send_to_community_mods},
+    report::{send_comment_report_to_mods, send_post_report_to_mods},
+    APIError,
+    Oper,
+    Perform,
+  },
+  blocking,
+  websocket::{UserOperation, WebsocketInfo},
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::{automod::*, naive_now, report::*, user_view::*, Crud};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ListAutomodRules {
+  community_id: i32,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CreateAutomodRule {
+  community_id: i32,
+  name: String,
+  condition: i16,
+  target: i16,
+  pattern: Option<String>,
+  threshold: Option<i32>,
+  action: i16,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct EditAutomodRule {
+  edit_id: i32,
+  name: String,
+  condition: i16,
+  target: i16,
+  pattern: Option<String>,
+  threshold: Option<i32>,
+  action: i16,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeleteAutomodRule {
+  rule_id: i32,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ListAutomodRulesResponse {
+  rules: Vec<AutomodRule>,
+}
+
+/// Runs the community's rules against some made up content, without acting on it. The author
+/// defaults to the caller.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct TestAutomodRules {
+  community_id: i32,
+  title: Option<String>,
+  body: Option<String>,
+  url: Option<String>,
+  author_id: Option<i32>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct TestAutomodRulesResponse {
+  matched: Vec<AutomodRule>,
+  removed: bool,
+}
+
+/// Sent live to the mods of the community when a new post or comment matched some of its rules
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct AutomodHitResponse {
+  community_id: i32,
+  post_id: Option<i32>,
+  comment_id: Option<i32>,
+  matched: Vec<AutomodRule>,
+  removed: bool,
+}
+
+/// The parts of a new post or comment that automod rules look at. Comments only have a body.
+pub struct AutomodSubject<'a> {
+  pub title: Option<&'a str>,
+  pub body: Option<&'a str>,
+  pub url: Option<&'a str>,
+}
+
+/// The rules that matched a new post or comment.
+pub struct AutomodVerdict {
+  pub matched: Vec<AutomodRule>,
+}
+
+impl AutomodVerdict {
+  /// Whether the content should be removed. Content held for approval is removed until a mod
+  /// restores it.
+  pub fn removes(&self) -> bool {
+    self.matched.iter().any(|r| {
+      let action = AutomodAction::from_i16(r.action);
+      action == Some(AutomodAction::Remove) || action == Some(AutomodAction::RequireApproval)
+    })
+  }
+
+  /// Files a report for every rule that asks for one, on behalf of the mod who made the rule.
+  pub async fn report_post(
+    &self,
+    pool: &DbPool,
+    websocket_info: &Option<WebsocketInfo>,
+    post_id: i32,
+  ) -> Result<(), LemmyError> {
+    for (creator_id, reason) in self.reports() {
+      let form = PostReportForm {
+        creator_id,
+        post_id,
+        reason,
+        rule_id: None,
+      };
+      let create = move |conn: &'_ _| PostReport::create(conn, &form);
+      let report = match blocking(pool, create).await? {
+        Ok(report) => report,
+        Err(_e) => return Err(APIError::err("couldnt_create_report").into()),
+      };
+      send_post_report_to_mods(pool, websocket_info, report.id).await?;
+    }
+    Ok(())
+  }
+
+  /// Same as `report_post`, for comments.
+  pub async fn report_comment(
+    &self,
+    pool: &DbPool,
+    websocket_info: &Option<WebsocketInfo>,
+    comment_id: i32,
+  ) -> Result<(), LemmyError> {
+    for (creator_id, reason) in self.reports() {
+      let form = CommentReportForm {
+        creator_id,
+        comment_id,
+        reason,
+        rule_id: None,
+      };
+      let create = move |conn: &'_ _| CommentReport::create(conn, &form);
+      let report = match blocking(pool, create).await? {
+        Ok(report) => report,
+        Err(_e) => return Err(APIError::err("couldnt_create_report").into()),
+      };
+      send_comment_report_to_mods(pool, websocket_info, report.id).await?;
+    }
+    Ok(())
+  }
+
+  /// Tells the mods which rules the new content matched, if any did.
+  pub async fn send_hit_to_mods(
+    &self,
+    pool: &DbPool,
+    websocket_info: &Option<WebsocketInfo>,
+    community_id: i32,
+    post_id: Option<i32>,
+    comment_id: Option<i32>,
+  ) -> Result<(), LemmyError> {
+    if self.matched.is_empty() {
+      return Ok(());
+    }
+
+    let res = AutomodHitResponse {
+      community_id,
+      post_id,
+      comment_id,
+      matched: self.matched.clone(),
+      removed: self.removes(),
+    };
+    send_to_community_mods(
+      pool,
+      websocket_info,
+      community_id,
+      UserOperation::AutomodHit,
+      res,
+    )
+    .await
+  }
+
+  /// The mod who made the rule, and the reason to give
+  fn reports(&self) -> Vec<(i32, String)> {
+    self
+      .matched
+      .iter()
+      .filter_map(|r| match AutomodAction::from_i16(r.action) {
+        Some(AutomodAction::Report) => Some((r.creator_id, format!("Automod: {}", r.name))),
+        Some(AutomodAction::RequireApproval) => Some((
+          r.creator_id,
+          format!("Awaiting approval (automod: {})", r.name),
+        )),
+        _ => None,
+      })
+      .collect()
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ListAutomodRules> {
+  type Response = ListAutomodRulesResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListAutomodRulesResponse, LemmyError> {
+    let data: &ListAutomodRules = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    // Only mods get to see the rules, so that spammers can't work around them
+    let user_id = claims.id;
+    check_community_mod_or_admin(pool, user_id, data.community_id).await?;
+
+    let community_id = data.community_id;
+    let rules = blocking(pool, move |conn| {
+      AutomodRule::for_community(conn, community_id)
+    })
+    .await??;
+
+    Ok(ListAutomodRulesResponse { rules })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<CreateAutomodRule> {
+  type Response = ListAutomodRulesResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListAutomodRulesResponse, LemmyError> {
+    let data: &CreateAutomodRule = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_valid_automod_rule(
+      &data.name,
+      data.condition,
+      data.target,
+      &data.pattern,
+      data.threshold,
+      data.action,
+    )?;
+
+    let user_id = claims.id;
+    check_community_mod_or_admin(pool, user_id, data.community_id).await?;
+
+    let rule_form = AutomodRuleForm {
+      community_id: data.community_id,
+      creator_id: user_id,
+      name: data.name.trim().to_owned(),
+      condition: data.condition,
+      target: data.target,
+      pattern: data.pattern.to_owned(),
+      threshold: data.threshold,
+      action: data.action,
+      updated: None,
+    };
+
+    let create = move |conn: &'_ _| AutomodRule::create(conn, &rule_form);
+    if blocking(pool, create).await?.is_err() {
+      return Err(APIError::err("couldnt_create_automod_rule").into());
+    }
+
+    let community_id = data.community_id;
+    let rules = blocking(pool, move |conn| {
+      AutomodRule::for_community(conn, community_id)
+    })
+    .await??;
+
+    Ok(ListAutomodRulesResponse { rules })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<EditAutomodRule> {
+  type Response = ListAutomodRulesResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListAutomodRulesResponse, LemmyError> {
+    let data: &EditAutomodRule = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_valid_automod_rule(
+      &data.name,
+      data.condition,
+      data.target,
+      &data.pattern,
+      data.threshold,
+      data.action,
+    )?;
+
+    let user_id = claims.id;
+
+    let edit_id = data.edit_id;
+    let read_rule = blocking(pool, move |conn| AutomodRule::read(conn, edit_id)).await??;
+    check_community_mod_or_admin(pool, user_id, read_rule.community_id).await?;
+
+    let rule_form = AutomodRuleForm {
+      community_id: read_rule.community_id,
+      creator_id: read_rule.creator_id,
+      name: data.name.trim().to_owned(),
+      condition: data.condition,
+      target: data.target,
+      pattern: data.pattern.to_owned(),
+      threshold: data.threshold,
+      action: data.action,
+      updated: Some(naive_now()),
+    };
+
+    let update = move |conn: &'_ _| AutomodRule::update(conn, edit_id, &rule_form);
+    if blocking(pool, update).await?.is_err() {
+      return Err(APIError::err("couldnt_update_automod_rule").into());
+    }
+
+    let community_id = read_rule.community_id;
+    let rules = blocking(pool, move |conn| {
+      AutomodRule::for_community(conn, community_id)
+    })
+    .await??;
+
+    Ok(ListAutomodRulesResponse { rules })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<DeleteAutomodRule> {
+  type Response = ListAutomodRulesResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListAutomodRulesResponse, LemmyError> {
+    let data: &DeleteAutomodRule = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let rule_id = data.rule_id;
+    let read_rule = blocking(pool, move |conn| AutomodRule::read(conn, rule_id)).await??;
+    check_community_mod_or_admin(pool, user_id, read_rule.community_id).await?;
+
+    let delete = move |conn: &'_ _| AutomodRule::delete(conn, rule_id);
+    if blocking(pool, delete).await?.is_err() {
+      return Err(APIError::err("couldnt_update_automod_rule").into());
+    }
+
+    let community_id = read_rule.community_id;
+    let rules = blocking(pool, move |conn| {
+      AutomodRule::for_community(conn, community_id)
+    })
+    .await??;
+
+    Ok(ListAutomodRulesResponse { rules })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<TestAutomodRules> {
+  type Response = TestAutomodRulesResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<TestAutomodRulesResponse, LemmyError> {
+    let data: &TestAutomodRules = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    check_community_mod_or_admin(pool, user_id, data.community_id).await?;
+
+    let subject = AutomodSubject {
+      title: data.title.as_deref(),
+      body: data.body.as_deref(),
+      url: data.url.as_deref(),
+    };
+
+    let author_id = data.author_id.unwrap_or(user_id);
+    let verdict = run_automod(pool, data.community_id, author_id, &subject).await?;
+    let removed = verdict.removes();
+
+    Ok(TestAutomodRulesResponse {
+      matched: verdict.matched,
+      removed,
+    })
+  }
+}
+
+/// Checks new content from the author against the rules of the community.
+pub async fn run_automod(
+  pool: &DbPool,
+  community_id: i32,
+  author_id: i32,
+  subject: &AutomodSubject<'_>,
+) -> Result<AutomodVerdict, LemmyError> {
+  let rules = blocking(pool, move |conn| {
+    AutomodRule::for_community(conn, community_id)
+  })
+  .await??;
+
+  if rules.is_empty() {
+    return Ok(AutomodVerdict {
+      matched: Vec::new(),
+    });
+  }
+
+  let author = blocking(pool, move |conn| UserView::read(conn, author_id)).await??;
+
+  let matched = rules
+    .into_iter()
+    .filter(|r| automod_rule_matches(r, subject, &author))
+    .collect();
+
+  Ok(AutomodVerdict { matched })
+}
+
+fn automod_rule_matches(rule: &AutomodRule, subject: &AutomodSubject, author: &UserView) -> bool {
+  let pattern = rule.pattern.to_owned().unwrap_or_default();
+  let threshold = rule.threshold.unwrap_or(0);
+
+  let texts: Vec<&str> = match AutomodTarget::from_i16(rule.target) {
+    Some(AutomodTarget::Title) => subject.title.into_iter().collect(),
+    Some(AutomodTarget::Body) => subject.body.into_iter().collect(),
+    Some(AutomodTarget::Url) => subject.url.into_iter().collect(),
+    _ => [subject.title, subject.body, subject.url]
+      .iter()
+      .flatten()
+      .copied()
+      .collect(),
+  };
+
+  match AutomodCondition::from_i16(rule.condition) {
+    Some(AutomodCondition::Keyword) => {
+      let keyword = pattern.to_lowercase();
+      texts.iter().any(|t| t.to_lowercase().contains(&keyword))
+    }
+    Some(AutomodCondition::Regex) => match Regex::new(&pattern) {
+      Ok(regex) => texts.iter().any(|t| regex.is_match(t)),
+      Err(_e) => false,
+    },
+    Some(AutomodCondition::Domain) => {
+      let domain = pattern.to_lowercase();
+      subject
+        .url
+        .and_then(|u| Url::parse(u).ok())
+        .and_then(|u| u.host_str().map(|h| h.to_lowercase()))
+        .map(|host| host == domain || host.ends_with(&format!(".{}", domain)))
+        .unwrap_or(false)
+    }
+    Some(AutomodCondition::AccountAge) => {
+      let age = naive_now().signed_duration_since(author.published);
+      age.num_days() < i64::from(threshold)
+    }
+    Some(AutomodCondition::AuthorScore) => {
+      author.post_score + author.comment_score < i64::from(threshold)
+    }
+    None => false,
+  }
+}
+
+fn check_valid_automod_rule(
+  name: &str,
+  condition: i16,
+  target: i16,
+  pattern: &Option<String>,
+  threshold: Option<i32>,
+  action: i16,
+) -> Result<(), LemmyError> {
+  let name_len = name.trim().chars().count();
+  if name_len == 0 || name_len > 100 {
+    return Err(APIError::err("invalid_automod_rule_name").into());
+  }
+
+  if AutomodTarget::from_i16(target).is_none() || AutomodAction::from_i16(action).is_none() {
+    return Err(APIError::err("invalid_automod_rule").into());
+  }
+
+  let pattern = pattern.to_owned().unwrap_or_default();
+  let valid = match AutomodCondition::from_i16(condition) {
+    Some(AutomodCondition::Keyword) | Some(AutomodCondition::Domain) => !pattern.trim().is_empty(),
+    Some(AutomodCondition::Regex) => !pattern.is_empty() && Regex::new(&pattern).is_ok(),
+    Some(AutomodCondition::AccountAge) | Some(AutomodCondition::AuthorScore) => threshold.is_some(),
+    None => false,
+  };
+  if !valid {
+    return Err(APIError::err("invalid_automod_rule").into());
+  }
+
+  Ok(())
+}
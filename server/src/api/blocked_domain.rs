@@ -0,0 +1,158 @@
+use crate::{
+  api::{check_admin_or_community_mod, claims::Claims, APIError, Oper, Perform},
+  blocking,
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::{blocked_domain::*, Crud};
+use lemmy_utils::{domain_matches, is_valid_domain_pattern};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Lists the site wide blocks, or the ones of a community.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ListBlockedDomains {
+  community_id: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CreateBlockedDomain {
+  community_id: Option<i32>,
+  domain: String,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeleteBlockedDomain {
+  blocked_domain_id: i32,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ListBlockedDomainsResponse {
+  domains: Vec<BlockedDomain>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ListBlockedDomains> {
+  type Response = ListBlockedDomainsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListBlockedDomainsResponse, LemmyError> {
+    let data: &ListBlockedDomains = &self.data;
+
+    let community_id = data.community_id;
+    let domains = blocking(pool, move |conn| BlockedDomain::list(conn, community_id)).await??;
+
+    Ok(ListBlockedDomainsResponse { domains })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<CreateBlockedDomain> {
+  type Response = ListBlockedDomainsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListBlockedDomainsResponse, LemmyError> {
+    let data: &CreateBlockedDomain = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let domain = data.domain.trim().to_lowercase();
+    if !is_valid_domain_pattern(&domain) {
+      return Err(APIError::err("invalid_domain").into());
+    }
+
+    let user_id = claims.id;
+    check_admin_or_community_mod(pool, user_id, data.community_id).await?;
+
+    let block_form = BlockedDomainForm {
+      community_id: data.community_id,
+      domain,
+      creator_id: user_id,
+    };
+
+    let create = move |conn: &'_ _| BlockedDomain::create(conn, &block_form);
+    if blocking(pool, create).await?.is_err() {
+      return Err(APIError::err("domain_already_blocked").into());
+    }
+
+    let community_id = data.community_id;
+    let domains = blocking(pool, move |conn| BlockedDomain::list(conn, community_id)).await??;
+
+    Ok(ListBlockedDomainsResponse { domains })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<DeleteBlockedDomain> {
+  type Response = ListBlockedDomainsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListBlockedDomainsResponse, LemmyError> {
+    let data: &DeleteBlockedDomain = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let blocked_domain_id = data.blocked_domain_id;
+    let read_block = blocking(pool, move |conn| {
+      BlockedDomain::read(conn, blocked_domain_id)
+    })
+    .await??;
+    check_admin_or_community_mod(pool, user_id, read_block.community_id).await?;
+
+    let delete = move |conn: &'_ _| BlockedDomain::delete(conn, blocked_domain_id);
+    if blocking(pool, delete).await?.is_err() {
+      return Err(APIError::err("couldnt_unblock_domain").into());
+    }
+
+    let community_id = read_block.community_id;
+    let domains = blocking(pool, move |conn| BlockedDomain::list(conn, community_id)).await??;
+
+    Ok(ListBlockedDomainsResponse { domains })
+  }
+}
+
+/// Rejects links to a domain that the site or the community has blocked.
+pub async fn check_url_domain_allowed(
+  pool: &DbPool,
+  community_id: i32,
+  url: &Option<String>,
+) -> Result<(), LemmyError> {
+  let host = match url.as_ref().and_then(|u| Url::parse(u).ok()) {
+    Some(url) => match url.host_str() {
+      Some(host) => host.to_owned(),
+      None => return Ok(()),
+    },
+    None => return Ok(()),
+  };
+
+  let blocks = blocking(pool, move |conn| {
+    BlockedDomain::applicable(conn, community_id)
+  })
+  .await??;
+  if blocks.iter().any(|b| domain_matches(&b.domain, &host)) {
+    return Err(APIError::err("domain_blocked").into());
+  }
+
+  Ok(())
+}
@@ -0,0 +1,223 @@
+use crate::{
+  api::{
+    claims::Claims,
+    community::{check_community_mod_or_admin, check_removal_reason},
+    APIError,
+    Oper,
+    Perform,
+  },
+  apub::ApubObjectType,
+  blocking,
+  cache::invalidate_post_listings,
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::{bulk_moderation::*, user::*, Crud};
+use lemmy_utils::naive_from_unix;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+/// The most posts or comments a single bulk action can change
+const MAX_BULK_ITEMS: usize = 500;
+
+/// Acts on the posts of a community, picked by id or by creator, and optionally by when they were
+/// published.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct BulkModeratePosts {
+  community_id: i32,
+  post_ids: Option<Vec<i32>>,
+  creator_id: Option<i32>,
+  after: Option<i64>,
+  before: Option<i64>,
+  action: BulkPostAction,
+  reason: Option<String>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct BulkModerateComments {
+  community_id: i32,
+  comment_ids: Option<Vec<i32>>,
+  creator_id: Option<i32>,
+  after: Option<i64>,
+  before: Option<i64>,
+  removed: bool,
+  reason: Option<String>,
+  auth: String,
+}
+
+/// The posts that changed. The ones that were already in that state are left out.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BulkModeratePostsResponse {
+  post_ids: Vec<i32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BulkModerateCommentsResponse {
+  comment_ids: Vec<i32>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<BulkModeratePosts> {
+  type Response = BulkModeratePostsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<BulkModeratePostsResponse, LemmyError> {
+    let data: &BulkModeratePosts = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    check_community_mod_or_admin(pool, user_id, data.community_id).await?;
+    if data.action == BulkPostAction::Remove {
+      check_removal_reason(pool, &data.reason).await?;
+    }
+
+    let target = bulk_target(
+      data.community_id,
+      &data.post_ids,
+      data.creator_id,
+      data.after,
+      data.before,
+    )?;
+    let post_ids = blocking(pool, move |conn| target.post_ids(conn)).await??;
+    if post_ids.len() > MAX_BULK_ITEMS {
+      return Err(APIError::err("too_many_bulk_items").into());
+    }
+
+    let action = data.action;
+    let reason = data.reason.to_owned();
+    let moderate = move |conn: &'_ _| bulk_moderate_posts(conn, user_id, post_ids, action, reason);
+    let updated_posts = match blocking(pool, moderate).await? {
+      Ok(posts) => posts,
+      Err(_e) => return Err(APIError::err("couldnt_update_post").into()),
+    };
+
+    invalidate_post_listings(Some(data.community_id));
+
+    // The changes are already committed by now, so one post failing to federate shouldn't fail
+    // the rest
+    let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+    for post in &updated_posts {
+      let sent = match action {
+        BulkPostAction::Remove => {
+          post
+            .send_remove(&user, data.reason.to_owned(), &self.client, pool)
+            .await
+        }
+        BulkPostAction::Restore => post.send_undo_remove(&user, &self.client, pool).await,
+        BulkPostAction::Lock | BulkPostAction::Unlock => {
+          post.send_update(&user, &self.client, pool).await
+        }
+      };
+      if let Err(e) = sent {
+        error!("Couldn't federate bulk action on post {}: {}", post.id, e);
+      }
+    }
+
+    Ok(BulkModeratePostsResponse {
+      post_ids: updated_posts.iter().map(|p| p.id).collect(),
+    })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<BulkModerateComments> {
+  type Response = BulkModerateCommentsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<BulkModerateCommentsResponse, LemmyError> {
+    let data: &BulkModerateComments = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    check_community_mod_or_admin(pool, user_id, data.community_id).await?;
+    if data.removed {
+      check_removal_reason(pool, &data.reason).await?;
+    }
+
+    let target = bulk_target(
+      data.community_id,
+      &data.comment_ids,
+      data.creator_id,
+      data.after,
+      data.before,
+    )?;
+    let comment_ids = blocking(pool, move |conn| target.comment_ids(conn)).await??;
+    if comment_ids.len() > MAX_BULK_ITEMS {
+      return Err(APIError::err("too_many_bulk_items").into());
+    }
+
+    let removed = data.removed;
+    let reason = data.reason.to_owned();
+    let moderate =
+      move |conn: &'_ _| bulk_remove_comments(conn, user_id, comment_ids, removed, reason);
+    let updated_comments = match blocking(pool, moderate).await? {
+      Ok(comments) => comments,
+      Err(_e) => return Err(APIError::err("couldnt_update_comment").into()),
+    };
+
+    let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+    for comment in &updated_comments {
+      let sent = if removed {
+        comment
+          .send_remove(&user, data.reason.to_owned(), &self.client, pool)
+          .await
+      } else {
+        comment.send_undo_remove(&user, &self.client, pool).await
+      };
+      if let Err(e) = sent {
+        error!(
+          "Couldn't federate bulk action on comment {}: {}",
+          comment.id, e
+        );
+      }
+    }
+
+    Ok(BulkModerateCommentsResponse {
+      comment_ids: updated_comments.iter().map(|c| c.id).collect(),
+    })
+  }
+}
+
+/// A bulk action has to be narrowed down to some ids or a creator, so that a mod can't clear out
+/// a whole community by accident.
+fn bulk_target(
+  community_id: i32,
+  ids: &Option<Vec<i32>>,
+  creator_id: Option<i32>,
+  after: Option<i64>,
+  before: Option<i64>,
+) -> Result<BulkModerationTarget, LemmyError> {
+  let has_ids = ids.as_ref().map_or(false, |ids| !ids.is_empty());
+  if !has_ids && creator_id.is_none() {
+    return Err(APIError::err("invalid_bulk_target").into());
+  }
+
+  if ids.as_ref().map_or(false, |ids| ids.len() > MAX_BULK_ITEMS) {
+    return Err(APIError::err("too_many_bulk_items").into());
+  }
+
+  Ok(BulkModerationTarget {
+    community_id,
+    ids: ids.to_owned().filter(|ids| !ids.is_empty()),
+    creator_id,
+    after: after.map(naive_from_unix),
+    before: before.map(naive_from_unix),
+  })
+}
@@ -1,12 +1,14 @@
+use actix_web::{http::header::AUTHORIZATION, HttpRequest};
 use diesel::{result::Error, PgConnection};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, TokenData, Validation};
-use lemmy_db::{user::User_, Crud};
+use lemmy_db::{oauth::OAuthToken, user::User_, Crud};
 use lemmy_utils::{is_email_regex, settings::Settings};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 type Jwt = String;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Claims {
   pub id: i32,
   pub username: String,
@@ -18,6 +20,13 @@ pub struct Claims {
   pub lang: String,
   pub avatar: Option<String>,
   pub show_avatars: bool,
+  // Tokens issued before this setting existed don't have it
+  #[serde(default)]
+  pub hide_read_posts: bool,
+  #[serde(default)]
+  pub hide_bot_accounts: bool,
+  #[serde(default)]
+  pub hide_presence: bool,
 }
 
 impl Claims {
@@ -33,8 +42,39 @@ impl Claims {
     )
   }
 
+  /// The claims of the `Authorization: Bearer` token of a request, with the token, for the
+  /// clients that use the header instead of the auth field
+  pub fn decode_bearer(request: &HttpRequest) -> Option<(Claims, Jwt)> {
+    let jwt = Claims::bearer_token(request)?;
+    let claims = Claims::decode(&jwt).ok()?.claims;
+    Some((claims, jwt))
+  }
+
+  /// The `Authorization: Bearer` token of a request, whatever kind it is
+  pub fn bearer_token(request: &HttpRequest) -> Option<String> {
+    let header = request.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    if !header.starts_with("Bearer ") {
+      return None;
+    }
+    Some(header["Bearer ".len()..].to_owned())
+  }
+
+  /// The claims of the user an OAuth access token was given to, with a JWT to act as them. The
+  /// tokens are looked up each time, so a revoked one stops working right away.
+  pub fn from_oauth_token(conn: &PgConnection, token: &str) -> Result<(Claims, Jwt), Error> {
+    let oauth_token = OAuthToken::read_from_token(conn, token)?;
+    let user = User_::read(conn, oauth_token.user_id)?;
+    let claims = Claims::for_user(&user, Settings::get().hostname);
+    let jwt = claims.encode();
+    Ok((claims, jwt))
+  }
+
   pub fn jwt(user: User_, hostname: String) -> Jwt {
-    let my_claims = Claims {
+    Claims::for_user(&user, hostname).encode()
+  }
+
+  fn for_user(user: &User_, hostname: String) -> Claims {
+    Claims {
       id: user.id,
       username: user.name.to_owned(),
       iss: hostname,
@@ -45,10 +85,16 @@ impl Claims {
       lang: user.lang.to_owned(),
       avatar: user.avatar.to_owned(),
       show_avatars: user.show_avatars.to_owned(),
-    };
+      hide_read_posts: user.hide_read_posts,
+      hide_bot_accounts: user.hide_bot_accounts,
+      hide_presence: user.hide_presence,
+    }
+  }
+
+  fn encode(&self) -> Jwt {
     encode(
       &Header::default(),
-      &my_claims,
+      self,
       &EncodingKey::from_secret(Settings::get().jwt_secret.as_ref()),
     )
     .unwrap()
@@ -1,9 +1,27 @@
 use crate::{
-  api::{claims::Claims, APIError, Oper, Perform},
-  apub::{ApubLikeableType, ApubObjectType},
+  api::{
+    automod::{run_automod, AutomodSubject},
+    claims::Claims,
+    community::{check_community_not_read_only, check_removal_reason},
+    decode_cursor,
+    decode_token,
+    encode_token,
+    muted_keyword::collapsed_comment_ids,
+    next_cursor,
+    post::can_view_edit_history,
+    user::send_mod_action_notice,
+    word_filter::{check_words, report_flagged_comment},
+    APIError,
+    Oper,
+    Perform,
+  },
+  apub::{ApubLikeableType, ApubObjectType, ApubPinnableType},
   blocking,
+  blocking_read,
+  matrix::queue_matrix_notifications,
+  push::{queue_push_notifications, PushNotification},
   websocket::{
-    server::{JoinCommunityRoom, SendComment},
+    server::{JoinCommunityRoom, SendComment, SendUnreadCounts, SendUserRoomMessage},
     UserOperation,
     WebsocketInfo,
   },
@@ -13,13 +31,16 @@ use crate::{
 use lemmy_db::{
   comment::*,
   comment_view::*,
+  community::*,
   community_view::*,
   moderator::*,
   naive_now,
   post::*,
   site_view::*,
   user::*,
+  user_alias::*,
   user_mention::*,
+  user_notification_settings::*,
   user_view::*,
   Crud,
   Likeable,
@@ -28,28 +49,29 @@ use lemmy_db::{
   SortType,
 };
 use lemmy_utils::{
+  i18n::{translate, user_langs},
   make_apub_endpoint,
-  remove_slurs,
   scrape_text_for_mentions,
   send_email,
   settings::Settings,
   EndpointType,
   MentionData,
 };
-use log::error;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
+use tracing::error;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct CreateComment {
-  content: String,
-  parent_id: Option<i32>,
-  edit_id: Option<i32>, // TODO this isn't used
+  pub content: String,
+  pub parent_id: Option<i32>,
+  pub edit_id: Option<i32>, // TODO this isn't used
   pub post_id: i32,
-  auth: String,
+  pub auth: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct EditComment {
   content: String,
   parent_id: Option<i32>, // TODO why are the parent_id, creator_id, post_id, etc fields required? They aren't going to change
@@ -63,40 +85,140 @@ pub struct EditComment {
   auth: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetCommentRevisions {
+  comment_id: i32,
+  auth: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetCommentRevisionsResponse {
+  revisions: Vec<CommentRevision>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct SaveComment {
+  pub comment_id: i32,
+  pub save: bool,
+  pub auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PinComment {
   comment_id: i32,
-  save: bool,
+  pinned: bool,
   auth: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct CommentResponse {
   pub comment: CommentView,
   pub recipient_ids: Vec<i32>,
 }
 
-#[derive(Serialize, Deserialize)]
+/// What the open posts get of a vote on one of their comments, instead of the whole comment
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct CommentScoreResponse {
+  pub comment_id: i32,
+  pub post_id: i32,
+  pub score: i64,
+  #[serde(default, skip_serializing_if = "lemmy_db::vote_counts_hidden")]
+  pub upvotes: i64,
+  #[serde(default, skip_serializing_if = "lemmy_db::vote_counts_hidden")]
+  pub downvotes: i64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct CreateCommentLike {
-  comment_id: i32,
+  pub comment_id: i32,
   pub post_id: i32,
-  score: i16,
-  auth: String,
+  pub score: i16,
+  pub auth: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetComments {
   type_: String,
   sort: String,
   page: Option<i64>,
   limit: Option<i64>,
   pub community_id: Option<i32>,
+  /// Only this comment and the replies below it
+  parent_id: Option<i32>,
+  /// How many levels of replies to go down, below the parent or the top level
+  max_depth: Option<i32>,
+  /// The `next_cursor` of the last page, used instead of `page`
+  cursor: Option<String>,
+  /// The `cursor` of one of the `more_replies`, used instead of `parent_id`
+  replies_cursor: Option<String>,
   auth: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetCommentsResponse {
   comments: Vec<CommentView>,
+  next_cursor: Option<String>,
+  /// For the listings of a thread, which replies were left out
+  more_replies: Vec<MoreReplies>,
+  /// The comments with a keyword you muted, to show collapsed
+  collapsed_comment_ids: Vec<i32>,
+}
+
+/// Replies a listing of a thread left out, like "load 57 more replies".
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct MoreReplies {
+  /// Missing for the top level comments of the post
+  parent_id: Option<i32>,
+  /// With all the replies below them
+  count: i64,
+  /// The `replies_cursor` of `GetComments` that lists them
+  cursor: String,
+}
+
+/// The replies to load below a comment, or the top level comments. The ones below it that the
+/// client already has aren't listed again.
+#[derive(Serialize, Deserialize)]
+struct RepliesCursor {
+  post_id: i32,
+  parent_id: Option<i32>,
+  seen: Vec<i32>,
+}
+
+/// What a limited listing of the thread below `root`, or of the whole post, left out. The `seen`
+/// replies to `root` were listed before this one.
+pub fn more_replies(
+  conn: &diesel::PgConnection,
+  post_id: i32,
+  comments: &[CommentView],
+  root: Option<i32>,
+  seen: Vec<i32>,
+) -> Result<Vec<MoreReplies>, LemmyError> {
+  let shown = comments.iter().map(|c| c.id).collect::<Vec<i32>>();
+  let hidden = CommentView::hidden_replies(conn, post_id, shown, seen.clone(), root.is_none())?;
+
+  hidden
+    .into_iter()
+    .map(|h| {
+      let mut seen_replies = comments
+        .iter()
+        .filter(|c| c.parent_id == h.parent_id)
+        .map(|c| c.id)
+        .collect::<Vec<i32>>();
+      if h.parent_id == root {
+        seen_replies.extend(&seen);
+      }
+      let cursor = encode_token(&RepliesCursor {
+        post_id,
+        parent_id: h.parent_id,
+        seen: seen_replies,
+      })?;
+      Ok(MoreReplies {
+        parent_id: h.parent_id,
+        count: h.count,
+        cursor,
+      })
+    })
+    .collect()
 }
 
 #[async_trait::async_trait(?Send)]
@@ -117,10 +239,15 @@ impl Perform for Oper<CreateComment> {
 
     let user_id = claims.id;
 
-    let content_slurs_removed = remove_slurs(&data.content.to_owned());
+    // Check for a community ban
+    let post_id = data.post_id;
+    let post = blocking(pool, move |conn| Post::read(conn, post_id)).await??;
+
+    let community_id = post.community_id;
+    let content = check_words(&data.content, community_id)?;
 
-    let comment_form = CommentForm {
-      content: content_slurs_removed,
+    let mut comment_form = CommentForm {
+      content: content.text.to_owned(),
       parent_id: data.parent_id.to_owned(),
       post_id: data.post_id,
       creator_id: user_id,
@@ -133,23 +260,48 @@ impl Perform for Oper<CreateComment> {
       local: true,
     };
 
-    // Check for a community ban
-    let post_id = data.post_id;
-    let post = blocking(pool, move |conn| Post::read(conn, post_id)).await??;
-
-    let community_id = post.community_id;
     let is_banned =
       move |conn: &'_ _| CommunityUserBanView::get(conn, user_id, community_id).is_ok();
     if blocking(pool, is_banned).await? {
       return Err(APIError::err("community_ban").into());
     }
 
+    // Check if the post is locked, which archived posts are too
+    if post.locked {
+      return Err(APIError::err("locked").into());
+    }
+
+    // Nobody can reply until a mod lets the post through
+    if post.pending {
+      return Err(APIError::err("post_pending_approval").into());
+    }
+
+    let community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
+    check_community_not_read_only(pool, user_id, &community).await?;
+
     // Check for a site ban
     let user = blocking(pool, move |conn| User_::read(&conn, user_id)).await??;
-    if user.banned {
+    if user.is_banned() {
       return Err(APIError::err("site_ban").into());
     }
 
+    // Check that the user has verified their email, if the site requires it
+    if let Ok(site) = blocking(pool, move |conn| SiteView::read(conn)).await? {
+      if site.require_email_verification && !user.email_verified && !user.admin {
+        return Err(APIError::err("email_not_verified").into());
+      }
+    }
+
+    let subject = AutomodSubject {
+      title: None,
+      body: Some(&comment_form.content),
+      url: None,
+    };
+    let automod = run_automod(pool, community_id, user_id, &subject).await?;
+    if automod.removes() {
+      comment_form.removed = Some(true);
+    }
+
     let comment_form2 = comment_form.clone();
     let inserted_comment =
       match blocking(pool, move |conn| Comment::create(&conn, &comment_form2)).await? {
@@ -169,14 +321,43 @@ impl Perform for Oper<CreateComment> {
       Err(_e) => return Err(APIError::err("couldnt_create_comment").into()),
     };
 
+    automod
+      .send_hit_to_mods(
+        pool,
+        &websocket_info,
+        community_id,
+        None,
+        Some(inserted_comment_id),
+      )
+      .await?;
+    automod
+      .report_comment(pool, &websocket_info, inserted_comment_id)
+      .await?;
+    report_flagged_comment(pool, &websocket_info, inserted_comment_id, &content.flagged).await?;
+
     updated_comment
       .send_create(&user, &self.client, pool)
       .await?;
 
     // Scan the comment for user mentions, add those rows
     let mentions = scrape_text_for_mentions(&comment_form.content);
-    let recipient_ids =
-      send_local_notifs(mentions, updated_comment.clone(), user.clone(), post, pool).await?;
+    let mut recipient_ids = send_local_notifs(
+      mentions,
+      updated_comment.clone(),
+      user.clone(),
+      post.clone(),
+      pool,
+    )
+    .await?;
+    let watcher_ids = send_watched_post_notifs(
+      updated_comment.clone(),
+      user.clone(),
+      post,
+      recipient_ids.clone(),
+      pool,
+    )
+    .await?;
+    recipient_ids.extend(watcher_ids);
 
     // You like your own comment by default
     let like_form = CommentLikeForm {
@@ -203,12 +384,16 @@ impl Perform for Oper<CreateComment> {
       recipient_ids,
     };
 
-    if let Some(ws) = websocket_info {
+    // Comments that automod took down, or from shadowbanned users, don't go out live
+    if let Some(ws) = websocket_info.filter(|_| !updated_comment.removed && !user.shadow_banned) {
       ws.chatserver.do_send(SendComment {
         op: UserOperation::CreateComment,
         comment: res.clone(),
         my_id: ws.id,
       });
+      ws.chatserver.do_send(SendUnreadCounts {
+        user_ids: res.recipient_ids.clone(),
+      });
 
       // strip out the recipient_ids, so that
       // users don't get double notifs
@@ -279,9 +464,13 @@ impl Perform for Oper<EditComment> {
       }
 
       // Check for a site ban
-      if user.banned {
+      if user.is_banned() {
         return Err(APIError::err("site_ban").into());
       }
+
+      if data.removed == Some(true) && moderators.contains(&user_id) {
+        check_removal_reason(pool, &data.reason).await?;
+      }
     } else {
       // check that user can mark as read
       let parent_id = orig_comment.parent_id;
@@ -303,13 +492,17 @@ impl Perform for Oper<EditComment> {
       }
     }
 
-    let content_slurs_removed = remove_slurs(&data.content.to_owned());
+    // Marking as read leaves the content alone
+    let content = match data.read {
+      None => Some(check_words(&data.content, orig_comment.community_id)?),
+      Some(_) => None,
+    };
 
     let edit_id = data.edit_id;
     let read_comment = blocking(pool, move |conn| Comment::read(conn, edit_id)).await??;
 
     let comment_form = {
-      if data.read.is_none() {
+      if let Some(content) = &content {
         // the ban etc checks should been made and have passed
         // the comment can be properly edited
         let post_removed = if moderators.contains(&user_id) {
@@ -319,7 +512,7 @@ impl Perform for Oper<EditComment> {
         };
 
         CommentForm {
-          content: content_slurs_removed,
+          content: content.text.to_owned(),
           parent_id: read_comment.parent_id,
           post_id: read_comment.post_id,
           creator_id: read_comment.creator_id,
@@ -360,6 +553,10 @@ impl Perform for Oper<EditComment> {
       Err(_e) => return Err(APIError::err("couldnt_update_comment").into()),
     };
 
+    if let Some(content) = &content {
+      report_flagged_comment(pool, &websocket_info, edit_id, &content.flagged).await?;
+    }
+
     if data.read.is_none() {
       if let Some(deleted) = data.deleted.to_owned() {
         if deleted {
@@ -375,7 +572,7 @@ impl Perform for Oper<EditComment> {
         if moderators.contains(&user_id) {
           if removed {
             updated_comment
-              .send_remove(&user, &self.client, pool)
+              .send_remove(&user, data.reason.to_owned(), &self.client, pool)
               .await?;
           } else {
             updated_comment
@@ -406,6 +603,23 @@ impl Perform for Oper<EditComment> {
     let post_id = data.post_id;
     let post = blocking(pool, move |conn| Post::read(conn, post_id)).await??;
 
+    if data.read.is_none() && data.removed == Some(true) && moderators.contains(&user_id) {
+      let action = format!(
+        "Your comment on [{}](/post/{}/comment/{}) was removed.",
+        post.name, post.id, data.edit_id
+      );
+      send_mod_action_notice(
+        pool,
+        &self.client,
+        websocket_info.clone(),
+        &user,
+        read_comment.creator_id,
+        &action,
+        &data.reason,
+      )
+      .await?;
+    }
+
     let mentions = scrape_text_for_mentions(&comment_form.content);
     let recipient_ids = send_local_notifs(mentions, updated_comment, user, post, pool).await?;
 
@@ -420,6 +634,17 @@ impl Perform for Oper<EditComment> {
       recipient_ids,
     };
 
+    // Let the creator know when a mod removes or restores their comment
+    let creator_id = read_comment.creator_id;
+    let notify_creator = data.read.is_none()
+      && data.removed.is_some()
+      && moderators.contains(&user_id)
+      && creator_id != user_id
+      && blocking(pool, move |conn| {
+        wants_notification(conn, creator_id, |s| s.mod_actions)
+      })
+      .await?;
+
     if let Some(ws) = websocket_info {
       ws.chatserver.do_send(SendComment {
         op: UserOperation::EditComment,
@@ -427,6 +652,28 @@ impl Perform for Oper<EditComment> {
         my_id: ws.id,
       });
 
+      // Marking a reply as read changes the reader's own count
+      let mut unread_user_ids = res.recipient_ids.clone();
+      if data.read.is_some() {
+        unread_user_ids.push(user_id);
+      }
+      ws.chatserver.do_send(SendUnreadCounts {
+        user_ids: unread_user_ids,
+      });
+
+      if notify_creator {
+        let mut creator_res = res.clone();
+        creator_res.comment.my_vote = None;
+        creator_res.comment.user_id = None;
+        creator_res.recipient_ids = Vec::new();
+        ws.chatserver.do_send(SendUserRoomMessage {
+          op: UserOperation::EditComment,
+          response: creator_res,
+          recipient_id: creator_id,
+          my_id: ws.id,
+        });
+      }
+
       // strip out the recipient_ids, so that
       // users don't get double notifs
       res.recipient_ids = Vec::new();
@@ -484,6 +731,111 @@ impl Perform for Oper<SaveComment> {
   }
 }
 
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<PinComment> {
+  type Response = CommentResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<CommentResponse, LemmyError> {
+    let data: &PinComment = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let user = blocking(pool, move |conn| User_::read(&conn, user_id)).await??;
+
+    let comment_id = data.comment_id;
+    let orig_comment =
+      blocking(pool, move |conn| CommentView::read(&conn, comment_id, None)).await??;
+
+    // Only top level comments can be pinned
+    if orig_comment.parent_id.is_some() {
+      return Err(APIError::err("cant_pin_reply").into());
+    }
+
+    // Verify its the post creator or a mod, or an admin
+    let post_id = orig_comment.post_id;
+    let post = blocking(pool, move |conn| Post::read(conn, post_id)).await??;
+
+    let mut pinners: Vec<i32> = vec![post.creator_id];
+    let community_id = orig_comment.community_id;
+    pinners.append(
+      &mut blocking(pool, move |conn| {
+        CommunityModeratorView::for_community(&conn, community_id)
+          .map(|v| v.into_iter().map(|m| m.user_id).collect())
+      })
+      .await??,
+    );
+    pinners.append(
+      &mut blocking(pool, move |conn| {
+        UserView::admins(conn).map(|v| v.into_iter().map(|a| a.id).collect())
+      })
+      .await??,
+    );
+
+    if !pinners.contains(&user_id) {
+      return Err(APIError::err("no_comment_pin_allowed").into());
+    }
+
+    // Check for a community ban
+    let is_banned =
+      move |conn: &'_ _| CommunityUserBanView::get(conn, user_id, community_id).is_ok();
+    if blocking(pool, is_banned).await? {
+      return Err(APIError::err("community_ban").into());
+    }
+
+    // Check for a site ban
+    if user.is_banned() {
+      return Err(APIError::err("site_ban").into());
+    }
+
+    let pinned = data.pinned;
+    let updated_comment = match blocking(pool, move |conn| {
+      Comment::update_pinned(conn, comment_id, pinned)
+    })
+    .await?
+    {
+      Ok(comment) => comment,
+      Err(_e) => return Err(APIError::err("couldnt_update_comment").into()),
+    };
+
+    if pinned {
+      updated_comment.send_pin(&user, &self.client, pool).await?;
+    } else {
+      updated_comment
+        .send_undo_pin(&user, &self.client, pool)
+        .await?;
+    }
+
+    let comment_view = blocking(pool, move |conn| {
+      CommentView::read(conn, comment_id, Some(user_id))
+    })
+    .await??;
+
+    let res = CommentResponse {
+      comment: comment_view,
+      recipient_ids: Vec::new(),
+    };
+
+    if let Some(ws) = websocket_info {
+      ws.chatserver.do_send(SendComment {
+        op: UserOperation::PinComment,
+        comment: res.clone(),
+        my_id: ws.id,
+      });
+    }
+
+    Ok(res)
+  }
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for Oper<CreateCommentLike> {
   type Response = CommentResponse;
@@ -524,7 +876,7 @@ impl Perform for Oper<CreateCommentLike> {
 
     // Check for a site ban
     let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
-    if user.banned {
+    if user.is_banned() {
       return Err(APIError::err("site_ban").into());
     }
 
@@ -629,18 +981,40 @@ impl Perform for Oper<GetComments> {
       None => None,
     };
 
+    let hide_bot_accounts = match &user_claims {
+      Some(claims) => claims.hide_bot_accounts,
+      None => false,
+    };
+
     let type_ = ListingType::from_str(&data.type_)?;
     let sort = SortType::from_str(&data.sort)?;
 
     let community_id = data.community_id;
+    let parent_id = data.parent_id;
+    let max_depth = data.max_depth;
     let page = data.page;
     let limit = data.limit;
-    let comments = blocking(pool, move |conn| {
+    let cursor = decode_cursor(&data.cursor)?;
+    let replies_cursor = decode_token::<RepliesCursor>(&data.replies_cursor)?;
+    let thread = replies_cursor.is_some() || parent_id.is_some();
+    let (post_id, parent_id, seen) = match replies_cursor {
+      Some(r) => (Some(r.post_id), r.parent_id, r.seen),
+      None => (None, parent_id, Vec::new()),
+    };
+    let excluded_threads = seen.clone();
+    let comments = blocking_read(pool, move |conn| {
       CommentQueryBuilder::create(conn)
         .listing_type(type_)
         .sort(&sort)
         .for_community_id(community_id)
+        .for_post_id(post_id)
+        .for_parent_id(parent_id)
+        .max_depth(max_depth)
+        .breadth_first(thread)
+        .excluded_threads(excluded_threads)
+        .hide_bot_accounts(hide_bot_accounts)
         .my_user_id(user_id)
+        .cursor(cursor)
         .page(page)
         .limit(limit)
         .list()
@@ -665,7 +1039,74 @@ impl Perform for Oper<GetComments> {
       }
     }
 
-    Ok(GetCommentsResponse { comments })
+    // The threads go on with the more replies instead
+    let (next_cursor, more_replies) = match comments.first().map(|c| c.post_id) {
+      Some(post_id) if thread => {
+        let shown = comments.clone();
+        let more_replies = blocking_read(pool, move |conn| {
+          more_replies(conn, post_id, &shown, parent_id, seen)
+        })
+        .await??;
+        (None, more_replies)
+      }
+      _ => {
+        let next_cursor = next_cursor(
+          comments.last().map(CommentView::cursor),
+          comments.len(),
+          limit,
+        )?;
+        (next_cursor, Vec::new())
+      }
+    };
+
+    let comment_ids = comments.iter().map(|c| c.id).collect();
+    let collapsed_comment_ids = collapsed_comment_ids(pool, user_id, comment_ids).await?;
+
+    Ok(GetCommentsResponse {
+      comments,
+      next_cursor,
+      more_replies,
+      collapsed_comment_ids,
+    })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetCommentRevisions> {
+  type Response = GetCommentRevisionsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<GetCommentRevisionsResponse, LemmyError> {
+    let data: &GetCommentRevisions = &self.data;
+
+    let user_id: Option<i32> = match &data.auth {
+      Some(auth) => match Claims::decode(&auth) {
+        Ok(claims) => Some(claims.claims.id),
+        Err(_e) => None,
+      },
+      None => None,
+    };
+
+    let comment_id = data.comment_id;
+    let comment =
+      match blocking(pool, move |conn| CommentView::read(conn, comment_id, None)).await? {
+        Ok(comment) => comment,
+        Err(_e) => return Err(APIError::err("couldnt_find_comment").into()),
+      };
+
+    if !can_view_edit_history(pool, comment.creator_id, comment.community_id, user_id).await? {
+      return Err(APIError::err("no_edit_history_allowed").into());
+    }
+
+    let revisions = blocking(pool, move |conn| {
+      CommentRevision::for_comment(conn, comment_id)
+    })
+    .await??;
+
+    Ok(GetCommentRevisionsResponse { revisions })
   }
 }
 
@@ -676,11 +1117,14 @@ pub async fn send_local_notifs(
   post: Post,
   pool: &DbPool,
 ) -> Result<Vec<i32>, LemmyError> {
-  let ids = blocking(pool, move |conn| {
+  let (ids, push_notifications) = blocking(pool, move |conn| {
     do_send_local_notifs(conn, &mentions, &comment, &user, &post)
   })
   .await?;
 
+  queue_matrix_notifications(pool, &push_notifications).await?;
+  queue_push_notifications(pool, push_notifications).await?;
+
   Ok(ids)
 }
 
@@ -690,9 +1134,16 @@ fn do_send_local_notifs(
   comment: &Comment,
   user: &User_,
   post: &Post,
-) -> Vec<i32> {
+) -> (Vec<i32>, Vec<PushNotification>) {
   let mut recipient_ids = Vec::new();
+  let mut push_notifications = Vec::new();
+
+  // Nobody hears from a shadowbanned user
+  if user.shadow_banned {
+    return (recipient_ids, push_notifications);
+  }
   let hostname = &format!("https://{}", Settings::get().hostname);
+  let inbox_url = &format!("{}/inbox", hostname);
 
   // Send the local mentions
   for mention in mentions
@@ -700,12 +1151,10 @@ fn do_send_local_notifs(
     .filter(|m| m.is_local() && m.name.ne(&user.name))
     .collect::<Vec<&MentionData>>()
   {
-    if let Ok(mention_user) = User_::read_from_name(&conn, &mention.name) {
+    if let Ok(mention_user) = UserAlias::read_user(&conn, &mention.name) {
       // TODO
       // At some point, make it so you can't tag the parent creator either
       // This can cause two notifications, one for reply and the other for mention
-      recipient_ids.push(mention_user.id);
-
       let user_mention_form = UserMentionForm {
         recipient_id: mention_user.id,
         comment_id: comment.id,
@@ -719,13 +1168,41 @@ fn do_send_local_notifs(
         Err(_e) => error!("{}", &_e),
       };
 
+      // The mention still shows up in their inbox, they just don't get notified
+      if !wants_notification(conn, mention_user.id, |s| s.mentions) {
+        continue;
+      }
+
+      recipient_ids.push(mention_user.id);
+      let langs = &user_langs(&mention_user.lang);
+      push_notifications.push(PushNotification::new(
+        mention_user.id,
+        &translate(
+          langs,
+          "notifications.mentioned_by",
+          &[("username", &user.name)],
+        ),
+        &comment.content,
+        inbox_url,
+      ));
+
       // Send an email to those users that have notifications on
       if mention_user.send_notifications_to_email {
         if let Some(mention_email) = mention_user.email {
-          let subject = &format!("{} - Mentioned by {}", Settings::get().hostname, user.name,);
-          let html = &format!(
-            "<h1>User Mention</h1><br><div>{} - {}</div><br><a href={}/inbox>inbox</a>",
-            user.name, comment.content, hostname
+          let subject = &translate(
+            langs,
+            "emails.mention_subject",
+            &[
+              ("site", &Settings::get().hostname),
+              ("username", &user.name),
+            ],
+          );
+          let html = &notification_email_html(
+            langs,
+            "emails.mention_title",
+            &user.name,
+            &comment.content,
+            hostname,
           );
           match send_email(subject, &mention_email, &mention_user.name, html) {
             Ok(_o) => _o,
@@ -740,16 +1217,39 @@ fn do_send_local_notifs(
   match comment.parent_id {
     Some(parent_id) => {
       if let Ok(parent_comment) = Comment::read(&conn, parent_id) {
-        if parent_comment.creator_id != user.id {
+        if parent_comment.creator_id != user.id
+          && wants_notification(conn, parent_comment.creator_id, |s| s.replies)
+        {
           if let Ok(parent_user) = User_::read(&conn, parent_comment.creator_id) {
             recipient_ids.push(parent_user.id);
+            let langs = &user_langs(&parent_user.lang);
+            push_notifications.push(PushNotification::new(
+              parent_user.id,
+              &translate(
+                langs,
+                "notifications.reply_from",
+                &[("username", &user.name)],
+              ),
+              &comment.content,
+              inbox_url,
+            ));
 
             if parent_user.send_notifications_to_email {
               if let Some(comment_reply_email) = parent_user.email {
-                let subject = &format!("{} - Reply from {}", Settings::get().hostname, user.name,);
-                let html = &format!(
-                  "<h1>Comment Reply</h1><br><div>{} - {}</div><br><a href={}/inbox>inbox</a>",
-                  user.name, comment.content, hostname
+                let subject = &translate(
+                  langs,
+                  "emails.comment_reply_subject",
+                  &[
+                    ("site", &Settings::get().hostname),
+                    ("username", &user.name),
+                  ],
+                );
+                let html = &notification_email_html(
+                  langs,
+                  "emails.comment_reply_title",
+                  &user.name,
+                  &comment.content,
+                  hostname,
                 );
                 match send_email(subject, &comment_reply_email, &parent_user.name, html) {
                   Ok(_o) => _o,
@@ -763,16 +1263,37 @@ fn do_send_local_notifs(
     }
     // Its a post
     None => {
-      if post.creator_id != user.id {
+      if post.creator_id != user.id && wants_notification(conn, post.creator_id, |s| s.replies) {
         if let Ok(parent_user) = User_::read(&conn, post.creator_id) {
           recipient_ids.push(parent_user.id);
+          let langs = &user_langs(&parent_user.lang);
+          push_notifications.push(PushNotification::new(
+            parent_user.id,
+            &translate(
+              langs,
+              "notifications.reply_from",
+              &[("username", &user.name)],
+            ),
+            &comment.content,
+            inbox_url,
+          ));
 
           if parent_user.send_notifications_to_email {
             if let Some(post_reply_email) = parent_user.email {
-              let subject = &format!("{} - Reply from {}", Settings::get().hostname, user.name,);
-              let html = &format!(
-                "<h1>Post Reply</h1><br><div>{} - {}</div><br><a href={}/inbox>inbox</a>",
-                user.name, comment.content, hostname
+              let subject = &translate(
+                langs,
+                "emails.comment_reply_subject",
+                &[
+                  ("site", &Settings::get().hostname),
+                  ("username", &user.name),
+                ],
+              );
+              let html = &notification_email_html(
+                langs,
+                "emails.post_reply_title",
+                &user.name,
+                &comment.content,
+                hostname,
               );
               match send_email(subject, &post_reply_email, &parent_user.name, html) {
                 Ok(_o) => _o,
@@ -784,5 +1305,125 @@ fn do_send_local_notifs(
       }
     }
   };
-  recipient_ids
+  (recipient_ids, push_notifications)
+}
+
+/// Tells the watchers of the post about a new comment in it, except for those `send_local_notifs`
+/// already told. Returns who got told.
+pub async fn send_watched_post_notifs(
+  comment: Comment,
+  user: User_,
+  post: Post,
+  notified: Vec<i32>,
+  pool: &DbPool,
+) -> Result<Vec<i32>, LemmyError> {
+  let (ids, push_notifications) = blocking(pool, move |conn| {
+    do_send_watched_post_notifs(conn, &comment, &user, &post, &notified)
+  })
+  .await?;
+
+  queue_matrix_notifications(pool, &push_notifications).await?;
+  queue_push_notifications(pool, push_notifications).await?;
+
+  Ok(ids)
+}
+
+fn do_send_watched_post_notifs(
+  conn: &diesel::PgConnection,
+  comment: &Comment,
+  user: &User_,
+  post: &Post,
+  notified: &[i32],
+) -> (Vec<i32>, Vec<PushNotification>) {
+  let mut recipient_ids = Vec::new();
+  let mut push_notifications = Vec::new();
+
+  if user.shadow_banned {
+    return (recipient_ids, push_notifications);
+  }
+  let hostname = Settings::get().hostname;
+  let post_url = &format!("https://{}/post/{}", hostname, post.id);
+
+  let watcher_ids = PostSubscription::subscriber_ids(conn, post.id).unwrap_or_default();
+  for watcher_id in watcher_ids
+    .into_iter()
+    .filter(|id| *id != user.id && !notified.contains(id))
+  {
+    if !wants_notification(conn, watcher_id, |s| s.replies) {
+      continue;
+    }
+    let watcher = match User_::read(conn, watcher_id) {
+      Ok(watcher) => watcher,
+      Err(_e) => continue,
+    };
+
+    recipient_ids.push(watcher.id);
+    let langs = &user_langs(&watcher.lang);
+    push_notifications.push(PushNotification::new(
+      watcher.id,
+      &translate(
+        langs,
+        "notifications.watched_post_comment",
+        &[("username", &user.name), ("post", &post.name)],
+      ),
+      &comment.content,
+      post_url,
+    ));
+
+    if watcher.send_notifications_to_email {
+      if let Some(watcher_email) = watcher.email {
+        let subject = &translate(
+          langs,
+          "emails.watched_post_subject",
+          &[
+            ("site", &hostname),
+            ("username", &user.name),
+            ("post", &post.name),
+          ],
+        );
+        let html = &format!(
+          "<h1>{}</h1><br><div>{} - {}</div><br><a href={}>{}</a>",
+          translate(langs, "emails.watched_post_title", &[]),
+          user.name,
+          comment.content,
+          post_url,
+          translate(langs, "emails.watched_post_link", &[])
+        );
+        match send_email(subject, &watcher_email, &watcher.name, html) {
+          Ok(_o) => _o,
+          Err(e) => error!("{}", e),
+        };
+      }
+    }
+  }
+  (recipient_ids, push_notifications)
+}
+
+/// The email about something that landed in the inbox, in the languages of whoever gets it.
+pub(crate) fn notification_email_html(
+  langs: &[String],
+  title_key: &str,
+  sender_name: &str,
+  content: &str,
+  hostname: &str,
+) -> String {
+  format!(
+    "<h1>{}</h1><br><div>{} - {}</div><br><a href={}/inbox>{}</a>",
+    translate(langs, title_key, &[]),
+    sender_name,
+    content,
+    hostname,
+    translate(langs, "emails.inbox", &[])
+  )
+}
+
+/// Checks one of the user's notification settings, notifying them if the settings can't be read.
+pub fn wants_notification(
+  conn: &diesel::PgConnection,
+  user_id: i32,
+  setting: fn(&UserNotificationSettings) -> bool,
+) -> bool {
+  UserNotificationSettings::read_for_user(conn, user_id)
+    .map(|settings| setting(&settings))
+    .unwrap_or(true)
 }
@@ -1,59 +1,90 @@
 use super::*;
 use crate::{
-  api::{claims::Claims, APIError, Oper, Perform},
+  api::{
+    claims::Claims,
+    comment::wants_notification,
+    user::{ban_notice, send_mod_action_notice},
+    APIError,
+    Oper,
+    Perform,
+  },
   apub::ActorType,
   blocking,
+  blocking_read,
   websocket::{
-    server::{JoinCommunityRoom, SendCommunityRoomMessage},
+    server::{
+      GetCommunityUsersOnline,
+      JoinCommunityRoom,
+      SendCommunityRoomMessage,
+      SendUserRoomMessage,
+    },
     UserOperation,
     WebsocketInfo,
   },
   DbPool,
 };
-use lemmy_db::{naive_now, Bannable, Crud, Followable, Joinable, SortType};
+use lemmy_db::{
+  community_flair::*,
+  community_rule::*,
+  naive_now,
+  Bannable,
+  Crud,
+  Followable,
+  Joinable,
+  SortType,
+};
 use lemmy_utils::{
   generate_actor_keypair,
   is_valid_community_name,
+  is_valid_language_code,
   make_apub_endpoint,
   naive_from_unix,
   slur_check,
   slurs_vec_to_str,
   EndpointType,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetCommunity {
   id: Option<i32>,
   pub name: Option<String>,
   auth: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetCommunityResponse {
   pub community: CommunityView,
   pub moderators: Vec<CommunityModeratorView>,
   pub admins: Vec<UserView>,
+  pub flairs: Vec<CommunityFlair>,
+  pub rules: Vec<CommunityRule>,
   pub online: usize,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct CreateCommunity {
-  name: String,
-  title: String,
-  description: Option<String>,
-  category_id: i32,
-  nsfw: bool,
-  auth: String,
+  pub name: String,
+  pub title: String,
+  pub description: Option<String>,
+  pub category_id: i32,
+  pub nsfw: Option<bool>,
+  pub icon: Option<String>,
+  pub banner: Option<String>,
+  pub allowed_post_types: Option<i16>,
+  pub default_sort_type: Option<i16>,
+  pub language: Option<String>,
+  pub auth: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct CommunityResponse {
   pub community: CommunityView,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct ListCommunities {
   pub sort: String,
   pub page: Option<i64>,
@@ -61,12 +92,12 @@ pub struct ListCommunities {
   pub auth: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct ListCommunitiesResponse {
   pub communities: Vec<CommunityView>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct BanFromCommunity {
   pub community_id: i32,
   user_id: i32,
@@ -76,13 +107,13 @@ pub struct BanFromCommunity {
   auth: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct BanFromCommunityResponse {
   user: UserView,
   banned: bool,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct AddModToCommunity {
   pub community_id: i32,
   user_id: i32,
@@ -90,12 +121,12 @@ pub struct AddModToCommunity {
   auth: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct AddModToCommunityResponse {
   moderators: Vec<CommunityModeratorView>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct EditCommunity {
   pub edit_id: i32,
   title: String,
@@ -104,35 +135,109 @@ pub struct EditCommunity {
   removed: Option<bool>,
   deleted: Option<bool>,
   nsfw: bool,
+  require_flair: Option<bool>,
+  icon: Option<String>,
+  banner: Option<String>,
+  archive_after_days: Option<i32>,
+  post_approval: Option<i16>,
+  read_only: Option<bool>,
+  allowed_post_types: Option<i16>,
+  default_sort_type: Option<i16>,
+  language: Option<String>,
   reason: Option<String>,
   expires: Option<i64>,
   auth: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct FollowCommunity {
-  community_id: i32,
-  follow: bool,
-  auth: String,
+  pub community_id: i32,
+  pub follow: bool,
+  pub auth: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetFollowedCommunities {
   auth: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetFollowedCommunitiesResponse {
   communities: Vec<CommunityFollowerView>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct TransferCommunity {
   community_id: i32,
   user_id: i32,
   auth: String,
 }
 
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ListCommunityFlairs {
+  community_id: i32,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CreateCommunityFlair {
+  community_id: i32,
+  name: String,
+  color: String,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct EditCommunityFlair {
+  edit_id: i32,
+  name: String,
+  color: String,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeleteCommunityFlair {
+  flair_id: i32,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ListCommunityFlairsResponse {
+  flairs: Vec<CommunityFlair>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ListCommunityRules {
+  community_id: i32,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CreateCommunityRule {
+  community_id: i32,
+  title: String,
+  description: Option<String>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct EditCommunityRule {
+  edit_id: i32,
+  title: String,
+  description: Option<String>,
+  position: i32,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeleteCommunityRule {
+  rule_id: i32,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ListCommunityRulesResponse {
+  rules: Vec<CommunityRule>,
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for Oper<GetCommunity> {
   type Response = GetCommunityResponse;
@@ -191,6 +296,17 @@ impl Perform for Oper<GetCommunity> {
     let creator_user = admins.remove(creator_index);
     admins.insert(0, creator_user);
 
+    let community_id = community.id;
+    let flairs = blocking(pool, move |conn| {
+      CommunityFlair::for_community(conn, community_id)
+    })
+    .await??;
+
+    let rules = blocking(pool, move |conn| {
+      CommunityRule::for_community(conn, community_id)
+    })
+    .await??;
+
     let online = if let Some(ws) = websocket_info {
       if let Some(id) = ws.id {
         ws.chatserver.do_send(JoinCommunityRoom {
@@ -199,12 +315,12 @@ impl Perform for Oper<GetCommunity> {
         });
       }
 
-      // TODO
-      1
-    // let fut = async {
-    //   ws.chatserver.send(GetCommunityUsersOnline {community_id}).await.unwrap()
-    // };
-    // Runtime::new().unwrap().block_on(fut)
+      // After the join, so that a logged in reader counts too
+      ws.chatserver
+        .send(GetCommunityUsersOnline {
+          community_id: community.id,
+        })
+        .await?
     } else {
       0
     };
@@ -213,6 +329,8 @@ impl Perform for Oper<GetCommunity> {
       community: community_view,
       moderators,
       admins,
+      flairs,
+      rules,
       online,
     };
 
@@ -255,11 +373,17 @@ impl Perform for Oper<CreateCommunity> {
       return Err(APIError::err("invalid_community_name").into());
     }
 
+    check_community_settings(
+      data.allowed_post_types,
+      data.default_sort_type,
+      &data.language,
+    )?;
+
     let user_id = claims.id;
 
     // Check for a site ban
-    let user_view = blocking(pool, move |conn| UserView::read(conn, user_id)).await??;
-    if user_view.banned {
+    let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+    if user.is_banned() {
       return Err(APIError::err("site_ban").into());
     }
 
@@ -274,6 +398,12 @@ impl Perform for Oper<CreateCommunity> {
       return Err(APIError::err("community_already_exists").into());
     }
 
+    // Whatever isn't picked comes from the site's defaults
+    let defaults = blocking(pool, move |conn| Site::read(conn, 1))
+      .await?
+      .map(|site| site.community_defaults())
+      .unwrap_or_default();
+
     // When you create a community, make sure the user becomes a moderator and a follower
     let keypair = generate_actor_keypair()?;
 
@@ -285,7 +415,7 @@ impl Perform for Oper<CreateCommunity> {
       creator_id: user_id,
       removed: None,
       deleted: None,
-      nsfw: data.nsfw,
+      nsfw: data.nsfw.unwrap_or(defaults.community_default_nsfw),
       updated: None,
       actor_id,
       local: true,
@@ -293,6 +423,26 @@ impl Perform for Oper<CreateCommunity> {
       public_key: Some(keypair.public_key),
       last_refreshed_at: None,
       published: None,
+      require_flair: None,
+      icon: data.icon.to_owned(),
+      banner: data.banner.to_owned(),
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: Some(
+        data
+          .allowed_post_types
+          .unwrap_or(defaults.community_default_post_types),
+      ),
+      default_sort_type: Some(
+        data
+          .default_sort_type
+          .unwrap_or(defaults.community_default_sort_type),
+      ),
+      language: data
+        .language
+        .to_owned()
+        .or(defaults.community_default_language),
     };
 
     let inserted_community =
@@ -353,6 +503,24 @@ impl Perform for Oper<EditCommunity> {
       }
     }
 
+    if let Some(archive_after_days) = data.archive_after_days {
+      if archive_after_days < 0 {
+        return Err(APIError::err("invalid_archive_age").into());
+      }
+    }
+
+    if let Some(post_approval) = data.post_approval {
+      if PostApprovalMode::from_i16(post_approval).is_none() {
+        return Err(APIError::err("invalid_post_approval").into());
+      }
+    }
+
+    check_community_settings(
+      data.allowed_post_types,
+      data.default_sort_type,
+      &data.language,
+    )?;
+
     let claims = match Claims::decode(&data.auth) {
       Ok(claims) => claims.claims,
       Err(_e) => return Err(APIError::err("not_logged_in").into()),
@@ -362,7 +530,7 @@ impl Perform for Oper<EditCommunity> {
 
     // Check for a site ban
     let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
-    if user.banned {
+    if user.is_banned() {
       return Err(APIError::err("site_ban").into());
     }
 
@@ -405,6 +573,15 @@ impl Perform for Oper<EditCommunity> {
       public_key: read_community.public_key,
       last_refreshed_at: None,
       published: None,
+      require_flair: data.require_flair.to_owned(),
+      icon: data.icon.to_owned(),
+      banner: data.banner.to_owned(),
+      archive_after_days: data.archive_after_days,
+      post_approval: data.post_approval,
+      read_only: data.read_only,
+      allowed_post_types: data.allowed_post_types,
+      default_sort_type: data.default_sort_type,
+      language: data.language.to_owned(),
     };
 
     let edit_id = data.edit_id;
@@ -453,6 +630,11 @@ impl Perform for Oper<EditCommunity> {
           .send_undo_remove(&user, &self.client, pool)
           .await?;
       }
+    } else if updated_community.local {
+      // The followers need to know who is allowed to post in it
+      updated_community
+        .send_update(&user, &self.client, pool)
+        .await?;
     }
 
     let edit_id = data.edit_id;
@@ -516,7 +698,7 @@ impl Perform for Oper<ListCommunities> {
 
     let page = data.page;
     let limit = data.limit;
-    let communities = blocking(pool, move |conn| {
+    let communities = blocking_read(pool, move |conn| {
       CommunityQueryBuilder::create(conn)
         .sort(&sort)
         .for_user(user_id)
@@ -676,9 +858,19 @@ impl Perform for Oper<BanFromCommunity> {
       return Err(APIError::err("couldnt_update_community").into());
     }
 
+    let expires = match data.expires {
+      Some(time) => Some(naive_from_unix(time)),
+      None => None,
+    };
+
+    if data.ban {
+      check_removal_reason(pool, &data.reason).await?;
+    }
+
     let community_user_ban_form = CommunityUserBanForm {
       community_id: data.community_id,
       user_id: data.user_id,
+      expires,
     };
 
     if data.ban {
@@ -694,11 +886,6 @@ impl Perform for Oper<BanFromCommunity> {
     }
 
     // Mod tables
-    let expires = match data.expires {
-      Some(time) => Some(naive_from_unix(time)),
-      None => None,
-    };
-
     let form = ModBanFromCommunityForm {
       mod_user_id: user_id,
       other_user_id: data.user_id,
@@ -717,6 +904,29 @@ impl Perform for Oper<BanFromCommunity> {
       banned: data.ban,
     };
 
+    if data.ban {
+      let mod_id = claims.id;
+      let mod_ = blocking(pool, move |conn| User_::read(conn, mod_id)).await??;
+      let community_id = data.community_id;
+      let community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
+      let action = ban_notice(&community.name, expires);
+      send_mod_action_notice(
+        pool,
+        &self.client,
+        websocket_info.clone(),
+        &mod_,
+        user_id,
+        &action,
+        &data.reason,
+      )
+      .await?;
+    }
+
+    let notify_banned = blocking(pool, move |conn| {
+      wants_notification(conn, user_id, |s| s.mod_actions)
+    })
+    .await?;
+
     if let Some(ws) = websocket_info {
       ws.chatserver.do_send(SendCommunityRoomMessage {
         op: UserOperation::BanFromCommunity,
@@ -724,6 +934,15 @@ impl Perform for Oper<BanFromCommunity> {
         community_id: data.community_id,
         my_id: ws.id,
       });
+
+      if notify_banned {
+        ws.chatserver.do_send(SendUserRoomMessage {
+          op: UserOperation::BanFromCommunity,
+          response: res.clone(),
+          recipient_id: user_id,
+          my_id: ws.id,
+        });
+      }
     }
 
     Ok(res)
@@ -824,7 +1043,7 @@ impl Perform for Oper<TransferCommunity> {
   async fn perform(
     &self,
     pool: &DbPool,
-    _websocket_info: Option<WebsocketInfo>,
+    websocket_info: Option<WebsocketInfo>,
   ) -> Result<GetCommunityResponse, LemmyError> {
     let data: &TransferCommunity = &self.data;
 
@@ -847,75 +1066,50 @@ impl Perform for Oper<TransferCommunity> {
     let creator_user = admins.remove(creator_index);
     admins.insert(0, creator_user);
 
-    // Make sure user is the creator, or an admin
-    if user_id != read_community.creator_id && !admins.iter().map(|a| a.id).any(|x| x == user_id) {
+    // The top mod hands the community to another mod. Admins can give it to anyone, for when the
+    // mods are gone.
+    let is_admin = admins.iter().any(|a| a.id == user_id);
+    if user_id != read_community.creator_id && !is_admin {
       return Err(APIError::err("not_an_admin").into());
     }
 
-    let community_form = CommunityForm {
-      name: read_community.name,
-      title: read_community.title,
-      description: read_community.description,
-      category_id: read_community.category_id,
-      creator_id: data.user_id, // This makes the new user the community creator
-      removed: None,
-      deleted: None,
-      nsfw: read_community.nsfw,
-      updated: Some(naive_now()),
-      actor_id: read_community.actor_id,
-      local: read_community.local,
-      private_key: read_community.private_key,
-      public_key: read_community.public_key,
-      last_refreshed_at: None,
-      published: None,
-    };
-
     let community_id = data.community_id;
-    let update = move |conn: &'_ _| Community::update(conn, community_id, &community_form);
-    if blocking(pool, update).await?.is_err() {
-      return Err(APIError::err("couldnt_update_community").into());
-    };
-
-    // You also have to re-do the community_moderator table, reordering it.
-    let community_id = data.community_id;
-    let mut community_mods = blocking(pool, move |conn| {
+    let new_creator_id = data.user_id;
+    let is_mod = blocking(pool, move |conn| {
       CommunityModeratorView::for_community(conn, community_id)
+        .map(|v| v.iter().any(|m| m.user_id == new_creator_id))
     })
     .await??;
-    let creator_index = community_mods
-      .iter()
-      .position(|r| r.user_id == data.user_id)
-      .unwrap();
-    let creator_user = community_mods.remove(creator_index);
-    community_mods.insert(0, creator_user);
-
-    let community_id = data.community_id;
-    blocking(pool, move |conn| {
-      CommunityModerator::delete_for_community(conn, community_id)
-    })
-    .await??;
-
-    // TODO: this should probably be a bulk operation
-    for cmod in &community_mods {
-      let community_moderator_form = CommunityModeratorForm {
-        community_id: cmod.community_id,
-        user_id: cmod.user_id,
-      };
-
-      let join = move |conn: &'_ _| CommunityModerator::join(conn, &community_moderator_form);
-      if blocking(pool, join).await?.is_err() {
-        return Err(APIError::err("community_moderator_already_exists").into());
-      }
+    if !is_mod && !is_admin {
+      return Err(APIError::err("not_a_moderator").into());
     }
 
-    // Mod tables
-    let form = ModAddCommunityForm {
-      mod_user_id: user_id,
-      other_user_id: data.user_id,
-      community_id: data.community_id,
-      removed: Some(false),
-    };
-    blocking(pool, move |conn| ModAddCommunity::create(conn, &form)).await??;
+    transfer_community(pool, user_id, community_id, new_creator_id).await?;
+
+    let mod_user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+    let community_name = format!("/c/{}", read_community.name);
+    send_mod_action_notice(
+      pool,
+      &self.client,
+      websocket_info.clone(),
+      &mod_user,
+      new_creator_id,
+      &format!("You are now the top mod of {}.", community_name),
+      &None,
+    )
+    .await?;
+    if read_community.creator_id != new_creator_id {
+      send_mod_action_notice(
+        pool,
+        &self.client,
+        websocket_info,
+        &mod_user,
+        read_community.creator_id,
+        &format!("{} was handed to a new top mod.", community_name),
+        &None,
+      )
+      .await?;
+    }
 
     let community_id = data.community_id;
     let community_view = match blocking(pool, move |conn| {
@@ -937,12 +1131,590 @@ impl Perform for Oper<TransferCommunity> {
       Err(_e) => return Err(APIError::err("couldnt_find_community").into()),
     };
 
+    let community_id = data.community_id;
+    let flairs = blocking(pool, move |conn| {
+      CommunityFlair::for_community(conn, community_id)
+    })
+    .await??;
+
+    let rules = blocking(pool, move |conn| {
+      CommunityRule::for_community(conn, community_id)
+    })
+    .await??;
+
     // Return the jwt
     Ok(GetCommunityResponse {
       community: community_view,
       moderators,
       admins,
+      flairs,
+      rules,
       online: 0,
     })
   }
 }
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ListCommunityFlairs> {
+  type Response = ListCommunityFlairsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListCommunityFlairsResponse, LemmyError> {
+    let data: &ListCommunityFlairs = &self.data;
+
+    let community_id = data.community_id;
+    let flairs = blocking(pool, move |conn| {
+      CommunityFlair::for_community(conn, community_id)
+    })
+    .await??;
+
+    Ok(ListCommunityFlairsResponse { flairs })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<CreateCommunityFlair> {
+  type Response = ListCommunityFlairsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListCommunityFlairsResponse, LemmyError> {
+    let data: &CreateCommunityFlair = &self.data;
+
+    check_valid_flair(&data.name, &data.color)?;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    check_community_mod_or_admin(pool, user_id, data.community_id).await?;
+
+    let flair_form = CommunityFlairForm {
+      community_id: data.community_id,
+      name: data.name.to_owned(),
+      color: data.color.to_owned(),
+    };
+
+    let create = move |conn: &'_ _| CommunityFlair::create(conn, &flair_form);
+    if blocking(pool, create).await?.is_err() {
+      return Err(APIError::err("community_flair_already_exists").into());
+    }
+
+    send_community_flairs(pool, websocket_info, data.community_id).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<EditCommunityFlair> {
+  type Response = ListCommunityFlairsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListCommunityFlairsResponse, LemmyError> {
+    let data: &EditCommunityFlair = &self.data;
+
+    check_valid_flair(&data.name, &data.color)?;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let edit_id = data.edit_id;
+    let read_flair = blocking(pool, move |conn| CommunityFlair::read(conn, edit_id)).await??;
+    check_community_mod_or_admin(pool, user_id, read_flair.community_id).await?;
+
+    let flair_form = CommunityFlairForm {
+      community_id: read_flair.community_id,
+      name: data.name.to_owned(),
+      color: data.color.to_owned(),
+    };
+
+    let update = move |conn: &'_ _| CommunityFlair::update(conn, edit_id, &flair_form);
+    if blocking(pool, update).await?.is_err() {
+      return Err(APIError::err("couldnt_update_community_flair").into());
+    }
+
+    send_community_flairs(pool, websocket_info, read_flair.community_id).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<DeleteCommunityFlair> {
+  type Response = ListCommunityFlairsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListCommunityFlairsResponse, LemmyError> {
+    let data: &DeleteCommunityFlair = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let flair_id = data.flair_id;
+    let read_flair = blocking(pool, move |conn| CommunityFlair::read(conn, flair_id)).await??;
+    check_community_mod_or_admin(pool, user_id, read_flair.community_id).await?;
+
+    // Deleting the flair cascades to the posts using it
+    let delete = move |conn: &'_ _| CommunityFlair::delete(conn, flair_id);
+    if blocking(pool, delete).await?.is_err() {
+      return Err(APIError::err("couldnt_update_community_flair").into());
+    }
+
+    send_community_flairs(pool, websocket_info, read_flair.community_id).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ListCommunityRules> {
+  type Response = ListCommunityRulesResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListCommunityRulesResponse, LemmyError> {
+    let data: &ListCommunityRules = &self.data;
+
+    let community_id = data.community_id;
+    let rules = blocking(pool, move |conn| {
+      CommunityRule::for_community(conn, community_id)
+    })
+    .await??;
+
+    Ok(ListCommunityRulesResponse { rules })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<CreateCommunityRule> {
+  type Response = ListCommunityRulesResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListCommunityRulesResponse, LemmyError> {
+    let data: &CreateCommunityRule = &self.data;
+
+    check_valid_rule(&data.title, &data.description)?;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    check_community_mod_or_admin(pool, user_id, data.community_id).await?;
+
+    // New rules go after the existing ones
+    let community_id = data.community_id;
+    let position = blocking(pool, move |conn| {
+      CommunityRule::next_position(conn, community_id)
+    })
+    .await??;
+
+    let rule_form = CommunityRuleForm {
+      community_id,
+      position,
+      title: data.title.trim().to_owned(),
+      description: data.description.to_owned(),
+      updated: None,
+    };
+
+    let create = move |conn: &'_ _| CommunityRule::create(conn, &rule_form);
+    if blocking(pool, create).await?.is_err() {
+      return Err(APIError::err("couldnt_create_community_rule").into());
+    }
+
+    send_community_rules(pool, websocket_info, community_id).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<EditCommunityRule> {
+  type Response = ListCommunityRulesResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListCommunityRulesResponse, LemmyError> {
+    let data: &EditCommunityRule = &self.data;
+
+    check_valid_rule(&data.title, &data.description)?;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let edit_id = data.edit_id;
+    let read_rule = blocking(pool, move |conn| CommunityRule::read(conn, edit_id)).await??;
+    check_community_mod_or_admin(pool, user_id, read_rule.community_id).await?;
+
+    let rule_form = CommunityRuleForm {
+      community_id: read_rule.community_id,
+      position: data.position,
+      title: data.title.trim().to_owned(),
+      description: data.description.to_owned(),
+      updated: Some(naive_now()),
+    };
+
+    let update = move |conn: &'_ _| CommunityRule::update(conn, edit_id, &rule_form);
+    if blocking(pool, update).await?.is_err() {
+      return Err(APIError::err("couldnt_update_community_rule").into());
+    }
+
+    send_community_rules(pool, websocket_info, read_rule.community_id).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<DeleteCommunityRule> {
+  type Response = ListCommunityRulesResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListCommunityRulesResponse, LemmyError> {
+    let data: &DeleteCommunityRule = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let rule_id = data.rule_id;
+    let read_rule = blocking(pool, move |conn| CommunityRule::read(conn, rule_id)).await??;
+    check_community_mod_or_admin(pool, user_id, read_rule.community_id).await?;
+
+    let delete = move |conn: &'_ _| CommunityRule::delete(conn, rule_id);
+    if blocking(pool, delete).await?.is_err() {
+      return Err(APIError::err("couldnt_update_community_rule").into());
+    }
+
+    send_community_rules(pool, websocket_info, read_rule.community_id).await
+  }
+}
+
+fn check_valid_flair(name: &str, color: &str) -> Result<(), LemmyError> {
+  if name.trim().is_empty() || name.chars().count() > 50 || color.chars().count() > 20 {
+    return Err(APIError::err("invalid_community_flair").into());
+  }
+
+  if let Err(slurs) = slur_check(name) {
+    return Err(APIError::err(&slurs_vec_to_str(slurs)).into());
+  }
+
+  Ok(())
+}
+
+fn check_valid_rule(title: &str, description: &Option<String>) -> Result<(), LemmyError> {
+  if title.trim().is_empty() || title.chars().count() > 100 {
+    return Err(APIError::err("invalid_community_rule").into());
+  }
+
+  if let Err(slurs) = slur_check(title) {
+    return Err(APIError::err(&slurs_vec_to_str(slurs)).into());
+  }
+
+  if let Some(description) = description {
+    if let Err(slurs) = slur_check(description) {
+      return Err(APIError::err(&slurs_vec_to_str(slurs)).into());
+    }
+  }
+
+  Ok(())
+}
+
+/// Makes the user the creator, and so the top mod, of the community. They're added as a mod if they
+/// weren't one, and the other mods keep their order.
+pub async fn transfer_community(
+  pool: &DbPool,
+  mod_user_id: i32,
+  community_id: i32,
+  new_creator_id: i32,
+) -> Result<(), LemmyError> {
+  let read_community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
+
+  let community_form = CommunityForm {
+    name: read_community.name,
+    title: read_community.title,
+    description: read_community.description,
+    category_id: read_community.category_id,
+    creator_id: new_creator_id, // This makes the new user the community creator
+    removed: None,
+    deleted: None,
+    nsfw: read_community.nsfw,
+    updated: Some(naive_now()),
+    actor_id: read_community.actor_id,
+    local: read_community.local,
+    private_key: read_community.private_key,
+    public_key: read_community.public_key,
+    last_refreshed_at: None,
+    published: None,
+    require_flair: None,
+    icon: None,
+    banner: None,
+    archive_after_days: None,
+    post_approval: None,
+    read_only: None,
+    allowed_post_types: None,
+    default_sort_type: None,
+    language: None,
+  };
+
+  let update = move |conn: &'_ _| Community::update(conn, community_id, &community_form);
+  if blocking(pool, update).await?.is_err() {
+    return Err(APIError::err("couldnt_update_community").into());
+  };
+
+  // You also have to re-do the community_moderator table, reordering it.
+  let community_mods = blocking(pool, move |conn| {
+    CommunityModeratorView::for_community(conn, community_id)
+  })
+  .await??;
+  let was_mod = community_mods.iter().any(|m| m.user_id == new_creator_id);
+  let mut mod_ids: Vec<i32> = community_mods
+    .iter()
+    .map(|m| m.user_id)
+    .filter(|id| *id != new_creator_id)
+    .collect();
+  mod_ids.insert(0, new_creator_id);
+
+  blocking(pool, move |conn| {
+    CommunityModerator::delete_for_community(conn, community_id)
+  })
+  .await??;
+
+  // TODO: this should probably be a bulk operation
+  for mod_id in mod_ids {
+    let community_moderator_form = CommunityModeratorForm {
+      community_id,
+      user_id: mod_id,
+    };
+
+    let join = move |conn: &'_ _| CommunityModerator::join(conn, &community_moderator_form);
+    if blocking(pool, join).await?.is_err() {
+      return Err(APIError::err("community_moderator_already_exists").into());
+    }
+  }
+
+  // Mod tables
+  if !was_mod {
+    let form = ModAddCommunityForm {
+      mod_user_id,
+      other_user_id: new_creator_id,
+      community_id,
+      removed: Some(false),
+    };
+    blocking(pool, move |conn| ModAddCommunity::create(conn, &form)).await??;
+  }
+
+  let form = ModTransferCommunityForm {
+    mod_user_id,
+    other_user_id: new_creator_id,
+    community_id,
+  };
+  blocking(pool, move |conn| ModTransferCommunity::create(conn, &form)).await??;
+
+  Ok(())
+}
+
+pub async fn check_community_mod_or_admin(
+  pool: &DbPool,
+  user_id: i32,
+  community_id: i32,
+) -> Result<(), LemmyError> {
+  let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+  if user.is_banned() {
+    return Err(APIError::err("site_ban").into());
+  }
+
+  let mut editors: Vec<i32> = Vec::new();
+  editors.append(
+    &mut blocking(pool, move |conn| {
+      CommunityModeratorView::for_community(conn, community_id)
+        .map(|v| v.into_iter().map(|m| m.user_id).collect())
+    })
+    .await??,
+  );
+  editors.append(
+    &mut blocking(pool, move |conn| {
+      UserView::admins(conn).map(|v| v.into_iter().map(|a| a.id).collect())
+    })
+    .await??,
+  );
+  if !editors.contains(&user_id) {
+    return Err(APIError::err("no_community_edit_allowed").into());
+  }
+
+  Ok(())
+}
+
+/// Sends a mod event live to the sessions of the community's moderators, so that the mod tools
+/// don't have to poll for it.
+pub async fn send_to_community_mods<Response>(
+  pool: &DbPool,
+  websocket_info: &Option<WebsocketInfo>,
+  community_id: i32,
+  op: UserOperation,
+  response: Response,
+) -> Result<(), LemmyError>
+where
+  Response: Serialize + Clone + Send + 'static,
+{
+  if let Some(ws) = websocket_info {
+    let moderators = blocking(pool, move |conn| {
+      CommunityModeratorView::for_community(conn, community_id)
+    })
+    .await??;
+
+    for moderator in moderators {
+      ws.chatserver.do_send(SendUserRoomMessage {
+        op: op.clone(),
+        response: response.clone(),
+        recipient_id: moderator.user_id,
+        my_id: ws.id,
+      });
+    }
+  }
+
+  Ok(())
+}
+
+/// In a read only community, only the mods and the admins can post and comment.
+pub async fn check_community_not_read_only(
+  pool: &DbPool,
+  user_id: i32,
+  community: &Community,
+) -> Result<(), LemmyError> {
+  if community.read_only
+    && check_community_mod_or_admin(pool, user_id, community.id)
+      .await
+      .is_err()
+  {
+    return Err(APIError::err("community_read_only").into());
+  }
+
+  Ok(())
+}
+
+/// Checks the post types, sort and language of a community, or of the site's defaults for them.
+pub fn check_community_settings(
+  allowed_post_types: Option<i16>,
+  default_sort_type: Option<i16>,
+  language: &Option<String>,
+) -> Result<(), LemmyError> {
+  if let Some(allowed_post_types) = allowed_post_types {
+    if AllowedPostTypes::from_i16(allowed_post_types).is_none() {
+      return Err(APIError::err("invalid_post_types").into());
+    }
+  }
+
+  if let Some(default_sort_type) = default_sort_type {
+    if !(0..=SortType::MostComments as i16).contains(&default_sort_type) {
+      return Err(APIError::err("invalid_sort_type").into());
+    }
+  }
+
+  if let Some(language) = language {
+    if !is_valid_language_code(language) {
+      return Err(APIError::err("invalid_language").into());
+    }
+  }
+
+  Ok(())
+}
+
+/// Sites can make mods say why they remove something or ban someone.
+pub async fn check_removal_reason(
+  pool: &DbPool,
+  reason: &Option<String>,
+) -> Result<(), LemmyError> {
+  if reason.as_ref().map_or(false, |r| !r.trim().is_empty()) {
+    return Ok(());
+  }
+
+  let require_reason = blocking(pool, move |conn| {
+    Site::read(conn, 1).map(|s| s.require_removal_reason)
+  })
+  .await?
+  .unwrap_or(false);
+  if require_reason {
+    return Err(APIError::err("removal_reason_required").into());
+  }
+
+  Ok(())
+}
+
+async fn send_community_flairs(
+  pool: &DbPool,
+  websocket_info: Option<WebsocketInfo>,
+  community_id: i32,
+) -> Result<ListCommunityFlairsResponse, LemmyError> {
+  let flairs = blocking(pool, move |conn| {
+    CommunityFlair::for_community(conn, community_id)
+  })
+  .await??;
+
+  let res = ListCommunityFlairsResponse { flairs };
+
+  if let Some(ws) = websocket_info {
+    ws.chatserver.do_send(SendCommunityRoomMessage {
+      op: UserOperation::ListCommunityFlairs,
+      response: res.clone(),
+      community_id,
+      my_id: ws.id,
+    });
+  }
+
+  Ok(res)
+}
+
+async fn send_community_rules(
+  pool: &DbPool,
+  websocket_info: Option<WebsocketInfo>,
+  community_id: i32,
+) -> Result<ListCommunityRulesResponse, LemmyError> {
+  let rules = blocking(pool, move |conn| {
+    CommunityRule::for_community(conn, community_id)
+  })
+  .await??;
+
+  let res = ListCommunityRulesResponse { rules };
+
+  if let Some(ws) = websocket_info {
+    ws.chatserver.do_send(SendCommunityRoomMessage {
+      op: UserOperation::ListCommunityRules,
+      response: res.clone(),
+      community_id,
+      my_id: ws.id,
+    });
+  }
+
+  Ok(res)
+}
@@ -0,0 +1,135 @@
+use crate::{
+  api::{check_admin, claims::Claims, community::CreateCommunity, APIError, Oper, Perform},
+  blocking,
+  community_import::parse_export,
+  job_queue::{enqueue, Task},
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::{community_import::*, Crud};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Creates a community and fills it with the posts and comments of `data` in the background. For
+/// `reddit` that's the submissions and comments of the subreddit, as a JSON array or one object
+/// per line like the Pushshift dumps. For `kbin` it's the entries of the magazine like its API
+/// lists them, with their comments.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ImportCommunity {
+  source: ImportSource,
+  data: String,
+  name: String,
+  title: String,
+  description: Option<String>,
+  category_id: i32,
+  nsfw: Option<bool>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetCommunityImport {
+  import_id: i32,
+  auth: String,
+}
+
+/// Also sent to the admin that started the import once it's done.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct CommunityImportResponse {
+  pub import: CommunityImport,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ImportCommunity> {
+  type Response = CommunityImportResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<CommunityImportResponse, LemmyError> {
+    let data: &ImportCommunity = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    check_admin(pool, user_id).await?;
+
+    // Finding out now beats an empty community
+    if parse_export(data.source, &data.data).is_err() {
+      return Err(APIError::err("invalid_import_data").into());
+    }
+
+    let create_community = CreateCommunity {
+      name: data.name.to_owned(),
+      title: data.title.to_owned(),
+      description: data.description.to_owned(),
+      category_id: data.category_id,
+      nsfw: data.nsfw,
+      icon: None,
+      banner: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
+      auth: data.auth.to_owned(),
+    };
+    let community = Oper::new(create_community, self.client.clone())
+      .perform(pool, websocket_info)
+      .await?
+      .community;
+
+    let import_form = CommunityImportForm {
+      community_id: community.id,
+      creator_id: user_id,
+      source: data.source.name().to_owned(),
+      data: Some(data.data.to_owned()),
+    };
+
+    let inserted_import = match blocking(pool, move |conn| {
+      CommunityImport::create(conn, &import_form)
+    })
+    .await?
+    {
+      Ok(import) => import,
+      Err(_e) => return Err(APIError::err("couldnt_create_import").into()),
+    };
+
+    let import_id = inserted_import.id;
+    enqueue(pool, Task::ImportCommunity { import_id }).await?;
+
+    Ok(CommunityImportResponse {
+      import: inserted_import,
+    })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetCommunityImport> {
+  type Response = CommunityImportResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<CommunityImportResponse, LemmyError> {
+    let data: &GetCommunityImport = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_admin(pool, claims.id).await?;
+
+    let import_id = data.import_id;
+    let import = match blocking(pool, move |conn| CommunityImport::read(conn, import_id)).await? {
+      Ok(import) => import,
+      Err(_e) => return Err(APIError::err("couldnt_find_import").into()),
+    };
+
+    Ok(CommunityImportResponse { import })
+  }
+}
@@ -0,0 +1,257 @@
+use crate::{
+  api::{
+    check_admin,
+    claims::Claims,
+    community::transfer_community,
+    user::send_mod_action_notice,
+    APIError,
+    Oper,
+    Perform,
+  },
+  blocking,
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::{
+  community::*,
+  community_takeover::*,
+  community_view::*,
+  naive_now,
+  site::*,
+  user::*,
+  Crud,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Asks the admins for a community. Only allowed once none of its mods have been active for the
+/// site's `mod_inactive_days`.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct RequestCommunityTakeover {
+  community_id: i32,
+  reason: Option<String>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ListCommunityTakeoverRequests {
+  auth: String,
+}
+
+/// Approving makes the requester the top mod of the community.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ResolveCommunityTakeoverRequest {
+  request_id: i32,
+  approved: bool,
+  reason: Option<String>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct CommunityTakeoverRequestResponse {
+  request: CommunityTakeoverRequest,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ListCommunityTakeoverRequestsResponse {
+  requests: Vec<CommunityTakeoverRequest>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<RequestCommunityTakeover> {
+  type Response = CommunityTakeoverRequestResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<CommunityTakeoverRequestResponse, LemmyError> {
+    let data: &RequestCommunityTakeover = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+    if user.is_banned() {
+      return Err(APIError::err("site_ban").into());
+    }
+
+    let community_id = data.community_id;
+    let is_banned =
+      move |conn: &'_ _| CommunityUserBanView::get(conn, user_id, community_id).is_ok();
+    if blocking(pool, is_banned).await? {
+      return Err(APIError::err("community_ban").into());
+    }
+
+    let read_community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
+    // A remote community is run by its own instance
+    if !read_community.local || read_community.removed || read_community.deleted {
+      return Err(APIError::err("couldnt_find_community").into());
+    }
+
+    let moderators = blocking(pool, move |conn| {
+      CommunityModeratorView::for_community(conn, community_id)
+    })
+    .await??;
+    if moderators.iter().any(|m| m.user_id == user_id) {
+      return Err(APIError::err("already_a_moderator").into());
+    }
+
+    let inactive_days = blocking(pool, move |conn| {
+      Site::read(conn, 1).map(|s| s.mod_inactive_days)
+    })
+    .await??;
+    let last_active = blocking(pool, move |conn| mods_last_active(conn, community_id)).await??;
+    let active_since = naive_now() - chrono::Duration::days(inactive_days.into());
+    if last_active.map_or(false, |l| l > active_since) {
+      return Err(APIError::err("community_mods_active").into());
+    }
+
+    let request_form = CommunityTakeoverRequestForm {
+      community_id,
+      creator_id: user_id,
+      reason: data.reason.to_owned(),
+    };
+
+    let create = move |conn: &'_ _| CommunityTakeoverRequest::create(conn, &request_form);
+    let request = match blocking(pool, create).await? {
+      Ok(request) => request,
+      Err(_e) => return Err(APIError::err("takeover_already_requested").into()),
+    };
+
+    // The mods can still speak up, by coming back
+    let action = format!(
+      "{} asked the admins to take over /c/{}, since none of its mods have been active for {} days.",
+      user.name, read_community.name, inactive_days
+    );
+    for moderator in &moderators {
+      send_mod_action_notice(
+        pool,
+        &self.client,
+        websocket_info.clone(),
+        &user,
+        moderator.user_id,
+        &action,
+        &data.reason,
+      )
+      .await?;
+    }
+
+    Ok(CommunityTakeoverRequestResponse { request })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ListCommunityTakeoverRequests> {
+  type Response = ListCommunityTakeoverRequestsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListCommunityTakeoverRequestsResponse, LemmyError> {
+    let data: &ListCommunityTakeoverRequests = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_admin(pool, claims.id).await?;
+
+    let requests = blocking(pool, move |conn| {
+      CommunityTakeoverRequest::list_pending(conn)
+    })
+    .await??;
+
+    Ok(ListCommunityTakeoverRequestsResponse { requests })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ResolveCommunityTakeoverRequest> {
+  type Response = CommunityTakeoverRequestResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<CommunityTakeoverRequestResponse, LemmyError> {
+    let data: &ResolveCommunityTakeoverRequest = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    check_admin(pool, user_id).await?;
+
+    let request_id = data.request_id;
+    let read_request = blocking(pool, move |conn| {
+      CommunityTakeoverRequest::read(conn, request_id)
+    })
+    .await??;
+    if TakeoverRequestStatus::from_i16(read_request.status) != Some(TakeoverRequestStatus::Pending)
+    {
+      return Err(APIError::err("takeover_request_not_pending").into());
+    }
+
+    let community_id = read_request.community_id;
+    let read_community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
+
+    let approved = data.approved;
+    if approved {
+      transfer_community(pool, user_id, community_id, read_request.creator_id).await?;
+    }
+
+    let status = if approved {
+      TakeoverRequestStatus::Approved
+    } else {
+      TakeoverRequestStatus::Rejected
+    };
+    let resolve =
+      move |conn: &'_ _| CommunityTakeoverRequest::resolve(conn, request_id, status, user_id);
+    let request = match blocking(pool, resolve).await? {
+      Ok(request) => request,
+      Err(_e) => return Err(APIError::err("couldnt_resolve_takeover_request").into()),
+    };
+
+    let admin = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+    let action = format!(
+      "Your request to take over /c/{} was {}.",
+      read_community.name,
+      if approved { "approved" } else { "rejected" }
+    );
+    send_mod_action_notice(
+      pool,
+      &self.client,
+      websocket_info.clone(),
+      &admin,
+      read_request.creator_id,
+      &action,
+      &data.reason,
+    )
+    .await?;
+
+    if approved {
+      send_mod_action_notice(
+        pool,
+        &self.client,
+        websocket_info,
+        &admin,
+        read_community.creator_id,
+        &format!("/c/{} was handed to a new top mod.", read_community.name),
+        &data.reason,
+      )
+      .await?;
+    }
+
+    Ok(CommunityTakeoverRequestResponse { request })
+  }
+}
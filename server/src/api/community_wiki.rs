@@ -0,0 +1,349 @@
+use crate::{
+  api::{claims::Claims, community::check_community_mod_or_admin, APIError, Oper, Perform},
+  blocking,
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::{community_view::*, community_wiki::*, naive_now, user::*, Crud};
+use lemmy_utils::{is_valid_wiki_slug, slur_check, slurs_vec_to_str};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The wiki content is markdown, same as post bodies, and clients render it the same way.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CreateWikiPage {
+  community_id: i32,
+  slug: String,
+  title: String,
+  content: String,
+  edit_permission: i16,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct EditWikiPage {
+  edit_id: i32,
+  title: String,
+  content: String,
+  edit_permission: Option<i16>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeleteWikiPage {
+  page_id: i32,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetWikiPage {
+  community_id: i32,
+  slug: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct WikiPageResponse {
+  page: CommunityWikiPage,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ListWikiPages {
+  community_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ListWikiPagesResponse {
+  pages: Vec<CommunityWikiPage>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetWikiPageRevisions {
+  page_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct GetWikiPageRevisionsResponse {
+  revisions: Vec<CommunityWikiPageRevision>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<CreateWikiPage> {
+  type Response = WikiPageResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<WikiPageResponse, LemmyError> {
+    let data: &CreateWikiPage = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    if !is_valid_wiki_slug(&data.slug) {
+      return Err(APIError::err("invalid_wiki_slug").into());
+    }
+
+    check_valid_wiki_page(&data.title, &data.content)?;
+
+    if WikiEditPermission::from_i16(data.edit_permission).is_none() {
+      return Err(APIError::err("invalid_wiki_edit_permission").into());
+    }
+
+    let user_id = claims.id;
+
+    check_community_mod_or_admin(pool, user_id, data.community_id).await?;
+
+    let page_form = CommunityWikiPageForm {
+      community_id: data.community_id,
+      slug: data.slug.to_owned(),
+      title: data.title.trim().to_owned(),
+      content: data.content.to_owned(),
+      edit_permission: data.edit_permission,
+      editor_id: Some(user_id),
+      updated: None,
+    };
+
+    let page = match blocking(pool, move |conn| {
+      CommunityWikiPage::create(conn, &page_form)
+    })
+    .await?
+    {
+      Ok(page) => page,
+      Err(_e) => return Err(APIError::err("wiki_page_already_exists").into()),
+    };
+
+    Ok(WikiPageResponse { page })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<EditWikiPage> {
+  type Response = WikiPageResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<WikiPageResponse, LemmyError> {
+    let data: &EditWikiPage = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_valid_wiki_page(&data.title, &data.content)?;
+
+    if let Some(edit_permission) = data.edit_permission {
+      if WikiEditPermission::from_i16(edit_permission).is_none() {
+        return Err(APIError::err("invalid_wiki_edit_permission").into());
+      }
+    }
+
+    let user_id = claims.id;
+
+    let edit_id = data.edit_id;
+    let read_page = match blocking(pool, move |conn| CommunityWikiPage::read(conn, edit_id)).await?
+    {
+      Ok(page) => page,
+      Err(_e) => return Err(APIError::err("couldnt_find_wiki_page").into()),
+    };
+
+    // Check for a site ban
+    let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+    if user.is_banned() {
+      return Err(APIError::err("site_ban").into());
+    }
+
+    // Check for a community ban
+    let community_id = read_page.community_id;
+    let is_banned =
+      move |conn: &'_ _| CommunityUserBanView::get(conn, user_id, community_id).is_ok();
+    if blocking(pool, is_banned).await? {
+      return Err(APIError::err("community_ban").into());
+    }
+
+    // Mods and admins can always edit, and they're the only ones who can change who else can
+    let is_mod_or_admin = check_community_mod_or_admin(pool, user_id, community_id)
+      .await
+      .is_ok();
+    if !is_mod_or_admin {
+      let can_edit = match WikiEditPermission::from_i16(read_page.edit_permission) {
+        Some(WikiEditPermission::Everyone) => true,
+        Some(WikiEditPermission::Subscribers) => {
+          let community = blocking(pool, move |conn| {
+            CommunityView::read(conn, community_id, Some(user_id))
+          })
+          .await??;
+          community.subscribed == Some(true)
+        }
+        _ => false,
+      };
+
+      let changes_permission = match data.edit_permission {
+        Some(edit_permission) => edit_permission != read_page.edit_permission,
+        None => false,
+      };
+
+      if !can_edit || changes_permission {
+        return Err(APIError::err("no_wiki_edit_allowed").into());
+      }
+    }
+
+    let page_form = CommunityWikiPageForm {
+      community_id,
+      slug: read_page.slug.to_owned(),
+      title: data.title.trim().to_owned(),
+      content: data.content.to_owned(),
+      edit_permission: data.edit_permission.unwrap_or(read_page.edit_permission),
+      editor_id: Some(user_id),
+      updated: Some(naive_now()),
+    };
+
+    let page = match blocking(pool, move |conn| {
+      CommunityWikiPage::update(conn, edit_id, &page_form)
+    })
+    .await?
+    {
+      Ok(page) => page,
+      Err(_e) => return Err(APIError::err("couldnt_update_wiki_page").into()),
+    };
+
+    Ok(WikiPageResponse { page })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<DeleteWikiPage> {
+  type Response = ListWikiPagesResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListWikiPagesResponse, LemmyError> {
+    let data: &DeleteWikiPage = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let page_id = data.page_id;
+    let read_page = match blocking(pool, move |conn| CommunityWikiPage::read(conn, page_id)).await?
+    {
+      Ok(page) => page,
+      Err(_e) => return Err(APIError::err("couldnt_find_wiki_page").into()),
+    };
+
+    let community_id = read_page.community_id;
+    check_community_mod_or_admin(pool, user_id, community_id).await?;
+
+    blocking(pool, move |conn| CommunityWikiPage::delete(conn, page_id)).await??;
+
+    let pages = blocking(pool, move |conn| {
+      CommunityWikiPage::for_community(conn, community_id)
+    })
+    .await??;
+
+    Ok(ListWikiPagesResponse { pages })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetWikiPage> {
+  type Response = WikiPageResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<WikiPageResponse, LemmyError> {
+    let data: &GetWikiPage = &self.data;
+
+    let community_id = data.community_id;
+    let slug = data.slug.to_owned();
+    let page = match blocking(pool, move |conn| {
+      CommunityWikiPage::read_from_slug(conn, community_id, &slug)
+    })
+    .await?
+    {
+      Ok(page) => page,
+      Err(_e) => return Err(APIError::err("couldnt_find_wiki_page").into()),
+    };
+
+    Ok(WikiPageResponse { page })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ListWikiPages> {
+  type Response = ListWikiPagesResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListWikiPagesResponse, LemmyError> {
+    let data: &ListWikiPages = &self.data;
+
+    let community_id = data.community_id;
+    let pages = blocking(pool, move |conn| {
+      CommunityWikiPage::for_community(conn, community_id)
+    })
+    .await??;
+
+    Ok(ListWikiPagesResponse { pages })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetWikiPageRevisions> {
+  type Response = GetWikiPageRevisionsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<GetWikiPageRevisionsResponse, LemmyError> {
+    let data: &GetWikiPageRevisions = &self.data;
+
+    let page_id = data.page_id;
+    if blocking(pool, move |conn| CommunityWikiPage::read(conn, page_id))
+      .await?
+      .is_err()
+    {
+      return Err(APIError::err("couldnt_find_wiki_page").into());
+    }
+
+    let revisions = blocking(pool, move |conn| {
+      CommunityWikiPageRevision::for_page(conn, page_id)
+    })
+    .await??;
+
+    Ok(GetWikiPageRevisionsResponse { revisions })
+  }
+}
+
+fn check_valid_wiki_page(title: &str, content: &str) -> Result<(), LemmyError> {
+  let title_len = title.trim().chars().count();
+  if title_len == 0 || title_len > 200 {
+    return Err(APIError::err("invalid_wiki_page_title").into());
+  }
+
+  if let Err(slurs) = slur_check(title) {
+    return Err(APIError::err(&slurs_vec_to_str(slurs)).into());
+  }
+
+  if let Err(slurs) = slur_check(content) {
+    return Err(APIError::err(&slurs_vec_to_str(slurs)).into());
+  }
+
+  Ok(())
+}
@@ -0,0 +1,366 @@
+use crate::{
+  api::{claims::Claims, decode_cursor, post::GetPostsResponse, APIError, Oper, Perform},
+  blocking,
+  blocking_read,
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use diesel::{result::Error, Connection};
+use lemmy_db::{
+  community_view::*,
+  custom_feed::*,
+  naive_now,
+  post_view::*,
+  Crud,
+  ListingType,
+  SortType,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// The most communities a single feed can group
+const MAX_FEED_COMMUNITIES: usize = 100;
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CreateCustomFeed {
+  name: String,
+  community_ids: Vec<i32>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct EditCustomFeed {
+  edit_id: i32,
+  name: String,
+  community_ids: Vec<i32>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeleteCustomFeed {
+  feed_id: i32,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetCustomFeed {
+  id: i32,
+  auth: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct CustomFeedResponse {
+  feed: CustomFeed,
+  communities: Vec<CommunityView>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ListCustomFeeds {
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ListCustomFeedsResponse {
+  feeds: Vec<CustomFeed>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetCustomFeedPosts {
+  feed_id: i32,
+  sort: String,
+  page: Option<i64>,
+  limit: Option<i64>,
+  cursor: Option<String>,
+  auth: Option<String>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<CreateCustomFeed> {
+  type Response = CustomFeedResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<CustomFeedResponse, LemmyError> {
+    let data: &CreateCustomFeed = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_valid_feed(&data.name, &data.community_ids)?;
+
+    let user_id = claims.id;
+
+    let feed_form = CustomFeedForm {
+      creator_id: user_id,
+      name: data.name.trim().to_owned(),
+      updated: None,
+    };
+
+    // A bad community leaves no feed behind, so the same name can be tried again
+    let community_ids = data.community_ids.to_owned();
+    let inserted_feed = blocking(pool, move |conn| {
+      let mut err_type = "custom_feed_already_exists";
+      conn
+        .transaction::<_, Error, _>(|| {
+          let feed = CustomFeed::create(conn, &feed_form)?;
+          err_type = "couldnt_find_community";
+          CustomFeedCommunity::set_communities(conn, feed.id, &community_ids)?;
+          Ok(feed)
+        })
+        .map_err(|_| err_type)
+    })
+    .await?;
+    let inserted_feed = match inserted_feed {
+      Ok(feed) => feed,
+      Err(err_type) => return Err(APIError::err(err_type).into()),
+    };
+
+    read_feed_response(pool, inserted_feed, Some(user_id)).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<EditCustomFeed> {
+  type Response = CustomFeedResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<CustomFeedResponse, LemmyError> {
+    let data: &EditCustomFeed = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_valid_feed(&data.name, &data.community_ids)?;
+
+    let user_id = claims.id;
+
+    let edit_id = data.edit_id;
+    let read_feed = blocking(pool, move |conn| CustomFeed::read(conn, edit_id)).await??;
+    if read_feed.creator_id != user_id {
+      return Err(APIError::err("no_custom_feed_edit_allowed").into());
+    }
+
+    let feed_form = CustomFeedForm {
+      creator_id: user_id,
+      name: data.name.trim().to_owned(),
+      updated: Some(naive_now()),
+    };
+
+    // Either the rename and the communities both go through, or neither does
+    let community_ids = data.community_ids.to_owned();
+    let updated_feed = blocking(pool, move |conn| {
+      let mut err_type = "custom_feed_already_exists";
+      conn
+        .transaction::<_, Error, _>(|| {
+          let feed = CustomFeed::update(conn, edit_id, &feed_form)?;
+          err_type = "couldnt_find_community";
+          CustomFeedCommunity::set_communities(conn, edit_id, &community_ids)?;
+          Ok(feed)
+        })
+        .map_err(|_| err_type)
+    })
+    .await?;
+    let updated_feed = match updated_feed {
+      Ok(feed) => feed,
+      Err(err_type) => return Err(APIError::err(err_type).into()),
+    };
+
+    read_feed_response(pool, updated_feed, Some(user_id)).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<DeleteCustomFeed> {
+  type Response = ListCustomFeedsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListCustomFeedsResponse, LemmyError> {
+    let data: &DeleteCustomFeed = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let feed_id = data.feed_id;
+    let read_feed = blocking(pool, move |conn| CustomFeed::read(conn, feed_id)).await??;
+    if read_feed.creator_id != user_id {
+      return Err(APIError::err("no_custom_feed_edit_allowed").into());
+    }
+
+    blocking(pool, move |conn| CustomFeed::delete(conn, feed_id)).await??;
+
+    let feeds = blocking(pool, move |conn| CustomFeed::for_creator(conn, user_id)).await??;
+
+    Ok(ListCustomFeedsResponse { feeds })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ListCustomFeeds> {
+  type Response = ListCustomFeedsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListCustomFeedsResponse, LemmyError> {
+    let data: &ListCustomFeeds = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    let feeds = blocking(pool, move |conn| CustomFeed::for_creator(conn, user_id)).await??;
+
+    Ok(ListCustomFeedsResponse { feeds })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetCustomFeed> {
+  type Response = CustomFeedResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<CustomFeedResponse, LemmyError> {
+    let data: &GetCustomFeed = &self.data;
+
+    let user_id: Option<i32> = match &data.auth {
+      Some(auth) => match Claims::decode(&auth) {
+        Ok(claims) => Some(claims.claims.id),
+        Err(_e) => None,
+      },
+      None => None,
+    };
+
+    let id = data.id;
+    let feed = match blocking(pool, move |conn| CustomFeed::read(conn, id)).await? {
+      Ok(feed) => feed,
+      Err(_e) => return Err(APIError::err("couldnt_find_custom_feed").into()),
+    };
+
+    read_feed_response(pool, feed, user_id).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetCustomFeedPosts> {
+  type Response = GetPostsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<GetPostsResponse, LemmyError> {
+    let data: &GetCustomFeedPosts = &self.data;
+
+    let user_claims: Option<Claims> = match &data.auth {
+      Some(auth) => match Claims::decode(&auth) {
+        Ok(claims) => Some(claims.claims),
+        Err(_e) => None,
+      },
+      None => None,
+    };
+
+    let user_id = match &user_claims {
+      Some(claims) => Some(claims.id),
+      None => None,
+    };
+
+    let show_nsfw = match &user_claims {
+      Some(claims) => claims.show_nsfw,
+      None => false,
+    };
+
+    let hide_read_posts = match &user_claims {
+      Some(claims) => claims.hide_read_posts,
+      None => false,
+    };
+
+    let hide_bot_accounts = match &user_claims {
+      Some(claims) => claims.hide_bot_accounts,
+      None => false,
+    };
+
+    let sort = SortType::from_str(&data.sort)?;
+
+    let feed_id = data.feed_id;
+    let page = data.page;
+    let limit = data.limit;
+    let cursor = decode_cursor(&data.cursor)?;
+    let posts = match blocking_read(pool, move |conn| {
+      PostQueryBuilder::create(conn)
+        .listing_type(ListingType::All)
+        .sort(&sort)
+        .show_nsfw(show_nsfw)
+        .for_custom_feed_id(feed_id)
+        .unread_only(hide_read_posts)
+        .hide_bot_accounts(hide_bot_accounts)
+        .my_user_id(user_id)
+        .cursor(cursor)
+        .page(page)
+        .limit(limit)
+        .list()
+    })
+    .await?
+    {
+      Ok(posts) => posts,
+      Err(_e) => return Err(APIError::err("couldnt_get_posts").into()),
+    };
+
+    GetPostsResponse::new(posts, limit)?
+      .collapse_muted(pool, user_id)
+      .await
+  }
+}
+
+fn check_valid_feed(name: &str, community_ids: &[i32]) -> Result<(), LemmyError> {
+  let name_len = name.trim().chars().count();
+  if name_len == 0 || name_len > 100 {
+    return Err(APIError::err("invalid_custom_feed_name").into());
+  }
+
+  if community_ids.len() > MAX_FEED_COMMUNITIES {
+    return Err(APIError::err("too_many_custom_feed_communities").into());
+  }
+
+  Ok(())
+}
+
+async fn read_feed_response(
+  pool: &DbPool,
+  feed: CustomFeed,
+  user_id: Option<i32>,
+) -> Result<CustomFeedResponse, LemmyError> {
+  let feed_id = feed.id;
+  let communities = blocking(pool, move |conn| {
+    CustomFeedCommunity::community_ids(conn, feed_id)?
+      .into_iter()
+      .map(|community_id| CommunityView::read(conn, community_id, user_id))
+      .collect::<Result<Vec<CommunityView>, _>>()
+  })
+  .await??;
+
+  Ok(CustomFeedResponse { feed, communities })
+}
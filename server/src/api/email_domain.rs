@@ -0,0 +1,174 @@
+use crate::{
+  api::{check_admin, claims::Claims, APIError, Oper, Perform},
+  blocking,
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::{email_domain::*, site::*, Crud};
+use lemmy_utils::{
+  disposable_email::is_disposable_email_domain,
+  domain_matches,
+  email_domain,
+  is_valid_domain_pattern,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ListEmailDomains {
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CreateEmailDomain {
+  domain: String,
+  allowed: bool,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeleteEmailDomain {
+  email_domain_id: i32,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ListEmailDomainsResponse {
+  domains: Vec<EmailDomain>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ListEmailDomains> {
+  type Response = ListEmailDomainsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListEmailDomainsResponse, LemmyError> {
+    let data: &ListEmailDomains = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_admin(pool, claims.id).await?;
+
+    let domains = blocking(pool, move |conn| EmailDomain::list(conn)).await??;
+
+    Ok(ListEmailDomainsResponse { domains })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<CreateEmailDomain> {
+  type Response = ListEmailDomainsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListEmailDomainsResponse, LemmyError> {
+    let data: &CreateEmailDomain = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let domain = data.domain.trim().to_lowercase();
+    if !is_valid_domain_pattern(&domain) {
+      return Err(APIError::err("invalid_domain").into());
+    }
+
+    let user_id = claims.id;
+    check_admin(pool, user_id).await?;
+
+    let domain_form = EmailDomainForm {
+      domain,
+      allowed: data.allowed,
+      creator_id: user_id,
+    };
+
+    let create = move |conn: &'_ _| EmailDomain::create(conn, &domain_form);
+    if blocking(pool, create).await?.is_err() {
+      return Err(APIError::err("email_domain_already_exists").into());
+    }
+
+    let domains = blocking(pool, move |conn| EmailDomain::list(conn)).await??;
+
+    Ok(ListEmailDomainsResponse { domains })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<DeleteEmailDomain> {
+  type Response = ListEmailDomainsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListEmailDomainsResponse, LemmyError> {
+    let data: &DeleteEmailDomain = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_admin(pool, claims.id).await?;
+
+    let email_domain_id = data.email_domain_id;
+    let delete = move |conn: &'_ _| EmailDomain::delete(conn, email_domain_id);
+    if blocking(pool, delete).await?.is_err() {
+      return Err(APIError::err("couldnt_delete_email_domain").into());
+    }
+
+    let domains = blocking(pool, move |conn| EmailDomain::list(conn)).await??;
+
+    Ok(ListEmailDomainsResponse { domains })
+  }
+}
+
+/// Rejects an email that the site doesn't let sign up. A denied domain always loses, and once
+/// there are allowed domains, nothing else gets in. An allowed domain also gets past the
+/// disposable check.
+pub async fn check_email_domain_allowed(pool: &DbPool, email: &str) -> Result<(), LemmyError> {
+  let domain = match email_domain(email) {
+    Some(domain) => domain,
+    None => return Err(APIError::err("invalid_email").into()),
+  };
+
+  let rules = blocking(pool, move |conn| EmailDomain::list(conn)).await??;
+  let matching = |allowed: bool| {
+    rules
+      .iter()
+      .filter(|r| r.allowed == allowed)
+      .any(|r| domain_matches(&r.domain, &domain))
+  };
+
+  if matching(false) {
+    return Err(APIError::err("email_domain_denied").into());
+  }
+
+  if rules.iter().any(|r| r.allowed) {
+    if matching(true) {
+      return Ok(());
+    }
+    return Err(APIError::err("email_domain_not_allowed").into());
+  }
+
+  let block_disposable = blocking(pool, move |conn| {
+    Site::read(conn, 1).map(|s| s.block_disposable_emails)
+  })
+  .await?
+  .unwrap_or(false);
+  if block_disposable && is_disposable_email_domain(&domain) {
+    return Err(APIError::err("disposable_email_not_allowed").into());
+  }
+
+  Ok(())
+}
@@ -0,0 +1,132 @@
+use crate::{
+  api::{check_admin, claims::Claims, APIError, Oper, Perform},
+  blocking,
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use diesel::PgConnection;
+use lemmy_db::{site::*, Crud};
+use lemmy_utils::{
+  is_valid_domain_pattern,
+  settings::{FederationOverrides, Settings},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetFederationSettings {
+  auth: String,
+}
+
+/// Replaces the site's federation settings. The ones left out go back to the config file's.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct EditFederationSettings {
+  federation: SiteFederation,
+  auth: String,
+}
+
+/// What the admins set, and what applies once the config file fills in the rest.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct FederationSettingsResponse {
+  federation: SiteFederation,
+  enabled: bool,
+  allowed_instances: String,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetFederationSettings> {
+  type Response = FederationSettingsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<FederationSettingsResponse, LemmyError> {
+    let data: &GetFederationSettings = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_admin(pool, claims.id).await?;
+
+    read_federation_settings(pool).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<EditFederationSettings> {
+  type Response = FederationSettingsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<FederationSettingsResponse, LemmyError> {
+    let data: &EditFederationSettings = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_admin(pool, claims.id).await?;
+
+    let mut federation = data.federation.to_owned();
+    if let Some(allowed_instances) = &federation.federation_allowed_instances {
+      let instances: Vec<&str> = allowed_instances
+        .split(',')
+        .map(|i| i.trim())
+        .filter(|i| !i.is_empty())
+        .collect();
+      // The instances are matched exactly, so there's no wildcard
+      if instances
+        .iter()
+        .any(|i| !is_valid_domain_pattern(i) || i.starts_with("*."))
+      {
+        return Err(APIError::err("invalid_allowed_instances").into());
+      }
+      federation.federation_allowed_instances = Some(instances.join(","));
+    }
+
+    let update_federation = move |conn: &'_ _| Site::update_federation(conn, 1, &federation);
+    if blocking(pool, update_federation).await?.is_err() {
+      return Err(APIError::err("couldnt_update_site").into());
+    }
+
+    read_federation_settings(pool).await
+  }
+}
+
+/// Reads the admins' federation settings from the database into the ones `Settings::get()`
+/// gives. Needs to run on startup, and after they change.
+pub fn load_federation_overrides(conn: &PgConnection) -> Result<(), LemmyError> {
+  // There's no site yet before the first admin sets it up
+  let federation = Site::read(conn, 1)
+    .map(|s| s.federation())
+    .unwrap_or_default();
+
+  Settings::set_federation_overrides(FederationOverrides {
+    enabled: federation.federation_enabled,
+    allowed_instances: federation.federation_allowed_instances,
+  });
+  Ok(())
+}
+
+/// Also swaps the new settings into the running server, so they apply right away.
+async fn read_federation_settings(pool: &DbPool) -> Result<FederationSettingsResponse, LemmyError> {
+  let federation = blocking(pool, move |conn| {
+    load_federation_overrides(conn)?;
+    Ok(Site::read(conn, 1)?.federation()) as Result<_, LemmyError>
+  })
+  .await??;
+
+  let settings = Settings::get().federation;
+  Ok(FederationSettingsResponse {
+    federation,
+    enabled: settings.enabled,
+    allowed_instances: settings.allowed_instances,
+  })
+}
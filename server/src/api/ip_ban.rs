@@ -0,0 +1,194 @@
+use crate::{
+  api::{check_admin, claims::Claims, APIError, Oper, Perform},
+  blocking,
+  rate_limit::load_ip_bans,
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::{ip_ban::*, naive_now, site::*, Crud};
+use lemmy_utils::{is_valid_ip_range, naive_from_unix, settings::Settings};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ListIpBans {
+  auth: String,
+}
+
+/// Bans a single address, or a range like 10.0.0.0/8. It's permanent without an expiry.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CreateIpBan {
+  ip_range: String,
+  reason: Option<String>,
+  expires: Option<i64>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeleteIpBan {
+  ip_ban_id: i32,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ListIpBansResponse {
+  bans: Vec<IpBan>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ListIpBans> {
+  type Response = ListIpBansResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListIpBansResponse, LemmyError> {
+    let data: &ListIpBans = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_admin(pool, claims.id).await?;
+
+    let bans = blocking(pool, move |conn| IpBan::list(conn)).await??;
+
+    Ok(ListIpBansResponse { bans })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<CreateIpBan> {
+  type Response = ListIpBansResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListIpBansResponse, LemmyError> {
+    let data: &CreateIpBan = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let ip_range = data.ip_range.trim().to_lowercase();
+    if !is_valid_ip_range(&ip_range) {
+      return Err(APIError::err("invalid_ip_range").into());
+    }
+
+    let expires = data.expires.map(naive_from_unix);
+    if expires.map_or(false, |expires| expires <= naive_now()) {
+      return Err(APIError::err("invalid_ban_expiry").into());
+    }
+
+    let user_id = claims.id;
+    check_admin(pool, user_id).await?;
+
+    let ban_form = IpBanForm {
+      ip_range,
+      reason: data.reason.to_owned(),
+      creator_id: user_id,
+      expires,
+    };
+
+    let create = move |conn: &'_ _| IpBan::create(conn, &ban_form);
+    if blocking(pool, create).await?.is_err() {
+      return Err(APIError::err("ip_already_banned").into());
+    }
+
+    reload_and_list(pool).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<DeleteIpBan> {
+  type Response = ListIpBansResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListIpBansResponse, LemmyError> {
+    let data: &DeleteIpBan = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_admin(pool, claims.id).await?;
+
+    let ip_ban_id = data.ip_ban_id;
+    let delete = move |conn: &'_ _| IpBan::delete(conn, ip_ban_id);
+    if blocking(pool, delete).await?.is_err() {
+      return Err(APIError::err("couldnt_unban_ip").into());
+    }
+
+    reload_and_list(pool).await
+  }
+}
+
+/// Keeps a salted hash of the IP the user registered or logged in from.
+pub async fn record_user_ip(
+  pool: &DbPool,
+  user_id: i32,
+  ip: &str,
+  registration: bool,
+) -> Result<(), LemmyError> {
+  let form = UserIpForm {
+    user_id,
+    ip_hash: hash_ip(ip),
+    registration,
+  };
+  blocking(pool, move |conn| UserIp::record(conn, &form)).await??;
+  Ok(())
+}
+
+/// Rejects a registration once the IP made as many accounts in the past day as the site allows.
+pub async fn check_registration_throttle(pool: &DbPool, ip: &str) -> Result<(), LemmyError> {
+  // There's no limit before the site is set up
+  let limit = blocking(pool, move |conn| {
+    Site::read(conn, 1).map(|s| s.registrations_per_ip_per_day)
+  })
+  .await?
+  .unwrap_or(0);
+  if limit == 0 {
+    return Ok(());
+  }
+
+  let ip_hash = hash_ip(ip);
+  let since = naive_now() - chrono::Duration::days(1);
+  let registrations = blocking(pool, move |conn| {
+    UserIp::count_registrations_since(conn, &ip_hash, since)
+  })
+  .await??;
+  if registrations >= i64::from(limit) {
+    return Err(APIError::err("too_many_registrations").into());
+  }
+
+  Ok(())
+}
+
+/// The jwt secret salts it, so that the hashes can't be looked up without it.
+fn hash_ip(ip: &str) -> String {
+  let mut hasher = Sha256::new();
+  hasher.update(Settings::get().jwt_secret.as_bytes());
+  hasher.update(ip.as_bytes());
+  format!("{:x}", hasher.finalize())
+}
+
+async fn reload_and_list(pool: &DbPool) -> Result<ListIpBansResponse, LemmyError> {
+  let bans = blocking(pool, move |conn| {
+    load_ip_bans(conn)?;
+    Ok(IpBan::list(conn)?) as Result<_, LemmyError>
+  })
+  .await??;
+
+  Ok(ListIpBansResponse { bans })
+}
@@ -0,0 +1,57 @@
+use crate::{
+  api::{check_admin, claims::Claims, APIError, Oper, Perform},
+  blocking,
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::job::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// How many of the jobs that failed for good are shown
+const FAILED_JOBS_LIMIT: i64 = 20;
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetJobQueue {
+  auth: String,
+}
+
+/// `oldest_due` is when the job that has waited the longest became due, which shows how far
+/// behind the workers are.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct JobQueueResponse {
+  stats: Vec<JobQueueStats>,
+  oldest_due: Option<chrono::NaiveDateTime>,
+  failed: Vec<Job>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetJobQueue> {
+  type Response = JobQueueResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<JobQueueResponse, LemmyError> {
+    let data: &GetJobQueue = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_admin(pool, claims.id).await?;
+
+    let stats = blocking(pool, move |conn| Job::stats(conn)).await??;
+    let oldest_due = blocking(pool, move |conn| Job::oldest_due(conn)).await??;
+    let failed = blocking(pool, move |conn| Job::list_failed(conn, FAILED_JOBS_LIMIT)).await??;
+
+    Ok(JobQueueResponse {
+      stats,
+      oldest_due,
+      failed,
+    })
+  }
+}
@@ -0,0 +1,118 @@
+use crate::{
+  api::{claims::Claims, APIError, Oper, Perform},
+  blocking,
+  delete_pictrs_image,
+  media::media_url,
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::{media::*, user::*, Crud};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+/// The images the user uploaded, newest first.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ListImages {
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ListImagesResponse {
+  images: Vec<ImageView>,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ImageView {
+  file: String,
+  url: String,
+  content_type: Option<String>,
+  published: chrono::NaiveDateTime,
+}
+
+/// Takes an uploaded image down from pictrs. Admins can delete anyone's.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeleteImage {
+  file: String,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct DeleteImageResponse {}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ListImages> {
+  type Response = ListImagesResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListImagesResponse, LemmyError> {
+    let data: &ListImages = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    let media = blocking(pool, move |conn| Media::for_user(conn, user_id)).await??;
+
+    let images = media
+      .into_iter()
+      .map(|m| ImageView {
+        url: media_url(&m.file),
+        file: m.file,
+        content_type: m.content_type,
+        published: m.published,
+      })
+      .collect();
+
+    Ok(ListImagesResponse { images })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<DeleteImage> {
+  type Response = DeleteImageResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<DeleteImageResponse, LemmyError> {
+    let data: &DeleteImage = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let file = data.file.to_owned();
+    let media = match blocking(pool, move |conn| Media::read_for_file(conn, &file)).await? {
+      Ok(media) => media,
+      Err(_e) => return Err(APIError::err("couldnt_find_image").into()),
+    };
+
+    if media.user_id != user_id {
+      let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+      if !user.admin {
+        return Err(APIError::err("no_image_delete_allowed").into());
+      }
+    }
+
+    if let Err(e) = delete_pictrs_image(&self.client, &media.file, &media.delete_token).await {
+      error!("Couldn't delete uploaded image {}: {}", media.file, e);
+      return Err(APIError::err("couldnt_delete_image").into());
+    }
+
+    let media_id = media.id;
+    blocking(pool, move |conn| Media::delete(conn, media_id)).await??;
+
+    Ok(DeleteImageResponse {})
+  }
+}
@@ -1,13 +1,48 @@
-use crate::{websocket::WebsocketInfo, DbPool, LemmyError};
+use crate::{blocking, websocket::WebsocketInfo, DbPool, IPAddr, LemmyError};
 use actix_web::client::Client;
-use lemmy_db::{community::*, community_view::*, moderator::*, site::*, user::*, user_view::*};
+use lemmy_db::{
+  community::*,
+  community_view::*,
+  limit_and_offset,
+  moderator::*,
+  site::*,
+  user::*,
+  user_view::*,
+  ListingCursor,
+};
+use serde::{de::DeserializeOwned, Serialize};
 
+pub mod appeal;
+pub mod automod;
+pub mod blocked_domain;
+pub mod bulk_moderation;
 pub mod claims;
 pub mod comment;
 pub mod community;
+pub mod community_import;
+pub mod community_takeover;
+pub mod community_wiki;
+pub mod custom_feed;
+pub mod email_domain;
+pub mod federation;
+pub mod ip_ban;
+pub mod job;
+pub mod media;
+pub mod mod_note;
+pub mod muted_keyword;
 pub mod post;
+pub mod post_approval;
+pub mod posting_requirement;
+pub mod purge;
+pub mod rate_limit;
+pub mod report;
+pub mod scheduled_post;
 pub mod site;
+pub mod site_document;
 pub mod user;
+pub mod webhook;
+pub mod websocket_subscription;
+pub mod word_filter;
 
 #[derive(Fail, Debug)]
 #[fail(display = "{{\"error\":\"{}\"}}", message)]
@@ -26,11 +61,88 @@ impl APIError {
 pub struct Oper<T> {
   data: T,
   client: Client,
+  /// Where the request came from, unless it was made from within lemmy
+  ip: Option<IPAddr>,
 }
 
 impl<Data> Oper<Data> {
   pub fn new(data: Data, client: Client) -> Oper<Data> {
-    Oper { data, client }
+    Oper {
+      data,
+      client,
+      ip: None,
+    }
+  }
+
+  pub fn with_ip(mut self, ip: IPAddr) -> Oper<Data> {
+    self.ip = Some(ip);
+    self
+  }
+}
+
+/// For the site wide settings, which only the admins may change.
+pub async fn check_admin(pool: &DbPool, user_id: i32) -> Result<(), LemmyError> {
+  let is_admin = move |conn: &'_ _| UserView::read(conn, user_id).map(|u| u.admin);
+  if !blocking(pool, is_admin).await?? {
+    return Err(APIError::err("not_an_admin").into());
+  }
+  Ok(())
+}
+
+/// For what the admins manage site wide, and the mods for their own community.
+pub async fn check_admin_or_community_mod(
+  pool: &DbPool,
+  user_id: i32,
+  community_id: Option<i32>,
+) -> Result<(), LemmyError> {
+  match community_id {
+    Some(community_id) => {
+      community::check_community_mod_or_admin(pool, user_id, community_id).await
+    }
+    None => check_admin(pool, user_id).await,
+  }
+}
+
+/// The cursors handed out are opaque to clients, so what's in them can change.
+pub fn encode_cursor(cursor: &ListingCursor) -> Result<String, LemmyError> {
+  encode_token(cursor)
+}
+
+pub fn decode_cursor(cursor: &Option<String>) -> Result<Option<ListingCursor>, LemmyError> {
+  decode_token(cursor)
+}
+
+/// Any of the cursors, as it goes to the client.
+pub fn encode_token<T: Serialize>(token: &T) -> Result<String, LemmyError> {
+  Ok(base64::encode_config(
+    serde_json::to_string(token)?,
+    base64::URL_SAFE_NO_PAD,
+  ))
+}
+
+pub fn decode_token<T: DeserializeOwned>(token: &Option<String>) -> Result<Option<T>, LemmyError> {
+  let token = match token {
+    Some(token) => token,
+    None => return Ok(None),
+  };
+
+  base64::decode_config(token, base64::URL_SAFE_NO_PAD)
+    .ok()
+    .and_then(|json| serde_json::from_slice(&json).ok())
+    .map(Some)
+    .ok_or_else(|| APIError::err("invalid_cursor").into())
+}
+
+/// The cursor for the page after one that ended with `last`, unless that page wasn't full.
+pub fn next_cursor(
+  last: Option<ListingCursor>,
+  count: usize,
+  limit: Option<i64>,
+) -> Result<Option<String>, LemmyError> {
+  let (limit, _) = limit_and_offset(None, limit);
+  match last {
+    Some(last) if count as i64 >= limit => Ok(Some(encode_cursor(&last)?)),
+    _ => Ok(None),
   }
 }
 
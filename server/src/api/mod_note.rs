@@ -0,0 +1,201 @@
+use crate::{
+  api::{claims::Claims, community::check_community_mod_or_admin, APIError, Oper, Perform},
+  blocking,
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::{community_view::*, mod_note::*, user_view::*, Crud};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CreateModNote {
+  community_id: i32,
+  user_id: i32,
+  content: String,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeleteModNote {
+  note_id: i32,
+  auth: String,
+}
+
+/// Without a community, admins get the notes of every community and mods the notes of the
+/// communities they moderate.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ListModNotes {
+  user_id: i32,
+  community_id: Option<i32>,
+  page: Option<i64>,
+  limit: Option<i64>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ModNoteResponse {
+  note: ModNote,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ListModNotesResponse {
+  notes: Vec<ModNote>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<CreateModNote> {
+  type Response = ModNoteResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ModNoteResponse, LemmyError> {
+    let data: &CreateModNote = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let content = data.content.trim().to_owned();
+    let content_len = content.chars().count();
+    if content_len == 0 || content_len > 10000 {
+      return Err(APIError::err("invalid_mod_note").into());
+    }
+
+    let user_id = claims.id;
+    check_community_mod_or_admin(pool, user_id, data.community_id).await?;
+
+    let note_form = ModNoteForm {
+      community_id: data.community_id,
+      user_id: data.user_id,
+      creator_id: user_id,
+      content,
+    };
+
+    let note = match blocking(pool, move |conn| ModNote::create(conn, &note_form)).await? {
+      Ok(note) => note,
+      Err(_e) => return Err(APIError::err("couldnt_create_mod_note").into()),
+    };
+
+    Ok(ModNoteResponse { note })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<DeleteModNote> {
+  type Response = ModNoteResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ModNoteResponse, LemmyError> {
+    let data: &DeleteModNote = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let note_id = data.note_id;
+    let note = blocking(pool, move |conn| ModNote::read(conn, note_id)).await??;
+    check_community_mod_or_admin(pool, user_id, note.community_id).await?;
+
+    let delete = move |conn: &'_ _| ModNote::delete(conn, note_id);
+    if blocking(pool, delete).await?.is_err() {
+      return Err(APIError::err("couldnt_delete_mod_note").into());
+    }
+
+    Ok(ModNoteResponse { note })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ListModNotes> {
+  type Response = ListModNotesResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListModNotesResponse, LemmyError> {
+    let data: &ListModNotes = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let community_ids = match data.community_id {
+      Some(community_id) => {
+        check_community_mod_or_admin(pool, user_id, community_id).await?;
+        Some(vec![community_id])
+      }
+      None => match noted_community_ids(pool, user_id).await? {
+        Some(community_ids) if community_ids.is_empty() => {
+          return Err(APIError::err("not_a_moderator").into())
+        }
+        community_ids => community_ids,
+      },
+    };
+
+    let noted_user_id = data.user_id;
+    let page = data.page;
+    let limit = data.limit;
+    let notes = blocking(pool, move |conn| {
+      ModNote::for_user(conn, noted_user_id, community_ids, page, limit)
+    })
+    .await??;
+
+    Ok(ListModNotesResponse { notes })
+  }
+}
+
+/// The notes about a user that the viewer may read. Anyone who isn't a mod or an admin sees none.
+pub async fn visible_mod_notes(
+  pool: &DbPool,
+  viewer_id: Option<i32>,
+  noted_user_id: i32,
+) -> Result<Vec<ModNote>, LemmyError> {
+  let viewer_id = match viewer_id {
+    Some(viewer_id) => viewer_id,
+    None => return Ok(Vec::new()),
+  };
+
+  let community_ids = match noted_community_ids(pool, viewer_id).await? {
+    Some(community_ids) if community_ids.is_empty() => return Ok(Vec::new()),
+    community_ids => community_ids,
+  };
+
+  let notes = blocking(pool, move |conn| {
+    ModNote::for_user(conn, noted_user_id, community_ids, None, None)
+  })
+  .await??;
+
+  Ok(notes)
+}
+
+/// The communities whose notes a user can read. Admins get `None`, since they can read them all.
+async fn noted_community_ids(pool: &DbPool, user_id: i32) -> Result<Option<Vec<i32>>, LemmyError> {
+  let user = blocking(pool, move |conn| UserView::read(conn, user_id)).await??;
+  if user.admin {
+    return Ok(None);
+  }
+
+  let moderated = blocking(pool, move |conn| {
+    CommunityModeratorView::for_user(conn, user_id)
+  })
+  .await??;
+
+  Ok(Some(
+    moderated.into_iter().map(|m| m.community_id).collect(),
+  ))
+}
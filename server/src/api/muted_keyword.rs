@@ -0,0 +1,262 @@
+use crate::{
+  api::{claims::Claims, APIError, Oper, Perform},
+  blocking,
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::{naive_now, user_muted_keyword::*, Crud};
+use lemmy_utils::naive_from_unix;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The most keywords a single user can mute
+const MAX_MUTED_KEYWORDS: usize = 100;
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ListMutedKeywords {
+  auth: String,
+}
+
+/// Mutes a word, or a regex. The posts and comments with it are collapsed, or with hide left out
+/// of the listings. It's muted for good without an expiry.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CreateMutedKeyword {
+  keyword: String,
+  is_regex: bool,
+  hide: bool,
+  expires: Option<i64>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct EditMutedKeyword {
+  edit_id: i32,
+  keyword: String,
+  is_regex: bool,
+  hide: bool,
+  expires: Option<i64>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeleteMutedKeyword {
+  keyword_id: i32,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct MutedKeywordsResponse {
+  keywords: Vec<UserMutedKeyword>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ListMutedKeywords> {
+  type Response = MutedKeywordsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<MutedKeywordsResponse, LemmyError> {
+    let data: &ListMutedKeywords = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    list_muted_keywords(pool, claims.id).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<CreateMutedKeyword> {
+  type Response = MutedKeywordsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<MutedKeywordsResponse, LemmyError> {
+    let data: &CreateMutedKeyword = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    let keyword_form = UserMutedKeywordForm {
+      user_id,
+      keyword: data.keyword.trim().to_owned(),
+      is_regex: data.is_regex,
+      hide: data.hide,
+      expires: data.expires.map(naive_from_unix),
+      updated: None,
+    };
+    check_muted_keyword(pool, &keyword_form).await?;
+
+    let count = move |conn: &'_ _| UserMutedKeyword::list_for_user(conn, user_id);
+    if blocking(pool, count).await??.len() >= MAX_MUTED_KEYWORDS {
+      return Err(APIError::err("too_many_muted_keywords").into());
+    }
+
+    let create = move |conn: &'_ _| UserMutedKeyword::create(conn, &keyword_form);
+    if blocking(pool, create).await?.is_err() {
+      return Err(APIError::err("couldnt_save_muted_keyword").into());
+    }
+
+    list_muted_keywords(pool, user_id).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<EditMutedKeyword> {
+  type Response = MutedKeywordsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<MutedKeywordsResponse, LemmyError> {
+    let data: &EditMutedKeyword = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    let edit_id = data.edit_id;
+    check_keyword_owner(pool, edit_id, user_id).await?;
+
+    let keyword_form = UserMutedKeywordForm {
+      user_id,
+      keyword: data.keyword.trim().to_owned(),
+      is_regex: data.is_regex,
+      hide: data.hide,
+      expires: data.expires.map(naive_from_unix),
+      updated: Some(naive_now()),
+    };
+    check_muted_keyword(pool, &keyword_form).await?;
+
+    let update = move |conn: &'_ _| UserMutedKeyword::update(conn, edit_id, &keyword_form);
+    if blocking(pool, update).await?.is_err() {
+      return Err(APIError::err("couldnt_save_muted_keyword").into());
+    }
+
+    list_muted_keywords(pool, user_id).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<DeleteMutedKeyword> {
+  type Response = MutedKeywordsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<MutedKeywordsResponse, LemmyError> {
+    let data: &DeleteMutedKeyword = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    let keyword_id = data.keyword_id;
+    check_keyword_owner(pool, keyword_id, user_id).await?;
+
+    let delete = move |conn: &'_ _| UserMutedKeyword::delete(conn, keyword_id);
+    if blocking(pool, delete).await?.is_err() {
+      return Err(APIError::err("couldnt_delete_muted_keyword").into());
+    }
+
+    list_muted_keywords(pool, user_id).await
+  }
+}
+
+/// Of the posts, the ones the user muted a keyword of, to show collapsed.
+pub async fn collapsed_post_ids(
+  pool: &DbPool,
+  user_id: Option<i32>,
+  post_ids: Vec<i32>,
+) -> Result<Vec<i32>, LemmyError> {
+  match user_id {
+    Some(user_id) => {
+      let collapsed =
+        move |conn: &'_ _| UserMutedKeyword::collapsed_post_ids(conn, user_id, post_ids);
+      Ok(blocking(pool, collapsed).await??)
+    }
+    None => Ok(Vec::new()),
+  }
+}
+
+/// Same as `collapsed_post_ids`, for comments.
+pub async fn collapsed_comment_ids(
+  pool: &DbPool,
+  user_id: Option<i32>,
+  comment_ids: Vec<i32>,
+) -> Result<Vec<i32>, LemmyError> {
+  match user_id {
+    Some(user_id) => {
+      let collapsed =
+        move |conn: &'_ _| UserMutedKeyword::collapsed_comment_ids(conn, user_id, comment_ids);
+      Ok(blocking(pool, collapsed).await??)
+    }
+    None => Ok(Vec::new()),
+  }
+}
+
+async fn check_muted_keyword(
+  pool: &DbPool,
+  keyword_form: &UserMutedKeywordForm,
+) -> Result<(), LemmyError> {
+  if keyword_form.keyword.is_empty() || keyword_form.keyword.chars().count() > 100 {
+    return Err(APIError::err("invalid_muted_keyword").into());
+  }
+
+  if keyword_form
+    .expires
+    .map_or(false, |expires| expires <= naive_now())
+  {
+    return Err(APIError::err("invalid_muted_keyword_expiry").into());
+  }
+
+  if keyword_form.is_regex {
+    let pattern = keyword_form.keyword.to_owned();
+    let is_valid = move |conn: &'_ _| UserMutedKeyword::is_valid_regex(conn, &pattern);
+    if !blocking(pool, is_valid).await? {
+      return Err(APIError::err("invalid_muted_keyword").into());
+    }
+  }
+  Ok(())
+}
+
+async fn check_keyword_owner(
+  pool: &DbPool,
+  keyword_id: i32,
+  user_id: i32,
+) -> Result<(), LemmyError> {
+  let keyword = match blocking(pool, move |conn| UserMutedKeyword::read(conn, keyword_id)).await? {
+    Ok(keyword) => keyword,
+    Err(_e) => return Err(APIError::err("couldnt_find_muted_keyword").into()),
+  };
+  if keyword.user_id != user_id {
+    return Err(APIError::err("no_muted_keyword_edit_allowed").into());
+  }
+  Ok(())
+}
+
+async fn list_muted_keywords(
+  pool: &DbPool,
+  user_id: i32,
+) -> Result<MutedKeywordsResponse, LemmyError> {
+  let list = move |conn: &'_ _| UserMutedKeyword::list_for_user(conn, user_id);
+  let keywords = blocking(pool, list).await??;
+
+  Ok(MutedKeywordsResponse { keywords })
+}
@@ -1,10 +1,44 @@
 use crate::{
-  api::{claims::Claims, APIError, Oper, Perform},
+  api::{
+    automod::{run_automod, AutomodSubject},
+    blocked_domain::check_url_domain_allowed,
+    claims::Claims,
+    comment::{more_replies, wants_notification, MoreReplies},
+    community::{
+      check_community_mod_or_admin,
+      check_community_not_read_only,
+      check_removal_reason,
+    },
+    decode_cursor,
+    muted_keyword::{collapsed_comment_ids, collapsed_post_ids},
+    next_cursor,
+    post_approval::{needs_approval, send_pending_post_to_mods},
+    posting_requirement::check_posting_requirements,
+    user::send_mod_action_notice,
+    word_filter::{check_words, report_flagged_post},
+    APIError,
+    Oper,
+    Perform,
+  },
   apub::{ApubLikeableType, ApubObjectType},
   blocking,
+  blocking_read,
+  cache::{get_post_listing, set_post_listing, PostListingKey},
   fetch_iframely_and_pictrs_data,
+  hooks::{pre_edit_post, pre_publish_post},
+  link_preview::refresh_post_embed,
+  media::attachment_media_type,
+  nsfw::{nsfw_image_action, NsfwAction},
+  webhook::queue_new_post_webhooks,
   websocket::{
-    server::{JoinCommunityRoom, JoinPostRoom, SendPost},
+    server::{
+      GetPostUsersOnline,
+      JoinCommunityRoom,
+      JoinPostRoom,
+      SendPost,
+      SendPostToSubscribers,
+      SendUserRoomMessage,
+    },
     UserOperation,
     WebsocketInfo,
   },
@@ -13,63 +47,92 @@ use crate::{
 };
 use lemmy_db::{
   comment_view::*,
+  community::*,
+  community_flair::*,
   community_view::*,
   moderator::*,
   naive_now,
   post::*,
   post_view::*,
+  scheduled_post::*,
   site::*,
   site_view::*,
   user::*,
+  user_notification_settings::*,
   user_view::*,
   Crud,
+  Followable,
+  Hideable,
   Likeable,
   ListingType,
+  Readable,
   Saveable,
   SortType,
 };
-use lemmy_utils::{
-  is_valid_post_title,
-  make_apub_endpoint,
-  slur_check,
-  slurs_vec_to_str,
-  EndpointType,
-};
+use lemmy_utils::{is_image_url, is_valid_post_title, make_apub_endpoint, EndpointType};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct CreatePost {
-  name: String,
-  url: Option<String>,
-  body: Option<String>,
-  nsfw: bool,
+  pub name: String,
+  pub url: Option<String>,
+  pub body: Option<String>,
+  pub nsfw: bool,
   pub community_id: i32,
-  auth: String,
+  pub flair_id: Option<i32>,
+  pub auth: String,
+  /// Set when the job queue puts up a scheduled post, which the new post is recorded on
+  #[serde(skip)]
+  pub scheduled_post_id: Option<i32>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct PostResponse {
   pub post: PostView,
 }
 
-#[derive(Serialize, Deserialize)]
+/// What the listings and the open post get of a vote, instead of the whole post
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct PostScoreResponse {
+  pub post_id: i32,
+  pub community_id: i32,
+  pub score: i64,
+  #[serde(default, skip_serializing_if = "lemmy_db::vote_counts_hidden")]
+  pub upvotes: i64,
+  #[serde(default, skip_serializing_if = "lemmy_db::vote_counts_hidden")]
+  pub downvotes: i64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetPost {
   pub id: i32,
+  /// How many levels of replies to send along with the top level comments, all of them without it
+  comment_depth: Option<i32>,
+  /// How many comments to send at most, the upper levels first
+  comment_limit: Option<i64>,
   auth: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetPostResponse {
   post: PostView,
   comments: Vec<CommentView>,
+  /// What the comment depth or limit left out
+  more_replies: Vec<MoreReplies>,
+  /// The comments with a keyword you muted, to show collapsed
+  collapsed_comment_ids: Vec<i32>,
+  /// Whether you hear about every new comment
+  watching: bool,
   community: CommunityView,
   moderators: Vec<CommunityModeratorView>,
   admins: Vec<UserView>,
+  flair: Option<CommunityFlair>,
   pub online: usize,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct GetPosts {
   type_: String,
   sort: String,
@@ -77,42 +140,114 @@ pub struct GetPosts {
   limit: Option<i64>,
   pub community_id: Option<i32>,
   pub community_name: Option<String>,
+  flair_id: Option<i32>,
+  /// Lists only the posts you've hidden, so they can be unhidden
+  show_hidden: Option<bool>,
+  /// The `next_cursor` of the last page, used instead of `page`
+  cursor: Option<String>,
   auth: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct GetPostsResponse {
   pub posts: Vec<PostView>,
+  next_cursor: Option<String>,
+  /// The posts with a keyword you muted, to show collapsed
+  collapsed_post_ids: Vec<i32>,
+}
+
+impl GetPostsResponse {
+  pub fn new(posts: Vec<PostView>, limit: Option<i64>) -> Result<Self, LemmyError> {
+    let next_cursor = next_cursor(posts.last().map(PostView::cursor), posts.len(), limit)?;
+    Ok(GetPostsResponse {
+      posts,
+      next_cursor,
+      collapsed_post_ids: Vec::new(),
+    })
+  }
+
+  /// Marks the posts the user muted a keyword of.
+  pub async fn collapse_muted(
+    mut self,
+    pool: &DbPool,
+    user_id: Option<i32>,
+  ) -> Result<Self, LemmyError> {
+    let post_ids = self.posts.iter().map(|p| p.id).collect();
+    self.collapsed_post_ids = collapsed_post_ids(pool, user_id, post_ids).await?;
+    Ok(self)
+  }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct CreatePostLike {
-  post_id: i32,
-  score: i16,
-  auth: String,
+  pub post_id: i32,
+  pub score: i16,
+  pub auth: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct EditPost {
   pub edit_id: i32,
-  creator_id: i32,
-  community_id: i32,
-  name: String,
-  url: Option<String>,
-  body: Option<String>,
-  removed: Option<bool>,
-  deleted: Option<bool>,
-  nsfw: bool,
-  locked: Option<bool>,
-  stickied: Option<bool>,
-  reason: Option<String>,
-  auth: String,
+  pub creator_id: i32,
+  pub community_id: i32,
+  pub name: String,
+  pub url: Option<String>,
+  pub body: Option<String>,
+  pub removed: Option<bool>,
+  pub deleted: Option<bool>,
+  pub nsfw: bool,
+  pub locked: Option<bool>,
+  pub stickied: Option<bool>,
+  /// Left out, the flair stays as it is. Clearing it is asked for with `clear_flair`.
+  pub flair_id: Option<i32>,
+  pub clear_flair: Option<bool>,
+  pub reason: Option<String>,
+  pub auth: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct SavePost {
+  pub post_id: i32,
+  pub save: bool,
+  pub auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetPostRevisions {
+  post_id: i32,
+  auth: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetPostRevisionsResponse {
+  revisions: Vec<PostRevision>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct HidePost {
+  post_id: i32,
+  hide: bool,
+  auth: String,
+}
+
+/// Watching a post notifies you of every new comment in it, not just the replies to you.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct WatchPost {
+  post_id: i32,
+  watch: bool,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct WatchPostResponse {
+  post: PostView,
+  watching: bool,
+}
+
+/// Fetches the preview of a link post again. The creator, the community's mods and the admins can.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct RefreshPostEmbed {
   post_id: i32,
-  save: bool,
   auth: String,
 }
 
@@ -132,15 +267,16 @@ impl Perform for Oper<CreatePost> {
       Err(_e) => return Err(APIError::err("not_logged_in").into()),
     };
 
-    if let Err(slurs) = slur_check(&data.name) {
-      return Err(APIError::err(&slurs_vec_to_str(slurs)).into());
-    }
+    // Everything below checks what the hooks let through
+    let hooked = pre_publish_post(claims.id, data).await?;
+    let data = &hooked;
 
-    if let Some(body) = &data.body {
-      if let Err(slurs) = slur_check(body) {
-        return Err(APIError::err(&slurs_vec_to_str(slurs)).into());
-      }
-    }
+    let name = check_words(&data.name, data.community_id)?;
+    let body = data
+      .body
+      .as_deref()
+      .map(|body| check_words(body, data.community_id))
+      .transpose()?;
 
     if !is_valid_post_title(&data.name) {
       return Err(APIError::err("invalid_post_title").into());
@@ -158,36 +294,75 @@ impl Perform for Oper<CreatePost> {
 
     // Check for a site ban
     let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
-    if user.banned {
+    if user.is_banned() {
       return Err(APIError::err("site_ban").into());
     }
 
+    // Check that the user has verified their email, if the site requires it
+    if let Ok(site) = blocking(pool, move |conn| SiteView::read(conn)).await? {
+      if site.require_email_verification && !user.email_verified && !user.admin {
+        return Err(APIError::err("email_not_verified").into());
+      }
+    }
+
+    // Check that the flair belongs to the community, and that one is picked if required
+    let community_id = data.community_id;
+    let community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
+    if data.flair_id.is_none() && community.require_flair {
+      return Err(APIError::err("flair_required").into());
+    }
+    check_community_not_read_only(pool, user_id, &community).await?;
+    check_post_type_allowed(&community, &data.url)?;
+    check_flair_in_community(pool, data.flair_id, community_id).await?;
+    check_url_domain_allowed(pool, community_id, &data.url).await?;
+    check_posting_requirements(pool, user_id, community_id, data.url.is_some()).await?;
+    let pending = needs_approval(pool, &community, user_id).await?;
+
+    let subject = AutomodSubject {
+      title: Some(&data.name),
+      body: data.body.as_deref(),
+      url: data.url.as_deref(),
+    };
+    let automod = run_automod(pool, community_id, user_id, &subject).await?;
+
     // Fetch Iframely and pictrs cached image
-    let (iframely_title, iframely_description, iframely_html, pictrs_thumbnail) =
-      fetch_iframely_and_pictrs_data(&self.client, data.url.to_owned()).await;
+    let (iframely_title, iframely_description, iframely_html, embed_type, pictrs_thumbnail) =
+      fetch_iframely_and_pictrs_data(&self.client, pool, data.url.to_owned()).await;
+    let url_media_type = attachment_media_type(pool, &data.url).await?;
+
+    let nsfw_action = nsfw_image_action(pool, &data.url, &pictrs_thumbnail, data.nsfw).await?;
+    let pending = pending || nsfw_action == Some(NsfwAction::Review);
 
     let post_form = PostForm {
-      name: data.name.trim().to_owned(),
+      name: name.text.trim().to_owned(),
       url: data.url.to_owned(),
-      body: data.body.to_owned(),
+      body: body.as_ref().map(|b| b.text.to_owned()),
       community_id: data.community_id,
       creator_id: user_id,
-      removed: None,
+      removed: if automod.removes() { Some(true) } else { None },
       deleted: None,
-      nsfw: data.nsfw,
+      nsfw: data.nsfw || nsfw_action == Some(NsfwAction::MarkNsfw),
       locked: None,
       stickied: None,
       updated: None,
       embed_title: iframely_title,
       embed_description: iframely_description,
       embed_html: iframely_html,
+      embed_type,
+      url_media_type,
       thumbnail_url: pictrs_thumbnail,
       ap_id: "http://fake.com".into(),
       local: true,
       published: None,
+      pending: if pending { Some(true) } else { None },
     };
 
-    let inserted_post = match blocking(pool, move |conn| Post::create(conn, &post_form)).await? {
+    let scheduled_post_id = data.scheduled_post_id;
+    let insert = move |conn: &'_ _| match scheduled_post_id {
+      Some(scheduled_post_id) => ScheduledPost::insert_post(conn, scheduled_post_id, &post_form),
+      None => Post::create(conn, &post_form),
+    };
+    let inserted_post = match blocking(pool, insert).await? {
       Ok(post) => post,
       Err(e) => {
         let err_type = if e.to_string() == "value too long for type character varying(200)" {
@@ -212,7 +387,35 @@ impl Perform for Oper<CreatePost> {
       Err(_e) => return Err(APIError::err("couldnt_create_post").into()),
     };
 
-    updated_post.send_create(&user, &self.client, pool).await?;
+    if data.flair_id.is_some() {
+      let flair_id = data.flair_id;
+      let set_flair = move |conn: &'_ _| PostFlair::set(conn, inserted_post_id, flair_id);
+      if blocking(pool, set_flair).await?.is_err() {
+        return Err(APIError::err("couldnt_create_post").into());
+      }
+    }
+
+    automod
+      .send_hit_to_mods(
+        pool,
+        &websocket_info,
+        community_id,
+        Some(inserted_post_id),
+        None,
+      )
+      .await?;
+    automod
+      .report_post(pool, &websocket_info, inserted_post_id)
+      .await?;
+    report_flagged_post(pool, &websocket_info, inserted_post_id, &name.flagged).await?;
+    if let Some(body) = &body {
+      report_flagged_post(pool, &websocket_info, inserted_post_id, &body.flagged).await?;
+    }
+
+    // Held back posts only federate once they're approved
+    if !pending {
+      updated_post.send_create(&user, &self.client, pool).await?;
+    }
 
     // They like their own post by default
     let like_form = PostLikeForm {
@@ -226,7 +429,9 @@ impl Perform for Oper<CreatePost> {
       return Err(APIError::err("couldnt_like_post").into());
     }
 
-    updated_post.send_like(&user, &self.client, pool).await?;
+    if !pending {
+      updated_post.send_like(&user, &self.client, pool).await?;
+    }
 
     // Refetch the view
     let inserted_post_id = inserted_post.id;
@@ -241,12 +446,32 @@ impl Perform for Oper<CreatePost> {
 
     let res = PostResponse { post: post_view };
 
-    if let Some(ws) = websocket_info {
+    if pending {
+      send_pending_post_to_mods(pool, &websocket_info, &res).await?;
+      return Ok(res);
+    }
+
+    let subscriber_ids = subscribed_post_recipients(pool, &res.post).await?;
+
+    // Posts that automod took down, or from shadowbanned users, don't go out live
+    if !updated_post.removed && !user.shadow_banned {
+      queue_new_post_webhooks(pool, &res.post).await?;
+    }
+    if let Some(ws) = websocket_info.filter(|_| !updated_post.removed && !user.shadow_banned) {
       ws.chatserver.do_send(SendPost {
         op: UserOperation::CreatePost,
         post: res.clone(),
         my_id: ws.id,
       });
+
+      if !subscriber_ids.is_empty() {
+        ws.chatserver.do_send(SendPostToSubscribers {
+          op: UserOperation::CreatePost,
+          post: res.clone(),
+          subscriber_ids,
+          my_id: ws.id,
+        });
+      }
     }
 
     Ok(res)
@@ -276,20 +501,73 @@ impl Perform for Oper<GetPost> {
     };
 
     let id = data.id;
-    let post_view = match blocking(pool, move |conn| PostView::read(conn, id, user_id)).await? {
+    let mut post_view = match blocking(pool, move |conn| PostView::read(conn, id, user_id)).await? {
       Ok(post) => post,
       Err(_e) => return Err(APIError::err("couldnt_find_post").into()),
     };
 
+    // Only the creator and the mods get to see a post that's waiting for approval
+    if post_view.pending && user_id != Some(post_view.creator_id) {
+      let community_id = post_view.community_id;
+      let is_mod = match user_id {
+        Some(user_id) => check_community_mod_or_admin(pool, user_id, community_id)
+          .await
+          .is_ok(),
+        None => false,
+      };
+      if !is_mod {
+        return Err(APIError::err("couldnt_find_post").into());
+      }
+    }
+
+    // Opening a post marks it as read, so it can be left out of the feeds afterwards
+    if let Some(user_id) = user_id {
+      if post_view.read != Some(true) {
+        let post_read_form = PostReadForm {
+          post_id: id,
+          user_id,
+        };
+        let mark_as_read = move |conn: &'_ _| PostRead::mark_as_read(conn, &post_read_form);
+        if blocking(pool, mark_as_read).await?.is_err() {
+          return Err(APIError::err("couldnt_mark_post_as_read").into());
+        }
+        post_view.read = Some(true);
+      }
+    }
+
     let id = data.id;
-    let comments = blocking(pool, move |conn| {
-      CommentQueryBuilder::create(conn)
+    let comment_depth = data.comment_depth;
+    let comment_limit = data.comment_limit;
+    let limited = comment_depth.is_some() || comment_limit.is_some();
+    let (comments, more_replies) = blocking(pool, move |conn| {
+      let comments = CommentQueryBuilder::create(conn)
         .for_post_id(id)
+        .max_depth(comment_depth)
+        .breadth_first(limited)
         .my_user_id(user_id)
-        .limit(9999)
-        .list()
+        .limit(comment_limit.unwrap_or(9999))
+        .list()?;
+      let more_replies = if limited {
+        more_replies(conn, id, &comments, None, Vec::new())?
+      } else {
+        Vec::new()
+      };
+      Ok((comments, more_replies)) as Result<_, LemmyError>
     })
     .await??;
+    let comment_ids = comments.iter().map(|c| c.id).collect();
+    let collapsed_comment_ids = collapsed_comment_ids(pool, user_id, comment_ids).await?;
+
+    let watching = match user_id {
+      Some(user_id) => {
+        let id = data.id;
+        blocking(pool, move |conn| {
+          PostSubscription::is_subscribed(conn, id, user_id)
+        })
+        .await??
+      }
+      None => false,
+    };
 
     let community_id = post_view.community_id;
     let community = blocking(pool, move |conn| {
@@ -311,6 +589,9 @@ impl Perform for Oper<GetPost> {
     let creator_user = admins.remove(creator_index);
     admins.insert(0, creator_user);
 
+    let id = data.id;
+    let flair = blocking(pool, move |conn| CommunityFlair::for_post(conn, id).ok()).await?;
+
     let online = if let Some(ws) = websocket_info {
       if let Some(id) = ws.id {
         ws.chatserver.do_send(JoinPostRoom {
@@ -319,12 +600,10 @@ impl Perform for Oper<GetPost> {
         });
       }
 
-      // TODO
-      1
-    // let fut = async {
-    //   ws.chatserver.send(GetPostUsersOnline {post_id: data.id}).await.unwrap()
-    // };
-    // Runtime::new().unwrap().block_on(fut)
+      // After the join, so that a logged in reader counts too
+      ws.chatserver
+        .send(GetPostUsersOnline { post_id: data.id })
+        .await?
     } else {
       0
     };
@@ -333,9 +612,13 @@ impl Perform for Oper<GetPost> {
     Ok(GetPostResponse {
       post: post_view,
       comments,
+      more_replies,
+      collapsed_comment_ids,
+      watching,
       community,
       moderators,
       admins,
+      flair,
       online,
     })
   }
@@ -370,6 +653,16 @@ impl Perform for Oper<GetPosts> {
       None => false,
     };
 
+    let hide_read_posts = match &user_claims {
+      Some(claims) => claims.hide_read_posts,
+      None => false,
+    };
+
+    let hide_bot_accounts = match &user_claims {
+      Some(claims) => claims.hide_bot_accounts,
+      None => false,
+    };
+
     let type_ = ListingType::from_str(&data.type_)?;
     let sort = SortType::from_str(&data.sort)?;
 
@@ -377,14 +670,44 @@ impl Perform for Oper<GetPosts> {
     let limit = data.limit;
     let community_id = data.community_id;
     let community_name = data.community_name.to_owned();
-    let posts = match blocking(pool, move |conn| {
+    let flair_id = data.flair_id;
+    let show_hidden = data.show_hidden.unwrap_or(false);
+    let cursor = decode_cursor(&data.cursor)?;
+
+    // Logged out visitors all see the same listings
+    let cache_key = if user_id.is_none() && !show_hidden {
+      Some(PostListingKey {
+        type_: data.type_.to_owned(),
+        sort: data.sort.to_owned(),
+        page,
+        limit,
+        community_id,
+        community_name: community_name.to_owned(),
+        flair_id,
+        cursor: data.cursor.to_owned(),
+      })
+    } else {
+      None
+    };
+    if let Some(posts) = cache_key.as_ref().and_then(get_post_listing) {
+      join_all_community_room(data, websocket_info);
+      return GetPostsResponse::new(posts, limit);
+    }
+
+    let posts = match blocking_read(pool, move |conn| {
       PostQueryBuilder::create(conn)
         .listing_type(type_)
         .sort(&sort)
         .show_nsfw(show_nsfw)
         .for_community_id(community_id)
         .for_community_name(community_name)
+        .for_flair_id(flair_id)
+        .hidden_only(show_hidden)
+        // Reviewing the hidden posts should show all of them
+        .unread_only(hide_read_posts && !show_hidden)
+        .hide_bot_accounts(hide_bot_accounts)
         .my_user_id(user_id)
+        .cursor(cursor)
         .page(page)
         .limit(limit)
         .list()
@@ -395,21 +718,31 @@ impl Perform for Oper<GetPosts> {
       Err(_e) => return Err(APIError::err("couldnt_get_posts").into()),
     };
 
-    if let Some(ws) = websocket_info {
-      // You don't need to join the specific community room, bc this is already handled by
-      // GetCommunity
-      if data.community_id.is_none() {
-        if let Some(id) = ws.id {
-          // 0 is the "all" community
-          ws.chatserver.do_send(JoinCommunityRoom {
-            community_id: 0,
-            id,
-          });
-        }
-      }
+    if let Some(cache_key) = cache_key {
+      set_post_listing(cache_key, posts.to_owned());
     }
 
-    Ok(GetPostsResponse { posts })
+    join_all_community_room(data, websocket_info);
+
+    GetPostsResponse::new(posts, limit)?
+      .collapse_muted(pool, user_id)
+      .await
+  }
+}
+
+fn join_all_community_room(data: &GetPosts, websocket_info: Option<WebsocketInfo>) {
+  if let Some(ws) = websocket_info {
+    // You don't need to join the specific community room, bc this is already handled by
+    // GetCommunity
+    if data.community_id.is_none() {
+      if let Some(id) = ws.id {
+        // 0 is the "all" community
+        ws.chatserver.do_send(JoinCommunityRoom {
+          community_id: 0,
+          id,
+        });
+      }
+    }
   }
 }
 
@@ -452,7 +785,7 @@ impl Perform for Oper<CreatePostLike> {
 
     // Check for a site ban
     let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
-    if user.banned {
+    if user.is_banned() {
       return Err(APIError::err("site_ban").into());
     }
 
@@ -519,16 +852,6 @@ impl Perform for Oper<EditPost> {
   ) -> Result<PostResponse, LemmyError> {
     let data: &EditPost = &self.data;
 
-    if let Err(slurs) = slur_check(&data.name) {
-      return Err(APIError::err(&slurs_vec_to_str(slurs)).into());
-    }
-
-    if let Some(body) = &data.body {
-      if let Err(slurs) = slur_check(body) {
-        return Err(APIError::err(&slurs_vec_to_str(slurs)).into());
-      }
-    }
-
     if !is_valid_post_title(&data.name) {
       return Err(APIError::err("invalid_post_title").into());
     }
@@ -543,6 +866,17 @@ impl Perform for Oper<EditPost> {
     let edit_id = data.edit_id;
     let read_post = blocking(pool, move |conn| Post::read(conn, edit_id)).await??;
 
+    // Everything below checks what the hooks let through
+    let hooked = pre_edit_post(&read_post, data).await?;
+    let data = &hooked;
+
+    let name = check_words(&data.name, read_post.community_id)?;
+    let body = data
+      .body
+      .as_deref()
+      .map(|body| check_words(body, read_post.community_id))
+      .transpose()?;
+
     // Verify its the creator or a mod or admin
     let community_id = read_post.community_id;
     let mut editors: Vec<i32> = vec![read_post.creator_id];
@@ -578,57 +912,111 @@ impl Perform for Oper<EditPost> {
 
     // Check for a site ban
     let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
-    if user.banned {
+    if user.is_banned() {
       return Err(APIError::err("site_ban").into());
     }
 
+    let community_id = read_post.community_id;
+    let community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
+
+    // The flair is only touched when the edit picks one or clears it, as the mod actions come
+    // through here too
+    let clear_flair = data.clear_flair == Some(true) && data.flair_id.is_none();
+    if community.require_flair {
+      if clear_flair {
+        return Err(APIError::err("flair_required").into());
+      }
+      // A post from before the flair was required gets one once its creator edits it. The mods
+      // can still lock or remove it as it is.
+      let post_id = data.edit_id;
+      let has_flair = data.flair_id.is_some()
+        || blocking(pool, move |conn| {
+          CommunityFlair::for_post(conn, post_id).is_ok()
+        })
+        .await?;
+      if !has_flair && !moderators.contains(&user_id) {
+        return Err(APIError::err("flair_required").into());
+      }
+    }
+    check_flair_in_community(pool, data.flair_id, read_post.community_id).await?;
+    check_url_domain_allowed(pool, read_post.community_id, &data.url).await?;
+
+    if read_post.url != data.url {
+      check_post_type_allowed(&community, &data.url)?;
+    }
+
+    // Adding a link to a text post needs what posting a link does
+    if read_post.url.is_none() && data.url.is_some() {
+      check_posting_requirements(pool, user_id, read_post.community_id, true).await?;
+    }
+
+    let mod_removed = data.removed == Some(true) && moderators.contains(&user_id);
+    if mod_removed {
+      check_removal_reason(pool, &data.reason).await?;
+    }
+
     // Fetch Iframely and Pictrs cached image
-    let (iframely_title, iframely_description, iframely_html, pictrs_thumbnail) =
-      fetch_iframely_and_pictrs_data(&self.client, data.url.to_owned()).await;
+    let (iframely_title, iframely_description, iframely_html, embed_type, pictrs_thumbnail) =
+      fetch_iframely_and_pictrs_data(&self.client, pool, data.url.to_owned()).await;
+    let url_media_type = attachment_media_type(pool, &data.url).await?;
+
+    // An edited post already went out, so it's marked rather than held back. Only the mods can
+    // say it's safe anyway.
+    let nsfw_flagged = nsfw_image_action(pool, &data.url, &pictrs_thumbnail, data.nsfw)
+      .await?
+      .is_some();
+    let nsfw_overridden = nsfw_flagged && moderators.contains(&user_id);
+    let nsfw = data.nsfw || (nsfw_flagged && !nsfw_overridden);
 
     let post_form = {
       // only modify some properties if they are a moderator
       if moderators.contains(&user_id) {
         PostForm {
-          name: data.name.trim().to_owned(),
+          name: name.text.trim().to_owned(),
           url: data.url.to_owned(),
-          body: data.body.to_owned(),
+          body: body.as_ref().map(|b| b.text.to_owned()),
           creator_id: read_post.creator_id.to_owned(),
           community_id: read_post.community_id,
           removed: data.removed.to_owned(),
           deleted: data.deleted.to_owned(),
-          nsfw: data.nsfw,
+          nsfw,
           locked: data.locked.to_owned(),
           stickied: data.stickied.to_owned(),
           updated: Some(naive_now()),
           embed_title: iframely_title,
           embed_description: iframely_description,
           embed_html: iframely_html,
+          embed_type,
+          url_media_type,
           thumbnail_url: pictrs_thumbnail,
           ap_id: read_post.ap_id,
           local: read_post.local,
           published: None,
+          pending: None,
         }
       } else {
         PostForm {
           name: read_post.name.trim().to_owned(),
           url: data.url.to_owned(),
-          body: data.body.to_owned(),
+          body: body.as_ref().map(|b| b.text.to_owned()),
           creator_id: read_post.creator_id.to_owned(),
           community_id: read_post.community_id,
           removed: Some(read_post.removed),
           deleted: data.deleted.to_owned(),
-          nsfw: data.nsfw,
+          nsfw,
           locked: Some(read_post.locked),
           stickied: Some(read_post.stickied),
           updated: Some(naive_now()),
           embed_title: iframely_title,
           embed_description: iframely_description,
           embed_html: iframely_html,
+          embed_type,
+          url_media_type,
           thumbnail_url: pictrs_thumbnail,
           ap_id: read_post.ap_id,
           local: read_post.local,
           published: None,
+          pending: None,
         }
       }
     };
@@ -648,8 +1036,33 @@ impl Perform for Oper<EditPost> {
       }
     };
 
+    if data.flair_id.is_some() || clear_flair {
+      let flair_id = data.flair_id;
+      let set_flair = move |conn: &'_ _| PostFlair::set(conn, edit_id, flair_id);
+      if blocking(pool, set_flair).await?.is_err() {
+        return Err(APIError::err("couldnt_update_post").into());
+      }
+    }
+
+    // Only mods get to change the title
+    if moderators.contains(&user_id) {
+      report_flagged_post(pool, &websocket_info, edit_id, &name.flagged).await?;
+    }
+    if let Some(body) = &body {
+      report_flagged_post(pool, &websocket_info, edit_id, &body.flagged).await?;
+    }
+
     if moderators.contains(&user_id) {
       // Mod tables
+      if nsfw_overridden && read_post.nsfw {
+        let form = ModNsfwPostForm {
+          mod_user_id: user_id,
+          post_id: data.edit_id,
+          nsfw: Some(false),
+        };
+        blocking(pool, move |conn| ModNsfwPost::create(conn, &form)).await??;
+      }
+
       if let Some(removed) = data.removed.to_owned() {
         let form = ModRemovePostForm {
           mod_user_id: user_id,
@@ -679,26 +1092,31 @@ impl Perform for Oper<EditPost> {
       }
     }
 
-    if let Some(deleted) = data.deleted.to_owned() {
-      if deleted {
-        updated_post.send_delete(&user, &self.client, pool).await?;
-      } else {
-        updated_post
-          .send_undo_delete(&user, &self.client, pool)
-          .await?;
-      }
-    } else if let Some(removed) = data.removed.to_owned() {
-      if moderators.contains(&user_id) {
-        if removed {
-          updated_post.send_remove(&user, &self.client, pool).await?;
+    // Posts waiting for approval haven't federated yet
+    if !updated_post.pending {
+      if let Some(deleted) = data.deleted.to_owned() {
+        if deleted {
+          updated_post.send_delete(&user, &self.client, pool).await?;
         } else {
           updated_post
-            .send_undo_remove(&user, &self.client, pool)
+            .send_undo_delete(&user, &self.client, pool)
             .await?;
         }
+      } else if let Some(removed) = data.removed.to_owned() {
+        if moderators.contains(&user_id) {
+          if removed {
+            updated_post
+              .send_remove(&user, data.reason.to_owned(), &self.client, pool)
+              .await?;
+          } else {
+            updated_post
+              .send_undo_remove(&user, &self.client, pool)
+              .await?;
+          }
+        }
+      } else {
+        updated_post.send_update(&user, &self.client, pool).await?;
       }
-    } else {
-      updated_post.send_update(&user, &self.client, pool).await?;
     }
 
     let edit_id = data.edit_id;
@@ -709,12 +1127,54 @@ impl Perform for Oper<EditPost> {
 
     let res = PostResponse { post: post_view };
 
+    // Let the creator know when a mod removes or restores their post
+    let creator_id = read_post.creator_id;
+    let notify_creator = data.removed.is_some()
+      && moderators.contains(&user_id)
+      && creator_id != user_id
+      && blocking(pool, move |conn| {
+        wants_notification(conn, creator_id, |s| s.mod_actions)
+      })
+      .await?;
+
+    if mod_removed {
+      let action = format!(
+        "Your post [{}](/post/{}) was removed.",
+        read_post.name, read_post.id
+      );
+      send_mod_action_notice(
+        pool,
+        &self.client,
+        websocket_info.clone(),
+        &user,
+        creator_id,
+        &action,
+        &data.reason,
+      )
+      .await?;
+    }
+
     if let Some(ws) = websocket_info {
-      ws.chatserver.do_send(SendPost {
-        op: UserOperation::EditPost,
-        post: res.clone(),
-        my_id: ws.id,
-      });
+      // A post waiting for approval isn't in the community's feed yet
+      if !updated_post.pending {
+        ws.chatserver.do_send(SendPost {
+          op: UserOperation::EditPost,
+          post: res.clone(),
+          my_id: ws.id,
+        });
+      }
+
+      if notify_creator {
+        let mut creator_res = res.clone();
+        creator_res.post.my_vote = None;
+        creator_res.post.user_id = None;
+        ws.chatserver.do_send(SendUserRoomMessage {
+          op: UserOperation::EditPost,
+          response: creator_res,
+          recipient_id: creator_id,
+          my_id: ws.id,
+        });
+      }
     }
 
     Ok(res)
@@ -765,3 +1225,290 @@ impl Perform for Oper<SavePost> {
     Ok(PostResponse { post: post_view })
   }
 }
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<HidePost> {
+  type Response = PostResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<PostResponse, LemmyError> {
+    let data: &HidePost = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let post_hide_form = PostHideForm {
+      post_id: data.post_id,
+      user_id,
+    };
+
+    if data.hide {
+      let hide = move |conn: &'_ _| PostHide::hide(conn, &post_hide_form);
+      if blocking(pool, hide).await?.is_err() {
+        return Err(APIError::err("couldnt_hide_post").into());
+      }
+    } else {
+      let unhide = move |conn: &'_ _| PostHide::unhide(conn, &post_hide_form);
+      if blocking(pool, unhide).await?.is_err() {
+        return Err(APIError::err("couldnt_hide_post").into());
+      }
+    }
+
+    let post_id = data.post_id;
+    let post_view = blocking(pool, move |conn| {
+      PostView::read(conn, post_id, Some(user_id))
+    })
+    .await??;
+
+    Ok(PostResponse { post: post_view })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<WatchPost> {
+  type Response = WatchPostResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<WatchPostResponse, LemmyError> {
+    let data: &WatchPost = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let post_id = data.post_id;
+    let post_view = match blocking(pool, move |conn| {
+      PostView::read(conn, post_id, Some(user_id))
+    })
+    .await?
+    {
+      Ok(post) => post,
+      Err(_e) => return Err(APIError::err("couldnt_find_post").into()),
+    };
+
+    let subscription_form = PostSubscriptionForm { post_id, user_id };
+
+    if data.watch {
+      let watch = move |conn: &'_ _| PostSubscription::follow(conn, &subscription_form);
+      if blocking(pool, watch).await?.is_err() {
+        return Err(APIError::err("couldnt_watch_post").into());
+      }
+    } else {
+      let unwatch = move |conn: &'_ _| PostSubscription::unfollow(conn, &subscription_form);
+      if blocking(pool, unwatch).await?.is_err() {
+        return Err(APIError::err("couldnt_watch_post").into());
+      }
+    }
+
+    Ok(WatchPostResponse {
+      post: post_view,
+      watching: data.watch,
+    })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetPostRevisions> {
+  type Response = GetPostRevisionsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<GetPostRevisionsResponse, LemmyError> {
+    let data: &GetPostRevisions = &self.data;
+
+    let user_id: Option<i32> = match &data.auth {
+      Some(auth) => match Claims::decode(&auth) {
+        Ok(claims) => Some(claims.claims.id),
+        Err(_e) => None,
+      },
+      None => None,
+    };
+
+    let post_id = data.post_id;
+    let post = match blocking(pool, move |conn| Post::read(conn, post_id)).await? {
+      Ok(post) => post,
+      Err(_e) => return Err(APIError::err("couldnt_find_post").into()),
+    };
+
+    if !can_view_edit_history(pool, post.creator_id, post.community_id, user_id).await? {
+      return Err(APIError::err("no_edit_history_allowed").into());
+    }
+
+    let revisions = blocking(pool, move |conn| PostRevision::for_post(conn, post_id)).await??;
+
+    Ok(GetPostRevisionsResponse { revisions })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<RefreshPostEmbed> {
+  type Response = PostResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<PostResponse, LemmyError> {
+    let data: &RefreshPostEmbed = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let post_id = data.post_id;
+    let post = match blocking(pool, move |conn| Post::read(conn, post_id)).await? {
+      Ok(post) => post,
+      Err(_e) => return Err(APIError::err("couldnt_find_post").into()),
+    };
+
+    if post.url.is_none() {
+      return Err(APIError::err("post_has_no_url").into());
+    }
+
+    if post.creator_id == user_id {
+      let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+      if user.is_banned() {
+        return Err(APIError::err("site_ban").into());
+      }
+    } else {
+      check_community_mod_or_admin(pool, user_id, post.community_id).await?;
+    }
+
+    let updated_post = match refresh_post_embed(pool, &self.client, &post).await? {
+      Some(updated_post) => updated_post,
+      None => return Err(APIError::err("couldnt_fetch_embed").into()),
+    };
+
+    let post_view = blocking(pool, move |conn| {
+      PostView::read(conn, post_id, Some(user_id))
+    })
+    .await??;
+
+    let res = PostResponse { post: post_view };
+
+    if let Some(ws) = websocket_info {
+      // A post waiting for approval isn't in the community's feed yet
+      if !updated_post.pending {
+        ws.chatserver.do_send(SendPost {
+          op: UserOperation::RefreshPostEmbed,
+          post: res.clone(),
+          my_id: ws.id,
+        });
+      }
+    }
+
+    Ok(res)
+  }
+}
+
+/// Unless the site makes edit history public, only the creator, the community's mods and the
+/// admins can see it.
+pub async fn can_view_edit_history(
+  pool: &DbPool,
+  creator_id: i32,
+  community_id: i32,
+  user_id: Option<i32>,
+) -> Result<bool, LemmyError> {
+  let public_edit_history = match blocking(pool, move |conn| SiteView::read(conn)).await? {
+    Ok(site) => site.public_edit_history,
+    Err(_e) => true,
+  };
+
+  if public_edit_history {
+    return Ok(true);
+  }
+
+  let user_id = match user_id {
+    Some(user_id) => user_id,
+    None => return Ok(false),
+  };
+
+  if user_id == creator_id {
+    return Ok(true);
+  }
+
+  let mut moderators: Vec<i32> = blocking(pool, move |conn| {
+    CommunityModeratorView::for_community(conn, community_id)
+      .map(|v| v.into_iter().map(|m| m.user_id).collect())
+  })
+  .await??;
+  moderators.append(
+    &mut blocking(pool, move |conn| {
+      UserView::admins(conn).map(|v| v.into_iter().map(|a| a.id).collect())
+    })
+    .await??,
+  );
+
+  Ok(moderators.contains(&user_id))
+}
+
+/// The subscribers of the post's community who want to be told about new posts, besides its creator.
+pub async fn subscribed_post_recipients(
+  pool: &DbPool,
+  post: &PostView,
+) -> Result<Vec<i32>, LemmyError> {
+  let community_id = post.community_id;
+  let creator_id = post.creator_id;
+  let subscriber_ids = blocking(pool, move |conn| {
+    UserNotificationSettings::subscribed_post_recipients(conn, community_id)
+  })
+  .await??;
+
+  Ok(
+    subscriber_ids
+      .into_iter()
+      .filter(|subscriber_id| *subscriber_id != creator_id)
+      .collect(),
+  )
+}
+
+pub async fn check_flair_in_community(
+  pool: &DbPool,
+  flair_id: Option<i32>,
+  community_id: i32,
+) -> Result<(), LemmyError> {
+  if let Some(flair_id) = flair_id {
+    let flair = match blocking(pool, move |conn| CommunityFlair::read(conn, flair_id)).await? {
+      Ok(flair) => flair,
+      Err(_e) => return Err(APIError::err("couldnt_find_community_flair").into()),
+    };
+    if flair.community_id != community_id {
+      return Err(APIError::err("couldnt_find_community_flair").into());
+    }
+  }
+
+  Ok(())
+}
+
+/// Some communities only take links, images or text posts.
+pub fn check_post_type_allowed(
+  community: &Community,
+  url: &Option<String>,
+) -> Result<(), LemmyError> {
+  let is_image = url.as_deref().map_or(false, is_image_url);
+  let allowed = AllowedPostTypes::from_i16(community.allowed_post_types)
+    .map_or(true, |types| types.allows(url.is_some(), is_image));
+  if !allowed {
+    return Err(APIError::err("post_type_not_allowed").into());
+  }
+
+  Ok(())
+}
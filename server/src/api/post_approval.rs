@@ -0,0 +1,268 @@
+use crate::{
+  api::{
+    claims::Claims,
+    community::{check_community_mod_or_admin, check_removal_reason, send_to_community_mods},
+    post::{subscribed_post_recipients, PostResponse},
+    user::send_mod_action_notice,
+    APIError,
+    Oper,
+    Perform,
+  },
+  apub::{ApubLikeableType, ApubObjectType},
+  blocking,
+  nsfw::nsfw_image_action,
+  webhook::queue_new_post_webhooks,
+  websocket::{
+    server::{SendPost, SendPostToSubscribers},
+    UserOperation,
+    WebsocketInfo,
+  },
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::{community::*, moderator::*, post::*, post_view::*, user::*, Crud, SortType};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The mod queue of a community, newest first.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ListPendingPosts {
+  community_id: i32,
+  page: Option<i64>,
+  limit: Option<i64>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ListPendingPostsResponse {
+  posts: Vec<PostView>,
+}
+
+/// Approves a pending post, or rejects it, which removes it.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ApprovePost {
+  post_id: i32,
+  approved: bool,
+  reason: Option<String>,
+  auth: String,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ListPendingPosts> {
+  type Response = ListPendingPostsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListPendingPostsResponse, LemmyError> {
+    let data: &ListPendingPosts = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    check_community_mod_or_admin(pool, user_id, data.community_id).await?;
+
+    let community_id = data.community_id;
+    let page = data.page;
+    let limit = data.limit;
+    let posts = blocking(pool, move |conn| {
+      PostQueryBuilder::create(conn)
+        .sort(&SortType::New)
+        .for_community_id(community_id)
+        .my_user_id(user_id)
+        .pending_only(true)
+        .page(page)
+        .limit(limit)
+        .list()
+    })
+    .await??;
+
+    Ok(ListPendingPostsResponse { posts })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ApprovePost> {
+  type Response = PostResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<PostResponse, LemmyError> {
+    let data: &ApprovePost = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let post_id = data.post_id;
+    let read_post = blocking(pool, move |conn| Post::read(conn, post_id)).await??;
+    check_community_mod_or_admin(pool, user_id, read_post.community_id).await?;
+
+    if !read_post.pending {
+      return Err(APIError::err("post_not_pending").into());
+    }
+
+    if !data.approved {
+      check_removal_reason(pool, &data.reason).await?;
+    }
+
+    let approved = data.approved;
+    let resolve = move |conn: &'_ _| Post::resolve_pending(conn, post_id, approved);
+    let updated_post = match blocking(pool, resolve).await? {
+      Ok(post) => post,
+      Err(_e) => return Err(APIError::err("couldnt_update_post").into()),
+    };
+
+    let mod_user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+    let creator_id = updated_post.creator_id;
+    let creator = blocking(pool, move |conn| User_::read(conn, creator_id)).await??;
+
+    // Approving a post the classifier held back, without marking it NSFW, overrides it
+    let nsfw_overridden = approved
+      && nsfw_image_action(
+        pool,
+        &updated_post.url,
+        &updated_post.thumbnail_url,
+        updated_post.nsfw,
+      )
+      .await?
+      .is_some();
+    if nsfw_overridden {
+      let form = ModNsfwPostForm {
+        mod_user_id: user_id,
+        post_id,
+        nsfw: Some(false),
+      };
+      blocking(pool, move |conn| ModNsfwPost::create(conn, &form)).await??;
+    }
+
+    if approved {
+      // It goes out the way it would have when it was posted
+      updated_post
+        .send_create(&creator, &self.client, pool)
+        .await?;
+      updated_post.send_like(&creator, &self.client, pool).await?;
+    } else {
+      let form = ModRemovePostForm {
+        mod_user_id: user_id,
+        post_id,
+        reason: data.reason.to_owned(),
+        removed: Some(true),
+      };
+      blocking(pool, move |conn| ModRemovePost::create(conn, &form)).await??;
+    }
+
+    let action = format!(
+      "Your post [{}](/post/{}) was {}.",
+      updated_post.name,
+      updated_post.id,
+      if approved { "approved" } else { "rejected" }
+    );
+    send_mod_action_notice(
+      pool,
+      &self.client,
+      websocket_info.clone(),
+      &mod_user,
+      creator_id,
+      &action,
+      &data.reason,
+    )
+    .await?;
+
+    let post_view = blocking(pool, move |conn| {
+      PostView::read(conn, post_id, Some(user_id))
+    })
+    .await??;
+
+    let res = PostResponse { post: post_view };
+
+    if approved && !creator.shadow_banned {
+      queue_new_post_webhooks(pool, &res.post).await?;
+    }
+
+    // An approved post shows up live, like a new one would
+    if let Some(ws) = websocket_info.filter(|_| approved && !creator.shadow_banned) {
+      let creator_view = blocking(pool, move |conn| {
+        PostView::read(conn, post_id, Some(creator_id))
+      })
+      .await??;
+      let creator_res = PostResponse { post: creator_view };
+
+      ws.chatserver.do_send(SendPost {
+        op: UserOperation::CreatePost,
+        post: creator_res.clone(),
+        my_id: ws.id,
+      });
+
+      let subscriber_ids = subscribed_post_recipients(pool, &creator_res.post).await?;
+      if !subscriber_ids.is_empty() {
+        ws.chatserver.do_send(SendPostToSubscribers {
+          op: UserOperation::CreatePost,
+          post: creator_res,
+          subscriber_ids,
+          my_id: ws.id,
+        });
+      }
+    }
+
+    Ok(res)
+  }
+}
+
+/// Whether a new post of the user has to wait for a mod to approve it. The community's mods and
+/// the admins are never held back.
+pub async fn needs_approval(
+  pool: &DbPool,
+  community: &Community,
+  user_id: i32,
+) -> Result<bool, LemmyError> {
+  match PostApprovalMode::from_i16(community.post_approval) {
+    Some(PostApprovalMode::All) => {}
+    Some(PostApprovalMode::NewPosters) => {
+      let community_id = community.id;
+      let approved = blocking(pool, move |conn| {
+        Post::has_approved_post(conn, community_id, user_id)
+      })
+      .await??;
+      if approved {
+        return Ok(false);
+      }
+    }
+    Some(PostApprovalMode::Off) | None => return Ok(false),
+  }
+
+  Ok(
+    check_community_mod_or_admin(pool, user_id, community.id)
+      .await
+      .is_err(),
+  )
+}
+
+/// Lets the mods of the community know live that a post is waiting for them. It goes out as a new
+/// post, which the mods can tell apart by it being pending.
+pub async fn send_pending_post_to_mods(
+  pool: &DbPool,
+  websocket_info: &Option<WebsocketInfo>,
+  res: &PostResponse,
+) -> Result<(), LemmyError> {
+  let mut mod_res = res.clone();
+  mod_res.post.my_vote = None;
+  mod_res.post.user_id = None;
+  send_to_community_mods(
+    pool,
+    websocket_info,
+    res.post.community_id,
+    UserOperation::CreatePost,
+    mod_res,
+  )
+  .await
+}
@@ -0,0 +1,157 @@
+use crate::{
+  api::{claims::Claims, community::check_community_mod_or_admin, APIError, Oper, Perform},
+  blocking,
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::{naive_now, posting_requirement::*, user::*, user_view::*, Crud};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Reads the site wide requirements, or the ones of a community.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetPostingRequirements {
+  community_id: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SetPostingRequirements {
+  community_id: Option<i32>,
+  min_account_age_days: i32,
+  min_comments: i32,
+  min_comment_score: i32,
+  links_only: bool,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct PostingRequirementsResponse {
+  requirements: Option<PostingRequirement>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetPostingRequirements> {
+  type Response = PostingRequirementsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<PostingRequirementsResponse, LemmyError> {
+    let data: &GetPostingRequirements = &self.data;
+
+    let community_id = data.community_id;
+    let requirements = blocking(pool, move |conn| {
+      PostingRequirement::read_for(conn, community_id).ok()
+    })
+    .await?;
+
+    Ok(PostingRequirementsResponse { requirements })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<SetPostingRequirements> {
+  type Response = PostingRequirementsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<PostingRequirementsResponse, LemmyError> {
+    let data: &SetPostingRequirements = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    // The score can be negative, to only hold back accounts that got downvoted
+    if data.min_account_age_days < 0 || data.min_comments < 0 {
+      return Err(APIError::err("invalid_posting_requirements").into());
+    }
+
+    let user_id = claims.id;
+    match data.community_id {
+      Some(community_id) => check_community_mod_or_admin(pool, user_id, community_id).await?,
+      None => {
+        let user = blocking(pool, move |conn| UserView::read(conn, user_id)).await??;
+        if !user.admin {
+          return Err(APIError::err("not_an_admin").into());
+        }
+      }
+    }
+
+    let requirement_form = PostingRequirementForm {
+      community_id: data.community_id,
+      min_account_age_days: data.min_account_age_days,
+      min_comments: data.min_comments,
+      min_comment_score: data.min_comment_score,
+      links_only: data.links_only,
+      updated: None,
+    };
+
+    let requirements = match blocking(pool, move |conn| {
+      PostingRequirement::set(conn, &requirement_form)
+    })
+    .await?
+    {
+      Ok(requirements) => requirements,
+      Err(_e) => return Err(APIError::err("couldnt_update_posting_requirements").into()),
+    };
+
+    Ok(PostingRequirementsResponse {
+      requirements: Some(requirements),
+    })
+  }
+}
+
+/// Holds back new accounts from posting in the community, until they meet the site's and the
+/// community's requirements. Admins and the community's mods are never held back.
+pub async fn check_posting_requirements(
+  pool: &DbPool,
+  user_id: i32,
+  community_id: i32,
+  is_link: bool,
+) -> Result<(), LemmyError> {
+  let requirements = blocking(pool, move |conn| {
+    PostingRequirement::applicable(conn, community_id)
+  })
+  .await??;
+
+  let requirements: Vec<PostingRequirement> = requirements
+    .into_iter()
+    .filter(|r| is_link || !r.links_only)
+    .collect();
+  if requirements.is_empty() {
+    return Ok(());
+  }
+
+  if check_community_mod_or_admin(pool, user_id, community_id)
+    .await
+    .is_ok()
+  {
+    return Ok(());
+  }
+
+  let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+  let user_view = blocking(pool, move |conn| UserView::read(conn, user_id)).await??;
+  let account_age_days = (naive_now() - user.published).num_days();
+
+  for requirement in &requirements {
+    if account_age_days < requirement.min_account_age_days.into() {
+      return Err(APIError::err("account_too_new_to_post").into());
+    }
+
+    if user_view.number_of_comments < requirement.min_comments.into() {
+      return Err(APIError::err("not_enough_comments_to_post").into());
+    }
+
+    if user_view.comment_score < requirement.min_comment_score.into() {
+      return Err(APIError::err("comment_score_too_low_to_post").into());
+    }
+  }
+
+  Ok(())
+}
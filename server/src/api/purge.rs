@@ -0,0 +1,351 @@
+use crate::{
+  api::{check_admin, claims::Claims, community::check_removal_reason, APIError, Oper, Perform},
+  apub::{
+    comment::comment_delete_activity,
+    community::{community_delete_activity, do_announce, send_community_delete},
+    post::post_delete_activity,
+    ApubObjectType,
+  },
+  blocking,
+  cache::{invalidate_post_listings, invalidate_site},
+  delete_pictrs_image,
+  media::{delete_linked_media, delete_media},
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use activitystreams::{Activity, Base};
+use actix_web::client::Client;
+use lemmy_db::{comment::*, community::*, media::*, post::*, purge::*, user::*, Crud};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use tracing::error;
+
+/// Deletes the user for good, with everything they posted. Unlike a ban, or a removal, there is
+/// no undoing it. The communities they created are handed to the admin.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PurgeUser {
+  user_id: i32,
+  reason: Option<String>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PurgePost {
+  post_id: i32,
+  reason: Option<String>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PurgeComment {
+  comment_id: i32,
+  reason: Option<String>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PurgeCommunity {
+  community_id: i32,
+  reason: Option<String>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct PurgeResponse {}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<PurgeUser> {
+  type Response = PurgeResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<PurgeResponse, LemmyError> {
+    let data: &PurgeUser = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    check_admin(pool, user_id).await?;
+    check_removal_reason(pool, &data.reason).await?;
+
+    let purged_user_id = data.user_id;
+    let purged_user = blocking(pool, move |conn| User_::read(conn, purged_user_id)).await??;
+    // This also keeps the site creator safe
+    if purged_user.admin {
+      return Err(APIError::err("cant_purge_admin").into());
+    }
+
+    // Once the rows are gone there is nothing left to build the activities from, so they're built
+    // now, and only sent if the purge goes through
+    let (posts, comments) = blocking(pool, move |conn| {
+      local_content_of_user(conn, purged_user_id)
+    })
+    .await??;
+    let mut post_deletes = Vec::new();
+    for post in &posts {
+      match post_delete_activity(post, &purged_user, pool).await {
+        Ok((delete, community)) => post_deletes.push((post.id, delete, community)),
+        Err(e) => error!("Couldn't federate purge of post {}: {}", post.id, e),
+      }
+    }
+    let mut comment_deletes = Vec::new();
+    for comment in &comments {
+      match comment_delete_activity(comment, &purged_user, pool).await {
+        Ok((delete, community)) => comment_deletes.push((comment.id, delete, community)),
+        Err(e) => error!("Couldn't federate purge of comment {}: {}", comment.id, e),
+      }
+    }
+
+    // The uploads go with the user, so the tokens to delete them are read first
+    let media = blocking(pool, move |conn| Media::for_user(conn, purged_user_id)).await??;
+
+    let reason = data.reason.to_owned();
+    let purge = move |conn: &'_ _| purge_user(conn, user_id, purged_user_id, reason);
+    let thumbnails = match blocking(pool, purge).await? {
+      Ok(thumbnails) => thumbnails,
+      Err(_e) => return Err(APIError::err("couldnt_purge").into()),
+    };
+
+    // The user is gone, so nothing can be signed or recorded in their name anymore. The local
+    // communities still pass the Deletes on to their followers, but the ones in remote communities
+    // stay here.
+    if !purged_user.shadow_banned {
+      for (post_id, delete, community) in post_deletes.into_iter().filter(|d| d.2.local) {
+        if let Err(e) = announce_purge(delete, community.id, &purged_user, &self.client, pool).await
+        {
+          error!("Couldn't federate purge of post {}: {}", post_id, e);
+        }
+      }
+      for (comment_id, delete, community) in comment_deletes.into_iter().filter(|d| d.2.local) {
+        if let Err(e) = announce_purge(delete, community.id, &purged_user, &self.client, pool).await
+        {
+          error!("Couldn't federate purge of comment {}: {}", comment_id, e);
+        }
+      }
+    }
+
+    delete_thumbnails(pool, &self.client, thumbnails).await?;
+    delete_media(pool, &self.client, media).await?;
+    invalidate_post_listings(None);
+    invalidate_site();
+
+    Ok(PurgeResponse {})
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<PurgePost> {
+  type Response = PurgeResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<PurgeResponse, LemmyError> {
+    let data: &PurgePost = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    check_admin(pool, user_id).await?;
+    check_removal_reason(pool, &data.reason).await?;
+
+    let post_id = data.post_id;
+    let read_post = blocking(pool, move |conn| Post::read(conn, post_id)).await??;
+
+    let reason = data.reason.to_owned();
+    let purge = move |conn: &'_ _| purge_post(conn, user_id, post_id, reason);
+    let thumbnails = match blocking(pool, purge).await? {
+      Ok(thumbnails) => thumbnails,
+      Err(_e) => return Err(APIError::err("couldnt_purge").into()),
+    };
+
+    // The creator and the community are still there to build the Delete from
+    if read_post.local {
+      let creator_id = read_post.creator_id;
+      let creator = blocking(pool, move |conn| User_::read(conn, creator_id)).await??;
+      if let Err(e) = read_post.send_delete(&creator, &self.client, pool).await {
+        error!("Couldn't federate purge of post {}: {}", post_id, e);
+      }
+    }
+
+    delete_thumbnails(pool, &self.client, thumbnails).await?;
+    delete_linked_media(
+      pool,
+      &self.client,
+      read_post.creator_id,
+      &[read_post.url.clone(), read_post.body.clone()],
+    )
+    .await?;
+    invalidate_post_listings(Some(read_post.community_id));
+
+    Ok(PurgeResponse {})
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<PurgeComment> {
+  type Response = PurgeResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<PurgeResponse, LemmyError> {
+    let data: &PurgeComment = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    check_admin(pool, user_id).await?;
+    check_removal_reason(pool, &data.reason).await?;
+
+    let comment_id = data.comment_id;
+    let read_comment = blocking(pool, move |conn| Comment::read(conn, comment_id)).await??;
+
+    let reason = data.reason.to_owned();
+    let purge = move |conn: &'_ _| purge_comment(conn, user_id, comment_id, reason);
+    if blocking(pool, purge).await?.is_err() {
+      return Err(APIError::err("couldnt_purge").into());
+    }
+
+    if read_comment.local {
+      let creator_id = read_comment.creator_id;
+      let creator = blocking(pool, move |conn| User_::read(conn, creator_id)).await??;
+      if let Err(e) = read_comment.send_delete(&creator, &self.client, pool).await {
+        error!("Couldn't federate purge of comment {}: {}", comment_id, e);
+      }
+    }
+
+    delete_linked_media(
+      pool,
+      &self.client,
+      read_comment.creator_id,
+      &[Some(read_comment.content.clone())],
+    )
+    .await?;
+
+    Ok(PurgeResponse {})
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<PurgeCommunity> {
+  type Response = PurgeResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<PurgeResponse, LemmyError> {
+    let data: &PurgeCommunity = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    check_admin(pool, user_id).await?;
+    check_removal_reason(pool, &data.reason).await?;
+
+    let community_id = data.community_id;
+    let read_community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
+
+    // The followers are told the community is gone, which takes its posts with it. They go with
+    // the purge too, so the Delete is built first, and only sent if the purge goes through.
+    let mut delete = None;
+    if read_community.local {
+      let creator_id = read_community.creator_id;
+      let creator = blocking(pool, move |conn| User_::read(conn, creator_id)).await??;
+      match community_delete_activity(&read_community, &creator, pool).await {
+        Ok((activity, inboxes)) => delete = Some((creator, activity, inboxes)),
+        Err(e) => error!(
+          "Couldn't federate purge of community {}: {}",
+          community_id, e
+        ),
+      }
+    }
+
+    let reason = data.reason.to_owned();
+    let purge = move |conn: &'_ _| purge_community(conn, user_id, community_id, reason);
+    let thumbnails = match blocking(pool, purge).await? {
+      Ok(thumbnails) => thumbnails,
+      Err(_e) => return Err(APIError::err("couldnt_purge").into()),
+    };
+
+    if let Some((creator, activity, inboxes)) = delete {
+      if let Err(e) =
+        send_community_delete(&read_community, &creator, activity, inboxes, pool).await
+      {
+        error!(
+          "Couldn't federate purge of community {}: {}",
+          community_id, e
+        );
+      }
+    }
+
+    delete_thumbnails(pool, &self.client, thumbnails).await?;
+    invalidate_post_listings(None);
+
+    Ok(PurgeResponse {})
+  }
+}
+
+/// Passes the Delete of a purged user's post or comment on to the followers of the local community
+/// it was in. The community is read again, as the purge may have handed it to the admin.
+async fn announce_purge<A>(
+  delete: A,
+  community_id: i32,
+  purged_user: &User_,
+  client: &Client,
+  pool: &DbPool,
+) -> Result<(), LemmyError>
+where
+  A: Activity + Base + Serialize + Debug,
+{
+  let community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
+  do_announce(delete, &community, purged_user, client, pool).await?;
+  Ok(())
+}
+
+/// Takes the thumbnails of purged posts down from pictrs. Only the ones lemmy kept a delete token
+/// for can go, and one failing doesn't stop the rest.
+async fn delete_thumbnails(
+  pool: &DbPool,
+  client: &Client,
+  files: Vec<String>,
+) -> Result<(), LemmyError> {
+  if files.is_empty() {
+    return Ok(());
+  }
+
+  let thumbnails = blocking(pool, move |conn| PictrsThumbnail::for_files(conn, files)).await??;
+  for thumbnail in thumbnails {
+    if let Err(e) = delete_pictrs_image(client, &thumbnail.file, &thumbnail.delete_token).await {
+      error!("Couldn't delete thumbnail {}: {}", thumbnail.file, e);
+      continue;
+    }
+
+    let thumbnail_id = thumbnail.id;
+    blocking(pool, move |conn| {
+      PictrsThumbnail::delete(conn, thumbnail_id)
+    })
+    .await??;
+  }
+
+  Ok(())
+}
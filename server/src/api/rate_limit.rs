@@ -0,0 +1,254 @@
+use crate::{
+  api::{check_admin, claims::Claims, APIError, Oper, Perform},
+  blocking,
+  rate_limit::load_rate_limit_overrides,
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::{rate_limit::*, site::*, user::*, Crud};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetRateLimits {
+  auth: String,
+}
+
+/// Replaces the site's limits. The ones left out go back to the config file's.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct EditRateLimits {
+  limits: SiteRateLimits,
+  auth: String,
+}
+
+/// Gives the endpoint its own limit of actions per the given seconds.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SetEndpointRateLimit {
+  endpoint: String,
+  actions: i32,
+  per_second: i32,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct RemoveEndpointRateLimit {
+  endpoint: String,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ExemptFromRateLimits {
+  user_id: i32,
+  exempt: bool,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct RateLimitsResponse {
+  limits: SiteRateLimits,
+  endpoints: Vec<RateLimitEndpoint>,
+  exemptions: Vec<RateLimitExemption>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetRateLimits> {
+  type Response = RateLimitsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<RateLimitsResponse, LemmyError> {
+    let data: &GetRateLimits = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_admin(pool, claims.id).await?;
+
+    read_rate_limits(pool).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<EditRateLimits> {
+  type Response = RateLimitsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<RateLimitsResponse, LemmyError> {
+    let data: &EditRateLimits = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_admin(pool, claims.id).await?;
+
+    let limits = data.limits.to_owned();
+    let all_limits = [
+      limits.rate_limit_message,
+      limits.rate_limit_message_per_second,
+      limits.rate_limit_post,
+      limits.rate_limit_post_per_second,
+      limits.rate_limit_register,
+      limits.rate_limit_register_per_second,
+      limits.rate_limit_report,
+      limits.rate_limit_report_per_second,
+    ];
+    if all_limits.iter().flatten().any(|l| *l < 1) {
+      return Err(APIError::err("invalid_rate_limit").into());
+    }
+
+    let update_limits = move |conn: &'_ _| Site::update_rate_limits(conn, 1, &limits);
+    if blocking(pool, update_limits).await?.is_err() {
+      return Err(APIError::err("couldnt_update_site").into());
+    }
+
+    read_rate_limits(pool).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<SetEndpointRateLimit> {
+  type Response = RateLimitsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<RateLimitsResponse, LemmyError> {
+    let data: &SetEndpointRateLimit = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    check_admin(pool, user_id).await?;
+
+    let endpoint = data.endpoint.trim().to_owned();
+    if endpoint.is_empty() || endpoint.chars().count() > 100 {
+      return Err(APIError::err("invalid_rate_limit_endpoint").into());
+    }
+
+    if data.actions < 1 || data.per_second < 1 {
+      return Err(APIError::err("invalid_rate_limit").into());
+    }
+
+    let endpoint_form = RateLimitEndpointForm {
+      endpoint,
+      actions: data.actions,
+      per_second: data.per_second,
+      creator_id: user_id,
+    };
+
+    let set = move |conn: &'_ _| RateLimitEndpoint::set(conn, &endpoint_form);
+    if blocking(pool, set).await?.is_err() {
+      return Err(APIError::err("couldnt_update_site").into());
+    }
+
+    read_rate_limits(pool).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<RemoveEndpointRateLimit> {
+  type Response = RateLimitsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<RateLimitsResponse, LemmyError> {
+    let data: &RemoveEndpointRateLimit = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_admin(pool, claims.id).await?;
+
+    let endpoint = data.endpoint.trim().to_owned();
+    let remove = move |conn: &'_ _| RateLimitEndpoint::remove(conn, &endpoint);
+    if blocking(pool, remove).await?.is_err() {
+      return Err(APIError::err("couldnt_update_site").into());
+    }
+
+    read_rate_limits(pool).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ExemptFromRateLimits> {
+  type Response = RateLimitsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<RateLimitsResponse, LemmyError> {
+    let data: &ExemptFromRateLimits = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    check_admin(pool, user_id).await?;
+
+    let exempt_user_id = data.user_id;
+    if blocking(pool, move |conn| User_::read(conn, exempt_user_id))
+      .await?
+      .is_err()
+    {
+      return Err(APIError::err("couldnt_find_that_username_or_email").into());
+    }
+
+    let exempt = data.exempt;
+    let exemption_form = RateLimitExemptionForm {
+      user_id: exempt_user_id,
+      creator_id: user_id,
+    };
+
+    let update_exemption = move |conn: &'_ _| {
+      if exempt {
+        RateLimitExemption::exempt(conn, &exemption_form)
+      } else {
+        RateLimitExemption::unexempt(conn, exempt_user_id)
+      }
+    };
+    if blocking(pool, update_exemption).await?.is_err() {
+      return Err(APIError::err("couldnt_update_user").into());
+    }
+
+    read_rate_limits(pool).await
+  }
+}
+
+/// Also swaps the new limits into the rate limiter, so they apply right away.
+async fn read_rate_limits(pool: &DbPool) -> Result<RateLimitsResponse, LemmyError> {
+  let (limits, endpoints, exemptions) = blocking(pool, move |conn| {
+    load_rate_limit_overrides(conn)?;
+    let limits = Site::read(conn, 1)?.rate_limits();
+    let endpoints = RateLimitEndpoint::list(conn)?;
+    let exemptions = RateLimitExemption::list(conn)?;
+    Ok((limits, endpoints, exemptions)) as Result<_, LemmyError>
+  })
+  .await??;
+
+  Ok(RateLimitsResponse {
+    limits,
+    endpoints,
+    exemptions,
+  })
+}
@@ -0,0 +1,452 @@
+use crate::{
+  api::{
+    claims::Claims,
+    community::{check_community_mod_or_admin, send_to_community_mods},
+    APIError,
+    Oper,
+    Perform,
+  },
+  blocking,
+  webhook::queue_webhooks,
+  websocket::{UserOperation, WebsocketInfo},
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::{
+  comment::*,
+  community_rule::*,
+  community_view::*,
+  moderator::*,
+  post::*,
+  report::*,
+  report_view::*,
+  user_view::*,
+  webhook::WebhookEvent,
+  Crud,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The longest reason a report can give
+const MAX_REPORT_REASON_LENGTH: usize = 1000;
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CreatePostReport {
+  post_id: i32,
+  reason: String,
+  rule_id: Option<i32>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct PostReportResponse {
+  report: PostReportView,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CreateCommentReport {
+  comment_id: i32,
+  reason: String,
+  rule_id: Option<i32>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct CommentReportResponse {
+  report: CommentReportView,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ListReports {
+  community_id: Option<i32>,
+  unresolved_only: bool,
+  page: Option<i64>,
+  limit: Option<i64>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ListReportsResponse {
+  posts: Vec<PostReportView>,
+  comments: Vec<CommentReportView>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ResolvePostReport {
+  report_id: i32,
+  resolved: bool,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ResolveCommentReport {
+  report_id: i32,
+  resolved: bool,
+  auth: String,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<CreatePostReport> {
+  type Response = PostReportResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<PostReportResponse, LemmyError> {
+    let data: &CreatePostReport = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_valid_report_reason(&data.reason)?;
+
+    let user_id = claims.id;
+
+    let post_id = data.post_id;
+    let post = match blocking(pool, move |conn| Post::read(conn, post_id)).await? {
+      Ok(post) => post,
+      Err(_e) => return Err(APIError::err("couldnt_find_post").into()),
+    };
+
+    check_report_rule(pool, data.rule_id, post.community_id).await?;
+
+    let report_form = PostReportForm {
+      creator_id: user_id,
+      post_id,
+      reason: data.reason.trim().to_owned(),
+      rule_id: data.rule_id,
+    };
+
+    let inserted_report =
+      match blocking(pool, move |conn| PostReport::create(conn, &report_form)).await? {
+        Ok(report) => report,
+        Err(_e) => return Err(APIError::err("couldnt_create_report").into()),
+      };
+
+    let report_id = inserted_report.id;
+    let report = blocking(pool, move |conn| PostReportView::read(conn, report_id)).await??;
+    let res = PostReportResponse { report };
+
+    let community_id = res.report.community_id;
+    queue_webhooks(
+      pool,
+      WebhookEvent::NewReport,
+      Some(community_id),
+      &res.report,
+    )
+    .await?;
+
+    send_to_community_mods(
+      pool,
+      &websocket_info,
+      community_id,
+      UserOperation::CreatePostReport,
+      res.clone(),
+    )
+    .await?;
+
+    Ok(res)
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<CreateCommentReport> {
+  type Response = CommentReportResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<CommentReportResponse, LemmyError> {
+    let data: &CreateCommentReport = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_valid_report_reason(&data.reason)?;
+
+    let user_id = claims.id;
+
+    let comment_id = data.comment_id;
+    let comment = match blocking(pool, move |conn| Comment::read(conn, comment_id)).await? {
+      Ok(comment) => comment,
+      Err(_e) => return Err(APIError::err("couldnt_find_comment").into()),
+    };
+
+    let post_id = comment.post_id;
+    let post = blocking(pool, move |conn| Post::read(conn, post_id)).await??;
+
+    check_report_rule(pool, data.rule_id, post.community_id).await?;
+
+    let report_form = CommentReportForm {
+      creator_id: user_id,
+      comment_id,
+      reason: data.reason.trim().to_owned(),
+      rule_id: data.rule_id,
+    };
+
+    let inserted_report =
+      match blocking(pool, move |conn| CommentReport::create(conn, &report_form)).await? {
+        Ok(report) => report,
+        Err(_e) => return Err(APIError::err("couldnt_create_report").into()),
+      };
+
+    let report_id = inserted_report.id;
+    let report = blocking(pool, move |conn| CommentReportView::read(conn, report_id)).await??;
+    let res = CommentReportResponse { report };
+
+    let community_id = res.report.community_id;
+    queue_webhooks(
+      pool,
+      WebhookEvent::NewReport,
+      Some(community_id),
+      &res.report,
+    )
+    .await?;
+
+    send_to_community_mods(
+      pool,
+      &websocket_info,
+      community_id,
+      UserOperation::CreateCommentReport,
+      res.clone(),
+    )
+    .await?;
+
+    Ok(res)
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ListReports> {
+  type Response = ListReportsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListReportsResponse, LemmyError> {
+    let data: &ListReports = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    // Without a community, admins get the whole site and mods get the communities they moderate
+    let community_ids = match data.community_id {
+      Some(community_id) => {
+        check_community_mod_or_admin(pool, user_id, community_id).await?;
+        Some(vec![community_id])
+      }
+      None => {
+        let user = blocking(pool, move |conn| UserView::read(conn, user_id)).await??;
+        if user.admin {
+          None
+        } else {
+          let moderated = blocking(pool, move |conn| {
+            CommunityModeratorView::for_user(conn, user_id)
+          })
+          .await??;
+          if moderated.is_empty() {
+            return Err(APIError::err("not_a_moderator").into());
+          }
+          Some(moderated.into_iter().map(|m| m.community_id).collect())
+        }
+      }
+    };
+
+    let post_community_ids = community_ids.to_owned();
+    let unresolved_only = data.unresolved_only;
+    let page = data.page;
+    let limit = data.limit;
+    let posts = blocking(pool, move |conn| {
+      PostReportView::list(conn, post_community_ids, unresolved_only, page, limit)
+    })
+    .await??;
+
+    let comments = blocking(pool, move |conn| {
+      CommentReportView::list(conn, community_ids, unresolved_only, page, limit)
+    })
+    .await??;
+
+    Ok(ListReportsResponse { posts, comments })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ResolvePostReport> {
+  type Response = PostReportResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<PostReportResponse, LemmyError> {
+    let data: &ResolvePostReport = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let report_id = data.report_id;
+    let report = match blocking(pool, move |conn| PostReportView::read(conn, report_id)).await? {
+      Ok(report) => report,
+      Err(_e) => return Err(APIError::err("couldnt_find_report").into()),
+    };
+
+    check_community_mod_or_admin(pool, user_id, report.community_id).await?;
+
+    let resolved = data.resolved;
+    let resolve = move |conn: &'_ _| PostReport::resolve(conn, report_id, user_id, resolved);
+    if blocking(pool, resolve).await?.is_err() {
+      return Err(APIError::err("couldnt_resolve_report").into());
+    }
+
+    // Mod tables
+    let form = ModResolvePostReportForm {
+      mod_user_id: user_id,
+      report_id,
+      resolved: Some(resolved),
+    };
+    blocking(pool, move |conn| ModResolvePostReport::create(conn, &form)).await??;
+
+    let report = blocking(pool, move |conn| PostReportView::read(conn, report_id)).await??;
+
+    Ok(PostReportResponse { report })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ResolveCommentReport> {
+  type Response = CommentReportResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<CommentReportResponse, LemmyError> {
+    let data: &ResolveCommentReport = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let report_id = data.report_id;
+    let report = match blocking(pool, move |conn| CommentReportView::read(conn, report_id)).await? {
+      Ok(report) => report,
+      Err(_e) => return Err(APIError::err("couldnt_find_report").into()),
+    };
+
+    check_community_mod_or_admin(pool, user_id, report.community_id).await?;
+
+    let resolved = data.resolved;
+    let resolve = move |conn: &'_ _| CommentReport::resolve(conn, report_id, user_id, resolved);
+    if blocking(pool, resolve).await?.is_err() {
+      return Err(APIError::err("couldnt_resolve_report").into());
+    }
+
+    // Mod tables
+    let form = ModResolveCommentReportForm {
+      mod_user_id: user_id,
+      report_id,
+      resolved: Some(resolved),
+    };
+    blocking(pool, move |conn| {
+      ModResolveCommentReport::create(conn, &form)
+    })
+    .await??;
+
+    let report = blocking(pool, move |conn| CommentReportView::read(conn, report_id)).await??;
+
+    Ok(CommentReportResponse { report })
+  }
+}
+
+/// Lets the mods know about a report that was filed automatically, like the ones of automod
+/// and the word filter.
+pub async fn send_post_report_to_mods(
+  pool: &DbPool,
+  websocket_info: &Option<WebsocketInfo>,
+  report_id: i32,
+) -> Result<(), LemmyError> {
+  let report = blocking(pool, move |conn| PostReportView::read(conn, report_id)).await??;
+  let community_id = report.community_id;
+  queue_webhooks(pool, WebhookEvent::NewReport, Some(community_id), &report).await?;
+
+  if websocket_info.is_none() {
+    return Ok(());
+  }
+
+  send_to_community_mods(
+    pool,
+    websocket_info,
+    community_id,
+    UserOperation::CreatePostReport,
+    PostReportResponse { report },
+  )
+  .await
+}
+
+/// Same as `send_post_report_to_mods`, for comments.
+pub async fn send_comment_report_to_mods(
+  pool: &DbPool,
+  websocket_info: &Option<WebsocketInfo>,
+  report_id: i32,
+) -> Result<(), LemmyError> {
+  let report = blocking(pool, move |conn| CommentReportView::read(conn, report_id)).await??;
+  let community_id = report.community_id;
+  queue_webhooks(pool, WebhookEvent::NewReport, Some(community_id), &report).await?;
+
+  if websocket_info.is_none() {
+    return Ok(());
+  }
+
+  send_to_community_mods(
+    pool,
+    websocket_info,
+    community_id,
+    UserOperation::CreateCommentReport,
+    CommentReportResponse { report },
+  )
+  .await
+}
+
+fn check_valid_report_reason(reason: &str) -> Result<(), LemmyError> {
+  let reason_len = reason.trim().chars().count();
+  if reason_len == 0 || reason_len > MAX_REPORT_REASON_LENGTH {
+    return Err(APIError::err("invalid_report_reason").into());
+  }
+
+  Ok(())
+}
+
+/// A report can only point at one of the rules of the community it was filed in.
+async fn check_report_rule(
+  pool: &DbPool,
+  rule_id: Option<i32>,
+  community_id: i32,
+) -> Result<(), LemmyError> {
+  if let Some(rule_id) = rule_id {
+    let rule = blocking(pool, move |conn| CommunityRule::read(conn, rule_id)).await?;
+    match rule {
+      Ok(rule) if rule.community_id == community_id => (),
+      _ => return Err(APIError::err("invalid_report_rule").into()),
+    }
+  }
+
+  Ok(())
+}
@@ -0,0 +1,277 @@
+use crate::{
+  api::{
+    claims::Claims,
+    post::{check_flair_in_community, CreatePost},
+    APIError,
+    Oper,
+    Perform,
+  },
+  blocking,
+  job_queue::{enqueue_at, Task},
+  websocket::{server::SendUserRoomMessage, UserOperation, WebsocketInfo},
+  DbPool,
+  LemmyError,
+};
+use actix_web::client::Client;
+use diesel::result::Error::NotFound;
+use lemmy_db::{community::*, community_view::*, naive_now, scheduled_post::*, user::*, Crud};
+use lemmy_utils::{is_valid_post_title, naive_from_unix, settings::Settings};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+/// The most posts a single user can have waiting
+const MAX_SCHEDULED_POSTS: usize = 50;
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ListScheduledPosts {
+  auth: String,
+}
+
+/// A post to put up later, at publish_at. It goes through everything a new post does then, so one
+/// that wouldn't be let through by that time doesn't go up.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CreateScheduledPost {
+  name: String,
+  url: Option<String>,
+  body: Option<String>,
+  nsfw: bool,
+  community_id: i32,
+  flair_id: Option<i32>,
+  publish_at: i64,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeleteScheduledPost {
+  scheduled_post_id: i32,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ScheduledPostsResponse {
+  scheduled_posts: Vec<ScheduledPost>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ListScheduledPosts> {
+  type Response = ScheduledPostsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ScheduledPostsResponse, LemmyError> {
+    let data: &ListScheduledPosts = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    list_scheduled_posts(pool, claims.id).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<CreateScheduledPost> {
+  type Response = ScheduledPostsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ScheduledPostsResponse, LemmyError> {
+    let data: &CreateScheduledPost = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    if !is_valid_post_title(&data.name) {
+      return Err(APIError::err("invalid_post_title").into());
+    }
+
+    let publish_at = naive_from_unix(data.publish_at);
+    if publish_at <= naive_now() {
+      return Err(APIError::err("invalid_publish_time").into());
+    }
+
+    // The checks that can be made this early, so the post doesn't fail only once it's due
+    let user_id = claims.id;
+    let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+    if user.is_banned() {
+      return Err(APIError::err("site_ban").into());
+    }
+
+    let community_id = data.community_id;
+    if blocking(pool, move |conn| Community::read(conn, community_id))
+      .await?
+      .is_err()
+    {
+      return Err(APIError::err("couldnt_find_community").into());
+    }
+    let is_banned =
+      move |conn: &'_ _| CommunityUserBanView::get(conn, user_id, community_id).is_ok();
+    if blocking(pool, is_banned).await? {
+      return Err(APIError::err("community_ban").into());
+    }
+    check_flair_in_community(pool, data.flair_id, community_id).await?;
+
+    // The ones that failed are only kept to show the error
+    let list = move |conn: &'_ _| ScheduledPost::list_for_creator(conn, user_id);
+    let waiting = blocking(pool, list)
+      .await??
+      .iter()
+      .filter(|scheduled_post| scheduled_post.error.is_none())
+      .count();
+    if waiting >= MAX_SCHEDULED_POSTS {
+      return Err(APIError::err("too_many_scheduled_posts").into());
+    }
+
+    let scheduled_post_form = ScheduledPostForm {
+      creator_id: user_id,
+      community_id,
+      name: data.name.trim().to_owned(),
+      url: data.url.to_owned(),
+      body: data.body.to_owned(),
+      nsfw: data.nsfw,
+      flair_id: data.flair_id,
+      publish_at,
+    };
+    let create = move |conn: &'_ _| ScheduledPost::create(conn, &scheduled_post_form);
+    let scheduled_post = match blocking(pool, create).await? {
+      Ok(scheduled_post) => scheduled_post,
+      Err(_e) => return Err(APIError::err("couldnt_save_scheduled_post").into()),
+    };
+
+    let scheduled_post_id = scheduled_post.id;
+    let task = Task::PublishScheduledPost { scheduled_post_id };
+    if let Err(e) = enqueue_at(pool, task, publish_at).await {
+      let delete = move |conn: &'_ _| ScheduledPost::delete(conn, scheduled_post_id);
+      blocking(pool, delete).await??;
+      return Err(e);
+    }
+
+    list_scheduled_posts(pool, user_id).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<DeleteScheduledPost> {
+  type Response = ScheduledPostsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ScheduledPostsResponse, LemmyError> {
+    let data: &DeleteScheduledPost = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    let scheduled_post_id = data.scheduled_post_id;
+    let read = move |conn: &'_ _| ScheduledPost::read(conn, scheduled_post_id);
+    let scheduled_post = match blocking(pool, read).await? {
+      Ok(scheduled_post) => scheduled_post,
+      Err(_e) => return Err(APIError::err("couldnt_find_scheduled_post").into()),
+    };
+    if scheduled_post.creator_id != user_id {
+      return Err(APIError::err("no_scheduled_post_edit_allowed").into());
+    }
+
+    // Its job finds it gone, and leaves it at that
+    let delete = move |conn: &'_ _| ScheduledPost::delete(conn, scheduled_post_id);
+    if blocking(pool, delete).await?.is_err() {
+      return Err(APIError::err("couldnt_delete_scheduled_post").into());
+    }
+
+    list_scheduled_posts(pool, user_id).await
+  }
+}
+
+/// Puts up a scheduled post that's due, as its creator. One that's gone was cancelled.
+pub async fn publish_scheduled_post(
+  pool: &DbPool,
+  client: &Client,
+  scheduled_post_id: i32,
+  websocket_info: Option<WebsocketInfo>,
+) -> Result<(), LemmyError> {
+  let read = move |conn: &'_ _| ScheduledPost::read(conn, scheduled_post_id);
+  let scheduled_post = match blocking(pool, read).await? {
+    Ok(scheduled_post) => scheduled_post,
+    Err(NotFound) => return Ok(()),
+    Err(e) => return Err(e.into()),
+  };
+
+  // An earlier attempt got it up before failing, so it mustn't go up twice
+  if scheduled_post.post_id.is_none() {
+    let creator_id = scheduled_post.creator_id;
+    let creator = blocking(pool, move |conn| User_::read(conn, creator_id)).await??;
+    let create_post = CreatePost {
+      name: scheduled_post.name,
+      url: scheduled_post.url,
+      body: scheduled_post.body,
+      nsfw: scheduled_post.nsfw,
+      community_id: scheduled_post.community_id,
+      flair_id: scheduled_post.flair_id,
+      auth: Claims::jwt(creator, Settings::get().hostname),
+      scheduled_post_id: Some(scheduled_post_id),
+    };
+    Oper::new(create_post, client.to_owned())
+      .perform(pool, websocket_info)
+      .await?;
+  }
+
+  let delete = move |conn: &'_ _| ScheduledPost::delete(conn, scheduled_post_id);
+  if let Err(e) = blocking(pool, delete).await? {
+    error!(
+      "Couldn't remove scheduled post {} once it was up: {}",
+      scheduled_post_id, e
+    );
+  }
+  Ok(())
+}
+
+/// Keeps a scheduled post that ran out of attempts with the error, and lets its creator know. It
+/// doesn't count towards their scheduled posts anymore, and they can delete it.
+pub async fn fail_scheduled_post(
+  pool: &DbPool,
+  scheduled_post_id: i32,
+  failure: String,
+  websocket_info: Option<WebsocketInfo>,
+) -> Result<(), LemmyError> {
+  let fail = move |conn: &'_ _| ScheduledPost::fail(conn, scheduled_post_id, &failure);
+  let scheduled_post = match blocking(pool, fail).await? {
+    Ok(scheduled_post) => scheduled_post,
+    Err(NotFound) => return Ok(()),
+    Err(e) => return Err(e.into()),
+  };
+
+  if let Some(ws) = websocket_info {
+    let creator_id = scheduled_post.creator_id;
+    ws.chatserver.do_send(SendUserRoomMessage {
+      op: UserOperation::ListScheduledPosts,
+      response: list_scheduled_posts(pool, creator_id).await?,
+      recipient_id: creator_id,
+      my_id: None,
+    });
+  }
+
+  Ok(())
+}
+
+async fn list_scheduled_posts(
+  pool: &DbPool,
+  user_id: i32,
+) -> Result<ScheduledPostsResponse, LemmyError> {
+  let list = move |conn: &'_ _| ScheduledPost::list_for_creator(conn, user_id);
+  let scheduled_posts = blocking(pool, list).await??;
+
+  Ok(ScheduledPostsResponse { scheduled_posts })
+}
@@ -1,52 +1,65 @@
 use super::user::Register;
 use crate::{
-  api::{claims::Claims, APIError, Oper, Perform},
+  api::{claims::Claims, community::check_community_settings, APIError, Oper, Perform},
   apub::fetcher::search_by_apub_id,
   blocking,
+  blocking_read,
+  cache::{get_site, invalidate_site, set_site},
   websocket::{server::SendAllMessage, UserOperation, WebsocketInfo},
   DbPool,
   LemmyError,
 };
+use diesel::PgConnection;
 use lemmy_db::{
   category::*,
   comment_view::*,
   community_view::*,
+  limit_and_offset,
   moderator::*,
   moderator_views::*,
   naive_now,
   post_view::*,
+  set_hide_downvote_counts,
   site::*,
+  site_announcement::*,
+  site_document::*,
   site_view::*,
   user_view::*,
   Crud,
   SearchType,
   SortType,
 };
-use lemmy_utils::{settings::Settings, slur_check, slurs_vec_to_str};
-use log::{debug, info};
+use lemmy_utils::{naive_from_unix, settings::Settings, slur_check, slurs_vec_to_str};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
+use tracing::{debug, info};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct ListCategories {}
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct ListCategoriesResponse {
   categories: Vec<Category>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct Search {
   q: String,
   type_: String,
   community_id: Option<i32>,
+  creator_id: Option<i32>,
+  published_after: Option<i64>,
+  published_before: Option<i64>,
+  url_domain: Option<String>,
+  nsfw: Option<bool>,
   sort: String,
   page: Option<i64>,
   limit: Option<i64>,
   auth: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct SearchResponse {
   pub type_: String,
   pub comments: Vec<CommentView>,
@@ -55,15 +68,41 @@ pub struct SearchResponse {
   pub users: Vec<UserView>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetModlog {
   mod_user_id: Option<i32>,
   community_id: Option<i32>,
+  other_user_id: Option<i32>,
+  action_type: Option<ModlogActionType>,
+  after: Option<i64>,
+  before: Option<i64>,
+  cursor: Option<chrono::NaiveDateTime>,
   page: Option<i64>,
   limit: Option<i64>,
+  auth: Option<String>,
+}
+
+/// Which of the modlog lists to return.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+pub enum ModlogActionType {
+  RemovePost,
+  LockPost,
+  StickyPost,
+  RemoveComment,
+  RemoveCommunity,
+  BanFromCommunity,
+  Ban,
+  AddCommunityMod,
+  TransferCommunity,
+  AddAdmin,
+  ResolvePostReport,
+  ResolveCommentReport,
+  ShadowBan,
+  Purge,
+  NsfwPost,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetModlogResponse {
   removed_posts: Vec<ModRemovePostView>,
   locked_posts: Vec<ModLockPostView>,
@@ -73,67 +112,138 @@ pub struct GetModlogResponse {
   banned_from_community: Vec<ModBanFromCommunityView>,
   banned: Vec<ModBanView>,
   added_to_community: Vec<ModAddCommunityView>,
+  transferred_communities: Vec<ModTransferCommunityView>,
   added: Vec<ModAddView>,
+  resolved_post_reports: Vec<ModResolvePostReportView>,
+  resolved_comment_reports: Vec<ModResolveCommentReportView>,
+  shadow_banned: Vec<ModShadowBanView>,
+  purged: Vec<ModPurgeView>,
+  nsfw_posts: Vec<ModNsfwPostView>,
+  next_cursor: Option<chrono::NaiveDateTime>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct CreateSite {
   pub name: String,
   pub description: Option<String>,
   pub enable_downvotes: bool,
   pub open_registration: bool,
   pub enable_nsfw: bool,
+  pub require_email_verification: bool,
+  pub public_edit_history: bool,
+  pub archive_after_days: i32,
+  pub require_removal_reason: bool,
+  pub block_disposable_emails: bool,
+  pub ip_retention_days: i32,
+  pub registrations_per_ip_per_day: i32,
+  pub mod_inactive_days: i32,
+  pub hide_downvote_counts: bool,
   pub auth: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct EditSite {
   name: String,
   description: Option<String>,
   enable_downvotes: bool,
   open_registration: bool,
   enable_nsfw: bool,
+  require_email_verification: bool,
+  public_edit_history: bool,
+  archive_after_days: i32,
+  require_removal_reason: bool,
+  block_disposable_emails: bool,
+  ip_retention_days: i32,
+  registrations_per_ip_per_day: i32,
+  mod_inactive_days: i32,
+  hide_downvote_counts: bool,
   auth: String,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct GetSite {}
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetSite {
+  auth: Option<String>,
+}
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct SiteResponse {
   site: SiteView,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct GetSiteResponse {
   site: Option<SiteView>,
   admins: Vec<UserView>,
   banned: Vec<UserView>,
+  announcements: Vec<SiteAnnouncement>,
+  documents: Vec<SiteDocumentSummary>,
   pub online: usize,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CreateSiteAnnouncement {
+  content: String,
+  severity: i16,
+  start_time: Option<i64>,
+  end_time: Option<i64>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeleteSiteAnnouncement {
+  announcement_id: i32,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DismissSiteAnnouncement {
+  announcement_id: i32,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct SiteAnnouncementResponse {
+  announcement: SiteAnnouncement,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct TransferSite {
   user_id: i32,
   auth: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetSiteConfig {
   auth: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetSiteConfigResponse {
   config_hjson: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct SaveSiteConfig {
   config_hjson: String,
   auth: String,
 }
 
+/// Anyone can read them, for the form that creates a community.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetCommunityDefaults {}
+
+/// Only new communities get the defaults, the existing ones keep their settings.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct EditCommunityDefaults {
+  defaults: SiteCommunityDefaults,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct CommunityDefaultsResponse {
+  defaults: SiteCommunityDefaults,
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for Oper<ListCategories> {
   type Response = ListCategoriesResponse;
@@ -163,52 +273,214 @@ impl Perform for Oper<GetModlog> {
   ) -> Result<GetModlogResponse, LemmyError> {
     let data: &GetModlog = &self.data;
 
-    let community_id = data.community_id;
-    let mod_user_id = data.mod_user_id;
-    let page = data.page;
-    let limit = data.limit;
-    let removed_posts = blocking(pool, move |conn| {
-      ModRemovePostView::list(conn, community_id, mod_user_id, page, limit)
-    })
-    .await??;
+    let before = match (data.before.map(naive_from_unix), data.cursor) {
+      (Some(before), Some(cursor)) => Some(before.min(cursor)),
+      (before, cursor) => before.or(cursor),
+    };
 
-    let locked_posts = blocking(pool, move |conn| {
-      ModLockPostView::list(conn, community_id, mod_user_id, page, limit)
-    })
-    .await??;
+    let filter = ModlogFilter {
+      community_id: data.community_id,
+      mod_user_id: data.mod_user_id,
+      other_user_id: data.other_user_id,
+      after: data.after.map(naive_from_unix),
+      before,
+      page: data.page,
+      limit: data.limit,
+    };
 
-    let stickied_posts = blocking(pool, move |conn| {
-      ModStickyPostView::list(conn, community_id, mod_user_id, page, limit)
-    })
-    .await??;
+    // Lists of actions without a target user are left out when filtering by one, and the site
+    // wide lists are only for the full modlog, when a community isn't given
+    let action_type = data.action_type;
+    let has_other_user = data.other_user_id.is_some();
+    let has_community = data.community_id.is_some();
+    let wanted = move |t: ModlogActionType, targets_user: bool, site_wide: bool| {
+      action_type.map_or(true, |a| a == t)
+        && (targets_user || !has_other_user)
+        && (!site_wide || !has_community)
+    };
 
-    let removed_comments = blocking(pool, move |conn| {
-      ModRemoveCommentView::list(conn, community_id, mod_user_id, page, limit)
-    })
-    .await??;
+    let mut removed_posts = if wanted(ModlogActionType::RemovePost, true, false) {
+      let filter = filter.clone();
+      blocking_read(pool, move |conn| ModRemovePostView::list(conn, &filter)).await??
+    } else {
+      Vec::new()
+    };
 
-    let banned_from_community = blocking(pool, move |conn| {
-      ModBanFromCommunityView::list(conn, community_id, mod_user_id, page, limit)
-    })
-    .await??;
+    let mut locked_posts = if wanted(ModlogActionType::LockPost, false, false) {
+      let filter = filter.clone();
+      blocking_read(pool, move |conn| ModLockPostView::list(conn, &filter)).await??
+    } else {
+      Vec::new()
+    };
 
-    let added_to_community = blocking(pool, move |conn| {
-      ModAddCommunityView::list(conn, community_id, mod_user_id, page, limit)
-    })
-    .await??;
+    let mut nsfw_posts = if wanted(ModlogActionType::NsfwPost, true, false) {
+      let filter = filter.clone();
+      blocking_read(pool, move |conn| ModNsfwPostView::list(conn, &filter)).await??
+    } else {
+      Vec::new()
+    };
+
+    let mut stickied_posts = if wanted(ModlogActionType::StickyPost, false, false) {
+      let filter = filter.clone();
+      blocking_read(pool, move |conn| ModStickyPostView::list(conn, &filter)).await??
+    } else {
+      Vec::new()
+    };
+
+    let mut removed_comments = if wanted(ModlogActionType::RemoveComment, true, false) {
+      let filter = filter.clone();
+      blocking_read(pool, move |conn| ModRemoveCommentView::list(conn, &filter)).await??
+    } else {
+      Vec::new()
+    };
+
+    let mut removed_communities = if wanted(ModlogActionType::RemoveCommunity, false, true) {
+      let filter = filter.clone();
+      blocking_read(pool, move |conn| {
+        ModRemoveCommunityView::list(conn, &filter)
+      })
+      .await??
+    } else {
+      Vec::new()
+    };
+
+    let mut banned_from_community = if wanted(ModlogActionType::BanFromCommunity, true, false) {
+      let filter = filter.clone();
+      blocking_read(pool, move |conn| {
+        ModBanFromCommunityView::list(conn, &filter)
+      })
+      .await??
+    } else {
+      Vec::new()
+    };
+
+    let mut banned = if wanted(ModlogActionType::Ban, true, true) {
+      let filter = filter.clone();
+      blocking_read(pool, move |conn| ModBanView::list(conn, &filter)).await??
+    } else {
+      Vec::new()
+    };
+
+    let mut added_to_community = if wanted(ModlogActionType::AddCommunityMod, true, false) {
+      let filter = filter.clone();
+      blocking_read(pool, move |conn| ModAddCommunityView::list(conn, &filter)).await??
+    } else {
+      Vec::new()
+    };
 
-    // These arrays are only for the full modlog, when a community isn't given
-    let (removed_communities, banned, added) = if data.community_id.is_none() {
-      blocking(pool, move |conn| {
-        Ok((
-          ModRemoveCommunityView::list(conn, mod_user_id, page, limit)?,
-          ModBanView::list(conn, mod_user_id, page, limit)?,
-          ModAddView::list(conn, mod_user_id, page, limit)?,
-        )) as Result<_, LemmyError>
+    let mut transferred_communities = if wanted(ModlogActionType::TransferCommunity, true, false) {
+      let filter = filter.clone();
+      blocking_read(pool, move |conn| {
+        ModTransferCommunityView::list(conn, &filter)
       })
       .await??
     } else {
-      (Vec::new(), Vec::new(), Vec::new())
+      Vec::new()
+    };
+
+    let mut added = if wanted(ModlogActionType::AddAdmin, true, true) {
+      let filter = filter.clone();
+      blocking_read(pool, move |conn| ModAddView::list(conn, &filter)).await??
+    } else {
+      Vec::new()
+    };
+
+    let mut resolved_post_reports = if wanted(ModlogActionType::ResolvePostReport, false, false) {
+      let filter = filter.clone();
+      blocking_read(pool, move |conn| {
+        ModResolvePostReportView::list(conn, &filter)
+      })
+      .await??
+    } else {
+      Vec::new()
+    };
+
+    let mut resolved_comment_reports =
+      if wanted(ModlogActionType::ResolveCommentReport, false, false) {
+        let filter = filter.clone();
+        blocking_read(pool, move |conn| {
+          ModResolveCommentReportView::list(conn, &filter)
+        })
+        .await??
+      } else {
+        Vec::new()
+      };
+
+    // What got purged isn't named, so this is for everyone
+    let mut purged = if wanted(ModlogActionType::Purge, false, false) {
+      let filter = filter.clone();
+      blocking_read(pool, move |conn| ModPurgeView::list(conn, &filter)).await??
+    } else {
+      Vec::new()
+    };
+
+    // Shadow bans only work if nobody else can see them
+    let is_admin = match &data.auth {
+      Some(auth) => match Claims::decode(&auth) {
+        Ok(claims) => {
+          let user_id = claims.claims.id;
+          blocking(pool, move |conn| {
+            UserView::read(conn, user_id).map(|u| u.admin)
+          })
+          .await??
+        }
+        Err(_e) => false,
+      },
+      None => false,
+    };
+
+    let mut shadow_banned = if is_admin && wanted(ModlogActionType::ShadowBan, true, true) {
+      blocking_read(pool, move |conn| ModShadowBanView::list(conn, &filter)).await??
+    } else {
+      Vec::new()
+    };
+
+    // Without a page the modlog is keyset paged. Everything down to the cursor is complete, so
+    // newer entries are returned and the next page starts below it.
+    let next_cursor = if data.page.is_none() {
+      let (limit, _) = limit_and_offset(None, data.limit);
+      let cursor = vec![
+        oldest_if_full(&removed_posts, limit, |m| m.when_),
+        oldest_if_full(&locked_posts, limit, |m| m.when_),
+        oldest_if_full(&stickied_posts, limit, |m| m.when_),
+        oldest_if_full(&nsfw_posts, limit, |m| m.when_),
+        oldest_if_full(&removed_comments, limit, |m| m.when_),
+        oldest_if_full(&removed_communities, limit, |m| m.when_),
+        oldest_if_full(&banned_from_community, limit, |m| m.when_),
+        oldest_if_full(&banned, limit, |m| m.when_),
+        oldest_if_full(&added_to_community, limit, |m| m.when_),
+        oldest_if_full(&transferred_communities, limit, |m| m.when_),
+        oldest_if_full(&added, limit, |m| m.when_),
+        oldest_if_full(&resolved_post_reports, limit, |m| m.when_),
+        oldest_if_full(&resolved_comment_reports, limit, |m| m.when_),
+        oldest_if_full(&shadow_banned, limit, |m| m.when_),
+        oldest_if_full(&purged, limit, |m| m.when_),
+      ]
+      .into_iter()
+      .flatten()
+      .max();
+
+      if let Some(cursor) = cursor {
+        removed_posts.retain(|m| m.when_ >= cursor);
+        locked_posts.retain(|m| m.when_ >= cursor);
+        stickied_posts.retain(|m| m.when_ >= cursor);
+        nsfw_posts.retain(|m| m.when_ >= cursor);
+        removed_comments.retain(|m| m.when_ >= cursor);
+        removed_communities.retain(|m| m.when_ >= cursor);
+        banned_from_community.retain(|m| m.when_ >= cursor);
+        banned.retain(|m| m.when_ >= cursor);
+        added_to_community.retain(|m| m.when_ >= cursor);
+        transferred_communities.retain(|m| m.when_ >= cursor);
+        added.retain(|m| m.when_ >= cursor);
+        resolved_post_reports.retain(|m| m.when_ >= cursor);
+        resolved_comment_reports.retain(|m| m.when_ >= cursor);
+        shadow_banned.retain(|m| m.when_ >= cursor);
+        purged.retain(|m| m.when_ >= cursor);
+      }
+
+      cursor
+    } else {
+      None
     };
 
     // Return the jwt
@@ -221,7 +493,14 @@ impl Perform for Oper<GetModlog> {
       banned_from_community,
       banned,
       added_to_community,
+      transferred_communities,
       added,
+      resolved_post_reports,
+      resolved_comment_reports,
+      shadow_banned,
+      purged,
+      nsfw_posts,
+      next_cursor,
     })
   }
 }
@@ -252,6 +531,23 @@ impl Perform for Oper<CreateSite> {
       }
     }
 
+    if data.archive_after_days < 0 {
+      return Err(APIError::err("invalid_archive_age").into());
+    }
+
+    // The registration throttle counts the IPs of the past day, so they have to be kept that long
+    if data.ip_retention_days < 1 {
+      return Err(APIError::err("invalid_ip_retention").into());
+    }
+
+    if data.registrations_per_ip_per_day < 0 {
+      return Err(APIError::err("invalid_registration_limit").into());
+    }
+
+    if data.mod_inactive_days < 1 {
+      return Err(APIError::err("invalid_mod_inactive_days").into());
+    }
+
     let user_id = claims.id;
 
     // Make sure user is an admin
@@ -267,6 +563,15 @@ impl Perform for Oper<CreateSite> {
       enable_downvotes: data.enable_downvotes,
       open_registration: data.open_registration,
       enable_nsfw: data.enable_nsfw,
+      require_email_verification: data.require_email_verification,
+      public_edit_history: data.public_edit_history,
+      archive_after_days: data.archive_after_days,
+      require_removal_reason: data.require_removal_reason,
+      block_disposable_emails: data.block_disposable_emails,
+      ip_retention_days: data.ip_retention_days,
+      registrations_per_ip_per_day: data.registrations_per_ip_per_day,
+      mod_inactive_days: data.mod_inactive_days,
+      hide_downvote_counts: data.hide_downvote_counts,
       updated: None,
     };
 
@@ -275,6 +580,9 @@ impl Perform for Oper<CreateSite> {
       return Err(APIError::err("site_already_exists").into());
     }
 
+    blocking(pool, move |conn| load_vote_count_settings(conn)).await??;
+    invalidate_site();
+
     let site_view = blocking(pool, move |conn| SiteView::read(conn)).await??;
 
     Ok(SiteResponse { site: site_view })
@@ -306,6 +614,23 @@ impl Perform for Oper<EditSite> {
       }
     }
 
+    if data.archive_after_days < 0 {
+      return Err(APIError::err("invalid_archive_age").into());
+    }
+
+    // The registration throttle counts the IPs of the past day, so they have to be kept that long
+    if data.ip_retention_days < 1 {
+      return Err(APIError::err("invalid_ip_retention").into());
+    }
+
+    if data.registrations_per_ip_per_day < 0 {
+      return Err(APIError::err("invalid_registration_limit").into());
+    }
+
+    if data.mod_inactive_days < 1 {
+      return Err(APIError::err("invalid_mod_inactive_days").into());
+    }
+
     let user_id = claims.id;
 
     // Make sure user is an admin
@@ -324,6 +649,15 @@ impl Perform for Oper<EditSite> {
       enable_downvotes: data.enable_downvotes,
       open_registration: data.open_registration,
       enable_nsfw: data.enable_nsfw,
+      require_email_verification: data.require_email_verification,
+      public_edit_history: data.public_edit_history,
+      archive_after_days: data.archive_after_days,
+      require_removal_reason: data.require_removal_reason,
+      block_disposable_emails: data.block_disposable_emails,
+      ip_retention_days: data.ip_retention_days,
+      registrations_per_ip_per_day: data.registrations_per_ip_per_day,
+      mod_inactive_days: data.mod_inactive_days,
+      hide_downvote_counts: data.hide_downvote_counts,
     };
 
     let update_site = move |conn: &'_ _| Site::update(conn, 1, &site_form);
@@ -331,6 +665,9 @@ impl Perform for Oper<EditSite> {
       return Err(APIError::err("couldnt_update_site").into());
     }
 
+    blocking(pool, move |conn| load_vote_count_settings(conn)).await??;
+    invalidate_site();
+
     let site_view = blocking(pool, move |conn| SiteView::read(conn)).await??;
 
     let res = SiteResponse { site: site_view };
@@ -356,7 +693,36 @@ impl Perform for Oper<GetSite> {
     pool: &DbPool,
     websocket_info: Option<WebsocketInfo>,
   ) -> Result<GetSiteResponse, LemmyError> {
-    let _data: &GetSite = &self.data;
+    let data: &GetSite = &self.data;
+
+    let user_id: Option<i32> = match &data.auth {
+      Some(auth) => match Claims::decode(&auth) {
+        Ok(claims) => Some(claims.claims.id),
+        Err(_e) => None,
+      },
+      None => None,
+    };
+
+    let online = if let Some(_ws) = websocket_info.to_owned() {
+      // TODO
+      1
+    // let fut = async {
+    //   ws.chatserver.send(GetUsersOnline).await.unwrap()
+    // };
+    // Runtime::new().unwrap().block_on(fut)
+    } else {
+      0
+    };
+
+    // No dismissed announcements to leave out when logged out
+    if user_id.is_none() {
+      if let Some(site_response) = get_site() {
+        return Ok(GetSiteResponse {
+          online,
+          ..site_response
+        });
+      }
+    }
 
     // TODO refactor this a little
     let res = blocking(pool, move |conn| Site::read(conn, 1)).await?;
@@ -370,6 +736,8 @@ impl Perform for Oper<GetSite> {
         password_verify: setup.admin_password.to_owned(),
         admin: true,
         show_nsfw: true,
+        captcha_uuid: None,
+        captcha_answer: None,
       };
       let login_response = Oper::new(register, self.client.clone())
         .perform(pool, websocket_info.clone())
@@ -382,6 +750,15 @@ impl Perform for Oper<GetSite> {
         enable_downvotes: true,
         open_registration: true,
         enable_nsfw: true,
+        require_email_verification: false,
+        public_edit_history: true,
+        archive_after_days: 0,
+        require_removal_reason: false,
+        block_disposable_emails: false,
+        ip_retention_days: 30,
+        registrations_per_ip_per_day: 0,
+        mod_inactive_days: 90,
+        hide_downvote_counts: false,
         auth: login_response.jwt,
       };
       Oper::new(create_site, self.client.clone())
@@ -408,23 +785,200 @@ impl Perform for Oper<GetSite> {
 
     let banned = blocking(pool, move |conn| UserView::banned(conn)).await??;
 
-    let online = if let Some(_ws) = websocket_info {
-      // TODO
-      1
-    // let fut = async {
-    //   ws.chatserver.send(GetUsersOnline).await.unwrap()
-    // };
-    // Runtime::new().unwrap().block_on(fut)
-    } else {
-      0
-    };
+    let announcements = blocking(pool, move |conn| {
+      SiteAnnouncement::list_active(conn, user_id)
+    })
+    .await??;
 
-    Ok(GetSiteResponse {
+    let documents = blocking(pool, move |conn| SiteDocument::list_summaries(conn)).await??;
+
+    let site_response = GetSiteResponse {
       site: site_view,
       admins,
       banned,
+      announcements,
+      documents,
       online,
+    };
+
+    // Not before the site is set up
+    if user_id.is_none() && site_response.site.is_some() {
+      set_site(site_response.to_owned());
+    }
+
+    Ok(site_response)
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<CreateSiteAnnouncement> {
+  type Response = SiteAnnouncementResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<SiteAnnouncementResponse, LemmyError> {
+    let data: &CreateSiteAnnouncement = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    // Make sure user is an admin
+    let user = blocking(pool, move |conn| UserView::read(conn, user_id)).await??;
+    if !user.admin {
+      return Err(APIError::err("not_an_admin").into());
+    }
+
+    if data.content.trim().is_empty() {
+      return Err(APIError::err("announcement_empty").into());
+    }
+
+    if AnnouncementSeverity::from_i16(data.severity).is_none() {
+      return Err(APIError::err("invalid_announcement_severity").into());
+    }
+
+    let start_time = data.start_time.map(naive_from_unix);
+    let end_time = data.end_time.map(naive_from_unix);
+    if let Some(end_time) = end_time {
+      if end_time <= start_time.unwrap_or_else(naive_now) {
+        return Err(APIError::err("invalid_announcement_time").into());
+      }
+    }
+
+    let announcement_form = SiteAnnouncementForm {
+      creator_id: user_id,
+      content: data.content.to_owned(),
+      severity: data.severity,
+      start_time,
+      end_time,
+    };
+
+    let announcement = match blocking(pool, move |conn| {
+      SiteAnnouncement::create(conn, &announcement_form)
+    })
+    .await?
+    {
+      Ok(announcement) => announcement,
+      Err(_e) => return Err(APIError::err("couldnt_create_announcement").into()),
+    };
+
+    invalidate_site();
+
+    let res = SiteAnnouncementResponse { announcement };
+
+    // Connected clients show it right away, unless it is scheduled for later
+    if let Some(ws) = websocket_info {
+      if res.announcement.start_time <= naive_now() {
+        ws.chatserver.do_send(SendAllMessage {
+          op: UserOperation::CreateSiteAnnouncement,
+          response: res.clone(),
+          my_id: ws.id,
+        });
+      }
+    }
+
+    Ok(res)
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<DeleteSiteAnnouncement> {
+  type Response = SiteAnnouncementResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<SiteAnnouncementResponse, LemmyError> {
+    let data: &DeleteSiteAnnouncement = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    // Make sure user is an admin
+    let user = blocking(pool, move |conn| UserView::read(conn, user_id)).await??;
+    if !user.admin {
+      return Err(APIError::err("not_an_admin").into());
+    }
+
+    let announcement_id = data.announcement_id;
+    let announcement = match blocking(pool, move |conn| {
+      SiteAnnouncement::read(conn, announcement_id)
     })
+    .await?
+    {
+      Ok(announcement) => announcement,
+      Err(_e) => return Err(APIError::err("couldnt_find_announcement").into()),
+    };
+
+    let delete = move |conn: &'_ _| SiteAnnouncement::delete(conn, announcement_id);
+    if blocking(pool, delete).await?.is_err() {
+      return Err(APIError::err("couldnt_delete_announcement").into());
+    }
+
+    invalidate_site();
+
+    let res = SiteAnnouncementResponse { announcement };
+
+    if let Some(ws) = websocket_info {
+      ws.chatserver.do_send(SendAllMessage {
+        op: UserOperation::DeleteSiteAnnouncement,
+        response: res.clone(),
+        my_id: ws.id,
+      });
+    }
+
+    Ok(res)
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<DismissSiteAnnouncement> {
+  type Response = SiteAnnouncementResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<SiteAnnouncementResponse, LemmyError> {
+    let data: &DismissSiteAnnouncement = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let announcement_id = data.announcement_id;
+    let announcement = match blocking(pool, move |conn| {
+      SiteAnnouncement::read(conn, announcement_id)
+    })
+    .await?
+    {
+      Ok(announcement) => announcement,
+      Err(_e) => return Err(APIError::err("couldnt_find_announcement").into()),
+    };
+
+    let dismissal_form = SiteAnnouncementDismissalForm {
+      announcement_id,
+      user_id,
+    };
+    let dismiss = move |conn: &'_ _| SiteAnnouncementDismissal::dismiss(conn, &dismissal_form);
+    if blocking(pool, dismiss).await?.is_err() {
+      return Err(APIError::err("couldnt_dismiss_announcement").into());
+    }
+
+    Ok(SiteAnnouncementResponse { announcement })
   }
 }
 
@@ -464,20 +1018,27 @@ impl Perform for Oper<Search> {
     let mut communities = Vec::new();
     let mut users = Vec::new();
 
-    // TODO no clean / non-nsfw searching rn
-
     let q = data.q.to_owned();
     let page = data.page;
     let limit = data.limit;
     let sort = SortType::from_str(&data.sort)?;
     let community_id = data.community_id;
+    let creator_id = data.creator_id;
+    let published_after = data.published_after.map(naive_from_unix);
+    let published_before = data.published_before.map(naive_from_unix);
+    let url_domain = data.url_domain.to_owned();
+    let show_nsfw = data.nsfw.unwrap_or(true);
     match type_ {
       SearchType::Posts => {
-        posts = blocking(pool, move |conn| {
+        posts = blocking_read(pool, move |conn| {
           PostQueryBuilder::create(conn)
             .sort(&sort)
-            .show_nsfw(true)
+            .show_nsfw(show_nsfw)
             .for_community_id(community_id)
+            .for_creator_id(creator_id)
+            .published_after(published_after)
+            .published_before(published_before)
+            .url_domain(url_domain)
             .search_term(q)
             .my_user_id(user_id)
             .page(page)
@@ -487,9 +1048,13 @@ impl Perform for Oper<Search> {
         .await??;
       }
       SearchType::Comments => {
-        comments = blocking(pool, move |conn| {
+        comments = blocking_read(pool, move |conn| {
           CommentQueryBuilder::create(&conn)
             .sort(&sort)
+            .for_community_id(community_id)
+            .for_creator_id(creator_id)
+            .published_after(published_after)
+            .published_before(published_before)
             .search_term(q)
             .my_user_id(user_id)
             .page(page)
@@ -499,9 +1064,10 @@ impl Perform for Oper<Search> {
         .await??;
       }
       SearchType::Communities => {
-        communities = blocking(pool, move |conn| {
+        communities = blocking_read(pool, move |conn| {
           CommunityQueryBuilder::create(conn)
             .sort(&sort)
+            .show_nsfw(show_nsfw)
             .search_term(q)
             .page(page)
             .limit(limit)
@@ -510,7 +1076,7 @@ impl Perform for Oper<Search> {
         .await??;
       }
       SearchType::Users => {
-        users = blocking(pool, move |conn| {
+        users = blocking_read(pool, move |conn| {
           UserQueryBuilder::create(conn)
             .sort(&sort)
             .search_term(q)
@@ -521,11 +1087,15 @@ impl Perform for Oper<Search> {
         .await??;
       }
       SearchType::All => {
-        posts = blocking(pool, move |conn| {
+        posts = blocking_read(pool, move |conn| {
           PostQueryBuilder::create(conn)
             .sort(&sort)
-            .show_nsfw(true)
+            .show_nsfw(show_nsfw)
             .for_community_id(community_id)
+            .for_creator_id(creator_id)
+            .published_after(published_after)
+            .published_before(published_before)
+            .url_domain(url_domain)
             .search_term(q)
             .my_user_id(user_id)
             .page(page)
@@ -537,9 +1107,13 @@ impl Perform for Oper<Search> {
         let q = data.q.to_owned();
         let sort = SortType::from_str(&data.sort)?;
 
-        comments = blocking(pool, move |conn| {
+        comments = blocking_read(pool, move |conn| {
           CommentQueryBuilder::create(conn)
             .sort(&sort)
+            .for_community_id(community_id)
+            .for_creator_id(creator_id)
+            .published_after(published_after)
+            .published_before(published_before)
             .search_term(q)
             .my_user_id(user_id)
             .page(page)
@@ -548,38 +1122,46 @@ impl Perform for Oper<Search> {
         })
         .await??;
 
-        let q = data.q.to_owned();
-        let sort = SortType::from_str(&data.sort)?;
-
-        communities = blocking(pool, move |conn| {
-          CommunityQueryBuilder::create(conn)
-            .sort(&sort)
-            .search_term(q)
-            .page(page)
-            .limit(limit)
-            .list()
-        })
-        .await??;
-
-        let q = data.q.to_owned();
-        let sort = SortType::from_str(&data.sort)?;
-
-        users = blocking(pool, move |conn| {
-          UserQueryBuilder::create(conn)
-            .sort(&sort)
-            .search_term(q)
-            .page(page)
-            .limit(limit)
-            .list()
-        })
-        .await??;
+        // Communities and users can't be filtered by the community or author
+        if community_id.is_none() && creator_id.is_none() {
+          let q = data.q.to_owned();
+          let sort = SortType::from_str(&data.sort)?;
+
+          communities = blocking_read(pool, move |conn| {
+            CommunityQueryBuilder::create(conn)
+              .sort(&sort)
+              .show_nsfw(show_nsfw)
+              .search_term(q)
+              .page(page)
+              .limit(limit)
+              .list()
+          })
+          .await??;
+
+          let q = data.q.to_owned();
+          let sort = SortType::from_str(&data.sort)?;
+
+          users = blocking_read(pool, move |conn| {
+            UserQueryBuilder::create(conn)
+              .sort(&sort)
+              .search_term(q)
+              .page(page)
+              .limit(limit)
+              .list()
+          })
+          .await??;
+        }
       }
       SearchType::Url => {
-        posts = blocking(pool, move |conn| {
+        posts = blocking_read(pool, move |conn| {
           PostQueryBuilder::create(conn)
             .sort(&sort)
-            .show_nsfw(true)
+            .show_nsfw(show_nsfw)
             .for_community_id(community_id)
+            .for_creator_id(creator_id)
+            .published_after(published_after)
+            .published_before(published_before)
+            .url_domain(url_domain)
             .url_search(q)
             .page(page)
             .limit(limit)
@@ -633,6 +1215,15 @@ impl Perform for Oper<TransferSite> {
       enable_downvotes: read_site.enable_downvotes,
       open_registration: read_site.open_registration,
       enable_nsfw: read_site.enable_nsfw,
+      require_email_verification: read_site.require_email_verification,
+      public_edit_history: read_site.public_edit_history,
+      archive_after_days: read_site.archive_after_days,
+      require_removal_reason: read_site.require_removal_reason,
+      block_disposable_emails: read_site.block_disposable_emails,
+      ip_retention_days: read_site.ip_retention_days,
+      registrations_per_ip_per_day: read_site.registrations_per_ip_per_day,
+      mod_inactive_days: read_site.mod_inactive_days,
+      hide_downvote_counts: read_site.hide_downvote_counts,
     };
 
     let update_site = move |conn: &'_ _| Site::update(conn, 1, &site_form);
@@ -649,6 +1240,8 @@ impl Perform for Oper<TransferSite> {
 
     blocking(pool, move |conn| ModAdd::create(conn, &form)).await??;
 
+    invalidate_site();
+
     let site_view = blocking(pool, move |conn| SiteView::read(conn)).await??;
 
     let mut admins = blocking(pool, move |conn| UserView::admins(conn)).await??;
@@ -661,10 +1254,19 @@ impl Perform for Oper<TransferSite> {
 
     let banned = blocking(pool, move |conn| UserView::banned(conn)).await??;
 
+    let announcements = blocking(pool, move |conn| {
+      SiteAnnouncement::list_active(conn, Some(user_id))
+    })
+    .await??;
+
+    let documents = blocking(pool, move |conn| SiteDocument::list_summaries(conn)).await??;
+
     Ok(GetSiteResponse {
       site: Some(site_view),
       admins,
       banned,
+      announcements,
+      documents,
       online: 0,
     })
   }
@@ -737,3 +1339,92 @@ impl Perform for Oper<SaveSiteConfig> {
     Ok(GetSiteConfigResponse { config_hjson })
   }
 }
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetCommunityDefaults> {
+  type Response = CommunityDefaultsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<CommunityDefaultsResponse, LemmyError> {
+    let _data: &GetCommunityDefaults = &self.data;
+
+    let defaults = blocking(pool, move |conn| Site::read(conn, 1))
+      .await?
+      .map(|site| site.community_defaults())
+      .unwrap_or_default();
+
+    Ok(CommunityDefaultsResponse { defaults })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<EditCommunityDefaults> {
+  type Response = CommunityDefaultsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<CommunityDefaultsResponse, LemmyError> {
+    let data: &EditCommunityDefaults = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let admins = blocking(pool, move |conn| UserView::admins(conn)).await??;
+    let admin_ids: Vec<i32> = admins.into_iter().map(|m| m.id).collect();
+
+    if !admin_ids.contains(&user_id) {
+      return Err(APIError::err("not_an_admin").into());
+    }
+
+    let defaults = data.defaults.to_owned();
+    check_community_settings(
+      Some(defaults.community_default_post_types),
+      Some(defaults.community_default_sort_type),
+      &defaults.community_default_language,
+    )?;
+
+    let update_defaults = move |conn: &'_ _| Site::update_community_defaults(conn, 1, &defaults);
+    let updated_site = match blocking(pool, update_defaults).await? {
+      Ok(site) => site,
+      Err(_e) => return Err(APIError::err("couldnt_update_site").into()),
+    };
+
+    Ok(CommunityDefaultsResponse {
+      defaults: updated_site.community_defaults(),
+    })
+  }
+}
+
+/// Reads whether the site hides the downvote counts into the views, which leave the vote counts
+/// out of what they serialize then. Needs to run on startup, and after the site changes.
+pub fn load_vote_count_settings(conn: &PgConnection) -> Result<(), LemmyError> {
+  // There's no site yet before the first admin sets it up
+  let hide = Site::read(conn, 1)
+    .map(|s| s.hide_downvote_counts)
+    .unwrap_or(false);
+
+  set_hide_downvote_counts(hide);
+  Ok(())
+}
+
+/// The time of the oldest entry of a modlog list, if the limit cut the list off.
+fn oldest_if_full<T>(
+  list: &[T],
+  limit: i64,
+  when: fn(&T) -> chrono::NaiveDateTime,
+) -> Option<chrono::NaiveDateTime> {
+  if list.len() as i64 >= limit {
+    list.last().map(when)
+  } else {
+    None
+  }
+}
@@ -0,0 +1,157 @@
+use crate::{
+  api::{check_admin, claims::Claims, APIError, Oper, Perform},
+  blocking,
+  blocking_read,
+  cache::invalidate_site,
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::{naive_now, site_document::*, Crud};
+use lemmy_utils::{is_valid_post_title, is_valid_wiki_slug};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetSiteDocument {
+  slug: String,
+}
+
+/// Writes the document of the slug, creating it if there's none yet.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SaveSiteDocument {
+  slug: String,
+  title: String,
+  content: String,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeleteSiteDocument {
+  slug: String,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct SiteDocumentResponse {
+  document: SiteDocument,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetSiteDocument> {
+  type Response = SiteDocumentResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<SiteDocumentResponse, LemmyError> {
+    let data: &GetSiteDocument = &self.data;
+
+    let slug = data.slug.to_owned();
+    let document =
+      match blocking_read(pool, move |conn| SiteDocument::read_from_slug(conn, &slug)).await? {
+        Ok(document) => document,
+        Err(_e) => return Err(APIError::err("couldnt_find_site_document").into()),
+      };
+
+    Ok(SiteDocumentResponse { document })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<SaveSiteDocument> {
+  type Response = SiteDocumentResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<SiteDocumentResponse, LemmyError> {
+    let data: &SaveSiteDocument = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    if !is_valid_wiki_slug(&data.slug) {
+      return Err(APIError::err("invalid_site_document_slug").into());
+    }
+
+    if !is_valid_post_title(&data.title) {
+      return Err(APIError::err("invalid_site_document_title").into());
+    }
+
+    if data.content.trim().is_empty() {
+      return Err(APIError::err("site_document_empty").into());
+    }
+
+    let user_id = claims.id;
+    check_admin(pool, user_id).await?;
+
+    let slug = data.slug.to_owned();
+    let existing = blocking(pool, move |conn| SiteDocument::read_from_slug(conn, &slug))
+      .await?
+      .ok();
+
+    let document_form = SiteDocumentForm {
+      slug: data.slug.to_owned(),
+      title: data.title.trim().to_owned(),
+      content: data.content.to_owned(),
+      editor_id: Some(user_id),
+      updated: existing.as_ref().map(|_| naive_now()),
+    };
+
+    let save = move |conn: &'_ _| match existing {
+      Some(existing) => SiteDocument::update(conn, existing.id, &document_form),
+      None => SiteDocument::create(conn, &document_form),
+    };
+    let document = match blocking(pool, save).await? {
+      Ok(document) => document,
+      Err(_e) => return Err(APIError::err("couldnt_save_site_document").into()),
+    };
+
+    // The listing in GetSite changes
+    invalidate_site();
+
+    Ok(SiteDocumentResponse { document })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<DeleteSiteDocument> {
+  type Response = SiteDocumentResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<SiteDocumentResponse, LemmyError> {
+    let data: &DeleteSiteDocument = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_admin(pool, claims.id).await?;
+
+    let slug = data.slug.to_owned();
+    let document =
+      match blocking(pool, move |conn| SiteDocument::read_from_slug(conn, &slug)).await? {
+        Ok(document) => document,
+        Err(_e) => return Err(APIError::err("couldnt_find_site_document").into()),
+      };
+
+    let document_id = document.id;
+    let delete = move |conn: &'_ _| SiteDocument::delete(conn, document_id);
+    if blocking(pool, delete).await?.is_err() {
+      return Err(APIError::err("couldnt_delete_site_document").into());
+    }
+
+    invalidate_site();
+
+    Ok(SiteDocumentResponse { document })
+  }
+}
@@ -1,43 +1,96 @@
 use crate::{
-  api::{claims::Claims, APIError, Oper, Perform},
-  apub::ApubObjectType,
+  api::{
+    claims::Claims,
+    comment::{notification_email_html, wants_notification},
+    community::check_removal_reason,
+    email_domain::check_email_domain_allowed,
+    ip_ban::{check_registration_throttle, record_user_ip},
+    mod_note::visible_mod_notes,
+    muted_keyword::{collapsed_comment_ids, collapsed_post_ids},
+    APIError,
+    Oper,
+    Perform,
+  },
+  apub::{
+    fetcher::{
+      get_or_fetch_and_insert_remote_comment,
+      get_or_fetch_and_insert_remote_post,
+      get_or_fetch_and_upsert_remote_community,
+    },
+    ActorType,
+    ApubObjectType,
+  },
+  auth::authenticate,
   blocking,
+  blocking_read,
+  cache::invalidate_site,
+  hooks::pre_register,
+  job_queue::{enqueue, Task},
+  matrix::queue_matrix_notifications,
+  media::delete_media,
+  push::{queue_push_notifications, PushNotification},
+  webhook::{queue_webhooks, NewRegistration},
   websocket::{
-    server::{JoinUserRoom, SendAllMessage, SendUserRoomMessage},
+    server::{
+      JoinConversationRoom,
+      JoinUserRoom,
+      SendAllMessage,
+      SendPrivateMessage,
+      SendUnreadCounts,
+      SendUserRoomMessage,
+    },
     UserOperation,
     WebsocketInfo,
   },
   DbPool,
   LemmyError,
 };
+use actix_web::client::Client;
 use bcrypt::verify;
+use captcha::{gen, Difficulty};
 use lemmy_db::{
+  captcha_answer::*,
   comment::*,
   comment_view::*,
   community::*,
   community_view::*,
+  email_verification::*,
+  media::*,
+  mod_note::*,
   moderator::*,
   naive_now,
   password_reset_request::*,
   post::*,
   post_view::*,
   private_message::*,
+  private_message_conversation_view::*,
   private_message_view::*,
   site::*,
   site_view::*,
   user::*,
+  user_alias::*,
+  user_export::*,
   user_mention::*,
   user_mention_view::*,
+  user_notification_settings::*,
+  user_push_subscription::*,
+  user_stats::*,
   user_view::*,
+  webhook::WebhookEvent,
   Crud,
   Followable,
+  InboxType,
   Joinable,
   ListingType,
+  Saveable,
   SortType,
 };
 use lemmy_utils::{
   generate_actor_keypair,
   generate_random_string,
+  get_apub_protocol_string,
+  i18n::{translate, user_langs},
+  is_valid_matrix_id,
   is_valid_username,
   make_apub_endpoint,
   naive_from_unix,
@@ -48,17 +101,25 @@ use lemmy_utils::{
   slurs_vec_to_str,
   EndpointType,
 };
-use log::error;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
+use tracing::error;
+
+/// How many times a user can rename themselves, since each old name stays reserved for them
+const MAX_USERNAME_CHANGES: usize = 3;
+
+/// The most communities, saved posts and saved comments one import can have, as each can be a
+/// fetch from another instance
+const MAX_IMPORT_ENTRIES: usize = 500;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct Login {
-  username_or_email: String,
-  password: String,
+  pub username_or_email: String,
+  pub password: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct Register {
   pub username: String,
   pub email: Option<String>,
@@ -66,9 +127,26 @@ pub struct Register {
   pub password_verify: String,
   pub admin: bool,
   pub show_nsfw: bool,
+  pub captcha_uuid: Option<String>,
+  pub captcha_answer: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetCaptcha {}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetCaptchaResponse {
+  ok: Option<CaptchaResponse>, // Will be None if captchas are disabled
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CaptchaResponse {
+  png: String,         // A Base64 encoded png
+  wav: Option<String>, // A Base64 encoded wav audio
+  uuid: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct SaveUserSettings {
   show_nsfw: bool,
   theme: String,
@@ -76,6 +154,8 @@ pub struct SaveUserSettings {
   default_listing_type: i16,
   lang: String,
   avatar: Option<String>,
+  banner: Option<String>,
+  bio: Option<String>,
   email: Option<String>,
   matrix_user_id: Option<String>,
   new_password: Option<String>,
@@ -83,15 +163,87 @@ pub struct SaveUserSettings {
   old_password: Option<String>,
   show_avatars: bool,
   send_notifications_to_email: bool,
+  hide_read_posts: bool,
+  bot_account: bool,
+  hide_bot_accounts: bool,
+  display_name: Option<String>,
+  /// Not counted in the users online of the communities and posts
+  hide_presence: bool,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ChangeUsername {
+  new_username: String,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetNotificationSettings {
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SaveNotificationSettings {
+  replies: bool,
+  mentions: bool,
+  private_messages: bool,
+  mod_actions: bool,
+  subscribed_posts: bool,
+  digest_frequency: i16,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct NotificationSettingsResponse {
+  settings: UserNotificationSettings,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct UnsubscribeDigest {
+  token: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct UnsubscribeDigestResponse {}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetPushPublicKey {}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct PushPublicKeyResponse {
+  public_key: String,
+}
+
+/// The keys of a browser `PushSubscription`
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PushSubscriptionKeys {
+  p256dh: String,
   auth: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct RegisterPushSubscription {
+  endpoint: String,
+  keys: PushSubscriptionKeys,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeletePushSubscription {
+  endpoint: String,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct PushSubscriptionResponse {}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct LoginResponse {
   pub jwt: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetUserDetails {
   user_id: Option<i32>,
   username: Option<String>,
@@ -103,7 +255,7 @@ pub struct GetUserDetails {
   auth: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetUserDetailsResponse {
   user: UserView,
   follows: Vec<CommunityFollowerView>,
@@ -111,36 +263,119 @@ pub struct GetUserDetailsResponse {
   comments: Vec<CommentView>,
   posts: Vec<PostView>,
   admins: Vec<UserView>,
+  mod_notes: Vec<ModNote>,
+}
+
+/// The karma and activity of a user, for profile pages and bots. The activity is bucketed by
+/// `day`, `week`, `month` or `year`, a month by default.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetUserStats {
+  user_id: Option<i32>,
+  username: Option<String>,
+  bucket: Option<String>,
+  limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetUserStatsResponse {
+  user_id: i32,
+  published: chrono::NaiveDateTime,
+  account_age_days: i64,
+  totals: UserAggregates,
+  karma_by_community: Vec<CommunityKarma>,
+  activity: Vec<ActivityBucket>,
+}
+
+/// Follows, or unfollows, a user. A remote user is only followed once their instance accepts.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct FollowUser {
+  user_id: i32,
+  follow: bool,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct FollowUserResponse {
+  user: UserView,
+  following: bool,
+}
+
+/// The posts and comments of the users you follow
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetFollowedUsersFeed {
+  sort: String,
+  page: Option<i64>,
+  limit: Option<i64>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetFollowedUsersFeedResponse {
+  posts: Vec<PostView>,
+  comments: Vec<CommentView>,
+  collapsed_post_ids: Vec<i32>,
+  collapsed_comment_ids: Vec<i32>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetRepliesResponse {
   replies: Vec<ReplyView>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetUserMentionsResponse {
   mentions: Vec<UserMentionView>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct MarkAllAsRead {
+  inbox_type: Option<String>,
   auth: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetUnreadCount {
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct GetUnreadCountResponse {
+  replies: i64,
+  mentions: i64,
+  private_messages: i64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetInbox {
+  inbox_type: String,
+  community_id: Option<i32>,
+  sort: String,
+  page: Option<i64>,
+  limit: Option<i64>,
+  unread_only: bool,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetInboxResponse {
+  replies: Vec<ReplyView>,
+  mentions: Vec<UserMentionView>,
+  messages: Vec<PrivateMessageView>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct AddAdmin {
   user_id: i32,
   added: bool,
   auth: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct AddAdminResponse {
   admins: Vec<UserView>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct BanUser {
   user_id: i32,
   ban: bool,
@@ -149,13 +384,27 @@ pub struct BanUser {
   auth: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct BanUserResponse {
   user: UserView,
   banned: bool,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ShadowBanUser {
+  user_id: i32,
+  shadow_ban: bool,
+  reason: Option<String>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ShadowBanUserResponse {
+  user: UserView,
+  shadow_banned: bool,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetReplies {
   sort: String,
   page: Option<i64>,
@@ -164,7 +413,7 @@ pub struct GetReplies {
   auth: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetUserMentions {
   sort: String,
   page: Option<i64>,
@@ -173,47 +422,99 @@ pub struct GetUserMentions {
   auth: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct EditUserMention {
   user_mention_id: i32,
   read: Option<bool>,
   auth: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct UserMentionResponse {
   mention: UserMentionView,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct DeleteAccount {
   password: String,
   auth: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct PasswordReset {
   email: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct PasswordResetResponse {}
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct PasswordChange {
   token: String,
   password: String,
   password_verify: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct VerifyEmail {
+  token: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct VerifyEmailResponse {}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ResendVerificationEmail {
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ResendVerificationEmailResponse {}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ExportUserData {
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetUserDataExport {
+  export_id: i32,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct UserExportResponse {
+  export: UserExport,
+}
+
+/// Either an archive from ExportUserData, or a plain list of community handles like
+/// `!main@lemmy.ml`, can be imported. There are no user blocks yet, so nothing to restore there.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ImportUserData {
+  archive: Option<UserExportData>,
+  communities: Option<Vec<String>>,
+  auth: String,
+}
+
+/// Sent back when the import is queued, and again to the user's room once it has run
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ImportUserDataResponse {
+  completed: bool,
+  followed_communities: i32,
+  saved_posts: i32,
+  saved_comments: i32,
+  failed: Vec<String>, // Whatever couldn't be found or restored
+  error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct CreatePrivateMessage {
   content: String,
   pub recipient_id: i32,
   auth: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct EditPrivateMessage {
   edit_id: i32,
   content: Option<String>,
@@ -222,7 +523,7 @@ pub struct EditPrivateMessage {
   auth: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetPrivateMessages {
   unread_only: bool,
   page: Option<i64>,
@@ -230,22 +531,49 @@ pub struct GetPrivateMessages {
   auth: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct PrivateMessagesResponse {
   messages: Vec<PrivateMessageView>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct PrivateMessageResponse {
   pub message: PrivateMessageView,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetPrivateMessageConversations {
+  unread_only: bool,
+  page: Option<i64>,
+  limit: Option<i64>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct PrivateMessageConversationsResponse {
+  conversations: Vec<PrivateMessageConversationView>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GetPrivateMessageConversation {
+  conversation_id: i32,
+  page: Option<i64>,
+  limit: Option<i64>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct PrivateMessageConversationResponse {
+  conversation: PrivateMessageConversationView,
+  messages: Vec<PrivateMessageView>,
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct UserJoin {
   auth: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct UserJoinResponse {
   pub user_id: i32,
 }
@@ -261,21 +589,14 @@ impl Perform for Oper<Login> {
   ) -> Result<LoginResponse, LemmyError> {
     let data: &Login = &self.data;
 
-    // Fetch that username / email
-    let username_or_email = data.username_or_email.clone();
-    let user = match blocking(pool, move |conn| {
-      Claims::find_by_email_or_username(conn, &username_or_email)
-    })
-    .await?
-    {
-      Ok(user) => user,
-      Err(_e) => return Err(APIError::err("couldnt_find_that_username_or_email").into()),
-    };
+    // Check the password with the directory, or the one of the account here
+    let user = authenticate(pool, &data.username_or_email, &data.password).await?;
 
-    // Verify the password
-    let valid: bool = verify(&data.password, &user.password_encrypted).unwrap_or(false);
-    if !valid {
-      return Err(APIError::err("password_incorrect").into());
+    // Keeping the IP is only bookkeeping, so it doesn't stop the login
+    if let Some(ip) = &self.ip {
+      if let Err(e) = record_user_ip(pool, user.id, ip, false).await {
+        error!("Couldn't record login IP: {}", e);
+      }
     }
 
     // Return the jwt
@@ -296,12 +617,30 @@ impl Perform for Oper<Register> {
   ) -> Result<LoginResponse, LemmyError> {
     let data: &Register = &self.data;
 
+    // Everything below checks what the hooks let through
+    let hooked = pre_register(data, &self.ip).await?;
+    let data = &hooked;
+
     // Make sure site has open registration
     if let Ok(site) = blocking(pool, move |conn| SiteView::read(conn)).await? {
       let site: SiteView = site;
       if !site.open_registration {
         return Err(APIError::err("registration_closed").into());
       }
+
+      // An email is needed to verify it
+      if site.require_email_verification && data.email.is_none() {
+        return Err(APIError::err("email_required").into());
+      }
+    }
+
+    // Make sure the email's domain is let in
+    if let Some(email) = data.email.as_ref().filter(|e| !e.trim().is_empty()) {
+      check_email_domain_allowed(pool, email).await?;
+    }
+
+    if let Some(ip) = &self.ip {
+      check_registration_throttle(pool, ip).await?;
     }
 
     // Make sure passwords match
@@ -309,6 +648,16 @@ impl Perform for Oper<Register> {
       return Err(APIError::err("passwords_dont_match").into());
     }
 
+    // Check the captcha, unless its the initial admin being set up
+    if Settings::get().captcha.enabled && !data.admin {
+      let uuid = data.captcha_uuid.to_owned().unwrap_or_default();
+      let answer = data.captcha_answer.to_owned().unwrap_or_default();
+      let check = move |conn: &'_ _| CaptchaAnswer::check(conn, &uuid, &answer);
+      if !blocking(pool, check).await?? {
+        return Err(APIError::err("captcha_incorrect").into());
+      }
+    }
+
     if let Err(slurs) = slur_check(&data.username) {
       return Err(APIError::err(&slurs_vec_to_str(slurs)).into());
     }
@@ -327,6 +676,13 @@ impl Perform for Oper<Register> {
       return Err(APIError::err("invalid_username").into());
     }
 
+    // Someone's old name still points to them
+    let username = data.username.to_owned();
+    let read_alias = move |conn: &'_ _| UserAlias::read_from_name(conn, &username);
+    if blocking(pool, read_alias).await?.is_ok() {
+      return Err(APIError::err("user_already_exists").into());
+    }
+
     // Register the new user
     let user_form = UserForm {
       name: data.username.to_owned(),
@@ -351,6 +707,12 @@ impl Perform for Oper<Register> {
       private_key: Some(user_keypair.private_key),
       public_key: Some(user_keypair.public_key),
       last_refreshed_at: None,
+      banner: None,
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name: None,
     };
 
     // Create the user
@@ -369,6 +731,13 @@ impl Perform for Oper<Register> {
       }
     };
 
+    // It counts towards the registration throttle
+    if let Some(ip) = &self.ip {
+      if let Err(e) = record_user_ip(pool, inserted_user.id, ip, true).await {
+        error!("Couldn't record registration IP: {}", e);
+      }
+    }
+
     let main_community_keypair = generate_actor_keypair()?;
 
     // Create the main community if it doesn't exist
@@ -392,6 +761,15 @@ impl Perform for Oper<Register> {
           public_key: Some(main_community_keypair.public_key),
           last_refreshed_at: None,
           published: None,
+          require_flair: None,
+          icon: None,
+          banner: None,
+          archive_after_days: None,
+          post_approval: None,
+          read_only: None,
+          allowed_post_types: None,
+          default_sort_type: None,
+          language: None,
         };
         blocking(pool, move |conn| Community::create(conn, &community_form)).await??
       }
@@ -421,6 +799,18 @@ impl Perform for Oper<Register> {
       }
     }
 
+    // Send the verification link, but don't fail the registration if the email couldn't be sent
+    if let Some(email) = &inserted_user.email {
+      if let Err(e) = send_verification_email(&inserted_user, email, pool).await {
+        error!("{}", e);
+      }
+    }
+
+    let registration = NewRegistration::new(&inserted_user);
+    if let Err(e) = queue_webhooks(pool, WebhookEvent::NewRegistration, None, &registration).await {
+      error!("Couldn't queue the registration webhooks: {}", e);
+    }
+
     // Return the jwt
     Ok(LoginResponse {
       jwt: Claims::jwt(inserted_user, Settings::get().hostname),
@@ -428,6 +818,61 @@ impl Perform for Oper<Register> {
   }
 }
 
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetCaptcha> {
+  type Response = GetCaptchaResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<GetCaptchaResponse, LemmyError> {
+    let captcha_settings = Settings::get().captcha;
+
+    if !captcha_settings.enabled {
+      return Ok(GetCaptchaResponse { ok: None });
+    }
+
+    let captcha = match captcha_settings.difficulty.as_str() {
+      "easy" => gen(Difficulty::Easy),
+      "hard" => gen(Difficulty::Hard),
+      _ => gen(Difficulty::Medium),
+    };
+
+    let png = match captcha.as_base64() {
+      Some(png) => png,
+      None => return Err(APIError::err("couldnt_create_captcha").into()),
+    };
+
+    // The audio version is a wav per letter, played back to back
+    let mut letters: Vec<u8> = Vec::new();
+    for letter in captcha.as_wav() {
+      letters.extend(letter.unwrap_or_default());
+    }
+    let wav = if letters.is_empty() {
+      None
+    } else {
+      Some(base64::encode(letters))
+    };
+
+    let uuid = uuid::Uuid::new_v4().to_string();
+
+    let captcha_form = CaptchaAnswerForm {
+      uuid: uuid.to_owned(),
+      answer: captcha.chars_as_string(),
+    };
+
+    let insert = move |conn: &'_ _| CaptchaAnswer::insert(conn, &captcha_form);
+    if blocking(pool, insert).await?.is_err() {
+      return Err(APIError::err("couldnt_create_captcha").into());
+    }
+
+    Ok(GetCaptchaResponse {
+      ok: Some(CaptchaResponse { png, wav, uuid }),
+    })
+  }
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for Oper<SaveUserSettings> {
   type Response = LoginResponse;
@@ -448,6 +893,13 @@ impl Perform for Oper<SaveUserSettings> {
 
     let read_user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
 
+    let email_changed = data.email.is_some() && data.email != read_user.email;
+    if email_changed {
+      if let Some(email) = data.email.as_ref().filter(|e| !e.trim().is_empty()) {
+        check_email_domain_allowed(pool, email).await?;
+      }
+    }
+
     let email = match &data.email {
       Some(email) => Some(email.to_owned()),
       None => read_user.email,
@@ -458,6 +910,58 @@ impl Perform for Oper<SaveUserSettings> {
       None => read_user.avatar,
     };
 
+    let banner = match &data.banner {
+      Some(banner) => Some(banner.to_owned()),
+      None => read_user.banner,
+    };
+
+    let bio = match &data.bio {
+      Some(bio) => {
+        if bio.chars().count() > 300 {
+          return Err(APIError::err("bio_length_overflow").into());
+        }
+
+        if let Err(slurs) = slur_check(bio) {
+          return Err(APIError::err(&slurs_vec_to_str(slurs)).into());
+        }
+
+        Some(bio.to_owned())
+      }
+      None => read_user.bio,
+    };
+
+    // An empty display name clears it, so the username gets shown again
+    let display_name = match &data.display_name {
+      Some(display_name) => {
+        let display_name = display_name.trim();
+        if display_name.chars().count() > 100 {
+          return Err(APIError::err("display_name_length_overflow").into());
+        }
+
+        if let Err(slurs) = slur_check(display_name) {
+          return Err(APIError::err(&slurs_vec_to_str(slurs)).into());
+        }
+
+        if display_name.is_empty() {
+          None
+        } else {
+          Some(display_name.to_owned())
+        }
+      }
+      None => read_user.display_name,
+    };
+
+    // The Matrix bot sends the notifications to it, so it has to be a real ID
+    let matrix_user_id = match &data.matrix_user_id {
+      Some(matrix_user_id) if !matrix_user_id.is_empty() => {
+        if !is_valid_matrix_id(matrix_user_id) {
+          return Err(APIError::err("invalid_matrix_id").into());
+        }
+        Some(matrix_user_id.to_owned())
+      }
+      _ => None,
+    };
+
     let password_encrypted = match &data.new_password {
       Some(new_password) => {
         match &data.new_password_verify {
@@ -494,7 +998,7 @@ impl Perform for Oper<SaveUserSettings> {
     let user_form = UserForm {
       name: read_user.name,
       email,
-      matrix_user_id: data.matrix_user_id.to_owned(),
+      matrix_user_id,
       avatar,
       password_encrypted,
       preferred_username: read_user.preferred_username,
@@ -509,11 +1013,17 @@ impl Perform for Oper<SaveUserSettings> {
       show_avatars: data.show_avatars,
       send_notifications_to_email: data.send_notifications_to_email,
       actor_id: read_user.actor_id,
-      bio: read_user.bio,
+      bio,
       local: read_user.local,
       private_key: read_user.private_key,
       public_key: read_user.public_key,
       last_refreshed_at: None,
+      banner,
+      hide_read_posts: data.hide_read_posts,
+      bot_account: data.bot_account,
+      hide_bot_accounts: data.hide_bot_accounts,
+      hide_presence: data.hide_presence,
+      display_name,
     };
 
     let res = blocking(pool, move |conn| User_::update(conn, user_id, &user_form)).await?;
@@ -532,6 +1042,22 @@ impl Perform for Oper<SaveUserSettings> {
       }
     };
 
+    // A new email has to be verified again
+    let updated_user = if email_changed {
+      let updated_user = blocking(pool, move |conn| {
+        User_::update_email_verified(conn, user_id, false)
+      })
+      .await??;
+      if let Some(email) = &updated_user.email {
+        if let Err(e) = send_verification_email(&updated_user, email, pool).await {
+          error!("{}", e);
+        }
+      }
+      updated_user
+    } else {
+      updated_user
+    };
+
     // Return the jwt
     Ok(LoginResponse {
       jwt: Claims::jwt(updated_user, Settings::get().hostname),
@@ -540,50 +1066,288 @@ impl Perform for Oper<SaveUserSettings> {
 }
 
 #[async_trait::async_trait(?Send)]
-impl Perform for Oper<GetUserDetails> {
-  type Response = GetUserDetailsResponse;
+impl Perform for Oper<ChangeUsername> {
+  type Response = LoginResponse;
 
   async fn perform(
     &self,
     pool: &DbPool,
     _websocket_info: Option<WebsocketInfo>,
-  ) -> Result<GetUserDetailsResponse, LemmyError> {
-    let data: &GetUserDetails = &self.data;
+  ) -> Result<LoginResponse, LemmyError> {
+    let data: &ChangeUsername = &self.data;
 
-    let user_claims: Option<Claims> = match &data.auth {
-      Some(auth) => match Claims::decode(&auth) {
-        Ok(claims) => Some(claims.claims),
-        Err(_e) => None,
-      },
-      None => None,
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
     };
 
-    let user_id = match &user_claims {
-      Some(claims) => Some(claims.id),
-      None => None,
-    };
+    let user_id = claims.id;
 
-    let show_nsfw = match &user_claims {
-      Some(claims) => claims.show_nsfw,
-      None => false,
-    };
+    if !is_valid_username(&data.new_username) {
+      return Err(APIError::err("invalid_username").into());
+    }
 
-    let sort = SortType::from_str(&data.sort)?;
+    if let Err(slurs) = slur_check(&data.new_username) {
+      return Err(APIError::err(&slurs_vec_to_str(slurs)).into());
+    }
 
-    let username = data
-      .username
-      .to_owned()
-      .unwrap_or_else(|| "admin".to_string());
-    let user_details_id = match data.user_id {
-      Some(id) => id,
-      None => {
-        let user = blocking(pool, move |conn| User_::read_from_name(conn, &username)).await?;
-        match user {
-          Ok(user) => user.id,
-          Err(_e) => return Err(APIError::err("couldnt_find_that_username_or_email").into()),
-        }
-      }
-    };
+    let read_user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+    if read_user.name == data.new_username {
+      return Err(APIError::err("username_unchanged").into());
+    }
+
+    let aliases = blocking(pool, move |conn| UserAlias::for_user(conn, user_id)).await??;
+    if aliases.len() >= MAX_USERNAME_CHANGES {
+      return Err(APIError::err("too_many_username_changes").into());
+    }
+
+    let new_username = data.new_username.to_owned();
+    let is_taken = move |conn: &'_ _| UserAlias::is_taken(conn, user_id, &new_username);
+    if blocking(pool, is_taken).await?? {
+      return Err(APIError::err("user_already_exists").into());
+    }
+
+    let new_username = data.new_username.to_owned();
+    let change_name = move |conn: &'_ _| UserAlias::change_name(conn, user_id, &new_username);
+    let updated_user = match blocking(pool, change_name).await? {
+      Ok(user) => user,
+      Err(_e) => return Err(APIError::err("couldnt_update_user").into()),
+    };
+
+    // The username is part of the jwt, so it needs a new one
+    Ok(LoginResponse {
+      jwt: Claims::jwt(updated_user, Settings::get().hostname),
+    })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetNotificationSettings> {
+  type Response = NotificationSettingsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<NotificationSettingsResponse, LemmyError> {
+    let data: &GetNotificationSettings = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    let settings = blocking(pool, move |conn| {
+      UserNotificationSettings::read_for_user(conn, user_id)
+    })
+    .await??;
+
+    Ok(NotificationSettingsResponse { settings })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<SaveNotificationSettings> {
+  type Response = NotificationSettingsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<NotificationSettingsResponse, LemmyError> {
+    let data: &SaveNotificationSettings = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    if DigestFrequency::from_i16(data.digest_frequency).is_none() {
+      return Err(APIError::err("invalid_digest_frequency").into());
+    }
+
+    let settings_form = UserNotificationSettingsForm {
+      user_id: claims.id,
+      replies: data.replies,
+      mentions: data.mentions,
+      private_messages: data.private_messages,
+      mod_actions: data.mod_actions,
+      subscribed_posts: data.subscribed_posts,
+      updated: Some(naive_now()),
+      digest_frequency: data.digest_frequency,
+    };
+
+    let settings = match blocking(pool, move |conn| {
+      UserNotificationSettings::update_for_user(conn, &settings_form)
+    })
+    .await?
+    {
+      Ok(settings) => settings,
+      Err(_e) => return Err(APIError::err("couldnt_update_user").into()),
+    };
+
+    Ok(NotificationSettingsResponse { settings })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<UnsubscribeDigest> {
+  type Response = UnsubscribeDigestResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<UnsubscribeDigestResponse, LemmyError> {
+    let data: &UnsubscribeDigest = &self.data;
+
+    // The token from the email is enough, so the link works without logging in
+    let token = data.token.to_owned();
+    let unsubscribe = move |conn: &'_ _| UserNotificationSettings::unsubscribe_digest(conn, &token);
+    if blocking(pool, unsubscribe).await?.is_err() {
+      return Err(APIError::err("invalid_digest_token").into());
+    }
+
+    Ok(UnsubscribeDigestResponse {})
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetPushPublicKey> {
+  type Response = PushPublicKeyResponse;
+
+  async fn perform(
+    &self,
+    _pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<PushPublicKeyResponse, LemmyError> {
+    let settings = Settings::get();
+    if !settings.web_push.enabled {
+      return Err(APIError::err("web_push_disabled").into());
+    }
+
+    let keypair = settings.get_vapid_keypair()?;
+
+    Ok(PushPublicKeyResponse {
+      public_key: keypair.public_key,
+    })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<RegisterPushSubscription> {
+  type Response = PushSubscriptionResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<PushSubscriptionResponse, LemmyError> {
+    let data: &RegisterPushSubscription = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    if !Settings::get().web_push.enabled {
+      return Err(APIError::err("web_push_disabled").into());
+    }
+
+    // Push services are always reached over https
+    if !data.endpoint.starts_with("https://") {
+      return Err(APIError::err("invalid_push_endpoint").into());
+    }
+
+    let subscription_form = UserPushSubscriptionForm {
+      user_id: claims.id,
+      endpoint: data.endpoint.to_owned(),
+      p256dh: data.keys.p256dh.to_owned(),
+      auth: data.keys.auth.to_owned(),
+    };
+
+    let subscribe = move |conn: &'_ _| UserPushSubscription::subscribe(conn, &subscription_form);
+    if blocking(pool, subscribe).await?.is_err() {
+      return Err(APIError::err("couldnt_register_push_subscription").into());
+    }
+
+    Ok(PushSubscriptionResponse {})
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<DeletePushSubscription> {
+  type Response = PushSubscriptionResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<PushSubscriptionResponse, LemmyError> {
+    let data: &DeletePushSubscription = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    let endpoint = data.endpoint.to_owned();
+    blocking(pool, move |conn| {
+      UserPushSubscription::unsubscribe(conn, user_id, &endpoint)
+    })
+    .await??;
+
+    Ok(PushSubscriptionResponse {})
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetUserDetails> {
+  type Response = GetUserDetailsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<GetUserDetailsResponse, LemmyError> {
+    let data: &GetUserDetails = &self.data;
+
+    let user_claims: Option<Claims> = match &data.auth {
+      Some(auth) => match Claims::decode(&auth) {
+        Ok(claims) => Some(claims.claims),
+        Err(_e) => None,
+      },
+      None => None,
+    };
+
+    let user_id = match &user_claims {
+      Some(claims) => Some(claims.id),
+      None => None,
+    };
+
+    let show_nsfw = match &user_claims {
+      Some(claims) => claims.show_nsfw,
+      None => false,
+    };
+
+    let sort = SortType::from_str(&data.sort)?;
+
+    let username = data
+      .username
+      .to_owned()
+      .unwrap_or_else(|| "admin".to_string());
+    let user_details_id = match data.user_id {
+      Some(id) => id,
+      None => {
+        let user = blocking(pool, move |conn| UserAlias::read_user(conn, &username)).await?;
+        match user {
+          Ok(user) => user.id,
+          Err(_e) => return Err(APIError::err("couldnt_find_that_username_or_email").into()),
+        }
+      }
+    };
 
     let mut user_view = blocking(pool, move |conn| UserView::read(conn, user_details_id)).await??;
 
@@ -591,7 +1355,7 @@ impl Perform for Oper<GetUserDetails> {
     let limit = data.limit;
     let saved_only = data.saved_only;
     let community_id = data.community_id;
-    let (posts, comments) = blocking(pool, move |conn| {
+    let (posts, comments) = blocking_read(pool, move |conn| {
       let mut posts_query = PostQueryBuilder::create(conn)
         .sort(&sort)
         .show_nsfw(show_nsfw)
@@ -646,6 +1410,9 @@ impl Perform for Oper<GetUserDetails> {
       user_view.email = None;
     }
 
+    // Only the mods and admins see the notes kept about the user
+    let mod_notes = visible_mod_notes(pool, user_id, user_details_id).await?;
+
     // Return the jwt
     Ok(GetUserDetailsResponse {
       user: user_view,
@@ -654,6 +1421,190 @@ impl Perform for Oper<GetUserDetails> {
       comments,
       posts,
       admins,
+      mod_notes,
+    })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetUserStats> {
+  type Response = GetUserStatsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<GetUserStatsResponse, LemmyError> {
+    let data: &GetUserStats = &self.data;
+
+    let bucket = data
+      .bucket
+      .to_owned()
+      .unwrap_or_else(|| "month".to_string());
+    if !ACTIVITY_BUCKETS.contains(&bucket.as_str()) {
+      return Err(APIError::err("invalid_stats_bucket").into());
+    }
+    let limit = data.limit.unwrap_or(12).max(1).min(100);
+
+    let user_id = data.user_id;
+    let username = data.username.to_owned();
+    let user = blocking_read(pool, move |conn| match (user_id, username) {
+      (Some(user_id), _) => User_::read(conn, user_id),
+      (None, Some(username)) => UserAlias::read_user(conn, &username),
+      (None, None) => Err(diesel::result::Error::NotFound),
+    })
+    .await?;
+    let user = match user {
+      Ok(user) => user,
+      Err(_e) => return Err(APIError::err("couldnt_find_that_username_or_email").into()),
+    };
+
+    let user_id = user.id;
+    let (totals, karma_by_community, activity) = blocking_read(pool, move |conn| {
+      let totals = UserAggregates::read(conn, user_id)?;
+      let karma_by_community = UserAggregates::karma_by_community(conn, user_id)?;
+      let activity = UserAggregates::activity_buckets(conn, user_id, &bucket, limit)?;
+      Ok((totals, karma_by_community, activity)) as Result<_, LemmyError>
+    })
+    .await??;
+
+    Ok(GetUserStatsResponse {
+      user_id,
+      published: user.published,
+      account_age_days: naive_now().signed_duration_since(user.published).num_days(),
+      totals,
+      karma_by_community,
+      activity,
+    })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<FollowUser> {
+  type Response = FollowUserResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<FollowUserResponse, LemmyError> {
+    let data: &FollowUser = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let follower_id = claims.id;
+    let followed_id = data.user_id;
+    if followed_id == follower_id {
+      return Err(APIError::err("cant_follow_yourself").into());
+    }
+
+    let followed = match blocking(pool, move |conn| User_::read(conn, followed_id)).await? {
+      Ok(user) => user,
+      Err(_e) => return Err(APIError::err("couldnt_find_that_username_or_email").into()),
+    };
+    let user_follower_form = UserFollowerForm {
+      user_id: followed_id,
+      follower_id,
+    };
+
+    if followed.local {
+      if data.follow {
+        let follow = move |conn: &'_ _| UserFollower::follow(conn, &user_follower_form);
+        if blocking(pool, follow).await?.is_err() {
+          return Err(APIError::err("couldnt_follow_user").into());
+        }
+      } else {
+        let unfollow = move |conn: &'_ _| UserFollower::unfollow(conn, &user_follower_form);
+        if blocking(pool, unfollow).await?.is_err() {
+          return Err(APIError::err("couldnt_follow_user").into());
+        }
+      }
+    } else {
+      let user = blocking(pool, move |conn| User_::read(conn, follower_id)).await??;
+
+      if data.follow {
+        // The follow is only added once their instance sends the accept
+        user
+          .send_follow(&followed.actor_id, &self.client, pool)
+          .await?;
+      } else {
+        user
+          .send_unfollow(&followed.actor_id, &self.client, pool)
+          .await?;
+        let unfollow = move |conn: &'_ _| UserFollower::unfollow(conn, &user_follower_form);
+        if blocking(pool, unfollow).await?.is_err() {
+          return Err(APIError::err("couldnt_follow_user").into());
+        }
+      }
+    }
+
+    let mut user = blocking(pool, move |conn| UserView::read(conn, followed_id)).await??;
+    user.email = None;
+    let following = blocking(pool, move |conn| {
+      UserFollower::is_following(conn, followed_id, follower_id)
+    })
+    .await??;
+
+    Ok(FollowUserResponse { user, following })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetFollowedUsersFeed> {
+  type Response = GetFollowedUsersFeedResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<GetFollowedUsersFeedResponse, LemmyError> {
+    let data: &GetFollowedUsersFeed = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    let show_nsfw = claims.show_nsfw;
+    let sort = SortType::from_str(&data.sort)?;
+    let page = data.page;
+    let limit = data.limit;
+    let (posts, comments) = blocking_read(pool, move |conn| {
+      let posts = PostQueryBuilder::create(conn)
+        .sort(&sort)
+        .show_nsfw(show_nsfw)
+        .followed_users_only(true)
+        .my_user_id(user_id)
+        .page(page)
+        .limit(limit)
+        .list()?;
+
+      let comments = CommentQueryBuilder::create(conn)
+        .sort(&sort)
+        .followed_users_only(true)
+        .my_user_id(user_id)
+        .page(page)
+        .limit(limit)
+        .list()?;
+
+      Ok((posts, comments)) as Result<_, LemmyError>
+    })
+    .await??;
+
+    let post_ids = posts.iter().map(|p| p.id).collect();
+    let collapsed_post_ids = collapsed_post_ids(pool, Some(user_id), post_ids).await?;
+    let comment_ids = comments.iter().map(|c| c.id).collect();
+    let collapsed_comment_ids = collapsed_comment_ids(pool, Some(user_id), comment_ids).await?;
+
+    Ok(GetFollowedUsersFeedResponse {
+      posts,
+      comments,
+      collapsed_post_ids,
+      collapsed_comment_ids,
     })
   }
 }
@@ -706,6 +1657,8 @@ impl Perform for Oper<AddAdmin> {
     let creator_user = admins.remove(creator_index);
     admins.insert(0, creator_user);
 
+    invalidate_site();
+
     let res = AddAdminResponse { admins };
 
     if let Some(ws) = websocket_info {
@@ -744,19 +1697,23 @@ impl Perform for Oper<BanUser> {
       return Err(APIError::err("not_an_admin").into());
     }
 
-    let ban = data.ban;
-    let banned_user_id = data.user_id;
-    let ban_user = move |conn: &'_ _| User_::ban_user(conn, banned_user_id, ban);
-    if blocking(pool, ban_user).await?.is_err() {
-      return Err(APIError::err("couldnt_update_user").into());
+    if data.ban {
+      check_removal_reason(pool, &data.reason).await?;
     }
 
-    // Mod tables
     let expires = match data.expires {
       Some(time) => Some(naive_from_unix(time)),
       None => None,
     };
 
+    let ban = data.ban;
+    let banned_user_id = data.user_id;
+    let ban_user = move |conn: &'_ _| User_::ban_user(conn, banned_user_id, ban, expires);
+    if blocking(pool, ban_user).await?.is_err() {
+      return Err(APIError::err("couldnt_update_user").into());
+    }
+
+    // Mod tables
     let form = ModBanForm {
       mod_user_id: user_id,
       other_user_id: data.user_id,
@@ -775,6 +1732,24 @@ impl Perform for Oper<BanUser> {
       banned: data.ban,
     };
 
+    invalidate_site();
+
+    if data.ban {
+      let admin_id = claims.id;
+      let admin = blocking(pool, move |conn| User_::read(conn, admin_id)).await??;
+      let action = ban_notice(&Settings::get().hostname, expires);
+      send_mod_action_notice(
+        pool,
+        &self.client,
+        websocket_info.clone(),
+        &admin,
+        user_id,
+        &action,
+        &data.reason,
+      )
+      .await?;
+    }
+
     if let Some(ws) = websocket_info {
       ws.chatserver.do_send(SendAllMessage {
         op: UserOperation::BanUser,
@@ -787,6 +1762,63 @@ impl Perform for Oper<BanUser> {
   }
 }
 
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ShadowBanUser> {
+  type Response = ShadowBanUserResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ShadowBanUserResponse, LemmyError> {
+    let data: &ShadowBanUser = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    // Make sure user is an admin
+    let is_admin = move |conn: &'_ _| UserView::read(conn, user_id).map(|u| u.admin);
+    if !blocking(pool, is_admin).await?? {
+      return Err(APIError::err("not_an_admin").into());
+    }
+
+    // Only local users can be shadowbanned, since it works by holding back their activities
+    let other_user_id = data.user_id;
+    let other_user = blocking(pool, move |conn| User_::read(conn, other_user_id)).await??;
+    if !other_user.local || other_user.admin {
+      return Err(APIError::err("couldnt_update_user").into());
+    }
+
+    let shadow_ban = data.shadow_ban;
+    let shadow_ban_user = move |conn: &'_ _| User_::shadow_ban(conn, other_user_id, shadow_ban);
+    if blocking(pool, shadow_ban_user).await?.is_err() {
+      return Err(APIError::err("couldnt_update_user").into());
+    }
+
+    // Mod tables
+    let form = ModShadowBanForm {
+      mod_user_id: user_id,
+      other_user_id,
+      reason: data.reason.to_owned(),
+      shadow_banned: Some(shadow_ban),
+    };
+
+    blocking(pool, move |conn| ModShadowBan::create(conn, &form)).await??;
+
+    let user = blocking(pool, move |conn| UserView::read(conn, other_user_id)).await??;
+
+    // Unlike a ban this isn't sent out over the websocket, since that would give it away
+    Ok(ShadowBanUserResponse {
+      user,
+      shadow_banned: shadow_ban,
+    })
+  }
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for Oper<GetReplies> {
   type Response = GetRepliesResponse;
@@ -861,6 +1893,132 @@ impl Perform for Oper<GetUserMentions> {
   }
 }
 
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetUnreadCount> {
+  type Response = GetUnreadCountResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<GetUnreadCountResponse, LemmyError> {
+    let data: &GetUnreadCount = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    unread_count(pool, claims.id).await
+  }
+}
+
+/// What the inbox badges show. Also pushed to the user's sessions when it changes.
+pub async fn unread_count(
+  pool: &DbPool,
+  user_id: i32,
+) -> Result<GetUnreadCountResponse, LemmyError> {
+  let replies = blocking(pool, move |conn| ReplyView::count_unread(conn, user_id)).await??;
+
+  let mentions = blocking(pool, move |conn| {
+    UserMentionView::count_unread(conn, user_id)
+  })
+  .await??;
+
+  let private_messages = blocking(pool, move |conn| {
+    PrivateMessageView::count_unread(conn, user_id)
+  })
+  .await??;
+
+  Ok(GetUnreadCountResponse {
+    replies,
+    mentions,
+    private_messages,
+  })
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetInbox> {
+  type Response = GetInboxResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<GetInboxResponse, LemmyError> {
+    let data: &GetInbox = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let inbox_type = InboxType::from_str(&data.inbox_type)?;
+    // The builders borrow their sort, so each needs its own
+    let reply_sort = SortType::from_str(&data.sort)?;
+    let mention_sort = SortType::from_str(&data.sort)?;
+
+    let community_id = data.community_id;
+    let page = data.page;
+    let limit = data.limit;
+    let unread_only = data.unread_only;
+
+    let replies = if inbox_type == InboxType::All || inbox_type == InboxType::Replies {
+      blocking(pool, move |conn| {
+        ReplyQueryBuilder::create(conn, user_id)
+          .sort(&reply_sort)
+          .for_community_id(community_id)
+          .unread_only(unread_only)
+          .page(page)
+          .limit(limit)
+          .list()
+      })
+      .await??
+    } else {
+      Vec::new()
+    };
+
+    let mentions = if inbox_type == InboxType::All || inbox_type == InboxType::Mentions {
+      blocking(pool, move |conn| {
+        UserMentionQueryBuilder::create(conn, user_id)
+          .sort(&mention_sort)
+          .for_community_id(community_id)
+          .unread_only(unread_only)
+          .page(page)
+          .limit(limit)
+          .list()
+      })
+      .await??
+    } else {
+      Vec::new()
+    };
+
+    // Private messages aren't in any community
+    let messages = if (inbox_type == InboxType::All || inbox_type == InboxType::Messages)
+      && community_id.is_none()
+    {
+      blocking(pool, move |conn| {
+        PrivateMessageQueryBuilder::create(conn, user_id)
+          .unread_only(unread_only)
+          .page(page)
+          .limit(limit)
+          .list()
+      })
+      .await??
+    } else {
+      Vec::new()
+    };
+
+    Ok(GetInboxResponse {
+      replies,
+      mentions,
+      messages,
+    })
+  }
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for Oper<EditUserMention> {
   type Response = UserMentionResponse;
@@ -868,7 +2026,7 @@ impl Perform for Oper<EditUserMention> {
   async fn perform(
     &self,
     pool: &DbPool,
-    _websocket_info: Option<WebsocketInfo>,
+    websocket_info: Option<WebsocketInfo>,
   ) -> Result<UserMentionResponse, LemmyError> {
     let data: &EditUserMention = &self.data;
 
@@ -906,6 +2064,12 @@ impl Perform for Oper<EditUserMention> {
     })
     .await??;
 
+    if let Some(ws) = websocket_info {
+      ws.chatserver.do_send(SendUnreadCounts {
+        user_ids: vec![user_id],
+      });
+    }
+
     Ok(UserMentionResponse {
       mention: user_mention_view,
     })
@@ -919,7 +2083,7 @@ impl Perform for Oper<MarkAllAsRead> {
   async fn perform(
     &self,
     pool: &DbPool,
-    _websocket_info: Option<WebsocketInfo>,
+    websocket_info: Option<WebsocketInfo>,
   ) -> Result<GetRepliesResponse, LemmyError> {
     let data: &MarkAllAsRead = &self.data;
 
@@ -930,82 +2094,39 @@ impl Perform for Oper<MarkAllAsRead> {
 
     let user_id = claims.id;
 
-    let replies = blocking(pool, move |conn| {
-      ReplyQueryBuilder::create(conn, user_id)
-        .unread_only(true)
-        .page(1)
-        .limit(999)
-        .list()
-    })
-    .await??;
+    // Leaving out the type marks everything as read
+    let inbox_type = match &data.inbox_type {
+      Some(inbox_type) => InboxType::from_str(inbox_type)?,
+      None => InboxType::All,
+    };
 
-    // TODO: this should probably be a bulk operation
-    for reply in &replies {
-      let reply_id = reply.id;
-      let mark_as_read = move |conn: &'_ _| Comment::mark_as_read(conn, reply_id);
+    if inbox_type == InboxType::All || inbox_type == InboxType::Replies {
+      let mark_as_read = move |conn: &'_ _| Comment::mark_all_replies_as_read(conn, user_id);
       if blocking(pool, mark_as_read).await?.is_err() {
         return Err(APIError::err("couldnt_update_comment").into());
       }
     }
 
-    // Mentions
-    let mentions = blocking(pool, move |conn| {
-      UserMentionQueryBuilder::create(conn, user_id)
-        .unread_only(true)
-        .page(1)
-        .limit(999)
-        .list()
-    })
-    .await??;
-
-    // TODO: this should probably be a bulk operation
-    for mention in &mentions {
-      let mention_form = UserMentionForm {
-        recipient_id: mention.to_owned().recipient_id,
-        comment_id: mention.to_owned().id,
-        read: Some(true),
-      };
-
-      let user_mention_id = mention.user_mention_id;
-      let update_mention =
-        move |conn: &'_ _| UserMention::update(conn, user_mention_id, &mention_form);
-      if blocking(pool, update_mention).await?.is_err() {
+    if inbox_type == InboxType::All || inbox_type == InboxType::Mentions {
+      let mark_as_read = move |conn: &'_ _| UserMention::mark_all_as_read(conn, user_id);
+      if blocking(pool, mark_as_read).await?.is_err() {
         return Err(APIError::err("couldnt_update_comment").into());
       }
     }
 
-    // messages
-    let messages = blocking(pool, move |conn| {
-      PrivateMessageQueryBuilder::create(conn, user_id)
-        .page(1)
-        .limit(999)
-        .unread_only(true)
-        .list()
-    })
-    .await??;
-
-    // TODO: this should probably be a bulk operation
-    for message in &messages {
-      let private_message_form = PrivateMessageForm {
-        content: message.to_owned().content,
-        creator_id: message.to_owned().creator_id,
-        recipient_id: message.to_owned().recipient_id,
-        deleted: None,
-        read: Some(true),
-        updated: None,
-        ap_id: message.to_owned().ap_id,
-        local: message.local,
-        published: None,
-      };
-
-      let message_id = message.id;
-      let update_pm =
-        move |conn: &'_ _| PrivateMessage::update(conn, message_id, &private_message_form);
-      if blocking(pool, update_pm).await?.is_err() {
+    if inbox_type == InboxType::All || inbox_type == InboxType::Messages {
+      let mark_as_read = move |conn: &'_ _| PrivateMessage::mark_all_as_read(conn, user_id);
+      if blocking(pool, mark_as_read).await?.is_err() {
         return Err(APIError::err("couldnt_update_private_message").into());
       }
     }
 
+    if let Some(ws) = websocket_info {
+      ws.chatserver.do_send(SendUnreadCounts {
+        user_ids: vec![user_id],
+      });
+    }
+
     Ok(GetRepliesResponse { replies: vec![] })
   }
 }
@@ -1073,6 +2194,10 @@ impl Perform for Oper<DeleteAccount> {
       }
     }
 
+    // And the images they uploaded
+    let media = blocking(pool, move |conn| Media::for_user(conn, user_id)).await??;
+    delete_media(pool, &self.client, media).await?;
+
     Ok(LoginResponse {
       jwt: data.auth.to_owned(),
     })
@@ -1097,6 +2222,11 @@ impl Perform for Oper<PasswordReset> {
       Err(_e) => return Err(APIError::err("couldnt_find_that_username_or_email").into()),
     };
 
+    // The directory keeps the passwords of its users
+    if user.directory_account {
+      return Err(APIError::err("directory_account_password").into());
+    }
+
     // Generate a random token
     let token = generate_random_string();
 
@@ -1109,71 +2239,239 @@ impl Perform for Oper<PasswordReset> {
     .await??;
 
     // Email the pure token to the user.
-    // TODO no i18n support here.
     let user_email = &user.email.expect("email");
-    let subject = &format!("Password reset for {}", user.name);
+    let langs = &user_langs(&user.lang);
+    let username = [("username", user.name.as_str())];
+    let subject = &translate(langs, "emails.password_reset_subject", &username);
     let hostname = &format!("https://{}", Settings::get().hostname); //TODO add https for now.
-    let html = &format!("<h1>Password Reset Request for {}</h1><br><a href={}/password_change/{}>Click here to reset your password</a>", user.name, hostname, &token);
+    let html = &format!(
+      "<h1>{}</h1><br><a href={}/password_change/{}>{}</a>",
+      translate(langs, "emails.password_reset_title", &username),
+      hostname,
+      &token,
+      translate(langs, "emails.password_reset_link", &[])
+    );
     match send_email(subject, user_email, &user.name, html) {
       Ok(_o) => _o,
       Err(_e) => return Err(APIError::err(&_e).into()),
     };
 
-    Ok(PasswordResetResponse {})
+    Ok(PasswordResetResponse {})
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<PasswordChange> {
+  type Response = LoginResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<LoginResponse, LemmyError> {
+    let data: &PasswordChange = &self.data;
+
+    // Fetch the user_id from the token
+    let token = data.token.clone();
+    let user_id = blocking(pool, move |conn| {
+      PasswordResetRequest::read_from_token(conn, &token).map(|p| p.user_id)
+    })
+    .await??;
+
+    // The directory keeps the passwords of its users
+    let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+    if user.directory_account {
+      return Err(APIError::err("directory_account_password").into());
+    }
+
+    // Make sure passwords match
+    if data.password != data.password_verify {
+      return Err(APIError::err("passwords_dont_match").into());
+    }
+
+    // Update the user with the new password
+    let password = data.password.clone();
+    let updated_user = match blocking(pool, move |conn| {
+      User_::update_password(conn, user_id, &password)
+    })
+    .await?
+    {
+      Ok(user) => user,
+      Err(_e) => return Err(APIError::err("couldnt_update_user").into()),
+    };
+
+    // Return the jwt
+    Ok(LoginResponse {
+      jwt: Claims::jwt(updated_user, Settings::get().hostname),
+    })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<VerifyEmail> {
+  type Response = VerifyEmailResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<VerifyEmailResponse, LemmyError> {
+    let data: &VerifyEmail = &self.data;
+
+    // Fetch the verification from the token
+    let token = data.token.clone();
+    let verification = match blocking(pool, move |conn| {
+      EmailVerification::read_from_token(conn, &token)
+    })
+    .await?
+    {
+      Ok(verification) => verification,
+      Err(_e) => return Err(APIError::err("couldnt_verify_email").into()),
+    };
+
+    // The token is only valid for the email it was sent to
+    let user_id = verification.user_id;
+    let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+    if user.email != Some(verification.email) {
+      return Err(APIError::err("couldnt_verify_email").into());
+    }
+
+    let verify = move |conn: &'_ _| User_::update_email_verified(conn, user_id, true);
+    if blocking(pool, verify).await?.is_err() {
+      return Err(APIError::err("couldnt_update_user").into());
+    }
+
+    blocking(pool, move |conn| {
+      EmailVerification::delete_for_user(conn, user_id)
+    })
+    .await??;
+
+    Ok(VerifyEmailResponse {})
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ResendVerificationEmail> {
+  type Response = ResendVerificationEmailResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ResendVerificationEmailResponse, LemmyError> {
+    let data: &ResendVerificationEmail = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+
+    if user.email_verified {
+      return Err(APIError::err("email_already_verified").into());
+    }
+
+    let email = match &user.email {
+      Some(email) => email.to_owned(),
+      None => return Err(APIError::err("no_email_setup").into()),
+    };
+
+    send_verification_email(&user, &email, pool).await?;
+
+    Ok(ResendVerificationEmailResponse {})
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ExportUserData> {
+  type Response = UserExportResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<UserExportResponse, LemmyError> {
+    let data: &ExportUserData = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    // Only one export at a time
+    if blocking(pool, move |conn| UserExport::in_progress(conn, user_id)).await?? {
+      return Err(APIError::err("export_already_in_progress").into());
+    }
+
+    let export_form = UserExportForm {
+      user_id,
+      data: None,
+      completed: None,
+    };
+
+    let inserted_export =
+      match blocking(pool, move |conn| UserExport::create(conn, &export_form)).await? {
+        Ok(export) => export,
+        Err(_e) => return Err(APIError::err("couldnt_create_export").into()),
+      };
+
+    // Assembling the archive can take a while, so its done in the background
+    let export_id = inserted_export.id;
+    enqueue(pool, Task::GenerateUserExport { export_id }).await?;
+
+    Ok(UserExportResponse {
+      export: inserted_export,
+    })
   }
 }
 
 #[async_trait::async_trait(?Send)]
-impl Perform for Oper<PasswordChange> {
-  type Response = LoginResponse;
+impl Perform for Oper<GetUserDataExport> {
+  type Response = UserExportResponse;
 
   async fn perform(
     &self,
     pool: &DbPool,
     _websocket_info: Option<WebsocketInfo>,
-  ) -> Result<LoginResponse, LemmyError> {
-    let data: &PasswordChange = &self.data;
+  ) -> Result<UserExportResponse, LemmyError> {
+    let data: &GetUserDataExport = &self.data;
 
-    // Fetch the user_id from the token
-    let token = data.token.clone();
-    let user_id = blocking(pool, move |conn| {
-      PasswordResetRequest::read_from_token(conn, &token).map(|p| p.user_id)
-    })
-    .await??;
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
 
-    // Make sure passwords match
-    if data.password != data.password_verify {
-      return Err(APIError::err("passwords_dont_match").into());
-    }
+    let user_id = claims.id;
 
-    // Update the user with the new password
-    let password = data.password.clone();
-    let updated_user = match blocking(pool, move |conn| {
-      User_::update_password(conn, user_id, &password)
-    })
-    .await?
-    {
-      Ok(user) => user,
-      Err(_e) => return Err(APIError::err("couldnt_update_user").into()),
+    let export_id = data.export_id;
+    let export = match blocking(pool, move |conn| UserExport::read(conn, export_id)).await? {
+      Ok(export) => export,
+      Err(_e) => return Err(APIError::err("couldnt_find_export").into()),
     };
 
-    // Return the jwt
-    Ok(LoginResponse {
-      jwt: Claims::jwt(updated_user, Settings::get().hostname),
-    })
+    // Only the user themselves can download it
+    if export.user_id != user_id {
+      return Err(APIError::err("couldnt_find_export").into());
+    }
+
+    Ok(UserExportResponse { export })
   }
 }
 
 #[async_trait::async_trait(?Send)]
-impl Perform for Oper<CreatePrivateMessage> {
-  type Response = PrivateMessageResponse;
+impl Perform for Oper<ImportUserData> {
+  type Response = ImportUserDataResponse;
 
   async fn perform(
     &self,
     pool: &DbPool,
-    websocket_info: Option<WebsocketInfo>,
-  ) -> Result<PrivateMessageResponse, LemmyError> {
-    let data: &CreatePrivateMessage = &self.data;
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ImportUserDataResponse, LemmyError> {
+    let data: &ImportUserData = &self.data;
 
     let claims = match Claims::decode(&data.auth) {
       Ok(claims) => claims.claims,
@@ -1182,96 +2480,87 @@ impl Perform for Oper<CreatePrivateMessage> {
 
     let user_id = claims.id;
 
-    let hostname = &format!("https://{}", Settings::get().hostname);
-
-    // Check for a site ban
-    let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
-    if user.banned {
-      return Err(APIError::err("site_ban").into());
+    let entries = data.archive.as_ref().map_or(0, |archive| {
+      archive.followed_communities.len() + archive.saved_posts.len() + archive.saved_comments.len()
+    }) + data.communities.as_ref().map_or(0, |c| c.len());
+    if entries > MAX_IMPORT_ENTRIES {
+      return Err(APIError::err("import_too_large").into());
     }
 
-    let content_slurs_removed = remove_slurs(&data.content.to_owned());
+    // Gather up the community actor ids from both sources
+    let mut failed: Vec<String> = Vec::new();
+    let mut communities: Vec<String> = Vec::new();
+    let mut saved_posts: Vec<String> = Vec::new();
+    let mut saved_comments: Vec<String> = Vec::new();
 
-    let private_message_form = PrivateMessageForm {
-      content: content_slurs_removed.to_owned(),
-      creator_id: user_id,
-      recipient_id: data.recipient_id,
-      deleted: None,
-      read: None,
-      updated: None,
-      ap_id: "http://fake.com".into(),
-      local: true,
-      published: None,
-    };
+    if let Some(archive) = &data.archive {
+      communities.extend(archive.followed_communities.to_owned());
+      saved_posts.extend(archive.saved_posts.to_owned());
+      saved_comments.extend(archive.saved_comments.to_owned());
+    }
 
-    let inserted_private_message = match blocking(pool, move |conn| {
-      PrivateMessage::create(conn, &private_message_form)
-    })
-    .await?
-    {
-      Ok(private_message) => private_message,
-      Err(_e) => {
-        return Err(APIError::err("couldnt_create_private_message").into());
+    if let Some(handles) = &data.communities {
+      for handle in handles {
+        match community_actor_id_from_handle(handle) {
+          Some(actor_id) => communities.push(actor_id),
+          None => failed.push(handle.to_owned()),
+        }
       }
-    };
+    }
 
-    let inserted_private_message_id = inserted_private_message.id;
-    let updated_private_message = match blocking(pool, move |conn| {
-      let apub_id = make_apub_endpoint(
-        EndpointType::PrivateMessage,
-        &inserted_private_message_id.to_string(),
-      )
-      .to_string();
-      PrivateMessage::update_ap_id(&conn, inserted_private_message_id, apub_id)
-    })
-    .await?
-    {
-      Ok(private_message) => private_message,
-      Err(_e) => return Err(APIError::err("couldnt_create_private_message").into()),
+    // Fetching all of it from the other instances can take a while, so its done in the background
+    let task = Task::ImportUserData {
+      user_id,
+      communities,
+      saved_posts,
+      saved_comments,
     };
+    enqueue(pool, task).await?;
+
+    Ok(ImportUserDataResponse {
+      completed: false,
+      followed_communities: 0,
+      saved_posts: 0,
+      saved_comments: 0,
+      failed,
+      error: None,
+    })
+  }
+}
 
-    updated_private_message
-      .send_create(&user, &self.client, pool)
-      .await?;
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<CreatePrivateMessage> {
+  type Response = PrivateMessageResponse;
 
-    // Send notifications to the recipient
-    let recipient_id = data.recipient_id;
-    let recipient_user = blocking(pool, move |conn| User_::read(conn, recipient_id)).await??;
-    if recipient_user.send_notifications_to_email {
-      if let Some(email) = recipient_user.email {
-        let subject = &format!(
-          "{} - Private Message from {}",
-          Settings::get().hostname,
-          claims.username
-        );
-        let html = &format!(
-          "<h1>Private Message</h1><br><div>{} - {}</div><br><a href={}/inbox>inbox</a>",
-          claims.username, &content_slurs_removed, hostname
-        );
-        match send_email(subject, &email, &recipient_user.name, html) {
-          Ok(_o) => _o,
-          Err(e) => error!("{}", e),
-        };
-      }
-    }
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<PrivateMessageResponse, LemmyError> {
+    let data: &CreatePrivateMessage = &self.data;
 
-    let message = blocking(pool, move |conn| {
-      PrivateMessageView::read(conn, inserted_private_message.id)
-    })
-    .await??;
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
 
-    let res = PrivateMessageResponse { message };
+    let user_id = claims.id;
 
-    if let Some(ws) = websocket_info {
-      ws.chatserver.do_send(SendUserRoomMessage {
-        op: UserOperation::CreatePrivateMessage,
-        response: res.clone(),
-        recipient_id: recipient_user.id,
-        my_id: ws.id,
-      });
+    // Check for a site ban
+    let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+    if user.is_banned() {
+      return Err(APIError::err("site_ban").into());
     }
 
-    Ok(res)
+    send_private_message(
+      pool,
+      &self.client,
+      websocket_info,
+      &user,
+      data.recipient_id,
+      &data.content,
+    )
+    .await
   }
 }
 
@@ -1299,7 +2588,7 @@ impl Perform for Oper<EditPrivateMessage> {
 
     // Check for a site ban
     let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
-    if user.banned {
+    if user.is_banned() {
       return Err(APIError::err("site_ban").into());
     }
 
@@ -1381,12 +2670,18 @@ impl Perform for Oper<EditPrivateMessage> {
     let res = PrivateMessageResponse { message };
 
     if let Some(ws) = websocket_info {
-      ws.chatserver.do_send(SendUserRoomMessage {
+      ws.chatserver.do_send(SendPrivateMessage {
         op: UserOperation::EditPrivateMessage,
-        response: res.clone(),
-        recipient_id: orig_private_message.recipient_id,
+        message: res.clone(),
+        notify_recipient: true,
         my_id: ws.id,
       });
+
+      if data.read.is_some() {
+        ws.chatserver.do_send(SendUnreadCounts {
+          user_ids: vec![user_id],
+        });
+      }
     }
 
     Ok(res)
@@ -1427,6 +2722,96 @@ impl Perform for Oper<GetPrivateMessages> {
   }
 }
 
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetPrivateMessageConversations> {
+  type Response = PrivateMessageConversationsResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<PrivateMessageConversationsResponse, LemmyError> {
+    let data: &GetPrivateMessageConversations = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let page = data.page;
+    let limit = data.limit;
+    let unread_only = data.unread_only;
+    let conversations = blocking(pool, move |conn| {
+      PrivateMessageConversationQueryBuilder::create(&conn, user_id)
+        .page(page)
+        .limit(limit)
+        .unread_only(unread_only)
+        .list()
+    })
+    .await??;
+
+    Ok(PrivateMessageConversationsResponse { conversations })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<GetPrivateMessageConversation> {
+  type Response = PrivateMessageConversationResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<PrivateMessageConversationResponse, LemmyError> {
+    let data: &GetPrivateMessageConversation = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    // Only the two participants can read a conversation
+    let conversation_id = data.conversation_id;
+    let conversation = match blocking(pool, move |conn| {
+      PrivateMessageConversationView::read(conn, conversation_id, user_id)
+    })
+    .await?
+    {
+      Ok(conversation) => conversation,
+      Err(_e) => return Err(APIError::err("couldnt_find_private_message_conversation").into()),
+    };
+
+    let page = data.page;
+    let limit = data.limit;
+    let messages = blocking(pool, move |conn| {
+      PrivateMessageQueryBuilder::create(&conn, user_id)
+        .for_conversation_id(conversation_id)
+        .page(page)
+        .limit(limit)
+        .list()
+    })
+    .await??;
+
+    if let Some(ws) = websocket_info {
+      if let Some(id) = ws.id {
+        ws.chatserver.do_send(JoinConversationRoom {
+          conversation_id,
+          id,
+        });
+      }
+    }
+
+    Ok(PrivateMessageConversationResponse {
+      conversation,
+      messages,
+    })
+  }
+}
+
 #[async_trait::async_trait(?Send)]
 impl Perform for Oper<UserJoin> {
   type Response = UserJoinResponse;
@@ -1447,10 +2832,462 @@ impl Perform for Oper<UserJoin> {
 
     if let Some(ws) = websocket_info {
       if let Some(id) = ws.id {
-        ws.chatserver.do_send(JoinUserRoom { user_id, id });
+        ws.chatserver.do_send(JoinUserRoom {
+          user_id,
+          hide_presence: claims.hide_presence,
+          id,
+        });
       }
     }
 
     Ok(UserJoinResponse { user_id })
   }
 }
+
+/// Sends a private message from a local user, letting the recipient know by email and push
+/// notification if they want that.
+pub async fn send_private_message(
+  pool: &DbPool,
+  client: &Client,
+  websocket_info: Option<WebsocketInfo>,
+  sender: &User_,
+  recipient_id: i32,
+  content: &str,
+) -> Result<PrivateMessageResponse, LemmyError> {
+  let hostname = &format!("https://{}", Settings::get().hostname);
+
+  let content_slurs_removed = remove_slurs(content);
+
+  let private_message_form = PrivateMessageForm {
+    content: content_slurs_removed.to_owned(),
+    creator_id: sender.id,
+    recipient_id,
+    deleted: None,
+    read: None,
+    updated: None,
+    ap_id: "http://fake.com".into(),
+    local: true,
+    published: None,
+  };
+
+  let inserted_private_message = match blocking(pool, move |conn| {
+    PrivateMessage::create(conn, &private_message_form)
+  })
+  .await?
+  {
+    Ok(private_message) => private_message,
+    Err(_e) => {
+      return Err(APIError::err("couldnt_create_private_message").into());
+    }
+  };
+
+  let inserted_private_message_id = inserted_private_message.id;
+  let updated_private_message = match blocking(pool, move |conn| {
+    let apub_id = make_apub_endpoint(
+      EndpointType::PrivateMessage,
+      &inserted_private_message_id.to_string(),
+    )
+    .to_string();
+    PrivateMessage::update_ap_id(&conn, inserted_private_message_id, apub_id)
+  })
+  .await?
+  {
+    Ok(private_message) => private_message,
+    Err(_e) => return Err(APIError::err("couldnt_create_private_message").into()),
+  };
+
+  updated_private_message
+    .send_create(sender, client, pool)
+    .await?;
+
+  // Send notifications to the recipient
+  let recipient_user = blocking(pool, move |conn| User_::read(conn, recipient_id)).await??;
+  let notify_recipient = blocking(pool, move |conn| {
+    wants_notification(conn, recipient_id, |s| s.private_messages)
+  })
+  .await?;
+  let langs = &user_langs(&recipient_user.lang);
+  if notify_recipient && recipient_user.send_notifications_to_email {
+    if let Some(email) = recipient_user.email {
+      let subject = &translate(
+        langs,
+        "emails.private_message_subject",
+        &[
+          ("site", &Settings::get().hostname),
+          ("username", &sender.name),
+        ],
+      );
+      let html = &notification_email_html(
+        langs,
+        "emails.private_message_title",
+        &sender.name,
+        &content_slurs_removed,
+        hostname,
+      );
+      match send_email(subject, &email, &recipient_user.name, html) {
+        Ok(_o) => _o,
+        Err(e) => error!("{}", e),
+      };
+    }
+  }
+
+  if notify_recipient {
+    let push_notification = PushNotification::new(
+      recipient_id,
+      &translate(
+        langs,
+        "notifications.private_message_from",
+        &[("username", &sender.name)],
+      ),
+      &content_slurs_removed,
+      &format!("{}/inbox", hostname),
+    );
+    let push_notifications = vec![push_notification];
+    queue_matrix_notifications(pool, &push_notifications).await?;
+    queue_push_notifications(pool, push_notifications).await?;
+  }
+
+  let message = blocking(pool, move |conn| {
+    PrivateMessageView::read(conn, inserted_private_message.id)
+  })
+  .await??;
+
+  let res = PrivateMessageResponse { message };
+
+  if let Some(ws) = websocket_info {
+    ws.chatserver.do_send(SendPrivateMessage {
+      op: UserOperation::CreatePrivateMessage,
+      message: res.clone(),
+      notify_recipient,
+      my_id: ws.id,
+    });
+    ws.chatserver.do_send(SendUnreadCounts {
+      user_ids: vec![recipient_id],
+    });
+  }
+
+  Ok(res)
+}
+
+/// Tells a user in their inbox that a mod acted on them or their content, with the reason if the
+/// mod gave one. Users who turned off mod action notifications don't get it.
+pub async fn send_mod_action_notice(
+  pool: &DbPool,
+  client: &Client,
+  websocket_info: Option<WebsocketInfo>,
+  mod_: &User_,
+  recipient_id: i32,
+  action: &str,
+  reason: &Option<String>,
+) -> Result<(), LemmyError> {
+  if recipient_id == mod_.id {
+    return Ok(());
+  }
+
+  let notify = blocking(pool, move |conn| {
+    wants_notification(conn, recipient_id, |s| s.mod_actions)
+  })
+  .await?;
+  if !notify {
+    return Ok(());
+  }
+
+  let content = match reason {
+    Some(reason) if !reason.trim().is_empty() => format!("{}\n\nReason: {}", action, reason.trim()),
+    _ => action.to_owned(),
+  };
+  send_private_message(pool, client, websocket_info, mod_, recipient_id, &content).await?;
+
+  Ok(())
+}
+
+/// What a banned user gets told, saying when the ban ends if it's temporary.
+pub fn ban_notice(banned_from: &str, expires: Option<chrono::NaiveDateTime>) -> String {
+  match expires {
+    Some(expires) => format!(
+      "You were banned from {} until {} UTC.",
+      banned_from,
+      expires.format("%Y-%m-%d %H:%M")
+    ),
+    None => format!("You were banned from {}.", banned_from),
+  }
+}
+
+/// Replaces any outstanding verification token for the user, and emails them a link with the new one.
+pub async fn send_verification_email(
+  user: &User_,
+  email: &str,
+  pool: &DbPool,
+) -> Result<(), LemmyError> {
+  // Generate a random token
+  let token = generate_random_string();
+
+  // Insert the row, dropping any older tokens
+  let token2 = token.clone();
+  let user_id = user.id;
+  let email2 = email.to_owned();
+  blocking(pool, move |conn| {
+    EmailVerification::delete_for_user(conn, user_id)?;
+    EmailVerification::create_token(conn, user_id, &email2, &token2)
+  })
+  .await??;
+
+  // Email the pure token to the user.
+  let langs = &user_langs(&user.lang);
+  let subject = &translate(
+    langs,
+    "emails.verify_email_subject",
+    &[("site", &Settings::get().hostname)],
+  );
+  let hostname = &format!("https://{}", Settings::get().hostname); //TODO add https for now.
+  let html = &format!(
+    "<h1>{}</h1><br><a href={}/verify_email/{}>{}</a>",
+    translate(
+      langs,
+      "emails.verify_email_title",
+      &[("username", &user.name)]
+    ),
+    hostname,
+    &token,
+    translate(langs, "emails.verify_email_link", &[])
+  );
+  match send_email(subject, email, &user.name, html) {
+    Ok(_o) => Ok(()),
+    Err(e) => Err(APIError::err(&e).into()),
+  }
+}
+
+/// Fills in the archive for an export, then lets the user know it can be downloaded.
+/// Assembles the archive, and lets the user know it's ready.
+pub async fn generate_user_export(
+  pool: &DbPool,
+  export_id: i32,
+  websocket_info: Option<WebsocketInfo>,
+) -> Result<(), LemmyError> {
+  let mut export = blocking(pool, move |conn| UserExport::generate(conn, export_id)).await??;
+
+  let user_id = export.user_id;
+  let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+
+  // The archive itself is left out of the notification, its fetched with GetUserDataExport
+  export.data = None;
+  let res = UserExportResponse { export };
+
+  if let Some(ws) = websocket_info {
+    ws.chatserver.do_send(SendUserRoomMessage {
+      op: UserOperation::ExportUserData,
+      response: res,
+      recipient_id: user.id,
+      my_id: None,
+    });
+  }
+
+  if let Some(email) = &user.email {
+    let langs = &user_langs(&user.lang);
+    let subject = &translate(
+      langs,
+      "emails.export_ready_subject",
+      &[("site", &Settings::get().hostname)],
+    );
+    let hostname = &format!("https://{}", Settings::get().hostname);
+    let html = &format!(
+      "<h1>{}</h1><br><a href={}/settings>{}</a>",
+      translate(
+        langs,
+        "emails.export_ready_title",
+        &[("username", &user.name)]
+      ),
+      hostname,
+      translate(langs, "emails.export_ready_link", &[])
+    );
+    if let Err(e) = send_email(subject, email, &user.name, html) {
+      error!("{}", e);
+    }
+  }
+
+  Ok(())
+}
+
+/// Gives up on an export that ran out of attempts, so the user can ask for another one.
+pub async fn fail_user_export(
+  pool: &DbPool,
+  export_id: i32,
+  failure: String,
+  websocket_info: Option<WebsocketInfo>,
+) -> Result<(), LemmyError> {
+  let export = blocking(pool, move |conn| {
+    UserExport::fail(conn, export_id, &failure)
+  })
+  .await??;
+
+  if let Some(ws) = websocket_info {
+    ws.chatserver.do_send(SendUserRoomMessage {
+      op: UserOperation::ExportUserData,
+      recipient_id: export.user_id,
+      response: UserExportResponse { export },
+      my_id: None,
+    });
+  }
+
+  Ok(())
+}
+
+/// Follows the communities and saves the posts and comments of an import, fetching the remote
+/// ones, then lets the user know how it went.
+pub async fn import_user_data(
+  pool: &DbPool,
+  client: &Client,
+  user_id: i32,
+  communities: &[String],
+  saved_posts: &[String],
+  saved_comments: &[String],
+  websocket_info: Option<WebsocketInfo>,
+) -> Result<(), LemmyError> {
+  let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+
+  let mut res = ImportUserDataResponse {
+    completed: true,
+    followed_communities: 0,
+    saved_posts: 0,
+    saved_comments: 0,
+    failed: Vec::new(),
+    error: None,
+  };
+
+  // Resolve each community, fetching the remote ones, and follow it
+  for actor_id in communities {
+    let community = match get_or_fetch_and_upsert_remote_community(actor_id, client, pool).await {
+      Ok(community) => community,
+      Err(_e) => {
+        res.failed.push(actor_id.to_owned());
+        continue;
+      }
+    };
+
+    if community.local {
+      let community_follower_form = CommunityFollowerForm {
+        community_id: community.id,
+        user_id,
+      };
+      let follow = move |conn: &'_ _| CommunityFollower::follow(conn, &community_follower_form);
+      // Already following it is fine
+      blocking(pool, follow).await?.ok();
+    } else if user
+      .send_follow(&community.actor_id, client, pool)
+      .await
+      .is_err()
+    {
+      res.failed.push(actor_id.to_owned());
+      continue;
+    }
+    res.followed_communities += 1;
+  }
+
+  for ap_id in saved_posts {
+    let post = match get_or_fetch_and_insert_remote_post(ap_id, client, pool).await {
+      Ok(post) => post,
+      Err(_e) => {
+        res.failed.push(ap_id.to_owned());
+        continue;
+      }
+    };
+
+    let post_saved_form = PostSavedForm {
+      post_id: post.id,
+      user_id,
+    };
+    blocking(pool, move |conn| PostSaved::save(conn, &post_saved_form))
+      .await?
+      .ok();
+    res.saved_posts += 1;
+  }
+
+  for ap_id in saved_comments {
+    let comment = match get_or_fetch_and_insert_remote_comment(ap_id, client, pool).await {
+      Ok(comment) => comment,
+      Err(_e) => {
+        res.failed.push(ap_id.to_owned());
+        continue;
+      }
+    };
+
+    let comment_saved_form = CommentSavedForm {
+      comment_id: comment.id,
+      user_id,
+    };
+    blocking(pool, move |conn| {
+      CommentSaved::save(conn, &comment_saved_form)
+    })
+    .await?
+    .ok();
+    res.saved_comments += 1;
+  }
+
+  if let Some(ws) = websocket_info {
+    ws.chatserver.do_send(SendUserRoomMessage {
+      op: UserOperation::ImportUserData,
+      response: res,
+      recipient_id: user_id,
+      my_id: None,
+    });
+  }
+
+  Ok(())
+}
+
+/// Lets the user know an import ran out of attempts. What got done before that stays done.
+pub async fn fail_user_import(
+  user_id: i32,
+  failure: String,
+  websocket_info: Option<WebsocketInfo>,
+) -> Result<(), LemmyError> {
+  if let Some(ws) = websocket_info {
+    ws.chatserver.do_send(SendUserRoomMessage {
+      op: UserOperation::ImportUserData,
+      recipient_id: user_id,
+      response: ImportUserDataResponse {
+        completed: true,
+        followed_communities: 0,
+        saved_posts: 0,
+        saved_comments: 0,
+        failed: Vec::new(),
+        error: Some(failure),
+      },
+      my_id: None,
+    });
+  }
+
+  Ok(())
+}
+
+/// Turns a handle like `!main@lemmy.ml` into the community's actor id. Actor ids themselves are
+/// passed through.
+fn community_actor_id_from_handle(handle: &str) -> Option<String> {
+  let handle = handle.trim();
+  if handle.starts_with("http://") || handle.starts_with("https://") {
+    return Some(handle.to_owned());
+  }
+
+  let split = handle
+    .trim_start_matches('!')
+    .split('@')
+    .collect::<Vec<&str>>();
+  match split.as_slice() {
+    [name] if !name.is_empty() => {
+      Some(make_apub_endpoint(EndpointType::Community, name).to_string())
+    }
+    [name, instance] if !name.is_empty() && !instance.is_empty() => {
+      if *instance == Settings::get().hostname {
+        Some(make_apub_endpoint(EndpointType::Community, name).to_string())
+      } else {
+        Some(format!(
+          "{}://{}/c/{}",
+          get_apub_protocol_string(),
+          instance,
+          name
+        ))
+      }
+    }
+    _ => None,
+  }
+}
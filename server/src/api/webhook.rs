@@ -0,0 +1,292 @@
+use crate::{
+  api::{check_admin_or_community_mod, claims::Claims, APIError, Oper, Perform},
+  blocking,
+  webhook::is_public_url,
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::{naive_now, user_view::*, webhook::*, Crud};
+use lemmy_utils::generate_random_string;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use url::Url;
+
+/// Lists the site wide webhooks, or the ones of a community.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ListWebhooks {
+  community_id: Option<i32>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CreateWebhook {
+  community_id: Option<i32>,
+  url: String,
+  events: Vec<WebhookEvent>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct EditWebhook {
+  webhook_id: i32,
+  url: String,
+  events: Vec<WebhookEvent>,
+  enabled: bool,
+  /// Makes a new secret, which is returned like on creation
+  new_secret: Option<bool>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeleteWebhook {
+  webhook_id: i32,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ListWebhooksResponse {
+  webhooks: Vec<Webhook>,
+}
+
+/// The secret that signs the deliveries is only shown here, when it's made.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct WebhookResponse {
+  webhook: Webhook,
+  secret: Option<String>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ListWebhooks> {
+  type Response = ListWebhooksResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListWebhooksResponse, LemmyError> {
+    let data: &ListWebhooks = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    let is_admin = check_webhook_allowed(pool, user_id, data.community_id).await?;
+
+    let community_id = data.community_id;
+    let webhooks = blocking(pool, move |conn| Webhook::list(conn, community_id)).await??;
+
+    // The receivers of the other mods can be anywhere, where only their creators should see them
+    let webhooks = webhooks
+      .into_iter()
+      .filter(|w| is_admin || w.creator_id == user_id)
+      .collect();
+
+    Ok(ListWebhooksResponse { webhooks })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<CreateWebhook> {
+  type Response = WebhookResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<WebhookResponse, LemmyError> {
+    let data: &CreateWebhook = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+    let is_admin = check_webhook_allowed(pool, user_id, data.community_id).await?;
+    check_webhook_url(&data.url, is_admin).await?;
+    let events = webhook_events(&data.events, data.community_id)?;
+
+    let secret = generate_random_string();
+    let webhook_form = WebhookForm {
+      community_id: data.community_id,
+      creator_id: user_id,
+      url: data.url.to_owned(),
+      secret: secret.to_owned(),
+      events,
+      enabled: true,
+      updated: None,
+    };
+
+    let create = move |conn: &'_ _| Webhook::create(conn, &webhook_form);
+    let webhook = match blocking(pool, create).await? {
+      Ok(webhook) => webhook,
+      Err(_e) => return Err(APIError::err("couldnt_create_webhook").into()),
+    };
+
+    Ok(WebhookResponse {
+      webhook,
+      secret: Some(secret),
+    })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<EditWebhook> {
+  type Response = WebhookResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<WebhookResponse, LemmyError> {
+    let data: &EditWebhook = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let webhook_id = data.webhook_id;
+    let read_webhook = match blocking(pool, move |conn| Webhook::read(conn, webhook_id)).await? {
+      Ok(webhook) => webhook,
+      Err(_e) => return Err(APIError::err("couldnt_find_webhook").into()),
+    };
+    let is_admin = check_webhook_allowed(pool, user_id, read_webhook.community_id).await?;
+    if !is_admin && read_webhook.creator_id != user_id {
+      return Err(APIError::err("no_webhook_edit_allowed").into());
+    }
+    check_webhook_url(&data.url, is_admin).await?;
+    let events = webhook_events(&data.events, read_webhook.community_id)?;
+
+    let new_secret = if data.new_secret == Some(true) {
+      Some(generate_random_string())
+    } else {
+      None
+    };
+    let webhook_form = WebhookForm {
+      community_id: read_webhook.community_id,
+      creator_id: read_webhook.creator_id,
+      url: data.url.to_owned(),
+      secret: new_secret.to_owned().unwrap_or(read_webhook.secret),
+      events,
+      enabled: data.enabled,
+      updated: Some(naive_now()),
+    };
+
+    let update = move |conn: &'_ _| Webhook::update(conn, webhook_id, &webhook_form);
+    let webhook = match blocking(pool, update).await? {
+      Ok(webhook) => webhook,
+      Err(_e) => return Err(APIError::err("couldnt_update_webhook").into()),
+    };
+
+    Ok(WebhookResponse {
+      webhook,
+      secret: new_secret,
+    })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<DeleteWebhook> {
+  type Response = ListWebhooksResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListWebhooksResponse, LemmyError> {
+    let data: &DeleteWebhook = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let webhook_id = data.webhook_id;
+    let read_webhook = match blocking(pool, move |conn| Webhook::read(conn, webhook_id)).await? {
+      Ok(webhook) => webhook,
+      Err(_e) => return Err(APIError::err("couldnt_find_webhook").into()),
+    };
+    let is_admin = check_webhook_allowed(pool, user_id, read_webhook.community_id).await?;
+    if !is_admin && read_webhook.creator_id != user_id {
+      return Err(APIError::err("no_webhook_edit_allowed").into());
+    }
+
+    let delete = move |conn: &'_ _| Webhook::delete(conn, webhook_id);
+    if blocking(pool, delete).await?.is_err() {
+      return Err(APIError::err("couldnt_delete_webhook").into());
+    }
+
+    let community_id = read_webhook.community_id;
+    let webhooks = blocking(pool, move |conn| Webhook::list(conn, community_id)).await??;
+    let webhooks = webhooks
+      .into_iter()
+      .filter(|w| is_admin || w.creator_id == user_id)
+      .collect();
+
+    Ok(ListWebhooksResponse { webhooks })
+  }
+}
+
+/// Admins manage the site wide webhooks, and mods the ones of their community. Whether the user
+/// is an admin.
+async fn check_webhook_allowed(
+  pool: &DbPool,
+  user_id: i32,
+  community_id: Option<i32>,
+) -> Result<bool, LemmyError> {
+  check_admin_or_community_mod(pool, user_id, community_id).await?;
+  let is_admin = move |conn: &'_ _| UserView::read(conn, user_id).map(|u| u.admin);
+  Ok(blocking(pool, is_admin).await??)
+}
+
+/// The server posts to the URL, so the ones of mods have to be https on a host name that resolves
+/// to public addresses only.
+async fn check_webhook_url(url: &str, is_admin: bool) -> Result<(), LemmyError> {
+  let url = match Url::parse(url) {
+    Ok(url) => url,
+    Err(_e) => return Err(APIError::err("invalid_webhook_url").into()),
+  };
+  let host = url.host_str().unwrap_or_default();
+
+  let valid = if is_admin {
+    (url.scheme() == "https" || url.scheme() == "http") && !host.is_empty()
+  } else {
+    url.scheme() == "https"
+      && host.contains('.')
+      && host.parse::<IpAddr>().is_err()
+      && !host.ends_with(".local")
+      && !host.ends_with(".internal")
+  };
+  if !valid || (!is_admin && !is_public_url(url.as_str()).await) {
+    return Err(APIError::err("invalid_webhook_url").into());
+  }
+
+  Ok(())
+}
+
+/// The names the events are stored by. The community webhooks only get community events.
+fn webhook_events(
+  events: &[WebhookEvent],
+  community_id: Option<i32>,
+) -> Result<Vec<String>, LemmyError> {
+  if events.is_empty() {
+    return Err(APIError::err("no_webhook_events").into());
+  }
+  if community_id.is_some() && events.iter().any(|e| e.is_site_wide()) {
+    return Err(APIError::err("site_wide_webhook_event").into());
+  }
+
+  let mut names: Vec<String> = events.iter().map(|e| e.name().to_owned()).collect();
+  names.sort();
+  names.dedup();
+  Ok(names)
+}
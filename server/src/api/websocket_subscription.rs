@@ -0,0 +1,213 @@
+use crate::{
+  api::{claims::Claims, APIError, Oper, Perform},
+  blocking,
+  websocket::{
+    server::{ChangeSessionUser, ChangeSubscriptions, ChatServer, Room, SessionUser},
+    WebsocketInfo,
+  },
+  ConnectionId,
+  DbPool,
+  LemmyError,
+};
+use actix::Addr;
+use lemmy_db::{user::User_, Crud};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Gets the live updates of these posts, communities and the inbox, on top of the rooms the
+/// session is already in. Only over the websocket.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct Subscribe {
+  #[serde(default)]
+  post_ids: Vec<i32>,
+  /// 0 is /c/all
+  #[serde(default)]
+  community_ids: Vec<i32>,
+  /// The replies, mentions and private messages of the logged in user
+  #[serde(default)]
+  inbox: bool,
+  auth: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct Unsubscribe {
+  #[serde(default)]
+  post_ids: Vec<i32>,
+  #[serde(default)]
+  community_ids: Vec<i32>,
+  #[serde(default)]
+  inbox: bool,
+  auth: Option<String>,
+}
+
+/// Everything the session gets the live updates of afterwards
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct SubscriptionsResponse {
+  post_ids: Vec<i32>,
+  community_ids: Vec<i32>,
+  inbox: bool,
+}
+
+/// Swaps in a new token on a live connection, keeping the subscriptions. Without one, the session
+/// leaves the inbox it was getting.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ReAuth {
+  auth: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ReAuthResponse {
+  user_id: Option<i32>,
+}
+
+/// Sent to the post and community rooms when the number of users reading them changes
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct UsersOnlineResponse {
+  pub post_id: Option<i32>,
+  pub community_id: Option<i32>,
+  pub online: usize,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<Subscribe> {
+  type Response = SubscriptionsResponse;
+
+  async fn perform(
+    &self,
+    _pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<SubscriptionsResponse, LemmyError> {
+    let data: &Subscribe = &self.data;
+
+    let rooms = rooms(&data.post_ids, &data.community_ids, data.inbox, &data.auth)?;
+    change_subscriptions(websocket_info, rooms, Vec::new()).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<Unsubscribe> {
+  type Response = SubscriptionsResponse;
+
+  async fn perform(
+    &self,
+    _pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<SubscriptionsResponse, LemmyError> {
+    let data: &Unsubscribe = &self.data;
+
+    let rooms = rooms(&data.post_ids, &data.community_ids, data.inbox, &data.auth)?;
+    change_subscriptions(websocket_info, Vec::new(), rooms).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ReAuth> {
+  type Response = ReAuthResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ReAuthResponse, LemmyError> {
+    let data: &ReAuth = &self.data;
+
+    let (chatserver, id) = websocket_session(websocket_info)?;
+
+    let user = match &data.auth {
+      Some(auth) => {
+        let user_id = match Claims::decode(auth) {
+          Ok(claims) => claims.claims.id,
+          Err(_e) => return Err(APIError::err("not_logged_in").into()),
+        };
+        let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+        if user.banned {
+          return Err(APIError::err("site_ban").into());
+        }
+        Some(SessionUser {
+          id: user.id,
+          hide_presence: user.hide_presence,
+        })
+      }
+      None => None,
+    };
+
+    chatserver.do_send(ChangeSessionUser { id, user });
+
+    Ok(ReAuthResponse {
+      user_id: user.map(|u| u.id),
+    })
+  }
+}
+
+/// The chat server and the connection, for the ops that only make sense over the websocket
+fn websocket_session(
+  websocket_info: Option<WebsocketInfo>,
+) -> Result<(Addr<ChatServer>, ConnectionId), LemmyError> {
+  match websocket_info {
+    Some(WebsocketInfo {
+      chatserver,
+      id: Some(id),
+    }) => Ok((chatserver, id)),
+    _ => Err(APIError::err("websocket_only").into()),
+  }
+}
+
+fn rooms(
+  post_ids: &[i32],
+  community_ids: &[i32],
+  inbox: bool,
+  auth: &Option<String>,
+) -> Result<Vec<Room>, LemmyError> {
+  let mut rooms = post_ids
+    .iter()
+    .map(|post_id| Room::Post(*post_id))
+    .chain(community_ids.iter().map(|id| Room::Community(*id)))
+    .collect::<Vec<Room>>();
+
+  if inbox {
+    let user_id = match auth.as_ref().map(|auth| Claims::decode(auth)) {
+      Some(Ok(claims)) => claims.claims.id,
+      _ => return Err(APIError::err("not_logged_in").into()),
+    };
+    rooms.push(Room::User(user_id));
+  }
+
+  Ok(rooms)
+}
+
+async fn change_subscriptions(
+  websocket_info: Option<WebsocketInfo>,
+  subscribe: Vec<Room>,
+  unsubscribe: Vec<Room>,
+) -> Result<SubscriptionsResponse, LemmyError> {
+  let (chatserver, id) = websocket_session(websocket_info)?;
+
+  let rooms = match chatserver
+    .send(ChangeSubscriptions {
+      id,
+      subscribe,
+      unsubscribe,
+    })
+    .await?
+  {
+    Ok(rooms) => rooms,
+    Err(_) => return Err(APIError::err("too_many_subscriptions").into()),
+  };
+
+  let mut res = SubscriptionsResponse {
+    post_ids: Vec::new(),
+    community_ids: Vec::new(),
+    inbox: false,
+  };
+  for room in rooms {
+    match room {
+      Room::Post(post_id) => res.post_ids.push(post_id),
+      Room::Community(community_id) => res.community_ids.push(community_id),
+      Room::User(_) => res.inbox = true,
+      Room::Conversation(_) => (),
+    }
+  }
+  res.post_ids.sort_unstable();
+  res.community_ids.sort_unstable();
+  Ok(res)
+}
@@ -0,0 +1,340 @@
+use crate::{
+  api::{
+    check_admin_or_community_mod,
+    claims::Claims,
+    report::{send_comment_report_to_mods, send_post_report_to_mods},
+    APIError,
+    Oper,
+    Perform,
+  },
+  blocking,
+  websocket::WebsocketInfo,
+  DbPool,
+  LemmyError,
+};
+use diesel::PgConnection;
+use lemmy_db::{naive_now, report::*, word_filter::*, Crud};
+use lemmy_utils::{
+  slurs_vec_to_str,
+  word_filter::{
+    build_word_filter_regex,
+    filter_words,
+    set_word_filters,
+    CompiledWordFilter,
+    FilteredText,
+    FlaggedWord,
+    WordFilterAction,
+  },
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::error;
+
+/// Lists the site wide filters, or the ones of a community.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ListWordFilters {
+  community_id: Option<i32>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CreateWordFilter {
+  community_id: Option<i32>,
+  pattern: String,
+  is_regex: bool,
+  action: i16,
+  replacement: Option<String>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct EditWordFilter {
+  edit_id: i32,
+  pattern: String,
+  is_regex: bool,
+  action: i16,
+  replacement: Option<String>,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeleteWordFilter {
+  filter_id: i32,
+  auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ListWordFiltersResponse {
+  filters: Vec<WordFilter>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<ListWordFilters> {
+  type Response = ListWordFiltersResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListWordFiltersResponse, LemmyError> {
+    let data: &ListWordFilters = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_admin_or_community_mod(pool, claims.id, data.community_id).await?;
+
+    let community_id = data.community_id;
+    let filters = blocking(pool, move |conn| WordFilter::list(conn, community_id)).await??;
+
+    Ok(ListWordFiltersResponse { filters })
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<CreateWordFilter> {
+  type Response = ListWordFiltersResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListWordFiltersResponse, LemmyError> {
+    let data: &CreateWordFilter = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_valid_word_filter(&data.pattern, data.is_regex, data.action)?;
+
+    let user_id = claims.id;
+    check_admin_or_community_mod(pool, user_id, data.community_id).await?;
+
+    let filter_form = WordFilterForm {
+      community_id: data.community_id,
+      pattern: data.pattern.trim().to_owned(),
+      is_regex: data.is_regex,
+      action: data.action,
+      replacement: data.replacement.to_owned(),
+      creator_id: Some(user_id),
+      updated: None,
+    };
+
+    let create = move |conn: &'_ _| WordFilter::create(conn, &filter_form);
+    if blocking(pool, create).await?.is_err() {
+      return Err(APIError::err("couldnt_create_word_filter").into());
+    }
+
+    reload_and_list(pool, data.community_id).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<EditWordFilter> {
+  type Response = ListWordFiltersResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListWordFiltersResponse, LemmyError> {
+    let data: &EditWordFilter = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    check_valid_word_filter(&data.pattern, data.is_regex, data.action)?;
+
+    let user_id = claims.id;
+
+    let edit_id = data.edit_id;
+    let read_filter = blocking(pool, move |conn| WordFilter::read(conn, edit_id)).await??;
+    check_admin_or_community_mod(pool, user_id, read_filter.community_id).await?;
+
+    let filter_form = WordFilterForm {
+      community_id: read_filter.community_id,
+      pattern: data.pattern.trim().to_owned(),
+      is_regex: data.is_regex,
+      action: data.action,
+      replacement: data.replacement.to_owned(),
+      // Flagged content needs someone to file the report as, even for the built in filter
+      creator_id: read_filter.creator_id.or(Some(user_id)),
+      updated: Some(naive_now()),
+    };
+
+    let update = move |conn: &'_ _| WordFilter::update(conn, edit_id, &filter_form);
+    if blocking(pool, update).await?.is_err() {
+      return Err(APIError::err("couldnt_update_word_filter").into());
+    }
+
+    reload_and_list(pool, read_filter.community_id).await
+  }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Perform for Oper<DeleteWordFilter> {
+  type Response = ListWordFiltersResponse;
+
+  async fn perform(
+    &self,
+    pool: &DbPool,
+    _websocket_info: Option<WebsocketInfo>,
+  ) -> Result<ListWordFiltersResponse, LemmyError> {
+    let data: &DeleteWordFilter = &self.data;
+
+    let claims = match Claims::decode(&data.auth) {
+      Ok(claims) => claims.claims,
+      Err(_e) => return Err(APIError::err("not_logged_in").into()),
+    };
+
+    let user_id = claims.id;
+
+    let filter_id = data.filter_id;
+    let read_filter = blocking(pool, move |conn| WordFilter::read(conn, filter_id)).await??;
+    check_admin_or_community_mod(pool, user_id, read_filter.community_id).await?;
+
+    let delete = move |conn: &'_ _| WordFilter::delete(conn, filter_id);
+    if blocking(pool, delete).await?.is_err() {
+      return Err(APIError::err("couldnt_delete_word_filter").into());
+    }
+
+    reload_and_list(pool, read_filter.community_id).await
+  }
+}
+
+/// Reads the word filters into the ones that get applied. Needs to run on startup, and after
+/// they change. Filters that don't compile anymore get skipped.
+pub fn load_word_filters(conn: &PgConnection) -> Result<(), LemmyError> {
+  let filters = WordFilter::list_all(conn)?
+    .into_iter()
+    .filter_map(|f| {
+      let action = WordFilterAction::from_i16(f.action)?;
+      match build_word_filter_regex(&f.pattern, f.is_regex) {
+        Ok(regex) => Some(CompiledWordFilter {
+          regex,
+          action,
+          replacement: f.replacement.unwrap_or_else(|| "*removed*".to_string()),
+          community_id: f.community_id,
+          creator_id: f.creator_id,
+        }),
+        Err(e) => {
+          error!("Skipping word filter {}: {}", f.id, e);
+          None
+        }
+      }
+    })
+    .collect();
+
+  set_word_filters(filters);
+  Ok(())
+}
+
+/// Runs the word filters of the site and the community over some text, failing if any blocks it.
+pub fn check_words(text: &str, community_id: i32) -> Result<FilteredText, LemmyError> {
+  match filter_words(text, Some(community_id)) {
+    Ok(filtered) => Ok(filtered),
+    Err(blocked) => Err(APIError::err(&slurs_vec_to_str(blocked)).into()),
+  }
+}
+
+/// Files a report for every filter creator whose flag filters matched, listing what matched.
+pub async fn report_flagged_post(
+  pool: &DbPool,
+  websocket_info: &Option<WebsocketInfo>,
+  post_id: i32,
+  flagged: &[FlaggedWord],
+) -> Result<(), LemmyError> {
+  for (creator_id, reason) in flagged_reasons(flagged) {
+    let form = PostReportForm {
+      creator_id,
+      post_id,
+      reason,
+      rule_id: None,
+    };
+    let create = move |conn: &'_ _| PostReport::create(conn, &form);
+    let report = match blocking(pool, create).await? {
+      Ok(report) => report,
+      Err(_e) => return Err(APIError::err("couldnt_create_report").into()),
+    };
+    send_post_report_to_mods(pool, websocket_info, report.id).await?;
+  }
+  Ok(())
+}
+
+/// Same as `report_flagged_post`, for comments.
+pub async fn report_flagged_comment(
+  pool: &DbPool,
+  websocket_info: &Option<WebsocketInfo>,
+  comment_id: i32,
+  flagged: &[FlaggedWord],
+) -> Result<(), LemmyError> {
+  for (creator_id, reason) in flagged_reasons(flagged) {
+    let form = CommentReportForm {
+      creator_id,
+      comment_id,
+      reason,
+      rule_id: None,
+    };
+    let create = move |conn: &'_ _| CommentReport::create(conn, &form);
+    let report = match blocking(pool, create).await? {
+      Ok(report) => report,
+      Err(_e) => return Err(APIError::err("couldnt_create_report").into()),
+    };
+    send_comment_report_to_mods(pool, websocket_info, report.id).await?;
+  }
+  Ok(())
+}
+
+fn flagged_reasons(flagged: &[FlaggedWord]) -> BTreeMap<i32, String> {
+  let mut words: BTreeMap<i32, Vec<&str>> = BTreeMap::new();
+  for flag in flagged {
+    if let Some(creator_id) = flag.creator_id {
+      let creator_words = words.entry(creator_id).or_insert_with(Vec::new);
+      if !creator_words.contains(&flag.word.as_str()) {
+        creator_words.push(&flag.word);
+      }
+    }
+  }
+
+  words
+    .into_iter()
+    .map(|(creator_id, words)| (creator_id, format!("Word filter: {}", words.join(", "))))
+    .collect()
+}
+
+async fn reload_and_list(
+  pool: &DbPool,
+  community_id: Option<i32>,
+) -> Result<ListWordFiltersResponse, LemmyError> {
+  let filters = blocking(pool, move |conn| {
+    load_word_filters(conn)?;
+    Ok(WordFilter::list(conn, community_id)?) as Result<_, LemmyError>
+  })
+  .await??;
+
+  Ok(ListWordFiltersResponse { filters })
+}
+
+fn check_valid_word_filter(pattern: &str, is_regex: bool, action: i16) -> Result<(), LemmyError> {
+  let pattern_len = pattern.trim().chars().count();
+  if pattern_len == 0 || pattern_len > 1000 {
+    return Err(APIError::err("invalid_word_filter").into());
+  }
+
+  if WordFilterAction::from_i16(action).is_none() {
+    return Err(APIError::err("invalid_word_filter").into());
+  }
+
+  if build_word_filter_regex(pattern, is_regex).is_err() {
+    return Err(APIError::err("invalid_word_filter").into());
+  }
+
+  Ok(())
+}
@@ -3,15 +3,20 @@ use crate::{
     community::do_announce, extensions::signatures::sign, insert_activity, is_apub_id_valid,
     ActorType,
   },
+  blocking,
+  hooks::{has_hooks, run_hooks, FederationHookData},
+  job_queue::{enqueue_all, Task},
   request::retry_custom,
   DbPool, LemmyError,
 };
 use activitystreams::{context, object::properties::ObjectProperties, public, Activity, Base};
 use actix_web::client::Client;
 use lemmy_db::{community::Community, user::User_};
-use log::debug;
+use lemmy_utils::settings::HookEvent;
 use serde::Serialize;
 use std::fmt::Debug;
+use tracing::{debug, info_span};
+use tracing_futures::Instrument;
 use url::Url;
 
 pub fn populate_object_props(
@@ -43,19 +48,36 @@ where
 {
   insert_activity(creator.id, activity.clone(), true, pool).await?;
 
+  // Nothing a shadowbanned user does leaves this instance
+  if creator.shadow_banned {
+    return Ok(());
+  }
+
+  // It also goes to the creator's followers on other instances, who may not follow the community
+  let follower_inboxes: Vec<String> = creator
+    .get_follower_inboxes(pool)
+    .await?
+    .into_iter()
+    .filter(|i| !to.contains(i))
+    .collect();
+  if !follower_inboxes.is_empty() {
+    send_activity(pool, &activity, creator, follower_inboxes).await?;
+  }
+
   // if this is a local community, we need to do an announce from the community instead
   if community.local {
     do_announce(activity, &community, creator, client, pool).await?;
   } else {
-    send_activity(client, &activity, creator, to).await?;
+    send_activity(pool, &activity, creator, to).await?;
   }
 
   Ok(())
 }
 
-/// Send an activity to a list of recipients, using the correct headers etc.
+/// Send an activity to a list of recipients, using the correct headers etc. Every inbox gets a
+/// job of its own, so an instance that is down only holds up the activities sent to it.
 pub async fn send_activity<A>(
-  client: &Client,
+  pool: &DbPool,
   activity: &A,
   actor: &dyn ActorType,
   to: Vec<String>,
@@ -63,9 +85,26 @@ pub async fn send_activity<A>(
 where
   A: Serialize,
 {
-  let activity = serde_json::to_string(&activity)?;
+  // The hooks can hold an activity back, or change it and where it goes
+  let (activity, to) = if has_hooks(HookEvent::PreFederate) {
+    let data = FederationHookData {
+      actor_id: actor.actor_id(),
+      inboxes: to,
+      activity: serde_json::to_value(&activity)?,
+    };
+    match run_hooks(HookEvent::PreFederate, data).await {
+      Ok(data) => (serde_json::to_string(&data.activity)?, data.inboxes),
+      Err(e) => {
+        debug!("Not sending activity, held back by a hook: {}", e);
+        return Ok(());
+      }
+    }
+  } else {
+    (serde_json::to_string(&activity)?, to)
+  };
   debug!("Sending activitypub activity {} to {:?}", activity, to);
 
+  let mut tasks = Vec::new();
   for t in to {
     let to_url = Url::parse(&t)?;
     if !is_apub_id_valid(&to_url) {
@@ -73,17 +112,56 @@ where
       continue;
     }
 
-    let res = retry_custom(|| async {
-      let request = client.post(&t).header("Content-Type", "application/json");
+    tasks.push(Task::DeliverActivity {
+      actor_id: actor.actor_id(),
+      inbox: t,
+      activity: activity.clone(),
+    });
+  }
+
+  enqueue_all(pool, tasks).await
+}
 
-      match sign(request, actor, activity.clone()).await {
-        Ok(signed) => Ok(signed.send().await),
-        Err(e) => Err(e),
-      }
-    })
-    .await?;
+/// Delivers an activity to a single inbox, signed by the local user or community that sent it.
+/// Fails when the other instance can't be reached or has trouble, so the job is tried again.
+pub async fn deliver_activity(
+  client: &Client,
+  pool: &DbPool,
+  actor_id: &str,
+  inbox: &str,
+  activity: &str,
+) -> Result<(), LemmyError> {
+  let user_actor_id = actor_id.to_owned();
+  let community_actor_id = actor_id.to_owned();
+  let actor: Box<dyn ActorType> = match blocking(pool, move |conn| {
+    User_::read_from_actor_id(conn, &user_actor_id)
+  })
+  .await?
+  {
+    Ok(user) => Box::new(user),
+    Err(_e) => Box::new(
+      blocking(pool, move |conn| {
+        Community::read_from_actor_id(conn, &community_actor_id)
+      })
+      .await??,
+    ),
+  };
+
+  let res = retry_custom(|| async {
+    let request = client.post(inbox).header("Content-Type", "application/json");
+
+    match sign(request, actor.as_ref(), activity.to_owned()).await {
+      Ok(signed) => Ok(signed.send().await),
+      Err(e) => Err(e),
+    }
+  })
+  .instrument(info_span!("deliver", actor_id, inbox))
+  .await?;
+
+  debug!("Result for activity send: {:?}", res);
 
-    debug!("Result for activity send: {:?}", res);
+  if res.status().is_server_error() {
+    return Err(format_err!("{} answered with {}", inbox, res.status()).into());
   }
 
   Ok(())
@@ -6,14 +6,15 @@ use crate::{
       get_or_fetch_and_insert_remote_comment, get_or_fetch_and_insert_remote_post,
       get_or_fetch_and_upsert_remote_user,
     },
-    ActorType, ApubLikeableType, ApubObjectType, FromApub, ToApub,
+    get_featured_replies_url, ActorType, ApubLikeableType, ApubObjectType, ApubPinnableType,
+    Freshness, FromApub, ToApub,
   },
   blocking,
   routes::DbPoolParam,
   DbPool, LemmyError,
 };
 use activitystreams_new::{
-  activity::{Create, Delete, Dislike, Like, Remove, Undo, Update},
+  activity::{Add, Create, Delete, Dislike, Like, Remove, Undo, Update},
   base::AnyBase,
   context,
   link::Mention,
@@ -22,7 +23,7 @@ use activitystreams_new::{
   primitives::XsdAnyUri,
   public,
 };
-use actix_web::{body::Body, client::Client, web::Path, HttpResponse};
+use actix_web::{body::Body, client::Client, web::Path, HttpRequest, HttpResponse};
 use itertools::Itertools;
 use lemmy_db::{
   comment::{Comment, CommentForm},
@@ -32,10 +33,10 @@ use lemmy_db::{
   Crud,
 };
 use lemmy_utils::{convert_datetime, scrape_text_for_mentions, MentionData};
-use log::debug;
 use serde::Deserialize;
 use serde_json::Error;
 use std::str::FromStr;
+use tracing::debug;
 
 #[derive(Deserialize)]
 pub struct CommentQuery {
@@ -44,16 +45,22 @@ pub struct CommentQuery {
 
 /// Return the post json over HTTP.
 pub async fn get_apub_comment(
+  req: HttpRequest,
   info: Path<CommentQuery>,
   db: DbPoolParam,
 ) -> Result<HttpResponse<Body>, LemmyError> {
   let id = info.comment_id.parse::<i32>()?;
   let comment = blocking(&db, move |conn| Comment::read(conn, id)).await??;
 
+  let freshness = Freshness::new(comment.published, comment.updated, comment.deleted);
+  if let Some(not_modified) = freshness.not_modified(&req) {
+    return Ok(not_modified);
+  }
+
   if !comment.deleted {
-    Ok(create_apub_response(&comment.to_apub(&db).await?))
+    Ok(freshness.add_headers(create_apub_response(&comment.to_apub(&db).await?)))
   } else {
-    Ok(create_apub_tombstone_response(&comment.to_tombstone()?))
+    Ok(freshness.add_headers(create_apub_tombstone_response(&comment.to_tombstone()?)))
   }
 }
 
@@ -177,6 +184,32 @@ impl FromApub for CommentForm {
   }
 }
 
+/// The Delete of a comment, with the community it goes to. A purge builds it before the comment is
+/// gone.
+pub async fn comment_delete_activity(
+  comment: &Comment,
+  creator: &User_,
+  pool: &DbPool,
+) -> Result<(Delete, Community), LemmyError> {
+  let note = comment.to_apub(pool).await?;
+
+  let post_id = comment.post_id;
+  let post = blocking(pool, move |conn| Post::read(conn, post_id)).await??;
+
+  let community_id = post.community_id;
+  let community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
+
+  let id = format!("{}/delete/{}", comment.ap_id, uuid::Uuid::new_v4());
+  let mut delete = Delete::new(creator.actor_id.to_owned(), note.into_any_base()?);
+  delete
+    .set_context(context())
+    .set_id(XsdAnyUri::from_str(&id)?)
+    .set_to(public())
+    .set_many_ccs(vec![community.get_followers_url()]);
+
+  Ok((delete, community))
+}
+
 #[async_trait::async_trait(?Send)]
 impl ApubObjectType for Comment {
   /// Send out information about a newly created comment, to the followers of the community.
@@ -249,21 +282,7 @@ impl ApubObjectType for Comment {
     client: &Client,
     pool: &DbPool,
   ) -> Result<(), LemmyError> {
-    let note = self.to_apub(pool).await?;
-
-    let post_id = self.post_id;
-    let post = blocking(pool, move |conn| Post::read(conn, post_id)).await??;
-
-    let community_id = post.community_id;
-    let community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
-
-    let id = format!("{}/delete/{}", self.ap_id, uuid::Uuid::new_v4());
-    let mut delete = Delete::new(creator.actor_id.to_owned(), note.into_any_base()?);
-    delete
-      .set_context(context())
-      .set_id(XsdAnyUri::from_str(&id)?)
-      .set_to(public())
-      .set_many_ccs(vec![community.get_followers_url()]);
+    let (delete, community) = comment_delete_activity(self, creator, pool).await?;
 
     send_activity_to_community(
       &creator,
@@ -325,6 +344,7 @@ impl ApubObjectType for Comment {
   async fn send_remove(
     &self,
     mod_: &User_,
+    reason: Option<String>,
     client: &Client,
     pool: &DbPool,
   ) -> Result<(), LemmyError> {
@@ -343,6 +363,9 @@ impl ApubObjectType for Comment {
       .set_id(XsdAnyUri::from_str(&id)?)
       .set_to(public())
       .set_many_ccs(vec![community.get_followers_url()]);
+    if let Some(reason) = reason {
+      remove.set_summary(reason);
+    }
 
     send_activity_to_community(
       &mod_,
@@ -519,6 +542,85 @@ impl ApubLikeableType for Comment {
   }
 }
 
+#[async_trait::async_trait(?Send)]
+impl ApubPinnableType for Comment {
+  /// Pin the comment, by adding it to the featured replies of its post.
+  async fn send_pin(&self, mod_: &User_, client: &Client, pool: &DbPool) -> Result<(), LemmyError> {
+    let note = self.to_apub(pool).await?;
+
+    let post_id = self.post_id;
+    let post = blocking(pool, move |conn| Post::read(conn, post_id)).await??;
+
+    let community_id = post.community_id;
+    let community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
+
+    let id = format!("{}/add/{}", self.ap_id, uuid::Uuid::new_v4());
+    let mut add = Add::new(mod_.actor_id.to_owned(), note.into_any_base()?);
+    add
+      .set_context(context())
+      .set_id(XsdAnyUri::from_str(&id)?)
+      .set_target(XsdAnyUri::from_str(&get_featured_replies_url(&post.ap_id))?)
+      .set_to(public())
+      .set_many_ccs(vec![community.get_followers_url()]);
+
+    send_activity_to_community(
+      &mod_,
+      &community,
+      vec![community.get_shared_inbox_url()],
+      add,
+      client,
+      pool,
+    )
+    .await?;
+    Ok(())
+  }
+
+  async fn send_undo_pin(
+    &self,
+    mod_: &User_,
+    client: &Client,
+    pool: &DbPool,
+  ) -> Result<(), LemmyError> {
+    let note = self.to_apub(pool).await?;
+
+    let post_id = self.post_id;
+    let post = blocking(pool, move |conn| Post::read(conn, post_id)).await??;
+
+    let community_id = post.community_id;
+    let community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
+
+    // Generate a fake add activity, with the correct object
+    let id = format!("{}/add/{}", self.ap_id, uuid::Uuid::new_v4());
+    let mut add = Add::new(mod_.actor_id.to_owned(), note.into_any_base()?);
+    add
+      .set_context(context())
+      .set_id(XsdAnyUri::from_str(&id)?)
+      .set_target(XsdAnyUri::from_str(&get_featured_replies_url(&post.ap_id))?)
+      .set_to(public())
+      .set_many_ccs(vec![community.get_followers_url()]);
+
+    // Undo that fake activity
+    let undo_id = format!("{}/undo/add/{}", self.ap_id, uuid::Uuid::new_v4());
+    let mut undo = Undo::new(mod_.actor_id.to_owned(), add.into_any_base()?);
+    undo
+      .set_context(context())
+      .set_id(XsdAnyUri::from_str(&undo_id)?)
+      .set_to(public())
+      .set_many_ccs(vec![community.get_followers_url()]);
+
+    send_activity_to_community(
+      &mod_,
+      &community,
+      vec![community.get_shared_inbox_url()],
+      undo,
+      client,
+      pool,
+    )
+    .await?;
+    Ok(())
+  }
+}
+
 struct MentionsAndAddresses {
   addressed_ccs: Vec<String>,
   inboxes: Vec<String>,
@@ -4,34 +4,38 @@ use crate::{
     create_apub_response, create_apub_tombstone_response, create_tombstone,
     extensions::group_extensions::GroupExtension,
     fetcher::get_or_fetch_and_upsert_remote_user,
-    get_shared_inbox, insert_activity, ActorType, FromApub, GroupExt, ToApub,
+    get_shared_inbox, insert_activity, ActorType, Freshness, FromApub, GroupExt, ToApub,
   },
   blocking,
+  image_proxy::proxy_image_url,
   routes::DbPoolParam,
   DbPool, LemmyError,
 };
 use activitystreams::{
-  activity::{Accept, Announce, Delete, Remove, Undo},
+  activity::{Accept, Announce, Delete, Remove, Undo, Update},
   Activity, Base, BaseBox,
 };
 use activitystreams_ext::Ext2;
 use activitystreams_new::{
   activity::Follow,
   actor::{kind::GroupType, ApActor, Endpoints, Group},
-  base::BaseExt,
-  collection::UnorderedCollection,
+  base::{AnyBase, BaseExt},
+  collection::{OrderedCollection, UnorderedCollection},
   context,
-  object::Tombstone,
+  object::{Image, Tombstone},
   prelude::*,
   primitives::{XsdAnyUri, XsdDateTime},
 };
-use actix_web::{body::Body, client::Client, web, HttpResponse};
+use actix_web::{body::Body, client::Client, web, HttpRequest, HttpResponse};
 use itertools::Itertools;
 use lemmy_db::{
   community::{Community, CommunityForm},
   community_view::{CommunityFollowerView, CommunityModeratorView},
   naive_now,
+  post_view::PostQueryBuilder,
   user::User_,
+  ListingType,
+  SortType,
 };
 use lemmy_utils::convert_datetime;
 use serde::{Deserialize, Serialize};
@@ -76,6 +80,18 @@ impl ToApub for Community {
       group.set_content(d);
     }
 
+    if let Some(icon_url) = &self.icon {
+      let mut image = Image::new();
+      image.set_url(icon_url.to_owned());
+      group.set_icon(image.into_any_base()?);
+    }
+
+    if let Some(banner_url) = &self.banner {
+      let mut image = Image::new();
+      image.set_url(banner_url.to_owned());
+      group.set_image(image.into_any_base()?);
+    }
+
     let mut ap_actor = ApActor::new(self.get_inbox_url().parse()?, group);
     ap_actor
       .set_preferred_username(self.title.to_owned())
@@ -90,8 +106,9 @@ impl ToApub for Community {
 
     let nsfw = self.nsfw;
     let category_id = self.category_id;
+    let read_only = self.read_only;
     let group_extension = blocking(pool, move |conn| {
-      GroupExtension::new(conn, category_id, nsfw)
+      GroupExtension::new(conn, category_id, nsfw, read_only)
     })
     .await??;
 
@@ -129,7 +146,7 @@ impl ActorType for Community {
   async fn send_accept_follow(
     &self,
     follow: &Follow,
-    client: &Client,
+    _client: &Client,
     pool: &DbPool,
   ) -> Result<(), LemmyError> {
     let actor_uri = follow.actor.as_single_xsd_any_uri().unwrap().to_string();
@@ -148,47 +165,54 @@ impl ActorType for Community {
 
     insert_activity(self.creator_id, accept.clone(), true, pool).await?;
 
-    send_activity(client, &accept, self, vec![to]).await?;
+    send_activity(pool, &accept, self, vec![to]).await?;
     Ok(())
   }
 
-  async fn send_delete(
+  async fn send_update(
     &self,
     creator: &User_,
-    client: &Client,
+    _client: &Client,
     pool: &DbPool,
   ) -> Result<(), LemmyError> {
     let group = self.to_apub(pool).await?;
 
-    let id = format!("{}/delete/{}", self.actor_id, uuid::Uuid::new_v4());
+    let id = format!("{}/update/{}", self.actor_id, uuid::Uuid::new_v4());
 
-    let mut delete = Delete::default();
+    let mut update = Update::default();
     populate_object_props(
-      &mut delete.object_props,
+      &mut update.object_props,
       vec![self.get_followers_url()],
       &id,
     )?;
 
-    delete
-      .delete_props
+    update
+      .update_props
       .set_actor_xsd_any_uri(creator.actor_id.to_owned())?
       .set_object_base_box(BaseBox::from_concrete(group)?)?;
 
-    insert_activity(self.creator_id, delete.clone(), true, pool).await?;
+    insert_activity(self.creator_id, update.clone(), true, pool).await?;
 
     let inboxes = self.get_follower_inboxes(pool).await?;
 
-    // Note: For an accept, since it was automatic, no one pushed a button,
-    // the community was the actor.
-    // But for delete, the creator is the actor, and does the signing
-    send_activity(client, &delete, creator, inboxes).await?;
+    send_activity(pool, &update, creator, inboxes).await?;
     Ok(())
   }
 
+  async fn send_delete(
+    &self,
+    creator: &User_,
+    _client: &Client,
+    pool: &DbPool,
+  ) -> Result<(), LemmyError> {
+    let (delete, inboxes) = community_delete_activity(self, creator, pool).await?;
+    send_community_delete(self, creator, delete, inboxes, pool).await
+  }
+
   async fn send_undo_delete(
     &self,
     creator: &User_,
-    client: &Client,
+    _client: &Client,
     pool: &DbPool,
   ) -> Result<(), LemmyError> {
     let group = self.to_apub(pool).await?;
@@ -230,14 +254,14 @@ impl ActorType for Community {
     // Note: For an accept, since it was automatic, no one pushed a button,
     // the community was the actor.
     // But for delete, the creator is the actor, and does the signing
-    send_activity(client, &undo, creator, inboxes).await?;
+    send_activity(pool, &undo, creator, inboxes).await?;
     Ok(())
   }
 
   async fn send_remove(
     &self,
     mod_: &User_,
-    client: &Client,
+    _client: &Client,
     pool: &DbPool,
   ) -> Result<(), LemmyError> {
     let group = self.to_apub(pool).await?;
@@ -263,14 +287,14 @@ impl ActorType for Community {
     // Note: For an accept, since it was automatic, no one pushed a button,
     // the community was the actor.
     // But for delete, the creator is the actor, and does the signing
-    send_activity(client, &remove, mod_, inboxes).await?;
+    send_activity(pool, &remove, mod_, inboxes).await?;
     Ok(())
   }
 
   async fn send_undo_remove(
     &self,
     mod_: &User_,
-    client: &Client,
+    _client: &Client,
     pool: &DbPool,
   ) -> Result<(), LemmyError> {
     let group = self.to_apub(pool).await?;
@@ -311,7 +335,7 @@ impl ActorType for Community {
     // Note: For an accept, since it was automatic, no one pushed a button,
     // the community was the actor.
     // But for remove , the creator is the actor, and does the signing
-    send_activity(client, &undo, mod_, inboxes).await?;
+    send_activity(pool, &undo, mod_, inboxes).await?;
     Ok(())
   }
 
@@ -370,6 +394,28 @@ impl FromApub for CommunityForm {
 
     let creator = get_or_fetch_and_upsert_remote_user(creator_uri, client, pool).await?;
 
+    let icon = match group.icon() {
+      Some(any_image) => Image::from_any_base(any_image.as_one().unwrap().clone())
+        .unwrap()
+        .unwrap()
+        .url
+        .unwrap()
+        .as_single_xsd_any_uri()
+        .map(|u| u.to_string()),
+      None => None,
+    };
+
+    let banner = match group.image() {
+      Some(any_image) => Image::from_any_base(any_image.as_one().unwrap().clone())
+        .unwrap()
+        .unwrap()
+        .url
+        .unwrap()
+        .as_single_xsd_any_uri()
+        .map(|u| u.to_string()),
+      None => None,
+    };
+
     Ok(CommunityForm {
       name: group.name().unwrap().as_single_xsd_string().unwrap().into(),
       title: group.inner.preferred_username().unwrap().to_string(),
@@ -392,12 +438,22 @@ impl FromApub for CommunityForm {
       private_key: None,
       public_key: Some(group.ext_two.to_owned().public_key.public_key_pem),
       last_refreshed_at: Some(naive_now()),
+      require_flair: None,
+      icon: proxy_image_url(icon),
+      banner: proxy_image_url(banner),
+      archive_after_days: None,
+      post_approval: None,
+      read_only: Some(group.ext_one.posting_restricted_to_mods),
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
     })
   }
 }
 
 /// Return the community json over HTTP.
 pub async fn get_apub_community_http(
+  req: HttpRequest,
   info: web::Path<CommunityQuery>,
   db: DbPoolParam,
 ) -> Result<HttpResponse<Body>, LemmyError> {
@@ -406,12 +462,17 @@ pub async fn get_apub_community_http(
   })
   .await??;
 
+  let freshness = Freshness::new(community.published, community.updated, community.deleted);
+  if let Some(not_modified) = freshness.not_modified(&req) {
+    return Ok(not_modified);
+  }
+
   if !community.deleted {
     let apub = community.to_apub(&db).await?;
 
-    Ok(create_apub_response(&apub))
+    Ok(freshness.add_headers(create_apub_response(&apub)))
   } else {
-    Ok(create_apub_tombstone_response(&community.to_tombstone()?))
+    Ok(freshness.add_headers(create_apub_tombstone_response(&community.to_tombstone()?)))
   }
 }
 
@@ -440,11 +501,107 @@ pub async fn get_apub_community_followers(
   Ok(create_apub_response(&collection))
 }
 
+/// How many posts a page of the outbox has
+const OUTBOX_PAGE_SIZE: i64 = 20;
+
+#[derive(Deserialize)]
+pub struct CommunityOutboxQuery {
+  sort: Option<String>,
+  page: Option<i64>,
+}
+
+/// Returns the ids of the community's posts, newest first unless another sort is asked for, like
+/// `?sort=Controversial`.
+pub async fn get_apub_community_outbox(
+  info: web::Path<CommunityQuery>,
+  query: web::Query<CommunityOutboxQuery>,
+  db: DbPoolParam,
+) -> Result<HttpResponse<Body>, LemmyError> {
+  let community = blocking(&db, move |conn| {
+    Community::read_from_name(&conn, &info.community_name)
+  })
+  .await??;
+
+  let sort = match &query.sort {
+    Some(sort) => SortType::from_str(sort)?,
+    None => SortType::New,
+  };
+  let page = query.page;
+  let community_id = community.id;
+  let posts = blocking(&db, move |conn| {
+    PostQueryBuilder::create(conn)
+      .listing_type(ListingType::Community)
+      .sort(&sort)
+      .for_community_id(community_id)
+      .show_nsfw(true)
+      .page(page)
+      .limit(OUTBOX_PAGE_SIZE)
+      .list()
+  })
+  .await??;
+
+  let items = posts
+    .iter()
+    .map(|p| Ok(AnyBase::from_xsd_any_uri(p.ap_id.parse()?)))
+    .collect::<Result<Vec<AnyBase>, LemmyError>>()?;
+
+  let mut collection = OrderedCollection::new(items);
+  collection
+    .set_context(context())
+    .set_id(community.get_outbox_url().parse()?)
+    .set_total_items(posts.len() as u64);
+  Ok(create_apub_response(&collection))
+}
+
+/// The Delete of a community, with the inboxes of its followers. A purge takes the followers with
+/// the community, so it reads them before, and sends with `send_community_delete` after.
+pub async fn community_delete_activity(
+  community: &Community,
+  creator: &User_,
+  pool: &DbPool,
+) -> Result<(Delete, Vec<String>), LemmyError> {
+  let group = community.to_apub(pool).await?;
+
+  let id = format!("{}/delete/{}", community.actor_id, uuid::Uuid::new_v4());
+
+  let mut delete = Delete::default();
+  populate_object_props(
+    &mut delete.object_props,
+    vec![community.get_followers_url()],
+    &id,
+  )?;
+
+  delete
+    .delete_props
+    .set_actor_xsd_any_uri(creator.actor_id.to_owned())?
+    .set_object_base_box(BaseBox::from_concrete(group)?)?;
+
+  let inboxes = community.get_follower_inboxes(pool).await?;
+
+  Ok((delete, inboxes))
+}
+
+pub async fn send_community_delete(
+  community: &Community,
+  creator: &User_,
+  delete: Delete,
+  inboxes: Vec<String>,
+  pool: &DbPool,
+) -> Result<(), LemmyError> {
+  insert_activity(community.creator_id, delete.clone(), true, pool).await?;
+
+  // Note: For an accept, since it was automatic, no one pushed a button,
+  // the community was the actor.
+  // But for delete, the creator is the actor, and does the signing
+  send_activity(pool, &delete, creator, inboxes).await?;
+  Ok(())
+}
+
 pub async fn do_announce<A>(
   activity: A,
   community: &Community,
   sender: &dyn ActorType,
-  client: &Client,
+  _client: &Client,
   pool: &DbPool,
 ) -> Result<HttpResponse, LemmyError>
 where
@@ -470,7 +627,7 @@ where
   // this seems to be the "easiest" stable alternative for remove_item()
   to.retain(|x| *x != sender.get_shared_inbox_url());
 
-  send_activity(client, &announce, community, to).await?;
+  send_activity(pool, &announce, community, to).await?;
 
   Ok(HttpResponse::Ok().finish())
 }
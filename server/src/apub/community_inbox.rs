@@ -2,7 +2,7 @@ use crate::{
   apub::{
     extensions::signatures::verify,
     fetcher::{get_or_fetch_and_upsert_remote_community, get_or_fetch_and_upsert_remote_user},
-    insert_activity, ActorType,
+    insert_activity, trace_activity, ActorType,
   },
   blocking,
   routes::{ChatServerParam, DbPoolParam},
@@ -16,12 +16,12 @@ use lemmy_db::{
   user::User_,
   Followable,
 };
-use log::debug;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
+use tracing::debug;
 
 #[serde(untagged)]
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub enum CommunityAcceptedObjects {
   Follow(Follow),
   Undo(Undo),
@@ -53,6 +53,7 @@ pub async fn community_inbox(
   _chat_server: ChatServerParam,
 ) -> Result<HttpResponse, LemmyError> {
   let input = input.into_inner();
+  trace_activity(&input);
 
   let path = path.into_inner();
   let community = blocking(&db, move |conn| Community::read_from_name(&conn, &path)).await??;
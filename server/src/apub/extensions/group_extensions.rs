@@ -9,6 +9,9 @@ use serde::{Deserialize, Serialize};
 pub struct GroupExtension {
   pub category: GroupCategory,
   pub sensitive: bool,
+  /// Only the mods can post and comment. Missing from the groups of older instances.
+  #[serde(default)]
+  pub posting_restricted_to_mods: bool,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
@@ -24,6 +27,7 @@ impl GroupExtension {
     conn: &PgConnection,
     category_id: i32,
     sensitive: bool,
+    posting_restricted_to_mods: bool,
   ) -> Result<GroupExtension, LemmyError> {
     let category = Category::read(conn, category_id)?;
     let group_category = GroupCategory {
@@ -33,6 +37,7 @@ impl GroupExtension {
     Ok(GroupExtension {
       category: group_category,
       sensitive,
+      posting_restricted_to_mods,
     })
   }
 }
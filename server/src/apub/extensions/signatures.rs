@@ -5,7 +5,6 @@ use http_signature_normalization_actix::{
   digest::{DigestClient, SignExt},
   Config,
 };
-use log::debug;
 use openssl::{
   hash::MessageDigest,
   pkey::PKey,
@@ -13,6 +12,7 @@ use openssl::{
 };
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use tracing::debug;
 
 lazy_static! {
   static ref HTTP_SIG_CONFIG: Config = Config::new();
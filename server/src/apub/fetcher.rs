@@ -23,9 +23,10 @@ use lemmy_db::{
   Crud, Joinable, SearchType,
 };
 use lemmy_utils::get_apub_protocol_string;
-use log::debug;
 use serde::Deserialize;
 use std::{fmt::Debug, time::Duration};
+use tracing::{debug, info_span, warn};
+use tracing_futures::Instrument;
 use url::Url;
 
 static ACTOR_REFETCH_INTERVAL_SECONDS: i64 = 24 * 60 * 60;
@@ -59,22 +60,36 @@ where
 
   let timeout = Duration::from_secs(60);
 
-  let json = retry(|| {
-    client
-      .get(url.as_str())
-      .header("Accept", APUB_JSON_CONTENT_TYPE)
-      .timeout(timeout)
-      .send()
-  })
-  .await?
-  .json()
-  .await
-  .map_err(|e| {
-    debug!("Receive error, {}", e);
-    RecvError(e.to_string())
-  })?;
-
-  Ok(json)
+  let fetch = async {
+    let json = retry(|| {
+      client
+        .get(url.as_str())
+        .header("Accept", APUB_JSON_CONTENT_TYPE)
+        .timeout(timeout)
+        .send()
+    })
+    .await?
+    .json()
+    .await
+    .map_err(|e| {
+      warn!("Receive error, {}", e);
+      RecvError(e.to_string())
+    })?;
+    Ok(json) as Result<Response, LemmyError>
+  };
+
+  fetch.instrument(info_span!("fetch", url = %url)).await
+}
+
+/// Fetch a remote user, returning whether it is a bot. Bots are published as Service actors,
+/// which are otherwise read the same way as a Person.
+async fn fetch_remote_person(client: &Client, url: &Url) -> Result<(PersonExt, bool), LemmyError> {
+  let mut json = fetch_remote_object::<serde_json::Value>(client, url).await?;
+  let bot_account = json.get("type").and_then(|t| t.as_str()) == Some("Service");
+  if bot_account {
+    json["type"] = "Person".into();
+  }
+  Ok((serde_json::from_value(json)?, bot_account))
 }
 
 /// The types of ActivityPub objects that can be fetched directly by searching for their ID.
@@ -205,10 +220,11 @@ pub async fn get_or_fetch_and_upsert_remote_user(
     // If its older than a day, re-fetch it
     Ok(u) if !u.local && should_refetch_actor(u.last_refreshed_at) => {
       debug!("Fetching and updating from remote user: {}", apub_id);
-      let person = fetch_remote_object::<PersonExt>(client, apub_id.as_url()).await?;
+      let (person, bot_account) = fetch_remote_person(client, apub_id.as_url()).await?;
 
       let mut uf = UserForm::from_apub(&person, client, pool).await?;
       uf.last_refreshed_at = Some(naive_now());
+      uf.bot_account = bot_account;
       let user = blocking(pool, move |conn| User_::update(conn, u.id, &uf)).await??;
 
       Ok(user)
@@ -216,9 +232,10 @@ pub async fn get_or_fetch_and_upsert_remote_user(
     Ok(u) => Ok(u),
     Err(NotFound {}) => {
       debug!("Fetching and creating remote user: {}", apub_id);
-      let person = fetch_remote_object::<PersonExt>(client, apub_id.as_url()).await?;
+      let (person, bot_account) = fetch_remote_person(client, apub_id.as_url()).await?;
 
-      let uf = UserForm::from_apub(&person, client, pool).await?;
+      let mut uf = UserForm::from_apub(&person, client, pool).await?;
+      uf.bot_account = bot_account;
       let user = blocking(pool, move |conn| User_::create(conn, &uf)).await??;
 
       Ok(user)
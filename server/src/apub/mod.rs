@@ -9,6 +9,7 @@ pub mod private_message;
 pub mod shared_inbox;
 pub mod user;
 pub mod user_inbox;
+pub mod user_outbox;
 
 use crate::{
   apub::extensions::{
@@ -29,14 +30,26 @@ use activitystreams_new::{
   prelude::*,
   primitives::XsdAnyUri,
 };
-use actix_web::{body::Body, client::Client, HttpResponse};
+use actix_web::{
+  body::Body,
+  client::Client,
+  http::{
+    header::{HttpDate, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    HeaderValue,
+  },
+  HttpRequest,
+  HttpResponse,
+};
 use chrono::NaiveDateTime;
 use failure::_core::fmt::Debug;
 use lemmy_db::{activity::do_insert_activity, user::User_};
 use lemmy_utils::{convert_datetime, get_apub_protocol_string, settings::Settings, MentionData};
-use log::debug;
 use serde::Serialize;
-use std::str::FromStr;
+use std::{
+  str::FromStr,
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, Span};
 use url::Url;
 
 type GroupExt = Ext2<ApActor<Group>, GroupExtension, PublicKeyExtension>;
@@ -65,9 +78,77 @@ where
     .json(data)
 }
 
+/// Which version of an object an instance fetched, so that it can ask again without getting the
+/// same json back. The tombstone of an object is a version of its own.
+pub struct Freshness {
+  etag: String,
+  last_modified: SystemTime,
+}
+
+impl Freshness {
+  pub fn new(published: NaiveDateTime, updated: Option<NaiveDateTime>, deleted: bool) -> Self {
+    let changed = updated.unwrap_or(published);
+    Freshness {
+      etag: format!(
+        "W/\"{}-{}\"",
+        changed.timestamp_millis(),
+        if deleted { "deleted" } else { "live" }
+      ),
+      last_modified: UNIX_EPOCH + Duration::from_secs(changed.timestamp().max(0) as u64),
+    }
+  }
+
+  /// A 304 if the request already has this version. An If-None-Match wins over an
+  /// If-Modified-Since.
+  pub fn not_modified(&self, req: &HttpRequest) -> Option<HttpResponse<Body>> {
+    let headers = req.headers();
+    let fresh = if let Some(if_none_match) = headers.get(IF_NONE_MATCH) {
+      let tag = self.etag.trim_start_matches("W/");
+      if_none_match.to_str().map_or(false, |tags| {
+        tags
+          .split(',')
+          .map(|t| t.trim())
+          .any(|t| t == "*" || t.trim_start_matches("W/") == tag)
+      })
+    } else if let Some(if_modified_since) = headers.get(IF_MODIFIED_SINCE) {
+      if_modified_since
+        .to_str()
+        .ok()
+        .and_then(|since| HttpDate::from_str(since).ok())
+        .map_or(false, |since| self.last_modified <= SystemTime::from(since))
+    } else {
+      false
+    };
+
+    if fresh {
+      Some(self.add_headers(HttpResponse::NotModified().finish()))
+    } else {
+      None
+    }
+  }
+
+  pub fn add_headers(&self, mut res: HttpResponse<Body>) -> HttpResponse<Body> {
+    let headers = res.headers_mut();
+    if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+      headers.insert(ETAG, etag);
+    }
+    let last_modified = HttpDate::from(self.last_modified).to_string();
+    if let Ok(last_modified) = HeaderValue::from_str(&last_modified) {
+      headers.insert(LAST_MODIFIED, last_modified);
+    }
+    res
+  }
+}
+
 // Checks if the ID has a valid format, correct scheme, and is in the allowed instance list.
+// Nothing is valid while federation is disabled, so nothing gets fetched or sent.
 fn is_apub_id_valid(apub_id: &Url) -> bool {
   debug!("Checking {}", apub_id);
+  if !Settings::get().federation.enabled {
+    debug!("federation is disabled");
+    return false;
+  }
+
   if apub_id.scheme() != get_apub_protocol_string() {
     debug!("invalid scheme: {:?}", apub_id.scheme());
     return false;
@@ -163,9 +244,11 @@ pub trait ApubObjectType {
     client: &Client,
     pool: &DbPool,
   ) -> Result<(), LemmyError>;
+  /// The reason goes out as the summary of the activity, so remote authors can see it.
   async fn send_remove(
     &self,
     mod_: &User_,
+    reason: Option<String>,
     client: &Client,
     pool: &DbPool,
   ) -> Result<(), LemmyError>;
@@ -199,6 +282,17 @@ pub trait ApubLikeableType {
   ) -> Result<(), LemmyError>;
 }
 
+#[async_trait::async_trait(?Send)]
+pub trait ApubPinnableType {
+  async fn send_pin(&self, mod_: &User_, client: &Client, pool: &DbPool) -> Result<(), LemmyError>;
+  async fn send_undo_pin(
+    &self,
+    mod_: &User_,
+    client: &Client,
+    pool: &DbPool,
+  ) -> Result<(), LemmyError>;
+}
+
 pub fn get_shared_inbox(actor_id: &str) -> String {
   let url = Url::parse(actor_id).unwrap();
   format!(
@@ -213,6 +307,11 @@ pub fn get_shared_inbox(actor_id: &str) -> String {
   )
 }
 
+/// The collection of a post's pinned comments, which pin activities use as their target.
+pub fn get_featured_replies_url(post_ap_id: &str) -> String {
+  format!("{}/featured_replies", post_ap_id)
+}
+
 #[async_trait::async_trait(?Send)]
 pub trait ActorType {
   fn actor_id(&self) -> String;
@@ -244,6 +343,14 @@ pub trait ActorType {
     pool: &DbPool,
   ) -> Result<(), LemmyError>;
 
+  /// Sends out the actor as it is now, for the changes to its settings.
+  async fn send_update(
+    &self,
+    creator: &User_,
+    client: &Client,
+    pool: &DbPool,
+  ) -> Result<(), LemmyError>;
+
   async fn send_delete(
     &self,
     creator: &User_,
@@ -356,3 +463,20 @@ where
   .await??;
   Ok(())
 }
+
+/// Puts the ids of an incoming activity and its actor on the span of the request it came with,
+/// so everything logged while handling it can be traced back to them.
+pub fn trace_activity<T>(activity: &T)
+where
+  T: Serialize,
+{
+  if let Ok(value) = serde_json::to_value(activity) {
+    let span = Span::current();
+    if let Some(activity_id) = value["id"].as_str() {
+      span.record("activity_id", &activity_id);
+    }
+    if let Some(actor_id) = value["actor"].as_str() {
+      span.record("actor_id", &actor_id);
+    }
+  }
+}
@@ -4,9 +4,14 @@ use crate::{
     create_apub_response, create_apub_tombstone_response, create_tombstone,
     extensions::page_extension::PageExtension,
     fetcher::{get_or_fetch_and_upsert_remote_community, get_or_fetch_and_upsert_remote_user},
-    ActorType, ApubLikeableType, ApubObjectType, FromApub, PageExt, ToApub,
+    get_featured_replies_url, ActorType, ApubLikeableType, ApubObjectType, Freshness, FromApub,
+    PageExt, ToApub,
   },
   blocking,
+  embed::sanitize_embed_html,
+  image_proxy::proxy_image_url,
+  media::{is_attachment_type, media_url},
+  nsfw::remote_thumbnail_is_nsfw,
   routes::DbPoolParam,
   DbPool, LemmyError,
 };
@@ -16,20 +21,24 @@ use activitystreams::{
 };
 use activitystreams_ext::Ext1;
 use activitystreams_new::{
+  base::AnyBase,
+  collection::OrderedCollection,
   context,
-  object::{kind::PageType, Image, Page, Tombstone},
+  object::{kind::PageType, Document, Image, Page, Tombstone},
   prelude::*,
-  primitives::{XsdAnyUri, XsdDateTime},
+  primitives::{MimeMediaType, XsdAnyUri, XsdDateTime},
 };
-use actix_web::{body::Body, client::Client, web, HttpResponse};
+use actix_web::{body::Body, client::Client, web, HttpRequest, HttpResponse};
 use lemmy_db::{
+  comment::Comment,
   community::Community,
   post::{Post, PostForm},
   user::User_,
   Crud,
 };
-use lemmy_utils::{convert_datetime, get_apub_protocol_string, settings::Settings};
+use lemmy_utils::convert_datetime;
 use serde::Deserialize;
+use serde_json::{json, Value};
 
 #[derive(Deserialize)]
 pub struct PostQuery {
@@ -38,19 +47,52 @@ pub struct PostQuery {
 
 /// Return the post json over HTTP.
 pub async fn get_apub_post(
+  req: HttpRequest,
   info: web::Path<PostQuery>,
   db: DbPoolParam,
 ) -> Result<HttpResponse<Body>, LemmyError> {
   let id = info.post_id.parse::<i32>()?;
   let post = blocking(&db, move |conn| Post::read(conn, id)).await??;
 
+  // Held back posts don't exist for other instances until they're approved
+  if post.pending {
+    return Ok(HttpResponse::NotFound().finish());
+  }
+
+  let freshness = Freshness::new(post.published, post.updated, post.deleted);
+  if let Some(not_modified) = freshness.not_modified(&req) {
+    return Ok(not_modified);
+  }
+
   if !post.deleted {
-    Ok(create_apub_response(&post.to_apub(&db).await?))
+    Ok(freshness.add_headers(create_apub_response(&post.to_apub(&db).await?)))
   } else {
-    Ok(create_apub_tombstone_response(&post.to_tombstone()?))
+    Ok(freshness.add_headers(create_apub_tombstone_response(&post.to_tombstone()?)))
   }
 }
 
+/// Return the pinned comments of the post over HTTP.
+pub async fn get_apub_post_featured_replies(
+  info: web::Path<PostQuery>,
+  db: DbPoolParam,
+) -> Result<HttpResponse<Body>, LemmyError> {
+  let id = info.post_id.parse::<i32>()?;
+  let post = blocking(&db, move |conn| Post::read(conn, id)).await??;
+
+  let pinned_comments = blocking(&db, move |conn| Comment::pinned_for_post(conn, id)).await??;
+  let items = pinned_comments
+    .iter()
+    .map(|c| Ok(AnyBase::from_xsd_any_uri(c.ap_id.parse()?)))
+    .collect::<Result<Vec<AnyBase>, LemmyError>>()?;
+
+  let mut collection = OrderedCollection::new(items);
+  collection
+    .set_context(context())
+    .set_id(get_featured_replies_url(&post.ap_id).parse()?)
+    .set_total_items(pinned_comments.len() as u64);
+  Ok(create_apub_response(&collection))
+}
+
 #[async_trait::async_trait(?Send)]
 impl ToApub for Post {
   type Response = PageExt;
@@ -105,18 +147,20 @@ impl ToApub for Post {
       }
 
       page.set_preview(page_preview.into_any_base()?);
+
+      // Audio and video files go in an attachment too, like PeerTube and Pixelfed send them
+      if let Some(media_type) = &self.url_media_type {
+        let mut document = Document::new();
+        document
+          .set_url(u.to_owned())
+          .set_media_type(media_type.parse::<MimeMediaType>()?);
+        page.set_attachment(document.into_any_base()?);
+      }
     }
 
     if let Some(thumbnail_url) = &self.thumbnail_url {
-      let full_url = format!(
-        "{}://{}/pictshare/{}",
-        get_apub_protocol_string(),
-        Settings::get().hostname,
-        thumbnail_url
-      );
-
       let mut image = Image::new();
-      image.set_url(full_url);
+      image.set_url(media_url(thumbnail_url));
       page.set_image(image.into_any_base()?);
     }
 
@@ -141,6 +185,120 @@ impl ToApub for Post {
   }
 }
 
+/// The first audio or video attachment, with its url and MIME type
+fn media_attachment(page: &PageExt) -> Option<(String, String)> {
+  let attachments = match serde_json::to_value(page.inner.attachment()?).ok()? {
+    Value::Array(attachments) => attachments,
+    attachment => vec![attachment],
+  };
+  attachments.iter().find_map(|attachment| {
+    let media_type = attachment.get("mediaType")?.as_str()?;
+    if !is_attachment_type(media_type) {
+      return None;
+    }
+    // A document has a url, which can be a link with an href, and a link has the href itself
+    let url = match attachment.get("url") {
+      Some(Value::String(url)) => url.as_str(),
+      Some(link) => link.get("href")?.as_str()?,
+      None => attachment.get("href")?.as_str()?,
+    };
+    Some((url.to_owned(), media_type.to_owned()))
+  })
+}
+
+/// Turns the videos of PeerTube and the notes of Pixelfed into the page lemmy sends, so that they
+/// come in as posts. Pages are left as they are.
+pub fn page_from_media_object(mut object: Value) -> Result<PageExt, LemmyError> {
+  if let Some(o) = object.as_object_mut() {
+    if o.get("type").and_then(Value::as_str) != Some("Page") {
+      // The title of a lemmy post is its summary
+      let title = match o.get("name").and_then(Value::as_str) {
+        Some(name) => name.to_owned(),
+        None => title_from_content(o.get("content").and_then(Value::as_str).unwrap_or("")),
+      };
+      o.insert("type".into(), "Page".into());
+      o.insert("summary".into(), title.into());
+
+      // PeerTube attributes videos to the person and their channel, which is the community
+      if let Some(Value::Array(attributed_to)) = o.get("attributedTo").cloned() {
+        let actor_id = |actor: &Value| match actor {
+          Value::String(id) => Some(id.to_owned()),
+          actor => actor.get("id")?.as_str().map(ToOwned::to_owned),
+        };
+        let is_group = |actor: &&Value| actor.get("type").and_then(Value::as_str) == Some("Group");
+        if let Some(person) = attributed_to
+          .iter()
+          .find(|a| !is_group(a))
+          .and_then(actor_id)
+        {
+          o.insert("attributedTo".into(), person.into());
+        }
+        if let Some(channel) = attributed_to.iter().find(is_group).and_then(actor_id) {
+          o.entry("audience").or_insert_with(|| channel.into());
+        }
+      }
+      if let Some(community) = o.get("audience").filter(|a| a.is_string()).cloned() {
+        o.insert("to".into(), community);
+      }
+
+      // PeerTube lists the files of a video in its url
+      if let Some(Value::Array(links)) = o.get("url").cloned() {
+        let files = links
+          .iter()
+          .filter(|link| {
+            link
+              .get("mediaType")
+              .and_then(Value::as_str)
+              .map_or(false, is_attachment_type)
+          })
+          .map(|link| {
+            json!({
+              "type": "Document",
+              "url": link.get("href"),
+              "mediaType": link.get("mediaType"),
+            })
+          })
+          .collect::<Vec<Value>>();
+        o.remove("url");
+        if !files.is_empty() {
+          o.insert("attachment".into(), files.into());
+        }
+      }
+
+      o.entry("commentsEnabled").or_insert_with(|| true.into());
+      o.entry("sensitive").or_insert_with(|| false.into());
+    }
+  }
+
+  Ok(serde_json::from_value(object)?)
+}
+
+/// The start of the text of the content, for the posts that come without a title
+fn title_from_content(content: &str) -> String {
+  let mut text = String::new();
+  let mut in_tag = false;
+  for c in content.chars() {
+    match c {
+      '<' => in_tag = true,
+      '>' => in_tag = false,
+      c if !in_tag => text.push(c),
+      _ => (),
+    }
+  }
+  let title = text
+    .split_whitespace()
+    .collect::<Vec<&str>>()
+    .join(" ")
+    .chars()
+    .take(100)
+    .collect::<String>();
+  if title.is_empty() {
+    "Untitled".to_owned()
+  } else {
+    title
+  }
+}
+
 #[async_trait::async_trait(?Send)]
 impl FromApub for PostForm {
   type ApubType = PageExt;
@@ -193,24 +351,38 @@ impl FromApub for PostForm {
         let summary = preview_page
           .summary()
           .map(|s| s.as_single_xsd_string().unwrap().to_string());
+        // The embed HTML of other instances gets the same cleaning as the one fetched here
         let content = preview_page
           .content()
-          .map(|c| c.as_single_xsd_string().unwrap().to_string());
+          .and_then(|c| sanitize_embed_html(c.as_single_xsd_string().unwrap()));
         (name, summary, content)
       }
       None => (None, None, None),
     };
 
-    let url = page
-      .inner
-      .url
-      .as_ref()
-      .map(|u| u.as_single_xsd_string().unwrap().to_string());
+    // The url is the audio or video attachment when there is one, so that clients can play it
+    let (url, url_media_type) = match media_attachment(page) {
+      Some((url, media_type)) => (Some(url), Some(media_type)),
+      None => (
+        page
+          .inner
+          .url
+          .as_ref()
+          .map(|u| u.as_single_xsd_string().unwrap().to_string()),
+        None,
+      ),
+    };
     let body = page
       .inner
       .content
       .as_ref()
       .map(|c| c.as_single_xsd_string().unwrap().to_string());
+    // Their instance may not classify images, so the thumbnail is classified here too
+    let nsfw = ext.sensitive
+      || match &thumbnail_url {
+        Some(thumbnail_url) => remote_thumbnail_is_nsfw(client, thumbnail_url).await,
+        None => false,
+      };
     Ok(PostForm {
       name: page
         .inner
@@ -237,18 +409,49 @@ impl FromApub for PostForm {
         .as_ref()
         .map(|u| u.as_ref().to_owned().naive_local()),
       deleted: None,
-      nsfw: ext.sensitive,
+      nsfw,
       stickied: None, // -> put it in "featured" collection of the community
       embed_title,
       embed_description,
       embed_html,
-      thumbnail_url,
+      embed_type: None,
+      url_media_type,
+      thumbnail_url: proxy_image_url(thumbnail_url),
       ap_id: page.inner.id().unwrap().to_string(),
       local: false,
+      pending: None,
     })
   }
 }
 
+/// The Delete of a post, with the community it goes to. A purge builds it before the post is gone.
+pub async fn post_delete_activity(
+  post: &Post,
+  creator: &User_,
+  pool: &DbPool,
+) -> Result<(Delete, Community), LemmyError> {
+  let page = post.to_apub(pool).await?;
+
+  let community_id = post.community_id;
+  let community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
+
+  let id = format!("{}/delete/{}", post.ap_id, uuid::Uuid::new_v4());
+  let mut delete = Delete::default();
+
+  populate_object_props(
+    &mut delete.object_props,
+    vec![community.get_followers_url()],
+    &id,
+  )?;
+
+  delete
+    .delete_props
+    .set_actor_xsd_any_uri(creator.actor_id.to_owned())?
+    .set_object_base_box(BaseBox::from_concrete(page)?)?;
+
+  Ok((delete, community))
+}
+
 #[async_trait::async_trait(?Send)]
 impl ApubObjectType for Post {
   /// Send out information about a newly created post, to the followers of the community.
@@ -331,24 +534,7 @@ impl ApubObjectType for Post {
     client: &Client,
     pool: &DbPool,
   ) -> Result<(), LemmyError> {
-    let page = self.to_apub(pool).await?;
-
-    let community_id = self.community_id;
-    let community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
-
-    let id = format!("{}/delete/{}", self.ap_id, uuid::Uuid::new_v4());
-    let mut delete = Delete::default();
-
-    populate_object_props(
-      &mut delete.object_props,
-      vec![community.get_followers_url()],
-      &id,
-    )?;
-
-    delete
-      .delete_props
-      .set_actor_xsd_any_uri(creator.actor_id.to_owned())?
-      .set_object_base_box(BaseBox::from_concrete(page)?)?;
+    let (delete, community) = post_delete_activity(self, creator, pool).await?;
 
     send_activity_to_community(
       creator,
@@ -418,6 +604,7 @@ impl ApubObjectType for Post {
   async fn send_remove(
     &self,
     mod_: &User_,
+    reason: Option<String>,
     client: &Client,
     pool: &DbPool,
   ) -> Result<(), LemmyError> {
@@ -439,6 +626,9 @@ impl ApubObjectType for Post {
       .remove_props
       .set_actor_xsd_any_uri(mod_.actor_id.to_owned())?
       .set_object_base_box(BaseBox::from_concrete(page)?)?;
+    if let Some(reason) = reason {
+      remove.object_props.set_summary_xsd_string(reason)?;
+    }
 
     send_activity_to_community(
       mod_,
@@ -104,7 +104,7 @@ impl ApubObjectType for PrivateMessage {
   async fn send_create(
     &self,
     creator: &User_,
-    client: &Client,
+    _client: &Client,
     pool: &DbPool,
   ) -> Result<(), LemmyError> {
     let note = self.to_apub(pool).await?;
@@ -127,7 +127,7 @@ impl ApubObjectType for PrivateMessage {
 
     insert_activity(creator.id, create.clone(), true, pool).await?;
 
-    send_activity(client, &create, creator, vec![to]).await?;
+    send_activity(pool, &create, creator, vec![to]).await?;
     Ok(())
   }
 
@@ -135,7 +135,7 @@ impl ApubObjectType for PrivateMessage {
   async fn send_update(
     &self,
     creator: &User_,
-    client: &Client,
+    _client: &Client,
     pool: &DbPool,
   ) -> Result<(), LemmyError> {
     let note = self.to_apub(pool).await?;
@@ -158,14 +158,14 @@ impl ApubObjectType for PrivateMessage {
 
     insert_activity(creator.id, update.clone(), true, pool).await?;
 
-    send_activity(client, &update, creator, vec![to]).await?;
+    send_activity(pool, &update, creator, vec![to]).await?;
     Ok(())
   }
 
   async fn send_delete(
     &self,
     creator: &User_,
-    client: &Client,
+    _client: &Client,
     pool: &DbPool,
   ) -> Result<(), LemmyError> {
     let note = self.to_apub(pool).await?;
@@ -188,14 +188,14 @@ impl ApubObjectType for PrivateMessage {
 
     insert_activity(creator.id, delete.clone(), true, pool).await?;
 
-    send_activity(client, &delete, creator, vec![to]).await?;
+    send_activity(pool, &delete, creator, vec![to]).await?;
     Ok(())
   }
 
   async fn send_undo_delete(
     &self,
     creator: &User_,
-    client: &Client,
+    _client: &Client,
     pool: &DbPool,
   ) -> Result<(), LemmyError> {
     let note = self.to_apub(pool).await?;
@@ -233,13 +233,14 @@ impl ApubObjectType for PrivateMessage {
 
     insert_activity(creator.id, undo.clone(), true, pool).await?;
 
-    send_activity(client, &undo, creator, vec![to]).await?;
+    send_activity(pool, &undo, creator, vec![to]).await?;
     Ok(())
   }
 
   async fn send_remove(
     &self,
     _mod_: &User_,
+    _reason: Option<String>,
     _client: &Client,
     _pool: &DbPool,
   ) -> Result<(), LemmyError> {
@@ -1,8 +1,9 @@
 use crate::{
   api::{
-    comment::{send_local_notifs, CommentResponse},
+    blocked_domain::check_url_domain_allowed,
+    comment::{send_local_notifs, send_watched_post_notifs, CommentResponse},
     community::CommunityResponse,
-    post::PostResponse,
+    post::{check_post_type_allowed, subscribed_post_recipients, PostResponse},
   },
   apub::{
     community::do_announce,
@@ -11,18 +12,27 @@ use crate::{
       get_or_fetch_and_insert_remote_comment, get_or_fetch_and_insert_remote_post,
       get_or_fetch_and_upsert_remote_community, get_or_fetch_and_upsert_remote_user,
     },
-    insert_activity, FromApub, GroupExt, PageExt,
+    get_featured_replies_url, insert_activity,
+    post::page_from_media_object,
+    trace_activity, FromApub, GroupExt, PageExt,
   },
   blocking,
   routes::{ChatServerParam, DbPoolParam},
+  webhook::queue_new_post_webhooks,
   websocket::{
-    server::{SendComment, SendCommunityRoomMessage, SendPost},
+    server::{
+      SendComment,
+      SendCommunityRoomMessage,
+      SendPost,
+      SendPostToSubscribers,
+      SendUnreadCounts,
+    },
     UserOperation,
   },
   DbPool, LemmyError,
 };
 use activitystreams::{
-  activity::{Announce, Create, Delete, Dislike, Like, Remove, Undo, Update},
+  activity::{Add, Announce, Create, Delete, Dislike, Like, Remove, Undo, Update},
   Activity, Base, BaseBox,
 };
 use activitystreams_new::{object::Note, primitives::XsdAnyUri};
@@ -38,9 +48,9 @@ use lemmy_db::{
   Crud, Likeable,
 };
 use lemmy_utils::scrape_text_for_mentions;
-use log::debug;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
+use tracing::debug;
 
 #[serde(untagged)]
 #[derive(Serialize, Deserialize, Debug)]
@@ -52,6 +62,7 @@ pub enum SharedAcceptedObjects {
   Delete(Box<Delete>),
   Undo(Box<Undo>),
   Remove(Box<Remove>),
+  Add(Box<Add>),
   Announce(Box<Announce>),
 }
 
@@ -65,6 +76,7 @@ impl SharedAcceptedObjects {
       SharedAcceptedObjects::Delete(d) => d.delete_props.get_object_base_box(),
       SharedAcceptedObjects::Undo(d) => d.undo_props.get_object_base_box(),
       SharedAcceptedObjects::Remove(r) => r.remove_props.get_object_base_box(),
+      SharedAcceptedObjects::Add(a) => a.add_props.get_object_base_box(),
       SharedAcceptedObjects::Announce(a) => a.announce_props.get_object_base_box(),
     }
   }
@@ -77,6 +89,7 @@ impl SharedAcceptedObjects {
       SharedAcceptedObjects::Delete(d) => d.delete_props.get_actor_xsd_any_uri(),
       SharedAcceptedObjects::Undo(d) => d.undo_props.get_actor_xsd_any_uri(),
       SharedAcceptedObjects::Remove(r) => r.remove_props.get_actor_xsd_any_uri(),
+      SharedAcceptedObjects::Add(a) => a.add_props.get_actor_xsd_any_uri(),
       SharedAcceptedObjects::Announce(a) => a.announce_props.get_actor_xsd_any_uri(),
     };
     uri.unwrap().clone()
@@ -91,6 +104,7 @@ impl SharedAcceptedObjects {
       SharedAcceptedObjects::Delete(d) => &d.object_props,
       SharedAcceptedObjects::Undo(d) => &d.object_props,
       SharedAcceptedObjects::Remove(r) => &r.object_props,
+      SharedAcceptedObjects::Add(a) => &a.object_props,
       SharedAcceptedObjects::Announce(a) => &a.object_props,
     };
     oprops
@@ -111,6 +125,7 @@ pub async fn shared_inbox(
   chat_server: ChatServerParam,
 ) -> Result<HttpResponse, LemmyError> {
   let activity = input.into_inner();
+  trace_activity(&activity);
   let pool = &pool;
   let client = &client;
 
@@ -133,7 +148,14 @@ pub async fn shared_inbox(
   }
 
   match (activity, object.kind()) {
-    (SharedAcceptedObjects::Create(c), Some("Page")) => {
+    (SharedAcceptedObjects::Create(c), Some("Page"))
+    | (SharedAcceptedObjects::Create(c), Some("Video"))
+    | (SharedAcceptedObjects::Create(c), Some("Audio")) => {
+      receive_create_post((*c).clone(), client, pool, chat_server).await?;
+      announce_activity_if_valid::<Create>(*c, &to, sender, client, pool).await
+    }
+    // A note that doesn't reply to anything is a post, like the ones of Pixelfed
+    (SharedAcceptedObjects::Create(c), Some("Note")) if !is_reply(&object) => {
       receive_create_post((*c).clone(), client, pool, chat_server).await?;
       announce_activity_if_valid::<Create>(*c, &to, sender, client, pool).await
     }
@@ -181,6 +203,14 @@ pub async fn shared_inbox(
       receive_remove_comment((*r).clone(), client, pool, chat_server).await?;
       announce_activity_if_valid::<Remove>(*r, &to, sender, client, pool).await
     }
+    (SharedAcceptedObjects::Add(a), Some("Note")) => {
+      receive_add_comment((*a).clone(), client, pool, chat_server).await?;
+      announce_activity_if_valid::<Add>(*a, &to, sender, client, pool).await
+    }
+    (SharedAcceptedObjects::Update(u), Some("Group")) => {
+      receive_update_community((*u).clone(), client, pool, chat_server).await?;
+      announce_activity_if_valid::<Update>(*u, &to, sender, client, pool).await
+    }
     (SharedAcceptedObjects::Delete(d), Some("Group")) => {
       receive_delete_community((*d).clone(), client, pool, chat_server).await?;
       announce_activity_if_valid::<Delete>(*d, &to, sender, client, pool).await
@@ -201,6 +231,10 @@ pub async fn shared_inbox(
       receive_undo_like((*u).clone(), client, pool, chat_server).await?;
       announce_activity_if_valid::<Undo>(*u, &to, sender, client, pool).await
     }
+    (SharedAcceptedObjects::Undo(u), Some("Add")) => {
+      receive_undo_add((*u).clone(), client, pool, chat_server).await?;
+      announce_activity_if_valid::<Undo>(*u, &to, sender, client, pool).await
+    }
     (SharedAcceptedObjects::Announce(a), _) => receive_announce(a, client, pool, chat_server).await,
     (a, _) => receive_unhandled_activity(a),
   }
@@ -249,7 +283,12 @@ async fn receive_announce(
       let create = object.into_concrete::<Create>()?;
       let inner_object = create.create_props.get_object_base_box().unwrap();
       match inner_object.kind() {
-        Some("Page") => receive_create_post(create, client, pool, chat_server).await,
+        Some("Page") | Some("Video") | Some("Audio") => {
+          receive_create_post(create, client, pool, chat_server).await
+        }
+        Some("Note") if !is_reply(inner_object) => {
+          receive_create_post(create, client, pool, chat_server).await
+        }
         Some("Note") => receive_create_comment(create, client, pool, chat_server).await,
         _ => receive_unhandled_activity(announce),
       }
@@ -299,6 +338,14 @@ async fn receive_announce(
         _ => receive_unhandled_activity(announce),
       }
     }
+    Some("Add") => {
+      let add = object.into_concrete::<Add>()?;
+      let inner_object = add.add_props.get_object_base_box().unwrap();
+      match inner_object.kind() {
+        Some("Note") => receive_add_comment(add, client, pool, chat_server).await,
+        _ => receive_unhandled_activity(announce),
+      }
+    }
     Some("Undo") => {
       let undo = object.into_concrete::<Undo>()?;
       let inner_object = undo.undo_props.get_object_base_box().unwrap();
@@ -306,6 +353,7 @@ async fn receive_announce(
         Some("Delete") => receive_undo_delete(undo, client, pool, chat_server).await,
         Some("Remove") => receive_undo_remove(undo, client, pool, chat_server).await,
         Some("Like") => receive_undo_like(undo, client, pool, chat_server).await,
+        Some("Add") => receive_undo_add(undo, client, pool, chat_server).await,
         _ => receive_unhandled_activity(announce),
       }
     }
@@ -313,6 +361,13 @@ async fn receive_announce(
   }
 }
 
+fn is_reply(object: &BaseBox) -> bool {
+  object
+    .to_owned()
+    .into_concrete::<serde_json::Value>()
+    .map_or(true, |o| o.get("inReplyTo").is_some())
+}
+
 fn receive_unhandled_activity<A>(activity: A) -> Result<HttpResponse, LemmyError>
 where
   A: Debug,
@@ -327,13 +382,14 @@ async fn receive_create_post(
   pool: &DbPool,
   chat_server: ChatServerParam,
 ) -> Result<HttpResponse, LemmyError> {
-  let page = create
+  let object = create
     .create_props
     .get_object_base_box()
     .to_owned()
     .unwrap()
     .to_owned()
-    .into_concrete::<PageExt>()?;
+    .into_concrete::<serde_json::Value>()?;
+  let page = page_from_media_object(object)?;
 
   let user_uri = create.create_props.get_actor_xsd_any_uri().unwrap();
 
@@ -342,6 +398,11 @@ async fn receive_create_post(
   insert_activity(user.id, create, false, pool).await?;
 
   let post = PostForm::from_apub(&page, client, pool).await?;
+  check_url_domain_allowed(pool, post.community_id, &post.url).await?;
+
+  let community_id = post.community_id;
+  let community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
+  check_post_type_allowed(&community, &post.url)?;
 
   let inserted_post = blocking(pool, move |conn| Post::create(conn, &post)).await??;
 
@@ -354,6 +415,18 @@ async fn receive_create_post(
 
   let res = PostResponse { post: post_view };
 
+  queue_new_post_webhooks(pool, &res.post).await?;
+
+  let subscriber_ids = subscribed_post_recipients(pool, &res.post).await?;
+  if !subscriber_ids.is_empty() {
+    chat_server.do_send(SendPostToSubscribers {
+      op: UserOperation::CreatePost,
+      post: res.clone(),
+      subscriber_ids,
+      my_id: None,
+    });
+  }
+
   chat_server.do_send(SendPost {
     op: UserOperation::CreatePost,
     post: res,
@@ -395,8 +468,23 @@ async fn receive_create_comment(
   // Its much easier to scrape them from the comment body, since the API has to do that
   // anyway.
   let mentions = scrape_text_for_mentions(&inserted_comment.content);
-  let recipient_ids =
-    send_local_notifs(mentions, inserted_comment.clone(), user, post, pool).await?;
+  let mut recipient_ids = send_local_notifs(
+    mentions,
+    inserted_comment.clone(),
+    user.clone(),
+    post.clone(),
+    pool,
+  )
+  .await?;
+  let watcher_ids = send_watched_post_notifs(
+    inserted_comment.clone(),
+    user,
+    post,
+    recipient_ids.clone(),
+    pool,
+  )
+  .await?;
+  recipient_ids.extend(watcher_ids);
 
   // Refetch the view
   let comment_view = blocking(pool, move |conn| {
@@ -409,6 +497,9 @@ async fn receive_create_comment(
     recipient_ids,
   };
 
+  chat_server.do_send(SendUnreadCounts {
+    user_ids: res.recipient_ids.clone(),
+  });
   chat_server.do_send(SendComment {
     op: UserOperation::CreateComment,
     comment: res,
@@ -439,6 +530,11 @@ async fn receive_update_post(
   insert_activity(user.id, update, false, pool).await?;
 
   let post = PostForm::from_apub(&page, client, pool).await?;
+  check_url_domain_allowed(pool, post.community_id, &post.url).await?;
+
+  let community_id = post.community_id;
+  let community = blocking(pool, move |conn| Community::read(conn, community_id)).await??;
+  check_post_type_allowed(&community, &post.url)?;
 
   let post_id = get_or_fetch_and_insert_remote_post(&post.ap_id, client, pool)
     .await?
@@ -608,6 +704,9 @@ async fn receive_update_comment(
     recipient_ids,
   };
 
+  chat_server.do_send(SendUnreadCounts {
+    user_ids: res.recipient_ids.clone(),
+  });
   chat_server.do_send(SendComment {
     op: UserOperation::EditComment,
     comment: res,
@@ -778,6 +877,15 @@ async fn receive_delete_community(
     private_key: community.private_key,
     public_key: community.public_key,
     last_refreshed_at: None,
+    require_flair: None,
+    icon: None,
+    banner: None,
+    archive_after_days: None,
+    post_approval: None,
+    read_only: None,
+    allowed_post_types: None,
+    default_sort_type: None,
+    language: None,
   };
 
   let community_id = community.id;
@@ -806,6 +914,62 @@ async fn receive_delete_community(
   Ok(HttpResponse::Ok().finish())
 }
 
+async fn receive_update_community(
+  update: Update,
+  client: &Client,
+  pool: &DbPool,
+  chat_server: ChatServerParam,
+) -> Result<HttpResponse, LemmyError> {
+  let user_uri = update.update_props.get_actor_xsd_any_uri().unwrap();
+
+  let group = update
+    .update_props
+    .get_object_base_box()
+    .to_owned()
+    .unwrap()
+    .to_owned()
+    .into_concrete::<GroupExt>()?;
+
+  let user = get_or_fetch_and_upsert_remote_user(&user_uri, client, pool).await?;
+
+  insert_activity(user.id, update, false, pool).await?;
+
+  let community_form = CommunityForm::from_apub(&group, client, pool).await?;
+
+  let community_actor_id = community_form.actor_id.to_owned();
+  let community = blocking(pool, move |conn| {
+    Community::read_from_actor_id(conn, &community_actor_id)
+  })
+  .await??;
+
+  // The settings of a local community only change through its own mods
+  if community.local {
+    return Ok(HttpResponse::Ok().finish());
+  }
+
+  let community_id = community.id;
+  blocking(pool, move |conn| {
+    Community::update(conn, community_id, &community_form)
+  })
+  .await??;
+
+  let res = CommunityResponse {
+    community: blocking(pool, move |conn| {
+      CommunityView::read(conn, community_id, None)
+    })
+    .await??,
+  };
+
+  chat_server.do_send(SendCommunityRoomMessage {
+    op: UserOperation::EditCommunity,
+    response: res,
+    community_id,
+    my_id: None,
+  });
+
+  Ok(HttpResponse::Ok().finish())
+}
+
 async fn receive_remove_community(
   remove: Remove,
   client: &Client,
@@ -851,6 +1015,15 @@ async fn receive_remove_community(
     private_key: community.private_key,
     public_key: community.public_key,
     last_refreshed_at: None,
+    require_flair: None,
+    icon: None,
+    banner: None,
+    archive_after_days: None,
+    post_approval: None,
+    read_only: None,
+    allowed_post_types: None,
+    default_sort_type: None,
+    language: None,
   };
 
   let community_id = community.id;
@@ -918,10 +1091,13 @@ async fn receive_delete_post(
     embed_title: post.embed_title,
     embed_description: post.embed_description,
     embed_html: post.embed_html,
+    embed_type: post.embed_type,
+    url_media_type: post.url_media_type,
     thumbnail_url: post.thumbnail_url,
     ap_id: post.ap_id,
     local: post.local,
     published: None,
+    pending: None,
   };
   let post_id = post.id;
   blocking(pool, move |conn| Post::update(conn, post_id, &post_form)).await??;
@@ -980,10 +1156,13 @@ async fn receive_remove_post(
     embed_title: post.embed_title,
     embed_description: post.embed_description,
     embed_html: post.embed_html,
+    embed_type: post.embed_type,
+    url_media_type: post.url_media_type,
     thumbnail_url: post.thumbnail_url,
     ap_id: post.ap_id,
     local: post.local,
     published: None,
+    pending: None,
   };
   let post_id = post.id;
   blocking(pool, move |conn| Post::update(conn, post_id, &post_form)).await??;
@@ -1131,6 +1310,85 @@ async fn receive_remove_comment(
   Ok(HttpResponse::Ok().finish())
 }
 
+async fn receive_add_comment(
+  add: Add,
+  client: &Client,
+  pool: &DbPool,
+  chat_server: ChatServerParam,
+) -> Result<HttpResponse, LemmyError> {
+  let mod_uri = add.add_props.get_actor_xsd_any_uri().unwrap();
+
+  let note = add
+    .add_props
+    .get_object_base_box()
+    .to_owned()
+    .unwrap()
+    .to_owned()
+    .into_concrete::<Note>()?;
+
+  let comment = get_featured_reply(&add, &note, client, pool).await?;
+
+  let mod_ = get_or_fetch_and_upsert_remote_user(&mod_uri, client, pool).await?;
+
+  insert_activity(mod_.id, add, false, pool).await?;
+
+  let comment_id = comment.id;
+  blocking(pool, move |conn| {
+    Comment::update_pinned(conn, comment_id, true)
+  })
+  .await??;
+
+  // Refetch the view
+  let comment_view =
+    blocking(pool, move |conn| CommentView::read(conn, comment_id, None)).await??;
+
+  // TODO get those recipient actor ids from somewhere
+  let recipient_ids = vec![];
+  let res = CommentResponse {
+    comment: comment_view,
+    recipient_ids,
+  };
+
+  chat_server.do_send(SendComment {
+    op: UserOperation::PinComment,
+    comment: res,
+    my_id: None,
+  });
+
+  Ok(HttpResponse::Ok().finish())
+}
+
+/// Reads the comment an add activity pins. Other servers also add notes to collections of their
+/// own, like a user's featured ones, so the target has to be the featured replies of the post.
+async fn get_featured_reply(
+  add: &Add,
+  note: &Note,
+  client: &Client,
+  pool: &DbPool,
+) -> Result<Comment, LemmyError> {
+  let target = match add.add_props.get_target_xsd_any_uri() {
+    Some(t) => t.to_string(),
+    None => return Err(format_err!("Add activity has no target").into()),
+  };
+
+  if !target.ends_with("/featured_replies") {
+    return Err(format_err!("Add target {} not supported", target).into());
+  }
+
+  let comment_ap_id = CommentForm::from_apub(note, client, pool).await?.ap_id;
+
+  let comment = get_or_fetch_and_insert_remote_comment(&comment_ap_id, client, pool).await?;
+
+  let post_id = comment.post_id;
+  let post = blocking(pool, move |conn| Post::read(conn, post_id)).await??;
+
+  if target != get_featured_replies_url(&post.ap_id) {
+    return Err(format_err!("Add target {} is not the post of the comment", target).into());
+  }
+
+  Ok(comment)
+}
+
 async fn receive_undo_delete(
   undo: Undo,
   client: &Client,
@@ -1358,10 +1616,13 @@ async fn receive_undo_delete_post(
     embed_title: post.embed_title,
     embed_description: post.embed_description,
     embed_html: post.embed_html,
+    embed_type: post.embed_type,
+    url_media_type: post.url_media_type,
     thumbnail_url: post.thumbnail_url,
     ap_id: post.ap_id,
     local: post.local,
     published: None,
+    pending: None,
   };
   let post_id = post.id;
   blocking(pool, move |conn| Post::update(conn, post_id, &post_form)).await??;
@@ -1420,10 +1681,13 @@ async fn receive_undo_remove_post(
     embed_title: post.embed_title,
     embed_description: post.embed_description,
     embed_html: post.embed_html,
+    embed_type: post.embed_type,
+    url_media_type: post.url_media_type,
     thumbnail_url: post.thumbnail_url,
     ap_id: post.ap_id,
     local: post.local,
     published: None,
+    pending: None,
   };
   let post_id = post.id;
   blocking(pool, move |conn| Post::update(conn, post_id, &post_form)).await??;
@@ -1488,6 +1752,15 @@ async fn receive_undo_delete_community(
     private_key: community.private_key,
     public_key: community.public_key,
     last_refreshed_at: None,
+    require_flair: None,
+    icon: None,
+    banner: None,
+    archive_after_days: None,
+    post_approval: None,
+    read_only: None,
+    allowed_post_types: None,
+    default_sort_type: None,
+    language: None,
   };
 
   let community_id = community.id;
@@ -1561,6 +1834,15 @@ async fn receive_undo_remove_community(
     private_key: community.private_key,
     public_key: community.public_key,
     last_refreshed_at: None,
+    require_flair: None,
+    icon: None,
+    banner: None,
+    archive_after_days: None,
+    post_approval: None,
+    read_only: None,
+    allowed_post_types: None,
+    default_sort_type: None,
+    language: None,
   };
 
   let community_id = community.id;
@@ -1718,3 +2000,79 @@ async fn receive_undo_like_post(
 
   Ok(HttpResponse::Ok().finish())
 }
+
+async fn receive_undo_add(
+  undo: Undo,
+  client: &Client,
+  pool: &DbPool,
+  chat_server: ChatServerParam,
+) -> Result<HttpResponse, LemmyError> {
+  let add = undo
+    .undo_props
+    .get_object_base_box()
+    .to_owned()
+    .unwrap()
+    .to_owned()
+    .into_concrete::<Add>()?;
+
+  let type_ = add
+    .add_props
+    .get_object_base_box()
+    .to_owned()
+    .unwrap()
+    .kind()
+    .unwrap();
+
+  match type_ {
+    "Note" => receive_undo_add_comment(add, client, pool, chat_server).await,
+    d => Err(format_err!("Undo Add type {} not supported", d).into()),
+  }
+}
+
+async fn receive_undo_add_comment(
+  add: Add,
+  client: &Client,
+  pool: &DbPool,
+  chat_server: ChatServerParam,
+) -> Result<HttpResponse, LemmyError> {
+  let mod_uri = add.add_props.get_actor_xsd_any_uri().unwrap();
+
+  let note = add
+    .add_props
+    .get_object_base_box()
+    .to_owned()
+    .unwrap()
+    .to_owned()
+    .into_concrete::<Note>()?;
+
+  let comment = get_featured_reply(&add, &note, client, pool).await?;
+
+  let mod_ = get_or_fetch_and_upsert_remote_user(&mod_uri, client, pool).await?;
+
+  insert_activity(mod_.id, add, false, pool).await?;
+
+  let comment_id = comment.id;
+  blocking(pool, move |conn| {
+    Comment::update_pinned(conn, comment_id, false)
+  })
+  .await??;
+
+  // Refetch the view
+  let comment_view =
+    blocking(pool, move |conn| CommentView::read(conn, comment_id, None)).await??;
+
+  // TODO get those recipient actor ids from somewhere
+  let recipient_ids = vec![];
+  let res = CommentResponse {
+    comment: comment_view,
+    recipient_ids,
+  };
+
+  chat_server.do_send(SendComment {
+    op: UserOperation::PinComment,
+    comment: res,
+    my_id: None,
+  });
+
+  Ok(HttpResponse::Ok().finish())
+}
@@ -1,29 +1,31 @@
 use crate::{
-  api::claims::Claims,
   apub::{
-    activities::send_activity, create_apub_response, insert_activity, ActorType, FromApub,
-    PersonExt, ToApub,
+    activities::send_activity, create_apub_response, get_shared_inbox, insert_activity, ActorType,
+    Freshness, FromApub, PersonExt, ToApub,
   },
   blocking,
+  image_proxy::proxy_image_url,
   routes::DbPoolParam,
   DbPool, LemmyError,
 };
 use activitystreams_ext::Ext1;
 use activitystreams_new::{
-  activity::{Follow, Undo},
+  activity::{Accept, Follow, Undo},
   actor::{ApActor, Endpoints, Person},
   context,
   object::{Image, Tombstone},
   prelude::*,
   primitives::{XsdAnyUri, XsdDateTime},
 };
-use actix_web::{body::Body, client::Client, web, HttpResponse};
+use actix_web::{body::Body, client::Client, web, HttpRequest, HttpResponse};
 use failure::_core::str::FromStr;
+use itertools::Itertools;
 use lemmy_db::{
   naive_now,
-  user::{UserForm, User_},
+  user::{UserFollower, UserForm, User_},
+  user_alias::UserAlias,
 };
-use lemmy_utils::convert_datetime;
+use lemmy_utils::{convert_datetime, make_apub_endpoint, EndpointType};
 use serde::Deserialize;
 
 #[derive(Deserialize)]
@@ -39,10 +41,16 @@ impl ToApub for User_ {
   async fn to_apub(&self, _pool: &DbPool) -> Result<PersonExt, LemmyError> {
     // TODO go through all these to_string and to_owned()
     let mut person = Person::new();
+    // The shown name can change freely, while the preferred username stays the login name
     person
       .set_context(context())
       .set_id(XsdAnyUri::from_str(&self.actor_id)?)
-      .set_name(self.name.to_owned())
+      .set_name(
+        self
+          .display_name
+          .to_owned()
+          .unwrap_or_else(|| self.name.to_owned()),
+      )
       .set_published(XsdDateTime::from(convert_datetime(self.published)));
 
     if let Some(u) = self.updated {
@@ -55,6 +63,16 @@ impl ToApub for User_ {
       person.set_icon(image.into_any_base()?);
     }
 
+    if let Some(banner_url) = &self.banner {
+      let mut image = Image::new();
+      image.set_url(banner_url.to_owned());
+      person.set_image(image.into_any_base()?);
+    }
+
+    if let Some(bio) = &self.bio {
+      person.set_summary(bio.to_owned());
+    }
+
     let mut ap_actor = ApActor::new(self.get_inbox_url().parse()?, person);
     ap_actor
       .set_outbox(self.get_outbox_url().parse()?)
@@ -63,12 +81,13 @@ impl ToApub for User_ {
       .set_liked(self.get_liked_url().parse()?)
       .set_endpoints(Endpoints {
         shared_inbox: Some(self.get_shared_inbox_url().parse()?),
+        // Where clients get a token for the outbox
+        oauth_authorization_endpoint: Some(oauth_endpoint("authorize").parse()?),
+        oauth_token_endpoint: Some(oauth_endpoint("token").parse()?),
         ..Default::default()
       });
 
-    if let Some(i) = &self.preferred_username {
-      ap_actor.set_preferred_username(i.to_owned());
-    }
+    ap_actor.set_preferred_username(self.name.to_owned());
 
     Ok(Ext1::new(ap_actor, self.get_public_key_ext()))
   }
@@ -95,7 +114,7 @@ impl ActorType for User_ {
   async fn send_follow(
     &self,
     follow_actor_id: &str,
-    client: &Client,
+    _client: &Client,
     pool: &DbPool,
   ) -> Result<(), LemmyError> {
     let id = format!("{}/follow/{}", self.actor_id, uuid::Uuid::new_v4());
@@ -105,14 +124,14 @@ impl ActorType for User_ {
 
     insert_activity(self.id, follow.clone(), true, pool).await?;
 
-    send_activity(client, &follow, self, vec![to]).await?;
+    send_activity(pool, &follow, self, vec![to]).await?;
     Ok(())
   }
 
   async fn send_unfollow(
     &self,
     follow_actor_id: &str,
-    client: &Client,
+    _client: &Client,
     pool: &DbPool,
   ) -> Result<(), LemmyError> {
     let id = format!("{}/follow/{}", self.actor_id, uuid::Uuid::new_v4());
@@ -129,10 +148,19 @@ impl ActorType for User_ {
 
     insert_activity(self.id, undo.clone(), true, pool).await?;
 
-    send_activity(client, &undo, self, vec![to]).await?;
+    send_activity(pool, &undo, self, vec![to]).await?;
     Ok(())
   }
 
+  async fn send_update(
+    &self,
+    _creator: &User_,
+    _client: &Client,
+    _pool: &DbPool,
+  ) -> Result<(), LemmyError> {
+    unimplemented!()
+  }
+
   async fn send_delete(
     &self,
     _creator: &User_,
@@ -169,17 +197,41 @@ impl ActorType for User_ {
     unimplemented!()
   }
 
+  /// As a local user, accept the follow request from a remote user.
   async fn send_accept_follow(
     &self,
-    _follow: &Follow,
+    follow: &Follow,
     _client: &Client,
-    _pool: &DbPool,
+    pool: &DbPool,
   ) -> Result<(), LemmyError> {
-    unimplemented!()
+    let actor_uri = follow.actor.as_single_xsd_any_uri().unwrap().to_string();
+    let id = format!("{}/accept/{}", self.actor_id, uuid::Uuid::new_v4());
+
+    let mut accept = Accept::new(self.actor_id.to_owned(), follow.clone().into_any_base()?);
+    accept.set_context(context()).set_id(id.parse()?);
+    let to = format!("{}/inbox", actor_uri);
+
+    insert_activity(self.id, accept.clone(), true, pool).await?;
+
+    send_activity(pool, &accept, self, vec![to]).await?;
+    Ok(())
   }
 
-  async fn get_follower_inboxes(&self, _pool: &DbPool) -> Result<Vec<String>, LemmyError> {
-    unimplemented!()
+  /// For a given user, returns the shared inboxes of their followers on other instances.
+  async fn get_follower_inboxes(&self, pool: &DbPool) -> Result<Vec<String>, LemmyError> {
+    let id = self.id;
+
+    let actor_ids = blocking(pool, move |conn| {
+      UserFollower::remote_follower_actor_ids(conn, id)
+    })
+    .await??;
+    let inboxes = actor_ids
+      .iter()
+      .map(|a| get_shared_inbox(a))
+      .unique()
+      .collect();
+
+    Ok(inboxes)
   }
 }
 
@@ -199,19 +251,38 @@ impl FromApub for UserForm {
       None => None,
     };
 
-    Ok(UserForm {
-      name: person
-        .name()
+    let banner = match person.image() {
+      Some(any_image) => Image::from_any_base(any_image.as_one().unwrap().clone())
+        .unwrap()
         .unwrap()
-        .as_single_xsd_string()
+        .url
         .unwrap()
-        .into(),
-      preferred_username: person.inner.preferred_username().map(|u| u.to_string()),
+        .as_single_xsd_any_uri()
+        .map(|u| u.to_string()),
+      None => None,
+    };
+
+    // Older instances don't send a preferred username, and use the name for the username instead
+    let name: String = person
+      .name()
+      .unwrap()
+      .as_single_xsd_string()
+      .unwrap()
+      .into();
+    let preferred_username = person.inner.preferred_username().map(|u| u.to_string());
+    let (name, display_name) = match &preferred_username {
+      Some(username) if username != &name => (username.to_owned(), Some(name)),
+      _ => (name, None),
+    };
+
+    Ok(UserForm {
+      name,
+      preferred_username,
       password_encrypted: "".to_string(),
       admin: false,
       banned: false,
       email: None,
-      avatar,
+      avatar: proxy_image_url(avatar),
       updated: person
         .updated()
         .map(|u| u.as_ref().to_owned().naive_local()),
@@ -231,20 +302,57 @@ impl FromApub for UserForm {
       private_key: None,
       public_key: Some(person.ext_one.public_key.to_owned().public_key_pem),
       last_refreshed_at: Some(naive_now()),
+      banner: proxy_image_url(banner),
+      hide_read_posts: false,
+      bot_account: false,
+      hide_bot_accounts: false,
+      hide_presence: false,
+      display_name,
     })
   }
 }
 
 /// Return the user json over HTTP.
 pub async fn get_apub_user_http(
+  req: HttpRequest,
   info: web::Path<UserQuery>,
   db: DbPoolParam,
 ) -> Result<HttpResponse<Body>, LemmyError> {
   let user_name = info.into_inner().user_name;
-  let user = blocking(&db, move |conn| {
-    Claims::find_by_email_or_username(conn, &user_name)
-  })
-  .await??;
+  let query_name = user_name.to_owned();
+  let user = blocking(&db, move |conn| UserAlias::read_user(conn, &query_name)).await??;
+
+  // Old names redirect to the current one, unless the old path is still what the actor id uses
+  let actor_url = make_apub_endpoint(EndpointType::User, &user_name).to_string();
+  if user_name != user.name && actor_url != user.actor_id {
+    let current_url = make_apub_endpoint(EndpointType::User, &user.name).to_string();
+    return Ok(
+      HttpResponse::MovedPermanently()
+        .header("Location", current_url)
+        .finish(),
+    );
+  }
+
+  let freshness = Freshness::new(user.published, user.updated, false);
+  if let Some(not_modified) = freshness.not_modified(&req) {
+    return Ok(not_modified);
+  }
+
   let u = user.to_apub(&db).await?;
-  Ok(create_apub_response(&u))
+  // Bots are served as a Service actor, which is otherwise laid out just like a Person
+  let mut json = serde_json::to_value(&u)?;
+  if user.bot_account {
+    json["type"] = "Service".into();
+  }
+  Ok(freshness.add_headers(create_apub_response(&json)))
+}
+
+/// The OAuth pages of the instance, the same for every user
+fn oauth_endpoint(page: &str) -> String {
+  format!(
+    "{}://{}/oauth/{}",
+    lemmy_utils::get_apub_protocol_string(),
+    lemmy_utils::settings::Settings::get().hostname,
+    page
+  )
 }
@@ -1,36 +1,44 @@
 use crate::{
-  api::user::PrivateMessageResponse,
+  api::{comment::wants_notification, user::PrivateMessageResponse},
   apub::{
     extensions::signatures::verify,
     fetcher::{get_or_fetch_and_upsert_remote_community, get_or_fetch_and_upsert_remote_user},
-    insert_activity, FromApub,
+    insert_activity, trace_activity, ActorType, FromApub,
   },
   blocking,
+  push::{queue_push_notifications, PushNotification},
   routes::{ChatServerParam, DbPoolParam},
-  websocket::{server::SendUserRoomMessage, UserOperation},
+  websocket::{
+    server::{SendPrivateMessage, SendUnreadCounts},
+    UserOperation,
+  },
   DbPool, LemmyError,
 };
 use activitystreams::{
   activity::{Accept, Create, Delete, Undo, Update},
   object::Note,
 };
+use activitystreams_new::activity::Follow;
 use actix_web::{client::Client, web, HttpRequest, HttpResponse};
 use lemmy_db::{
   community::{CommunityFollower, CommunityFollowerForm},
   naive_now,
   private_message::{PrivateMessage, PrivateMessageForm},
   private_message_view::PrivateMessageView,
-  user::User_,
+  user::{UserFollower, UserFollowerForm, User_},
+  user_alias::UserAlias,
   Crud, Followable,
 };
-use log::debug;
-use serde::Deserialize;
+use lemmy_utils::settings::Settings;
+use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
+use tracing::debug;
 
 #[serde(untagged)]
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub enum UserAcceptedObjects {
   Accept(Box<Accept>),
+  Follow(Box<Follow>),
   Create(Box<Create>),
   Update(Box<Update>),
   Delete(Box<Delete>),
@@ -49,10 +57,12 @@ pub async fn user_inbox(
   // TODO: would be nice if we could do the signature check here, but we cant access the actor property
   let input = input.into_inner();
   let username = path.into_inner();
+  trace_activity(&input);
   debug!("User {} received activity: {:?}", &username, &input);
 
   match input {
     UserAcceptedObjects::Accept(a) => receive_accept(*a, &request, &username, &client, &db).await,
+    UserAcceptedObjects::Follow(f) => receive_follow(*f, &request, &username, &client, &db).await,
     UserAcceptedObjects::Create(c) => {
       receive_create_private_message(*c, &request, &client, &db, chat_server).await
     }
@@ -63,7 +73,15 @@ pub async fn user_inbox(
       receive_delete_private_message(*d, &request, &client, &db, chat_server).await
     }
     UserAcceptedObjects::Undo(u) => {
-      receive_undo_delete_private_message(*u, &request, &client, &db, chat_server).await
+      let kind = u
+        .undo_props
+        .get_object_base_box()
+        .and_then(|o| o.kind())
+        .map(|k| k.to_owned());
+      match kind.as_deref() {
+        Some("Follow") => receive_undo_follow(*u, &request, &username, &client, &db).await,
+        _ => receive_undo_delete_private_message(*u, &request, &client, &db, chat_server).await,
+      }
     }
   }
 }
@@ -82,11 +100,18 @@ async fn receive_accept(
     .unwrap()
     .to_string();
 
+  // Remote users accept follows too, and they were stored when they got followed
+  let actor_id = community_uri.to_owned();
+  let followed = blocking(pool, move |conn| User_::read_from_actor_id(conn, &actor_id)).await?;
+  if let Ok(followed) = followed {
+    return receive_accept_user_follow(accept, request, username, followed, pool).await;
+  }
+
   let community = get_or_fetch_and_upsert_remote_community(&community_uri, client, pool).await?;
   verify(request, &community)?;
 
   let username = username.to_owned();
-  let user = blocking(pool, move |conn| User_::read_from_name(conn, &username)).await??;
+  let user = blocking(pool, move |conn| UserAlias::read_user(conn, &username)).await??;
 
   insert_activity(community.creator_id, accept, false, pool).await?;
 
@@ -106,6 +131,111 @@ async fn receive_accept(
   Ok(HttpResponse::Ok().finish())
 }
 
+/// Handle a remote user accepting a follow from a local one.
+async fn receive_accept_user_follow(
+  accept: Accept,
+  request: &HttpRequest,
+  username: &str,
+  followed: User_,
+  pool: &DbPool,
+) -> Result<HttpResponse, LemmyError> {
+  verify(request, &followed)?;
+
+  let username = username.to_owned();
+  let user = blocking(pool, move |conn| UserAlias::read_user(conn, &username)).await??;
+
+  insert_activity(followed.id, accept, false, pool).await?;
+
+  let user_follower_form = UserFollowerForm {
+    user_id: followed.id,
+    follower_id: user.id,
+  };
+
+  blocking(pool, move |conn| {
+    UserFollower::follow(conn, &user_follower_form)
+  })
+  .await??;
+
+  Ok(HttpResponse::Ok().finish())
+}
+
+/// Handle a follow request from a remote user, adding it to the local database and returning an
+/// Accept activity.
+async fn receive_follow(
+  follow: Follow,
+  request: &HttpRequest,
+  username: &str,
+  client: &Client,
+  pool: &DbPool,
+) -> Result<HttpResponse, LemmyError> {
+  let follower_uri = follow.actor.as_single_xsd_any_uri().unwrap();
+
+  let follower = get_or_fetch_and_upsert_remote_user(&follower_uri, client, pool).await?;
+  verify(request, &follower)?;
+
+  let username = username.to_owned();
+  let user = blocking(pool, move |conn| UserAlias::read_user(conn, &username)).await??;
+
+  if !user.local {
+    return Err(format_err!("Received follow for remote user {}", &user.actor_id).into());
+  }
+
+  insert_activity(follower.id, follow.clone(), false, pool).await?;
+
+  let user_follower_form = UserFollowerForm {
+    user_id: user.id,
+    follower_id: follower.id,
+  };
+
+  blocking(pool, move |conn| {
+    UserFollower::follow(conn, &user_follower_form)
+  })
+  .await??;
+
+  user.send_accept_follow(&follow, client, pool).await?;
+
+  Ok(HttpResponse::Ok().finish())
+}
+
+async fn receive_undo_follow(
+  undo: Undo,
+  request: &HttpRequest,
+  username: &str,
+  client: &Client,
+  pool: &DbPool,
+) -> Result<HttpResponse, LemmyError> {
+  let follow = undo
+    .undo_props
+    .get_object_base_box()
+    .to_owned()
+    .unwrap()
+    .to_owned()
+    .into_concrete::<Follow>()?;
+
+  let follower_uri = follow.actor.as_single_xsd_any_uri().unwrap();
+
+  let follower = get_or_fetch_and_upsert_remote_user(&follower_uri, client, pool).await?;
+  verify(request, &follower)?;
+
+  let username = username.to_owned();
+  let user = blocking(pool, move |conn| UserAlias::read_user(conn, &username)).await??;
+
+  insert_activity(follower.id, undo, false, pool).await?;
+
+  let user_follower_form = UserFollowerForm {
+    user_id: user.id,
+    follower_id: follower.id,
+  };
+
+  // This will fail if they aren't a follower, but ignore the error.
+  blocking(pool, move |conn| {
+    UserFollower::unfollow(conn, &user_follower_form).ok()
+  })
+  .await?;
+
+  Ok(HttpResponse::Ok().finish())
+}
+
 async fn receive_create_private_message(
   create: Create,
   request: &HttpRequest,
@@ -143,13 +273,30 @@ async fn receive_create_private_message(
   let res = PrivateMessageResponse { message };
 
   let recipient_id = res.message.recipient_id;
+  let notify_recipient = blocking(pool, move |conn| {
+    wants_notification(conn, recipient_id, |s| s.private_messages)
+  })
+  .await?;
+
+  if notify_recipient {
+    let push_notification = PushNotification::new(
+      recipient_id,
+      &format!("Private message from {}", res.message.creator_name),
+      &res.message.content,
+      &format!("https://{}/inbox", Settings::get().hostname),
+    );
+    queue_push_notifications(pool, vec![push_notification]).await?;
+  }
 
-  chat_server.do_send(SendUserRoomMessage {
+  chat_server.do_send(SendPrivateMessage {
     op: UserOperation::CreatePrivateMessage,
-    response: res,
-    recipient_id,
+    message: res,
+    notify_recipient,
     my_id: None,
   });
+  chat_server.do_send(SendUnreadCounts {
+    user_ids: vec![recipient_id],
+  });
 
   Ok(HttpResponse::Ok().finish())
 }
@@ -198,12 +345,10 @@ async fn receive_update_private_message(
 
   let res = PrivateMessageResponse { message };
 
-  let recipient_id = res.message.recipient_id;
-
-  chat_server.do_send(SendUserRoomMessage {
+  chat_server.do_send(SendPrivateMessage {
     op: UserOperation::EditPrivateMessage,
-    response: res,
-    recipient_id,
+    message: res,
+    notify_recipient: true,
     my_id: None,
   });
 
@@ -266,12 +411,10 @@ async fn receive_delete_private_message(
 
   let res = PrivateMessageResponse { message };
 
-  let recipient_id = res.message.recipient_id;
-
-  chat_server.do_send(SendUserRoomMessage {
+  chat_server.do_send(SendPrivateMessage {
     op: UserOperation::EditPrivateMessage,
-    response: res,
-    recipient_id,
+    message: res,
+    notify_recipient: true,
     my_id: None,
   });
 
@@ -340,12 +483,10 @@ async fn receive_undo_delete_private_message(
 
   let res = PrivateMessageResponse { message };
 
-  let recipient_id = res.message.recipient_id;
-
-  chat_server.do_send(SendUserRoomMessage {
+  chat_server.do_send(SendPrivateMessage {
     op: UserOperation::EditPrivateMessage,
-    response: res,
-    recipient_id,
+    message: res,
+    notify_recipient: true,
     my_id: None,
   });
 
@@ -0,0 +1,349 @@
+use crate::{
+  api::{
+    claims::Claims,
+    comment::{CommentResponse, CreateComment, CreateCommentLike},
+    community::FollowCommunity,
+    post::{CreatePost, CreatePostLike, PostResponse},
+    APIError,
+    Oper,
+    Perform,
+  },
+  apub::{
+    fetcher::{get_or_fetch_and_upsert_remote_community, search_by_apub_id},
+    trace_activity,
+  },
+  blocking,
+  get_ip,
+  rate_limit::RateLimit,
+  routes::{oauth_bearer, perform, ChatServerParam, DbPoolParam},
+  DbPool,
+  ErrorResponse,
+  LemmyError,
+};
+use actix_web::{client::Client, http::header::LOCATION, web, HttpRequest, HttpResponse};
+use lemmy_db::{comment::Comment, post::Post, user_alias::UserAlias};
+use serde_json::Value;
+use tracing::debug;
+
+/// Addressed to everyone, as opposed to a community
+const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+
+/// What the operations an activity turns into are performed with
+struct OutboxContext<'a> {
+  auth: String,
+  request: &'a HttpRequest,
+  client: &'a Client,
+  pool: &'a DbPool,
+  chat_server: &'a ChatServerParam,
+}
+
+impl<'a> OutboxContext<'a> {
+  async fn perform<Request>(
+    &self,
+    data: Request,
+  ) -> Result<<Oper<Request> as Perform>::Response, LemmyError>
+  where
+    Oper<Request>: Perform,
+  {
+    perform(data, self.request, self.client, self.pool, self.chat_server).await
+  }
+}
+
+/// The post or comment an activity of a client is about
+enum OutboxObject {
+  Post(i32),
+  Comment { id: i32, post_id: i32 },
+}
+
+/// Handler for the activities local users post to their outbox with an ActivityPub client. The
+/// request has the JWT of the user as a bearer token. Each activity is done as the operation of
+/// the API that does the same, which sends it on to the other instances, and objects posted
+/// without an activity are created.
+pub async fn user_outbox(
+  request: HttpRequest,
+  input: web::Json<Value>,
+  path: web::Path<String>,
+  client: web::Data<Client>,
+  db: DbPoolParam,
+  chat_server: ChatServerParam,
+  rate_limit: web::Data<RateLimit>,
+) -> Result<HttpResponse, LemmyError> {
+  let activity = input.into_inner();
+  trace_activity(&activity);
+
+  // The clients log in with a JWT, or through the OAuth pages
+  let bearer = match Claims::decode_bearer(&request) {
+    Some(bearer) => Some(bearer),
+    None => oauth_bearer(&request, &db).await?,
+  };
+  let (claims, auth) = match bearer {
+    Some(bearer) => bearer,
+    None => {
+      return Ok(HttpResponse::Unauthorized().json(ErrorResponse {
+        error: "not_logged_in".to_string(),
+        message: None,
+      }))
+    }
+  };
+
+  // New posts count against the same limit as the ones made through the API
+  let rate_limited = if creates_post(&activity) {
+    rate_limit.post()
+  } else {
+    rate_limit.message()
+  };
+  let ip = get_ip(&request.connection_info());
+  let endpoint = request.path().to_string();
+  let user_id = claims.id;
+
+  let context = OutboxContext {
+    auth,
+    request: &request,
+    client: &client,
+    pool: &db,
+    chat_server: &chat_server,
+  };
+  let username = path.into_inner();
+  let fut = post_to_outbox(activity, username, user_id, &context);
+  rate_limited.wrap(ip, endpoint, Some(user_id), fut).await
+}
+
+async fn post_to_outbox(
+  activity: Value,
+  username: String,
+  user_id: i32,
+  context: &OutboxContext<'_>,
+) -> Result<HttpResponse, LemmyError> {
+  let user = blocking(context.pool, move |conn| {
+    UserAlias::read_user(conn, &username)
+  })
+  .await??;
+  if user.id != user_id {
+    return Ok(HttpResponse::Forbidden().json(ErrorResponse {
+      error: "not_your_outbox".to_string(),
+      message: None,
+    }));
+  }
+  debug!("User {} posted to outbox: {:?}", &user.name, &activity);
+
+  let location = match activity["type"].as_str().unwrap_or_default() {
+    "Create" => create_object(&activity["object"], &activity, context).await?,
+    "Note" | "Page" | "Article" => create_object(&activity, &activity, context).await?,
+    "Like" => {
+      vote(&activity["object"], 1, context).await?;
+      None
+    }
+    "Dislike" => {
+      vote(&activity["object"], -1, context).await?;
+      None
+    }
+    "Follow" => {
+      follow(&activity["object"], true, context).await?;
+      None
+    }
+    // The activities aren't kept, so the one undone has to come with the undo
+    "Undo" => {
+      let undone = &activity["object"];
+      match undone["type"].as_str().unwrap_or_default() {
+        "Like" | "Dislike" => vote(&undone["object"], 0, context).await?,
+        "Follow" => follow(&undone["object"], false, context).await?,
+        _ => return Err(APIError::err("unsupported_activity").into()),
+      }
+      None
+    }
+    _ => return Err(APIError::err("unsupported_activity").into()),
+  };
+
+  let mut response = HttpResponse::Created();
+  if let Some(location) = location {
+    response.header(LOCATION, location);
+  }
+  Ok(response.finish())
+}
+
+/// Whether the activity creates a post, as opposed to a comment or something else
+fn creates_post(activity: &Value) -> bool {
+  let object = match activity["type"].as_str().unwrap_or_default() {
+    "Create" => &activity["object"],
+    "Note" | "Page" | "Article" => activity,
+    _ => return false,
+  };
+  object_id(&object["inReplyTo"]).is_none()
+}
+
+/// Notes replying to something become comments, and the rest a post in the community they're
+/// addressed to. Returns the id of what was created.
+async fn create_object(
+  object: &Value,
+  activity: &Value,
+  context: &OutboxContext<'_>,
+) -> Result<Option<String>, LemmyError> {
+  let content = object_content(object);
+
+  if let Some(in_reply_to) = object_id(&object["inReplyTo"]) {
+    let (post_id, parent_id) =
+      match resolve_object(in_reply_to, context.client, context.pool).await? {
+        OutboxObject::Post(post_id) => (post_id, None),
+        OutboxObject::Comment { id, post_id } => (post_id, Some(id)),
+      };
+    let create_comment = CreateComment {
+      content: content.unwrap_or_default(),
+      parent_id,
+      edit_id: None,
+      post_id,
+      auth: context.auth.to_owned(),
+    };
+    let res: CommentResponse = context.perform(create_comment).await?;
+    return Ok(Some(res.comment.ap_id));
+  }
+
+  let name = match object["name"].as_str() {
+    Some(name) => name.to_owned(),
+    None => return Err(APIError::err("invalid_post_title").into()),
+  };
+  let community_id = addressed_community(&[object, activity], context.client, context.pool).await?;
+  let url = object["url"]
+    .as_str()
+    .or_else(|| object["url"]["href"].as_str())
+    .map(|url| url.to_owned());
+
+  let create_post = CreatePost {
+    name,
+    url,
+    body: content,
+    nsfw: object["sensitive"].as_bool().unwrap_or(false),
+    community_id,
+    flair_id: None,
+    auth: context.auth.to_owned(),
+    scheduled_post_id: None,
+  };
+  let res: PostResponse = context.perform(create_post).await?;
+  Ok(Some(res.post.ap_id))
+}
+
+/// A like is an upvote, a dislike a downvote, and undoing either takes the vote back
+async fn vote(object: &Value, score: i16, context: &OutboxContext<'_>) -> Result<(), LemmyError> {
+  let object_id = object_id(object).ok_or_else(|| APIError::err("couldnt_find_object"))?;
+
+  match resolve_object(object_id, context.client, context.pool).await? {
+    OutboxObject::Post(post_id) => {
+      let like = CreatePostLike {
+        post_id,
+        score,
+        auth: context.auth.to_owned(),
+      };
+      context.perform(like).await?;
+    }
+    OutboxObject::Comment { id, post_id } => {
+      let like = CreateCommentLike {
+        comment_id: id,
+        post_id,
+        score,
+        auth: context.auth.to_owned(),
+      };
+      context.perform(like).await?;
+    }
+  }
+
+  Ok(())
+}
+
+async fn follow(
+  object: &Value,
+  follow: bool,
+  context: &OutboxContext<'_>,
+) -> Result<(), LemmyError> {
+  let community_id = object_id(object).ok_or_else(|| APIError::err("couldnt_find_community"))?;
+  let community =
+    get_or_fetch_and_upsert_remote_community(community_id, context.client, context.pool)
+      .await
+      .map_err(|_| APIError::err("couldnt_find_community"))?;
+
+  let follow_community = FollowCommunity {
+    community_id: community.id,
+    follow,
+    auth: context.auth.to_owned(),
+  };
+  context.perform(follow_community).await?;
+
+  Ok(())
+}
+
+/// Finds the post or comment with the id here, or fetches it from its instance.
+async fn resolve_object(
+  object_id: &str,
+  client: &Client,
+  pool: &DbPool,
+) -> Result<OutboxObject, LemmyError> {
+  let object_id_owned = object_id.to_owned();
+  let known = blocking(pool, move |conn| {
+    if let Ok(post) = Post::read_from_apub_id(conn, &object_id_owned) {
+      return Some(OutboxObject::Post(post.id));
+    }
+    Comment::read_from_apub_id(conn, &object_id_owned)
+      .ok()
+      .map(|comment| OutboxObject::Comment {
+        id: comment.id,
+        post_id: comment.post_id,
+      })
+  })
+  .await?;
+  if let Some(object) = known {
+    return Ok(object);
+  }
+
+  let res = search_by_apub_id(object_id, client, pool)
+    .await
+    .map_err(|_| APIError::err("couldnt_find_object"))?;
+  if let Some(post) = res.posts.first() {
+    Ok(OutboxObject::Post(post.id))
+  } else if let Some(comment) = res.comments.first() {
+    Ok(OutboxObject::Comment {
+      id: comment.id,
+      post_id: comment.post_id,
+    })
+  } else {
+    Err(APIError::err("couldnt_find_object").into())
+  }
+}
+
+/// The first community the object or its activity is addressed to
+async fn addressed_community(
+  addressed: &[&Value],
+  client: &Client,
+  pool: &DbPool,
+) -> Result<i32, LemmyError> {
+  let addresses = addressed
+    .iter()
+    .flat_map(|value| vec![&value["audience"], &value["to"], &value["cc"]])
+    .flat_map(|address| match address {
+      Value::Array(addresses) => addresses.iter().filter_map(|a| a.as_str()).collect(),
+      Value::String(address) => vec![address.as_str()],
+      _ => vec![],
+    })
+    .filter(|address| *address != PUBLIC && !address.ends_with("/followers"));
+
+  for address in addresses {
+    if let Ok(community) = get_or_fetch_and_upsert_remote_community(address, client, pool).await {
+      return Ok(community.id);
+    }
+  }
+
+  Err(APIError::err("no_community_addressed").into())
+}
+
+/// An object can be given as its id, or embedded with it
+fn object_id(object: &Value) -> Option<&str> {
+  object.as_str().or_else(|| object["id"].as_str())
+}
+
+/// Lemmy keeps the markdown, which the clients that have it send as the source
+pub fn object_content(object: &Value) -> Option<String> {
+  let source = &object["source"];
+  if source["mediaType"].as_str() == Some("text/markdown") {
+    if let Some(content) = source["content"].as_str() {
+      return Some(content.to_owned());
+    }
+  }
+  object["content"].as_str().map(|content| content.to_owned())
+}
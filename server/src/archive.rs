@@ -0,0 +1,47 @@
+//! Locks and archives the posts that got older than the archive age of their community, or of the
+//! site when the community doesn't set one, so ancient threads stop getting replies.
+
+use crate::{apub::ApubObjectType, blocking, DbPool, LemmyError};
+use actix_web::client::Client;
+use lemmy_db::{community::Community, naive_now, post::Post, site::Site, user::User_, Crud};
+use tracing::error;
+
+pub async fn archive_old_posts(pool: &DbPool, client: &Client) -> Result<(), LemmyError> {
+  // There's nothing to archive before the site is set up
+  let site = match blocking(pool, move |conn| Site::read(conn, 1)).await? {
+    Ok(site) => site,
+    Err(_e) => return Ok(()),
+  };
+
+  // Remote communities archive their own posts
+  let communities = blocking(pool, move |conn| Community::list_local(conn)).await??;
+
+  for community in communities {
+    let archive_after_days = if community.archive_after_days > 0 {
+      community.archive_after_days
+    } else {
+      site.archive_after_days
+    };
+    if archive_after_days == 0 {
+      continue;
+    }
+
+    let community_id = community.id;
+    let before = naive_now() - chrono::Duration::days(archive_after_days.into());
+    let archived_posts = blocking(pool, move |conn| {
+      Post::archive_published_before(conn, community_id, before)
+    })
+    .await??;
+
+    // The lock goes out as an update from the creator, so only local posts can be sent
+    for post in archived_posts.iter().filter(|p| p.local) {
+      let creator_id = post.creator_id;
+      let creator = blocking(pool, move |conn| User_::read(conn, creator_id)).await??;
+      if let Err(e) = post.send_update(&creator, client, pool).await {
+        error!("Couldn't federate the archival of post {}: {}", post.id, e);
+      }
+    }
+  }
+
+  Ok(())
+}
@@ -0,0 +1,290 @@
+//! Checks the name and password users log in with. Each backend is asked in turn: the LDAP
+//! directory first when one is set up, then the passwords of the accounts here. The users of the
+//! directory get an account here the first time they log in, which they log in to with their
+//! password there from then on.
+
+use crate::{
+  api::{claims::Claims, APIError},
+  blocking,
+  webhook::{queue_webhooks, NewRegistration},
+  DbPool,
+  LemmyError,
+};
+use bcrypt::verify;
+use ldap3::{ldap_escape, Ldap, LdapConnAsync, Scope, SearchEntry};
+use lemmy_db::{
+  community::{Community, CommunityFollower, CommunityFollowerForm},
+  user::{UserForm, User_},
+  webhook::WebhookEvent,
+  Crud,
+  Followable,
+  ListingType,
+  SortType,
+};
+use lemmy_utils::{
+  generate_actor_keypair,
+  generate_random_string,
+  is_valid_username,
+  make_apub_endpoint,
+  settings::{LdapConfig, Settings},
+  EndpointType,
+};
+use tracing::error;
+
+/// The community everyone is subscribed to when they sign up
+const MAIN_COMMUNITY_ID: i32 = 2;
+
+#[async_trait::async_trait(?Send)]
+pub trait AuthBackend {
+  /// The user the name and password log in as. None when the backend doesn't know the name, so the
+  /// next one is asked.
+  async fn authenticate(
+    &self,
+    pool: &DbPool,
+    username_or_email: &str,
+    password: &str,
+  ) -> Result<Option<User_>, LemmyError>;
+}
+
+/// The passwords of the accounts here, but not the ones of the directory
+pub struct PasswordAuth;
+
+#[async_trait::async_trait(?Send)]
+impl AuthBackend for PasswordAuth {
+  async fn authenticate(
+    &self,
+    pool: &DbPool,
+    username_or_email: &str,
+    password: &str,
+  ) -> Result<Option<User_>, LemmyError> {
+    let username_or_email = username_or_email.to_owned();
+    let user = match blocking(pool, move |conn| {
+      Claims::find_by_email_or_username(conn, &username_or_email)
+    })
+    .await?
+    {
+      Ok(user) => user,
+      Err(_e) => return Ok(None),
+    };
+
+    // The users of the directory log in with its password, even when it can't be reached
+    if user.directory_account {
+      return Err(APIError::err("directory_account_password").into());
+    }
+
+    let valid: bool = verify(password, &user.password_encrypted).unwrap_or(false);
+    if !valid {
+      return Err(APIError::err("password_incorrect").into());
+    }
+
+    Ok(Some(user))
+  }
+}
+
+/// An LDAP directory. The user is searched for, and logs in when the directory takes their
+/// password. When it can't be reached, the next backend is asked, so the accounts here can still
+/// log in.
+pub struct LdapAuth {
+  config: LdapConfig,
+}
+
+#[async_trait::async_trait(?Send)]
+impl AuthBackend for LdapAuth {
+  async fn authenticate(
+    &self,
+    pool: &DbPool,
+    username_or_email: &str,
+    password: &str,
+  ) -> Result<Option<User_>, LemmyError> {
+    let (conn, mut ldap) = match LdapConnAsync::new(&self.config.url).await {
+      Ok(connection) => connection,
+      Err(e) => {
+        error!("Couldn't connect to the LDAP directory: {}", e);
+        return Ok(None);
+      }
+    };
+    ldap3::drive!(conn);
+
+    let entry = match self.find_user(&mut ldap, username_or_email).await {
+      Ok(Some(entry)) => entry,
+      Ok(None) => return Ok(None),
+      Err(e) => {
+        error!("Couldn't search the LDAP directory: {}", e);
+        return Ok(None);
+      }
+    };
+
+    // The directory takes a bind without a password as an anonymous one
+    if password.is_empty() {
+      return Err(APIError::err("password_incorrect").into());
+    }
+    let bind = ldap.simple_bind(&entry.dn, password).await?;
+    ldap.unbind().await?;
+    if bind.rc != 0 {
+      return Err(APIError::err("password_incorrect").into());
+    }
+
+    let name = first_attribute(&entry, &self.config.username_attribute)
+      .unwrap_or_else(|| username_or_email.to_owned());
+    let email = first_attribute(&entry, &self.config.email_attribute);
+    directory_user(pool, &name, email).await.map(Some)
+  }
+}
+
+impl LdapAuth {
+  async fn find_user(
+    &self,
+    ldap: &mut Ldap,
+    username_or_email: &str,
+  ) -> Result<Option<SearchEntry>, LemmyError> {
+    if let (Some(bind_dn), Some(bind_password)) = (&self.config.bind_dn, &self.config.bind_password)
+    {
+      ldap.simple_bind(bind_dn, bind_password).await?.success()?;
+    }
+
+    let filter = ldap_user_filter(&self.config.user_filter, username_or_email);
+    let attributes = vec![
+      self.config.username_attribute.as_str(),
+      self.config.email_attribute.as_str(),
+    ];
+    let (entries, _res) = ldap
+      .search(
+        &self.config.user_base_dn,
+        Scope::Subtree,
+        &filter,
+        attributes,
+      )
+      .await?
+      .success()?;
+
+    // A name that matches more than one entry can't tell who's logging in
+    if entries.len() != 1 {
+      return Ok(None);
+    }
+    Ok(entries.into_iter().next().map(SearchEntry::construct))
+  }
+}
+
+/// The backends in the order they're asked
+pub fn auth_backends() -> Vec<Box<dyn AuthBackend>> {
+  let mut backends: Vec<Box<dyn AuthBackend>> = Vec::new();
+  if let Some(config) = Settings::get().ldap {
+    backends.push(Box::new(LdapAuth { config }));
+  }
+  backends.push(Box::new(PasswordAuth));
+  backends
+}
+
+/// The user the name and password log in as
+pub async fn authenticate(
+  pool: &DbPool,
+  username_or_email: &str,
+  password: &str,
+) -> Result<User_, LemmyError> {
+  for backend in auth_backends() {
+    if let Some(user) = backend
+      .authenticate(pool, username_or_email, password)
+      .await?
+    {
+      return Ok(user);
+    }
+  }
+
+  Err(APIError::err("couldnt_find_that_username_or_email").into())
+}
+
+/// The search filter for what the user logs in with, escaped so it can't change the filter
+pub fn ldap_user_filter(user_filter: &str, username_or_email: &str) -> String {
+  user_filter.replace("{username}", &ldap_escape(username_or_email))
+}
+
+fn first_attribute(entry: &SearchEntry, attribute: &str) -> Option<String> {
+  entry
+    .attrs
+    .get(attribute)
+    .and_then(|values| values.first())
+    .map(|value| value.to_owned())
+}
+
+/// The account here of a user of the directory, made the first time they log in. It gets a random
+/// password, as they log in with the one of the directory. An account that was made here with the
+/// same name isn't theirs, so they can't log in until it's renamed.
+async fn directory_user(
+  pool: &DbPool,
+  name: &str,
+  email: Option<String>,
+) -> Result<User_, LemmyError> {
+  if !is_valid_username(name) {
+    return Err(APIError::err("invalid_username").into());
+  }
+
+  let actor_id = make_apub_endpoint(EndpointType::User, name).to_string();
+  let read_actor_id = actor_id.to_owned();
+  let read_user = move |conn: &'_ _| User_::read_from_actor_id(conn, &read_actor_id);
+  if let Ok(user) = blocking(pool, read_user).await? {
+    if !user.directory_account {
+      return Err(APIError::err("username_taken_outside_directory").into());
+    }
+    return Ok(user);
+  }
+
+  let user_keypair = generate_actor_keypair()?;
+  let user_form = UserForm {
+    name: name.to_owned(),
+    email,
+    matrix_user_id: None,
+    avatar: None,
+    password_encrypted: generate_random_string(),
+    preferred_username: None,
+    updated: None,
+    admin: false,
+    banned: false,
+    show_nsfw: false,
+    theme: "darkly".into(),
+    default_sort_type: SortType::Hot as i16,
+    default_listing_type: ListingType::Subscribed as i16,
+    lang: "browser".into(),
+    show_avatars: true,
+    send_notifications_to_email: false,
+    actor_id,
+    bio: None,
+    local: true,
+    private_key: Some(user_keypair.private_key),
+    public_key: Some(user_keypair.public_key),
+    last_refreshed_at: None,
+    banner: None,
+    hide_read_posts: false,
+    bot_account: false,
+    hide_bot_accounts: false,
+    hide_presence: false,
+    display_name: None,
+  };
+
+  let register = move |conn: &'_ _| User_::register_directory_account(conn, &user_form);
+  let inserted_user = match blocking(pool, register).await? {
+    Ok(user) => user,
+    Err(_e) => return Err(APIError::err("user_already_exists").into()),
+  };
+
+  // Subscribed to the main community like on sign up, when there is one
+  if blocking(pool, |conn| Community::read(conn, MAIN_COMMUNITY_ID))
+    .await?
+    .is_ok()
+  {
+    let community_follower_form = CommunityFollowerForm {
+      community_id: MAIN_COMMUNITY_ID,
+      user_id: inserted_user.id,
+    };
+    let follow = move |conn: &'_ _| CommunityFollower::follow(conn, &community_follower_form);
+    if let Err(e) = blocking(pool, follow).await? {
+      error!("Couldn't subscribe {} to the main community: {}", name, e);
+    }
+  }
+
+  let registration = NewRegistration::new(&inserted_user);
+  if let Err(e) = queue_webhooks(pool, WebhookEvent::NewRegistration, None, &registration).await {
+    error!("Couldn't queue the registration webhooks: {}", e);
+  }
+
+  Ok(inserted_user)
+}
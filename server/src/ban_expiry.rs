@@ -0,0 +1,15 @@
+//! Lifts the site and community bans whose expiry has passed. The permission checks already ignore
+//! expired bans, this makes the ban flags in the views and ban lists catch up.
+
+use crate::{blocking, cache::invalidate_site, DbPool, LemmyError};
+use lemmy_db::{community::CommunityUserBan, user::User_};
+
+pub async fn lift_expired_bans(pool: &DbPool) -> Result<(), LemmyError> {
+  let lifted = blocking(pool, move |conn| User_::lift_expired_bans(conn)).await??;
+  // The site lists the banned users
+  if !lifted.is_empty() {
+    invalidate_site();
+  }
+  blocking(pool, move |conn| CommunityUserBan::lift_expired(conn)).await??;
+  Ok(())
+}
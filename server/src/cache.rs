@@ -0,0 +1,149 @@
+//! A short lived cache of the post listings and the site that logged out visitors get, which are
+//! the same for all of them. Entries expire on their own after a few seconds, and the writes that
+//! change what's shown drop them right away. Logged in users always get fresh results, since their
+//! votes, subscriptions and settings are part of them.
+
+use crate::api::site::GetSiteResponse;
+use lemmy_db::post_view::PostView;
+use lemmy_utils::settings::Settings;
+use std::{
+  collections::HashMap,
+  hash::Hash,
+  sync::RwLock,
+  time::{Duration, Instant},
+};
+
+/// What a logged out post listing depends on.
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub struct PostListingKey {
+  pub type_: String,
+  pub sort: String,
+  pub page: Option<i64>,
+  pub limit: Option<i64>,
+  pub community_id: Option<i32>,
+  pub community_name: Option<String>,
+  pub flair_id: Option<i32>,
+  pub cursor: Option<String>,
+}
+
+/// Values that are dropped once they're older than the ttl. When it's full, the entry that
+/// expires soonest makes room.
+pub struct TtlCache<K, V> {
+  entries: HashMap<K, (Instant, V)>,
+  capacity: usize,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> TtlCache<K, V> {
+  pub fn new(capacity: usize) -> Self {
+    TtlCache {
+      entries: HashMap::new(),
+      capacity,
+    }
+  }
+
+  pub fn get(&self, key: &K, ttl: Duration) -> Option<V> {
+    match self.entries.get(key) {
+      Some((inserted, value)) if inserted.elapsed() < ttl => Some(value.to_owned()),
+      _ => None,
+    }
+  }
+
+  pub fn insert(&mut self, key: K, value: V, ttl: Duration) {
+    if self.capacity == 0 {
+      return;
+    }
+
+    if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+      self
+        .entries
+        .retain(|_, (inserted, _)| inserted.elapsed() < ttl);
+      if self.entries.len() >= self.capacity {
+        let oldest = self
+          .entries
+          .iter()
+          .min_by_key(|(_, (inserted, _))| *inserted)
+          .map(|(k, _)| k.to_owned());
+        if let Some(oldest) = oldest {
+          self.entries.remove(&oldest);
+        }
+      }
+    }
+
+    self.entries.insert(key, (Instant::now(), value));
+  }
+
+  pub fn retain<F: Fn(&K) -> bool>(&mut self, keep: F) {
+    self.entries.retain(|k, _| keep(k));
+  }
+
+  pub fn clear(&mut self) {
+    self.entries.clear();
+  }
+}
+
+lazy_static! {
+  static ref POST_LISTINGS: RwLock<TtlCache<PostListingKey, Vec<PostView>>> =
+    RwLock::new(TtlCache::new(Settings::get().cache.max_post_listings));
+  static ref SITE: RwLock<TtlCache<(), GetSiteResponse>> = RwLock::new(TtlCache::new(1));
+}
+
+fn post_listing_ttl() -> Option<Duration> {
+  let cache = Settings::get().cache;
+  if cache.enabled && cache.post_listing_ttl_seconds > 0 {
+    Some(Duration::from_secs(cache.post_listing_ttl_seconds))
+  } else {
+    None
+  }
+}
+
+fn site_ttl() -> Option<Duration> {
+  let cache = Settings::get().cache;
+  if cache.enabled && cache.site_ttl_seconds > 0 {
+    Some(Duration::from_secs(cache.site_ttl_seconds))
+  } else {
+    None
+  }
+}
+
+// None of these hold the locks across await points, so they're fine to call from the handlers.
+
+pub fn get_post_listing(key: &PostListingKey) -> Option<Vec<PostView>> {
+  let ttl = post_listing_ttl()?;
+  POST_LISTINGS.read().ok()?.get(key, ttl)
+}
+
+pub fn set_post_listing(key: PostListingKey, posts: Vec<PostView>) {
+  if let (Some(ttl), Ok(mut listings)) = (post_listing_ttl(), POST_LISTINGS.write()) {
+    listings.insert(key, posts, ttl);
+  }
+}
+
+/// Drops the listings a post of the community could be in. Those by community name can't be told
+/// apart, so they all go. Without a community, every listing goes.
+pub fn invalidate_post_listings(community_id: Option<i32>) {
+  if let Ok(mut listings) = POST_LISTINGS.write() {
+    match community_id {
+      Some(community_id) => listings.retain(|key| {
+        key.community_name.is_none() && key.community_id.map_or(false, |c| c != community_id)
+      }),
+      None => listings.clear(),
+    }
+  }
+}
+
+pub fn get_site() -> Option<GetSiteResponse> {
+  let ttl = site_ttl()?;
+  SITE.read().ok()?.get(&(), ttl)
+}
+
+pub fn set_site(site: GetSiteResponse) {
+  if let (Some(ttl), Ok(mut cached)) = (site_ttl(), SITE.write()) {
+    cached.insert((), site, ttl);
+  }
+}
+
+pub fn invalidate_site() {
+  if let Ok(mut cached) = SITE.write() {
+    cached.clear();
+  }
+}
@@ -1,5 +1,5 @@
 // This is for db migrations that require code
-use crate::LemmyError;
+use crate::{embed::sanitize_embed_html, LemmyError};
 use diesel::*;
 use lemmy_db::{
   comment::Comment,
@@ -11,7 +11,7 @@ use lemmy_db::{
   Crud,
 };
 use lemmy_utils::{generate_actor_keypair, make_apub_endpoint, EndpointType};
-use log::info;
+use tracing::info;
 
 pub fn run_advanced_migrations(conn: &PgConnection) -> Result<(), LemmyError> {
   user_updates_2020_04_02(&conn)?;
@@ -19,6 +19,7 @@ pub fn run_advanced_migrations(conn: &PgConnection) -> Result<(), LemmyError> {
   post_updates_2020_04_03(&conn)?;
   comment_updates_2020_04_03(&conn)?;
   private_message_updates_2020_05_05(&conn)?;
+  post_updates_2020_08_23(&conn)?;
 
   Ok(())
 }
@@ -62,6 +63,12 @@ fn user_updates_2020_04_02(conn: &PgConnection) -> Result<(), LemmyError> {
       private_key: Some(keypair.private_key),
       public_key: Some(keypair.public_key),
       last_refreshed_at: Some(naive_now()),
+      banner: cuser.banner.to_owned(),
+      hide_read_posts: cuser.hide_read_posts,
+      bot_account: cuser.bot_account,
+      hide_bot_accounts: cuser.hide_bot_accounts,
+      hide_presence: cuser.hide_presence,
+      display_name: cuser.display_name.to_owned(),
     };
 
     User_::update(&conn, cuser.id, &form)?;
@@ -106,6 +113,15 @@ fn community_updates_2020_04_02(conn: &PgConnection) -> Result<(), LemmyError> {
       public_key: Some(keypair.public_key),
       last_refreshed_at: Some(naive_now()),
       published: None,
+      require_flair: None,
+      icon: None,
+      banner: None,
+      archive_after_days: None,
+      post_approval: None,
+      read_only: None,
+      allowed_post_types: None,
+      default_sort_type: None,
+      language: None,
     };
 
     Community::update(&conn, ccommunity.id, &form)?;
@@ -188,3 +204,30 @@ fn private_message_updates_2020_05_05(conn: &PgConnection) -> Result<(), LemmyEr
 
   Ok(())
 }
+
+fn post_updates_2020_08_23(conn: &PgConnection) -> Result<(), LemmyError> {
+  use lemmy_db::schema::post::dsl::*;
+
+  info!("Running post_updates_2020_08_23");
+
+  // Clean the embed HTML that was stored before it got sanitized
+  let unsanitized_posts = post
+    .filter(embed_html.is_not_null())
+    .filter(embed_type.is_null())
+    .load::<Post>(conn)?;
+
+  let mut sanitized = 0;
+  for cpost in &unsanitized_posts {
+    let html = cpost.embed_html.as_deref().and_then(sanitize_embed_html);
+    if html != cpost.embed_html {
+      diesel::update(post.find(cpost.id))
+        .set(embed_html.eq(html))
+        .execute(conn)?;
+      sanitized += 1;
+    }
+  }
+
+  info!("{} post rows updated.", sanitized);
+
+  Ok(())
+}
@@ -0,0 +1,532 @@
+//! Fills a new community with the posts and comments of a subreddit or a kbin magazine, in the
+//! background. Everything is posted by an archive bot, with the time and the score it had there,
+//! and the counts of the import show how far along it is. An import that got cut off isn't picked
+//! up again, as it would post what's already there twice.
+
+use crate::{
+  api::community_import::CommunityImportResponse,
+  blocking,
+  websocket::{server::SendUserRoomMessage, UserOperation, WebsocketInfo},
+  DbPool,
+  LemmyError,
+};
+use diesel::PgConnection;
+use lemmy_db::{
+  comment::{Comment, CommentForm},
+  community_import::{CommunityImport, ImportSource},
+  post::{Post, PostForm},
+  user::{UserForm, User_},
+  Crud,
+  ListingType,
+  SortType,
+};
+use lemmy_utils::{
+  generate_actor_keypair,
+  generate_random_string,
+  is_valid_post_title,
+  make_apub_endpoint,
+  naive_from_unix,
+  EndpointType,
+};
+use serde_json::Value;
+use std::{
+  collections::{HashMap, HashSet},
+  sync::Arc,
+};
+
+/// How many posts or comments go in before the progress is written down
+const IMPORT_BATCH_SIZE: usize = 100;
+
+/// The longest title a post can have
+const MAX_TITLE_LENGTH: usize = 200;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportedPost {
+  pub source_id: String,
+  pub name: String,
+  pub url: Option<String>,
+  pub body: Option<String>,
+  pub nsfw: bool,
+  pub published: chrono::NaiveDateTime,
+  pub score: i64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportedComment {
+  pub source_id: String,
+  pub post_source_id: String,
+  pub parent_source_id: Option<String>,
+  pub content: String,
+  pub deleted: bool,
+  pub published: chrono::NaiveDateTime,
+  pub score: i64,
+}
+
+/// What an export has to import. The comments come after the ones they reply to.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportedCommunity {
+  pub posts: Vec<ImportedPost>,
+  pub comments: Vec<ImportedComment>,
+}
+
+impl ImportedCommunity {
+  fn new(posts: Vec<ImportedPost>, comments: Vec<ImportedComment>) -> Self {
+    let post_ids: HashSet<String> = posts.iter().map(|p| p.source_id.to_owned()).collect();
+    let comment_ids: HashSet<String> = comments.iter().map(|c| c.source_id.to_owned()).collect();
+
+    // Replies to comments that aren't in the export go on the post instead
+    let mut pending: Vec<ImportedComment> = comments
+      .into_iter()
+      .filter(|c| post_ids.contains(&c.post_source_id))
+      .map(|mut c| {
+        if let Some(parent) = &c.parent_source_id {
+          if !comment_ids.contains(parent) {
+            c.parent_source_id = None;
+          }
+        }
+        c
+      })
+      .collect();
+    pending.sort_by_key(|c| c.published);
+
+    // A reply can be older than its parent when the clocks were off, so the whole chain is waited
+    // for. Cycles never get their turn.
+    let mut ordered: Vec<ImportedComment> = Vec::new();
+    let mut placed: HashSet<String> = HashSet::new();
+    loop {
+      let (ready, waiting): (Vec<ImportedComment>, Vec<ImportedComment>) =
+        pending.into_iter().partition(|c| {
+          c.parent_source_id
+            .as_ref()
+            .map_or(true, |parent| placed.contains(parent))
+        });
+      if ready.is_empty() {
+        break;
+      }
+      placed.extend(ready.iter().map(|c| c.source_id.to_owned()));
+      ordered.extend(ready);
+      pending = waiting;
+    }
+
+    ImportedCommunity {
+      posts,
+      comments: ordered,
+    }
+  }
+}
+
+pub fn parse_export(source: ImportSource, data: &str) -> Result<ImportedCommunity, LemmyError> {
+  match source {
+    ImportSource::Reddit => parse_reddit_export(data),
+    ImportSource::Kbin => parse_kbin_export(data),
+  }
+}
+
+/// The submissions and comments of a subreddit, as a JSON array or one JSON object per line like
+/// in the Pushshift dumps. They can be wrapped in a `{"kind": "t3", "data": {..}}` like the API
+/// answers.
+pub fn parse_reddit_export(data: &str) -> Result<ImportedCommunity, LemmyError> {
+  let items: Vec<Value> = if data.trim_start().starts_with('[') {
+    serde_json::from_str(data)?
+  } else {
+    data
+      .lines()
+      .filter(|line| !line.trim().is_empty())
+      .map(serde_json::from_str)
+      .collect::<Result<Vec<Value>, _>>()?
+  };
+
+  let mut posts = Vec::new();
+  let mut comments = Vec::new();
+  for item in &items {
+    let item = if item["data"].is_object() {
+      &item["data"]
+    } else {
+      item
+    };
+    let source_id = match value_id(&item["id"]) {
+      Some(source_id) => source_id,
+      None => continue,
+    };
+    let published = match &item["created_utc"] {
+      Value::Number(n) => n.as_f64().map(|t| naive_from_unix(t as i64)),
+      Value::String(s) => s.parse::<f64>().ok().map(|t| naive_from_unix(t as i64)),
+      _ => None,
+    };
+    let published = match published {
+      Some(published) => published,
+      None => continue,
+    };
+    let score = item["score"].as_i64().unwrap_or(0);
+
+    if let Some(title) = item["title"].as_str() {
+      let url = if item["is_self"].as_bool().unwrap_or(false) {
+        None
+      } else {
+        item["url"].as_str().map(|url| url.to_owned())
+      };
+      posts.push(ImportedPost {
+        source_id,
+        name: title.to_owned(),
+        url,
+        body: item["selftext"]
+          .as_str()
+          .filter(|text| !text.is_empty() && !is_reddit_deleted(text))
+          .map(|text| text.to_owned()),
+        nsfw: item["over_18"].as_bool().unwrap_or(false),
+        published,
+        score,
+      });
+    } else if let Some(link_id) = item["link_id"].as_str() {
+      let body = item["body"].as_str().unwrap_or_default();
+      let parent_source_id = item["parent_id"]
+        .as_str()
+        .filter(|parent| parent.starts_with("t1_"))
+        .map(|parent| parent[3..].to_owned());
+      comments.push(ImportedComment {
+        source_id,
+        post_source_id: link_id.trim_start_matches("t3_").to_owned(),
+        parent_source_id,
+        content: body.to_owned(),
+        deleted: is_reddit_deleted(body),
+        published,
+        score,
+      });
+    }
+  }
+
+  Ok(ImportedCommunity::new(posts, comments))
+}
+
+/// The entries of a kbin magazine like its API lists them, as an array or the `items` of a page.
+/// Each has its `comments`, whose replies are either in their `children` or point to the comment
+/// they reply to with `parentId`.
+pub fn parse_kbin_export(data: &str) -> Result<ImportedCommunity, LemmyError> {
+  let export: Value = serde_json::from_str(data)?;
+  let entries = match &export {
+    Value::Array(entries) => entries.to_owned(),
+    _ => export["items"].as_array().cloned().unwrap_or_default(),
+  };
+
+  let mut posts = Vec::new();
+  let mut comments = Vec::new();
+  for entry in &entries {
+    let source_id = match value_id(&entry["entryId"]) {
+      Some(source_id) => source_id,
+      None => continue,
+    };
+    let (title, published) = match (entry["title"].as_str(), kbin_time(&entry["createdAt"])) {
+      (Some(title), Some(published)) => (title, published),
+      _ => continue,
+    };
+    posts.push(ImportedPost {
+      source_id: source_id.to_owned(),
+      name: title.to_owned(),
+      url: entry["url"].as_str().map(|url| url.to_owned()),
+      body: entry["body"]
+        .as_str()
+        .filter(|body| !body.is_empty())
+        .map(|body| body.to_owned()),
+      nsfw: entry["isAdult"].as_bool().unwrap_or(false),
+      published,
+      score: kbin_score(entry),
+    });
+
+    if let Some(entry_comments) = entry["comments"].as_array() {
+      for comment in entry_comments {
+        push_kbin_comment(comment, &source_id, None, &mut comments);
+      }
+    }
+  }
+
+  Ok(ImportedCommunity::new(posts, comments))
+}
+
+fn push_kbin_comment(
+  comment: &Value,
+  post_source_id: &str,
+  parent_source_id: Option<String>,
+  comments: &mut Vec<ImportedComment>,
+) {
+  let (source_id, published) = match (
+    value_id(&comment["commentId"]),
+    kbin_time(&comment["createdAt"]),
+  ) {
+    (Some(source_id), Some(published)) => (source_id, published),
+    _ => return,
+  };
+  let visibility = comment["visibility"].as_str().unwrap_or("visible");
+  comments.push(ImportedComment {
+    source_id: source_id.to_owned(),
+    post_source_id: post_source_id.to_owned(),
+    parent_source_id: value_id(&comment["parentId"]).or(parent_source_id),
+    content: comment["body"].as_str().unwrap_or_default().to_owned(),
+    deleted: visibility != "visible",
+    published,
+    score: kbin_score(comment),
+  });
+
+  if let Some(children) = comment["children"].as_array() {
+    for child in children {
+      push_kbin_comment(child, post_source_id, Some(source_id.to_owned()), comments);
+    }
+  }
+}
+
+/// Ids come as numbers from kbin, and as strings from reddit
+fn value_id(value: &Value) -> Option<String> {
+  match value {
+    Value::String(id) if !id.is_empty() => Some(id.to_owned()),
+    Value::Number(id) => Some(id.to_string()),
+    _ => None,
+  }
+}
+
+fn kbin_time(value: &Value) -> Option<chrono::NaiveDateTime> {
+  value
+    .as_str()
+    .and_then(|time| chrono::DateTime::parse_from_rfc3339(time).ok())
+    .map(|time| time.naive_utc())
+}
+
+/// Boosts count as upvotes, like they do for the ranking there
+fn kbin_score(item: &Value) -> i64 {
+  item["uv"].as_i64().unwrap_or(0) + item["favourites"].as_i64().unwrap_or(0)
+    - item["dv"].as_i64().unwrap_or(0)
+}
+
+fn is_reddit_deleted(text: &str) -> bool {
+  text == "[deleted]" || text == "[removed]"
+}
+
+/// Runs the import, and lets the admin that started it know when it's done. A failed import keeps
+/// what got in, and the error.
+pub async fn run_community_import(
+  pool: &DbPool,
+  import_id: i32,
+  websocket_info: Option<WebsocketInfo>,
+) -> Result<(), LemmyError> {
+  let import = blocking(pool, move |conn| CommunityImport::read(conn, import_id)).await??;
+  if import.completed.is_some() {
+    return Ok(());
+  }
+
+  let failure = if import.posts_total > 0 || import.comments_total > 0 {
+    Some("import_interrupted".to_string())
+  } else {
+    import_content(pool, &import)
+      .await
+      .err()
+      .map(|e| e.to_string())
+  };
+  let import = blocking(pool, move |conn| {
+    CommunityImport::complete(conn, import_id, failure)
+  })
+  .await??;
+
+  if let Some(ws) = websocket_info {
+    ws.chatserver.do_send(SendUserRoomMessage {
+      op: UserOperation::ImportCommunity,
+      recipient_id: import.creator_id,
+      response: CommunityImportResponse { import },
+      my_id: None,
+    });
+  }
+
+  Ok(())
+}
+
+async fn import_content(pool: &DbPool, import: &CommunityImport) -> Result<(), LemmyError> {
+  let source = match import.source.as_str() {
+    "kbin" => ImportSource::Kbin,
+    _ => ImportSource::Reddit,
+  };
+  let content = parse_export(source, import.data.as_deref().unwrap_or_default())?;
+  let archive_user = archive_user(pool, source).await?;
+
+  let import_id = import.id;
+  let posts_total = content.posts.len() as i32;
+  let comments_total = content.comments.len() as i32;
+  blocking(pool, move |conn| {
+    CommunityImport::record_totals(conn, import_id, posts_total, comments_total)
+  })
+  .await??;
+
+  let target = ImportTarget {
+    import_id,
+    community_id: import.community_id,
+    creator_id: archive_user.id,
+  };
+  let mut post_ids: HashMap<String, i32> = HashMap::new();
+  for batch in content.posts.chunks(IMPORT_BATCH_SIZE) {
+    let batch = batch.to_vec();
+    let posts_imported = post_ids.len() as i32;
+    let imported = blocking(pool, move |conn| {
+      insert_posts(conn, target, batch, posts_imported)
+    })
+    .await??;
+    post_ids.extend(imported);
+  }
+
+  // The ids of the comments so far go along with each batch, for the replies to find them
+  let post_ids = Arc::new(post_ids);
+  let mut comment_ids: HashMap<String, i32> = HashMap::new();
+  for batch in content.comments.chunks(IMPORT_BATCH_SIZE) {
+    let batch = batch.to_vec();
+    let batch_post_ids = post_ids.clone();
+    comment_ids = blocking(pool, move |conn| {
+      insert_comments(conn, target, batch, &batch_post_ids, comment_ids)
+    })
+    .await??;
+  }
+
+  Ok(())
+}
+
+/// Where the posts and comments go, and who they're by
+#[derive(Clone, Copy)]
+struct ImportTarget {
+  import_id: i32,
+  community_id: i32,
+  creator_id: i32,
+}
+
+/// The ids the posts got here, by their ids there. The ones without a title are left out.
+fn insert_posts(
+  conn: &PgConnection,
+  target: ImportTarget,
+  posts: Vec<ImportedPost>,
+  posts_imported: i32,
+) -> Result<Vec<(String, i32)>, LemmyError> {
+  let mut imported = Vec::new();
+  for post in posts {
+    let name: String = post.name.trim().chars().take(MAX_TITLE_LENGTH).collect();
+    if !is_valid_post_title(&name) {
+      continue;
+    }
+    let post_form = PostForm {
+      name,
+      url: post.url,
+      body: post.body,
+      creator_id: target.creator_id,
+      community_id: target.community_id,
+      removed: None,
+      locked: None,
+      published: Some(post.published),
+      updated: None,
+      deleted: None,
+      nsfw: post.nsfw,
+      stickied: None,
+      embed_title: None,
+      embed_description: None,
+      embed_html: None,
+      embed_type: None,
+      url_media_type: None,
+      thumbnail_url: None,
+      ap_id: "http://fake.com".into(),
+      local: true,
+      pending: None,
+    };
+    let inserted_post = Post::create(conn, &post_form)?;
+    CommunityImport::set_post_score(conn, inserted_post.id, target.creator_id, post.score)?;
+    let apub_id = make_apub_endpoint(EndpointType::Post, &inserted_post.id.to_string()).to_string();
+    Post::update_ap_id(conn, inserted_post.id, apub_id)?;
+    imported.push((post.source_id, inserted_post.id));
+  }
+
+  let posts_imported = posts_imported + imported.len() as i32;
+  CommunityImport::record_progress(conn, target.import_id, posts_imported, 0)?;
+  Ok(imported)
+}
+
+/// Same as `insert_posts`, for the comments. The ones whose post was left out are too.
+fn insert_comments(
+  conn: &PgConnection,
+  target: ImportTarget,
+  comments: Vec<ImportedComment>,
+  post_ids: &HashMap<String, i32>,
+  mut comment_ids: HashMap<String, i32>,
+) -> Result<HashMap<String, i32>, LemmyError> {
+  for comment in comments {
+    let post_id = match post_ids.get(&comment.post_source_id) {
+      Some(post_id) => *post_id,
+      None => continue,
+    };
+    let parent_id = comment
+      .parent_source_id
+      .as_ref()
+      .and_then(|parent| comment_ids.get(parent))
+      .copied();
+    let comment_form = CommentForm {
+      creator_id: target.creator_id,
+      post_id,
+      parent_id,
+      content: comment.content,
+      removed: None,
+      read: Some(true),
+      published: Some(comment.published),
+      updated: None,
+      deleted: if comment.deleted { Some(true) } else { None },
+      ap_id: "http://fake.com".into(),
+      local: true,
+    };
+    let inserted_comment = Comment::create(conn, &comment_form)?;
+    let comment_id = inserted_comment.id;
+    CommunityImport::set_comment_score(conn, comment_id, target.creator_id, comment.score)?;
+    let apub_id = make_apub_endpoint(EndpointType::Comment, &comment_id.to_string()).to_string();
+    Comment::update_ap_id(conn, comment_id, apub_id)?;
+    comment_ids.insert(comment.source_id, comment_id);
+  }
+
+  let posts_imported = post_ids.len() as i32;
+  let comments_imported = comment_ids.len() as i32;
+  CommunityImport::record_progress(conn, target.import_id, posts_imported, comments_imported)?;
+  Ok(comment_ids)
+}
+
+/// The bot the content of a source is posted by, made for the first import from there.
+async fn archive_user(pool: &DbPool, source: ImportSource) -> Result<User_, LemmyError> {
+  let name = format!("{}_archive", source.name());
+  let read_name = name.to_owned();
+  if let Ok(user) = blocking(pool, move |conn| User_::read_from_name(conn, &read_name)).await? {
+    return Ok(user);
+  }
+
+  let user_keypair = generate_actor_keypair()?;
+  let user_form = UserForm {
+    name: name.to_owned(),
+    email: None,
+    matrix_user_id: None,
+    avatar: None,
+    password_encrypted: generate_random_string(),
+    preferred_username: None,
+    updated: None,
+    admin: false,
+    banned: false,
+    show_nsfw: false,
+    theme: "darkly".into(),
+    default_sort_type: SortType::Hot as i16,
+    default_listing_type: ListingType::Subscribed as i16,
+    lang: "browser".into(),
+    show_avatars: true,
+    send_notifications_to_email: false,
+    actor_id: make_apub_endpoint(EndpointType::User, &name).to_string(),
+    bio: Some(format!(
+      "Posts the content imported from {}.",
+      source.name()
+    )),
+    local: true,
+    private_key: Some(user_keypair.private_key),
+    public_key: Some(user_keypair.public_key),
+    last_refreshed_at: None,
+    banner: None,
+    hide_read_posts: false,
+    bot_account: true,
+    hide_bot_accounts: false,
+    hide_presence: true,
+    display_name: None,
+  };
+
+  let user = blocking(pool, move |conn| User_::register(conn, &user_form)).await??;
+  Ok(user)
+}
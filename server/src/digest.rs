@@ -0,0 +1,134 @@
+//! Emails users a digest of the top posts in their subscribed communities, for those who opted
+//! into a daily or weekly one.
+
+use crate::{api::APIError, blocking, DbPool, LemmyError};
+use lemmy_db::{
+  post_view::{PostQueryBuilder, PostView},
+  user::User_,
+  user_notification_settings::{DigestFrequency, UserNotificationSettings},
+  Crud,
+  ListingType,
+  SortType,
+};
+use lemmy_utils::{
+  i18n::{translate, user_langs},
+  send_email,
+  settings::Settings,
+};
+use tracing::error;
+
+/// How many posts go in a single digest
+const DIGEST_POST_LIMIT: i64 = 10;
+
+/// Sends the digests that are due. Does nothing without email.
+pub async fn send_due_digests(pool: &DbPool) -> Result<(), LemmyError> {
+  if Settings::get().email.is_none() {
+    return Ok(());
+  }
+
+  let due = blocking(pool, move |conn| {
+    UserNotificationSettings::due_for_digest(conn)
+  })
+  .await??;
+
+  for settings in due {
+    let user_id = settings.user_id;
+    if let Err(e) = send_digest(pool, settings).await {
+      error!("Couldn't send the email digest for user {}: {}", user_id, e);
+    }
+
+    // It's marked as sent even if it failed, so a bad address doesn't get retried every hour
+    blocking(pool, move |conn| {
+      UserNotificationSettings::mark_digest_sent(conn, user_id)
+    })
+    .await??;
+  }
+
+  Ok(())
+}
+
+async fn send_digest(pool: &DbPool, settings: UserNotificationSettings) -> Result<(), LemmyError> {
+  let user_id = settings.user_id;
+  let user = blocking(pool, move |conn| User_::read(conn, user_id)).await??;
+
+  let email = match &user.email {
+    Some(email) => email.to_owned(),
+    None => return Ok(()),
+  };
+
+  let (sort, subject_key) = match DigestFrequency::from_i16(settings.digest_frequency) {
+    Some(DigestFrequency::Weekly) => (SortType::TopWeek, "emails.digest_week_subject"),
+    _ => (SortType::TopDay, "emails.digest_day_subject"),
+  };
+
+  let show_nsfw = user.show_nsfw;
+  let posts = blocking(pool, move |conn| {
+    PostQueryBuilder::create(conn)
+      .listing_type(ListingType::Subscribed)
+      .sort(&sort)
+      .show_nsfw(show_nsfw)
+      .my_user_id(user_id)
+      .limit(DIGEST_POST_LIMIT)
+      .list()
+  })
+  .await??;
+
+  // Nothing worth an email
+  if posts.is_empty() {
+    return Ok(());
+  }
+
+  let hostname = Settings::get().hostname;
+  let langs = &user_langs(&user.lang);
+  let subject = &translate(langs, subject_key, &[("site", &hostname)]);
+  let html = &digest_html(langs, &hostname, &posts, &settings.digest_token);
+
+  match send_email(subject, &email, &user.name, html) {
+    Ok(_o) => Ok(()),
+    Err(e) => Err(APIError::err(&e).into()),
+  }
+}
+
+fn digest_html(langs: &[String], hostname: &str, posts: &[PostView], digest_token: &str) -> String {
+  let post_list = posts
+    .iter()
+    .map(|post| {
+      let link = format!(
+        "<a href=https://{}/post/{}>{}</a>",
+        hostname,
+        post.id,
+        escape_html(&post.name)
+      );
+      let line = translate(
+        langs,
+        "emails.digest_post",
+        &[
+          ("post", &link),
+          ("community", &post.community_name),
+          ("score", &post.score.to_string()),
+          ("comments", &post.number_of_comments.to_string()),
+        ],
+      );
+      format!("<li>{}</li>", line)
+    })
+    .collect::<Vec<String>>()
+    .join("");
+
+  format!(
+    "<h1>{}</h1><br><ul>{}</ul><br><a href=https://{}/api/v1/user/digest/unsubscribe?token={}>{}</a>",
+    translate(langs, "emails.digest_title", &[]),
+    post_list,
+    hostname,
+    digest_token,
+    translate(langs, "emails.digest_unsubscribe", &[])
+  )
+}
+
+/// Post titles are user content, so keep them from turning into markup
+pub fn escape_html(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
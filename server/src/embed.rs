@@ -0,0 +1,151 @@
+//! The players and cards that link posts show. The sites with an oEmbed endpoint are asked
+//! directly, and iframely reads the OpenGraph tags of the rest. The HTML comes from other sites,
+//! so it's cleaned before it's stored: only the iframes of the known players are kept.
+
+use crate::{
+  request::{retry, RecvError},
+  LemmyError,
+};
+use actix_web::client::Client;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use url::Url;
+
+struct OEmbedProvider {
+  /// The sites the links are on, subdomains included
+  hosts: &'static [&'static str],
+  endpoint: &'static str,
+  /// Besides the url of the link
+  params: &'static str,
+  /// Where the player of the embed HTML is served from
+  iframe_hosts: &'static [&'static str],
+}
+
+const PROVIDERS: &[OEmbedProvider] = &[
+  OEmbedProvider {
+    hosts: &["youtube.com", "youtu.be"],
+    endpoint: "https://www.youtube.com/oembed",
+    params: "format=json",
+    iframe_hosts: &["www.youtube.com", "www.youtube-nocookie.com"],
+  },
+  OEmbedProvider {
+    hosts: &["vimeo.com"],
+    endpoint: "https://vimeo.com/api/oembed.json",
+    params: "",
+    iframe_hosts: &["player.vimeo.com"],
+  },
+  OEmbedProvider {
+    hosts: &["soundcloud.com"],
+    endpoint: "https://soundcloud.com/oembed",
+    params: "format=json",
+    iframe_hosts: &["w.soundcloud.com"],
+  },
+  // Tweets are a blockquote, which the twitter script would turn into a card
+  OEmbedProvider {
+    hosts: &["twitter.com"],
+    endpoint: "https://publish.twitter.com/oembed",
+    params: "omit_script=true&dnt=true",
+    iframe_hosts: &[],
+  },
+];
+
+#[derive(Deserialize, Debug)]
+pub struct OEmbedResponse {
+  /// video, rich, photo or link
+  #[serde(rename = "type")]
+  pub type_: String,
+  pub title: Option<String>,
+  pub author_name: Option<String>,
+  pub provider_name: Option<String>,
+  pub thumbnail_url: Option<String>,
+  pub html: Option<String>,
+}
+
+fn provider(url: &str) -> Option<&'static OEmbedProvider> {
+  let url = Url::parse(url).ok()?;
+  let host = url.host_str()?;
+  PROVIDERS.iter().find(|provider| {
+    provider
+      .hosts
+      .iter()
+      .any(|h| host == *h || host.ends_with(&format!(".{}", h)))
+  })
+}
+
+/// The oEmbed of the link, when its site is one of the known providers.
+pub async fn fetch_oembed(
+  client: &Client,
+  url: &str,
+) -> Result<Option<OEmbedResponse>, LemmyError> {
+  let provider = match provider(url) {
+    Some(provider) => provider,
+    None => return Ok(None),
+  };
+
+  let mut fetch_url = format!(
+    "{}?url={}",
+    provider.endpoint,
+    utf8_percent_encode(url, NON_ALPHANUMERIC)
+  );
+  if !provider.params.is_empty() {
+    fetch_url.push('&');
+    fetch_url.push_str(provider.params);
+  }
+
+  let mut response = retry(|| client.get(&fetch_url).send()).await?;
+  if !response.status().is_success() {
+    return Err(format_err!("oembed fetch failed: {}", response.status()).into());
+  }
+
+  let res: OEmbedResponse = response
+    .json()
+    .await
+    .map_err(|e| RecvError(e.to_string()))?;
+  Ok(Some(res))
+}
+
+fn is_player_url(src: &str) -> bool {
+  match Url::parse(src) {
+    Ok(url) if url.scheme() == "https" => url.host_str().map_or(false, |host| {
+      PROVIDERS
+        .iter()
+        .flat_map(|provider| provider.iframe_hosts)
+        .any(|h| host == *h)
+    }),
+    _ => false,
+  }
+}
+
+/// Strips scripts, event handlers and styles from embed HTML. Iframes lose their src unless it's
+/// one of the known players.
+pub fn sanitize_embed_html(html: &str) -> Option<String> {
+  let clean = ammonia::Builder::default()
+    .add_tags(&["iframe"])
+    .add_tag_attributes(
+      "iframe",
+      &[
+        "src",
+        "width",
+        "height",
+        "title",
+        "allow",
+        "allowfullscreen",
+        "frameborder",
+      ],
+    )
+    .attribute_filter(|element, attribute, value| {
+      if element == "iframe" && attribute == "src" && !is_player_url(value) {
+        None
+      } else {
+        Some(value.into())
+      }
+    })
+    .clean(html)
+    .to_string();
+
+  if clean.trim().is_empty() {
+    None
+  } else {
+    Some(clean)
+  }
+}
@@ -0,0 +1,242 @@
+//! Asks the services in the `hooks` of the config about a post before it's published or edited, a
+//! registration before the account is made and an activity before it's federated, so an instance
+//! can have its own spam filter without a fork. A hook gets the action as JSON, signed like the
+//! webhooks when it has a secret, and lets it through, changes it or rejects it. The hooks of an
+//! event are asked in order, each with what the one before let through.
+
+use crate::{
+  api::{
+    post::{CreatePost, EditPost},
+    user::Register,
+    APIError,
+  },
+  request::{retry, RecvError},
+  webhook::{sign_webhook_payload, EVENT_HEADER, SIGNATURE_HEADER},
+  IPAddr,
+  LemmyError,
+};
+use actix_web::{client::Client, http::header::CONTENT_TYPE};
+use lemmy_db::post::Post;
+use lemmy_utils::settings::{HookConfig, HookEvent, Settings};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// The largest answer that's read, which can be a whole activity
+const HOOK_RESPONSE_LIMIT: usize = 1024 * 1024;
+
+#[derive(Serialize)]
+struct HookRequest<'a, T: Serialize> {
+  event: HookEvent,
+  data: &'a T,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum HookResponse<T> {
+  Allow,
+  Reject { reason: Option<String> },
+  Modify { data: T },
+}
+
+/// A post that's about to be published. The community it's in can't be changed.
+#[derive(Serialize, Deserialize)]
+pub struct PostHookData {
+  pub creator_id: i32,
+  pub community_id: i32,
+  pub name: String,
+  pub url: Option<String>,
+  pub body: Option<String>,
+  pub nsfw: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RegistrationHookData {
+  pub username: String,
+  pub email: Option<String>,
+  pub ip: Option<String>,
+}
+
+/// An activity that's about to go out, and the inboxes it goes to.
+#[derive(Serialize, Deserialize)]
+pub struct FederationHookData {
+  pub actor_id: String,
+  pub inboxes: Vec<String>,
+  pub activity: serde_json::Value,
+}
+
+/// Whether any hook wants the event, which saves building its data when none does.
+pub fn has_hooks(event: HookEvent) -> bool {
+  !hooks_for(event).is_empty()
+}
+
+/// Runs the action by the hooks of the event. What comes back is what they let through, or the
+/// error when one rejected it.
+pub async fn run_hooks<T>(event: HookEvent, data: T) -> Result<T, LemmyError>
+where
+  T: Serialize + DeserializeOwned,
+{
+  let hooks = hooks_for(event);
+  if hooks.is_empty() {
+    return Ok(data);
+  }
+
+  let client = Client::default();
+  let mut data = data;
+  for hook in hooks {
+    match call_hook(&client, &hook, event, &data).await {
+      Ok(HookResponse::Allow) => {}
+      Ok(HookResponse::Modify { data: modified }) => data = modified,
+      Ok(HookResponse::Reject { reason }) => {
+        info!(
+          "Hook {} rejected a {}: {}",
+          hook.url,
+          event.name(),
+          reason.unwrap_or_default()
+        );
+        return Err(APIError::err("rejected_by_hook").into());
+      }
+      Err(e) if hook.fail_closed => {
+        error!(
+          "Hook {} failed, rejecting a {}: {}",
+          hook.url,
+          event.name(),
+          e
+        );
+        return Err(APIError::err("hook_unavailable").into());
+      }
+      Err(e) => warn!("Hook {} failed, skipping it: {}", hook.url, e),
+    }
+  }
+
+  Ok(data)
+}
+
+/// The post the hooks let through, with anything they changed.
+pub async fn pre_publish_post(
+  creator_id: i32,
+  post: &CreatePost,
+) -> Result<CreatePost, LemmyError> {
+  let data = PostHookData {
+    creator_id,
+    community_id: post.community_id,
+    name: post.name.to_owned(),
+    url: post.url.to_owned(),
+    body: post.body.to_owned(),
+    nsfw: post.nsfw,
+  };
+  let data = run_hooks(HookEvent::PrePublishPost, data).await?;
+
+  Ok(CreatePost {
+    name: data.name,
+    url: data.url,
+    body: data.body,
+    nsfw: data.nsfw,
+    community_id: post.community_id,
+    flair_id: post.flair_id,
+    auth: post.auth.to_owned(),
+    scheduled_post_id: post.scheduled_post_id,
+  })
+}
+
+/// Same as `pre_publish_post`, for an edit of the post. An edit that leaves what the hooks see
+/// alone, like a mod locking the post, doesn't ask them.
+pub async fn pre_edit_post(post: &Post, edit: &EditPost) -> Result<EditPost, LemmyError> {
+  if edit.name == post.name
+    && edit.url == post.url
+    && edit.body == post.body
+    && edit.nsfw == post.nsfw
+  {
+    return Ok(edit.to_owned());
+  }
+
+  let data = PostHookData {
+    creator_id: post.creator_id,
+    community_id: post.community_id,
+    name: edit.name.to_owned(),
+    url: edit.url.to_owned(),
+    body: edit.body.to_owned(),
+    nsfw: edit.nsfw,
+  };
+  let data = run_hooks(HookEvent::PrePublishPost, data).await?;
+
+  Ok(EditPost {
+    name: data.name,
+    url: data.url,
+    body: data.body,
+    nsfw: data.nsfw,
+    ..edit.to_owned()
+  })
+}
+
+/// Same as `pre_publish_post`, for a registration. The password never goes to the hooks.
+pub async fn pre_register(
+  register: &Register,
+  ip: &Option<IPAddr>,
+) -> Result<Register, LemmyError> {
+  let data = RegistrationHookData {
+    username: register.username.to_owned(),
+    email: register.email.to_owned(),
+    ip: ip.to_owned(),
+  };
+  let data = run_hooks(HookEvent::PreRegister, data).await?;
+
+  Ok(Register {
+    username: data.username,
+    email: data.email,
+    password: register.password.to_owned(),
+    password_verify: register.password_verify.to_owned(),
+    admin: register.admin,
+    show_nsfw: register.show_nsfw,
+    captcha_uuid: register.captcha_uuid.to_owned(),
+    captcha_answer: register.captcha_answer.to_owned(),
+  })
+}
+
+fn hooks_for(event: HookEvent) -> Vec<HookConfig> {
+  Settings::get()
+    .hooks
+    .unwrap_or_default()
+    .into_iter()
+    .filter(|hook| hook.events.contains(&event))
+    .collect()
+}
+
+async fn call_hook<T>(
+  client: &Client,
+  hook: &HookConfig,
+  event: HookEvent,
+  data: &T,
+) -> Result<HookResponse<T>, LemmyError>
+where
+  T: Serialize + DeserializeOwned,
+{
+  let body = serde_json::to_string(&HookRequest { event, data })?;
+  let signature = match &hook.secret {
+    Some(secret) => Some(format!("sha256={}", sign_webhook_payload(secret, &body)?)),
+    None => None,
+  };
+
+  let mut response = retry(|| {
+    let mut request = client
+      .post(&hook.url)
+      .timeout(Duration::from_secs(hook.timeout_seconds))
+      .header(CONTENT_TYPE, "application/json")
+      .header(EVENT_HEADER, event.name());
+    if let Some(signature) = &signature {
+      request = request.header(SIGNATURE_HEADER, signature.as_str());
+    }
+    request.send_body(body.to_owned())
+  })
+  .await?;
+  if !response.status().is_success() {
+    return Err(format_err!("hook answered {}", response.status()).into());
+  }
+
+  let res: HookResponse<T> = response
+    .json()
+    .limit(HOOK_RESPONSE_LIMIT)
+    .await
+    .map_err(|e| RecvError(e.to_string()))?;
+  Ok(res)
+}
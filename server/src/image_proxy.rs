@@ -0,0 +1,95 @@
+//! Serves the remote thumbnails, avatars, icons and banners from here, so that looking at them
+//! doesn't hand the reader's IP to other instances. Each image is downloaded to pictrs once, which
+//! also resizes it, and the ones that went unused the longest are dropped when there are too many.
+//! The proxy URLs are signed, so that it can't be used to fetch anything else.
+
+use crate::{blocking, delete_pictrs_image, fetch_pictrs, DbPool, LemmyError};
+use actix_web::client::Client;
+use lemmy_db::image_proxy::{ImageProxy, ImageProxyForm};
+use lemmy_utils::{get_apub_protocol_string, settings::Settings};
+use openssl::{hash::MessageDigest, memcmp, pkey::PKey, sign::Signer};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use tracing::error;
+
+/// Where a remote image is served from here. The URL is kept as it is while the proxy is off.
+pub fn proxy_image_url(url: Option<String>) -> Option<String> {
+  let settings = Settings::get();
+  if !settings.image_proxy.enabled {
+    return url;
+  }
+
+  url.map(|url| match sign_url(&url) {
+    Ok(signature) => format!(
+      "{}://{}/image_proxy?url={}&signature={}",
+      get_apub_protocol_string(),
+      settings.hostname,
+      utf8_percent_encode(&url, NON_ALPHANUMERIC),
+      signature
+    ),
+    Err(e) => {
+      error!("Couldn't sign image url {}: {}", url, e);
+      url
+    }
+  })
+}
+
+/// Keyed with the jwt secret, in hex
+fn sign_url(url: &str) -> Result<String, LemmyError> {
+  let key = PKey::hmac(Settings::get().jwt_secret.as_bytes())?;
+  let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+  signer.update(url.as_bytes())?;
+  let signature = signer.sign_to_vec()?;
+  Ok(signature.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+pub fn is_signed_url(url: &str, signature: &str) -> bool {
+  match sign_url(url) {
+    Ok(expected) => {
+      expected.len() == signature.len() && memcmp::eq(expected.as_bytes(), signature.as_bytes())
+    }
+    Err(_) => false,
+  }
+}
+
+/// The pictrs file with the image, which is downloaded the first time.
+pub async fn proxied_file(pool: &DbPool, client: &Client, url: &str) -> Result<String, LemmyError> {
+  let image_url = url.to_owned();
+  let cached = blocking(pool, move |conn| ImageProxy::read_for_url(conn, &image_url)).await??;
+  if let Some(cached) = cached {
+    let image_id = cached.id;
+    blocking(pool, move |conn| ImageProxy::mark_used(conn, image_id)).await??;
+    return Ok(cached.file);
+  }
+
+  let pictrs = fetch_pictrs(client, url).await?;
+  let pictrs_file = pictrs
+    .files
+    .into_iter()
+    .next()
+    .ok_or_else(|| format_err!("pictrs returned no file"))?;
+  let form = ImageProxyForm {
+    url: url.to_owned(),
+    file: pictrs_file.file,
+    delete_token: pictrs_file.delete_token,
+  };
+  let image = blocking(pool, move |conn| ImageProxy::create(conn, &form)).await??;
+  Ok(image.file)
+}
+
+/// Takes the least recently used images down from pictrs, once there are more than the cache
+/// holds. One failing doesn't stop the rest.
+pub async fn prune_image_proxy(pool: &DbPool, client: &Client) -> Result<(), LemmyError> {
+  let keep = Settings::get().image_proxy.max_cached_images;
+  let images = blocking(pool, move |conn| ImageProxy::list_beyond(conn, keep)).await??;
+  for image in images {
+    if let Err(e) = delete_pictrs_image(client, &image.file, &image.delete_token).await {
+      error!("Couldn't delete proxied image {}: {}", image.file, e);
+      continue;
+    }
+
+    let image_id = image.id;
+    blocking(pool, move |conn| ImageProxy::delete(conn, image_id)).await??;
+  }
+
+  Ok(())
+}
@@ -0,0 +1,16 @@
+//! Drops the hashed IPs of registrations and logins once they're older than the site keeps them.
+
+use crate::{blocking, DbPool, LemmyError};
+use lemmy_db::{ip_ban::UserIp, naive_now, site::Site, Crud};
+
+pub async fn prune_user_ips(pool: &DbPool) -> Result<(), LemmyError> {
+  // Nothing gets recorded before the site is set up
+  let site = match blocking(pool, move |conn| Site::read(conn, 1)).await? {
+    Ok(site) => site,
+    Err(_e) => return Ok(()),
+  };
+
+  let before = naive_now() - chrono::Duration::days(site.ip_retention_days.into());
+  blocking(pool, move |conn| UserIp::prune_before(conn, before)).await??;
+  Ok(())
+}
@@ -0,0 +1,539 @@
+//! The background work of the server, kept in the database so it survives restarts. Workers take
+//! the jobs that are due one at a time, and the ones that fail are tried again later, waiting
+//! longer each time, until they run out of attempts. The recurring tasks queue their next run
+//! themselves. A worker holds a lease on its job for as long as it's on it, so the servers sharing
+//! the queue only take up the jobs of one that went away.
+
+use crate::{
+  api::{
+    scheduled_post::{fail_scheduled_post, publish_scheduled_post},
+    user::{fail_user_export, fail_user_import, generate_user_export, import_user_data},
+  },
+  apub::activities::deliver_activity,
+  archive::archive_old_posts,
+  ban_expiry::lift_expired_bans,
+  blocking,
+  community_import::run_community_import,
+  digest::send_due_digests,
+  image_proxy::prune_image_proxy,
+  ip_retention::prune_user_ips,
+  link_preview::refresh_missing_embeds,
+  matrix::send_matrix_notification,
+  metrics::count_delivery_failure,
+  push::{send_push_notification, PushNotification},
+  sitemap::generate_sitemaps,
+  webhook::{deliver_webhook, queue_webhooks, FederationFailure},
+  websocket::{server::ChatServer, WebsocketInfo},
+  DbPool,
+  LemmyError,
+};
+use actix::Addr;
+use actix_web::client::Client;
+use futures::future::{select, Either};
+use lemmy_db::{
+  comment_view::CommentView,
+  job::{Job, JobForm, JobStatus},
+  naive_now,
+  post_view::PostView,
+  webhook::WebhookEvent,
+  Crud,
+};
+use lemmy_utils::settings::Settings;
+use serde::{Deserialize, Serialize};
+use std::{
+  future::Future,
+  sync::atomic::{AtomicBool, AtomicI64, AtomicUsize, Ordering},
+  time::{Duration, Instant},
+};
+use tracing::{error, info, info_span, Span};
+use tracing_futures::Instrument;
+
+/// How long an idle worker waits before looking for due jobs again
+const JOB_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long a job is held for without its worker renewing the lease, which it does a few times
+/// over while it's on it
+const JOB_LEASE_SECONDS: i64 = 60;
+
+/// How long the finished jobs are kept around, for the queue's stats
+const FINISHED_JOB_RETENTION_DAYS: i64 = 7;
+
+/// The longest a failed job waits before its next attempt
+const MAX_RETRY_DELAY_MINUTES: i64 = 24 * 60;
+
+/// How far back the hot ranks are kept up to date
+const HOT_RANK_DAYS: i64 = 7;
+
+/// How long the workers can go without looking for jobs before they count as stuck. Some jobs,
+/// like the exports, take a while.
+const WORKER_STALL_SECONDS: i64 = 5 * 60;
+
+/// When a worker last looked for a job, as a unix timestamp. Zero until the workers start.
+static LAST_WORKER_POLL: AtomicI64 = AtomicI64::new(0);
+
+/// Set when the server shuts down, after which the workers don't take any more jobs
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// The jobs the workers are on right now
+static RUNNING_JOBS: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts a job as running for as long as it's kept.
+struct RunningJob;
+
+impl RunningJob {
+  fn start() -> Self {
+    RUNNING_JOBS.fetch_add(1, Ordering::SeqCst);
+    RunningJob
+  }
+}
+
+impl Drop for RunningJob {
+  fn drop(&mut self) {
+    RUNNING_JOBS.fetch_sub(1, Ordering::SeqCst);
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Task {
+  /// An activity for a remote inbox, signed by the local user or community with the actor id
+  DeliverActivity {
+    actor_id: String,
+    inbox: String,
+    activity: String,
+  },
+  SendPushNotification {
+    recipient_id: i32,
+    notification: PushNotification,
+  },
+  SendMatrixNotification {
+    recipient_id: i32,
+    notification: PushNotification,
+  },
+  GenerateUserExport {
+    export_id: i32,
+  },
+  /// The actor ids of the communities to follow, and of the posts and comments to save
+  ImportUserData {
+    user_id: i32,
+    communities: Vec<String>,
+    saved_posts: Vec<String>,
+    saved_comments: Vec<String>,
+  },
+  ImportCommunity {
+    import_id: i32,
+  },
+  /// Queued for when the post is due, and let go of if it was cancelled by then
+  PublishScheduledPost {
+    scheduled_post_id: i32,
+  },
+  /// The signed payload of an event for one webhook
+  DeliverWebhook {
+    webhook_id: i32,
+    event: WebhookEvent,
+    payload: String,
+  },
+  SendDigests,
+  ArchivePosts,
+  LiftExpiredBans,
+  PruneUserIps,
+  PruneJobs,
+  UpdateHotRanks,
+  PruneImageProxy,
+  RefreshMissingEmbeds,
+  GenerateSitemaps,
+}
+
+impl Task {
+  pub fn kind(&self) -> &'static str {
+    match self {
+      Task::DeliverActivity { .. } => "DeliverActivity",
+      Task::SendPushNotification { .. } => "SendPushNotification",
+      Task::SendMatrixNotification { .. } => "SendMatrixNotification",
+      Task::GenerateUserExport { .. } => "GenerateUserExport",
+      Task::ImportUserData { .. } => "ImportUserData",
+      Task::ImportCommunity { .. } => "ImportCommunity",
+      Task::PublishScheduledPost { .. } => "PublishScheduledPost",
+      Task::DeliverWebhook { .. } => "DeliverWebhook",
+      Task::SendDigests => "SendDigests",
+      Task::ArchivePosts => "ArchivePosts",
+      Task::LiftExpiredBans => "LiftExpiredBans",
+      Task::PruneUserIps => "PruneUserIps",
+      Task::PruneJobs => "PruneJobs",
+      Task::UpdateHotRanks => "UpdateHotRanks",
+      Task::PruneImageProxy => "PruneImageProxy",
+      Task::RefreshMissingEmbeds => "RefreshMissingEmbeds",
+      Task::GenerateSitemaps => "GenerateSitemaps",
+    }
+  }
+
+  /// How long after a run the recurring tasks run again. A failed run simply waits for the next.
+  fn interval(&self) -> Option<chrono::Duration> {
+    match self {
+      Task::SendDigests
+      | Task::ArchivePosts
+      | Task::PruneUserIps
+      | Task::PruneJobs
+      | Task::PruneImageProxy
+      | Task::RefreshMissingEmbeds
+      | Task::GenerateSitemaps => Some(chrono::Duration::hours(1)),
+      Task::LiftExpiredBans | Task::UpdateHotRanks => Some(chrono::Duration::minutes(5)),
+      _ => None,
+    }
+  }
+
+  fn max_attempts(&self) -> i32 {
+    match self {
+      // An instance can be down for a good while
+      Task::DeliverActivity { .. } => 10,
+      // Another run would only find it interrupted
+      Task::ImportCommunity { .. } => 1,
+      _ if self.interval().is_some() => 1,
+      _ => 5,
+    }
+  }
+
+  fn to_form(&self, run_at: chrono::NaiveDateTime) -> Result<JobForm, LemmyError> {
+    Ok(JobForm {
+      kind: self.kind().to_owned(),
+      payload: serde_json::to_string(self)?,
+      max_attempts: self.max_attempts(),
+      run_at,
+    })
+  }
+}
+
+/// The tasks that keep coming back, each queued once on startup.
+fn recurring_tasks() -> Vec<Task> {
+  vec![
+    Task::SendDigests,
+    Task::ArchivePosts,
+    Task::LiftExpiredBans,
+    Task::PruneUserIps,
+    Task::PruneJobs,
+    Task::UpdateHotRanks,
+    Task::PruneImageProxy,
+    Task::RefreshMissingEmbeds,
+    Task::GenerateSitemaps,
+  ]
+}
+
+pub async fn enqueue(pool: &DbPool, task: Task) -> Result<(), LemmyError> {
+  enqueue_at(pool, task, naive_now()).await
+}
+
+pub async fn enqueue_at(
+  pool: &DbPool,
+  task: Task,
+  run_at: chrono::NaiveDateTime,
+) -> Result<(), LemmyError> {
+  let form = task.to_form(run_at)?;
+  blocking(pool, move |conn| Job::create(conn, &form)).await??;
+  Ok(())
+}
+
+pub async fn enqueue_all(pool: &DbPool, tasks: Vec<Task>) -> Result<(), LemmyError> {
+  if tasks.is_empty() {
+    return Ok(());
+  }
+
+  let run_at = naive_now();
+  let forms = tasks
+    .iter()
+    .map(|task| task.to_form(run_at))
+    .collect::<Result<Vec<JobForm>, LemmyError>>()?;
+  blocking(pool, move |conn| Job::create_many(conn, &forms)).await??;
+  Ok(())
+}
+
+/// Starts the workers, after queueing the recurring tasks that aren't already.
+pub async fn start_job_queue(
+  pool: DbPool,
+  client: Client,
+  chat_server: Addr<ChatServer>,
+) -> Result<(), LemmyError> {
+  for task in recurring_tasks() {
+    let kind = task.kind();
+    let scheduled = blocking(&pool, move |conn| Job::is_scheduled(conn, kind)).await??;
+    if !scheduled {
+      enqueue(&pool, task).await?;
+    }
+  }
+
+  for _ in 0..Settings::get().jobs.workers {
+    actix_rt::spawn(run_worker(
+      pool.clone(),
+      client.clone(),
+      chat_server.clone(),
+    ));
+  }
+
+  Ok(())
+}
+
+/// Whether the workers are running, and got to look for jobs lately.
+pub fn job_workers_alive() -> bool {
+  let last_poll = LAST_WORKER_POLL.load(Ordering::Relaxed);
+  last_poll > 0 && naive_now().timestamp() - last_poll < WORKER_STALL_SECONDS
+}
+
+/// Has the workers stop taking jobs, and waits for the ones they're on, up to the timeout. The
+/// jobs still running after that, like the deliveries still queued, are all in the database, and
+/// run again once their leases run out.
+pub async fn stop_job_queue(timeout: Duration) {
+  SHUTTING_DOWN.store(true, Ordering::SeqCst);
+
+  let start = Instant::now();
+  while RUNNING_JOBS.load(Ordering::SeqCst) > 0 && start.elapsed() < timeout {
+    actix_rt::time::delay_for(Duration::from_millis(100)).await;
+  }
+
+  match RUNNING_JOBS.load(Ordering::SeqCst) {
+    0 => info!("The job workers finished their jobs"),
+    running => error!("Cut off {} jobs, they run again on the next start", running),
+  }
+}
+
+/// Runs until the server shuts down, working through the due jobs.
+async fn run_worker(pool: DbPool, client: Client, chat_server: Addr<ChatServer>) {
+  while !SHUTTING_DOWN.load(Ordering::SeqCst) {
+    LAST_WORKER_POLL.store(naive_now().timestamp(), Ordering::Relaxed);
+    match run_next_job(&pool, &client, &chat_server).await {
+      Ok(true) => continue,
+      Ok(false) => (),
+      Err(e) => error!("Couldn't run a job: {}", e),
+    }
+    actix_rt::time::delay_for(JOB_POLL_INTERVAL).await;
+  }
+}
+
+/// Runs the next due job, if there is one.
+async fn run_next_job(
+  pool: &DbPool,
+  client: &Client,
+  chat_server: &Addr<ChatServer>,
+) -> Result<bool, LemmyError> {
+  let _running = RunningJob::start();
+  let lease_until = naive_now() + chrono::Duration::seconds(JOB_LEASE_SECONDS);
+  let job = match blocking(pool, move |conn| Job::claim_next(conn, lease_until)).await?? {
+    Some(job) => job,
+    None => return Ok(false),
+  };
+
+  let span = info_span!("job", job_id = job.id, kind = %job.kind, attempt = job.attempts);
+  let (task, result) = match serde_json::from_str::<Task>(&job.payload) {
+    Ok(task) => {
+      let run = run_task(&task, pool, client, chat_server).instrument(span.clone());
+      let result = hold_lease(pool, job.id, run).await;
+      (Some(task), result)
+    }
+    Err(e) => (None, Err(e.into())),
+  };
+
+  if let Err(e) = finish_job(pool, chat_server, &job, result, &span).await {
+    error!("Couldn't finish job {} ({}): {}", job.id, job.kind, e);
+  }
+
+  // Queued once this run is done with, so the next one can't find it still running
+  if let Some(task) = task {
+    if let Some(interval) = task.interval() {
+      if let Err(e) = enqueue_at(pool, task, naive_now() + interval).await {
+        error!("Couldn't queue the next {} run: {}", job.kind, e);
+      }
+    }
+  }
+
+  Ok(true)
+}
+
+/// Marks the job done, or failed to be tried again later.
+async fn finish_job(
+  pool: &DbPool,
+  chat_server: &Addr<ChatServer>,
+  job: &Job,
+  result: Result<(), LemmyError>,
+  span: &Span,
+) -> Result<(), LemmyError> {
+  let job_id = job.id;
+  match result {
+    Ok(()) => {
+      blocking(pool, move |conn| Job::complete(conn, job_id)).await??;
+    }
+    Err(e) => {
+      span.in_scope(|| error!("Job {} ({}) failed: {}", job_id, job.kind, e));
+      if job.kind == "DeliverActivity" {
+        count_delivery_failure();
+      }
+      // A minute after the first attempt, then twice as long after each next one
+      let delay_minutes = 2i64
+        .saturating_pow((job.attempts - 1).max(0) as u32)
+        .min(MAX_RETRY_DELAY_MINUTES);
+      let retry_at = naive_now() + chrono::Duration::minutes(delay_minutes);
+      let error = e.to_string();
+      let failed_job =
+        blocking(pool, move |conn| Job::fail(conn, job_id, &error, retry_at)).await??;
+      if failed_job.status == JobStatus::Failed as i16 {
+        queue_federation_failure_webhooks(pool, &failed_job).await;
+        if let Err(e) = give_up_task(pool, chat_server, &failed_job).await {
+          error!("Couldn't give up on job {} ({}): {}", job_id, job.kind, e);
+        }
+      }
+    }
+  }
+
+  Ok(())
+}
+
+/// Runs the job, renewing its lease until it's done.
+async fn hold_lease<F>(pool: &DbPool, job_id: i32, run: F) -> Result<(), LemmyError>
+where
+  F: Future<Output = Result<(), LemmyError>>,
+{
+  let renew = async {
+    loop {
+      actix_rt::time::delay_for(Duration::from_secs(JOB_LEASE_SECONDS as u64 / 3)).await;
+      let lease_until = naive_now() + chrono::Duration::seconds(JOB_LEASE_SECONDS);
+      let renew_lease = move |conn: &'_ _| Job::renew_lease(conn, job_id, lease_until);
+      match blocking(pool, renew_lease).await {
+        Ok(Ok(_)) => (),
+        Ok(Err(e)) => error!("Couldn't renew the lease of job {}: {}", job_id, e),
+        Err(e) => error!("Couldn't renew the lease of job {}: {}", job_id, e),
+      }
+    }
+  };
+
+  match select(Box::pin(run), Box::pin(renew)).await {
+    Either::Left((result, _)) => result,
+    Either::Right((_, run)) => run.await,
+  }
+}
+
+async fn run_task(
+  task: &Task,
+  pool: &DbPool,
+  client: &Client,
+  chat_server: &Addr<ChatServer>,
+) -> Result<(), LemmyError> {
+  match task {
+    Task::DeliverActivity {
+      actor_id,
+      inbox,
+      activity,
+    } => deliver_activity(client, pool, actor_id, inbox, activity).await,
+    Task::SendPushNotification {
+      recipient_id,
+      notification,
+    } => send_push_notification(pool, *recipient_id, notification).await,
+    Task::SendMatrixNotification {
+      recipient_id,
+      notification,
+    } => send_matrix_notification(pool, client, *recipient_id, notification).await,
+    Task::GenerateUserExport { export_id } => {
+      let websocket_info = WebsocketInfo {
+        chatserver: chat_server.to_owned(),
+        id: None,
+      };
+      generate_user_export(pool, *export_id, Some(websocket_info)).await
+    }
+    Task::ImportUserData {
+      user_id,
+      communities,
+      saved_posts,
+      saved_comments,
+    } => {
+      let websocket_info = WebsocketInfo {
+        chatserver: chat_server.to_owned(),
+        id: None,
+      };
+      import_user_data(
+        pool,
+        client,
+        *user_id,
+        communities,
+        saved_posts,
+        saved_comments,
+        Some(websocket_info),
+      )
+      .await
+    }
+    Task::ImportCommunity { import_id } => {
+      let websocket_info = WebsocketInfo {
+        chatserver: chat_server.to_owned(),
+        id: None,
+      };
+      run_community_import(pool, *import_id, Some(websocket_info)).await
+    }
+    Task::PublishScheduledPost { scheduled_post_id } => {
+      let websocket_info = WebsocketInfo {
+        chatserver: chat_server.to_owned(),
+        id: None,
+      };
+      publish_scheduled_post(pool, client, *scheduled_post_id, Some(websocket_info)).await
+    }
+    Task::SendDigests => send_due_digests(pool).await,
+    Task::ArchivePosts => archive_old_posts(pool, client).await,
+    Task::LiftExpiredBans => lift_expired_bans(pool).await,
+    Task::PruneUserIps => prune_user_ips(pool).await,
+    Task::PruneJobs => {
+      let before = naive_now() - chrono::Duration::days(FINISHED_JOB_RETENTION_DAYS);
+      blocking(pool, move |conn| Job::prune_finished_before(conn, before)).await??;
+      Ok(())
+    }
+    Task::UpdateHotRanks => {
+      let since = naive_now() - chrono::Duration::days(HOT_RANK_DAYS);
+      blocking(pool, move |conn| PostView::update_hot_ranks(conn, since)).await??;
+      blocking(pool, move |conn| CommentView::update_hot_ranks(conn, since)).await??;
+      Ok(())
+    }
+    Task::PruneImageProxy => prune_image_proxy(pool, client).await,
+    Task::DeliverWebhook {
+      webhook_id,
+      event,
+      payload,
+    } => deliver_webhook(pool, client, *webhook_id, *event, payload).await,
+    Task::RefreshMissingEmbeds => refresh_missing_embeds(pool, client, chat_server).await,
+    Task::GenerateSitemaps => generate_sitemaps(pool).await,
+  }
+}
+
+/// Leaves what a task that ran out of attempts was working on as failed, so it isn't waited on.
+async fn give_up_task(
+  pool: &DbPool,
+  chat_server: &Addr<ChatServer>,
+  job: &Job,
+) -> Result<(), LemmyError> {
+  let websocket_info = WebsocketInfo {
+    chatserver: chat_server.to_owned(),
+    id: None,
+  };
+  let failure = job.last_error.to_owned().unwrap_or_default();
+  match serde_json::from_str::<Task>(&job.payload) {
+    Ok(Task::GenerateUserExport { export_id }) => {
+      fail_user_export(pool, export_id, failure, Some(websocket_info)).await
+    }
+    Ok(Task::ImportUserData { user_id, .. }) => {
+      fail_user_import(user_id, failure, Some(websocket_info)).await
+    }
+    Ok(Task::PublishScheduledPost { scheduled_post_id }) => {
+      fail_scheduled_post(pool, scheduled_post_id, failure, Some(websocket_info)).await
+    }
+    _ => Ok(()),
+  }
+}
+
+/// Lets the admins know about an activity that ran out of attempts.
+async fn queue_federation_failure_webhooks(pool: &DbPool, job: &Job) {
+  let (actor_id, inbox) = match serde_json::from_str::<Task>(&job.payload) {
+    Ok(Task::DeliverActivity {
+      actor_id, inbox, ..
+    }) => (actor_id, inbox),
+    _ => return,
+  };
+  let failure = FederationFailure {
+    actor_id,
+    inbox,
+    error: job.last_error.to_owned().unwrap_or_default(),
+  };
+  if let Err(e) = queue_webhooks(pool, WebhookEvent::FederationFailure, None, &failure).await {
+    error!("Couldn't queue the federation failure webhooks: {}", e);
+  }
+}
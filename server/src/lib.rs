@@ -12,7 +12,6 @@ pub extern crate chrono;
 pub extern crate diesel;
 pub extern crate dotenv;
 pub extern crate jsonwebtoken;
-extern crate log;
 pub extern crate openssl;
 pub extern crate rss;
 pub extern crate serde;
@@ -22,23 +21,70 @@ pub extern crate strum;
 
 pub mod api;
 pub mod apub;
+pub mod archive;
+pub mod auth;
+pub mod ban_expiry;
+pub mod cache;
 pub mod code_migrations;
+pub mod community_import;
+pub mod digest;
+pub mod embed;
+pub mod hooks;
+pub mod image_proxy;
+pub mod ip_retention;
+pub mod job_queue;
+pub mod link_preview;
+pub mod mastodon;
+pub mod matrix;
+pub mod media;
+pub mod metrics;
+pub mod nsfw;
+pub mod push;
 pub mod rate_limit;
 pub mod request;
 pub mod routes;
+pub mod sitemap;
+pub mod upload;
 pub mod version;
+pub mod webhook;
 pub mod websocket;
 
-use crate::request::{retry, RecvError};
-use actix_web::{client::Client, dev::ConnectionInfo};
-use log::error;
+use crate::{
+  api::APIError,
+  embed::{fetch_oembed, sanitize_embed_html},
+  nsfw::classify_pictrs_file,
+  request::{retry, RecvError},
+};
+use actix_web::{
+  client::Client,
+  dev::ConnectionInfo,
+  http::{header::ACCEPT_LANGUAGE, StatusCode},
+  HttpRequest,
+  HttpResponse,
+};
+use diesel::{
+  r2d2::{ConnectionManager, Pool},
+  PgConnection,
+};
+use lemmy_db::purge::{PictrsThumbnail, PictrsThumbnailForm};
+use lemmy_utils::{
+  i18n::{accept_language, translate},
+  settings::Settings,
+};
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::{
+  atomic::{AtomicUsize, Ordering},
+  Arc,
+};
+use tracing::{debug_span, error};
 
-pub type DbPool = diesel::r2d2::Pool<diesel::r2d2::ConnectionManager<diesel::PgConnection>>;
+pub type PgPool = Pool<ConnectionManager<PgConnection>>;
 pub type ConnectionId = usize;
 pub type PostId = i32;
 pub type CommunityId = i32;
+pub type ConversationId = i32;
 pub type UserId = i32;
 pub type IPAddr = String;
 
@@ -62,7 +108,63 @@ impl std::fmt::Display for LemmyError {
   }
 }
 
-impl actix_web::error::ResponseError for LemmyError {}
+impl LemmyError {
+  /// What the operation answers with, like `not_logged_in`, or the error itself when it didn't
+  /// fail on purpose
+  pub fn message(&self) -> String {
+    match self.inner.downcast_ref::<APIError>() {
+      Some(api_error) => api_error.message.to_owned(),
+      None => self.inner.to_string(),
+    }
+  }
+
+  pub fn to_response(&self) -> ErrorResponse {
+    ErrorResponse {
+      error: self.message(),
+      message: None,
+    }
+  }
+
+  /// The same, with the error explained in the first of the languages that has it. The ones that
+  /// didn't fail on purpose have nothing to explain.
+  pub fn to_localized_response(&self, langs: &[String]) -> ErrorResponse {
+    let message = match self.inner.downcast_ref::<APIError>() {
+      Some(api_error) => Some(translate(
+        langs,
+        &format!("errors.{}", api_error.message),
+        &[],
+      )),
+      None => None,
+    };
+    ErrorResponse {
+      error: self.message(),
+      message,
+    }
+  }
+}
+
+/// The body of every failed operation, over HTTP and the websocket alike. The error stays the
+/// same in every language, the message is for showing to people.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ErrorResponse {
+  pub error: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub message: Option<String>,
+}
+
+impl actix_web::error::ResponseError for LemmyError {
+  fn status_code(&self) -> StatusCode {
+    if self.inner.downcast_ref::<APIError>().is_some() {
+      StatusCode::BAD_REQUEST
+    } else {
+      StatusCode::INTERNAL_SERVER_ERROR
+    }
+  }
+
+  fn error_response(&self) -> HttpResponse {
+    HttpResponse::build(self.status_code()).json(self.to_response())
+  }
+}
 
 #[derive(Deserialize, Debug)]
 pub struct IframelyResponse {
@@ -86,6 +188,8 @@ pub async fn fetch_iframely(client: &Client, url: &str) -> Result<IframelyRespon
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct PictrsResponse {
+  /// Missing when pictrs turned the images down
+  #[serde(default)]
   files: Vec<PictrsFile>,
   msg: String,
 }
@@ -100,7 +204,8 @@ pub async fn fetch_pictrs(client: &Client, image_url: &str) -> Result<PictrsResp
   is_image_content_type(client, image_url).await?;
 
   let fetch_url = format!(
-    "http://pictrs:8080/image/download?url={}",
+    "{}/image/download?url={}",
+    Settings::get().pictrs.url,
     utf8_percent_encode(image_url, NON_ALPHANUMERIC) // TODO this might not be needed
   );
 
@@ -118,14 +223,38 @@ pub async fn fetch_pictrs(client: &Client, image_url: &str) -> Result<PictrsResp
   }
 }
 
+/// Takes an image down from pictrs. That needs the delete token pictrs handed out with it.
+pub async fn delete_pictrs_image(
+  client: &Client,
+  file: &str,
+  delete_token: &str,
+) -> Result<(), LemmyError> {
+  let delete_url = format!(
+    "{}/image/delete/{}/{}",
+    Settings::get().pictrs.url,
+    utf8_percent_encode(delete_token, NON_ALPHANUMERIC),
+    utf8_percent_encode(file, NON_ALPHANUMERIC)
+  );
+
+  let response = retry(|| client.delete(&delete_url).send()).await?;
+  if response.status().is_success() {
+    Ok(())
+  } else {
+    Err(format_err!("pictrs delete failed: {}", response.status()).into())
+  }
+}
+
+/// The title, description, sanitized embed HTML, oEmbed type and pictrs thumbnail of the link
 async fn fetch_iframely_and_pictrs_data(
   client: &Client,
+  pool: &DbPool,
   url: Option<String>,
 ) -> (
   Option<String>,
   Option<String>,
   Option<String>,
   Option<String>,
+  Option<String>,
 ) {
   match &url {
     Some(url) => {
@@ -139,10 +268,29 @@ async fn fetch_iframely_and_pictrs_data(
           }
         };
 
+      // The oEmbed of the known sites has their player, which wins over the OpenGraph tags
+      let oembed = match fetch_oembed(client, url).await {
+        Ok(oembed) => oembed,
+        Err(e) => {
+          error!("oembed err: {}", e);
+          None
+        }
+      };
+      let (title, thumbnail_url, html, embed_type) = match oembed {
+        Some(oembed) => (
+          oembed.title.or(iframely_title),
+          oembed.thumbnail_url.or(iframely_thumbnail_url),
+          oembed.html.or(iframely_html),
+          Some(oembed.type_),
+        ),
+        None => (iframely_title, iframely_thumbnail_url, iframely_html, None),
+      };
+      let html = html.and_then(|html| sanitize_embed_html(&html));
+
       // Fetch pictrs thumbnail
-      let pictrs_thumbnail = match iframely_thumbnail_url {
-        Some(iframely_thumbnail_url) => match fetch_pictrs(client, &iframely_thumbnail_url).await {
-          Ok(res) => Some(res.files[0].file.to_owned()),
+      let pictrs_file = match thumbnail_url {
+        Some(thumbnail_url) => match fetch_pictrs(client, &thumbnail_url).await {
+          Ok(res) => Some(res.files[0].to_owned()),
           Err(e) => {
             error!("pictrs err: {}", e);
             None
@@ -150,7 +298,7 @@ async fn fetch_iframely_and_pictrs_data(
         },
         // Try to generate a small thumbnail if iframely is not supported
         None => match fetch_pictrs(client, &url).await {
-          Ok(res) => Some(res.files[0].file.to_owned()),
+          Ok(res) => Some(res.files[0].to_owned()),
           Err(e) => {
             error!("pictrs err: {}", e);
             None
@@ -158,14 +306,38 @@ async fn fetch_iframely_and_pictrs_data(
         },
       };
 
+      // Keep the delete token, so that a purge can take the thumbnail down again
+      if let Some(pictrs_file) = &pictrs_file {
+        let nsfw_score = match classify_pictrs_file(client, &pictrs_file.file).await {
+          Ok(nsfw_score) => nsfw_score,
+          Err(e) => {
+            error!("nsfw classifier err: {}", e);
+            None
+          }
+        };
+        let form = PictrsThumbnailForm {
+          file: pictrs_file.file.to_owned(),
+          delete_token: pictrs_file.delete_token.to_owned(),
+          nsfw_score,
+        };
+        let create = move |conn: &'_ _| PictrsThumbnail::create(conn, &form);
+        match blocking(pool, create).await {
+          Ok(Ok(_)) => {}
+          Ok(Err(e)) => error!("Couldn't save pictrs delete token: {}", e),
+          Err(e) => error!("Couldn't save pictrs delete token: {}", e),
+        }
+      }
+      let pictrs_thumbnail = pictrs_file.map(|f| f.file);
+
       (
-        iframely_title,
+        title,
         iframely_description,
-        iframely_html,
+        html,
+        embed_type,
         pictrs_thumbnail,
       )
     }
-    None => (None, None, None, None),
+    None => (None, None, None, None, None),
   }
 }
 
@@ -185,6 +357,16 @@ pub async fn is_image_content_type(client: &Client, test: &str) -> Result<(), Le
   }
 }
 
+/// The languages the request asks for, the most preferred first.
+pub fn request_langs(req: &HttpRequest) -> Vec<String> {
+  req
+    .headers()
+    .get(ACCEPT_LANGUAGE)
+    .and_then(|h| h.to_str().ok())
+    .map(accept_language)
+    .unwrap_or_default()
+}
+
 pub fn get_ip(conn_info: &ConnectionInfo) -> String {
   conn_info
     .realip_remote_addr()
@@ -195,13 +377,55 @@ pub fn get_ip(conn_info: &ConnectionInfo) -> String {
     .to_string()
 }
 
+/// The connections to the primary database, and to its read replicas if there are any. Writes,
+/// and the reads that have to see them, go to the primary. The replicas can lag a little behind,
+/// so only the listings that don't mind that are read from them.
+#[derive(Clone)]
+pub struct DbPool {
+  primary: PgPool,
+  replicas: Vec<PgPool>,
+  next_replica: Arc<AtomicUsize>,
+}
+
+impl DbPool {
+  pub fn new(primary: PgPool, replicas: Vec<PgPool>) -> Self {
+    DbPool {
+      primary,
+      replicas,
+      next_replica: Arc::new(AtomicUsize::new(0)),
+    }
+  }
+
+  pub fn primary(&self) -> &PgPool {
+    &self.primary
+  }
+
+  pub fn replicas(&self) -> &[PgPool] {
+    &self.replicas
+  }
+
+  /// Takes turns between the replicas, or gives the primary when there are none.
+  fn replica(&self) -> &PgPool {
+    if self.replicas.is_empty() {
+      return &self.primary;
+    }
+    let next = self.next_replica.fetch_add(1, Ordering::Relaxed);
+    &self.replicas[next % self.replicas.len()]
+  }
+}
+
+/// Runs the queries on the primary. Use this for anything that writes, or reads what was just
+/// written.
 pub async fn blocking<F, T>(pool: &DbPool, f: F) -> Result<T, LemmyError>
 where
   F: FnOnce(&diesel::PgConnection) -> T + Send + 'static,
   T: Send + 'static,
 {
-  let pool = pool.clone();
+  let pool = pool.primary.clone();
+  // The span of the caller doesn't carry over to the thread the queries run on by itself
+  let span = debug_span!("db", pool = "primary");
   let res = actix_web::web::block(move || {
+    let _entered = span.enter();
     let conn = pool.get()?;
     let res = (f)(&conn);
     Ok(res) as Result<_, LemmyError>
@@ -211,9 +435,53 @@ where
   Ok(res)
 }
 
+/// Runs read only queries on one of the replicas, which might not have the latest writes yet.
+/// Falls back to the primary when the replica can't be reached.
+pub async fn blocking_read<F, T>(pool: &DbPool, f: F) -> Result<T, LemmyError>
+where
+  F: FnOnce(&diesel::PgConnection) -> T + Send + 'static,
+  T: Send + 'static,
+{
+  let replica = pool.replica().clone();
+  let primary = pool.primary.clone();
+  let span = debug_span!("db", pool = "replica");
+  let res = actix_web::web::block(move || {
+    let _entered = span.enter();
+    let conn = match replica.get() {
+      Ok(conn) => conn,
+      Err(e) => {
+        error!("Read replica unavailable, using the primary: {}", e);
+        primary.get()?
+      }
+    };
+    let res = (f)(&conn);
+    Ok(res) as Result<_, LemmyError>
+  })
+  .await?;
+
+  Ok(res)
+}
+
 #[cfg(test)]
 mod tests {
-  use crate::is_image_content_type;
+  use crate::{
+    apub::{post::page_from_media_object, user_outbox::object_content},
+    auth::ldap_user_filter,
+    community_import::{parse_kbin_export, parse_reddit_export},
+    embed::sanitize_embed_html,
+    hooks::HookResponse,
+    is_image_content_type,
+    mastodon::StatusId,
+    media::{linked_media_files, media_url},
+    routes::{feeds::channel_to_atom, oembed::oembed_post_id, openapi::openapi_document},
+    sitemap::{sitemap_page_xml, SitemapEntry},
+    upload::process_image,
+    webhook::{is_public_ip, sign_webhook_payload},
+  };
+  use lemmy_utils::settings::{Settings, UploadConfig};
+  use rss::{ChannelBuilder, GuidBuilder, ItemBuilder};
+  use serde_json::json;
+  use std::str::FromStr;
 
   #[test]
   fn test_image() {
@@ -228,6 +496,372 @@ mod tests {
     });
   }
 
+  #[test]
+  fn test_linked_media_files() {
+    let text = format!(
+      "![cat]({}) and [a dog]({}?thumbnail=96), but not https://example.com/pictrs/image/x.png",
+      media_url("cat.png"),
+      media_url("dog-1.jpg")
+    );
+    assert_eq!(
+      vec!["cat.png".to_string(), "dog-1.jpg".to_string()],
+      linked_media_files(&text)
+    );
+  }
+
+  #[test]
+  fn test_sanitize_embed_html() {
+    let player = r#"<iframe src="https://www.youtube.com/embed/abc" width="480" height="270" allowfullscreen=""></iframe>"#;
+    assert_eq!(Some(player.to_string()), sanitize_embed_html(player));
+
+    let tweet = r#"<blockquote><p>hi</p></blockquote><script src="https://platform.twitter.com/widgets.js"></script>"#;
+    assert_eq!(
+      Some("<blockquote><p>hi</p></blockquote>".to_string()),
+      sanitize_embed_html(tweet)
+    );
+
+    assert_eq!(
+      Some(r#"<iframe width="480"></iframe>"#.to_string()),
+      sanitize_embed_html(
+        r#"<iframe src="http://www.youtube.com/embed/abc" width="480"></iframe>"#
+      )
+    );
+    assert_eq!(
+      Some("<iframe></iframe>".to_string()),
+      sanitize_embed_html(r#"<iframe src="https://evil.com/player" onload="alert(1)"></iframe>"#)
+    );
+    assert_eq!(None, sanitize_embed_html("<script>alert(1)</script>"));
+  }
+
+  #[test]
+  fn test_strip_jpeg_metadata() {
+    let jfif: &[u8] = &[0xFF, 0xE0, 0, 4, 0, 0];
+    // Only the orientation, which is put back the same
+    let exif = [
+      &[0xFF, 0xE1, 0, 34][..],
+      b"Exif\0\0MM\0\x2a\0\0\0\x08",
+      &[0, 1, 0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0, 0, 0, 0, 0],
+    ]
+    .concat();
+    let comment: &[u8] = &[0xFF, 0xFE, 0, 6, b'h', b'o', b'm', b'e'];
+    let scan: &[u8] = &[0xFF, 0xDA, 0, 2, 1, 2, 3, 0xFF, 0xD9];
+    let jpeg = [&[0xFF, 0xD8][..], jfif, &exif[..], comment, scan].concat();
+
+    let config = UploadConfig {
+      max_size: 1024,
+      max_attachment_size: 1024,
+      allowed_types: vec!["image/jpeg".into()],
+      max_per_day: None,
+      reencode: false,
+      max_dimension: 256,
+      webp: false,
+      webp_quality: 80.0,
+    };
+    let stripped = process_image(jpeg, &config).unwrap();
+    assert_eq!(
+      [&[0xFF, 0xD8][..], jfif, &exif[..], scan].concat(),
+      stripped.data
+    );
+  }
+
+  #[test]
+  fn test_page_from_peertube_video() {
+    let video = json!({
+      "type": "Video",
+      "id": "https://tube.example/videos/watch/1",
+      "name": "A video",
+      "attributedTo": [
+        {"type": "Person", "id": "https://tube.example/accounts/someone"},
+        {"type": "Group", "id": "https://tube.example/video-channels/channel"}
+      ],
+      "to": ["https://www.w3.org/ns/activitystreams#Public"],
+      "url": [
+        {"type": "Link", "mediaType": "text/html", "href": "https://tube.example/videos/watch/1"},
+        {"type": "Link", "mediaType": "video/mp4", "href": "https://tube.example/static/1.mp4"}
+      ],
+      "commentsEnabled": true,
+      "sensitive": false
+    });
+
+    let page = serde_json::to_value(page_from_media_object(video).unwrap()).unwrap();
+    assert_eq!(json!("Page"), page["type"]);
+    assert_eq!(json!("A video"), page["summary"]);
+    assert_eq!(
+      json!("https://tube.example/accounts/someone"),
+      page["attributedTo"]
+    );
+    assert_eq!(
+      json!("https://tube.example/video-channels/channel"),
+      page["to"]
+    );
+    assert_eq!(
+      json!({"type": "Document", "url": "https://tube.example/static/1.mp4", "mediaType": "video/mp4"}),
+      page["attachment"][0]
+    );
+  }
+
+  #[test]
+  fn test_sign_webhook_payload() {
+    assert_eq!(
+      "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8",
+      sign_webhook_payload("key", "The quick brown fox jumps over the lazy dog").unwrap()
+    );
+  }
+
+  #[test]
+  fn test_webhook_public_ip() {
+    let public = |ip: &str| is_public_ip(&ip.parse().unwrap());
+    assert!(public("93.184.216.34"));
+    assert!(public("2606:2800:220:1:248:1893:25c8:1946"));
+    assert!(!public("127.0.0.1"));
+    assert!(!public("10.1.2.3"));
+    assert!(!public("192.168.0.1"));
+    assert!(!public("169.254.169.254"));
+    assert!(!public("100.64.0.1"));
+    assert!(!public("0.0.0.0"));
+    assert!(!public("::1"));
+    assert!(!public("fd00::1"));
+    assert!(!public("fe80::1"));
+    assert!(!public("::ffff:127.0.0.1"));
+  }
+
+  #[test]
+  fn test_outbox_object_content() {
+    let note = json!({
+      "type": "Note",
+      "content": "<p><strong>bold</strong></p>",
+      "source": { "content": "**bold**", "mediaType": "text/markdown" }
+    });
+    assert_eq!(Some("**bold**".to_string()), object_content(&note));
+
+    let note = json!({ "type": "Note", "content": "plain" });
+    assert_eq!(Some("plain".to_string()), object_content(&note));
+
+    let page = json!({ "type": "Page", "name": "A link" });
+    assert_eq!(None, object_content(&page));
+  }
+
+  #[test]
+  fn test_mastodon_status_ids() {
+    assert_eq!("84", StatusId::Post(42).to_string());
+    assert_eq!("85", StatusId::Comment(42).to_string());
+    assert_eq!(Some(StatusId::Post(42)), StatusId::parse("84"));
+    assert_eq!(Some(StatusId::Comment(42)), StatusId::parse("85"));
+    assert_eq!(
+      Some(StatusId::Comment(i32::MAX)),
+      StatusId::parse(&StatusId::Comment(i32::MAX).to_string())
+    );
+    assert_eq!(None, StatusId::parse("-2"));
+    assert_eq!(None, StatusId::parse("9999999999"));
+    assert_eq!(None, StatusId::parse("abc"));
+  }
+
+  #[test]
+  fn test_openapi_document_has_every_route() {
+    let document = openapi_document();
+    let operation_ids: Vec<&str> = document["paths"]
+      .as_object()
+      .unwrap()
+      .values()
+      .flat_map(|path_item| path_item.as_object().unwrap().values())
+      .map(|operation| operation["operationId"].as_str().unwrap())
+      .collect();
+
+    let routes = include_str!("routes/api.rs");
+    let route_re = regex::Regex::new(r"route_(get|post)::<(\w+)>").unwrap();
+    let routed: Vec<&str> = route_re
+      .captures_iter(routes)
+      .map(|captures| captures.get(2).unwrap().as_str())
+      .collect();
+    for name in &routed {
+      assert!(operation_ids.contains(name), "{} is missing", name);
+    }
+    assert_eq!(routed.len(), operation_ids.len());
+
+    assert_eq!(
+      json!({"$ref": "#/components/schemas/PostResponse"}),
+      document["paths"]["/api/v1/post"]["post"]["responses"]["200"]["content"]["application/json"]
+        ["schema"]
+    );
+    assert!(document["components"]["schemas"]["PostView"]["properties"]["name"].is_object());
+  }
+
+  #[test]
+  fn test_every_operation_is_routed() {
+    let ops = include_str!("websocket/mod.rs");
+    let ops = &ops[ops.find("pub enum UserOperation {").unwrap()..];
+    let ops = &ops[..ops.find('}').unwrap()];
+    let routes = include_str!("routes/api.rs");
+
+    // Sent by the server, or only about the websocket session
+    let not_routed = [
+      "CommentScore",
+      "PostScore",
+      "UsersOnline",
+      "AutomodHit",
+      "UserJoin",
+      "Subscribe",
+      "Unsubscribe",
+      "ReAuth",
+    ];
+    for line in ops.lines().skip(1) {
+      let op = line.trim().trim_end_matches(',');
+      if !op.is_empty() && !not_routed.contains(&op) {
+        let route = format!("::<{}>", op);
+        assert!(routes.contains(&route), "{} has no route", op);
+      }
+    }
+  }
+
+  #[test]
+  fn test_ldap_user_filter() {
+    assert_eq!("(uid=alice)", ldap_user_filter("(uid={username})", "alice"));
+    assert_eq!(
+      "(uid=a\\2a\\29\\28uid=\\2a)",
+      ldap_user_filter("(uid={username})", "a*)(uid=*")
+    );
+  }
+
+  #[test]
+  fn test_hook_response() {
+    let allow: HookResponse<String> = serde_json::from_value(json!({"action": "allow"})).unwrap();
+    let reject: HookResponse<String> =
+      serde_json::from_value(json!({"action": "reject", "reason": "spam"})).unwrap();
+    let modify: HookResponse<String> =
+      serde_json::from_value(json!({"action": "modify", "data": "changed"})).unwrap();
+
+    assert_eq!(HookResponse::Allow, allow);
+    assert_eq!(
+      HookResponse::Reject {
+        reason: Some("spam".into())
+      },
+      reject
+    );
+    assert_eq!(
+      HookResponse::Modify {
+        data: "changed".to_string()
+      },
+      modify
+    );
+  }
+
+  #[test]
+  fn test_oembed_post_id() {
+    let hostname = Settings::get().hostname;
+    let post_url = format!("https://{}/post/12", hostname);
+    let comment_url = format!("https://{}/post/12/comment/3", hostname);
+
+    assert_eq!(Some(12), oembed_post_id(&post_url));
+    assert_eq!(None, oembed_post_id(&comment_url));
+    assert_eq!(None, oembed_post_id("https://elsewhere.example/post/12"));
+  }
+
+  #[test]
+  fn test_atom_feed_escaping() {
+    let mut item = ItemBuilder::default();
+    item.title("Fish & chips <3".to_string());
+    item.description("<p>1 &lt; 2 &amp; more</p>".to_string());
+    let guid = GuidBuilder::default()
+      .permalink(true)
+      .value("https://example.com/post/1".to_string())
+      .build();
+    item.guid(guid.unwrap());
+    let channel = ChannelBuilder::default()
+      .title("Tom & Jerry")
+      .link("https://example.com/c/a&b")
+      .description("Cats & mice")
+      .items(vec![item.build().unwrap()])
+      .build()
+      .unwrap();
+
+    // Read back, the text is what it was only if it was escaped exactly once
+    let atom = channel_to_atom(&channel).to_string();
+    let feed = atom_syndication::Feed::from_str(&atom).unwrap();
+    let entry = &feed.entries()[0];
+
+    assert!(atom.contains("<title>Tom &amp; Jerry</title>"));
+    assert_eq!("Tom & Jerry", feed.title());
+    assert_eq!("https://example.com/c/a&b", feed.id());
+    assert_eq!("Fish & chips <3", entry.title());
+    assert_eq!(
+      Some("<p>1 &lt; 2 &amp; more</p>"),
+      entry.content().and_then(|c| c.value())
+    );
+  }
+
+  #[test]
+  fn test_sitemap_page_xml() {
+    let entries = vec![SitemapEntry {
+      url: "https://example.com/c/a&b".into(),
+      lastmod: chrono::NaiveDate::from_ymd(2020, 8, 29).and_hms(10, 22, 33),
+    }];
+    let xml = sitemap_page_xml(&entries);
+
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>"));
+    assert!(xml.contains(
+      "<url><loc>https://example.com/c/a&amp;b</loc><lastmod>2020-08-29T10:22:33Z</lastmod></url>"
+    ));
+  }
+
+  #[test]
+  fn test_parse_reddit_export() {
+    let data = r#"{"id": "p1", "title": "A post", "is_self": true, "selftext": "[removed]", "created_utc": 1590000000, "score": 12}
+{"kind": "t1", "data": {"id": "c2", "link_id": "t3_p1", "parent_id": "t1_c1", "body": "A reply", "created_utc": 1590000100, "score": -2}}
+{"id": "c1", "link_id": "t3_p1", "parent_id": "t3_p1", "body": "[deleted]", "created_utc": "1590000200.0", "score": 3}
+{"id": "c3", "link_id": "t3_gone", "parent_id": "t3_gone", "body": "Elsewhere", "created_utc": 1590000300}
+"#;
+    let imported = parse_reddit_export(data).unwrap();
+
+    assert_eq!(1, imported.posts.len());
+    assert_eq!(None, imported.posts[0].body);
+    assert_eq!(12, imported.posts[0].score);
+    // The reply is older than its parent, but comes after it
+    let comments = &imported.comments;
+    let ids: Vec<&str> = comments.iter().map(|c| c.source_id.as_str()).collect();
+    assert_eq!(vec!["c1", "c2"], ids);
+    assert!(comments[0].deleted);
+    assert_eq!(Some("c1"), comments[1].parent_source_id.as_deref());
+    assert_eq!(-2, comments[1].score);
+  }
+
+  #[test]
+  fn test_parse_kbin_export() {
+    let data = json!({
+      "items": [{
+        "entryId": 7,
+        "title": "An entry",
+        "url": "https://example.com",
+        "createdAt": "2023-06-01T12:00:00+00:00",
+        "uv": 5,
+        "dv": 1,
+        "favourites": 2,
+        "comments": [{
+          "commentId": 70,
+          "body": "A comment",
+          "createdAt": "2023-06-01T13:00:00+00:00",
+          "children": [{
+            "commentId": 71,
+            "body": "A reply",
+            "visibility": "trashed",
+            "createdAt": "2023-06-01T14:00:00+00:00",
+          }],
+        }],
+      }],
+    })
+    .to_string();
+    let imported = parse_kbin_export(&data).unwrap();
+
+    assert_eq!(1, imported.posts.len());
+    assert_eq!("7", imported.posts[0].source_id);
+    assert_eq!(6, imported.posts[0].score);
+    let comments = &imported.comments;
+    assert_eq!(2, comments.len());
+    assert_eq!("7", comments[1].post_source_id);
+    assert_eq!(Some("70"), comments[1].parent_source_id.as_deref());
+    assert!(!comments[0].deleted);
+    assert!(comments[1].deleted);
+  }
+
   // These helped with testing
   // #[test]
   // fn test_iframely() {
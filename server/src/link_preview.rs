@@ -0,0 +1,106 @@
+//! Fetches the previews of link posts again. The fetch made with the post fails when the site is
+//! slow or down, and the post would go without a title or thumbnail for good. Its creator or a mod
+//! can ask for another one, and the posts of the last day that got none are retried every hour.
+
+use crate::{
+  api::post::PostResponse,
+  apub::ApubObjectType,
+  blocking,
+  fetch_iframely_and_pictrs_data,
+  websocket::{
+    server::{ChatServer, SendPost},
+    UserOperation,
+  },
+  DbPool,
+  LemmyError,
+};
+use actix::Addr;
+use actix_web::client::Client;
+use lemmy_db::{
+  naive_now,
+  post::{Post, PostEmbedForm},
+  post_view::PostView,
+  user::User_,
+  Crud,
+};
+use tracing::error;
+
+/// How far back the posts without a preview are retried
+const MISSING_EMBED_DAYS: i64 = 1;
+
+/// How many of them are retried in one run, as each one is a few requests to other sites
+const MISSING_EMBED_BATCH: i64 = 50;
+
+/// Fetches the preview of the post's url again and stores what was found, leaving the rest as it
+/// was. Local posts go out to the other instances as an update from their creator. None when
+/// nothing was found.
+pub async fn refresh_post_embed(
+  pool: &DbPool,
+  client: &Client,
+  post: &Post,
+) -> Result<Option<Post>, LemmyError> {
+  let (embed_title, embed_description, embed_html, embed_type, thumbnail_url) =
+    fetch_iframely_and_pictrs_data(client, pool, post.url.to_owned()).await;
+  if embed_title.is_none()
+    && embed_description.is_none()
+    && embed_html.is_none()
+    && thumbnail_url.is_none()
+  {
+    return Ok(None);
+  }
+
+  let form = PostEmbedForm {
+    embed_title,
+    embed_description,
+    embed_html,
+    embed_type,
+    thumbnail_url,
+  };
+  let post_id = post.id;
+  let updated_post = blocking(pool, move |conn| Post::update_embed(conn, post_id, &form)).await??;
+
+  // Posts waiting for approval haven't federated yet
+  if updated_post.local && !updated_post.pending {
+    let creator_id = updated_post.creator_id;
+    let creator = blocking(pool, move |conn| User_::read(conn, creator_id)).await??;
+    updated_post.send_update(&creator, client, pool).await?;
+  }
+
+  Ok(Some(updated_post))
+}
+
+/// Retries the previews of the recent local link posts that didn't get one.
+pub async fn refresh_missing_embeds(
+  pool: &DbPool,
+  client: &Client,
+  chat_server: &Addr<ChatServer>,
+) -> Result<(), LemmyError> {
+  let since = naive_now() - chrono::Duration::days(MISSING_EMBED_DAYS);
+  let posts = blocking(pool, move |conn| {
+    Post::missing_embed_since(conn, since, MISSING_EMBED_BATCH)
+  })
+  .await??;
+
+  for post in posts {
+    let updated_post = match refresh_post_embed(pool, client, &post).await {
+      Ok(Some(updated_post)) => updated_post,
+      Ok(None) => continue,
+      Err(e) => {
+        error!("Couldn't refresh the preview of post {}: {}", post.id, e);
+        continue;
+      }
+    };
+
+    if !updated_post.pending {
+      let post_id = updated_post.id;
+      let post_view = blocking(pool, move |conn| PostView::read(conn, post_id, None)).await??;
+      chat_server.do_send(SendPost {
+        op: UserOperation::RefreshPostEmbed,
+        post: PostResponse { post: post_view },
+        my_id: None,
+      });
+    }
+  }
+
+  Ok(())
+}
@@ -4,16 +4,18 @@ extern crate diesel_migrations;
 #[macro_use]
 pub extern crate lazy_static;
 
-pub type DbPool = Pool<ConnectionManager<PgConnection>>;
-
 use crate::lemmy_server::actix_web::dev::Service;
 use actix::prelude::*;
+use actix_rt::signal::{
+  ctrl_c,
+  unix::{signal, SignalKind},
+};
 use actix_web::{
   body::Body,
   client::Client,
-  dev::{ServiceRequest, ServiceResponse},
+  dev::{Server, ServiceRequest, ServiceResponse},
   http::{
-    header::{CACHE_CONTROL, CONTENT_TYPE},
+    header::{HeaderName, CACHE_CONTROL, CONTENT_TYPE},
     HeaderValue,
   },
   *,
@@ -22,18 +24,58 @@ use diesel::{
   r2d2::{ConnectionManager, Pool},
   PgConnection,
 };
-use lemmy_db::get_database_url_from_env;
+use futures::future::{select, Either};
+use lemmy_db::{get_database_url_from_env, newest_migration};
 use lemmy_server::{
+  api::{
+    federation::load_federation_overrides,
+    site::load_vote_count_settings,
+    word_filter::load_word_filters,
+  },
   blocking,
   code_migrations::run_advanced_migrations,
-  rate_limit::{rate_limiter::RateLimiter, RateLimit},
-  routes::{api, federation, feeds, index, nodeinfo, webfinger},
-  websocket::server::*,
+  job_queue::{start_job_queue, stop_job_queue},
+  metrics::observe_request,
+  rate_limit::{
+    load_ip_bans,
+    load_rate_limit_overrides,
+    rate_limiter::RateLimiter,
+    redis_backend::RedisRateLimiter,
+    RateLimit,
+  },
+  routes::{
+    api,
+    federation,
+    feeds,
+    health::set_expected_migration,
+    image_proxy,
+    index,
+    mastodon,
+    media,
+    metrics,
+    nodeinfo,
+    sitemap,
+    webfinger,
+  },
+  websocket::{pubsub::PubSub, server::*},
+  DbPool,
   LemmyError,
 };
-use lemmy_utils::{settings::Settings, CACHE_CONTROL_REGEX};
-use std::sync::Arc;
+use lemmy_utils::{
+  i18n::{get_translations_location, load_translations},
+  settings::Settings,
+  CACHE_CONTROL_REGEX,
+};
+use std::{
+  path::Path,
+  sync::Arc,
+  time::{Duration, Instant},
+};
 use tokio::sync::Mutex;
+use tracing::{error, field, info, info_span, warn};
+use tracing_futures::Instrument;
+use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
 
 lazy_static! {
   // static ref CACHE_CONTROL_VALUE: String = format!("public, max-age={}", 365 * 24 * 60 * 60);
@@ -43,10 +85,21 @@ lazy_static! {
 
 embed_migrations!();
 
+/// How often every server process reads the settings the admins change again. A process loads
+/// them right away after a change it made itself, the others only here.
+const ADMIN_SETTINGS_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
 #[actix_rt::main]
 async fn main() -> Result<(), LemmyError> {
-  env_logger::init();
   let settings = Settings::get();
+  init_logging(settings.logging.json);
+
+  // Without the translations, the emails and errors show their keys
+  let translations = get_translations_location();
+  match load_translations(Path::new(&translations)) {
+    Ok(langs) => info!("Loaded the translations for {}", langs.join(", ")),
+    Err(e) => error!("Couldn't load the translations: {}", e),
+  }
 
   // Set up the r2d2 connection pool
   let db_url = match get_database_url_from_env() {
@@ -54,26 +107,112 @@ async fn main() -> Result<(), LemmyError> {
     Err(_) => settings.get_database_url(),
   };
   let manager = ConnectionManager::<PgConnection>::new(&db_url);
-  let pool = Pool::builder()
+  let primary = Pool::builder()
     .max_size(settings.database.pool_size)
     .build(manager)
     .unwrap_or_else(|_| panic!("Error connecting to {}", db_url));
 
+  // The replicas only take reads, so they don't need the migrations. One that's down is left out,
+  // and its reads go to the others.
+  let replicas = settings
+    .get_read_replica_urls()
+    .iter()
+    .filter_map(|replica_url| {
+      let manager = ConnectionManager::<PgConnection>::new(replica_url);
+      match Pool::builder()
+        .max_size(settings.database.pool_size)
+        .build(manager)
+      {
+        Ok(replica) => Some(replica),
+        Err(e) => {
+          warn!(
+            "Couldn't connect to the read replica {}, leaving it out: {}",
+            replica_url, e
+          );
+          None
+        }
+      }
+    })
+    .collect();
+  let pool = DbPool::new(primary, replicas);
+
   // Run the migrations from code
-  blocking(&pool, move |conn| {
+  let newest_migration = blocking(&pool, move |conn| {
     embedded_migrations::run(conn)?;
     run_advanced_migrations(conn)?;
-    Ok(()) as Result<(), LemmyError>
+    Ok(newest_migration(conn)?) as Result<Option<String>, LemmyError>
   })
   .await??;
+  set_expected_migration(newest_migration);
+
+  // Generate the web push keys up front, so they're the same for every subscription
+  if settings.web_push.enabled {
+    settings.get_vapid_keypair()?;
+  }
+
+  // Load the word filters, which the admins and mods can change later on
+  blocking(&pool, move |conn| load_word_filters(conn)).await??;
 
-  // Set up the rate limiter
+  // Federation can be switched on and off by the admins, over what the config file says
+  blocking(&pool, move |conn| load_federation_overrides(conn)).await??;
+
+  // The admins can hide the downvote counts of the posts and comments
+  blocking(&pool, move |conn| load_vote_count_settings(conn)).await??;
+
+  // Set up the rate limiter, with the limits the admins changed
+  blocking(&pool, move |conn| load_rate_limit_overrides(conn)).await??;
+  blocking(&pool, move |conn| load_ip_bans(conn)).await??;
+  // The buckets are shared with the other server processes through Redis, if there is one
+  let redis = match &settings.rate_limit.redis_url {
+    Some(redis_url) => Some(RedisRateLimiter::connect(redis_url).await?),
+    None => None,
+  };
   let rate_limiter = RateLimit {
     rate_limiter: Arc::new(Mutex::new(RateLimiter::default())),
+    redis,
   };
 
+  // What the admins change through the other server processes is picked up every so often
+  actix_rt::spawn(refresh_admin_settings(pool.clone()));
+
   // Set up websocket server
-  let server = ChatServer::startup(pool.clone(), rate_limiter.clone(), Client::default()).start();
+  let pubsub = match &settings.websocket.redis_url {
+    Some(redis_url) => Some(PubSub::connect(redis_url).await?),
+    None => None,
+  };
+  let server = ChatServer::startup(
+    pool.clone(),
+    rate_limiter.clone(),
+    Client::default(),
+    pubsub,
+  )
+  .start();
+
+  // Start the background workers, which also send the federated activities
+  start_job_queue(pool.clone(), Client::default(), server.clone()).await?;
+
+  // The metrics get an address of their own, if there is one
+  let metrics_server = match (settings.metrics.enabled, settings.metrics.bind) {
+    (true, Some(metrics_bind)) => {
+      println!(
+        "Starting metrics server at {}:{}",
+        metrics_bind, settings.metrics.port
+      );
+      let pool = pool.clone();
+      Some(
+        HttpServer::new(move || {
+          App::new()
+            .data(pool.clone())
+            .configure(metrics::config_private)
+        })
+        .workers(1)
+        .disable_signals()
+        .bind((metrics_bind, settings.metrics.port))?
+        .run(),
+      )
+    }
+    _ => None,
+  };
 
   println!(
     "Starting http server at {}:{}",
@@ -81,21 +220,29 @@ async fn main() -> Result<(), LemmyError> {
   );
 
   // Create Http server with websocket support
-  HttpServer::new(move || {
+  let chat_server = server.clone();
+  let http_server = HttpServer::new(move || {
     let settings = Settings::get();
     let rate_limiter = rate_limiter.clone();
     App::new()
       .wrap_fn(add_cache_headers)
+      .wrap_fn(record_latency)
       .wrap(middleware::Logger::default())
+      .wrap_fn(trace_request)
       .data(pool.clone())
       .data(server.clone())
       .data(Client::default())
       // The routes
+      .configure(|cfg| mastodon::config(cfg, &rate_limiter))
       .configure(move |cfg| api::config(cfg, &rate_limiter))
-      .configure(federation::config)
+      .configure(|cfg| federation::config(cfg, &rate_limiter))
       .configure(feeds::config)
+      .configure(image_proxy::config)
       .configure(index::config)
+      .configure(media::config)
+      .configure(metrics::config)
       .configure(nodeinfo::config)
+      .configure(sitemap::config)
       .configure(webfinger::config)
       // static files
       .service(actix_files::Files::new(
@@ -107,13 +254,156 @@ async fn main() -> Result<(), LemmyError> {
         settings.front_end_dir + "/documentation",
       ))
   })
+  .disable_signals()
   .bind((settings.bind, settings.port))?
-  .run()
-  .await?;
+  .run();
+
+  actix_rt::spawn(shutdown_on_signal(http_server.clone(), chat_server));
+  actix_rt::spawn(reload_on_sighup());
+  http_server.await?;
+
+  // The requests are answered, so what's left is the work of the job queue
+  stop_job_queue(Duration::from_secs(settings.jobs.shutdown_timeout_seconds)).await;
+
+  if let Some(metrics_server) = metrics_server {
+    metrics_server.stop(true).await;
+  }
+
+  // Nothing uses the database anymore, and the pools close their connections as the runtime
+  // drops them
+  info!("Shut down");
 
   Ok(())
 }
 
+/// On SIGTERM or ctrl-c, the websocket clients are told to connect again in a moment, and the
+/// server stops taking connections and answers the requests it already has.
+async fn shutdown_on_signal(http_server: Server, chat_server: Addr<ChatServer>) {
+  match signal(SignalKind::terminate()) {
+    Ok(mut terminate) => {
+      let signalled = select(Box::pin(ctrl_c()), Box::pin(terminate.recv())).await;
+      if let Either::Left((Err(e), _)) = signalled {
+        error!("Couldn't listen for ctrl-c: {}", e);
+      }
+    }
+    Err(e) => {
+      error!("Couldn't listen for SIGTERM: {}", e);
+      if let Err(e) = ctrl_c().await {
+        error!("Couldn't listen for ctrl-c: {}", e);
+        return;
+      }
+    }
+  }
+
+  info!("Shutting down");
+  if let Err(e) = chat_server.send(Shutdown).await {
+    error!("Couldn't close the websocket sessions: {}", e);
+  }
+  http_server.stop(true).await;
+}
+
+/// On SIGHUP, the config file and environment are read again. An invalid config is logged and
+/// the one from before is kept.
+async fn reload_on_sighup() {
+  let mut hangup = match signal(SignalKind::hangup()) {
+    Ok(hangup) => hangup,
+    Err(e) => {
+      error!("Couldn't listen for SIGHUP: {}", e);
+      return;
+    }
+  };
+
+  while hangup.recv().await.is_some() {
+    match Settings::reload() {
+      Ok(()) => info!("Reloaded the config"),
+      Err(e) => error!("Couldn't reload the config, keeping the old one: {}", e),
+    }
+  }
+}
+
+/// Loads the settings the admins change over and over, for the changes saved by the other
+/// server processes sharing the database.
+async fn refresh_admin_settings(pool: DbPool) {
+  loop {
+    actix_rt::time::delay_for(ADMIN_SETTINGS_REFRESH_INTERVAL).await;
+    let refreshed = blocking(&pool, move |conn| {
+      load_word_filters(conn)?;
+      load_federation_overrides(conn)?;
+      load_vote_count_settings(conn)?;
+      load_rate_limit_overrides(conn)?;
+      load_ip_bans(conn)?;
+      Ok(()) as Result<(), LemmyError>
+    })
+    .await;
+    if let Err(e) = refreshed.and_then(|refreshed| refreshed) {
+      error!("Couldn't refresh the admin settings: {}", e);
+    }
+  }
+}
+
+/// Logs to stdout, at the levels RUST_LOG asks for. The log macros of the other crates end up
+/// here too.
+fn init_logging(json: bool) {
+  let subscriber = tracing_subscriber::fmt().with_env_filter(EnvFilter::from_default_env());
+  if json {
+    subscriber.json().init();
+  } else {
+    subscriber.init();
+  }
+}
+
+/// Gives every request a span with an id of its own, which is also sent back in the
+/// X-Request-Id header. The inboxes add the ids of the actor and activity to it.
+fn trace_request<S>(
+  req: ServiceRequest,
+  srv: &mut S,
+) -> impl Future<Output = Result<ServiceResponse, Error>>
+where
+  S: Service<Request = ServiceRequest, Response = ServiceResponse<Body>, Error = Error>,
+{
+  let request_id = Uuid::new_v4().to_string();
+  let span = info_span!(
+    "request",
+    request_id = %request_id,
+    method = %req.method(),
+    path = %req.path(),
+    actor_id = field::Empty,
+    activity_id = field::Empty,
+  );
+  let fut = span.in_scope(|| srv.call(req));
+  async move {
+    let mut res = fut.instrument(span).await?;
+    if let Ok(header_val) = HeaderValue::from_str(&request_id) {
+      res
+        .headers_mut()
+        .insert(HeaderName::from_static("x-request-id"), header_val);
+    }
+    Ok(res)
+  }
+}
+
+/// Times the requests for the metrics, by the route they matched.
+fn record_latency<S>(
+  req: ServiceRequest,
+  srv: &mut S,
+) -> impl Future<Output = Result<ServiceResponse, Error>>
+where
+  S: Service<Request = ServiceRequest, Response = ServiceResponse<Body>, Error = Error>,
+{
+  let start = Instant::now();
+  let method = req.method().to_string();
+  let fut = srv.call(req);
+  async move {
+    let res = fut.await?;
+    let route = res
+      .request()
+      .match_pattern()
+      .unwrap_or_else(|| "unmatched".to_owned());
+    observe_request(&method, &route, start.elapsed());
+    Ok(res)
+  }
+}
+
 fn add_cache_headers<S>(
   req: ServiceRequest,
   srv: &mut S,
@@ -0,0 +1,562 @@
+//! The entities of the Mastodon client API, made from posts, comments and users, so that the
+//! Mastodon apps can browse the instance. Posts and comments are both statuses, and share one id
+//! space: posts get the even ids and comments the odd ones. Replies go under a post or comment,
+//! as a status of its own would need a community.
+
+use crate::{digest::escape_html, media::media_url, version};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use lemmy_db::{
+  comment_view::CommentView,
+  post_view::PostView,
+  site_view::SiteView,
+  user_view::UserView,
+};
+use lemmy_utils::{is_image_url, markdown_to_html, settings::Settings};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// What a Mastodon status id points to
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StatusId {
+  Post(i32),
+  Comment(i32),
+}
+
+impl StatusId {
+  pub fn parse(id: &str) -> Option<Self> {
+    let id: i64 = id.parse().ok()?;
+    if id < 0 || id / 2 > i32::MAX as i64 {
+      return None;
+    }
+    let lemmy_id = (id / 2) as i32;
+    if id % 2 == 0 {
+      Some(StatusId::Post(lemmy_id))
+    } else {
+      Some(StatusId::Comment(lemmy_id))
+    }
+  }
+}
+
+impl ToString for StatusId {
+  fn to_string(&self) -> String {
+    match self {
+      StatusId::Post(id) => (*id as i64 * 2).to_string(),
+      StatusId::Comment(id) => (*id as i64 * 2 + 1).to_string(),
+    }
+  }
+}
+
+#[derive(Serialize, Clone)]
+pub struct Account {
+  pub id: String,
+  pub username: String,
+  /// The name with the instance for remote users, like `name@example.com`
+  pub acct: String,
+  pub display_name: String,
+  pub locked: bool,
+  pub bot: bool,
+  pub created_at: DateTime<Utc>,
+  pub note: String,
+  pub url: String,
+  pub avatar: String,
+  pub avatar_static: String,
+  pub header: String,
+  pub header_static: String,
+  pub followers_count: i64,
+  pub following_count: i64,
+  pub statuses_count: i64,
+  pub emojis: Vec<()>,
+  pub fields: Vec<()>,
+  /// Only in the account of the user themselves
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub source: Option<Source>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct Source {
+  pub privacy: &'static str,
+  pub sensitive: bool,
+  pub language: Option<String>,
+  pub note: String,
+  pub fields: Vec<()>,
+}
+
+#[derive(Serialize)]
+pub struct Status {
+  pub id: String,
+  pub uri: String,
+  pub url: String,
+  pub created_at: DateTime<Utc>,
+  pub edited_at: Option<DateTime<Utc>>,
+  pub account: Account,
+  pub content: String,
+  pub visibility: &'static str,
+  pub sensitive: bool,
+  pub spoiler_text: String,
+  pub in_reply_to_id: Option<String>,
+  pub in_reply_to_account_id: Option<String>,
+  pub replies_count: i64,
+  pub reblogs_count: i64,
+  pub favourites_count: i64,
+  pub favourited: bool,
+  pub reblogged: bool,
+  pub muted: bool,
+  pub bookmarked: bool,
+  pub pinned: bool,
+  pub reblog: Option<()>,
+  pub card: Option<Card>,
+  pub media_attachments: Vec<Attachment>,
+  pub mentions: Vec<()>,
+  pub tags: Vec<()>,
+  pub emojis: Vec<()>,
+  pub poll: Option<()>,
+  pub language: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Card {
+  pub url: String,
+  pub title: String,
+  pub description: String,
+  #[serde(rename = "type")]
+  pub type_: &'static str,
+  pub image: Option<String>,
+  pub html: String,
+}
+
+#[derive(Serialize)]
+pub struct Attachment {
+  pub id: String,
+  #[serde(rename = "type")]
+  pub type_: &'static str,
+  pub url: String,
+  pub preview_url: String,
+  pub remote_url: Option<String>,
+  pub description: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Context {
+  pub ancestors: Vec<Status>,
+  pub descendants: Vec<Status>,
+}
+
+#[derive(Serialize)]
+pub struct Instance {
+  pub uri: String,
+  pub title: String,
+  pub short_description: String,
+  pub description: String,
+  pub email: String,
+  pub version: String,
+  pub urls: InstanceUrls,
+  pub stats: InstanceStats,
+  pub thumbnail: Option<String>,
+  pub languages: Vec<String>,
+  pub registrations: bool,
+  pub approval_required: bool,
+  pub invites_enabled: bool,
+}
+
+#[derive(Serialize)]
+pub struct InstanceUrls {
+  pub streaming_api: String,
+}
+
+#[derive(Serialize)]
+pub struct InstanceStats {
+  pub user_count: i64,
+  pub status_count: i64,
+  pub domain_count: i64,
+}
+
+/// The apps aren't kept, as any of them can log in. The client ids are only made up for the apps
+/// that expect them.
+#[derive(Serialize)]
+pub struct Application {
+  pub id: String,
+  pub name: String,
+  pub website: Option<String>,
+  pub redirect_uri: String,
+  pub client_id: String,
+  pub client_secret: String,
+}
+
+#[derive(Serialize)]
+pub struct Token {
+  pub access_token: String,
+  pub token_type: &'static str,
+  pub scope: String,
+  pub created_at: i64,
+}
+
+/// Replies and mentions are both mentions
+#[derive(Serialize)]
+pub struct Notification {
+  pub id: String,
+  #[serde(rename = "type")]
+  pub type_: &'static str,
+  pub created_at: DateTime<Utc>,
+  pub account: Account,
+  pub status: Status,
+}
+
+/// The part of a post or comment that's about its creator
+pub struct Creator<'a> {
+  pub id: i32,
+  pub name: &'a str,
+  pub display_name: &'a Option<String>,
+  pub actor_id: &'a str,
+  pub local: bool,
+  pub avatar: &'a Option<String>,
+  pub bot: bool,
+  pub published: NaiveDateTime,
+}
+
+impl<'a> Creator<'a> {
+  fn to_account(&self) -> Account {
+    let acct = if self.local {
+      self.name.to_owned()
+    } else {
+      let host = url::Url::parse(self.actor_id)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_owned()))
+        .unwrap_or_default();
+      format!("{}@{}", self.name, host)
+    };
+    let avatar = self
+      .avatar
+      .as_ref()
+      .map(|a| image_url(a))
+      .unwrap_or_default();
+    Account {
+      id: self.id.to_string(),
+      username: self.name.to_owned(),
+      acct,
+      display_name: self
+        .display_name
+        .to_owned()
+        .unwrap_or_else(|| self.name.to_owned()),
+      locked: false,
+      bot: self.bot,
+      created_at: to_utc(self.published),
+      note: String::new(),
+      url: self.actor_id.to_owned(),
+      avatar_static: avatar.to_owned(),
+      avatar,
+      header: String::new(),
+      header_static: String::new(),
+      followers_count: 0,
+      following_count: 0,
+      statuses_count: 0,
+      emojis: Vec::new(),
+      fields: Vec::new(),
+      source: None,
+    }
+  }
+}
+
+pub fn user_account(user: &UserView) -> Account {
+  let creator = Creator {
+    id: user.id,
+    name: &user.name,
+    display_name: &user.display_name,
+    actor_id: &user.actor_id,
+    local: user.local,
+    avatar: &user.avatar,
+    bot: user.bot_account,
+    published: user.published,
+  };
+  let banner = user
+    .banner
+    .as_ref()
+    .map(|b| image_url(b))
+    .unwrap_or_default();
+  Account {
+    note: user
+      .bio
+      .as_ref()
+      .map(|bio| markdown_to_html(bio))
+      .unwrap_or_default(),
+    header: banner.to_owned(),
+    header_static: banner,
+    statuses_count: user.number_of_posts + user.number_of_comments,
+    ..creator.to_account()
+  }
+}
+
+/// The account of the user themselves, with what they write it from
+pub fn own_account(user: &UserView, show_nsfw: bool) -> Account {
+  let account = user_account(user);
+  Account {
+    source: Some(Source {
+      privacy: "public",
+      sensitive: show_nsfw,
+      language: None,
+      note: user.bio.to_owned().unwrap_or_default(),
+      fields: Vec::new(),
+    }),
+    ..account
+  }
+}
+
+pub fn instance(site: &SiteView) -> Instance {
+  let hostname = Settings::get().hostname;
+  let streaming_protocol = if lemmy_utils::get_apub_protocol_string() == "https" {
+    "wss"
+  } else {
+    "ws"
+  };
+  let description = site.description.to_owned().unwrap_or_default();
+  Instance {
+    uri: hostname.to_owned(),
+    title: site.name.to_owned(),
+    short_description: description.to_owned(),
+    description: markdown_to_html(&description),
+    email: String::new(),
+    version: format!("3.0.0 (compatible; Lemmy {})", version::VERSION),
+    urls: InstanceUrls {
+      streaming_api: format!("{}://{}", streaming_protocol, hostname),
+    },
+    stats: InstanceStats {
+      user_count: site.number_of_users,
+      status_count: site.number_of_posts + site.number_of_comments,
+      domain_count: 0,
+    },
+    thumbnail: None,
+    languages: Vec::new(),
+    registrations: site.open_registration,
+    approval_required: false,
+    invites_enabled: false,
+  }
+}
+
+/// A post is a status of its title, the text and the link. Images and the audio and video uploads
+/// are attachments, and other links get their preview as a card.
+pub fn post_status(post: &PostView) -> Status {
+  let creator = Creator {
+    id: post.creator_id,
+    name: &post.creator_name,
+    display_name: &post.creator_display_name,
+    actor_id: &post.creator_actor_id,
+    local: post.creator_local,
+    avatar: &post.creator_avatar,
+    bot: post.creator_bot_account,
+    published: post.creator_published,
+  };
+
+  let mut content = format!("<p><strong>{}</strong></p>", escape_html(&post.name));
+  if let Some(url) = &post.url {
+    content.push_str(&format!("<p><a href=\"{0}\">{0}</a></p>", escape_html(url)));
+  }
+  if let Some(body) = &post.body {
+    content.push_str(&markdown_to_html(body));
+  }
+
+  let mut media_attachments = Vec::new();
+  let mut card = None;
+  if let Some(url) = &post.url {
+    let type_ = match &post.url_media_type {
+      Some(t) if t.starts_with("video/") => Some("video"),
+      Some(t) if t.starts_with("audio/") => Some("audio"),
+      _ if is_image_url(url) => Some("image"),
+      _ => None,
+    };
+    match type_ {
+      Some(type_) => media_attachments.push(Attachment {
+        id: post.id.to_string(),
+        type_,
+        url: url.to_owned(),
+        preview_url: post
+          .thumbnail_url
+          .as_ref()
+          .map(|t| image_url(t))
+          .unwrap_or_else(|| url.to_owned()),
+        remote_url: None,
+        description: None,
+      }),
+      None => {
+        card = Some(Card {
+          url: url.to_owned(),
+          title: post
+            .embed_title
+            .to_owned()
+            .unwrap_or_else(|| post.name.to_owned()),
+          description: post.embed_description.to_owned().unwrap_or_default(),
+          type_: if post.embed_html.is_some() {
+            "video"
+          } else {
+            "link"
+          },
+          image: post.thumbnail_url.as_ref().map(|t| image_url(t)),
+          html: post.embed_html.to_owned().unwrap_or_default(),
+        })
+      }
+    }
+  }
+
+  Status {
+    id: StatusId::Post(post.id).to_string(),
+    uri: post.ap_id.to_owned(),
+    url: local_url(&format!("post/{}", post.id)),
+    created_at: to_utc(post.published),
+    edited_at: post.updated.map(to_utc),
+    account: creator.to_account(),
+    content,
+    visibility: "public",
+    sensitive: post.nsfw || post.community_nsfw,
+    spoiler_text: if post.nsfw || post.community_nsfw {
+      "NSFW".to_owned()
+    } else {
+      String::new()
+    },
+    in_reply_to_id: None,
+    in_reply_to_account_id: None,
+    replies_count: post.number_of_comments,
+    reblogs_count: 0,
+    favourites_count: post.upvotes,
+    favourited: post.my_vote == Some(1),
+    reblogged: false,
+    muted: false,
+    bookmarked: post.saved == Some(true),
+    pinned: post.stickied,
+    reblog: None,
+    card,
+    media_attachments,
+    mentions: Vec::new(),
+    tags: Vec::new(),
+    emojis: Vec::new(),
+    poll: None,
+    language: None,
+  }
+}
+
+/// A comment is a reply to its parent, or to its post when it's a top level one.
+pub fn comment_status(comment: &CommentView) -> Status {
+  let creator = Creator {
+    id: comment.creator_id,
+    name: &comment.creator_name,
+    display_name: &comment.creator_display_name,
+    actor_id: &comment.creator_actor_id,
+    local: comment.creator_local,
+    avatar: &comment.creator_avatar,
+    bot: comment.creator_bot_account,
+    published: comment.creator_published,
+  };
+  let in_reply_to = match comment.parent_id {
+    Some(parent_id) => StatusId::Comment(parent_id),
+    None => StatusId::Post(comment.post_id),
+  };
+
+  Status {
+    id: StatusId::Comment(comment.id).to_string(),
+    uri: comment.ap_id.to_owned(),
+    url: local_url(&format!("post/{}/comment/{}", comment.post_id, comment.id)),
+    created_at: to_utc(comment.published),
+    edited_at: comment.updated.map(to_utc),
+    account: creator.to_account(),
+    content: markdown_to_html(&comment.content),
+    visibility: "public",
+    sensitive: false,
+    spoiler_text: String::new(),
+    in_reply_to_id: Some(in_reply_to.to_string()),
+    in_reply_to_account_id: None,
+    replies_count: 0,
+    reblogs_count: 0,
+    favourites_count: comment.upvotes,
+    favourited: comment.my_vote == Some(1),
+    reblogged: false,
+    muted: false,
+    bookmarked: comment.saved == Some(true),
+    pinned: comment.pinned,
+    reblog: None,
+    card: None,
+    media_attachments: Vec::new(),
+    mentions: Vec::new(),
+    tags: Vec::new(),
+    emojis: Vec::new(),
+    poll: None,
+    language: None,
+  }
+}
+
+pub fn comment_notification(comment: &CommentView) -> Notification {
+  let status = comment_status(comment);
+  Notification {
+    id: status.id.to_owned(),
+    type_: "mention",
+    created_at: status.created_at,
+    account: status.account.clone(),
+    status,
+  }
+}
+
+/// The statuses around one of the post or its comments. The ancestors of a comment are the post
+/// and the comments it's under, and its descendants the comments under it. The comments are
+/// oldest first, and the removed and deleted ones are left out.
+pub fn context(post: &PostView, comments: &[CommentView], comment_id: Option<i32>) -> Context {
+  let parents: HashMap<i32, Option<i32>> = comments.iter().map(|c| (c.id, c.parent_id)).collect();
+  let is_under = |mut id: i32, ancestor_id: i32| {
+    while let Some(Some(parent_id)) = parents.get(&id) {
+      if *parent_id == ancestor_id {
+        return true;
+      }
+      id = *parent_id;
+    }
+    false
+  };
+
+  let mut comments: Vec<&CommentView> = comments
+    .iter()
+    .filter(|c| !c.removed && !c.deleted)
+    .collect();
+  comments.sort_by_key(|c| c.published);
+
+  match comment_id {
+    None => Context {
+      ancestors: Vec::new(),
+      descendants: comments.into_iter().map(comment_status).collect(),
+    },
+    Some(comment_id) => {
+      let ancestors = std::iter::once(post_status(post))
+        .chain(
+          comments
+            .iter()
+            .filter(|c| is_under(comment_id, c.id))
+            .map(|c| comment_status(c)),
+        )
+        .collect();
+      let descendants = comments
+        .iter()
+        .filter(|c| is_under(c.id, comment_id))
+        .map(|c| comment_status(c))
+        .collect();
+      Context {
+        ancestors,
+        descendants,
+      }
+    }
+  }
+}
+
+pub fn to_utc(time: NaiveDateTime) -> DateTime<Utc> {
+  DateTime::<Utc>::from_utc(time, Utc)
+}
+
+/// The images uploaded here are kept as their pictrs file, and the remote ones as their url
+fn image_url(image: &str) -> String {
+  if image.starts_with("http://") || image.starts_with("https://") {
+    image.to_owned()
+  } else {
+    media_url(image)
+  }
+}
+
+fn local_url(path: &str) -> String {
+  format!(
+    "{}://{}/{}",
+    lemmy_utils::get_apub_protocol_string(),
+    Settings::get().hostname,
+    path
+  )
+}
@@ -0,0 +1,208 @@
+//! Sends inbox notifications over Matrix to the users that linked a Matrix ID, when a bot is set
+//! up. The bot invites each user to a direct chat the first time, and keeps sending there until
+//! they link another ID or leave it. Every user only gets so many per period, and the rest wait in
+//! the queue until the limit lets them through.
+
+use crate::{
+  blocking,
+  digest::escape_html,
+  job_queue::{enqueue_all, enqueue_at, Task},
+  push::PushNotification,
+  rate_limit::rate_limiter::RateLimiter,
+  request::{retry, RecvError},
+  DbPool,
+  LemmyError,
+};
+use actix_web::{
+  client::Client,
+  http::{header::AUTHORIZATION, StatusCode},
+};
+use lemmy_db::{
+  naive_now,
+  user::User_,
+  user_matrix_room::{UserMatrixRoom, UserMatrixRoomForm},
+  Crud,
+};
+use lemmy_utils::{
+  generate_random_string,
+  settings::{MatrixConfig, Settings},
+};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Mutex;
+
+const RATE_LIMIT_BUCKET: &str = "matrix_notification";
+
+lazy_static! {
+  /// The notifications each user got lately, by user id
+  static ref MATRIX_RATE_LIMITER: Mutex<RateLimiter> = Mutex::new(RateLimiter::default());
+}
+
+#[derive(Deserialize)]
+struct CreateRoomResponse {
+  room_id: String,
+}
+
+/// Queues the notifications of the recipients that linked a Matrix ID.
+pub async fn queue_matrix_notifications(
+  pool: &DbPool,
+  notifications: &[PushNotification],
+) -> Result<(), LemmyError> {
+  if Settings::get().matrix.is_none() || notifications.is_empty() {
+    return Ok(());
+  }
+
+  let notifications = notifications.to_vec();
+  let tasks = blocking(pool, move |conn| {
+    notifications
+      .into_iter()
+      .filter(|notification| {
+        User_::read(conn, notification.recipient_id)
+          .map(|user| user.matrix_user_id.is_some())
+          .unwrap_or(false)
+      })
+      .map(|notification| Task::SendMatrixNotification {
+        recipient_id: notification.recipient_id,
+        notification,
+      })
+      .collect::<Vec<Task>>()
+  })
+  .await?;
+  enqueue_all(pool, tasks).await
+}
+
+/// Sends the notification to the Matrix ID the recipient has linked now, or has it wait when
+/// they're over the limit.
+pub async fn send_matrix_notification(
+  pool: &DbPool,
+  client: &Client,
+  recipient_id: i32,
+  notification: &PushNotification,
+) -> Result<(), LemmyError> {
+  let config = match Settings::get().matrix {
+    Some(config) => config,
+    None => return Ok(()),
+  };
+
+  // They unlinked it since
+  let user = blocking(pool, move |conn| User_::read(conn, recipient_id)).await??;
+  let matrix_user_id = match user.matrix_user_id {
+    Some(matrix_user_id) => matrix_user_id,
+    None => return Ok(()),
+  };
+
+  let limited = MATRIX_RATE_LIMITER
+    .lock()
+    .unwrap()
+    .check_rate_limit_full(
+      RATE_LIMIT_BUCKET,
+      &recipient_id.to_string(),
+      config.notifications,
+      config.notifications_per_second,
+      false,
+    )
+    .is_err();
+  if limited {
+    // Waiting doesn't count as a failed attempt
+    let wait = (config.notifications_per_second / config.notifications.max(1)).max(1);
+    let task = Task::SendMatrixNotification {
+      recipient_id,
+      notification: notification.to_owned(),
+    };
+    let run_at = naive_now() + chrono::Duration::seconds(wait.into());
+    return enqueue_at(pool, task, run_at).await;
+  }
+
+  let room_id = matrix_room(pool, client, &config, recipient_id, &matrix_user_id).await?;
+  let url = format!(
+    "{}/_matrix/client/r0/rooms/{}/send/m.room.message/{}",
+    config.homeserver_url.trim_end_matches('/'),
+    utf8_percent_encode(&room_id, NON_ALPHANUMERIC),
+    generate_random_string()
+  );
+  let message = json!({
+    "msgtype": "m.notice",
+    "body": format!("{}\n{}\n{}", notification.title, notification.body, notification.url),
+    "format": "org.matrix.custom.html",
+    "formatted_body": format!(
+      "<strong>{}</strong><br>{}<br><a href=\"{}\">{}</a>",
+      escape_html(&notification.title),
+      escape_html(&notification.body),
+      escape_html(&notification.url),
+      escape_html(&notification.url)
+    ),
+  });
+  let auth = format!("Bearer {}", config.access_token);
+  let response = retry(|| {
+    client
+      .put(&url)
+      .header(AUTHORIZATION, auth.as_str())
+      .send_json(&message)
+  })
+  .await?;
+
+  // They left the chat, so the next attempt invites them to a new one
+  if response.status() == StatusCode::FORBIDDEN {
+    blocking(pool, move |conn| {
+      let room = UserMatrixRoom::read_for_user(conn, recipient_id, &matrix_user_id)?;
+      UserMatrixRoom::delete(conn, room.id)
+    })
+    .await??;
+  }
+  if !response.status().is_success() {
+    return Err(format_err!("matrix homeserver answered {}", response.status()).into());
+  }
+
+  Ok(())
+}
+
+/// The direct chat with the user, which is created the first time.
+async fn matrix_room(
+  pool: &DbPool,
+  client: &Client,
+  config: &MatrixConfig,
+  recipient_id: i32,
+  matrix_user_id: &str,
+) -> Result<String, LemmyError> {
+  let for_matrix_user_id = matrix_user_id.to_owned();
+  let read_room =
+    move |conn: &'_ _| UserMatrixRoom::read_for_user(conn, recipient_id, &for_matrix_user_id);
+  if let Ok(room) = blocking(pool, read_room).await? {
+    return Ok(room.room_id);
+  }
+
+  let url = format!(
+    "{}/_matrix/client/r0/createRoom",
+    config.homeserver_url.trim_end_matches('/')
+  );
+  let request = json!({
+    "preset": "trusted_private_chat",
+    "is_direct": true,
+    "invite": [matrix_user_id],
+    "name": format!("{} notifications", Settings::get().hostname),
+  });
+  let auth = format!("Bearer {}", config.access_token);
+  let mut response = retry(|| {
+    client
+      .post(&url)
+      .header(AUTHORIZATION, auth.as_str())
+      .send_json(&request)
+  })
+  .await?;
+  if !response.status().is_success() {
+    return Err(format_err!("matrix homeserver answered {}", response.status()).into());
+  }
+  let created: CreateRoomResponse = response
+    .json()
+    .await
+    .map_err(|e| RecvError(e.to_string()))?;
+
+  let room_form = UserMatrixRoomForm {
+    user_id: recipient_id,
+    matrix_user_id: matrix_user_id.to_owned(),
+    room_id: created.room_id,
+  };
+  let room = blocking(pool, move |conn| UserMatrixRoom::upsert(conn, &room_form)).await??;
+  Ok(room.room_id)
+}
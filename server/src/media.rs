@@ -0,0 +1,127 @@
+//! The images users upload. pictrs keeps them, and they're served to everyone from here, like the
+//! thumbnails lemmy makes. Each upload is kept with its uploader and the token that deletes it, so
+//! that it can go along with their content.
+
+use crate::{blocking, delete_pictrs_image, DbPool, LemmyError};
+use actix_web::client::Client;
+use lemmy_db::media::Media;
+use lemmy_utils::{get_apub_protocol_string, settings::Settings};
+use tracing::error;
+
+/// Where an uploaded image, or a thumbnail lemmy made, is served from.
+pub fn media_url(file: &str) -> String {
+  format!(
+    "{}://{}/pictrs/image/{}",
+    get_apub_protocol_string(),
+    Settings::get().hostname,
+    file
+  )
+}
+
+/// Where lemmy gets a file from pictrs, at its full size or shrunk to a thumbnail.
+pub fn pictrs_file_url(file: &str, thumbnail: Option<u32>) -> String {
+  let pictrs_url = Settings::get().pictrs.url;
+  match thumbnail {
+    Some(size) => format!(
+      "{}/image/process.{}?src={}&thumbnail={}",
+      pictrs_url,
+      file.rsplit('.').next().unwrap_or("jpg"),
+      file,
+      size
+    ),
+    None => format!("{}/image/original/{}", pictrs_url, file),
+  }
+}
+
+/// The names pictrs gives files, which keeps the paths to it from being made up.
+pub fn is_media_file(file: &str) -> bool {
+  !file.is_empty() && !file.starts_with('.') && file.chars().all(is_media_file_char)
+}
+
+fn is_media_file_char(c: char) -> bool {
+  c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_'
+}
+
+/// Audio and video uploads, which posts have as attachments for clients to play
+pub fn is_attachment_type(content_type: &str) -> bool {
+  content_type.starts_with("audio/") || content_type.starts_with("video/")
+}
+
+/// The MIME type of the post's url, when it's an audio or video file uploaded here.
+pub async fn attachment_media_type(
+  pool: &DbPool,
+  url: &Option<String>,
+) -> Result<Option<String>, LemmyError> {
+  let prefix = media_url("");
+  let file = match url {
+    Some(url) if url.starts_with(&prefix) && is_media_file(&url[prefix.len()..]) => {
+      url[prefix.len()..].to_owned()
+    }
+    _ => return Ok(None),
+  };
+
+  let media = blocking(pool, move |conn| Media::read_for_file(conn, &file)).await?;
+  Ok(
+    media
+      .ok()
+      .and_then(|m| m.content_type)
+      .filter(|t| is_attachment_type(t)),
+  )
+}
+
+/// The files of the images served from here that the text links to.
+pub fn linked_media_files(text: &str) -> Vec<String> {
+  let prefix = media_url("");
+  text
+    .match_indices(&prefix)
+    .filter_map(|(start, _)| {
+      text[start + prefix.len()..]
+        .split(|c: char| !is_media_file_char(c))
+        .next()
+        .filter(|file| is_media_file(file))
+        .map(ToOwned::to_owned)
+    })
+    .collect()
+}
+
+/// Takes the uploads down from pictrs, then forgets them. One failing doesn't stop the rest.
+pub async fn delete_media(
+  pool: &DbPool,
+  client: &Client,
+  media: Vec<Media>,
+) -> Result<(), LemmyError> {
+  for image in media {
+    if let Err(e) = delete_pictrs_image(client, &image.file, &image.delete_token).await {
+      error!("Couldn't delete uploaded image {}: {}", image.file, e);
+      continue;
+    }
+
+    let media_id = image.id;
+    blocking(pool, move |conn| Media::delete(conn, media_id)).await??;
+  }
+
+  Ok(())
+}
+
+/// Deletes the uploads of the user that their content links to, once the content is gone for good.
+pub async fn delete_linked_media(
+  pool: &DbPool,
+  client: &Client,
+  user_id: i32,
+  texts: &[Option<String>],
+) -> Result<(), LemmyError> {
+  let files = texts
+    .iter()
+    .flatten()
+    .flat_map(|text| linked_media_files(text))
+    .collect::<Vec<String>>();
+  if files.is_empty() {
+    return Ok(());
+  }
+
+  let media = blocking(pool, move |conn| {
+    Media::for_user_files(conn, user_id, files)
+  })
+  .await??;
+  delete_media(pool, client, media).await
+}
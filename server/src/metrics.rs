@@ -0,0 +1,224 @@
+//! The numbers the `/metrics` endpoint hands to Prometheus. The request latencies, websocket
+//! connections and delivery failures are counted as they happen, while the pool and job queue
+//! numbers are read when the metrics are scraped.
+
+use crate::{blocking, DbPool, LemmyError};
+use lemmy_db::job::{Job, JobStatus};
+use std::{
+  collections::BTreeMap,
+  fmt::Write,
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    RwLock,
+  },
+  time::Duration,
+};
+
+/// The upper bounds of the latency buckets, in seconds
+const LATENCY_BUCKETS: [f64; 11] = [
+  0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Default)]
+struct Histogram {
+  buckets: [u64; LATENCY_BUCKETS.len()],
+  sum: f64,
+  count: u64,
+}
+
+impl Histogram {
+  fn observe(&mut self, seconds: f64) {
+    for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS.iter()) {
+      if seconds <= *bound {
+        *bucket += 1;
+      }
+    }
+    self.sum += seconds;
+    self.count += 1;
+  }
+}
+
+lazy_static! {
+  /// By method and route pattern. The websocket ops go under the method `WS`.
+  static ref REQUEST_LATENCIES: RwLock<BTreeMap<(String, String), Histogram>> =
+    RwLock::new(BTreeMap::new());
+}
+
+static WEBSOCKET_CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+static DELIVERY_FAILURES: AtomicUsize = AtomicUsize::new(0);
+
+pub fn observe_request(method: &str, route: &str, elapsed: Duration) {
+  if let Ok(mut latencies) = REQUEST_LATENCIES.write() {
+    latencies
+      .entry((method.to_owned(), route.to_owned()))
+      .or_default()
+      .observe(elapsed.as_secs_f64());
+  }
+}
+
+pub fn set_websocket_connections(connections: usize) {
+  WEBSOCKET_CONNECTIONS.store(connections, Ordering::Relaxed);
+}
+
+pub fn count_delivery_failure() {
+  DELIVERY_FAILURES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Everything, in the Prometheus text format.
+pub async fn render_metrics(pool: &DbPool) -> Result<String, LemmyError> {
+  let job_stats = blocking(pool, move |conn| Job::stats(conn)).await??;
+
+  let mut out = String::new();
+
+  header(
+    &mut out,
+    "lemmy_http_request_duration_seconds",
+    "histogram",
+    "How long the requests took to answer",
+  )?;
+  if let Ok(latencies) = REQUEST_LATENCIES.read() {
+    for ((method, route), histogram) in latencies.iter() {
+      let labels = format!("method=\"{}\",route=\"{}\"", method, escape(route));
+      for (bucket, bound) in histogram.buckets.iter().zip(LATENCY_BUCKETS.iter()) {
+        writeln!(
+          out,
+          "lemmy_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+          labels, bound, bucket
+        )?;
+      }
+      writeln!(
+        out,
+        "lemmy_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+        labels, histogram.count
+      )?;
+      writeln!(
+        out,
+        "lemmy_http_request_duration_seconds_sum{{{}}} {}",
+        labels, histogram.sum
+      )?;
+      writeln!(
+        out,
+        "lemmy_http_request_duration_seconds_count{{{}}} {}",
+        labels, histogram.count
+      )?;
+    }
+  }
+
+  header(
+    &mut out,
+    "lemmy_db_pool_connections",
+    "gauge",
+    "Open database connections",
+  )?;
+  header(
+    &mut out,
+    "lemmy_db_pool_idle_connections",
+    "gauge",
+    "Open database connections not in use",
+  )?;
+  header(
+    &mut out,
+    "lemmy_db_pool_max_connections",
+    "gauge",
+    "The most connections the pool opens",
+  )?;
+  let replicas = pool.replicas().iter().enumerate();
+  let pools = std::iter::once(("primary".to_owned(), pool.primary()))
+    .chain(replicas.map(|(i, replica)| (format!("replica{}", i), replica)));
+  for (name, db_pool) in pools {
+    let state = db_pool.state();
+    writeln!(
+      out,
+      "lemmy_db_pool_connections{{pool=\"{}\"}} {}",
+      name, state.connections
+    )?;
+    writeln!(
+      out,
+      "lemmy_db_pool_idle_connections{{pool=\"{}\"}} {}",
+      name, state.idle_connections
+    )?;
+    writeln!(
+      out,
+      "lemmy_db_pool_max_connections{{pool=\"{}\"}} {}",
+      name,
+      db_pool.max_size()
+    )?;
+  }
+
+  header(
+    &mut out,
+    "lemmy_websocket_connections",
+    "gauge",
+    "Open websocket connections",
+  )?;
+  writeln!(
+    out,
+    "lemmy_websocket_connections {}",
+    WEBSOCKET_CONNECTIONS.load(Ordering::Relaxed)
+  )?;
+
+  header(
+    &mut out,
+    "lemmy_jobs",
+    "gauge",
+    "Background jobs by kind and status",
+  )?;
+  let mut federation_queue_depth = 0;
+  for stat in job_stats.iter() {
+    let status = match JobStatus::from_i16(stat.status) {
+      Some(JobStatus::Queued) => "queued",
+      Some(JobStatus::Running) => "running",
+      Some(JobStatus::Done) => "done",
+      Some(JobStatus::Failed) => "failed",
+      None => continue,
+    };
+    writeln!(
+      out,
+      "lemmy_jobs{{kind=\"{}\",status=\"{}\"}} {}",
+      escape(&stat.kind),
+      status,
+      stat.count
+    )?;
+    if stat.kind == "DeliverActivity" && (status == "queued" || status == "running") {
+      federation_queue_depth += stat.count;
+    }
+  }
+
+  header(
+    &mut out,
+    "lemmy_federation_queue_depth",
+    "gauge",
+    "Activities waiting to be delivered",
+  )?;
+  writeln!(
+    out,
+    "lemmy_federation_queue_depth {}",
+    federation_queue_depth
+  )?;
+
+  header(
+    &mut out,
+    "lemmy_federation_delivery_failures_total",
+    "counter",
+    "Failed activity deliveries",
+  )?;
+  writeln!(
+    out,
+    "lemmy_federation_delivery_failures_total {}",
+    DELIVERY_FAILURES.load(Ordering::Relaxed)
+  )?;
+
+  Ok(out)
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) -> std::fmt::Result {
+  writeln!(out, "# HELP {} {}", name, help)?;
+  writeln!(out, "# TYPE {} {}", name, kind)
+}
+
+fn escape(label: &str) -> String {
+  label
+    .replace('\\', "\\\\")
+    .replace('"', "\\\"")
+    .replace('\n', "\\n")
+}
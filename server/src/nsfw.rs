@@ -0,0 +1,159 @@
+//! Asks an outside classifier how likely images are NSFW, when one is set up. The uploads are
+//! classified as they come in and the thumbnails of links when they're fetched, and the scores are
+//! kept with them. A new post with an image above the threshold is marked NSFW or held back for
+//! the mods, and a mod marking it safe anyway goes in the modlog.
+
+use crate::{
+  blocking,
+  media::{is_media_file, media_url, pictrs_file_url},
+  request::{retry, RecvError},
+  DbPool,
+  LemmyError,
+};
+use actix_web::{client::Client, http::header::CONTENT_TYPE, web::Bytes};
+use lemmy_db::{media::Media, purge::PictrsThumbnail};
+use lemmy_utils::settings::Settings;
+use serde::Deserialize;
+use tracing::error;
+
+#[derive(Deserialize)]
+struct ClassifierResponse {
+  nsfw: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NsfwAction {
+  MarkNsfw,
+  /// Holds the post back for the mods to approve
+  Review,
+}
+
+/// How likely the image is NSFW, from 0 to 1. None when there's no classifier.
+pub async fn classify_image(
+  client: &Client,
+  data: Vec<u8>,
+  content_type: &str,
+) -> Result<Option<f32>, LemmyError> {
+  let config = match Settings::get().nsfw_classifier {
+    Some(config) => config,
+    None => return Ok(None),
+  };
+
+  let data = Bytes::from(data);
+  let mut response = retry(|| {
+    client
+      .post(&config.url)
+      .header(CONTENT_TYPE, content_type)
+      .send_body(data.clone())
+  })
+  .await?;
+  if !response.status().is_success() {
+    return Err(format_err!("nsfw classifier failed: {}", response.status()).into());
+  }
+
+  let res: ClassifierResponse = response
+    .json()
+    .await
+    .map_err(|e| RecvError(e.to_string()))?;
+  Ok(Some(res.nsfw))
+}
+
+/// Same as `classify_image`, for an image somewhere else. It's fetched first, up to the size of the
+/// largest upload.
+pub async fn classify_image_url(client: &Client, url: &str) -> Result<Option<f32>, LemmyError> {
+  if Settings::get().nsfw_classifier.is_none() {
+    return Ok(None);
+  }
+
+  let mut response = retry(|| client.get(url).send()).await?;
+  if !response.status().is_success() {
+    return Err(format_err!("image fetch failed: {}", response.status()).into());
+  }
+  let content_type = response
+    .headers()
+    .get(CONTENT_TYPE)
+    .and_then(|t| t.to_str().ok())
+    .unwrap_or("application/octet-stream")
+    .to_owned();
+  let data = response
+    .body()
+    .limit(Settings::get().pictrs.uploads.max_size)
+    .await
+    .map_err(|e| RecvError(e.to_string()))?;
+
+  classify_image(client, data.to_vec(), &content_type).await
+}
+
+/// Same as `classify_image`, for a file pictrs keeps.
+pub async fn classify_pictrs_file(client: &Client, file: &str) -> Result<Option<f32>, LemmyError> {
+  classify_image_url(client, &pictrs_file_url(file, None)).await
+}
+
+/// What's done with a post whose image went over the threshold. None when it didn't, or the post
+/// is marked NSFW already. The uploads linked as the url and the thumbnails lemmy made count.
+pub async fn nsfw_image_action(
+  pool: &DbPool,
+  url: &Option<String>,
+  thumbnail_url: &Option<String>,
+  nsfw: bool,
+) -> Result<Option<NsfwAction>, LemmyError> {
+  let config = match Settings::get().nsfw_classifier {
+    Some(config) => config,
+    None => return Ok(None),
+  };
+  if nsfw {
+    return Ok(None);
+  }
+
+  let prefix = media_url("");
+  let upload = url
+    .as_ref()
+    .filter(|url| url.starts_with(&prefix) && is_media_file(&url[prefix.len()..]))
+    .map(|url| url[prefix.len()..].to_owned());
+  let upload_score = match upload {
+    Some(file) => blocking(pool, move |conn| Media::read_for_file(conn, &file))
+      .await?
+      .ok()
+      .and_then(|m| m.nsfw_score),
+    None => None,
+  };
+
+  let thumbnail_score = match thumbnail_url.to_owned() {
+    Some(file) => blocking(pool, move |conn| {
+      PictrsThumbnail::for_files(conn, vec![file])
+    })
+    .await??
+    .into_iter()
+    .find_map(|t| t.nsfw_score),
+    None => None,
+  };
+
+  let over_threshold = upload_score
+    .into_iter()
+    .chain(thumbnail_score)
+    .any(|score| score > config.threshold);
+  if !over_threshold {
+    Ok(None)
+  } else if config.action == "review" {
+    Ok(Some(NsfwAction::Review))
+  } else {
+    Ok(Some(NsfwAction::MarkNsfw))
+  }
+}
+
+/// Whether a remote post should be marked NSFW here, going by its thumbnail. Remote posts can't be
+/// held back, so they're always marked.
+pub async fn remote_thumbnail_is_nsfw(client: &Client, thumbnail_url: &str) -> bool {
+  let threshold = match Settings::get().nsfw_classifier {
+    Some(config) => config.threshold,
+    None => return false,
+  };
+
+  match classify_image_url(client, thumbnail_url).await {
+    Ok(score) => score.map_or(false, |score| score > threshold),
+    Err(e) => {
+      error!("Couldn't classify thumbnail {}: {}", thumbnail_url, e);
+      false
+    }
+  }
+}
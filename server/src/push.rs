@@ -0,0 +1,115 @@
+//! Web push notifications, sent to the devices users registered for them.
+
+use crate::{
+  blocking,
+  job_queue::{enqueue_all, Task},
+  DbPool,
+  LemmyError,
+};
+use lemmy_db::{user_push_subscription::UserPushSubscription, Crud};
+use lemmy_utils::settings::Settings;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use web_push::{
+  ContentEncoding,
+  SubscriptionInfo,
+  VapidSignatureBuilder,
+  WebPushClient,
+  WebPushError,
+  WebPushMessageBuilder,
+};
+
+/// Push services cap the payload at around 4kb, so long bodies get cut short
+const MAX_BODY_LENGTH: usize = 200;
+
+/// The payload handed to the service worker, which decides how to show it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PushNotification {
+  #[serde(skip)]
+  pub recipient_id: i32,
+  pub title: String,
+  pub body: String,
+  pub url: String,
+}
+
+impl PushNotification {
+  pub fn new(recipient_id: i32, title: &str, body: &str, url: &str) -> Self {
+    PushNotification {
+      recipient_id,
+      title: title.to_owned(),
+      body: body.chars().take(MAX_BODY_LENGTH).collect(),
+      url: url.to_owned(),
+    }
+  }
+}
+
+/// Queues the notifications, so a slow push service doesn't hold up the request.
+pub async fn queue_push_notifications(
+  pool: &DbPool,
+  notifications: Vec<PushNotification>,
+) -> Result<(), LemmyError> {
+  if !Settings::get().web_push.enabled {
+    return Ok(());
+  }
+
+  let tasks = notifications
+    .into_iter()
+    .map(|notification| Task::SendPushNotification {
+      recipient_id: notification.recipient_id,
+      notification,
+    })
+    .collect();
+  enqueue_all(pool, tasks).await
+}
+
+/// Sends the notification to every device of the recipient.
+pub async fn send_push_notification(
+  pool: &DbPool,
+  recipient_id: i32,
+  notification: &PushNotification,
+) -> Result<(), LemmyError> {
+  let settings = Settings::get();
+  let keypair = settings.get_vapid_keypair()?;
+  let client = WebPushClient::new();
+
+  let subscriptions = blocking(pool, move |conn| {
+    UserPushSubscription::for_user(conn, recipient_id)
+  })
+  .await??;
+
+  let payload = serde_json::to_string(notification)?;
+
+  for subscription in subscriptions {
+    let subscription_info = SubscriptionInfo::new(
+      &subscription.endpoint,
+      &subscription.p256dh,
+      &subscription.auth,
+    );
+
+    let mut signature =
+      VapidSignatureBuilder::from_pem(keypair.private_key.as_bytes(), &subscription_info)?;
+    signature.add_claim("sub", settings.web_push.subject.as_str());
+
+    let mut message = WebPushMessageBuilder::new(&subscription_info)?;
+    message.set_payload(ContentEncoding::AesGcm, payload.as_bytes());
+    message.set_vapid_signature(signature.build()?);
+
+    match client.send(message.build()?).await {
+      Ok(_) => (),
+      // The browser dropped the subscription, so forget about it
+      Err(WebPushError::EndpointNotValid) | Err(WebPushError::EndpointNotFound) => {
+        let subscription_id = subscription.id;
+        blocking(pool, move |conn| {
+          UserPushSubscription::delete(conn, subscription_id)
+        })
+        .await??;
+      }
+      Err(e) => error!(
+        "Couldn't send push notification to {}: {}",
+        subscription.endpoint, e
+      ),
+    }
+  }
+
+  Ok(())
+}
@@ -1,29 +1,148 @@
 use super::IPAddr;
-use crate::{get_ip, LemmyError};
+use crate::{
+  api::{claims::Claims, APIError},
+  get_ip,
+  LemmyError,
+};
 use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use diesel::PgConnection;
 use futures::future::{ok, Ready};
-use lemmy_utils::settings::{RateLimitConfig, Settings};
-use rate_limiter::{RateLimitType, RateLimiter};
+use lemmy_db::{
+  ip_ban::IpBan,
+  naive_now,
+  rate_limit::{RateLimitEndpoint, RateLimitExemption},
+  site::{Site, SiteRateLimits},
+  Crud,
+};
+use lemmy_utils::{
+  ip_in_range,
+  settings::{RateLimitConfig, Settings},
+};
+use rate_limiter::{rate_limit_error, RateLimitType, RateLimiter};
+use redis_backend::RedisRateLimiter;
 use std::{
+  collections::{HashMap, HashSet},
   future::Future,
   pin::Pin,
-  sync::Arc,
+  sync::{Arc, RwLock},
   task::{Context, Poll},
 };
 use tokio::sync::Mutex;
+use tracing::error;
+use url::form_urlencoded;
 
 pub mod rate_limiter;
+pub mod redis_backend;
+
+lazy_static! {
+  static ref OVERRIDES: RwLock<RateLimitOverrides> = RwLock::new(RateLimitOverrides::default());
+  static ref IP_BANS: RwLock<Vec<IpBan>> = RwLock::new(Vec::new());
+}
+
+/// What the admins changed about the config file's rate limits, kept in memory so that checking
+/// a request doesn't need the database.
+#[derive(Debug, Clone, Default)]
+struct RateLimitOverrides {
+  site: SiteRateLimits,
+  endpoints: HashMap<String, (i32, i32)>,
+  exempt_user_ids: HashSet<i32>,
+}
+
+impl RateLimitOverrides {
+  /// The bucket the request goes into, and its rate and period
+  fn limit_for(&self, type_: RateLimitType, endpoint: &str) -> (String, i32, i32) {
+    if let Some((rate, per)) = self.endpoints.get(endpoint) {
+      return (format!("endpoint:{}", endpoint), *rate, *per);
+    }
+
+    let config: RateLimitConfig = Settings::get().rate_limit;
+    let site = &self.site;
+    let (rate, per) = match type_ {
+      RateLimitType::Message => (
+        site.rate_limit_message.unwrap_or(config.message),
+        site
+          .rate_limit_message_per_second
+          .unwrap_or(config.message_per_second),
+      ),
+      RateLimitType::Post => (
+        site.rate_limit_post.unwrap_or(config.post),
+        site
+          .rate_limit_post_per_second
+          .unwrap_or(config.post_per_second),
+      ),
+      RateLimitType::Register => (
+        site.rate_limit_register.unwrap_or(config.register),
+        site
+          .rate_limit_register_per_second
+          .unwrap_or(config.register_per_second),
+      ),
+      RateLimitType::Report => (
+        site.rate_limit_report.unwrap_or(config.report),
+        site
+          .rate_limit_report_per_second
+          .unwrap_or(config.report_per_second),
+      ),
+    };
+    (type_.as_ref().to_string(), rate, per)
+  }
+}
+
+/// Reads the admins' rate limits from the database. Needs to run on startup, and after they
+/// change.
+pub fn load_rate_limit_overrides(conn: &PgConnection) -> Result<(), LemmyError> {
+  // There's no site yet before the first admin sets it up
+  let site = Site::read(conn, 1)
+    .map(|s| s.rate_limits())
+    .unwrap_or_default();
+  let endpoints = RateLimitEndpoint::list(conn)?
+    .into_iter()
+    .map(|e| (e.endpoint, (e.actions, e.per_second)))
+    .collect();
+  let exempt_user_ids = RateLimitExemption::list(conn)?
+    .into_iter()
+    .map(|e| e.user_id)
+    .collect();
+
+  *OVERRIDES.write().unwrap() = RateLimitOverrides {
+    site,
+    endpoints,
+    exempt_user_ids,
+  };
+  Ok(())
+}
+
+/// Reads the IP bans that haven't expired, so that checking a request doesn't need the database.
+/// Needs to run on startup, and after they change.
+pub fn load_ip_bans(conn: &PgConnection) -> Result<(), LemmyError> {
+  *IP_BANS.write().unwrap() = IpBan::list_active(conn)?;
+  Ok(())
+}
+
+/// Bans can run out between two loads, so the expiry is checked again here.
+fn is_ip_banned(ip_addr: &str) -> bool {
+  let now = naive_now();
+  IP_BANS
+    .read()
+    .unwrap()
+    .iter()
+    .filter(|b| b.expires.map_or(true, |expires| expires > now))
+    .any(|b| ip_in_range(&b.ip_range, ip_addr))
+}
 
 #[derive(Debug, Clone)]
 pub struct RateLimit {
   // it might be reasonable to use a std::sync::Mutex here, since we don't need to lock this
   // across await points
   pub rate_limiter: Arc<Mutex<RateLimiter>>,
+  /// Shared with the other server processes. The in-memory buckets are only used while it can't be
+  /// reached.
+  pub redis: Option<RedisRateLimiter>,
 }
 
 #[derive(Debug, Clone)]
 pub struct RateLimited {
   rate_limiter: Arc<Mutex<RateLimiter>>,
+  redis: Option<RedisRateLimiter>,
   type_: RateLimitType,
 }
 
@@ -45,97 +164,94 @@ impl RateLimit {
     self.kind(RateLimitType::Register)
   }
 
+  pub fn report(&self) -> RateLimited {
+    self.kind(RateLimitType::Report)
+  }
+
   fn kind(&self, type_: RateLimitType) -> RateLimited {
     RateLimited {
       rate_limiter: self.rate_limiter.clone(),
+      redis: self.redis.clone(),
       type_,
     }
   }
 }
 
 impl RateLimited {
+  /// The endpoint is the websocket op or the HTTP path, and the user is the one logged in, if any.
+  /// Banned IPs are turned away here, before anything else.
   pub async fn wrap<T, E>(
     self,
     ip_addr: String,
+    endpoint: String,
+    user_id: Option<i32>,
     fut: impl Future<Output = Result<T, E>>,
   ) -> Result<T, E>
   where
     E: From<LemmyError>,
   {
-    // Does not need to be blocking because the RwLock in settings never held across await points,
+    if is_ip_banned(&ip_addr) {
+      return Err(LemmyError::from(APIError::err("ip_banned")).into());
+    }
+
+    // Does not need to be blocking because the RwLocks are never held across await points,
     // and the operation here locks only long enough to clone
-    let rate_limit: RateLimitConfig = Settings::get().rate_limit;
+    let overrides = OVERRIDES.read().unwrap().clone();
+
+    if let Some(user_id) = user_id {
+      if overrides.exempt_user_ids.contains(&user_id) {
+        return fut.await;
+      }
+    }
+
+    let (bucket, rate, per) = overrides.limit_for(self.type_, &endpoint);
+
+    // Messages count up front, everything else only once it went through
+    let count_up_front = self.type_ == RateLimitType::Message;
 
     // before
-    {
-      let mut limiter = self.rate_limiter.lock().await;
-
-      match self.type_ {
-        RateLimitType::Message => {
-          limiter.check_rate_limit_full(
-            self.type_,
-            &ip_addr,
-            rate_limit.message,
-            rate_limit.message_per_second,
-            false,
-          )?;
-
-          drop(limiter);
-          return fut.await;
-        }
-        RateLimitType::Post => {
-          limiter.check_rate_limit_full(
-            self.type_,
-            &ip_addr,
-            rate_limit.post,
-            rate_limit.post_per_second,
-            true,
-          )?;
-        }
-        RateLimitType::Register => {
-          limiter.check_rate_limit_full(
-            self.type_,
-            &ip_addr,
-            rate_limit.register,
-            rate_limit.register_per_second,
-            true,
-          )?;
-        }
-      };
+    self
+      .check_rate_limit(&bucket, &ip_addr, rate, per, !count_up_front)
+      .await?;
+
+    if count_up_front {
+      return fut.await;
     }
 
     let res = fut.await;
 
     // after
-    {
-      let mut limiter = self.rate_limiter.lock().await;
-      if res.is_ok() {
-        match self.type_ {
-          RateLimitType::Post => {
-            limiter.check_rate_limit_full(
-              self.type_,
-              &ip_addr,
-              rate_limit.post,
-              rate_limit.post_per_second,
-              false,
-            )?;
-          }
-          RateLimitType::Register => {
-            limiter.check_rate_limit_full(
-              self.type_,
-              &ip_addr,
-              rate_limit.register,
-              rate_limit.register_per_second,
-              false,
-            )?;
-          }
-          _ => (),
-        };
-      }
+    if res.is_ok() {
+      self
+        .check_rate_limit(&bucket, &ip_addr, rate, per, false)
+        .await?;
     }
 
     res
   }
+
+  async fn check_rate_limit(
+    &self,
+    bucket: &str,
+    ip_addr: &str,
+    rate: i32,
+    per: i32,
+    check_only: bool,
+  ) -> Result<(), LemmyError> {
+    if let Some(redis) = &self.redis {
+      match redis
+        .check_rate_limit_full(bucket, ip_addr, rate, per, check_only)
+        .await
+      {
+        Ok(true) => return Ok(()),
+        Ok(false) => return Err(rate_limit_error(bucket, ip_addr, rate, per)),
+        Err(e) => error!("Redis unavailable, rate limiting in memory: {}", e),
+      }
+    }
+
+    let mut limiter = self.rate_limiter.lock().await;
+    limiter.check_rate_limit_full(bucket, ip_addr, rate, per, check_only)
+  }
 }
 
 impl<S> Transform<S> for RateLimited
@@ -176,11 +292,18 @@ where
 
   fn call(&mut self, req: S::Request) -> Self::Future {
     let ip_addr = get_ip(&req.connection_info());
+    let endpoint = req.path().to_string();
+
+    // Only the auth in the query string is looked at, since reading the body here would use it up
+    let user_id = form_urlencoded::parse(req.query_string().as_bytes())
+      .find(|(key, _)| key == "auth")
+      .and_then(|(_, auth)| Claims::decode(&auth).ok())
+      .map(|claims| claims.claims.id);
 
     let fut = self
       .rate_limited
       .clone()
-      .wrap(ip_addr, self.service.call(req));
+      .wrap(ip_addr, endpoint, user_id, self.service.call(req));
 
     Box::pin(async move { fut.await.map_err(actix_web::Error::from) })
   }
@@ -1,8 +1,7 @@
 use super::IPAddr;
 use crate::{api::APIError, LemmyError};
-use log::debug;
 use std::{collections::HashMap, time::SystemTime};
-use strum::IntoEnumIterator;
+use tracing::debug;
 
 #[derive(Debug, Clone)]
 pub struct RateLimitBucket {
@@ -15,12 +14,14 @@ pub enum RateLimitType {
   Message,
   Register,
   Post,
+  Report,
 }
 
-/// Rate limiting based on rate type and IP addr
+/// Rate limiting based on bucket and IP addr. Every rate limit type has a bucket, and so does
+/// every endpoint with a limit of its own.
 #[derive(Debug, Clone)]
 pub struct RateLimiter {
-  pub buckets: HashMap<RateLimitType, HashMap<IPAddr, RateLimitBucket>>,
+  pub buckets: HashMap<String, HashMap<IPAddr, RateLimitBucket>>,
 }
 
 impl Default for RateLimiter {
@@ -31,38 +32,46 @@ impl Default for RateLimiter {
   }
 }
 
+/// The same for every backend
+pub(super) fn rate_limit_error(bucket_name: &str, ip: &str, rate: i32, per: i32) -> LemmyError {
+  APIError {
+    message: format!(
+      "Too many requests. type: {}, IP: {}, {} per {} seconds",
+      bucket_name, ip, rate, per
+    ),
+  }
+  .into()
+}
+
 impl RateLimiter {
-  fn insert_ip(&mut self, ip: &str) {
-    for rate_limit_type in RateLimitType::iter() {
-      if self.buckets.get(&rate_limit_type).is_none() {
-        self.buckets.insert(rate_limit_type, HashMap::new());
-      }
+  fn insert_ip(&mut self, bucket_name: &str, ip: &str) {
+    let bucket = self
+      .buckets
+      .entry(bucket_name.to_string())
+      .or_insert_with(HashMap::new);
 
-      if let Some(bucket) = self.buckets.get_mut(&rate_limit_type) {
-        if bucket.get(ip).is_none() {
-          bucket.insert(
-            ip.to_string(),
-            RateLimitBucket {
-              last_checked: SystemTime::now(),
-              allowance: -2f64,
-            },
-          );
-        }
-      }
+    if bucket.get(ip).is_none() {
+      bucket.insert(
+        ip.to_string(),
+        RateLimitBucket {
+          last_checked: SystemTime::now(),
+          allowance: -2f64,
+        },
+      );
     }
   }
 
   #[allow(clippy::float_cmp)]
-  pub(super) fn check_rate_limit_full(
+  pub(crate) fn check_rate_limit_full(
     &mut self,
-    type_: RateLimitType,
+    bucket_name: &str,
     ip: &str,
     rate: i32,
     per: i32,
     check_only: bool,
   ) -> Result<(), LemmyError> {
-    self.insert_ip(ip);
-    if let Some(bucket) = self.buckets.get_mut(&type_) {
+    self.insert_ip(bucket_name, ip);
+    if let Some(bucket) = self.buckets.get_mut(bucket_name) {
       if let Some(rate_limit) = bucket.get_mut(ip) {
         let current = SystemTime::now();
         let time_passed = current.duration_since(rate_limit.last_checked)?.as_secs() as f64;
@@ -81,23 +90,9 @@ impl RateLimiter {
         if rate_limit.allowance < 1.0 {
           debug!(
             "Rate limited type: {}, IP: {}, time_passed: {}, allowance: {}",
-            type_.as_ref(),
-            ip,
-            time_passed,
-            rate_limit.allowance
+            bucket_name, ip, time_passed, rate_limit.allowance
           );
-          Err(
-            APIError {
-              message: format!(
-                "Too many requests. type: {}, IP: {}, {} per {} seconds",
-                type_.as_ref(),
-                ip,
-                rate,
-                per
-              ),
-            }
-            .into(),
-          )
+          Err(rate_limit_error(bucket_name, ip, rate, per))
         } else {
           if !check_only {
             rate_limit.allowance -= 1.0;
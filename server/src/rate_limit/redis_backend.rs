@@ -0,0 +1,96 @@
+//! The rate limit buckets kept in Redis instead of in memory, so that several server processes
+//! behind a load balancer count every request against the same buckets.
+
+use crate::LemmyError;
+use redis::{aio::ConnectionManager, Client, RedisError, Script};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+/// Does the same as `RateLimiter::check_rate_limit_full`, in one step so that two processes can't
+/// both take the last request a bucket has. Buckets nobody used for a whole period are full again,
+/// so they are left to expire.
+const CHECK_RATE_LIMIT_SCRIPT: &str = r#"
+local rate = tonumber(ARGV[1])
+local per = tonumber(ARGV[2])
+local check_only = ARGV[3] == "1"
+local now = tonumber(ARGV[4])
+
+local bucket = redis.call("HMGET", KEYS[1], "allowance", "last_checked")
+local allowance = tonumber(bucket[1]) or rate
+local last_checked = tonumber(bucket[2]) or now
+
+allowance = allowance + math.max(now - last_checked, 0) * (rate / per)
+if not check_only and allowance > rate then
+  allowance = rate
+end
+
+local allowed = allowance >= 1
+if allowed and not check_only then
+  allowance = allowance - 1
+end
+
+redis.call("HMSET", KEYS[1], "allowance", tostring(allowance), "last_checked", tostring(now))
+redis.call("EXPIRE", KEYS[1], math.max(per, 1))
+
+if allowed then
+  return 1
+else
+  return 0
+end
+"#;
+
+#[derive(Clone)]
+pub struct RedisRateLimiter {
+  // Reconnects by itself when the connection drops
+  connection: ConnectionManager,
+  script: Script,
+}
+
+impl std::fmt::Debug for RedisRateLimiter {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    f.write_str("RedisRateLimiter")
+  }
+}
+
+impl RedisRateLimiter {
+  pub async fn connect(redis_url: &str) -> Result<Self, LemmyError> {
+    let client = Client::open(redis_url)?;
+    let connection = ConnectionManager::new(client.get_connection_info().clone()).await?;
+    Ok(RedisRateLimiter {
+      connection,
+      script: Script::new(CHECK_RATE_LIMIT_SCRIPT),
+    })
+  }
+
+  /// Whether the request is allowed. Errors are only about reaching Redis.
+  pub(super) async fn check_rate_limit_full(
+    &self,
+    bucket_name: &str,
+    ip: &str,
+    rate: i32,
+    per: i32,
+    check_only: bool,
+  ) -> Result<bool, RedisError> {
+    // Whole seconds, like the in-memory buckets
+    let now = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_secs())
+      .unwrap_or_default();
+
+    let mut connection = self.connection.clone();
+    let allowed: i32 = self
+      .script
+      .key(format!("lemmy:rate_limit:{}:{}", bucket_name, ip))
+      .arg(rate)
+      .arg(per)
+      .arg(check_only as i32)
+      .arg(now)
+      .invoke_async(&mut connection)
+      .await?;
+
+    if allowed == 0 {
+      debug!("Rate limited type: {}, IP: {}", bucket_name, ip);
+    }
+    Ok(allowed == 1)
+  }
+}
@@ -1,17 +1,72 @@
 use crate::{
-  api::{comment::*, community::*, post::*, site::*, user::*, Oper, Perform},
+  api::{
+    appeal::*,
+    automod::*,
+    blocked_domain::*,
+    bulk_moderation::*,
+    comment::*,
+    community::*,
+    community_import::*,
+    community_takeover::*,
+    community_wiki::*,
+    custom_feed::*,
+    email_domain::*,
+    federation::*,
+    ip_ban::*,
+    job::*,
+    media::*,
+    mod_note::*,
+    muted_keyword::*,
+    post::*,
+    post_approval::*,
+    posting_requirement::*,
+    purge::*,
+    rate_limit::*,
+    report::*,
+    scheduled_post::*,
+    site::*,
+    site_document::*,
+    user::*,
+    webhook::*,
+    word_filter::*,
+    APIError,
+    Oper,
+    Perform,
+  },
+  get_ip,
   rate_limit::RateLimit,
-  routes::{ChatServerParam, DbPoolParam},
+  request_langs,
+  routes::{health, oembed, openapi, ChatServerParam, DbPoolParam},
   websocket::WebsocketInfo,
+  ErrorResponse,
+  LemmyError,
 };
-use actix_web::{client::Client, error::ErrorBadRequest, *};
+use actix_web::{client::Client, *};
 use serde::Serialize;
 
+/// The largest export a community import takes, as it comes in whole
+const MAX_IMPORT_SIZE: usize = 100 * 1024 * 1024;
+
 pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
   cfg.service(
     web::scope("/api/v1")
-      // Websockets
+      // Data that doesn't parse and unknown paths get errors like the ones of the operations
+      .app_data(web::JsonConfig::default().error_handler(|e, _req| data_error(e)))
+      .app_data(web::QueryConfig::default().error_handler(|e, _req| data_error(e)))
+      .default_service(web::route().to(unknown_operation))
+      // Websockets. Every op is routed below too, except the ones that change the session:
+      // Subscribe, Unsubscribe, ReAuth and UserJoin. The events stream is opened with its rooms
+      // instead.
       .service(web::resource("/ws").to(super::websocket::chat_route))
+      // The same updates as Server-Sent Events
+      .route("/events", web::get().to(super::events::get_events))
+      // Health checks, polled often so not rate limited
+      .route("/health", web::get().to(health::get_health))
+      .route("/ready", web::get().to(health::get_ready))
+      // For other sites to show cards of the links to posts
+      .route("/oembed", web::get().to(oembed::get_oembed))
+      // What the operations below take and answer
+      .route("/openapi.json", web::get().to(openapi::get_openapi))
       // Site
       .service(
         web::scope("/site")
@@ -21,8 +76,126 @@ pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
           .route("", web::post().to(route_post::<CreateSite>))
           .route("", web::put().to(route_post::<EditSite>))
           .route("/transfer", web::post().to(route_post::<TransferSite>))
+          .route(
+            "/announcement",
+            web::post().to(route_post::<CreateSiteAnnouncement>),
+          )
+          .route(
+            "/announcement/delete",
+            web::post().to(route_post::<DeleteSiteAnnouncement>),
+          )
+          .route(
+            "/announcement/dismiss",
+            web::post().to(route_post::<DismissSiteAnnouncement>),
+          )
+          .route("/document", web::get().to(route_get::<GetSiteDocument>))
+          .route("/document", web::put().to(route_post::<SaveSiteDocument>))
+          .route(
+            "/document/delete",
+            web::post().to(route_post::<DeleteSiteDocument>),
+          )
           .route("/config", web::get().to(route_get::<GetSiteConfig>))
-          .route("/config", web::put().to(route_post::<SaveSiteConfig>)),
+          .route("/config", web::put().to(route_post::<SaveSiteConfig>))
+          .route(
+            "/community_defaults",
+            web::get().to(route_get::<GetCommunityDefaults>),
+          )
+          .route(
+            "/community_defaults",
+            web::put().to(route_post::<EditCommunityDefaults>),
+          )
+          .route("/job_queue", web::get().to(route_get::<GetJobQueue>))
+          .service(
+            web::resource("/import")
+              .app_data(
+                web::JsonConfig::default()
+                  .limit(MAX_IMPORT_SIZE)
+                  .error_handler(|e, _req| data_error(e)),
+              )
+              .route(web::post().to(route_post::<ImportCommunity>))
+              .route(web::get().to(route_get::<GetCommunityImport>)),
+          )
+          .route(
+            "/blocked_domain",
+            web::get().to(route_get::<ListBlockedDomains>),
+          )
+          .route(
+            "/blocked_domain",
+            web::post().to(route_post::<CreateBlockedDomain>),
+          )
+          .route(
+            "/blocked_domain/delete",
+            web::post().to(route_post::<DeleteBlockedDomain>),
+          )
+          .route("/webhook", web::get().to(route_get::<ListWebhooks>))
+          .route("/webhook", web::post().to(route_post::<CreateWebhook>))
+          .route("/webhook", web::put().to(route_post::<EditWebhook>))
+          .route(
+            "/webhook/delete",
+            web::post().to(route_post::<DeleteWebhook>),
+          )
+          .route("/rate_limit", web::get().to(route_get::<GetRateLimits>))
+          .route("/rate_limit", web::put().to(route_post::<EditRateLimits>))
+          .route(
+            "/rate_limit/endpoint",
+            web::put().to(route_post::<SetEndpointRateLimit>),
+          )
+          .route(
+            "/rate_limit/endpoint/delete",
+            web::post().to(route_post::<RemoveEndpointRateLimit>),
+          )
+          .route(
+            "/rate_limit/exempt",
+            web::post().to(route_post::<ExemptFromRateLimits>),
+          )
+          .route(
+            "/federation",
+            web::get().to(route_get::<GetFederationSettings>),
+          )
+          .route(
+            "/federation",
+            web::put().to(route_post::<EditFederationSettings>),
+          )
+          .route(
+            "/posting_requirements",
+            web::get().to(route_get::<GetPostingRequirements>),
+          )
+          .route(
+            "/posting_requirements",
+            web::put().to(route_post::<SetPostingRequirements>),
+          )
+          .route("/word_filter", web::get().to(route_get::<ListWordFilters>))
+          .route(
+            "/word_filter",
+            web::post().to(route_post::<CreateWordFilter>),
+          )
+          .route("/word_filter", web::put().to(route_post::<EditWordFilter>))
+          .route(
+            "/word_filter/delete",
+            web::post().to(route_post::<DeleteWordFilter>),
+          )
+          .route(
+            "/email_domain",
+            web::get().to(route_get::<ListEmailDomains>),
+          )
+          .route(
+            "/email_domain",
+            web::post().to(route_post::<CreateEmailDomain>),
+          )
+          .route(
+            "/email_domain/delete",
+            web::post().to(route_post::<DeleteEmailDomain>),
+          )
+          .route("/ip_ban", web::get().to(route_get::<ListIpBans>))
+          .route("/ip_ban", web::post().to(route_post::<CreateIpBan>))
+          .route("/ip_ban/delete", web::post().to(route_post::<DeleteIpBan>))
+          .route("/purge/user", web::post().to(route_post::<PurgeUser>))
+          .route("/purge/post", web::post().to(route_post::<PurgePost>))
+          .route("/purge/comment", web::post().to(route_post::<PurgeComment>))
+          .route(
+            "/purge/community",
+            web::post().to(route_post::<PurgeCommunity>),
+          ),
       )
       .service(
         web::resource("/categories")
@@ -53,11 +226,74 @@ pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
           .route("", web::put().to(route_post::<EditCommunity>))
           .route("/list", web::get().to(route_get::<ListCommunities>))
           .route("/follow", web::post().to(route_post::<FollowCommunity>))
+          .route("/flair", web::get().to(route_get::<ListCommunityFlairs>))
+          .route("/flair", web::post().to(route_post::<CreateCommunityFlair>))
+          .route("/flair", web::put().to(route_post::<EditCommunityFlair>))
+          .route(
+            "/flair/delete",
+            web::post().to(route_post::<DeleteCommunityFlair>),
+          )
+          .route("/rule", web::get().to(route_get::<ListCommunityRules>))
+          .route("/rule", web::post().to(route_post::<CreateCommunityRule>))
+          .route("/rule", web::put().to(route_post::<EditCommunityRule>))
+          .route(
+            "/rule/delete",
+            web::post().to(route_post::<DeleteCommunityRule>),
+          )
+          .route("/automod", web::get().to(route_get::<ListAutomodRules>))
+          .route("/automod", web::post().to(route_post::<CreateAutomodRule>))
+          .route("/automod", web::put().to(route_post::<EditAutomodRule>))
+          .route(
+            "/automod/delete",
+            web::post().to(route_post::<DeleteAutomodRule>),
+          )
+          .route("/mod_note", web::get().to(route_get::<ListModNotes>))
+          .route("/mod_note", web::post().to(route_post::<CreateModNote>))
+          .route(
+            "/mod_note/delete",
+            web::post().to(route_post::<DeleteModNote>),
+          )
+          .route(
+            "/automod/test",
+            web::post().to(route_post::<TestAutomodRules>),
+          )
+          .route("/wiki", web::get().to(route_get::<GetWikiPage>))
+          .route("/wiki", web::post().to(route_post::<CreateWikiPage>))
+          .route("/wiki", web::put().to(route_post::<EditWikiPage>))
+          .route("/wiki/delete", web::post().to(route_post::<DeleteWikiPage>))
+          .route("/wiki/list", web::get().to(route_get::<ListWikiPages>))
+          .route(
+            "/wiki/revisions",
+            web::get().to(route_get::<GetWikiPageRevisions>),
+          )
           // Mod Actions
           .route("/transfer", web::post().to(route_post::<TransferCommunity>))
+          .route(
+            "/takeover",
+            web::post().to(route_post::<RequestCommunityTakeover>),
+          )
+          .route(
+            "/takeover/list",
+            web::get().to(route_get::<ListCommunityTakeoverRequests>),
+          )
+          .route(
+            "/takeover/resolve",
+            web::post().to(route_post::<ResolveCommunityTakeoverRequest>),
+          )
           .route("/ban_user", web::post().to(route_post::<BanFromCommunity>))
           .route("/mod", web::post().to(route_post::<AddModToCommunity>)),
       )
+      // Custom feeds
+      .service(
+        web::scope("/custom_feed")
+          .wrap(rate_limit.message())
+          .route("", web::get().to(route_get::<GetCustomFeed>))
+          .route("", web::post().to(route_post::<CreateCustomFeed>))
+          .route("", web::put().to(route_post::<EditCustomFeed>))
+          .route("/delete", web::post().to(route_post::<DeleteCustomFeed>))
+          .route("/list", web::get().to(route_get::<ListCustomFeeds>))
+          .route("/posts", web::get().to(route_get::<GetCustomFeedPosts>)),
+      )
       // Post
       .service(
         // Handle POST to /post separately to add the post() rate limitter
@@ -66,6 +302,17 @@ pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
           .wrap(rate_limit.post())
           .route(web::post().to(route_post::<CreatePost>)),
       )
+      .service(
+        web::resource("/post/scheduled")
+          .guard(guard::Post())
+          .wrap(rate_limit.post())
+          .route(web::post().to(route_post::<CreateScheduledPost>)),
+      )
+      .service(
+        web::resource("/post/report")
+          .wrap(rate_limit.report())
+          .route(web::post().to(route_post::<CreatePostReport>)),
+      )
       .service(
         web::scope("/post")
           .wrap(rate_limit.message())
@@ -73,22 +320,80 @@ pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
           .route("", web::put().to(route_post::<EditPost>))
           .route("/list", web::get().to(route_get::<GetPosts>))
           .route("/like", web::post().to(route_post::<CreatePostLike>))
-          .route("/save", web::put().to(route_post::<SavePost>)),
+          .route("/save", web::put().to(route_post::<SavePost>))
+          .route("/hide", web::put().to(route_post::<HidePost>))
+          .route("/watch", web::put().to(route_post::<WatchPost>))
+          .route("/bulk", web::put().to(route_post::<BulkModeratePosts>))
+          .route("/pending", web::get().to(route_get::<ListPendingPosts>))
+          .route("/approve", web::put().to(route_post::<ApprovePost>))
+          .route("/revisions", web::get().to(route_get::<GetPostRevisions>))
+          .route("/scheduled", web::get().to(route_get::<ListScheduledPosts>))
+          .route(
+            "/scheduled/delete",
+            web::post().to(route_post::<DeleteScheduledPost>),
+          )
+          .route(
+            "/refresh_embed",
+            web::post().to(route_post::<RefreshPostEmbed>),
+          ),
       )
       // Comment
+      .service(
+        web::resource("/comment/report")
+          .wrap(rate_limit.report())
+          .route(web::post().to(route_post::<CreateCommentReport>)),
+      )
       .service(
         web::scope("/comment")
           .wrap(rate_limit.message())
           .route("", web::post().to(route_post::<CreateComment>))
           .route("", web::put().to(route_post::<EditComment>))
+          .route("/list", web::get().to(route_get::<GetComments>))
           .route("/like", web::post().to(route_post::<CreateCommentLike>))
-          .route("/save", web::put().to(route_post::<SaveComment>)),
+          .route("/save", web::put().to(route_post::<SaveComment>))
+          .route("/pin", web::put().to(route_post::<PinComment>))
+          .route("/bulk", web::put().to(route_post::<BulkModerateComments>))
+          .route(
+            "/revisions",
+            web::get().to(route_get::<GetCommentRevisions>),
+          ),
+      )
+      // Reports
+      .service(
+        web::scope("/report")
+          .wrap(rate_limit.message())
+          .route("/list", web::get().to(route_get::<ListReports>))
+          .route(
+            "/post/resolve",
+            web::put().to(route_post::<ResolvePostReport>),
+          )
+          .route(
+            "/comment/resolve",
+            web::put().to(route_post::<ResolveCommentReport>),
+          ),
+      )
+      // Appeals
+      .service(
+        web::scope("/appeal")
+          .wrap(rate_limit.message())
+          .route("", web::post().to(route_post::<CreateAppeal>))
+          .route("/list", web::get().to(route_get::<ListAppeals>))
+          .route("/mine", web::get().to(route_get::<GetMyAppeals>))
+          .route("/resolve", web::put().to(route_post::<ResolveAppeal>)),
       )
       // Private Message
       .service(
         web::scope("/private_message")
           .wrap(rate_limit.message())
           .route("/list", web::get().to(route_get::<GetPrivateMessages>))
+          .route(
+            "/conversation",
+            web::get().to(route_get::<GetPrivateMessageConversation>),
+          )
+          .route(
+            "/conversation/list",
+            web::get().to(route_get::<GetPrivateMessageConversations>),
+          )
           .route("", web::post().to(route_post::<CreatePrivateMessage>))
           .route("", web::put().to(route_post::<EditPrivateMessage>)),
       )
@@ -101,26 +406,46 @@ pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
           .wrap(rate_limit.register())
           .route(web::post().to(route_post::<Register>)),
       )
+      // Handle /user/resend_verification_email separately, so it can't be used to spam emails
+      .service(
+        web::resource("/user/resend_verification_email")
+          .guard(guard::Post())
+          .wrap(rate_limit.register())
+          .route(web::post().to(route_post::<ResendVerificationEmail>)),
+      )
       // User actions
       .service(
         web::scope("/user")
           .wrap(rate_limit.message())
           .route("", web::get().to(route_get::<GetUserDetails>))
+          .route("/stats", web::get().to(route_get::<GetUserStats>))
+          .route("/get_captcha", web::get().to(route_get::<GetCaptcha>))
           .route("/mention", web::get().to(route_get::<GetUserMentions>))
           .route("/mention", web::put().to(route_post::<EditUserMention>))
           .route("/replies", web::get().to(route_get::<GetReplies>))
+          .route("/inbox", web::get().to(route_get::<GetInbox>))
+          .route("/unread_count", web::get().to(route_get::<GetUnreadCount>))
           .route(
             "/followed_communities",
             web::get().to(route_get::<GetFollowedCommunities>),
           )
+          .route("/follow", web::post().to(route_post::<FollowUser>))
+          .route(
+            "/followed_users_feed",
+            web::get().to(route_get::<GetFollowedUsersFeed>),
+          )
           // Admin action. I don't like that it's in /user
           .route("/ban", web::post().to(route_post::<BanUser>))
+          .route("/shadow_ban", web::post().to(route_post::<ShadowBanUser>))
           // Account actions. I don't like that they're in /user maybe /accounts
           .route("/login", web::post().to(route_post::<Login>))
           .route(
             "/delete_account",
             web::post().to(route_post::<DeleteAccount>),
           )
+          .route("/export", web::post().to(route_post::<ExportUserData>))
+          .route("/export", web::get().to(route_get::<GetUserDataExport>))
+          .route("/import", web::post().to(route_post::<ImportUserData>))
           .route(
             "/password_reset",
             web::post().to(route_post::<PasswordReset>),
@@ -129,6 +454,7 @@ pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
             "/password_change",
             web::post().to(route_post::<PasswordChange>),
           )
+          .route("/verify_email", web::post().to(route_post::<VerifyEmail>))
           // mark_all_as_read feels off being in this section as well
           .route(
             "/mark_all_as_read",
@@ -137,8 +463,57 @@ pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
           .route(
             "/save_user_settings",
             web::put().to(route_post::<SaveUserSettings>),
+          )
+          .route(
+            "/change_username",
+            web::post().to(route_post::<ChangeUsername>),
+          )
+          .route(
+            "/notification_settings",
+            web::get().to(route_get::<GetNotificationSettings>),
+          )
+          .route(
+            "/notification_settings",
+            web::put().to(route_post::<SaveNotificationSettings>),
+          )
+          .route(
+            "/digest/unsubscribe",
+            web::get().to(route_get::<UnsubscribeDigest>),
+          )
+          .route("/push", web::get().to(route_get::<GetPushPublicKey>))
+          .route(
+            "/push",
+            web::post().to(route_post::<RegisterPushSubscription>),
+          )
+          .route(
+            "/push/delete",
+            web::post().to(route_post::<DeletePushSubscription>),
+          )
+          .route(
+            "/muted_keyword",
+            web::get().to(route_get::<ListMutedKeywords>),
+          )
+          .route(
+            "/muted_keyword",
+            web::post().to(route_post::<CreateMutedKeyword>),
+          )
+          .route(
+            "/muted_keyword",
+            web::put().to(route_post::<EditMutedKeyword>),
+          )
+          .route(
+            "/muted_keyword/delete",
+            web::post().to(route_post::<DeleteMutedKeyword>),
           ),
       )
+      // Uploads
+      .service(
+        web::scope("/image")
+          .wrap(rate_limit.message())
+          .route("", web::post().to(super::media::upload_image))
+          .route("/list", web::get().to(route_get::<ListImages>))
+          .route("/delete", web::post().to(route_post::<DeleteImage>)),
+      )
       // Admin Actions
       .service(
         web::resource("/admin/add")
@@ -150,6 +525,7 @@ pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
 
 async fn perform<Request>(
   data: Request,
+  req: HttpRequest,
   client: &Client,
   db: DbPoolParam,
   chat_server: ChatServerParam,
@@ -163,18 +539,31 @@ where
     id: None,
   };
 
-  let oper: Oper<Request> = Oper::new(data, client.clone());
+  let ip = get_ip(&req.connection_info());
+  let oper: Oper<Request> = Oper::new(data, client.clone()).with_ip(ip);
 
-  let res = oper
-    .perform(&db, Some(ws_info))
-    .await
-    .map(|json| HttpResponse::Ok().json(json))
-    .map_err(ErrorBadRequest)?;
+  let res = match oper.perform(&db, Some(ws_info)).await {
+    Ok(json) => HttpResponse::Ok().json(json),
+    Err(e) => HttpResponse::BadRequest().json(e.to_localized_response(&request_langs(&req))),
+  };
   Ok(res)
 }
 
+fn data_error(e: impl std::fmt::Display) -> Error {
+  LemmyError::from(APIError::err(&e.to_string())).into()
+}
+
+/// The same as the websocket answers for an op it doesn't take
+async fn unknown_operation() -> HttpResponse {
+  HttpResponse::NotFound().json(ErrorResponse {
+    error: "unknown_op".to_string(),
+    message: None,
+  })
+}
+
 async fn route_get<Data>(
   data: web::Query<Data>,
+  req: HttpRequest,
   client: web::Data<Client>,
   db: DbPoolParam,
   chat_server: ChatServerParam,
@@ -183,11 +572,12 @@ where
   Data: Serialize + Send + 'static,
   Oper<Data>: Perform,
 {
-  perform::<Data>(data.0, &client, db, chat_server).await
+  perform::<Data>(data.0, req, &client, db, chat_server).await
 }
 
 async fn route_post<Data>(
   data: web::Json<Data>,
+  req: HttpRequest,
   client: web::Data<Client>,
   db: DbPoolParam,
   chat_server: ChatServerParam,
@@ -196,5 +586,5 @@ where
   Data: Serialize + Send + 'static,
   Oper<Data>: Perform,
 {
-  perform::<Data>(data.0, &client, db, chat_server).await
+  perform::<Data>(data.0, req, &client, db, chat_server).await
 }
@@ -0,0 +1,183 @@
+//! The same live updates as the websocket, as Server-Sent Events, for the clients and proxies that
+//! can't keep a websocket open. Each stream is a session of the chat server that only listens.
+
+use crate::{
+  api::claims::Claims,
+  get_ip,
+  routes::ChatServerParam,
+  websocket::server::{ChatServer, *},
+};
+use actix::prelude::*;
+use actix_web::{
+  error::{ErrorBadRequest, ErrorUnauthorized},
+  http::header::{CACHE_CONTROL, CONTENT_TYPE},
+  web::Bytes,
+  *,
+};
+use futures::{
+  channel::mpsc::{unbounded, UnboundedSender},
+  StreamExt,
+};
+use serde::Deserialize;
+use std::time::Duration;
+use tracing::debug;
+
+/// How often a comment line is sent, so that the proxies keep the stream open and a client that
+/// went away is noticed
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How long the clients wait before connecting again, in milliseconds
+const RETRY_MILLIS: u32 = 5000;
+
+#[derive(Deserialize)]
+pub struct EventsQuery {
+  /// Comma separated, like the rooms of `Subscribe`
+  post_ids: Option<String>,
+  community_ids: Option<String>,
+  /// The inbox of this user is sent too
+  auth: Option<String>,
+}
+
+pub async fn get_events(
+  req: HttpRequest,
+  info: web::Query<EventsQuery>,
+  chat_server: ChatServerParam,
+) -> Result<HttpResponse, Error> {
+  let mut rooms = parse_ids(&info.post_ids)?
+    .into_iter()
+    .map(Room::Post)
+    .chain(
+      parse_ids(&info.community_ids)?
+        .into_iter()
+        .map(Room::Community),
+    )
+    .collect::<Vec<Room>>();
+  if let Some(auth) = &info.auth {
+    let claims = Claims::decode(auth).map_err(|_| ErrorUnauthorized("not_logged_in"))?;
+    rooms.push(Room::User(claims.claims.id));
+  }
+
+  let (sender, receiver) = unbounded();
+  let _ = sender.unbounded_send(Bytes::from(format!("retry: {}\n\n", RETRY_MILLIS)));
+  SseSession {
+    cs_addr: chat_server.get_ref().to_owned(),
+    id: 0,
+    ip: get_ip(&req.connection_info()),
+    rooms,
+    sender,
+  }
+  .start();
+
+  Ok(
+    HttpResponse::Ok()
+      .header(CONTENT_TYPE, "text/event-stream")
+      .header(CACHE_CONTROL, "no-cache")
+      // Or nginx holds the events back
+      .header("X-Accel-Buffering", "no")
+      .streaming(receiver.map(Ok::<_, Error>)),
+  )
+}
+
+fn parse_ids(ids: &Option<String>) -> Result<Vec<i32>, Error> {
+  match ids {
+    Some(ids) => ids
+      .split(',')
+      .filter(|id| !id.is_empty())
+      .map(|id| id.trim().parse::<i32>().map_err(ErrorBadRequest))
+      .collect(),
+    None => Ok(Vec::new()),
+  }
+}
+
+struct SseSession {
+  cs_addr: Addr<ChatServer>,
+  id: usize,
+  ip: String,
+  rooms: Vec<Room>,
+  /// Goes to the response body. The stream ends when this is dropped with the session.
+  sender: UnboundedSender<Bytes>,
+}
+
+impl Actor for SseSession {
+  type Context = Context<Self>;
+
+  /// Registers with the chat server like a websocket session, then joins the rooms.
+  fn started(&mut self, ctx: &mut Self::Context) {
+    self.keepalive(ctx);
+
+    let addr = ctx.address();
+    self
+      .cs_addr
+      .send(Connect {
+        addr: addr.clone().recipient(),
+        close: addr.recipient(),
+        ip: self.ip.to_owned(),
+      })
+      .into_actor(self)
+      .then(|res, act, ctx| {
+        match res {
+          Ok(res) => act.id = res,
+          // something is wrong with chat server
+          _ => ctx.stop(),
+        }
+        act
+          .cs_addr
+          .send(ChangeSubscriptions {
+            id: act.id,
+            subscribe: act.rooms.clone(),
+            unsubscribe: Vec::new(),
+          })
+          .into_actor(act)
+          .then(|res, _, ctx| {
+            match res {
+              Ok(Ok(_)) => (),
+              // something is wrong with chat server, or too many rooms
+              _ => ctx.stop(),
+            }
+            actix::fut::ready(())
+          })
+      })
+      .wait(ctx);
+  }
+
+  fn stopping(&mut self, _ctx: &mut Self::Context) -> Running {
+    self.cs_addr.do_send(Disconnect {
+      id: self.id,
+      ip: self.ip.to_owned(),
+    });
+    Running::Stop
+  }
+}
+
+impl SseSession {
+  fn send(&self, event: String, ctx: &mut Context<Self>) {
+    if self.sender.unbounded_send(Bytes::from(event)).is_err() {
+      debug!("Event stream closed, disconnecting");
+      ctx.stop();
+    }
+  }
+
+  fn keepalive(&self, ctx: &mut Context<Self>) {
+    ctx.run_interval(KEEPALIVE_INTERVAL, |act, ctx| {
+      act.send(":\n\n".to_owned(), ctx);
+    });
+  }
+}
+
+/// The messages are the same as over the websocket. JSON has no line breaks to split them.
+impl Handler<WSMessage> for SseSession {
+  type Result = ();
+
+  fn handle(&mut self, msg: WSMessage, ctx: &mut Self::Context) {
+    self.send(format!("data: {}\n\n", msg.0), ctx);
+  }
+}
+
+/// Ending the stream has the client connect again after the retry delay.
+impl Handler<CloseSession> for SseSession {
+  type Result = ();
+
+  fn handle(&mut self, _: CloseSession, ctx: &mut Self::Context) {
+    ctx.stop();
+  }
+}
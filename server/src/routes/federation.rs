@@ -1,59 +1,84 @@
-use crate::apub::{
-  comment::get_apub_comment,
-  community::*,
-  community_inbox::community_inbox,
-  post::get_apub_post,
-  shared_inbox::shared_inbox,
-  user::*,
-  user_inbox::user_inbox,
-  APUB_JSON_CONTENT_TYPE,
+use crate::{
+  apub::{
+    comment::get_apub_comment,
+    community::*,
+    community_inbox::community_inbox,
+    post::{get_apub_post, get_apub_post_featured_replies},
+    shared_inbox::shared_inbox,
+    user::*,
+    user_inbox::user_inbox,
+    user_outbox::user_outbox,
+    APUB_JSON_CONTENT_TYPE,
+  },
+  rate_limit::RateLimit,
 };
 use actix_web::*;
 use http_signature_normalization_actix::digest::middleware::VerifyDigest;
 use lemmy_utils::settings::Settings;
 use sha2::{Digest, Sha256};
 
-pub fn config(cfg: &mut web::ServiceConfig) {
+/// The routes are always there, but only answer while federation is enabled, which the admins
+/// can change at runtime.
+pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
   if Settings::get().federation.enabled {
     println!("federation enabled, host is {}", Settings::get().hostname);
-    let digest_verifier = VerifyDigest::new(Sha256::new());
-
-    cfg
-      .service(
-        web::scope("/")
-          .guard(guard::Header("Accept", APUB_JSON_CONTENT_TYPE))
-          .route(
-            "/c/{community_name}",
-            web::get().to(get_apub_community_http),
-          )
-          .route(
-            "/c/{community_name}/followers",
-            web::get().to(get_apub_community_followers),
-          )
-          // TODO This is only useful for history which we aren't doing right now
-          // .route(
-          //   "/c/{community_name}/outbox",
-          //   web::get().to(get_apub_community_outbox),
-          // )
-          .route("/u/{user_name}", web::get().to(get_apub_user_http))
-          .route("/post/{post_id}", web::get().to(get_apub_post))
-          .route("/comment/{comment_id}", web::get().to(get_apub_comment)),
-      )
-      // Inboxes dont work with the header guard for some reason.
-      .service(
-        web::resource("/c/{community_name}/inbox")
-          .wrap(digest_verifier.clone())
-          .route(web::post().to(community_inbox)),
-      )
-      .service(
-        web::resource("/u/{user_name}/inbox")
-          .wrap(digest_verifier.clone())
-          .route(web::post().to(user_inbox)),
-      )
-      .service(
-        web::resource("/inbox")
-          .wrap(digest_verifier)
-          .route(web::post().to(shared_inbox)),
-      );
   }
+  let digest_verifier = VerifyDigest::new(Sha256::new());
+
+  cfg
+    .service(
+      web::scope("/")
+        .guard(guard::fn_guard(federation_enabled))
+        .guard(guard::Header("Accept", APUB_JSON_CONTENT_TYPE))
+        .route(
+          "/c/{community_name}",
+          web::get().to(get_apub_community_http),
+        )
+        .route(
+          "/c/{community_name}/followers",
+          web::get().to(get_apub_community_followers),
+        )
+        .route(
+          "/c/{community_name}/outbox",
+          web::get().to(get_apub_community_outbox),
+        )
+        .route("/u/{user_name}", web::get().to(get_apub_user_http))
+        .route("/post/{post_id}", web::get().to(get_apub_post))
+        .route(
+          "/post/{post_id}/featured_replies",
+          web::get().to(get_apub_post_featured_replies),
+        )
+        .route("/comment/{comment_id}", web::get().to(get_apub_comment)),
+    )
+    // Inboxes dont work with the header guard for some reason.
+    .service(
+      web::resource("/c/{community_name}/inbox")
+        .guard(guard::fn_guard(federation_enabled))
+        .wrap(digest_verifier.clone())
+        .route(web::post().to(community_inbox)),
+    )
+    .service(
+      web::resource("/u/{user_name}/inbox")
+        .guard(guard::fn_guard(federation_enabled))
+        .wrap(digest_verifier.clone())
+        .route(web::post().to(user_inbox)),
+    )
+    // Posted to by the user's own clients, which aren't signed like the other instances. Rate
+    // limited by the handler, which knows what the activity is.
+    .service(
+      web::resource("/u/{user_name}/outbox")
+        .guard(guard::fn_guard(federation_enabled))
+        .data(rate_limit.clone())
+        .route(web::post().to(user_outbox)),
+    )
+    .service(
+      web::resource("/inbox")
+        .guard(guard::fn_guard(federation_enabled))
+        .wrap(digest_verifier)
+        .route(web::post().to(shared_inbox)),
+    );
+}
+
+pub fn federation_enabled(_head: &dev::RequestHead) -> bool {
+  Settings::get().federation.enabled
 }
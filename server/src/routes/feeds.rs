@@ -1,25 +1,42 @@
-use crate::{api::claims::Claims, blocking, routes::DbPoolParam, LemmyError};
+use crate::{api::claims::Claims, blocking_read, routes::DbPoolParam, LemmyError};
 use actix_web::{error::ErrorBadRequest, *};
-use chrono::{DateTime, NaiveDateTime, Utc};
-use diesel::{
-  r2d2::{ConnectionManager, Pool},
-  PgConnection,
+use atom_syndication::{
+  CategoryBuilder as AtomCategoryBuilder,
+  ContentBuilder,
+  Entry,
+  EntryBuilder,
+  Feed,
+  FeedBuilder,
+  LinkBuilder,
+  PersonBuilder,
 };
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::PgConnection;
 use lemmy_db::{
   comment_view::{ReplyQueryBuilder, ReplyView},
   community::Community,
   post_view::{PostQueryBuilder, PostView},
   site_view::SiteView,
-  user::User_,
+  user_alias::UserAlias,
   user_mention_view::{UserMentionQueryBuilder, UserMentionView},
   ListingType,
   SortType,
 };
 use lemmy_utils::{markdown_to_html, settings::Settings};
-use rss::{CategoryBuilder, ChannelBuilder, GuidBuilder, Item, ItemBuilder};
+use rss::{
+  CategoryBuilder,
+  Channel,
+  ChannelBuilder,
+  Enclosure,
+  EnclosureBuilder,
+  GuidBuilder,
+  Item,
+  ItemBuilder,
+};
 use serde::Deserialize;
 use std::str::FromStr;
 use strum::ParseError;
+use url::Url;
 
 #[derive(Deserialize)]
 pub struct Params {
@@ -33,27 +50,53 @@ enum RequestType {
   Inbox,
 }
 
+#[derive(Clone, Copy)]
+enum FeedFormat {
+  Rss,
+  Atom,
+}
+
 pub fn config(cfg: &mut web::ServiceConfig) {
   cfg
-    .route("/feeds/{type}/{name}.xml", web::get().to(get_feed))
-    .route("/feeds/all.xml", web::get().to(get_all_feed));
+    .route("/feeds/{type}/{name}.xml", web::get().to(get_rss_feed))
+    .route("/feeds/{type}/{name}.atom", web::get().to(get_atom_feed))
+    .route("/feeds/all.xml", web::get().to(get_all_rss_feed))
+    .route("/feeds/all.atom", web::get().to(get_all_atom_feed));
+}
+
+async fn get_all_rss_feed(
+  info: web::Query<Params>,
+  db: DbPoolParam,
+) -> Result<HttpResponse, Error> {
+  get_all_feed(info, db, FeedFormat::Rss).await
 }
 
-async fn get_all_feed(info: web::Query<Params>, db: DbPoolParam) -> Result<HttpResponse, Error> {
+async fn get_all_atom_feed(
+  info: web::Query<Params>,
+  db: DbPoolParam,
+) -> Result<HttpResponse, Error> {
+  get_all_feed(info, db, FeedFormat::Atom).await
+}
+
+async fn get_all_feed(
+  info: web::Query<Params>,
+  db: DbPoolParam,
+  format: FeedFormat,
+) -> Result<HttpResponse, Error> {
   let sort_type = get_sort_type(info).map_err(ErrorBadRequest)?;
 
-  let rss = blocking(&db, move |conn| get_feed_all_data(conn, &sort_type))
+  let builder = blocking_read(&db, move |conn| get_feed_all_data(conn, &sort_type))
     .await?
     .map_err(ErrorBadRequest)?;
 
-  Ok(
-    HttpResponse::Ok()
-      .content_type("application/rss+xml")
-      .body(rss),
-  )
+  let channel = builder.build().map_err(ErrorBadRequest)?;
+  Ok(feed_response(&channel, format))
 }
 
-fn get_feed_all_data(conn: &PgConnection, sort_type: &SortType) -> Result<String, LemmyError> {
+fn get_feed_all_data(
+  conn: &PgConnection,
+  sort_type: &SortType,
+) -> Result<ChannelBuilder, LemmyError> {
   let site_view = SiteView::read(&conn)?;
 
   let posts = PostQueryBuilder::create(&conn)
@@ -73,13 +116,30 @@ fn get_feed_all_data(conn: &PgConnection, sort_type: &SortType) -> Result<String
     channel_builder.description(&site_desc);
   }
 
-  Ok(channel_builder.build().unwrap().to_string())
+  Ok(channel_builder)
+}
+
+async fn get_rss_feed(
+  path: web::Path<(String, String)>,
+  info: web::Query<Params>,
+  db: DbPoolParam,
+) -> Result<HttpResponse, Error> {
+  get_feed(path, info, db, FeedFormat::Rss).await
+}
+
+async fn get_atom_feed(
+  path: web::Path<(String, String)>,
+  info: web::Query<Params>,
+  db: DbPoolParam,
+) -> Result<HttpResponse, Error> {
+  get_feed(path, info, db, FeedFormat::Atom).await
 }
 
 async fn get_feed(
   path: web::Path<(String, String)>,
   info: web::Query<Params>,
-  db: web::Data<Pool<ConnectionManager<PgConnection>>>,
+  db: DbPoolParam,
+  format: FeedFormat,
 ) -> Result<HttpResponse, Error> {
   let sort_type = get_sort_type(info).map_err(ErrorBadRequest)?;
 
@@ -93,7 +153,7 @@ async fn get_feed(
 
   let param = path.1.to_owned();
 
-  let builder = blocking(&db, move |conn| match request_type {
+  let builder = blocking_read(&db, move |conn| match request_type {
     RequestType::User => get_feed_user(conn, &sort_type, param),
     RequestType::Community => get_feed_community(conn, &sort_type, param),
     RequestType::Front => get_feed_front(conn, &sort_type, param),
@@ -102,13 +162,140 @@ async fn get_feed(
   .await?
   .map_err(ErrorBadRequest)?;
 
-  let rss = builder.build().map_err(ErrorBadRequest)?.to_string();
+  let channel = builder.build().map_err(ErrorBadRequest)?;
+  Ok(feed_response(&channel, format))
+}
 
-  Ok(
-    HttpResponse::Ok()
+fn feed_response(channel: &Channel, format: FeedFormat) -> HttpResponse {
+  match format {
+    FeedFormat::Rss => HttpResponse::Ok()
       .content_type("application/rss+xml")
-      .body(rss),
-  )
+      .body(channel.to_string()),
+    FeedFormat::Atom => HttpResponse::Ok()
+      .content_type("application/atom+xml")
+      .body(channel_to_atom(channel).to_string()),
+  }
+}
+
+/// The Atom feeds are the RSS ones written the other way, so the two can't drift apart.
+pub fn channel_to_atom(channel: &Channel) -> Feed {
+  let entries: Vec<Entry> = channel.items().iter().map(item_to_atom).collect();
+
+  // A feed was last updated when its newest entry was
+  let updated = entries
+    .iter()
+    .map(|e| e.updated)
+    .max()
+    .unwrap_or_else(|| Utc::now().into());
+
+  let link = LinkBuilder::default()
+    .href(channel.link().to_owned())
+    .rel("alternate".to_string())
+    .build()
+    .unwrap();
+
+  let mut feed = FeedBuilder::default()
+    .title(escape_xml(channel.title()))
+    .id(escape_xml(channel.link()))
+    .updated(updated)
+    .links(vec![link])
+    .entries(entries)
+    .build()
+    .unwrap();
+
+  if !channel.description().is_empty() {
+    feed.subtitle = Some(escape_xml(channel.description()));
+  }
+
+  feed
+}
+
+fn item_to_atom(item: &Item) -> Entry {
+  let updated = item
+    .pub_date()
+    .and_then(|d| DateTime::parse_from_rfc2822(d).ok())
+    .unwrap_or_else(|| Utc::now().into());
+
+  let author = PersonBuilder::default()
+    .name(escape_xml(&without_link(item.author().unwrap_or_default())))
+    .build()
+    .unwrap();
+
+  let categories = item
+    .categories()
+    .iter()
+    .map(|c| {
+      AtomCategoryBuilder::default()
+        .term(without_link(c.name()))
+        .build()
+        .unwrap()
+    })
+    .collect::<Vec<_>>();
+
+  let mut links = Vec::new();
+  if let Some(link) = item.link() {
+    links.push(
+      LinkBuilder::default()
+        .href(link.to_owned())
+        .rel("alternate".to_string())
+        .build()
+        .unwrap(),
+    );
+  }
+  if let Some(comments) = item.comments() {
+    if item.link() != Some(comments) {
+      links.push(
+        LinkBuilder::default()
+          .href(comments.to_owned())
+          .rel("replies".to_string())
+          .build()
+          .unwrap(),
+      );
+    }
+  }
+  if let Some(enclosure) = item.enclosure() {
+    links.push(
+      LinkBuilder::default()
+        .href(enclosure.url().to_owned())
+        .rel("enclosure".to_string())
+        .mime_type(enclosure.mime_type().to_owned())
+        .build()
+        .unwrap(),
+    );
+  }
+
+  let content = ContentBuilder::default()
+    .value(item.description().map(escape_xml))
+    .content_type("html".to_string())
+    .build()
+    .unwrap();
+
+  EntryBuilder::default()
+    .title(escape_xml(item.title().unwrap_or_default()))
+    .id(escape_xml(
+      item.guid().map(|g| g.value()).unwrap_or_default(),
+    ))
+    .updated(updated)
+    .published(updated)
+    .authors(vec![author])
+    .categories(categories)
+    .links(links)
+    .content(content)
+    .build()
+    .unwrap()
+}
+
+/// atom_syndication writes the text of the elements as it's given, but escapes the attributes.
+fn escape_xml(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+}
+
+/// The RSS authors and categories have a link after the name, which doesn't belong in Atom.
+fn without_link(text: &str) -> String {
+  text.split(" <a ").next().unwrap_or_default().to_owned()
 }
 
 fn get_sort_type(info: web::Query<Params>) -> Result<SortType, ParseError> {
@@ -125,7 +312,7 @@ fn get_feed_user(
   user_name: String,
 ) -> Result<ChannelBuilder, LemmyError> {
   let site_view = SiteView::read(&conn)?;
-  let user = User_::find_by_username(&conn, &user_name)?;
+  let user = UserAlias::read_user(&conn, &user_name)?;
   let user_url = user.get_profile_url(&Settings::get().hostname);
 
   let posts = PostQueryBuilder::create(&conn)
@@ -326,11 +513,14 @@ fn create_post_items(posts: Vec<PostView>) -> Vec<Item> {
       .build();
     i.categories(vec![category.unwrap()]);
 
+    if let Some(enclosure) = build_enclosure(&p.url, &p.thumbnail_url) {
+      i.enclosure(enclosure);
+    }
+
     if let Some(url) = p.url {
       i.link(url);
     }
 
-    // TODO add images
     let mut description = format!("submitted by <a href=\"{}\">{}</a> to <a href=\"{}\">{}</a><br>{} points | <a href=\"{}\">{} comments</a>",
     author_url,
     p.creator_name,
@@ -352,3 +542,57 @@ fn create_post_items(posts: Vec<PostView>) -> Vec<Item> {
 
   items
 }
+
+/// Link posts to media files get that file as their enclosure, other posts fall back to their
+/// thumbnail, if they have one.
+fn build_enclosure(url: &Option<String>, thumbnail_url: &Option<String>) -> Option<Enclosure> {
+  let media = url
+    .as_ref()
+    .and_then(|u| media_mime_type(u).map(|mime_type| (u.to_owned(), mime_type)));
+
+  let (enclosure_url, mime_type) = match media {
+    Some(media) => media,
+    None => {
+      let thumbnail_url = thumbnail_url.as_ref()?;
+      // The federated posts have the whole url of the thumbnail, the local ones its pictrs name
+      let enclosure_url = if Url::parse(thumbnail_url).is_ok() {
+        thumbnail_url.to_owned()
+      } else {
+        format!(
+          "https://{}/pictrs/image/{}",
+          Settings::get().hostname,
+          thumbnail_url
+        )
+      };
+      (
+        enclosure_url,
+        media_mime_type(thumbnail_url).unwrap_or("image/jpeg"),
+      )
+    }
+  };
+
+  // The size isn't known without fetching the file, and 0 is the accepted value for that
+  EnclosureBuilder::default()
+    .url(enclosure_url)
+    .length("0".to_string())
+    .mime_type(mime_type.to_string())
+    .build()
+    .ok()
+}
+
+fn media_mime_type(url: &str) -> Option<&'static str> {
+  let path = url.split(|c| c == '?' || c == '#').next()?.to_lowercase();
+  match path.rsplit('.').next()? {
+    "jpg" | "jpeg" => Some("image/jpeg"),
+    "png" => Some("image/png"),
+    "gif" => Some("image/gif"),
+    "webp" => Some("image/webp"),
+    "svg" => Some("image/svg+xml"),
+    "mp4" => Some("video/mp4"),
+    "webm" => Some("video/webm"),
+    "mp3" => Some("audio/mpeg"),
+    "ogg" => Some("audio/ogg"),
+    "wav" => Some("audio/wav"),
+    _ => None,
+  }
+}
@@ -0,0 +1,70 @@
+//! For container orchestrators and load balancers. Health only says the server is up, while
+//! ready also checks that it can do its work.
+
+use crate::{blocking, job_queue::job_workers_alive, routes::DbPoolParam};
+use actix_web::*;
+use lemmy_db::newest_migration;
+use lemmy_utils::settings::Settings;
+use serde::Serialize;
+use std::sync::RwLock;
+
+lazy_static! {
+  /// The newest migration this build ran on startup
+  static ref EXPECTED_MIGRATION: RwLock<Option<String>> = RwLock::new(None);
+}
+
+#[derive(Serialize)]
+pub struct HealthResponse {
+  status: String,
+}
+
+#[derive(Serialize)]
+pub struct ReadyResponse {
+  database: bool,
+  migrations: bool,
+  job_workers: bool,
+  federation_enabled: bool,
+}
+
+pub fn set_expected_migration(version: Option<String>) {
+  if let Ok(mut expected) = EXPECTED_MIGRATION.write() {
+    *expected = version;
+  }
+}
+
+pub async fn get_health() -> HttpResponse {
+  HttpResponse::Ok().json(HealthResponse {
+    status: "ok".to_string(),
+  })
+}
+
+/// The database has to answer, with the migrations this build expects, and the job workers, which
+/// also deliver the federated activities, can't be stuck.
+pub async fn get_ready(db: DbPoolParam) -> HttpResponse {
+  let newest = blocking(&db, move |conn| newest_migration(conn)).await;
+  let (database, migrations) = match newest {
+    Ok(Ok(version)) => {
+      let expected = EXPECTED_MIGRATION
+        .read()
+        .map(|expected| expected.to_owned())
+        .unwrap_or(None);
+      (true, expected.is_some() && version == expected)
+    }
+    _ => (false, false),
+  };
+
+  let settings = Settings::get();
+  let job_workers = settings.jobs.workers == 0 || job_workers_alive();
+
+  let ready = ReadyResponse {
+    database,
+    migrations,
+    job_workers,
+    federation_enabled: settings.federation.enabled,
+  };
+  if database && migrations && job_workers {
+    HttpResponse::Ok().json(ready)
+  } else {
+    HttpResponse::ServiceUnavailable().json(ready)
+  }
+}
@@ -0,0 +1,57 @@
+use crate::{
+  image_proxy::{is_signed_url, proxied_file},
+  routes::{media::stream_pictrs_file, DbPoolParam},
+};
+use actix_web::{
+  client::Client,
+  error::{ErrorBadGateway, ErrorBadRequest},
+  http::header::LOCATION,
+  *,
+};
+use lemmy_utils::settings::Settings;
+use serde::Deserialize;
+use url::Url;
+
+#[derive(Deserialize)]
+pub struct ImageProxyQuery {
+  url: String,
+  signature: String,
+  /// The longest side in pixels, for a smaller version
+  thumbnail: Option<u32>,
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+  cfg.route("/image_proxy", web::get().to(get_image));
+}
+
+async fn get_image(
+  info: web::Query<ImageProxyQuery>,
+  db: DbPoolParam,
+  client: web::Data<Client>,
+) -> Result<HttpResponse, Error> {
+  if !is_signed_url(&info.url, &info.signature) {
+    return Ok(HttpResponse::NotFound().finish());
+  }
+  let url = Url::parse(&info.url).map_err(ErrorBadRequest)?;
+  if url.scheme() != "http" && url.scheme() != "https" {
+    return Err(ErrorBadRequest("not an http url"));
+  }
+
+  // The images that were proxied before it was turned off are hotlinked again
+  let settings = Settings::get().image_proxy;
+  if !settings.enabled {
+    return Ok(
+      HttpResponse::Found()
+        .header(LOCATION, url.as_str())
+        .finish(),
+    );
+  }
+
+  let file = proxied_file(&db, &client, url.as_str())
+    .await
+    .map_err(ErrorBadGateway)?;
+  let thumbnail = info
+    .thumbnail
+    .map(|size| size.min(settings.max_thumbnail_size));
+  stream_pictrs_file(&client, &file, thumbnail).await
+}
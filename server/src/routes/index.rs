@@ -1,3 +1,4 @@
+use crate::routes::oembed::oembed_discovery_link;
 use actix_files::NamedFile;
 use actix_web::*;
 use lemmy_utils::settings::Settings;
@@ -16,7 +17,7 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     .route("/communities/page/{page}", web::get().to(index))
     .route("/communities", web::get().to(index))
     .route("/post/{id}/comment/{id2}", web::get().to(index))
-    .route("/post/{id}", web::get().to(index))
+    .route("/post/{id}", web::get().to(post_index))
     .route(
       "/c/{name}/data_type/{data_type}/sort/{sort}/page/{page}",
       web::get().to(index),
@@ -48,3 +49,23 @@ async fn index() -> Result<NamedFile, Error> {
     Settings::get().front_end_dir + "/index.html",
   )?)
 }
+
+/// The front end, with the link to the oEmbed of the post in its head.
+async fn post_index(path: web::Path<String>) -> Result<HttpResponse, Error> {
+  let html =
+    web::block(|| std::fs::read_to_string(Settings::get().front_end_dir + "/index.html")).await?;
+  let html = match path.parse::<i32>() {
+    Ok(post_id) => html.replacen(
+      "</head>",
+      &format!("{}</head>", oembed_discovery_link(post_id)),
+      1,
+    ),
+    Err(_e) => html,
+  };
+
+  Ok(
+    HttpResponse::Ok()
+      .content_type("text/html; charset=utf-8")
+      .body(html),
+  )
+}
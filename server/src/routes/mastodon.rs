@@ -0,0 +1,982 @@
+//! The part of the Mastodon client API the apps need to browse and reply, made from the Lemmy
+//! operations. The timelines are post listings, and replying, favouriting and bookmarking a status
+//! votes on or saves the post or comment. Apps register and log in through the OAuth pages here,
+//! and their access tokens are kept until the app revokes them.
+
+use crate::{
+  api::{
+    claims::Claims,
+    comment::{CommentResponse, CreateComment, CreateCommentLike, SaveComment},
+    post::{CreatePostLike, PostResponse, SavePost},
+    user::{Login, LoginResponse},
+    APIError,
+  },
+  blocking,
+  blocking_read,
+  digest::escape_html,
+  mastodon::*,
+  rate_limit::RateLimit,
+  routes::{oauth_bearer, perform, ChatServerParam, DbPoolParam},
+  DbPool,
+  ErrorResponse,
+  LemmyError,
+};
+use actix_web::{
+  client::Client,
+  http::header::{CONTENT_TYPE, LINK, LOCATION},
+  *,
+};
+use chrono::{NaiveDateTime, Utc};
+use diesel::PgConnection;
+use lemmy_db::{
+  comment::Comment,
+  comment_view::{CommentQueryBuilder, CommentView, ReplyQueryBuilder},
+  oauth::{OAuthApp, OAuthAppForm, OAuthAuthorization, OAuthToken},
+  post::Post,
+  post_view::{PostQueryBuilder, PostView},
+  site_view::SiteView,
+  user_mention_view::UserMentionQueryBuilder,
+  user_view::UserView,
+  Crud,
+  ListingType,
+  SortType,
+};
+use lemmy_utils::{generate_random_string, get_apub_protocol_string, settings::Settings};
+use serde::{de::DeserializeOwned, Deserialize};
+use url::{form_urlencoded, Url};
+
+/// How many statuses a page has when the app doesn't say, and the most it can ask for
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 40;
+
+/// Only the latest replies and mentions are paged through
+const NOTIFICATION_HISTORY: i64 = 200;
+
+/// The redirect of the apps that show the code for the user to paste instead
+const OOB_REDIRECT_URI: &str = "urn:ietf:wg:oauth:2.0:oob";
+
+#[derive(Deserialize)]
+struct Page {
+  max_id: Option<String>,
+  since_id: Option<String>,
+  min_id: Option<String>,
+  limit: Option<i64>,
+  /// Asked for with the statuses of an account. Lemmy users have no pinned statuses.
+  pinned: Option<bool>,
+}
+
+impl Page {
+  fn limit(&self) -> i64 {
+    self.limit.unwrap_or(DEFAULT_LIMIT).max(1).min(MAX_LIMIT)
+  }
+}
+
+#[derive(Deserialize)]
+struct AppForm {
+  client_name: String,
+  redirect_uris: String,
+  website: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AuthorizeQuery {
+  client_id: String,
+  redirect_uri: String,
+  state: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AuthorizeForm {
+  client_id: String,
+  redirect_uri: String,
+  state: String,
+  username_or_email: String,
+  password: String,
+}
+
+#[derive(Deserialize)]
+struct TokenForm {
+  grant_type: String,
+  client_id: String,
+  client_secret: String,
+  redirect_uri: Option<String>,
+  code: Option<String>,
+  username: Option<String>,
+  password: Option<String>,
+  scope: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RevokeForm {
+  client_id: String,
+  client_secret: String,
+  token: String,
+}
+
+#[derive(Deserialize)]
+struct StatusForm {
+  status: String,
+  in_reply_to_id: Option<String>,
+}
+
+pub fn config(cfg: &mut web::ServiceConfig, rate_limit: &RateLimit) {
+  // These go before the Lemmy API, as its /api/v1 scope answers every path under it
+  cfg
+    .service(
+      web::scope("/oauth")
+        .wrap(rate_limit.register())
+        .route("/authorize", web::get().to(get_authorize))
+        .route("/authorize", web::post().to(post_authorize))
+        .route("/token", web::post().to(post_token))
+        .route("/revoke", web::post().to(post_revoke)),
+    )
+    .service(
+      web::scope("/api/v1/apps")
+        .wrap(rate_limit.register())
+        .route("", web::post().to(create_app)),
+    )
+    .service(
+      web::scope("/api/v1/instance")
+        .wrap(rate_limit.message())
+        .route("", web::get().to(get_instance)),
+    )
+    .service(
+      web::scope("/api/v1/accounts")
+        .wrap(rate_limit.message())
+        .route("/verify_credentials", web::get().to(verify_credentials))
+        .route("/{id}", web::get().to(get_account))
+        .route("/{id}/statuses", web::get().to(get_account_statuses)),
+    )
+    .service(
+      web::scope("/api/v1/timelines")
+        .wrap(rate_limit.message())
+        .route("/home", web::get().to(get_home_timeline))
+        .route("/public", web::get().to(get_public_timeline)),
+    )
+    .service(
+      // Replying counts against the post limit, like it does through the API
+      web::resource("/api/v1/statuses")
+        .guard(guard::Post())
+        .wrap(rate_limit.post())
+        .route(web::post().to(create_status)),
+    )
+    .service(
+      web::scope("/api/v1/statuses")
+        .wrap(rate_limit.message())
+        .route("/{id}", web::get().to(get_status))
+        .route("/{id}/context", web::get().to(get_status_context))
+        .route("/{id}/favourite", web::post().to(favourite_status))
+        .route("/{id}/unfavourite", web::post().to(unfavourite_status))
+        .route("/{id}/bookmark", web::post().to(bookmark_status))
+        .route("/{id}/unbookmark", web::post().to(unbookmark_status)),
+    )
+    .service(
+      web::scope("/api/v1/notifications")
+        .wrap(rate_limit.message())
+        .route("", web::get().to(get_notifications)),
+    );
+}
+
+/// The app is kept, so it can only send the user back to the redirect uris it registered
+async fn create_app(
+  req: HttpRequest,
+  body: web::Bytes,
+  db: DbPoolParam,
+) -> Result<HttpResponse, Error> {
+  let form: AppForm = parse_body(&req, &body)?;
+  let app_form = OAuthAppForm {
+    client_id: generate_random_string(),
+    client_secret: generate_random_string(),
+    name: form.client_name,
+    website: form.website,
+    redirect_uris: form.redirect_uris,
+  };
+  let app = match blocking(&db, move |conn| OAuthApp::create(conn, &app_form)).await? {
+    Ok(app) => app,
+    Err(_e) => return Err(LemmyError::from(APIError::err("couldnt_create_app")).into()),
+  };
+
+  Ok(HttpResponse::Ok().json(Application {
+    id: app.id.to_string(),
+    name: app.name,
+    website: app.website,
+    redirect_uri: app.redirect_uris,
+    client_id: app.client_id,
+    client_secret: app.client_secret,
+  }))
+}
+
+async fn get_authorize(
+  query: web::Query<AuthorizeQuery>,
+  db: DbPoolParam,
+) -> Result<HttpResponse, Error> {
+  if registered_app(&db, &query.client_id, &query.redirect_uri)
+    .await?
+    .is_none()
+  {
+    return Ok(invalid_redirect_page());
+  }
+  let state = query.state.to_owned().unwrap_or_default();
+  Ok(authorize_page(
+    &query.client_id,
+    &query.redirect_uri,
+    &state,
+    None,
+  ))
+}
+
+/// Logs the user in, and sends them back to the app with a code it can exchange for a token once
+async fn post_authorize(
+  req: HttpRequest,
+  form: web::Form<AuthorizeForm>,
+  client: web::Data<Client>,
+  db: DbPoolParam,
+  chat_server: ChatServerParam,
+) -> Result<HttpResponse, Error> {
+  let form = form.into_inner();
+  let app = match registered_app(&db, &form.client_id, &form.redirect_uri).await? {
+    Some(app) => app,
+    None => return Ok(invalid_redirect_page()),
+  };
+
+  let login = Login {
+    username_or_email: form.username_or_email,
+    password: form.password,
+  };
+  let user_id = match perform::<Login>(login, &req, &client, &db, &chat_server).await {
+    Ok(LoginResponse { jwt }) => {
+      Claims::decode(&jwt)
+        .map_err(|_| LemmyError::from(APIError::err("not_logged_in")))?
+        .claims
+        .id
+    }
+    Err(e) => {
+      return Ok(authorize_page(
+        &form.client_id,
+        &form.redirect_uri,
+        &form.state,
+        Some(&e.message()),
+      ))
+    }
+  };
+
+  let code = generate_random_string();
+  let redirect_uri = form.redirect_uri.to_owned();
+  let code_ = code.to_owned();
+  write(&db, move |conn| {
+    OAuthAuthorization::create_code(conn, app.id, user_id, &redirect_uri, &code_)
+  })
+  .await?;
+
+  if form.redirect_uri == OOB_REDIRECT_URI {
+    return Ok(oauth_page(&format!(
+      "<p>Paste this code in the app:</p><p><code>{}</code></p>",
+      escape_html(&code)
+    )));
+  }
+
+  let mut redirect = Url::parse(&form.redirect_uri)
+    .map_err(|_| LemmyError::from(APIError::err("invalid_redirect_uri")))?;
+  redirect.query_pairs_mut().append_pair("code", &code);
+  if !form.state.is_empty() {
+    redirect.query_pairs_mut().append_pair("state", &form.state);
+  }
+  Ok(
+    HttpResponse::Found()
+      .header(LOCATION, redirect.as_str())
+      .finish(),
+  )
+}
+
+/// Exchanges the code the app got back, or the password of apps that log in themselves, for a
+/// token
+async fn post_token(
+  req: HttpRequest,
+  body: web::Bytes,
+  client: web::Data<Client>,
+  db: DbPoolParam,
+  chat_server: ChatServerParam,
+) -> Result<HttpResponse, Error> {
+  let form: TokenForm = parse_body(&req, &body)?;
+  let app = match app_from_credentials(&db, &form.client_id, &form.client_secret).await? {
+    Some(app) => app,
+    None => return Ok(oauth_error("invalid_client")),
+  };
+
+  let user_id = match form.grant_type.as_str() {
+    "authorization_code" => {
+      let code = form.code.unwrap_or_default();
+      let redirect_uri = form.redirect_uri.unwrap_or_default();
+      let app_id = app.id;
+      match blocking(&db, move |conn| {
+        OAuthAuthorization::consume_code(conn, app_id, &redirect_uri, &code)
+      })
+      .await?
+      {
+        Ok(authorization) => authorization.user_id,
+        Err(_e) => return Ok(oauth_error("invalid_grant")),
+      }
+    }
+    "password" => {
+      let login = Login {
+        username_or_email: form.username.unwrap_or_default(),
+        password: form.password.unwrap_or_default(),
+      };
+      match perform::<Login>(login, &req, &client, &db, &chat_server).await {
+        Ok(res) => match Claims::decode(&res.jwt) {
+          Ok(token) => token.claims.id,
+          Err(_e) => return Ok(oauth_error("invalid_grant")),
+        },
+        Err(_e) => return Ok(oauth_error("invalid_grant")),
+      }
+    }
+    _ => return Ok(oauth_error("unsupported_grant_type")),
+  };
+
+  let access_token = generate_random_string();
+  let scope = form.scope.unwrap_or_else(|| "read write".to_owned());
+  let token = access_token.to_owned();
+  let token_scope = scope.to_owned();
+  write(&db, move |conn| {
+    OAuthToken::create_token(conn, app.id, user_id, &token_scope, &token)
+  })
+  .await?;
+
+  Ok(HttpResponse::Ok().json(Token {
+    access_token,
+    token_type: "Bearer",
+    scope,
+    created_at: Utc::now().timestamp(),
+  }))
+}
+
+/// Logs the app out. Like Mastodon, a token that's already gone is revoked too.
+async fn post_revoke(
+  req: HttpRequest,
+  body: web::Bytes,
+  db: DbPoolParam,
+) -> Result<HttpResponse, Error> {
+  let form: RevokeForm = parse_body(&req, &body)?;
+  let app = match app_from_credentials(&db, &form.client_id, &form.client_secret).await? {
+    Some(app) => app,
+    None => return Ok(oauth_error("invalid_client")),
+  };
+
+  let token = form.token;
+  write(&db, move |conn| OAuthToken::revoke(conn, app.id, &token)).await?;
+  Ok(HttpResponse::Ok().json(serde_json::json!({})))
+}
+
+/// The app, if it registered the redirect uri
+async fn registered_app(
+  pool: &DbPool,
+  client_id: &str,
+  redirect_uri: &str,
+) -> Result<Option<OAuthApp>, LemmyError> {
+  let client_id = client_id.to_owned();
+  let app = blocking(pool, move |conn| {
+    OAuthApp::read_from_client_id(conn, &client_id)
+  })
+  .await?;
+  Ok(app.ok().filter(|app| app.allows_redirect(redirect_uri)))
+}
+
+async fn app_from_credentials(
+  pool: &DbPool,
+  client_id: &str,
+  client_secret: &str,
+) -> Result<Option<OAuthApp>, LemmyError> {
+  let client_id = client_id.to_owned();
+  let client_secret = client_secret.to_owned();
+  let app = blocking(pool, move |conn| {
+    OAuthApp::read_from_credentials(conn, &client_id, &client_secret)
+  })
+  .await?;
+  Ok(app.ok())
+}
+
+async fn get_instance(db: DbPoolParam) -> Result<HttpResponse, Error> {
+  match blocking_read(&db, move |conn| SiteView::read(conn)).await? {
+    Ok(site) => Ok(HttpResponse::Ok().json(instance(&site))),
+    Err(_e) => Ok(not_found()),
+  }
+}
+
+async fn verify_credentials(req: HttpRequest, db: DbPoolParam) -> Result<HttpResponse, Error> {
+  let (claims, _auth) = match oauth_bearer(&req, &db).await? {
+    Some(token) => token,
+    None => return Ok(not_logged_in()),
+  };
+
+  let user_id = claims.id;
+  let user = read(&db, move |conn| UserView::read(conn, user_id)).await?;
+  Ok(HttpResponse::Ok().json(own_account(&user, claims.show_nsfw)))
+}
+
+async fn get_account(path: web::Path<i32>, db: DbPoolParam) -> Result<HttpResponse, Error> {
+  let user_id = path.into_inner();
+  match blocking_read(&db, move |conn| UserView::read(conn, user_id)).await? {
+    Ok(user) => Ok(HttpResponse::Ok().json(user_account(&user))),
+    Err(_e) => Ok(not_found()),
+  }
+}
+
+/// The posts and comments of the user, newest first
+async fn get_account_statuses(
+  req: HttpRequest,
+  path: web::Path<i32>,
+  query: web::Query<Page>,
+  db: DbPoolParam,
+) -> Result<HttpResponse, Error> {
+  if query.pinned == Some(true) {
+    return Ok(HttpResponse::Ok().json(Vec::<Status>::new()));
+  }
+
+  let claims = oauth_bearer(&req, &db).await?.map(|(claims, _auth)| claims);
+  let user_id = claims.as_ref().map(|c| c.id);
+  let show_nsfw = claims.as_ref().map_or(false, |c| c.show_nsfw);
+  let creator_id = path.into_inner();
+  let (before, after) = page_bounds(&db, &query).await?;
+  let limit = query.limit();
+
+  let (posts, comments) = read(&db, move |conn| {
+    let posts = PostQueryBuilder::create(conn)
+      .sort(&SortType::New)
+      .show_nsfw(show_nsfw)
+      .for_creator_id(creator_id)
+      .my_user_id(user_id)
+      .published_before(before)
+      .published_after(after)
+      .limit(limit)
+      .list()?;
+    let comments = CommentQueryBuilder::create(conn)
+      .sort(&SortType::New)
+      .for_creator_id(creator_id)
+      .my_user_id(user_id)
+      .published_before(before)
+      .published_after(after)
+      .limit(limit)
+      .list()?;
+    Ok((posts, comments))
+  })
+  .await?;
+
+  let mut statuses: Vec<Status> = posts
+    .iter()
+    .filter(|p| visible_post(p))
+    .map(post_status)
+    .chain(
+      comments
+        .iter()
+        .filter(|c| visible_comment(c))
+        .map(comment_status),
+    )
+    .collect();
+  statuses.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+  statuses.truncate(limit as usize);
+
+  Ok(page_response(&req, &statuses))
+}
+
+/// The posts of the communities the user is subscribed to
+async fn get_home_timeline(
+  req: HttpRequest,
+  query: web::Query<Page>,
+  db: DbPoolParam,
+) -> Result<HttpResponse, Error> {
+  let (claims, _auth) = match oauth_bearer(&req, &db).await? {
+    Some(token) => token,
+    None => return Ok(not_logged_in()),
+  };
+  timeline(&req, &query, &db, ListingType::Subscribed, Some(claims)).await
+}
+
+async fn get_public_timeline(
+  req: HttpRequest,
+  query: web::Query<Page>,
+  db: DbPoolParam,
+) -> Result<HttpResponse, Error> {
+  let claims = oauth_bearer(&req, &db).await?.map(|(claims, _auth)| claims);
+  timeline(&req, &query, &db, ListingType::All, claims).await
+}
+
+async fn timeline(
+  req: &HttpRequest,
+  page: &Page,
+  pool: &DbPool,
+  listing_type: ListingType,
+  claims: Option<Claims>,
+) -> Result<HttpResponse, Error> {
+  let user_id = claims.as_ref().map(|c| c.id);
+  let show_nsfw = claims.as_ref().map_or(false, |c| c.show_nsfw);
+  let hide_bot_accounts = claims.as_ref().map_or(false, |c| c.hide_bot_accounts);
+  let (before, after) = page_bounds(pool, page).await?;
+  let limit = page.limit();
+
+  let posts = read(pool, move |conn| {
+    PostQueryBuilder::create(conn)
+      .listing_type(listing_type)
+      .sort(&SortType::New)
+      .show_nsfw(show_nsfw)
+      .hide_bot_accounts(hide_bot_accounts)
+      .my_user_id(user_id)
+      .published_before(before)
+      .published_after(after)
+      .limit(limit)
+      .list()
+  })
+  .await?;
+
+  let statuses: Vec<Status> = posts.iter().map(post_status).collect();
+  Ok(page_response(req, &statuses))
+}
+
+async fn get_status(
+  req: HttpRequest,
+  path: web::Path<String>,
+  db: DbPoolParam,
+) -> Result<HttpResponse, Error> {
+  let user_id = oauth_bearer(&req, &db)
+    .await?
+    .map(|(claims, _auth)| claims.id);
+  match read_status(&db, &path, user_id).await? {
+    Some(status) => Ok(HttpResponse::Ok().json(status)),
+    None => Ok(not_found()),
+  }
+}
+
+async fn get_status_context(
+  req: HttpRequest,
+  path: web::Path<String>,
+  db: DbPoolParam,
+) -> Result<HttpResponse, Error> {
+  let user_id = oauth_bearer(&req, &db)
+    .await?
+    .map(|(claims, _auth)| claims.id);
+  let (post_id, comment_id) = match StatusId::parse(&path) {
+    Some(StatusId::Post(post_id)) => (post_id, None),
+    Some(StatusId::Comment(comment_id)) => {
+      match blocking_read(&db, move |conn| Comment::read(conn, comment_id)).await? {
+        Ok(comment) => (comment.post_id, Some(comment.id)),
+        Err(_e) => return Ok(not_found()),
+      }
+    }
+    None => return Ok(not_found()),
+  };
+
+  let (post, comments) = read(&db, move |conn| {
+    let post = PostView::read(conn, post_id, user_id)?;
+    let comments = CommentQueryBuilder::create(conn)
+      .for_post_id(post_id)
+      .my_user_id(user_id)
+      .limit(9999)
+      .list()?;
+    Ok((post, comments))
+  })
+  .await?;
+  if !visible_post(&post) {
+    return Ok(not_found());
+  }
+
+  Ok(HttpResponse::Ok().json(context(&post, &comments, comment_id)))
+}
+
+/// Statuses of their own would need a community, so only replies can be made
+async fn create_status(
+  req: HttpRequest,
+  body: web::Bytes,
+  client: web::Data<Client>,
+  db: DbPoolParam,
+  chat_server: ChatServerParam,
+) -> Result<HttpResponse, Error> {
+  let (_claims, auth) = match oauth_bearer(&req, &db).await? {
+    Some(token) => token,
+    None => return Ok(not_logged_in()),
+  };
+
+  let form: StatusForm = parse_body(&req, &body)?;
+  let (post_id, parent_id) = match form.in_reply_to_id.as_deref().and_then(StatusId::parse) {
+    Some(StatusId::Post(post_id)) => (post_id, None),
+    Some(StatusId::Comment(comment_id)) => {
+      let comment = read_comment(&db, comment_id).await?;
+      (comment.post_id, Some(comment.id))
+    }
+    None => return Err(LemmyError::from(APIError::err("status_must_be_reply")).into()),
+  };
+
+  let create_comment = CreateComment {
+    content: form.status,
+    parent_id,
+    edit_id: None,
+    post_id,
+    auth,
+  };
+  let res: CommentResponse = perform(create_comment, &req, &client, &db, &chat_server).await?;
+  Ok(HttpResponse::Ok().json(comment_status(&res.comment)))
+}
+
+async fn favourite_status(
+  req: HttpRequest,
+  path: web::Path<String>,
+  client: web::Data<Client>,
+  db: DbPoolParam,
+  chat_server: ChatServerParam,
+) -> Result<HttpResponse, Error> {
+  vote_status(req, &path, 1, client, db, chat_server).await
+}
+
+async fn unfavourite_status(
+  req: HttpRequest,
+  path: web::Path<String>,
+  client: web::Data<Client>,
+  db: DbPoolParam,
+  chat_server: ChatServerParam,
+) -> Result<HttpResponse, Error> {
+  vote_status(req, &path, 0, client, db, chat_server).await
+}
+
+async fn bookmark_status(
+  req: HttpRequest,
+  path: web::Path<String>,
+  client: web::Data<Client>,
+  db: DbPoolParam,
+  chat_server: ChatServerParam,
+) -> Result<HttpResponse, Error> {
+  save_status(req, &path, true, client, db, chat_server).await
+}
+
+async fn unbookmark_status(
+  req: HttpRequest,
+  path: web::Path<String>,
+  client: web::Data<Client>,
+  db: DbPoolParam,
+  chat_server: ChatServerParam,
+) -> Result<HttpResponse, Error> {
+  save_status(req, &path, false, client, db, chat_server).await
+}
+
+/// A favourite is an upvote, and taking it back removes the vote
+async fn vote_status(
+  req: HttpRequest,
+  id: &str,
+  score: i16,
+  client: web::Data<Client>,
+  db: DbPoolParam,
+  chat_server: ChatServerParam,
+) -> Result<HttpResponse, Error> {
+  let (_claims, auth) = match oauth_bearer(&req, &db).await? {
+    Some(token) => token,
+    None => return Ok(not_logged_in()),
+  };
+
+  let status = match StatusId::parse(id) {
+    Some(StatusId::Post(post_id)) => {
+      let like = CreatePostLike {
+        post_id,
+        score,
+        auth,
+      };
+      let res: PostResponse = perform(like, &req, &client, &db, &chat_server).await?;
+      post_status(&res.post)
+    }
+    Some(StatusId::Comment(comment_id)) => {
+      let comment = read_comment(&db, comment_id).await?;
+      let like = CreateCommentLike {
+        comment_id,
+        post_id: comment.post_id,
+        score,
+        auth,
+      };
+      let res: CommentResponse = perform(like, &req, &client, &db, &chat_server).await?;
+      comment_status(&res.comment)
+    }
+    None => return Ok(not_found()),
+  };
+  Ok(HttpResponse::Ok().json(status))
+}
+
+/// A bookmark saves the post or comment
+async fn save_status(
+  req: HttpRequest,
+  id: &str,
+  save: bool,
+  client: web::Data<Client>,
+  db: DbPoolParam,
+  chat_server: ChatServerParam,
+) -> Result<HttpResponse, Error> {
+  let (_claims, auth) = match oauth_bearer(&req, &db).await? {
+    Some(token) => token,
+    None => return Ok(not_logged_in()),
+  };
+
+  let status = match StatusId::parse(id) {
+    Some(StatusId::Post(post_id)) => {
+      let save_post = SavePost {
+        post_id,
+        save,
+        auth,
+      };
+      let res: PostResponse = perform(save_post, &req, &client, &db, &chat_server).await?;
+      post_status(&res.post)
+    }
+    Some(StatusId::Comment(comment_id)) => {
+      let save_comment = SaveComment {
+        comment_id,
+        save,
+        auth,
+      };
+      let res: CommentResponse = perform(save_comment, &req, &client, &db, &chat_server).await?;
+      comment_status(&res.comment)
+    }
+    None => return Ok(not_found()),
+  };
+  Ok(HttpResponse::Ok().json(status))
+}
+
+/// The replies to the user and the comments mentioning them, newest first
+async fn get_notifications(
+  req: HttpRequest,
+  query: web::Query<Page>,
+  db: DbPoolParam,
+) -> Result<HttpResponse, Error> {
+  let (claims, _auth) = match oauth_bearer(&req, &db).await? {
+    Some(token) => token,
+    None => return Ok(not_logged_in()),
+  };
+
+  let user_id = claims.id;
+  let comments = read(&db, move |conn| {
+    let replies = ReplyQueryBuilder::create(conn, user_id)
+      .sort(&SortType::New)
+      .limit(NOTIFICATION_HISTORY)
+      .list()?;
+    let mentions = UserMentionQueryBuilder::create(conn, user_id)
+      .sort(&SortType::New)
+      .limit(NOTIFICATION_HISTORY)
+      .list()?;
+
+    // A reply can mention the user too
+    let mut comment_ids: Vec<i32> = replies
+      .iter()
+      .map(|r| r.id)
+      .chain(mentions.iter().map(|m| m.id))
+      .collect();
+    comment_ids.sort_unstable();
+    comment_ids.dedup();
+    comment_ids
+      .into_iter()
+      .map(|comment_id| CommentView::read(conn, comment_id, Some(user_id)))
+      .collect::<Result<Vec<CommentView>, _>>()
+  })
+  .await?;
+
+  let (before, after) = page_bounds(&db, &query).await?;
+  let mut notifications: Vec<Notification> = comments
+    .iter()
+    .filter(|c| visible_comment(c))
+    .filter(|c| before.map_or(true, |before| c.published < before))
+    .filter(|c| after.map_or(true, |after| c.published > after))
+    .map(comment_notification)
+    .collect();
+  notifications.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+  notifications.truncate(query.limit() as usize);
+
+  Ok(HttpResponse::Ok().json(notifications))
+}
+
+async fn read<F, T>(pool: &DbPool, f: F) -> Result<T, LemmyError>
+where
+  F: FnOnce(&PgConnection) -> Result<T, diesel::result::Error> + Send + 'static,
+  T: Send + 'static,
+{
+  Ok(blocking_read(pool, f).await??)
+}
+
+async fn write<F, T>(pool: &DbPool, f: F) -> Result<T, LemmyError>
+where
+  F: FnOnce(&PgConnection) -> Result<T, diesel::result::Error> + Send + 'static,
+  T: Send + 'static,
+{
+  Ok(blocking(pool, f).await??)
+}
+
+async fn read_comment(pool: &DbPool, comment_id: i32) -> Result<Comment, LemmyError> {
+  match blocking_read(pool, move |conn| Comment::read(conn, comment_id)).await? {
+    Ok(comment) => Ok(comment),
+    Err(_e) => Err(APIError::err("couldnt_find_comment").into()),
+  }
+}
+
+/// None when there's no such status, or it was taken down
+async fn read_status(
+  pool: &DbPool,
+  id: &str,
+  user_id: Option<i32>,
+) -> Result<Option<Status>, LemmyError> {
+  let status = match StatusId::parse(id) {
+    Some(StatusId::Post(post_id)) => {
+      blocking_read(pool, move |conn| PostView::read(conn, post_id, user_id))
+        .await?
+        .ok()
+        .filter(visible_post)
+        .map(|post| post_status(&post))
+    }
+    Some(StatusId::Comment(comment_id)) => blocking_read(pool, move |conn| {
+      CommentView::read(conn, comment_id, user_id)
+    })
+    .await?
+    .ok()
+    .filter(visible_comment)
+    .map(|comment| comment_status(&comment)),
+    None => None,
+  };
+  Ok(status)
+}
+
+/// When the statuses of the page have to be published before and after, going by the ones the app
+/// paged from
+async fn page_bounds(
+  pool: &DbPool,
+  page: &Page,
+) -> Result<(Option<NaiveDateTime>, Option<NaiveDateTime>), LemmyError> {
+  let before = match &page.max_id {
+    Some(id) => status_published(pool, id).await?,
+    None => None,
+  };
+  let after = match page.min_id.as_ref().or_else(|| page.since_id.as_ref()) {
+    Some(id) => status_published(pool, id).await?,
+    None => None,
+  };
+  Ok((before, after))
+}
+
+async fn status_published(pool: &DbPool, id: &str) -> Result<Option<NaiveDateTime>, LemmyError> {
+  let published = match StatusId::parse(id) {
+    Some(StatusId::Post(post_id)) => blocking_read(pool, move |conn| Post::read(conn, post_id))
+      .await?
+      .ok()
+      .map(|post| post.published),
+    Some(StatusId::Comment(comment_id)) => {
+      blocking_read(pool, move |conn| Comment::read(conn, comment_id))
+        .await?
+        .ok()
+        .map(|comment| comment.published)
+    }
+    None => None,
+  };
+  Ok(published)
+}
+
+fn visible_post(post: &PostView) -> bool {
+  !post.removed
+    && !post.deleted
+    && !post.pending
+    && !post.community_removed
+    && !post.community_deleted
+}
+
+fn visible_comment(comment: &CommentView) -> bool {
+  !comment.removed && !comment.deleted
+}
+
+/// The apps send forms or JSON. The fields they send as forms are all strings.
+fn parse_body<T: DeserializeOwned>(req: &HttpRequest, body: &[u8]) -> Result<T, LemmyError> {
+  let is_json = req
+    .headers()
+    .get(CONTENT_TYPE)
+    .and_then(|t| t.to_str().ok())
+    .map_or(false, |t| t.starts_with("application/json"));
+  let parsed = if is_json {
+    serde_json::from_slice(body)
+  } else {
+    let fields = form_urlencoded::parse(body)
+      .map(|(k, v)| (k.into_owned(), serde_json::Value::String(v.into_owned())))
+      .collect();
+    serde_json::from_value(serde_json::Value::Object(fields))
+  };
+  parsed.map_err(|e| APIError::err(&e.to_string()).into())
+}
+
+/// The statuses with the links to the pages after and before them
+fn page_response(req: &HttpRequest, statuses: &[Status]) -> HttpResponse {
+  let mut res = HttpResponse::Ok();
+  if let (Some(first), Some(last)) = (statuses.first(), statuses.last()) {
+    let url = format!(
+      "{}://{}{}",
+      get_apub_protocol_string(),
+      Settings::get().hostname,
+      req.path()
+    );
+    res.header(
+      LINK,
+      format!(
+        "<{0}?max_id={1}>; rel=\"next\", <{0}?min_id={2}>; rel=\"prev\"",
+        url, last.id, first.id
+      ),
+    );
+  }
+  res.json(statuses)
+}
+
+fn authorize_page(
+  client_id: &str,
+  redirect_uri: &str,
+  state: &str,
+  error: Option<&str>,
+) -> HttpResponse {
+  let error = error
+    .map(|e| format!("<p><strong>{}</strong></p>", escape_html(e)))
+    .unwrap_or_default();
+  oauth_page(&format!(
+    "{}<form method=\"post\" action=\"/oauth/authorize\">\
+    <input type=\"hidden\" name=\"client_id\" value=\"{}\">\
+    <input type=\"hidden\" name=\"redirect_uri\" value=\"{}\">\
+    <input type=\"hidden\" name=\"state\" value=\"{}\">\
+    <p><input name=\"username_or_email\" placeholder=\"Username or email\" required></p>\
+    <p><input name=\"password\" type=\"password\" placeholder=\"Password\" required></p>\
+    <p><button type=\"submit\">Log in</button></p>\
+    </form>",
+    error,
+    escape_html(client_id),
+    escape_html(redirect_uri),
+    escape_html(state)
+  ))
+}
+
+/// The app isn't registered, or didn't register the redirect uri, so the user isn't sent there
+fn invalid_redirect_page() -> HttpResponse {
+  let mut res = oauth_page("<p>The app can't log in here: its redirect uri isn't registered.</p>");
+  *res.status_mut() = http::StatusCode::BAD_REQUEST;
+  res
+}
+
+fn oauth_page(body: &str) -> HttpResponse {
+  HttpResponse::Ok().content_type("text/html").body(format!(
+    "<!DOCTYPE html><html><head><meta charset=\"utf-8\">\
+    <meta name=\"viewport\" content=\"width=device-width\"><title>Log in to {0}</title></head>\
+    <body><h1>Log in to {0}</h1>{1}</body></html>",
+    escape_html(&Settings::get().hostname),
+    body
+  ))
+}
+
+fn oauth_error(error: &str) -> HttpResponse {
+  HttpResponse::BadRequest().json(ErrorResponse {
+    error: error.to_string(),
+    message: None,
+  })
+}
+
+fn not_logged_in() -> HttpResponse {
+  HttpResponse::Unauthorized().json(ErrorResponse {
+    error: "not_logged_in".to_string(),
+    message: None,
+  })
+}
+
+fn not_found() -> HttpResponse {
+  HttpResponse::NotFound().json(ErrorResponse {
+    error: "not_found".to_string(),
+    message: None,
+  })
+}
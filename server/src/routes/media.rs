@@ -0,0 +1,293 @@
+use crate::{
+  api::claims::Claims,
+  blocking,
+  media::{is_attachment_type, is_media_file, media_url, pictrs_file_url},
+  nsfw::classify_image,
+  routes::DbPoolParam,
+  upload::{detect_attachment_type, detect_content_type, process_image},
+  PictrsResponse,
+};
+use actix_multipart::Multipart;
+use actix_web::{
+  body::BodyStream,
+  client::Client,
+  error::{ErrorBadGateway, ErrorForbidden, ErrorInternalServerError, ErrorUnauthorized},
+  http::header::CONTENT_TYPE,
+  *,
+};
+use futures::{StreamExt, TryStreamExt};
+use lemmy_db::{media::*, naive_now, user::User_, Crud};
+use lemmy_utils::settings::Settings;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::error;
+
+/// Uploads take longer to pass on than the other requests to pictrs
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize)]
+pub struct UploadImageQuery {
+  auth: String,
+}
+
+#[derive(Serialize)]
+pub struct UploadImageResponse {
+  msg: String,
+  files: Vec<UploadedImage>,
+}
+
+/// The delete token stays with lemmy. The uploader deletes the image with `DeleteImage`.
+#[derive(Serialize)]
+pub struct UploadedImage {
+  file: String,
+  url: String,
+}
+
+/// Why an upload was turned down, with the limit it went over
+#[derive(Serialize)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum UploadError {
+  ImageTooLarge {
+    max_size: usize,
+  },
+  ImageTypeNotAllowed {
+    content_type: String,
+    allowed_types: Vec<String>,
+  },
+  UploadQuotaReached {
+    max_per_day: i64,
+  },
+  InvalidImage,
+}
+
+impl UploadError {
+  fn response(&self) -> HttpResponse {
+    let mut res = match self {
+      UploadError::ImageTooLarge { .. } => HttpResponse::PayloadTooLarge(),
+      UploadError::ImageTypeNotAllowed { .. } => HttpResponse::UnsupportedMediaType(),
+      UploadError::UploadQuotaReached { .. } => HttpResponse::TooManyRequests(),
+      UploadError::InvalidImage => HttpResponse::BadRequest(),
+    };
+    res.json(self)
+  }
+}
+
+#[derive(Deserialize)]
+pub struct MediaQuery {
+  /// The longest side in pixels, for a smaller version
+  thumbnail: Option<u32>,
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+  cfg.route("/pictrs/image/{file}", web::get().to(get_media));
+}
+
+/// Takes the same multipart form as pictrs, with the images in `images[]`. Their metadata is
+/// stripped, or they're encoded again, before they're passed on. Short audio and video files for
+/// attachment posts go in there too, and are passed on as they are. Nothing is uploaded when one
+/// of them goes over the limits.
+pub async fn upload_image(
+  mut form: Multipart,
+  info: web::Query<UploadImageQuery>,
+  db: DbPoolParam,
+  client: web::Data<Client>,
+) -> Result<HttpResponse, Error> {
+  let user_id = Claims::decode(&info.auth)
+    .map_err(|_| ErrorUnauthorized("not_logged_in"))?
+    .claims
+    .id;
+  let user = blocking(&db, move |conn| User_::read(conn, user_id))
+    .await?
+    .map_err(|_| ErrorUnauthorized("not_logged_in"))?;
+  if user.is_banned() {
+    return Err(ErrorForbidden("site_ban"));
+  }
+
+  let config = Settings::get().pictrs.uploads;
+  let mut uploads_left = match config.max_per_day {
+    Some(max_per_day) => {
+      let since = naive_now() - chrono::Duration::days(1);
+      let uploaded = blocking(&db, move |conn| {
+        Media::count_for_user_since(conn, user_id, since)
+      })
+      .await?
+      .map_err(ErrorInternalServerError)?;
+      Some(max_per_day - uploaded)
+    }
+    None => None,
+  };
+
+  let max_size = config.max_size.max(config.max_attachment_size);
+  let boundary = uuid::Uuid::new_v4().to_simple().to_string();
+  let mut body = Vec::new();
+  let mut uploads = Vec::new();
+  while let Some(mut field) = form.try_next().await? {
+    let is_image = field
+      .content_disposition()
+      .map_or(false, |cd| cd.get_name() == Some("images[]"));
+    if !is_image {
+      continue;
+    }
+
+    if let Some(left) = uploads_left.as_mut() {
+      if *left <= 0 {
+        return Ok(
+          UploadError::UploadQuotaReached {
+            max_per_day: config.max_per_day.unwrap_or_default(),
+          }
+          .response(),
+        );
+      }
+      *left -= 1;
+    }
+
+    let mut data = Vec::new();
+    while let Some(chunk) = field.next().await {
+      let chunk = chunk?;
+      if data.len() + chunk.len() > max_size {
+        return Ok(UploadError::ImageTooLarge { max_size }.response());
+      }
+      data.extend_from_slice(&chunk);
+    }
+
+    let content_type = match detect_content_type(&data).or_else(|| detect_attachment_type(&data)) {
+      Some(content_type) => content_type,
+      None => return Ok(UploadError::InvalidImage.response()),
+    };
+    if !config.allowed_types.iter().any(|t| t == content_type) {
+      return Ok(
+        UploadError::ImageTypeNotAllowed {
+          content_type: content_type.to_owned(),
+          allowed_types: config.allowed_types,
+        }
+        .response(),
+      );
+    }
+
+    if is_attachment_type(content_type) {
+      if data.len() > config.max_attachment_size {
+        return Ok(
+          UploadError::ImageTooLarge {
+            max_size: config.max_attachment_size,
+          }
+          .response(),
+        );
+      }
+      append_form_file(&mut body, &boundary, content_type, &data);
+      uploads.push((content_type, None));
+      continue;
+    }
+
+    if data.len() > config.max_size {
+      return Ok(
+        UploadError::ImageTooLarge {
+          max_size: config.max_size,
+        }
+        .response(),
+      );
+    }
+    let process_config = config.to_owned();
+    let image = match web::block(move || process_image(data, &process_config)).await {
+      Ok(image) => image,
+      Err(e) => {
+        error!("Couldn't process uploaded image: {}", e);
+        return Ok(UploadError::InvalidImage.response());
+      }
+    };
+    // A classifier that's down doesn't hold the uploads up
+    let nsfw_score = match classify_image(&client, image.data.clone(), image.content_type()).await {
+      Ok(nsfw_score) => nsfw_score,
+      Err(e) => {
+        error!("Couldn't classify uploaded image: {}", e);
+        None
+      }
+    };
+    append_form_file(&mut body, &boundary, image.content_type(), &image.data);
+    uploads.push((image.content_type(), nsfw_score));
+  }
+  body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+  let mut res = client
+    .post(&format!("{}/image", Settings::get().pictrs.url))
+    .timeout(UPLOAD_TIMEOUT)
+    .header(
+      CONTENT_TYPE,
+      format!("multipart/form-data; boundary={}", boundary),
+    )
+    .send_body(body)
+    .await
+    .map_err(ErrorBadGateway)?;
+  let pictrs: PictrsResponse = res.json().await.map_err(ErrorBadGateway)?;
+
+  let mut files = Vec::new();
+  // pictrs answers with the files in the order they were sent
+  for (pictrs_file, (content_type, nsfw_score)) in pictrs.files.into_iter().zip(uploads) {
+    let form = MediaForm {
+      user_id,
+      file: pictrs_file.file.to_owned(),
+      delete_token: pictrs_file.delete_token,
+      content_type: Some(content_type.to_owned()),
+      nsfw_score,
+    };
+    blocking(&db, move |conn| Media::create(conn, &form))
+      .await?
+      .map_err(ErrorInternalServerError)?;
+
+    files.push(UploadedImage {
+      url: media_url(&pictrs_file.file),
+      file: pictrs_file.file,
+    });
+  }
+
+  Ok(HttpResponse::build(res.status()).json(UploadImageResponse {
+    msg: pictrs.msg,
+    files,
+  }))
+}
+
+/// pictrs names the files after what's in them, so they go without a filename of their own
+fn append_form_file(body: &mut Vec<u8>, boundary: &str, content_type: &str, data: &[u8]) {
+  body.extend_from_slice(
+    format!(
+      "--{}\r\nContent-Disposition: form-data; name=\"images[]\"; filename=\"image\"\r\nContent-Type: {}\r\n\r\n",
+      boundary, content_type
+    )
+    .as_bytes(),
+  );
+  body.extend_from_slice(data);
+  body.extend_from_slice(b"\r\n");
+}
+
+async fn get_media(
+  file: web::Path<String>,
+  info: web::Query<MediaQuery>,
+  client: web::Data<Client>,
+) -> Result<HttpResponse, Error> {
+  if !is_media_file(&file) {
+    return Ok(HttpResponse::NotFound().finish());
+  }
+
+  let thumbnail = info
+    .thumbnail
+    .map(|size| size.min(Settings::get().image_proxy.max_thumbnail_size));
+  stream_pictrs_file(&client, &file, thumbnail).await
+}
+
+/// Serves a file from pictrs as it comes in, at its full size or as a thumbnail.
+pub async fn stream_pictrs_file(
+  client: &Client,
+  file: &str,
+  thumbnail: Option<u32>,
+) -> Result<HttpResponse, Error> {
+  let res = client
+    .get(&pictrs_file_url(file, thumbnail))
+    .send()
+    .await
+    .map_err(ErrorBadGateway)?;
+
+  let mut client_res = HttpResponse::build(res.status());
+  if let Some(content_type) = res.headers().get(CONTENT_TYPE) {
+    client_res.header(CONTENT_TYPE, content_type.to_owned());
+  }
+  Ok(client_res.body(BodyStream::new(res)))
+}
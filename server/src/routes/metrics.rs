@@ -0,0 +1,42 @@
+use crate::{metrics::render_metrics, routes::DbPoolParam};
+use actix_web::{error::ErrorInternalServerError, http::header::AUTHORIZATION, *};
+use lemmy_utils::settings::Settings;
+
+/// On the main address, the metrics are only there for those with the token.
+pub fn config(cfg: &mut web::ServiceConfig) {
+  let metrics = Settings::get().metrics;
+  if metrics.enabled && metrics.bind.is_none() && metrics.token.is_some() {
+    cfg.route("/metrics", web::get().to(get_metrics_with_token));
+  }
+}
+
+/// For the metrics address, which is kept private.
+pub fn config_private(cfg: &mut web::ServiceConfig) {
+  cfg.route("/metrics", web::get().to(get_metrics));
+}
+
+async fn get_metrics_with_token(req: HttpRequest, db: DbPoolParam) -> Result<HttpResponse, Error> {
+  let token = Settings::get().metrics.token.unwrap_or_default();
+  let authorized = req
+    .headers()
+    .get(AUTHORIZATION)
+    .and_then(|header| header.to_str().ok())
+    .map(|header| !token.is_empty() && header == format!("Bearer {}", token))
+    .unwrap_or(false);
+  if !authorized {
+    return Ok(HttpResponse::Unauthorized().finish());
+  }
+
+  get_metrics(db).await
+}
+
+async fn get_metrics(db: DbPoolParam) -> Result<HttpResponse, Error> {
+  let metrics = render_metrics(&db)
+    .await
+    .map_err(ErrorInternalServerError)?;
+  Ok(
+    HttpResponse::Ok()
+      .content_type("text/plain; version=0.0.4")
+      .body(metrics),
+  )
+}
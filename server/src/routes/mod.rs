@@ -1,20 +1,68 @@
 pub mod api;
+pub mod events;
 pub mod federation;
 pub mod feeds;
+pub mod health;
+pub mod image_proxy;
 pub mod index;
+pub mod mastodon;
+pub mod media;
+pub mod metrics;
 pub mod nodeinfo;
+pub mod oembed;
+pub mod openapi;
+pub mod sitemap;
 pub mod webfinger;
 pub mod websocket;
 
-use crate::{rate_limit::rate_limiter::RateLimiter, websocket::server::ChatServer};
-use actix::prelude::*;
-use actix_web::*;
-use diesel::{
-  r2d2::{ConnectionManager, Pool},
-  PgConnection,
+use crate::{
+  api::{claims::Claims, Oper, Perform},
+  blocking,
+  get_ip,
+  rate_limit::rate_limiter::RateLimiter,
+  websocket::{server::ChatServer, WebsocketInfo},
+  DbPool,
+  LemmyError,
 };
+use actix::prelude::*;
+use actix_web::{client::Client, *};
 use std::sync::{Arc, Mutex};
 
-pub type DbPoolParam = web::Data<Pool<ConnectionManager<PgConnection>>>;
+pub type DbPoolParam = web::Data<DbPool>;
 pub type RateLimitParam = web::Data<Arc<Mutex<RateLimiter>>>;
 pub type ChatServerParam = web::Data<Addr<ChatServer>>;
+
+/// Performs an operation for the routes that speak another API, like the HTTP ones do
+pub async fn perform<Request>(
+  data: Request,
+  req: &HttpRequest,
+  client: &Client,
+  pool: &DbPool,
+  chat_server: &ChatServerParam,
+) -> Result<<Oper<Request> as Perform>::Response, LemmyError>
+where
+  Oper<Request>: Perform,
+{
+  let ws_info = WebsocketInfo {
+    chatserver: chat_server.get_ref().to_owned(),
+    id: None,
+  };
+
+  let ip = get_ip(&req.connection_info());
+  let oper: Oper<Request> = Oper::new(data, client.clone()).with_ip(ip);
+  oper.perform(pool, Some(ws_info)).await
+}
+
+/// The claims of the user the request's OAuth access token was given to, with a JWT for the
+/// operations. It's read from the primary, so a token stops working as soon as it's revoked.
+pub async fn oauth_bearer(
+  req: &HttpRequest,
+  pool: &DbPool,
+) -> Result<Option<(Claims, String)>, LemmyError> {
+  let token = match Claims::bearer_token(req) {
+    Some(token) => token,
+    None => return Ok(None),
+  };
+  let bearer = blocking(pool, move |conn| Claims::from_oauth_token(conn, &token)).await?;
+  Ok(bearer.ok())
+}
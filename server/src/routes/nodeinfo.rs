@@ -1,6 +1,6 @@
 use crate::{blocking, routes::DbPoolParam, version, LemmyError};
 use actix_web::{body::Body, error::ErrorBadRequest, *};
-use lemmy_db::site_view::SiteView;
+use lemmy_db::{site_document::SiteDocument, site_view::SiteView};
 use lemmy_utils::{get_apub_protocol_string, settings::Settings};
 use serde::{Deserialize, Serialize};
 use url::Url;
@@ -30,6 +30,10 @@ async fn node_info(db: DbPoolParam) -> Result<HttpResponse, Error> {
     .await?
     .map_err(|_| ErrorBadRequest(LemmyError::from(format_err!("not_found"))))?;
 
+  let documents = blocking(&db, SiteDocument::list_summaries)
+    .await?
+    .map_err(ErrorBadRequest)?;
+
   let protocols = if Settings::get().federation.enabled {
     vec!["activitypub".to_string()]
   } else {
@@ -51,6 +55,21 @@ async fn node_info(db: DbPoolParam) -> Result<HttpResponse, Error> {
       local_comments: site_view.number_of_comments,
       open_registrations: site_view.open_registration,
     },
+    metadata: NodeInfoMetadata {
+      documents: documents
+        .into_iter()
+        .map(|document| NodeInfoDocument {
+          url: format!(
+            "{}://{}/api/v1/site/document?slug={}",
+            get_apub_protocol_string(),
+            Settings::get().hostname,
+            document.slug
+          ),
+          slug: document.slug,
+          title: document.title,
+        })
+        .collect(),
+    },
   };
 
   Ok(HttpResponse::Ok().json(json))
@@ -73,6 +92,9 @@ pub struct NodeInfo {
   pub software: NodeInfoSoftware,
   pub protocols: Vec<String>,
   pub usage: NodeInfoUsage,
+  // Other software fills this in with what it likes
+  #[serde(default)]
+  pub metadata: NodeInfoMetadata,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -94,3 +116,17 @@ pub struct NodeInfoUsage {
 pub struct NodeInfoUsers {
   pub total: i64,
 }
+
+/// The terms of service, privacy policy and such of the instance, for the directories of instances
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct NodeInfoMetadata {
+  #[serde(default)]
+  pub documents: Vec<NodeInfoDocument>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NodeInfoDocument {
+  pub slug: String,
+  pub title: String,
+  pub url: String,
+}
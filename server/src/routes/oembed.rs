@@ -0,0 +1,172 @@
+//! The oEmbed provider for the local posts, so that other sites can show a card for a link to one.
+//! The post pages point to it with a `<link rel="alternate">`, and it answers with the title, the
+//! author and a snippet of HTML made of nothing but escaped text and links.
+
+use crate::{blocking_read, digest::escape_html, routes::DbPoolParam, ErrorResponse};
+use actix_web::*;
+use lemmy_db::{post_view::PostView, site_view::SiteView};
+use lemmy_utils::{get_apub_protocol_string, settings::Settings};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// The widest the snippet is, unless the consumer asks for less
+const OEMBED_WIDTH: i32 = 550;
+
+/// How much of the body the snippet shows
+const OEMBED_BODY_LENGTH: usize = 300;
+
+#[derive(Deserialize)]
+pub struct OEmbedQuery {
+  url: String,
+  maxwidth: Option<i32>,
+  format: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct OEmbedResponse {
+  version: String,
+  #[serde(rename = "type")]
+  type_: String,
+  title: String,
+  author_name: String,
+  author_url: String,
+  provider_name: String,
+  provider_url: String,
+  html: String,
+  width: i32,
+  height: Option<i32>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  thumbnail_url: Option<String>,
+}
+
+pub async fn get_oembed(
+  query: web::Query<OEmbedQuery>,
+  db: DbPoolParam,
+) -> Result<HttpResponse, Error> {
+  // The spec only has xml besides json
+  if query.format.as_deref().map_or(false, |f| f != "json") {
+    return Ok(HttpResponse::NotImplemented().json(ErrorResponse {
+      error: "unsupported_oembed_format".to_string(),
+      message: None,
+    }));
+  }
+
+  let not_found = HttpResponse::NotFound().json(ErrorResponse {
+    error: "couldnt_find_post".to_string(),
+    message: None,
+  });
+  let post_id = match oembed_post_id(&query.url) {
+    Some(post_id) => post_id,
+    None => return Ok(not_found),
+  };
+  let post = match blocking_read(&db, move |conn| PostView::read(conn, post_id, None)).await? {
+    Ok(post) => post,
+    Err(_e) => return Ok(not_found),
+  };
+  if post.deleted
+    || post.removed
+    || post.pending
+    || post.community_deleted
+    || post.community_removed
+  {
+    return Ok(not_found);
+  }
+  let site_name = blocking_read(&db, move |conn| SiteView::read(conn))
+    .await?
+    .map(|site| site.name)
+    .unwrap_or_else(|_| Settings::get().hostname);
+
+  let width = query
+    .maxwidth
+    .filter(|w| *w > 0)
+    .map_or(OEMBED_WIDTH, |w| w.min(OEMBED_WIDTH));
+  Ok(HttpResponse::Ok().json(OEmbedResponse {
+    version: "1.0".to_string(),
+    type_: "rich".to_string(),
+    title: post.name.to_owned(),
+    author_name: post.creator_name.to_owned(),
+    author_url: post.creator_actor_id.to_owned(),
+    provider_name: site_name,
+    provider_url: site_url(),
+    html: post_embed_html(&post),
+    width,
+    height: None,
+    thumbnail_url: post.thumbnail_url.to_owned(),
+  }))
+}
+
+/// The id of the post a link is to, when it's a post page of this instance.
+pub fn oembed_post_id(url: &str) -> Option<i32> {
+  let url = Url::parse(url).ok()?;
+  let host = match url.port() {
+    Some(port) => format!("{}:{}", url.host_str()?, port),
+    None => url.host_str()?.to_owned(),
+  };
+  if host != Settings::get().hostname {
+    return None;
+  }
+
+  let mut segments = url.path_segments()?;
+  match (segments.next(), segments.next(), segments.next()) {
+    (Some("post"), Some(id), None) => id.parse().ok(),
+    _ => None,
+  }
+}
+
+/// A card with the title, the start of the body and who posted it where. A blockquote, so it works
+/// without any script or style of ours.
+pub fn post_embed_html(post: &PostView) -> String {
+  let base = site_url();
+  let post_url = format!("{}/post/{}", base, post.id);
+  let community_url = format!("{}/c/{}", base, post.community_name);
+
+  let mut excerpt: String = post
+    .body
+    .as_deref()
+    .unwrap_or_default()
+    .chars()
+    .take(OEMBED_BODY_LENGTH)
+    .collect();
+  if post.body.as_deref().unwrap_or_default().chars().count() > OEMBED_BODY_LENGTH {
+    excerpt.push('…');
+  }
+  let excerpt = if excerpt.trim().is_empty() {
+    String::new()
+  } else {
+    format!("<p>{}</p>", escape_html(excerpt.trim()))
+  };
+
+  format!(
+    "<blockquote class=\"lemmy-post\"><p><a href=\"{}\">{}</a></p>{}<p>— <a href=\"{}\">{}</a> in <a href=\"{}\">{}</a></p></blockquote>",
+    escape_html(&post_url),
+    escape_html(&post.name),
+    excerpt,
+    escape_html(&post.creator_actor_id),
+    escape_html(&post.creator_name),
+    escape_html(&community_url),
+    escape_html(&post.community_name),
+  )
+}
+
+/// The `<link>` a post page has for consumers to find the provider.
+pub fn oembed_discovery_link(post_id: i32) -> String {
+  let post_url = format!("{}/post/{}", site_url(), post_id);
+  let href = format!(
+    "{}/api/v1/oembed?url={}",
+    site_url(),
+    utf8_percent_encode(&post_url, NON_ALPHANUMERIC)
+  );
+  format!(
+    "<link rel=\"alternate\" type=\"application/json+oembed\" href=\"{}\">",
+    escape_html(&href)
+  )
+}
+
+fn site_url() -> String {
+  format!(
+    "{}://{}",
+    get_apub_protocol_string(),
+    Settings::get().hostname
+  )
+}
@@ -0,0 +1,308 @@
+//! The OpenAPI 3 document of the HTTP API, served at `/api/v1/openapi.json`. The schemas are
+//! derived from the request and response types themselves, so a field added to one of them shows
+//! up here with the next build. Each operation is also a websocket op of the same name, with the
+//! same data and response.
+
+use crate::{
+  api::{
+    appeal::*,
+    automod::*,
+    blocked_domain::*,
+    bulk_moderation::*,
+    comment::*,
+    community::*,
+    community_import::*,
+    community_takeover::*,
+    community_wiki::*,
+    custom_feed::*,
+    email_domain::*,
+    federation::*,
+    ip_ban::*,
+    job::*,
+    media::*,
+    mod_note::*,
+    muted_keyword::*,
+    post::*,
+    post_approval::*,
+    posting_requirement::*,
+    purge::*,
+    rate_limit::*,
+    report::*,
+    scheduled_post::*,
+    site::*,
+    site_document::*,
+    user::*,
+    webhook::*,
+    word_filter::*,
+    Oper,
+    Perform,
+  },
+  version::VERSION,
+  ErrorResponse,
+};
+use actix_web::*;
+use lemmy_utils::{get_apub_protocol_string, settings::Settings};
+use schemars::{
+  gen::{SchemaGenerator, SchemaSettings},
+  JsonSchema,
+};
+use serde_json::{json, Map, Value};
+
+lazy_static! {
+  static ref OPENAPI_DOCUMENT: Value = openapi_document();
+}
+
+/// The operations of `routes::api`, as `method path data`. The response is the one the data
+/// performs to.
+macro_rules! operations {
+  ($gen:ident, $($method:ident $path:literal $data:ident,)*) => {
+    vec![$(operation::<$data>(&mut $gen, stringify!($method), $path, stringify!($data)),)*]
+  };
+}
+
+pub async fn get_openapi() -> HttpResponse {
+  HttpResponse::Ok().json(&*OPENAPI_DOCUMENT)
+}
+
+pub fn openapi_document() -> Value {
+  let mut gen = SchemaSettings::openapi3().into_generator();
+  let operations = operations!(gen,
+    get "/api/v1/site" GetSite,
+    post "/api/v1/site" CreateSite,
+    put "/api/v1/site" EditSite,
+    post "/api/v1/site/transfer" TransferSite,
+    post "/api/v1/site/announcement" CreateSiteAnnouncement,
+    post "/api/v1/site/announcement/delete" DeleteSiteAnnouncement,
+    post "/api/v1/site/announcement/dismiss" DismissSiteAnnouncement,
+    get "/api/v1/site/document" GetSiteDocument,
+    put "/api/v1/site/document" SaveSiteDocument,
+    post "/api/v1/site/document/delete" DeleteSiteDocument,
+    get "/api/v1/site/config" GetSiteConfig,
+    put "/api/v1/site/config" SaveSiteConfig,
+    get "/api/v1/site/community_defaults" GetCommunityDefaults,
+    put "/api/v1/site/community_defaults" EditCommunityDefaults,
+    get "/api/v1/site/job_queue" GetJobQueue,
+    post "/api/v1/site/import" ImportCommunity,
+    get "/api/v1/site/import" GetCommunityImport,
+    get "/api/v1/site/blocked_domain" ListBlockedDomains,
+    post "/api/v1/site/blocked_domain" CreateBlockedDomain,
+    post "/api/v1/site/blocked_domain/delete" DeleteBlockedDomain,
+    get "/api/v1/site/webhook" ListWebhooks,
+    post "/api/v1/site/webhook" CreateWebhook,
+    put "/api/v1/site/webhook" EditWebhook,
+    post "/api/v1/site/webhook/delete" DeleteWebhook,
+    get "/api/v1/site/rate_limit" GetRateLimits,
+    put "/api/v1/site/rate_limit" EditRateLimits,
+    put "/api/v1/site/rate_limit/endpoint" SetEndpointRateLimit,
+    post "/api/v1/site/rate_limit/endpoint/delete" RemoveEndpointRateLimit,
+    post "/api/v1/site/rate_limit/exempt" ExemptFromRateLimits,
+    get "/api/v1/site/federation" GetFederationSettings,
+    put "/api/v1/site/federation" EditFederationSettings,
+    get "/api/v1/site/posting_requirements" GetPostingRequirements,
+    put "/api/v1/site/posting_requirements" SetPostingRequirements,
+    get "/api/v1/site/word_filter" ListWordFilters,
+    post "/api/v1/site/word_filter" CreateWordFilter,
+    put "/api/v1/site/word_filter" EditWordFilter,
+    post "/api/v1/site/word_filter/delete" DeleteWordFilter,
+    get "/api/v1/site/email_domain" ListEmailDomains,
+    post "/api/v1/site/email_domain" CreateEmailDomain,
+    post "/api/v1/site/email_domain/delete" DeleteEmailDomain,
+    get "/api/v1/site/ip_ban" ListIpBans,
+    post "/api/v1/site/ip_ban" CreateIpBan,
+    post "/api/v1/site/ip_ban/delete" DeleteIpBan,
+    post "/api/v1/site/purge/user" PurgeUser,
+    post "/api/v1/site/purge/post" PurgePost,
+    post "/api/v1/site/purge/comment" PurgeComment,
+    post "/api/v1/site/purge/community" PurgeCommunity,
+    get "/api/v1/categories" ListCategories,
+    get "/api/v1/modlog" GetModlog,
+    get "/api/v1/search" Search,
+    post "/api/v1/community" CreateCommunity,
+    get "/api/v1/community" GetCommunity,
+    put "/api/v1/community" EditCommunity,
+    get "/api/v1/community/list" ListCommunities,
+    post "/api/v1/community/follow" FollowCommunity,
+    get "/api/v1/community/flair" ListCommunityFlairs,
+    post "/api/v1/community/flair" CreateCommunityFlair,
+    put "/api/v1/community/flair" EditCommunityFlair,
+    post "/api/v1/community/flair/delete" DeleteCommunityFlair,
+    get "/api/v1/community/rule" ListCommunityRules,
+    post "/api/v1/community/rule" CreateCommunityRule,
+    put "/api/v1/community/rule" EditCommunityRule,
+    post "/api/v1/community/rule/delete" DeleteCommunityRule,
+    get "/api/v1/community/automod" ListAutomodRules,
+    post "/api/v1/community/automod" CreateAutomodRule,
+    put "/api/v1/community/automod" EditAutomodRule,
+    post "/api/v1/community/automod/delete" DeleteAutomodRule,
+    get "/api/v1/community/mod_note" ListModNotes,
+    post "/api/v1/community/mod_note" CreateModNote,
+    post "/api/v1/community/mod_note/delete" DeleteModNote,
+    post "/api/v1/community/automod/test" TestAutomodRules,
+    get "/api/v1/community/wiki" GetWikiPage,
+    post "/api/v1/community/wiki" CreateWikiPage,
+    put "/api/v1/community/wiki" EditWikiPage,
+    post "/api/v1/community/wiki/delete" DeleteWikiPage,
+    get "/api/v1/community/wiki/list" ListWikiPages,
+    get "/api/v1/community/wiki/revisions" GetWikiPageRevisions,
+    post "/api/v1/community/transfer" TransferCommunity,
+    post "/api/v1/community/takeover" RequestCommunityTakeover,
+    get "/api/v1/community/takeover/list" ListCommunityTakeoverRequests,
+    post "/api/v1/community/takeover/resolve" ResolveCommunityTakeoverRequest,
+    post "/api/v1/community/ban_user" BanFromCommunity,
+    post "/api/v1/community/mod" AddModToCommunity,
+    get "/api/v1/custom_feed" GetCustomFeed,
+    post "/api/v1/custom_feed" CreateCustomFeed,
+    put "/api/v1/custom_feed" EditCustomFeed,
+    post "/api/v1/custom_feed/delete" DeleteCustomFeed,
+    get "/api/v1/custom_feed/list" ListCustomFeeds,
+    get "/api/v1/custom_feed/posts" GetCustomFeedPosts,
+    post "/api/v1/post" CreatePost,
+    post "/api/v1/post/report" CreatePostReport,
+    get "/api/v1/post" GetPost,
+    put "/api/v1/post" EditPost,
+    get "/api/v1/post/list" GetPosts,
+    post "/api/v1/post/like" CreatePostLike,
+    put "/api/v1/post/save" SavePost,
+    put "/api/v1/post/hide" HidePost,
+    put "/api/v1/post/watch" WatchPost,
+    put "/api/v1/post/bulk" BulkModeratePosts,
+    get "/api/v1/post/pending" ListPendingPosts,
+    put "/api/v1/post/approve" ApprovePost,
+    get "/api/v1/post/revisions" GetPostRevisions,
+    get "/api/v1/post/scheduled" ListScheduledPosts,
+    post "/api/v1/post/scheduled" CreateScheduledPost,
+    post "/api/v1/post/scheduled/delete" DeleteScheduledPost,
+    post "/api/v1/post/refresh_embed" RefreshPostEmbed,
+    post "/api/v1/comment/report" CreateCommentReport,
+    post "/api/v1/comment" CreateComment,
+    put "/api/v1/comment" EditComment,
+    get "/api/v1/comment/list" GetComments,
+    post "/api/v1/comment/like" CreateCommentLike,
+    put "/api/v1/comment/save" SaveComment,
+    put "/api/v1/comment/pin" PinComment,
+    put "/api/v1/comment/bulk" BulkModerateComments,
+    get "/api/v1/comment/revisions" GetCommentRevisions,
+    get "/api/v1/report/list" ListReports,
+    put "/api/v1/report/post/resolve" ResolvePostReport,
+    put "/api/v1/report/comment/resolve" ResolveCommentReport,
+    post "/api/v1/appeal" CreateAppeal,
+    get "/api/v1/appeal/list" ListAppeals,
+    get "/api/v1/appeal/mine" GetMyAppeals,
+    put "/api/v1/appeal/resolve" ResolveAppeal,
+    get "/api/v1/private_message/list" GetPrivateMessages,
+    get "/api/v1/private_message/conversation" GetPrivateMessageConversation,
+    get "/api/v1/private_message/conversation/list" GetPrivateMessageConversations,
+    post "/api/v1/private_message" CreatePrivateMessage,
+    put "/api/v1/private_message" EditPrivateMessage,
+    post "/api/v1/user/register" Register,
+    post "/api/v1/user/resend_verification_email" ResendVerificationEmail,
+    get "/api/v1/user" GetUserDetails,
+    get "/api/v1/user/stats" GetUserStats,
+    get "/api/v1/user/get_captcha" GetCaptcha,
+    get "/api/v1/user/mention" GetUserMentions,
+    put "/api/v1/user/mention" EditUserMention,
+    get "/api/v1/user/replies" GetReplies,
+    get "/api/v1/user/inbox" GetInbox,
+    get "/api/v1/user/unread_count" GetUnreadCount,
+    get "/api/v1/user/followed_communities" GetFollowedCommunities,
+    post "/api/v1/user/follow" FollowUser,
+    get "/api/v1/user/followed_users_feed" GetFollowedUsersFeed,
+    post "/api/v1/user/ban" BanUser,
+    post "/api/v1/user/shadow_ban" ShadowBanUser,
+    post "/api/v1/user/login" Login,
+    post "/api/v1/user/delete_account" DeleteAccount,
+    post "/api/v1/user/export" ExportUserData,
+    get "/api/v1/user/export" GetUserDataExport,
+    post "/api/v1/user/import" ImportUserData,
+    post "/api/v1/user/password_reset" PasswordReset,
+    post "/api/v1/user/password_change" PasswordChange,
+    post "/api/v1/user/verify_email" VerifyEmail,
+    post "/api/v1/user/mark_all_as_read" MarkAllAsRead,
+    put "/api/v1/user/save_user_settings" SaveUserSettings,
+    post "/api/v1/user/change_username" ChangeUsername,
+    get "/api/v1/user/notification_settings" GetNotificationSettings,
+    put "/api/v1/user/notification_settings" SaveNotificationSettings,
+    get "/api/v1/user/digest/unsubscribe" UnsubscribeDigest,
+    get "/api/v1/user/push" GetPushPublicKey,
+    post "/api/v1/user/push" RegisterPushSubscription,
+    post "/api/v1/user/push/delete" DeletePushSubscription,
+    get "/api/v1/user/muted_keyword" ListMutedKeywords,
+    post "/api/v1/user/muted_keyword" CreateMutedKeyword,
+    put "/api/v1/user/muted_keyword" EditMutedKeyword,
+    post "/api/v1/user/muted_keyword/delete" DeleteMutedKeyword,
+    get "/api/v1/image/list" ListImages,
+    post "/api/v1/image/delete" DeleteImage,
+    post "/api/v1/admin/add" AddAdmin,
+  );
+
+  let mut paths = Map::new();
+  for (method, path, operation) in operations {
+    paths.entry(path).or_insert_with(|| json!({}))[method] = operation;
+  }
+
+  let schemas = json!(gen.definitions());
+
+  json!({
+    "openapi": "3.0.3",
+    "info": {
+      "title": "Lemmy",
+      "version": VERSION,
+    },
+    "servers": [{
+      "url": format!("{}://{}", get_apub_protocol_string(), Settings::get().hostname),
+    }],
+    "paths": paths,
+    "components": { "schemas": schemas },
+  })
+}
+
+/// The GET operations take their data as query parameters, and the others as a JSON body.
+fn operation<Data>(
+  gen: &mut SchemaGenerator,
+  method: &'static str,
+  path: &'static str,
+  name: &'static str,
+) -> (&'static str, &'static str, Value)
+where
+  Data: JsonSchema,
+  Oper<Data>: Perform,
+  <Oper<Data> as Perform>::Response: JsonSchema,
+{
+  let data = json!(gen.subschema_for::<Data>());
+  let response = json!(gen.subschema_for::<<Oper<Data> as Perform>::Response>());
+  let error = json!(gen.subschema_for::<ErrorResponse>());
+  // The group is the scope it's under, like site or post
+  let tag = path.split('/').nth(3).unwrap_or_default();
+
+  let mut operation = json!({
+    "operationId": name,
+    "tags": [tag],
+    "responses": {
+      "200": {
+        "description": "Success",
+        "content": { "application/json": { "schema": response } },
+      },
+      "400": {
+        "description": "Failure",
+        "content": { "application/json": { "schema": error } },
+      },
+    },
+  });
+  if method == "get" {
+    operation["parameters"] = json!([{
+      "name": "data",
+      "in": "query",
+      "required": true,
+      "style": "form",
+      "explode": true,
+      "schema": data,
+    }]);
+  } else {
+    operation["requestBody"] = json!({
+      "required": true,
+      "content": { "application/json": { "schema": data } },
+    });
+  }
+
+  (method, path, operation)
+}
@@ -0,0 +1,39 @@
+use crate::{blocking_read, routes::DbPoolParam, sitemap::sitemap_index_xml};
+use actix_web::{error::ErrorNotFound, *};
+use lemmy_db::sitemap::Sitemap;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+  cfg
+    .route("/sitemap.xml", web::get().to(get_sitemap_index))
+    .route("/sitemap/{page}.xml", web::get().to(get_sitemap_page));
+}
+
+async fn get_sitemap_index(db: DbPoolParam) -> Result<HttpResponse, Error> {
+  let pages = blocking_read(&db, move |conn| Sitemap::list_pages(conn))
+    .await?
+    .map_err(ErrorNotFound)?;
+
+  // Crawlers come back later when it's not generated yet
+  if pages.is_empty() {
+    return Ok(HttpResponse::NotFound().finish());
+  }
+
+  Ok(
+    HttpResponse::Ok()
+      .content_type("application/xml")
+      .body(sitemap_index_xml(&pages)),
+  )
+}
+
+async fn get_sitemap_page(path: web::Path<i32>, db: DbPoolParam) -> Result<HttpResponse, Error> {
+  let page = path.into_inner();
+  let sitemap = blocking_read(&db, move |conn| Sitemap::read_page(conn, page))
+    .await?
+    .map_err(ErrorNotFound)?;
+
+  Ok(
+    HttpResponse::Ok()
+      .content_type("application/xml")
+      .body(sitemap.content),
+  )
+}
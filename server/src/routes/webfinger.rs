@@ -1,7 +1,11 @@
-use crate::{blocking, routes::DbPoolParam, LemmyError};
+use crate::{
+  blocking,
+  routes::{federation::federation_enabled, DbPoolParam},
+  LemmyError,
+};
 use actix_web::{error::ErrorBadRequest, web::Query, *};
-use lemmy_db::{community::Community, user::User_};
-use lemmy_utils::{settings::Settings, WEBFINGER_COMMUNITY_REGEX, WEBFINGER_USER_REGEX};
+use lemmy_db::{community::Community, user_alias::UserAlias};
+use lemmy_utils::{WEBFINGER_COMMUNITY_REGEX, WEBFINGER_USER_REGEX};
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize)]
@@ -27,12 +31,11 @@ pub struct WebFingerLink {
 }
 
 pub fn config(cfg: &mut web::ServiceConfig) {
-  if Settings::get().federation.enabled {
-    cfg.route(
-      ".well-known/webfinger",
-      web::get().to(get_webfinger_response),
-    );
-  }
+  cfg.service(
+    web::resource(".well-known/webfinger")
+      .guard(guard::fn_guard(federation_enabled))
+      .route(web::get().to(get_webfinger_response)),
+  );
 }
 
 /// Responds to webfinger requests of the following format. There isn't any real documentation for
@@ -66,8 +69,8 @@ async fn get_webfinger_response(
     .actor_id
   } else if let Some(user_name) = user_regex_parsed {
     let user_name = user_name.as_str().to_owned();
-    // Make sure the requested user exists.
-    blocking(&db, move |conn| User_::read_from_name(conn, &user_name))
+    // Make sure the requested user exists. Old names resolve to the user's actor too.
+    blocking(&db, move |conn| UserAlias::read_user(conn, &user_name))
       .await?
       .map_err(|_| ErrorBadRequest(LemmyError::from(format_err!("not_found"))))?
       .actor_id
@@ -5,8 +5,8 @@ use crate::{
 use actix::prelude::*;
 use actix_web::*;
 use actix_web_actors::ws;
-use log::{debug, error, info};
 use std::time::{Duration, Instant};
+use tracing::{debug, error, info};
 
 /// How often heartbeat pings are sent
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
@@ -58,7 +58,8 @@ impl Actor for WSSession {
     self
       .cs_addr
       .send(Connect {
-        addr: addr.recipient(),
+        addr: addr.clone().recipient(),
+        close: addr.recipient(),
         ip: self.ip.to_owned(),
       })
       .into_actor(self)
@@ -93,6 +94,19 @@ impl Handler<WSMessage> for WSSession {
   }
 }
 
+/// The server is shutting down. The restart code tells the client to connect again in a moment.
+impl Handler<CloseSession> for WSSession {
+  type Result = ();
+
+  fn handle(&mut self, _: CloseSession, ctx: &mut Self::Context) {
+    ctx.close(Some(ws::CloseReason {
+      code: ws::CloseCode::Restart,
+      description: Some("The server is restarting".to_owned()),
+    }));
+    ctx.stop();
+  }
+}
+
 /// WebSocket message handler
 impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WSSession {
   fn handle(&mut self, result: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
@@ -0,0 +1,118 @@
+//! Generates the sitemap search engines find the local communities and the recent posts with. It's
+//! split in pages of a few thousand urls, which the index at `/sitemap.xml` lists, and generated
+//! again every hour rather than on each request from a crawler.
+
+use crate::{blocking, digest::escape_html, DbPool, LemmyError};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use lemmy_db::{
+  naive_now,
+  sitemap::{Sitemap, SitemapForm},
+};
+use lemmy_utils::{get_apub_protocol_string, settings::Settings};
+
+/// How many urls go on a page. The protocol allows up to 50,000.
+pub const SITEMAP_PAGE_SIZE: usize = 10_000;
+
+/// How many of the newest posts are listed
+const SITEMAP_POSTS: i64 = 200_000;
+
+pub struct SitemapEntry {
+  pub url: String,
+  pub lastmod: NaiveDateTime,
+}
+
+pub async fn generate_sitemaps(pool: &DbPool) -> Result<(), LemmyError> {
+  let base = site_url();
+  let communities = blocking(pool, move |conn| Sitemap::communities(conn)).await??;
+  let posts = blocking(pool, move |conn| Sitemap::posts(conn, SITEMAP_POSTS)).await??;
+
+  let community_entries = communities
+    .into_iter()
+    .map(|(name, published, updated)| SitemapEntry {
+      url: format!("{}/c/{}", base, name),
+      lastmod: updated.unwrap_or(published),
+    });
+  // A new comment changes the page of the post too
+  let post_entries = posts
+    .into_iter()
+    .map(|(id, published, updated, commented)| {
+      let lastmod = [updated, commented]
+        .iter()
+        .flatten()
+        .fold(published, |a, b| a.max(*b));
+      SitemapEntry {
+        url: format!("{}/post/{}", base, id),
+        lastmod,
+      }
+    });
+  let entries: Vec<SitemapEntry> = community_entries.chain(post_entries).collect();
+
+  let forms: Vec<SitemapForm> = entries
+    .chunks(SITEMAP_PAGE_SIZE)
+    .enumerate()
+    .map(|(i, page_entries)| SitemapForm {
+      page: i as i32 + 1,
+      content: sitemap_page_xml(page_entries),
+      lastmod: page_entries
+        .iter()
+        .map(|e| e.lastmod)
+        .max()
+        .unwrap_or_else(naive_now),
+    })
+    .collect();
+  blocking(pool, move |conn| Sitemap::replace_all(conn, &forms)).await??;
+
+  Ok(())
+}
+
+/// The urlset of one page.
+pub fn sitemap_page_xml(entries: &[SitemapEntry]) -> String {
+  let urls: String = entries
+    .iter()
+    .map(|e| {
+      format!(
+        "<url><loc>{}</loc><lastmod>{}</lastmod></url>",
+        escape_html(&e.url),
+        w3c_datetime(e.lastmod)
+      )
+    })
+    .collect();
+  format!(
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">{}</urlset>\n",
+    urls
+  )
+}
+
+/// The index of the pages, each with its `/sitemap/{page}.xml` url.
+pub fn sitemap_index_xml(pages: &[(i32, NaiveDateTime)]) -> String {
+  let base = site_url();
+  let sitemaps: String = pages
+    .iter()
+    .map(|(page, lastmod)| {
+      format!(
+        "<sitemap><loc>{}/sitemap/{}.xml</loc><lastmod>{}</lastmod></sitemap>",
+        escape_html(&base),
+        page,
+        w3c_datetime(*lastmod)
+      )
+    })
+    .collect();
+  format!(
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">{}</sitemapindex>\n",
+    sitemaps
+  )
+}
+
+fn site_url() -> String {
+  format!(
+    "{}://{}",
+    get_apub_protocol_string(),
+    Settings::get().hostname
+  )
+}
+
+fn w3c_datetime(time: NaiveDateTime) -> String {
+  DateTime::<Utc>::from_utc(time, Utc)
+    .format("%Y-%m-%dT%H:%M:%SZ")
+    .to_string()
+}
@@ -0,0 +1,248 @@
+//! What's done to the images users upload before pictrs keeps them. Their metadata, like where a
+//! photo was taken, is stripped without touching the image, or they're decoded and encoded again,
+//! shrunk and maybe as WebP.
+
+use crate::LemmyError;
+use image::{imageops::FilterType, GenericImageView, ImageFormat};
+use lemmy_utils::settings::UploadConfig;
+use std::io::Cursor;
+
+pub struct ProcessedImage {
+  pub data: Vec<u8>,
+  pub format: ImageFormat,
+}
+
+impl ProcessedImage {
+  pub fn content_type(&self) -> &'static str {
+    content_type(self.format)
+  }
+}
+
+/// What the image is, going by its first bytes
+pub fn detect_content_type(data: &[u8]) -> Option<&'static str> {
+  image::guess_format(data).ok().map(content_type)
+}
+
+/// The audio and video files that can be attached to posts, going by their first bytes
+pub fn detect_attachment_type(data: &[u8]) -> Option<&'static str> {
+  if data.len() < 12 {
+    None
+  } else if &data[4..8] == b"ftyp" {
+    if &data[8..12] == b"M4A " {
+      Some("audio/mp4")
+    } else {
+      Some("video/mp4")
+    }
+  } else if data.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+    Some("video/webm")
+  } else if data.starts_with(b"OggS") {
+    Some("audio/ogg")
+  } else if data.starts_with(b"ID3") || data.starts_with(&[0xFF, 0xFB]) {
+    Some("audio/mpeg")
+  } else if data.starts_with(b"fLaC") {
+    Some("audio/flac")
+  } else if data.starts_with(b"RIFF") && &data[8..12] == b"WAVE" {
+    Some("audio/wav")
+  } else {
+    None
+  }
+}
+
+fn content_type(format: ImageFormat) -> &'static str {
+  match format {
+    ImageFormat::Jpeg => "image/jpeg",
+    ImageFormat::Png => "image/png",
+    ImageFormat::Gif => "image/gif",
+    ImageFormat::WebP => "image/webp",
+    ImageFormat::Bmp => "image/bmp",
+    ImageFormat::Tiff => "image/tiff",
+    ImageFormat::Ico => "image/x-icon",
+    _ => "application/octet-stream",
+  }
+}
+
+pub fn process_image(data: Vec<u8>, config: &UploadConfig) -> Result<ProcessedImage, LemmyError> {
+  let format = image::guess_format(&data)?;
+  let orientation = exif_orientation(&data);
+
+  // Re-encoding would keep only the first frame of an animation
+  if config.reencode && format != ImageFormat::Gif {
+    return reencode(&data, format, orientation, config);
+  }
+
+  let data = match format {
+    ImageFormat::Jpeg => strip_jpeg(&data, orientation)?,
+    ImageFormat::Png => strip_png(&data)?,
+    ImageFormat::WebP => strip_webp(&data)?,
+    _ => data,
+  };
+  Ok(ProcessedImage { data, format })
+}
+
+/// Which way up the camera was, 1 being upright
+fn exif_orientation(data: &[u8]) -> Option<u32> {
+  let exif = exif::Reader::new()
+    .read_from_container(&mut Cursor::new(data))
+    .ok()?;
+  exif
+    .get_field(exif::Tag::Orientation, exif::In::PRIMARY)?
+    .value
+    .get_uint(0)
+}
+
+fn reencode(
+  data: &[u8],
+  format: ImageFormat,
+  orientation: Option<u32>,
+  config: &UploadConfig,
+) -> Result<ProcessedImage, LemmyError> {
+  // The orientation goes away with the rest of the metadata, so the pixels are turned instead
+  let mut image = match (
+    image::load_from_memory_with_format(data, format)?,
+    orientation,
+  ) {
+    (image, Some(2)) => image.fliph(),
+    (image, Some(3)) => image.rotate180(),
+    (image, Some(4)) => image.flipv(),
+    (image, Some(5)) => image.rotate90().fliph(),
+    (image, Some(6)) => image.rotate90(),
+    (image, Some(7)) => image.rotate270().fliph(),
+    (image, Some(8)) => image.rotate270(),
+    (image, _) => image,
+  };
+
+  let max = config.max_dimension;
+  if image.width() > max || image.height() > max {
+    image = image.resize(max, max, FilterType::Lanczos3);
+  }
+
+  // There's no WebP encoder in image, so WebPs stay WebPs through libwebp
+  if config.webp || format == ImageFormat::WebP {
+    let rgba = image.to_rgba();
+    let webp = webp::Encoder::from_rgba(&rgba, image.width(), image.height())
+      .encode(config.webp_quality)
+      .to_vec();
+    return Ok(ProcessedImage {
+      data: webp,
+      format: ImageFormat::WebP,
+    });
+  }
+
+  let mut out = Vec::new();
+  image.write_to(&mut out, format)?;
+  Ok(ProcessedImage { data: out, format })
+}
+
+/// Drops the EXIF, XMP and IPTC segments and the comments. The color profile stays. A new EXIF
+/// segment with nothing but the orientation keeps the photo the right way up.
+fn strip_jpeg(data: &[u8], orientation: Option<u32>) -> Result<Vec<u8>, LemmyError> {
+  let mut orientation = orientation.filter(|o| *o != 1);
+  let mut out = data[..2].to_vec();
+
+  let mut pos = 2;
+  while pos + 4 <= data.len() {
+    if data[pos] != 0xFF {
+      break;
+    }
+    let marker = data[pos + 1];
+    // Fill bytes before a marker
+    if marker == 0xFF {
+      pos += 1;
+      continue;
+    }
+
+    // After JFIF, where the EXIF would've been
+    if marker != 0xE0 {
+      if let Some(orientation) = orientation.take() {
+        out.extend_from_slice(&orientation_exif(orientation));
+      }
+    }
+
+    // The compressed image data follows the start of scan
+    if marker == 0xDA {
+      out.extend_from_slice(&data[pos..]);
+      return Ok(out);
+    }
+
+    let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+    let end = pos + 2 + len;
+    if len < 2 || end > data.len() {
+      break;
+    }
+    let metadata = marker == 0xE1 || marker == 0xED || marker == 0xFE;
+    if !metadata {
+      out.extend_from_slice(&data[pos..end]);
+    }
+    pos = end;
+  }
+
+  Err(format_err!("broken jpeg").into())
+}
+
+/// An APP1 segment with a TIFF header and the one orientation field
+fn orientation_exif(orientation: u32) -> Vec<u8> {
+  let mut segment = vec![0xFF, 0xE1, 0, 34];
+  segment.extend_from_slice(b"Exif\0\0MM\0\x2a\0\0\0\x08");
+  segment.extend_from_slice(&[0, 1, 0x01, 0x12, 0, 3, 0, 0, 0, 1]);
+  segment.extend_from_slice(&(orientation as u16).to_be_bytes());
+  segment.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+  segment
+}
+
+/// Drops the EXIF, text and time chunks
+fn strip_png(data: &[u8]) -> Result<Vec<u8>, LemmyError> {
+  let mut out = data[..8].to_vec();
+
+  let mut pos = 8;
+  while pos + 12 <= data.len() {
+    let len = u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize;
+    let end = pos + 12 + len;
+    if end > data.len() {
+      break;
+    }
+    let chunk_type = &data[pos + 4..pos + 8];
+    if !matches!(chunk_type, b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" | b"tIME") {
+      out.extend_from_slice(&data[pos..end]);
+    }
+    if chunk_type == b"IEND" {
+      return Ok(out);
+    }
+    pos = end;
+  }
+
+  Err(format_err!("broken png").into())
+}
+
+/// Drops the EXIF and XMP chunks, and their flags in the extended header
+fn strip_webp(data: &[u8]) -> Result<Vec<u8>, LemmyError> {
+  if data.len() < 12 {
+    return Err(format_err!("broken webp").into());
+  }
+  let mut out = data[..12].to_vec();
+
+  let mut pos = 12;
+  while pos + 8 <= data.len() {
+    let len =
+      u32::from_le_bytes([data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]]) as usize;
+    // Chunks are padded to an even size
+    let end = pos + 8 + len + len % 2;
+    if end > data.len() {
+      return Err(format_err!("broken webp").into());
+    }
+    match &data[pos..pos + 4] {
+      b"EXIF" | b"XMP " => (),
+      b"VP8X" if len > 0 => {
+        out.extend_from_slice(&data[pos..end]);
+        let flags = out.len() - (end - pos) + 8;
+        out[flags] &= !(0x08 | 0x04);
+      }
+      _ => out.extend_from_slice(&data[pos..end]),
+    }
+    pos = end;
+  }
+
+  // The RIFF size counts everything after itself
+  let riff_size = (out.len() - 8) as u32;
+  out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+  Ok(out)
+}
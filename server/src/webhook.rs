@@ -0,0 +1,206 @@
+//! Posts the events of the instance to the webhooks admins and mods registered. Every delivery is
+//! a job of its own, so a receiver that's down gets the event again later. The body is signed with
+//! the secret of the webhook: the `X-Lemmy-Signature` header is `sha256=` and the HMAC-SHA256 of
+//! the body in hex.
+
+use crate::{
+  blocking,
+  job_queue::{enqueue_all, Task},
+  request::retry,
+  DbPool,
+  LemmyError,
+};
+use actix_web::{client::Client, http::header::CONTENT_TYPE, web};
+use lemmy_db::{
+  post_view::PostView,
+  user::User_,
+  user_view::UserView,
+  webhook::{Webhook, WebhookEvent},
+  Crud,
+};
+use openssl::{hash::MessageDigest, pkey::PKey, sign::Signer};
+use serde::Serialize;
+use std::net::{IpAddr, ToSocketAddrs};
+use url::Url;
+
+pub(crate) const SIGNATURE_HEADER: &str = "X-Lemmy-Signature";
+pub(crate) const EVENT_HEADER: &str = "X-Lemmy-Event";
+
+#[derive(Serialize)]
+struct WebhookPayload<'a, T: Serialize> {
+  event: WebhookEvent,
+  community_id: Option<i32>,
+  data: &'a T,
+}
+
+#[derive(Serialize)]
+pub struct NewRegistration {
+  pub user_id: i32,
+  pub name: String,
+  pub actor_id: String,
+  pub published: chrono::NaiveDateTime,
+}
+
+impl NewRegistration {
+  pub fn new(user: &User_) -> Self {
+    NewRegistration {
+      user_id: user.id,
+      name: user.name.to_owned(),
+      actor_id: user.actor_id.to_owned(),
+      published: user.published,
+    }
+  }
+}
+
+/// An activity that didn't get to the other instance, after all its attempts
+#[derive(Serialize)]
+pub struct FederationFailure {
+  pub actor_id: String,
+  pub inbox: String,
+  pub error: String,
+}
+
+/// Queues a delivery of the event for every webhook that wants it. The community ones only get
+/// the events of their community.
+pub async fn queue_webhooks<T: Serialize>(
+  pool: &DbPool,
+  event: WebhookEvent,
+  community_id: Option<i32>,
+  data: &T,
+) -> Result<(), LemmyError> {
+  let webhooks = blocking(pool, move |conn| {
+    Webhook::for_event(conn, event, community_id)
+  })
+  .await??;
+  if webhooks.is_empty() {
+    return Ok(());
+  }
+
+  let payload = serde_json::to_string(&WebhookPayload {
+    event,
+    community_id,
+    data,
+  })?;
+  let tasks = webhooks
+    .into_iter()
+    .map(|webhook| Task::DeliverWebhook {
+      webhook_id: webhook.id,
+      event,
+      payload: payload.to_owned(),
+    })
+    .collect();
+  enqueue_all(pool, tasks).await
+}
+
+/// A new post, as anyone would see it.
+pub async fn queue_new_post_webhooks(pool: &DbPool, post: &PostView) -> Result<(), LemmyError> {
+  let mut post = post.to_owned();
+  post.user_id = None;
+  post.my_vote = None;
+  post.subscribed = None;
+  post.read = None;
+  post.saved = None;
+  queue_webhooks(pool, WebhookEvent::NewPost, Some(post.community_id), &post).await
+}
+
+/// Posts the event to the webhook. Anything but a success is tried again.
+pub async fn deliver_webhook(
+  pool: &DbPool,
+  client: &Client,
+  webhook_id: i32,
+  event: WebhookEvent,
+  payload: &str,
+) -> Result<(), LemmyError> {
+  // It was deleted or turned off since
+  let webhook = match blocking(pool, move |conn| Webhook::read(conn, webhook_id)).await? {
+    Ok(webhook) if webhook.enabled => webhook,
+    _ => return Ok(()),
+  };
+
+  // The name can point somewhere else than when the webhook was registered, so the ones of the
+  // mods are checked again before every delivery
+  let creator_id = webhook.creator_id;
+  let by_admin = move |conn: &'_ _| UserView::read(conn, creator_id).map(|u| u.admin);
+  if !blocking(pool, by_admin).await?? && !is_public_url(&webhook.url).await {
+    return Err(format_err!("webhook {} isn't on a public address", webhook.id).into());
+  }
+
+  let signature = format!("sha256={}", sign_webhook_payload(&webhook.secret, payload)?);
+  let response = retry(|| {
+    client
+      .post(&webhook.url)
+      .header(CONTENT_TYPE, "application/json")
+      .header(EVENT_HEADER, event.name())
+      .header(SIGNATURE_HEADER, signature.as_str())
+      .send_body(payload.to_owned())
+  })
+  .await?;
+  if !response.status().is_success() {
+    return Err(format_err!("webhook {} answered {}", webhook.id, response.status()).into());
+  }
+
+  Ok(())
+}
+
+/// Whether every address the host of the URL resolves to is out on the internet, rather than the
+/// server itself or a network it's on. A host that doesn't resolve isn't.
+pub async fn is_public_url(url: &str) -> bool {
+  let url = match Url::parse(url) {
+    Ok(url) => url,
+    Err(_e) => return false,
+  };
+  let host = match url.host_str() {
+    Some(host) => host.to_owned(),
+    None => return false,
+  };
+  let port = url.port_or_known_default().unwrap_or(443);
+
+  let resolve = move || {
+    (host.as_str(), port)
+      .to_socket_addrs()
+      .map(|addrs| addrs.collect::<Vec<_>>())
+  };
+  match web::block(resolve).await {
+    Ok(addrs) => !addrs.is_empty() && addrs.iter().all(|addr| is_public_ip(&addr.ip())),
+    Err(_e) => false,
+  }
+}
+
+pub fn is_public_ip(ip: &IpAddr) -> bool {
+  match ip {
+    IpAddr::V4(ip) => {
+      let [a, b, ..] = ip.octets();
+      !(ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        // The shared address space of carrier grade NAT, 100.64.0.0/10
+        || (a == 100 && (b & 0xc0) == 64))
+    }
+    IpAddr::V6(ip) => {
+      let segments = ip.segments();
+      // An IPv4 address written as IPv6, ::ffff:a.b.c.d
+      if segments[..5].iter().all(|s| *s == 0) && segments[5] == 0xffff {
+        let [a, b] = segments[6].to_be_bytes();
+        let [c, d] = segments[7].to_be_bytes();
+        return is_public_ip(&IpAddr::from([a, b, c, d]));
+      }
+      !(ip.is_loopback()
+        || ip.is_unspecified()
+        // Unique local, fc00::/7, and link local, fe80::/10
+        || (segments[0] & 0xfe00) == 0xfc00
+        || (segments[0] & 0xffc0) == 0xfe80)
+    }
+  }
+}
+
+/// The HMAC-SHA256 of the payload in hex, keyed with the secret of the webhook
+pub fn sign_webhook_payload(secret: &str, payload: &str) -> Result<String, LemmyError> {
+  let key = PKey::hmac(secret.as_bytes())?;
+  let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+  signer.update(payload.as_bytes())?;
+  let signature = signer.sign_to_vec()?;
+  Ok(signature.iter().map(|b| format!("{:02x}", b)).collect())
+}
@@ -1,12 +1,8 @@
+pub mod pubsub;
 pub mod server;
 
 use crate::ConnectionId;
 use actix::prelude::*;
-use diesel::{
-  r2d2::{ConnectionManager, Pool},
-  PgConnection,
-};
-use log::{error, info};
 use rand::{rngs::ThreadRng, Rng};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -15,11 +11,13 @@ use std::{
   collections::{HashMap, HashSet},
   str::FromStr,
 };
+use tracing::{error, info};
 
 #[derive(EnumString, ToString, Debug, Clone)]
 pub enum UserOperation {
   Login,
   Register,
+  GetCaptcha,
   CreateCommunity,
   CreatePost,
   ListCommunities,
@@ -29,41 +27,168 @@ pub enum UserOperation {
   CreateComment,
   EditComment,
   SaveComment,
+  PinComment,
+  GetCommentRevisions,
   CreateCommentLike,
+  CommentScore,
   GetPosts,
   CreatePostLike,
+  PostScore,
+  UsersOnline,
+  AutomodHit,
   EditPost,
   SavePost,
+  HidePost,
+  WatchPost,
+  GetPostRevisions,
+  RefreshPostEmbed,
+  ListScheduledPosts,
+  CreateScheduledPost,
+  DeleteScheduledPost,
   EditCommunity,
   FollowCommunity,
   GetFollowedCommunities,
   GetUserDetails,
+  GetUserStats,
+  FollowUser,
+  GetFollowedUsersFeed,
   GetReplies,
   GetUserMentions,
+  GetUnreadCount,
+  GetInbox,
   EditUserMention,
   GetModlog,
   BanFromCommunity,
   AddModToCommunity,
+  ListCommunityFlairs,
+  CreateCommunityFlair,
+  EditCommunityFlair,
+  DeleteCommunityFlair,
+  ListCommunityRules,
+  CreateCommunityRule,
+  EditCommunityRule,
+  DeleteCommunityRule,
+  ListAutomodRules,
+  CreateAutomodRule,
+  EditAutomodRule,
+  DeleteAutomodRule,
+  TestAutomodRules,
+  ListBlockedDomains,
+  CreateBlockedDomain,
+  DeleteBlockedDomain,
+  ListWebhooks,
+  CreateWebhook,
+  EditWebhook,
+  DeleteWebhook,
+  GetRateLimits,
+  EditRateLimits,
+  SetEndpointRateLimit,
+  RemoveEndpointRateLimit,
+  ExemptFromRateLimits,
+  GetFederationSettings,
+  EditFederationSettings,
+  GetPostingRequirements,
+  SetPostingRequirements,
+  ListWordFilters,
+  CreateWordFilter,
+  EditWordFilter,
+  DeleteWordFilter,
+  BulkModeratePosts,
+  BulkModerateComments,
+  ListPendingPosts,
+  ApprovePost,
+  ListEmailDomains,
+  CreateEmailDomain,
+  DeleteEmailDomain,
+  ListIpBans,
+  CreateIpBan,
+  DeleteIpBan,
+  PurgeUser,
+  PurgePost,
+  PurgeComment,
+  PurgeCommunity,
+  CreateModNote,
+  DeleteModNote,
+  ListModNotes,
+  CreateAppeal,
+  ListAppeals,
+  GetMyAppeals,
+  ResolveAppeal,
+  CreatePostReport,
+  CreateCommentReport,
+  ListReports,
+  ResolvePostReport,
+  ResolveCommentReport,
+  CreateWikiPage,
+  EditWikiPage,
+  DeleteWikiPage,
+  GetWikiPage,
+  ListWikiPages,
+  GetWikiPageRevisions,
+  CreateCustomFeed,
+  EditCustomFeed,
+  DeleteCustomFeed,
+  GetCustomFeed,
+  ListCustomFeeds,
+  GetCustomFeedPosts,
   CreateSite,
   EditSite,
   GetSite,
+  CreateSiteAnnouncement,
+  DeleteSiteAnnouncement,
+  DismissSiteAnnouncement,
+  GetSiteDocument,
+  SaveSiteDocument,
+  DeleteSiteDocument,
   AddAdmin,
   BanUser,
+  ShadowBanUser,
   Search,
   MarkAllAsRead,
   SaveUserSettings,
+  ChangeUsername,
+  GetNotificationSettings,
+  SaveNotificationSettings,
+  UnsubscribeDigest,
+  GetPushPublicKey,
+  RegisterPushSubscription,
+  DeletePushSubscription,
+  ListMutedKeywords,
+  CreateMutedKeyword,
+  EditMutedKeyword,
+  DeleteMutedKeyword,
+  ListImages,
+  DeleteImage,
   TransferCommunity,
+  RequestCommunityTakeover,
+  ListCommunityTakeoverRequests,
+  ResolveCommunityTakeoverRequest,
   TransferSite,
   DeleteAccount,
+  ExportUserData,
+  GetUserDataExport,
+  ImportUserData,
   PasswordReset,
   PasswordChange,
+  VerifyEmail,
+  ResendVerificationEmail,
   CreatePrivateMessage,
   EditPrivateMessage,
   GetPrivateMessages,
+  GetPrivateMessageConversations,
+  GetPrivateMessageConversation,
   UserJoin,
+  Subscribe,
+  Unsubscribe,
+  ReAuth,
   GetComments,
   GetSiteConfig,
   SaveSiteConfig,
+  GetCommunityDefaults,
+  EditCommunityDefaults,
+  GetJobQueue,
+  ImportCommunity,
+  GetCommunityImport,
 }
 
 #[derive(Clone)]
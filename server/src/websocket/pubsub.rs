@@ -0,0 +1,111 @@
+//! Passes the websocket broadcasts between the server processes through Redis, so that the clients
+//! connected to one of them hear about what happened on the others. Every process publishes what
+//! it sends to its own rooms, and sends on what the others published.
+
+use super::server::{Broadcast, BroadcastTarget, ChatServer};
+use crate::LemmyError;
+use actix::Addr;
+use futures::{
+  channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+  StreamExt,
+};
+use redis::{aio::ConnectionManager, Client};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::error;
+use uuid::Uuid;
+
+const CHANNEL: &str = "lemmy:websocket";
+
+/// How long to wait before subscribing again after losing Redis
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize)]
+struct PubSubMessage {
+  /// The process that sent it, which already did so to its own rooms
+  node_id: Uuid,
+  target: BroadcastTarget,
+  message: String,
+}
+
+#[derive(Clone)]
+pub struct PubSub {
+  client: Client,
+  node_id: Uuid,
+  // The messages go out one after the other, in the order they were sent
+  sender: UnboundedSender<String>,
+}
+
+impl PubSub {
+  pub async fn connect(redis_url: &str) -> Result<Self, LemmyError> {
+    let client = Client::open(redis_url)?;
+    let connection = ConnectionManager::new(client.get_connection_info().clone()).await?;
+    let (sender, receiver) = unbounded();
+    actix_rt::spawn(publish_all(connection, receiver));
+    Ok(PubSub {
+      client,
+      node_id: Uuid::new_v4(),
+      sender,
+    })
+  }
+
+  pub(super) fn publish(&self, target: BroadcastTarget, message: &str) {
+    let message = PubSubMessage {
+      node_id: self.node_id,
+      target,
+      message: message.to_owned(),
+    };
+    match serde_json::to_string(&message) {
+      Ok(payload) => {
+        let _ = self.sender.unbounded_send(payload);
+      }
+      Err(e) => error!("Couldn't publish a websocket message: {}", e),
+    }
+  }
+
+  /// Runs forever, handing the other processes' messages to the chat server.
+  pub(super) async fn listen(self, chat_server: Addr<ChatServer>) {
+    loop {
+      if let Err(e) = self.listen_once(&chat_server).await {
+        error!("Lost the websocket messages of the other servers: {}", e);
+      }
+      actix_rt::time::delay_for(RESUBSCRIBE_DELAY).await;
+    }
+  }
+
+  async fn listen_once(&self, chat_server: &Addr<ChatServer>) -> Result<(), LemmyError> {
+    let mut pubsub = self.client.get_async_connection().await?.into_pubsub();
+    pubsub.subscribe(CHANNEL).await?;
+
+    let mut messages = pubsub.on_message();
+    while let Some(msg) = messages.next().await {
+      match parse_message(msg) {
+        Ok(message) if message.node_id != self.node_id => chat_server.do_send(Broadcast {
+          target: message.target,
+          message: message.message,
+        }),
+        Ok(_) => (),
+        Err(e) => error!("Unreadable websocket message from another server: {}", e),
+      }
+    }
+    Ok(())
+  }
+}
+
+fn parse_message(msg: redis::Msg) -> Result<PubSubMessage, LemmyError> {
+  let payload: String = msg.get_payload()?;
+  Ok(serde_json::from_str(&payload)?)
+}
+
+async fn publish_all(mut connection: ConnectionManager, mut receiver: UnboundedReceiver<String>) {
+  while let Some(payload) = receiver.next().await {
+    let published = redis::cmd("PUBLISH")
+      .arg(CHANNEL)
+      .arg(payload)
+      .query_async::<_, ()>(&mut connection)
+      .await;
+    if let Err(e) = published {
+      error!("Couldn't publish a websocket message: {}", e);
+    }
+  }
+}
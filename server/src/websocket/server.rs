@@ -4,11 +4,47 @@
 
 use super::*;
 use crate::{
-  api::{comment::*, community::*, post::*, site::*, user::*, *},
+  api::{
+    appeal::*,
+    automod::*,
+    blocked_domain::*,
+    bulk_moderation::*,
+    claims::Claims,
+    comment::*,
+    community::*,
+    community_import::*,
+    community_takeover::*,
+    community_wiki::*,
+    custom_feed::*,
+    email_domain::*,
+    federation::*,
+    ip_ban::*,
+    job::*,
+    media::*,
+    mod_note::*,
+    muted_keyword::*,
+    post::*,
+    post_approval::*,
+    posting_requirement::*,
+    purge::*,
+    rate_limit::*,
+    report::*,
+    scheduled_post::*,
+    site::*,
+    site_document::*,
+    user::*,
+    webhook::*,
+    websocket_subscription::*,
+    word_filter::*,
+    *,
+  },
+  cache::invalidate_post_listings,
+  metrics::{observe_request, set_websocket_connections},
   rate_limit::RateLimit,
-  websocket::UserOperation,
+  websocket::{pubsub::PubSub, UserOperation},
   CommunityId,
   ConnectionId,
+  ConversationId,
   DbPool,
   IPAddr,
   LemmyError,
@@ -16,6 +52,10 @@ use crate::{
   UserId,
 };
 use actix_web::client::Client;
+use std::time::{Duration, Instant};
+use tracing::{field, info_span};
+use tracing_futures::Instrument;
+use uuid::Uuid;
 
 /// Chat server sends this messages to session
 #[derive(Message)]
@@ -29,9 +69,20 @@ pub struct WSMessage(pub String);
 #[rtype(usize)]
 pub struct Connect {
   pub addr: Recipient<WSMessage>,
+  pub close: Recipient<CloseSession>,
   pub ip: IPAddr,
 }
 
+/// Chat server sends this to a session to close it, telling the client to connect again
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CloseSession;
+
+/// The server is going down, so every session is closed
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Shutdown;
+
 /// Session is disconnected
 #[derive(Message)]
 #[rtype(result = "()")]
@@ -84,6 +135,15 @@ pub struct SendPost {
   pub my_id: Option<ConnectionId>,
 }
 
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SendPostToSubscribers {
+  pub op: UserOperation,
+  pub post: PostResponse,
+  pub subscriber_ids: Vec<UserId>,
+  pub my_id: Option<ConnectionId>,
+}
+
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct SendComment {
@@ -92,10 +152,21 @@ pub struct SendComment {
   pub my_id: Option<ConnectionId>,
 }
 
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SendPrivateMessage {
+  pub op: UserOperation,
+  pub message: PrivateMessageResponse,
+  /// Whether the recipient's own sessions get it too, depending on their notification settings
+  pub notify_recipient: bool,
+  pub my_id: Option<ConnectionId>,
+}
+
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct JoinUserRoom {
   pub user_id: UserId,
+  pub hide_presence: bool,
   pub id: ConnectionId,
 }
 
@@ -113,6 +184,51 @@ pub struct JoinPostRoom {
   pub id: ConnectionId,
 }
 
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct JoinConversationRoom {
+  pub conversation_id: ConversationId,
+  pub id: ConnectionId,
+}
+
+/// A session's own subscriptions, which it gets the messages of on top of the rooms it joined.
+/// Answers with the rooms the session is in afterwards.
+#[derive(Message)]
+#[rtype(result = "Result<Vec<Room>, RoomLimitReached>")]
+pub struct ChangeSubscriptions {
+  pub id: ConnectionId,
+  pub subscribe: Vec<Room>,
+  pub unsubscribe: Vec<Room>,
+}
+
+/// The session is in as many rooms as it can be
+#[derive(Debug)]
+pub struct RoomLimitReached;
+
+/// The session logged in as someone else, or out. Only the inbox changes with it.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ChangeSessionUser {
+  pub id: ConnectionId,
+  pub user: Option<SessionUser>,
+}
+
+/// Something landed in the inboxes of these users, or was read from them. All their sessions get
+/// the new unread counts, so that the badges agree across devices.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SendUnreadCounts {
+  pub user_ids: Vec<UserId>,
+}
+
+/// Who's logged in on a session
+#[derive(Clone, Copy, Debug)]
+pub struct SessionUser {
+  pub id: UserId,
+  /// Not counted in the users online
+  pub hide_presence: bool,
+}
+
 #[derive(Message)]
 #[rtype(usize)]
 pub struct GetUsersOnline;
@@ -129,9 +245,58 @@ pub struct GetCommunityUsersOnline {
   pub community_id: CommunityId,
 }
 
+/// The most rooms a session can be in, so that one client can't make the server track any number
+const MAX_ROOMS_PER_SESSION: usize = 100;
+
+/// How often the rooms hear how many users are online, when it changed
+const USERS_ONLINE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// One of the rooms a session gets the messages of
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Room {
+  Post(PostId),
+  /// 0 is /c/all
+  Community(CommunityId),
+  /// The user's inbox
+  User(UserId),
+  Conversation(ConversationId),
+}
+
+/// Who a message goes to. The other server processes get it too, to send it to the same rooms.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum BroadcastTarget {
+  All,
+  /// A session in more than one of them only gets the message once
+  Rooms(Vec<Room>),
+  CommunityRoom(CommunityId),
+  UserRoom(UserId),
+  /// Everyone reading the conversation, and the recipient's sessions if there is one
+  PrivateMessage {
+    conversation_id: ConversationId,
+    recipient_id: Option<UserId>,
+  },
+  /// The subscribers' sessions that aren't in /c/all or the community room
+  Subscribers {
+    community_id: CommunityId,
+    subscriber_ids: Vec<UserId>,
+  },
+}
+
+/// A message another server process sent to its rooms
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Broadcast {
+  pub target: BroadcastTarget,
+  pub message: String,
+}
+
 pub struct SessionInfo {
   pub addr: Recipient<WSMessage>,
+  pub close: Recipient<CloseSession>,
   pub ip: IPAddr,
+  /// So that leaving them doesn't go through all the rooms
+  pub rooms: HashSet<Room>,
+  pub user: Option<SessionUser>,
 }
 
 /// `ChatServer` manages chat rooms and responsible for coordinating chat
@@ -150,116 +315,229 @@ pub struct ChatServer {
   /// sessions (IE clients)
   user_rooms: HashMap<UserId, HashSet<ConnectionId>>,
 
+  /// A map from private message conversation to the connections currently reading it
+  conversation_rooms: HashMap<ConversationId, HashSet<ConnectionId>>,
+
   rng: ThreadRng,
 
   /// The DB Pool
-  pool: Pool<ConnectionManager<PgConnection>>,
+  pool: DbPool,
 
   /// Rate limiting based on rate type and IP addr
   rate_limiter: RateLimit,
 
   /// An HTTP Client
   client: Client,
+
+  /// Shares the broadcasts with the other server processes, if there are any
+  pubsub: Option<PubSub>,
+
+  /// The users online each room heard of last
+  users_online: HashMap<Room, usize>,
 }
 
 impl ChatServer {
   pub fn startup(
-    pool: Pool<ConnectionManager<PgConnection>>,
+    pool: DbPool,
     rate_limiter: RateLimit,
     client: Client,
+    pubsub: Option<PubSub>,
   ) -> ChatServer {
     ChatServer {
       sessions: HashMap::new(),
       post_rooms: HashMap::new(),
       community_rooms: HashMap::new(),
       user_rooms: HashMap::new(),
+      conversation_rooms: HashMap::new(),
       rng: rand::thread_rng(),
       pool,
       rate_limiter,
       client,
+      pubsub,
+      users_online: HashMap::new(),
     }
   }
 
+  /// Joining a community or a post leaves the other ones, which also avoids double messages.
   pub fn join_community_room(&mut self, community_id: CommunityId, id: ConnectionId) {
-    // remove session from all rooms
-    for sessions in self.community_rooms.values_mut() {
-      sessions.remove(&id);
-    }
+    self.leave_rooms(id, |room| match room {
+      Room::Community(_) | Room::Post(_) => true,
+      _ => false,
+    });
+    self.enter_room(Room::Community(community_id), id);
+  }
 
-    // Also leave all post rooms
-    // This avoids double messages
-    for sessions in self.post_rooms.values_mut() {
-      sessions.remove(&id);
+  pub fn join_post_room(&mut self, post_id: PostId, id: ConnectionId) {
+    self.leave_rooms(id, |room| match room {
+      Room::Community(_) | Room::Post(_) => true,
+      _ => false,
+    });
+    self.enter_room(Room::Post(post_id), id);
+  }
+
+  /// The session is logged in as that user from then on.
+  pub fn join_user_room(&mut self, user: SessionUser, id: ConnectionId) {
+    self.leave_rooms(id, |room| match room {
+      Room::User(_) => true,
+      _ => false,
+    });
+    if let Some(info) = self.sessions.get_mut(&id) {
+      info.user = Some(user);
     }
+    self.enter_room(Room::User(user.id), id);
+  }
 
-    // If the room doesn't exist yet
-    if self.community_rooms.get_mut(&community_id).is_none() {
-      self.community_rooms.insert(community_id, HashSet::new());
+  /// The session isn't logged in anymore.
+  pub fn leave_user_room(&mut self, id: ConnectionId) {
+    self.leave_rooms(id, |room| match room {
+      Room::User(_) => true,
+      _ => false,
+    });
+    if let Some(info) = self.sessions.get_mut(&id) {
+      info.user = None;
     }
+  }
 
-    self
-      .community_rooms
-      .get_mut(&community_id)
-      .unwrap()
-      .insert(id);
+  /// The logged in users reading a post or community, without the ones who hide it. Only the
+  /// sessions of this server process are counted.
+  fn count_users_online(&self, room: Room) -> usize {
+    let sessions = match room {
+      Room::Post(post_id) => self.post_rooms.get(&post_id),
+      Room::Community(community_id) => self.community_rooms.get(&community_id),
+      _ => None,
+    };
+    sessions.map_or(0, |sessions| {
+      sessions
+        .iter()
+        .filter_map(|id| self.sessions.get(id).and_then(|info| info.user))
+        .filter(|user| !user.hide_presence)
+        .map(|user| user.id)
+        .collect::<HashSet<UserId>>()
+        .len()
+    })
   }
 
-  pub fn join_post_room(&mut self, post_id: PostId, id: ConnectionId) {
-    // remove session from all rooms
-    for sessions in self.post_rooms.values_mut() {
-      sessions.remove(&id);
+  /// Tells the post and community rooms how many users are online, if it changed since last time.
+  fn send_users_online(&mut self) {
+    let rooms = self
+      .post_rooms
+      .keys()
+      .map(|post_id| Room::Post(*post_id))
+      .chain(self.community_rooms.keys().map(|id| Room::Community(*id)))
+      .collect::<Vec<Room>>();
+
+    let mut users_online = HashMap::new();
+    for room in rooms {
+      let online = self.count_users_online(room);
+      users_online.insert(room, online);
+      if self.users_online.get(&room) == Some(&online) {
+        continue;
+      }
+
+      let res = UsersOnlineResponse {
+        post_id: match room {
+          Room::Post(post_id) => Some(post_id),
+          _ => None,
+        },
+        community_id: match room {
+          Room::Community(community_id) => Some(community_id),
+          _ => None,
+        },
+        online,
+      };
+      match to_json_string(&UserOperation::UsersOnline, &res) {
+        Ok(message) => self.send_to_target(&BroadcastTarget::Rooms(vec![room]), &message, None),
+        Err(e) => error!("Couldn't send the users online: {}", e),
+      }
     }
+    self.users_online = users_online;
+  }
 
-    // Also leave all communities
-    // This avoids double messages
-    for sessions in self.community_rooms.values_mut() {
-      sessions.remove(&id);
+  pub fn join_conversation_room(&mut self, conversation_id: ConversationId, id: ConnectionId) {
+    self.leave_rooms(id, |room| match room {
+      Room::Conversation(_) => true,
+      _ => false,
+    });
+    self.enter_room(Room::Conversation(conversation_id), id);
+  }
+
+  /// Subscribing doesn't leave any of the other rooms.
+  fn change_subscriptions(
+    &mut self,
+    msg: ChangeSubscriptions,
+  ) -> Result<Vec<Room>, RoomLimitReached> {
+    for room in msg.unsubscribe {
+      self.leave_room(room, msg.id);
     }
 
-    // If the room doesn't exist yet
-    if self.post_rooms.get_mut(&post_id).is_none() {
-      self.post_rooms.insert(post_id, HashSet::new());
+    if let Some(info) = self.sessions.get(&msg.id) {
+      let new_rooms = msg
+        .subscribe
+        .iter()
+        .filter(|room| !info.rooms.contains(room))
+        .collect::<HashSet<&Room>>();
+      if info.rooms.len() + new_rooms.len() > MAX_ROOMS_PER_SESSION {
+        return Err(RoomLimitReached);
+      }
+    }
+    for room in msg.subscribe {
+      self.enter_room(room, msg.id);
     }
 
-    self.post_rooms.get_mut(&post_id).unwrap().insert(id);
+    Ok(
+      self
+        .sessions
+        .get(&msg.id)
+        .map(|info| info.rooms.iter().cloned().collect())
+        .unwrap_or_default(),
+    )
   }
 
-  pub fn join_user_room(&mut self, user_id: UserId, id: ConnectionId) {
-    // remove session from all rooms
-    for sessions in self.user_rooms.values_mut() {
-      sessions.remove(&id);
-    }
+  fn enter_room(&mut self, room: Room, id: ConnectionId) {
+    let info = match self.sessions.get_mut(&id) {
+      Some(info) => info,
+      None => return,
+    };
+    info.rooms.insert(room);
+    let (rooms, room_id) = self.rooms_of(room);
+    rooms.entry(room_id).or_default().insert(id);
+  }
 
-    // If the room doesn't exist yet
-    if self.user_rooms.get_mut(&user_id).is_none() {
-      self.user_rooms.insert(user_id, HashSet::new());
+  fn leave_room(&mut self, room: Room, id: ConnectionId) {
+    if let Some(info) = self.sessions.get_mut(&id) {
+      info.rooms.remove(&room);
     }
+    self.remove_from_room(room, id);
+  }
 
-    self.user_rooms.get_mut(&user_id).unwrap().insert(id);
+  fn leave_rooms(&mut self, id: ConnectionId, leave: impl Fn(&Room) -> bool) {
+    let rooms = match self.sessions.get(&id) {
+      Some(info) => info.rooms.iter().filter(|r| leave(r)).cloned().collect(),
+      None => Vec::new(),
+    };
+    for room in rooms {
+      self.leave_room(room, id);
+    }
   }
 
-  fn send_post_room_message<Response>(
-    &self,
-    op: &UserOperation,
-    response: &Response,
-    post_id: PostId,
-    my_id: Option<ConnectionId>,
-  ) -> Result<(), LemmyError>
-  where
-    Response: Serialize,
-  {
-    let res_str = &to_json_string(op, response)?;
-    if let Some(sessions) = self.post_rooms.get(&post_id) {
-      for id in sessions {
-        if let Some(my_id) = my_id {
-          if *id == my_id {
-            continue;
-          }
-        }
-        self.sendit(res_str, *id);
+  /// The rooms nobody is in anymore are dropped
+  fn remove_from_room(&mut self, room: Room, id: ConnectionId) {
+    let (rooms, room_id) = self.rooms_of(room);
+    if let Some(sessions) = rooms.get_mut(&room_id) {
+      sessions.remove(&id);
+      if sessions.is_empty() {
+        rooms.remove(&room_id);
       }
     }
-    Ok(())
+  }
+
+  fn rooms_of(&mut self, room: Room) -> (&mut HashMap<i32, HashSet<ConnectionId>>, i32) {
+    match room {
+      Room::Post(post_id) => (&mut self.post_rooms, post_id),
+      Room::Community(community_id) => (&mut self.community_rooms, community_id),
+      Room::User(user_id) => (&mut self.user_rooms, user_id),
+      Room::Conversation(conversation_id) => (&mut self.conversation_rooms, conversation_id),
+    }
   }
 
   pub fn send_community_room_message<Response>(
@@ -273,16 +551,7 @@ impl ChatServer {
     Response: Serialize,
   {
     let res_str = &to_json_string(op, response)?;
-    if let Some(sessions) = self.community_rooms.get(&community_id) {
-      for id in sessions {
-        if let Some(my_id) = my_id {
-          if *id == my_id {
-            continue;
-          }
-        }
-        self.sendit(res_str, *id);
-      }
-    }
+    self.broadcast(BroadcastTarget::CommunityRoom(community_id), res_str, my_id);
     Ok(())
   }
 
@@ -296,14 +565,7 @@ impl ChatServer {
     Response: Serialize,
   {
     let res_str = &to_json_string(op, response)?;
-    for id in self.sessions.keys() {
-      if let Some(my_id) = my_id {
-        if *id == my_id {
-          continue;
-        }
-      }
-      self.sendit(res_str, *id);
-    }
+    self.broadcast(BroadcastTarget::All, res_str, my_id);
     Ok(())
   }
 
@@ -318,16 +580,29 @@ impl ChatServer {
     Response: Serialize,
   {
     let res_str = &to_json_string(op, response)?;
-    if let Some(sessions) = self.user_rooms.get(&recipient_id) {
-      for id in sessions {
-        if let Some(my_id) = my_id {
-          if *id == my_id {
-            continue;
-          }
-        }
-        self.sendit(res_str, *id);
-      }
-    }
+    self.broadcast(BroadcastTarget::UserRoom(recipient_id), res_str, my_id);
+    Ok(())
+  }
+
+  /// Sends a private message to everyone reading its conversation, and optionally to the
+  /// recipient's sessions so their inbox updates. A session in both only gets it once.
+  pub fn send_private_message(
+    &self,
+    user_operation: &UserOperation,
+    message: &PrivateMessageResponse,
+    notify_recipient: bool,
+    my_id: Option<ConnectionId>,
+  ) -> Result<(), LemmyError> {
+    let res_str = &to_json_string(user_operation, message)?;
+    let target = BroadcastTarget::PrivateMessage {
+      conversation_id: message.message.conversation_id,
+      recipient_id: if notify_recipient {
+        Some(message.message.recipient_id)
+      } else {
+        None
+      },
+    };
+    self.broadcast(target, res_str, my_id);
     Ok(())
   }
 
@@ -337,6 +612,21 @@ impl ChatServer {
     comment: &CommentResponse,
     my_id: Option<ConnectionId>,
   ) -> Result<(), LemmyError> {
+    // A vote only changes the scores, which is all the open post needs
+    if let UserOperation::CreateCommentLike = user_operation {
+      let score = CommentScoreResponse {
+        comment_id: comment.comment.id,
+        post_id: comment.comment.post_id,
+        score: comment.comment.score,
+        upvotes: comment.comment.upvotes,
+        downvotes: comment.comment.downvotes,
+      };
+      let res_str = &to_json_string(&UserOperation::CommentScore, &score)?;
+      let target = BroadcastTarget::Rooms(vec![Room::Post(score.post_id)]);
+      self.broadcast(target, res_str, my_id);
+      return Ok(());
+    }
+
     let mut comment_reply_sent = comment.clone();
     comment_reply_sent.comment.my_vote = None;
     comment_reply_sent.comment.user_id = None;
@@ -344,28 +634,20 @@ impl ChatServer {
     let mut comment_post_sent = comment_reply_sent.clone();
     comment_post_sent.recipient_ids = Vec::new();
 
-    // Send it to the post room
-    self.send_post_room_message(
-      user_operation,
-      &comment_post_sent,
-      comment_post_sent.comment.post_id,
-      my_id,
-    )?;
+    // Send it to the post room, /c/all and the community
+    let res_str = &to_json_string(user_operation, &comment_post_sent)?;
+    let target = BroadcastTarget::Rooms(vec![
+      Room::Post(comment.comment.post_id),
+      Room::Community(0),
+      Room::Community(comment.comment.community_id),
+    ]);
+    self.broadcast(target, res_str, my_id);
 
     // Send it to the recipient(s) including the mentioned users
     for recipient_id in &comment_reply_sent.recipient_ids {
       self.send_user_room_message(user_operation, &comment_reply_sent, *recipient_id, my_id)?;
     }
 
-    // Send it to the community too
-    self.send_community_room_message(user_operation, &comment_post_sent, 0, my_id)?;
-    self.send_community_room_message(
-      user_operation,
-      &comment_post_sent,
-      comment.comment.community_id,
-      my_id,
-    )?;
-
     Ok(())
   }
 
@@ -377,21 +659,135 @@ impl ChatServer {
   ) -> Result<(), LemmyError> {
     let community_id = post.post.community_id;
 
+    // Every change to a post comes through here. The scores of votes can wait for the cache to
+    // expire.
+    match user_operation {
+      UserOperation::CreatePostLike => (),
+      _ => invalidate_post_listings(Some(community_id)),
+    }
+
+    let target = BroadcastTarget::Rooms(vec![
+      Room::Community(0),
+      Room::Community(community_id),
+      Room::Post(post.post.id),
+    ]);
+
+    // The listings only need the new scores of a vote
+    if let UserOperation::CreatePostLike = user_operation {
+      let score = PostScoreResponse {
+        post_id: post.post.id,
+        community_id,
+        score: post.post.score,
+        upvotes: post.post.upvotes,
+        downvotes: post.post.downvotes,
+      };
+      let res_str = &to_json_string(&UserOperation::PostScore, &score)?;
+      self.broadcast(target, res_str, my_id);
+      return Ok(());
+    }
+
     // Don't send my data with it
     let mut post_sent = post.clone();
     post_sent.post.my_vote = None;
     post_sent.post.user_id = None;
 
-    // Send it to /c/all and that community
-    self.send_community_room_message(user_operation, &post_sent, 0, my_id)?;
-    self.send_community_room_message(user_operation, &post_sent, community_id, my_id)?;
+    // Send it to /c/all, that community and the post room
+    let res_str = &to_json_string(user_operation, &post_sent)?;
+    self.broadcast(target, res_str, my_id);
+    Ok(())
+  }
+
+  /// Sends a new post to the subscribers who asked for it, skipping the sessions that already
+  /// got it from /c/all or the community room.
+  pub fn send_post_to_subscribers(
+    &self,
+    user_operation: &UserOperation,
+    post: &PostResponse,
+    subscriber_ids: &[UserId],
+    my_id: Option<ConnectionId>,
+  ) -> Result<(), LemmyError> {
+    let community_id = post.post.community_id;
 
-    // Send it to the post room
-    self.send_post_room_message(user_operation, &post_sent, post.post.id, my_id)?;
+    // Don't send my data with it
+    let mut post_sent = post.clone();
+    post_sent.post.my_vote = None;
+    post_sent.post.user_id = None;
 
+    let res_str = &to_json_string(user_operation, &post_sent)?;
+    let target = BroadcastTarget::Subscribers {
+      community_id,
+      subscriber_ids: subscriber_ids.to_vec(),
+    };
+    self.broadcast(target, res_str, my_id);
     Ok(())
   }
 
+  /// Sends the message to the sessions here, and has the other server processes send it to
+  /// theirs.
+  fn broadcast(&self, target: BroadcastTarget, message: &str, my_id: Option<ConnectionId>) {
+    self.send_to_target(&target, message, my_id);
+    if let Some(pubsub) = &self.pubsub {
+      pubsub.publish(target, message);
+    }
+  }
+
+  /// A session in more than one of the rooms only gets the message once.
+  fn send_to_target(&self, target: &BroadcastTarget, message: &str, my_id: Option<ConnectionId>) {
+    let room = |rooms: &HashMap<i32, HashSet<ConnectionId>>, room_id: &i32| {
+      rooms.get(room_id).cloned().unwrap_or_default()
+    };
+
+    let sessions: HashSet<ConnectionId> = match target {
+      BroadcastTarget::All => self.sessions.keys().cloned().collect(),
+      BroadcastTarget::Rooms(rooms) => rooms
+        .iter()
+        .flat_map(|r| match r {
+          Room::Post(post_id) => room(&self.post_rooms, post_id),
+          Room::Community(community_id) => room(&self.community_rooms, community_id),
+          Room::User(user_id) => room(&self.user_rooms, user_id),
+          Room::Conversation(conversation_id) => room(&self.conversation_rooms, conversation_id),
+        })
+        .collect(),
+      BroadcastTarget::CommunityRoom(community_id) => room(&self.community_rooms, community_id),
+      BroadcastTarget::UserRoom(user_id) => room(&self.user_rooms, user_id),
+      BroadcastTarget::PrivateMessage {
+        conversation_id,
+        recipient_id,
+      } => {
+        let mut sessions = room(&self.conversation_rooms, conversation_id);
+        if let Some(recipient_id) = recipient_id {
+          sessions.extend(room(&self.user_rooms, recipient_id));
+        }
+        sessions
+      }
+      BroadcastTarget::Subscribers {
+        community_id,
+        subscriber_ids,
+      } => {
+        let already_sent = |id: &ConnectionId| {
+          [0, *community_id].iter().any(|room_id| {
+            self
+              .community_rooms
+              .get(room_id)
+              .map_or(false, |sessions| sessions.contains(id))
+          })
+        };
+        subscriber_ids
+          .iter()
+          .flat_map(|subscriber_id| room(&self.user_rooms, subscriber_id))
+          .filter(|id| !already_sent(id))
+          .collect()
+      }
+    };
+
+    for id in sessions {
+      if Some(id) == my_id {
+        continue;
+      }
+      self.sendit(message, id);
+    }
+  }
+
   fn sendit(&self, message: &str, id: ConnectionId) {
     if let Some(info) = self.sessions.get(&id) {
       let _ = info.addr.do_send(WSMessage(message.to_owned()));
@@ -423,6 +819,12 @@ impl ChatServer {
 
       let user_operation: UserOperation = UserOperation::from_str(&op)?;
 
+      // Only needed for the rate limit exemptions, the ops check the auth themselves
+      let user_id = json["data"]["auth"]
+        .as_str()
+        .and_then(|auth| Claims::decode(auth).ok())
+        .map(|claims| claims.claims.id);
+
       let args = Args {
         client,
         pool,
@@ -430,6 +832,7 @@ impl ChatServer {
         chatserver: addr,
         id: msg.id,
         ip,
+        user_id,
         op: user_operation.clone(),
         data,
       };
@@ -438,33 +841,109 @@ impl ChatServer {
         // User ops
         UserOperation::Login => do_user_operation::<Login>(args).await,
         UserOperation::Register => do_user_operation::<Register>(args).await,
+        UserOperation::GetCaptcha => do_user_operation::<GetCaptcha>(args).await,
         UserOperation::GetUserDetails => do_user_operation::<GetUserDetails>(args).await,
+        UserOperation::GetUserStats => do_user_operation::<GetUserStats>(args).await,
+        UserOperation::FollowUser => do_user_operation::<FollowUser>(args).await,
+        UserOperation::GetFollowedUsersFeed => {
+          do_user_operation::<GetFollowedUsersFeed>(args).await
+        }
         UserOperation::GetReplies => do_user_operation::<GetReplies>(args).await,
         UserOperation::AddAdmin => do_user_operation::<AddAdmin>(args).await,
         UserOperation::BanUser => do_user_operation::<BanUser>(args).await,
+        UserOperation::ShadowBanUser => do_user_operation::<ShadowBanUser>(args).await,
         UserOperation::GetUserMentions => do_user_operation::<GetUserMentions>(args).await,
+        UserOperation::GetUnreadCount => do_user_operation::<GetUnreadCount>(args).await,
+        UserOperation::GetInbox => do_user_operation::<GetInbox>(args).await,
         UserOperation::EditUserMention => do_user_operation::<EditUserMention>(args).await,
         UserOperation::MarkAllAsRead => do_user_operation::<MarkAllAsRead>(args).await,
         UserOperation::DeleteAccount => do_user_operation::<DeleteAccount>(args).await,
+        UserOperation::ExportUserData => do_user_operation::<ExportUserData>(args).await,
+        UserOperation::GetUserDataExport => do_user_operation::<GetUserDataExport>(args).await,
+        UserOperation::ImportUserData => do_user_operation::<ImportUserData>(args).await,
         UserOperation::PasswordReset => do_user_operation::<PasswordReset>(args).await,
         UserOperation::PasswordChange => do_user_operation::<PasswordChange>(args).await,
+        UserOperation::VerifyEmail => do_user_operation::<VerifyEmail>(args).await,
+        UserOperation::ResendVerificationEmail => {
+          do_user_operation::<ResendVerificationEmail>(args).await
+        }
         UserOperation::CreatePrivateMessage => {
           do_user_operation::<CreatePrivateMessage>(args).await
         }
         UserOperation::EditPrivateMessage => do_user_operation::<EditPrivateMessage>(args).await,
         UserOperation::GetPrivateMessages => do_user_operation::<GetPrivateMessages>(args).await,
+        UserOperation::GetPrivateMessageConversations => {
+          do_user_operation::<GetPrivateMessageConversations>(args).await
+        }
+        UserOperation::GetPrivateMessageConversation => {
+          do_user_operation::<GetPrivateMessageConversation>(args).await
+        }
         UserOperation::UserJoin => do_user_operation::<UserJoin>(args).await,
+        UserOperation::Subscribe => do_user_operation::<Subscribe>(args).await,
+        UserOperation::Unsubscribe => do_user_operation::<Unsubscribe>(args).await,
+        UserOperation::ReAuth => do_user_operation::<ReAuth>(args).await,
         UserOperation::SaveUserSettings => do_user_operation::<SaveUserSettings>(args).await,
+        UserOperation::ChangeUsername => do_user_operation::<ChangeUsername>(args).await,
+        UserOperation::GetNotificationSettings => {
+          do_user_operation::<GetNotificationSettings>(args).await
+        }
+        UserOperation::SaveNotificationSettings => {
+          do_user_operation::<SaveNotificationSettings>(args).await
+        }
+        UserOperation::UnsubscribeDigest => do_user_operation::<UnsubscribeDigest>(args).await,
+        UserOperation::GetPushPublicKey => do_user_operation::<GetPushPublicKey>(args).await,
+        UserOperation::RegisterPushSubscription => {
+          do_user_operation::<RegisterPushSubscription>(args).await
+        }
+        UserOperation::DeletePushSubscription => {
+          do_user_operation::<DeletePushSubscription>(args).await
+        }
+        UserOperation::ListMutedKeywords => do_user_operation::<ListMutedKeywords>(args).await,
+        UserOperation::CreateMutedKeyword => do_user_operation::<CreateMutedKeyword>(args).await,
+        UserOperation::EditMutedKeyword => do_user_operation::<EditMutedKeyword>(args).await,
+        UserOperation::DeleteMutedKeyword => do_user_operation::<DeleteMutedKeyword>(args).await,
+        UserOperation::ListImages => do_user_operation::<ListImages>(args).await,
+        UserOperation::DeleteImage => do_user_operation::<DeleteImage>(args).await,
 
         // Site ops
         UserOperation::GetModlog => do_user_operation::<GetModlog>(args).await,
         UserOperation::CreateSite => do_user_operation::<CreateSite>(args).await,
         UserOperation::EditSite => do_user_operation::<EditSite>(args).await,
         UserOperation::GetSite => do_user_operation::<GetSite>(args).await,
+        UserOperation::CreateSiteAnnouncement => {
+          do_user_operation::<CreateSiteAnnouncement>(args).await
+        }
+        UserOperation::DeleteSiteAnnouncement => {
+          do_user_operation::<DeleteSiteAnnouncement>(args).await
+        }
+        UserOperation::DismissSiteAnnouncement => {
+          do_user_operation::<DismissSiteAnnouncement>(args).await
+        }
+        UserOperation::GetSiteDocument => do_user_operation::<GetSiteDocument>(args).await,
+        UserOperation::SaveSiteDocument => do_user_operation::<SaveSiteDocument>(args).await,
+        UserOperation::DeleteSiteDocument => do_user_operation::<DeleteSiteDocument>(args).await,
         UserOperation::GetSiteConfig => do_user_operation::<GetSiteConfig>(args).await,
         UserOperation::SaveSiteConfig => do_user_operation::<SaveSiteConfig>(args).await,
+        UserOperation::GetCommunityDefaults => {
+          do_user_operation::<GetCommunityDefaults>(args).await
+        }
+        UserOperation::EditCommunityDefaults => {
+          do_user_operation::<EditCommunityDefaults>(args).await
+        }
+        UserOperation::GetJobQueue => do_user_operation::<GetJobQueue>(args).await,
+        UserOperation::ImportCommunity => do_user_operation::<ImportCommunity>(args).await,
+        UserOperation::GetCommunityImport => do_user_operation::<GetCommunityImport>(args).await,
         UserOperation::Search => do_user_operation::<Search>(args).await,
         UserOperation::TransferCommunity => do_user_operation::<TransferCommunity>(args).await,
+        UserOperation::RequestCommunityTakeover => {
+          do_user_operation::<RequestCommunityTakeover>(args).await
+        }
+        UserOperation::ListCommunityTakeoverRequests => {
+          do_user_operation::<ListCommunityTakeoverRequests>(args).await
+        }
+        UserOperation::ResolveCommunityTakeoverRequest => {
+          do_user_operation::<ResolveCommunityTakeoverRequest>(args).await
+        }
         UserOperation::TransferSite => do_user_operation::<TransferSite>(args).await,
         UserOperation::ListCategories => do_user_operation::<ListCategories>(args).await,
 
@@ -479,6 +958,107 @@ impl ChatServer {
         }
         UserOperation::BanFromCommunity => do_user_operation::<BanFromCommunity>(args).await,
         UserOperation::AddModToCommunity => do_user_operation::<AddModToCommunity>(args).await,
+        UserOperation::ListCommunityFlairs => do_user_operation::<ListCommunityFlairs>(args).await,
+        UserOperation::CreateCommunityFlair => {
+          do_user_operation::<CreateCommunityFlair>(args).await
+        }
+        UserOperation::EditCommunityFlair => do_user_operation::<EditCommunityFlair>(args).await,
+        UserOperation::DeleteCommunityFlair => {
+          do_user_operation::<DeleteCommunityFlair>(args).await
+        }
+        UserOperation::ListCommunityRules => do_user_operation::<ListCommunityRules>(args).await,
+        UserOperation::CreateCommunityRule => do_user_operation::<CreateCommunityRule>(args).await,
+        UserOperation::EditCommunityRule => do_user_operation::<EditCommunityRule>(args).await,
+        UserOperation::DeleteCommunityRule => do_user_operation::<DeleteCommunityRule>(args).await,
+        UserOperation::ListAutomodRules => do_user_operation::<ListAutomodRules>(args).await,
+        UserOperation::CreateAutomodRule => do_user_operation::<CreateAutomodRule>(args).await,
+        UserOperation::EditAutomodRule => do_user_operation::<EditAutomodRule>(args).await,
+        UserOperation::DeleteAutomodRule => do_user_operation::<DeleteAutomodRule>(args).await,
+        UserOperation::TestAutomodRules => do_user_operation::<TestAutomodRules>(args).await,
+        UserOperation::ListBlockedDomains => do_user_operation::<ListBlockedDomains>(args).await,
+        UserOperation::CreateBlockedDomain => do_user_operation::<CreateBlockedDomain>(args).await,
+        UserOperation::DeleteBlockedDomain => do_user_operation::<DeleteBlockedDomain>(args).await,
+        UserOperation::ListWebhooks => do_user_operation::<ListWebhooks>(args).await,
+        UserOperation::CreateWebhook => do_user_operation::<CreateWebhook>(args).await,
+        UserOperation::EditWebhook => do_user_operation::<EditWebhook>(args).await,
+        UserOperation::DeleteWebhook => do_user_operation::<DeleteWebhook>(args).await,
+        UserOperation::GetRateLimits => do_user_operation::<GetRateLimits>(args).await,
+        UserOperation::EditRateLimits => do_user_operation::<EditRateLimits>(args).await,
+        UserOperation::SetEndpointRateLimit => {
+          do_user_operation::<SetEndpointRateLimit>(args).await
+        }
+        UserOperation::RemoveEndpointRateLimit => {
+          do_user_operation::<RemoveEndpointRateLimit>(args).await
+        }
+        UserOperation::ExemptFromRateLimits => {
+          do_user_operation::<ExemptFromRateLimits>(args).await
+        }
+        UserOperation::GetFederationSettings => {
+          do_user_operation::<GetFederationSettings>(args).await
+        }
+        UserOperation::EditFederationSettings => {
+          do_user_operation::<EditFederationSettings>(args).await
+        }
+        UserOperation::GetPostingRequirements => {
+          do_user_operation::<GetPostingRequirements>(args).await
+        }
+        UserOperation::SetPostingRequirements => {
+          do_user_operation::<SetPostingRequirements>(args).await
+        }
+        UserOperation::ListWordFilters => do_user_operation::<ListWordFilters>(args).await,
+        UserOperation::CreateWordFilter => do_user_operation::<CreateWordFilter>(args).await,
+        UserOperation::EditWordFilter => do_user_operation::<EditWordFilter>(args).await,
+        UserOperation::DeleteWordFilter => do_user_operation::<DeleteWordFilter>(args).await,
+        UserOperation::BulkModeratePosts => do_user_operation::<BulkModeratePosts>(args).await,
+        UserOperation::BulkModerateComments => {
+          do_user_operation::<BulkModerateComments>(args).await
+        }
+        UserOperation::ListPendingPosts => do_user_operation::<ListPendingPosts>(args).await,
+        UserOperation::ApprovePost => do_user_operation::<ApprovePost>(args).await,
+        UserOperation::ListEmailDomains => do_user_operation::<ListEmailDomains>(args).await,
+        UserOperation::CreateEmailDomain => do_user_operation::<CreateEmailDomain>(args).await,
+        UserOperation::DeleteEmailDomain => do_user_operation::<DeleteEmailDomain>(args).await,
+        UserOperation::ListIpBans => do_user_operation::<ListIpBans>(args).await,
+        UserOperation::CreateIpBan => do_user_operation::<CreateIpBan>(args).await,
+        UserOperation::DeleteIpBan => do_user_operation::<DeleteIpBan>(args).await,
+        UserOperation::PurgeUser => do_user_operation::<PurgeUser>(args).await,
+        UserOperation::PurgePost => do_user_operation::<PurgePost>(args).await,
+        UserOperation::PurgeComment => do_user_operation::<PurgeComment>(args).await,
+        UserOperation::PurgeCommunity => do_user_operation::<PurgeCommunity>(args).await,
+        UserOperation::CreateModNote => do_user_operation::<CreateModNote>(args).await,
+        UserOperation::DeleteModNote => do_user_operation::<DeleteModNote>(args).await,
+        UserOperation::ListModNotes => do_user_operation::<ListModNotes>(args).await,
+        UserOperation::CreateAppeal => do_user_operation::<CreateAppeal>(args).await,
+        UserOperation::ListAppeals => do_user_operation::<ListAppeals>(args).await,
+        UserOperation::GetMyAppeals => do_user_operation::<GetMyAppeals>(args).await,
+        UserOperation::ResolveAppeal => do_user_operation::<ResolveAppeal>(args).await,
+
+        // Community wiki ops
+        UserOperation::CreateWikiPage => do_user_operation::<CreateWikiPage>(args).await,
+        UserOperation::EditWikiPage => do_user_operation::<EditWikiPage>(args).await,
+        UserOperation::DeleteWikiPage => do_user_operation::<DeleteWikiPage>(args).await,
+        UserOperation::GetWikiPage => do_user_operation::<GetWikiPage>(args).await,
+        UserOperation::ListWikiPages => do_user_operation::<ListWikiPages>(args).await,
+        UserOperation::GetWikiPageRevisions => {
+          do_user_operation::<GetWikiPageRevisions>(args).await
+        }
+
+        // Report ops
+        UserOperation::CreatePostReport => do_user_operation::<CreatePostReport>(args).await,
+        UserOperation::CreateCommentReport => do_user_operation::<CreateCommentReport>(args).await,
+        UserOperation::ListReports => do_user_operation::<ListReports>(args).await,
+        UserOperation::ResolvePostReport => do_user_operation::<ResolvePostReport>(args).await,
+        UserOperation::ResolveCommentReport => {
+          do_user_operation::<ResolveCommentReport>(args).await
+        }
+
+        // Custom feed ops
+        UserOperation::CreateCustomFeed => do_user_operation::<CreateCustomFeed>(args).await,
+        UserOperation::EditCustomFeed => do_user_operation::<EditCustomFeed>(args).await,
+        UserOperation::DeleteCustomFeed => do_user_operation::<DeleteCustomFeed>(args).await,
+        UserOperation::GetCustomFeed => do_user_operation::<GetCustomFeed>(args).await,
+        UserOperation::ListCustomFeeds => do_user_operation::<ListCustomFeeds>(args).await,
+        UserOperation::GetCustomFeedPosts => do_user_operation::<GetCustomFeedPosts>(args).await,
 
         // Post ops
         UserOperation::CreatePost => do_user_operation::<CreatePost>(args).await,
@@ -487,13 +1067,27 @@ impl ChatServer {
         UserOperation::EditPost => do_user_operation::<EditPost>(args).await,
         UserOperation::CreatePostLike => do_user_operation::<CreatePostLike>(args).await,
         UserOperation::SavePost => do_user_operation::<SavePost>(args).await,
+        UserOperation::HidePost => do_user_operation::<HidePost>(args).await,
+        UserOperation::WatchPost => do_user_operation::<WatchPost>(args).await,
+        UserOperation::GetPostRevisions => do_user_operation::<GetPostRevisions>(args).await,
+        UserOperation::RefreshPostEmbed => do_user_operation::<RefreshPostEmbed>(args).await,
+        UserOperation::ListScheduledPosts => do_user_operation::<ListScheduledPosts>(args).await,
+        UserOperation::CreateScheduledPost => do_user_operation::<CreateScheduledPost>(args).await,
+        UserOperation::DeleteScheduledPost => do_user_operation::<DeleteScheduledPost>(args).await,
 
         // Comment ops
         UserOperation::CreateComment => do_user_operation::<CreateComment>(args).await,
         UserOperation::EditComment => do_user_operation::<EditComment>(args).await,
         UserOperation::SaveComment => do_user_operation::<SaveComment>(args).await,
+        UserOperation::PinComment => do_user_operation::<PinComment>(args).await,
+        UserOperation::GetCommentRevisions => do_user_operation::<GetCommentRevisions>(args).await,
         UserOperation::GetComments => do_user_operation::<GetComments>(args).await,
         UserOperation::CreateCommentLike => do_user_operation::<CreateCommentLike>(args).await,
+        // Only sent by the server
+        UserOperation::CommentScore
+        | UserOperation::PostScore
+        | UserOperation::UsersOnline
+        | UserOperation::AutomodHit => Err(APIError::err("unknown_op").into()),
       }
     }
   }
@@ -506,6 +1100,7 @@ struct Args<'a> {
   chatserver: Addr<ChatServer>,
   id: ConnectionId,
   ip: IPAddr,
+  user_id: Option<i32>,
   op: UserOperation,
   data: &'a str,
 }
@@ -522,6 +1117,7 @@ where
     chatserver,
     id,
     ip,
+    user_id,
     op,
     data,
   } = args;
@@ -535,21 +1131,37 @@ where
   let op2 = op.clone();
 
   let client = client.clone();
+  let oper_ip = ip.clone();
+  let span = info_span!(
+    "ws_operation",
+    request_id = %Uuid::new_v4(),
+    op = %op.to_string(),
+    user_id = field::debug(user_id),
+  );
   let fut = async move {
     let pool = pool.clone();
     let parsed_data: Data = serde_json::from_str(&data)?;
+    let start = Instant::now();
     let res = Oper::new(parsed_data, client)
+      .with_ip(oper_ip)
       .perform(&pool, Some(ws_info))
-      .await?;
-    to_json_string(&op, &res)
-  };
-
-  match op2 {
-    UserOperation::Register => rate_limiter.register().wrap(ip, fut).await,
-    UserOperation::CreatePost => rate_limiter.post().wrap(ip, fut).await,
-    UserOperation::CreateCommunity => rate_limiter.register().wrap(ip, fut).await,
-    _ => rate_limiter.message().wrap(ip, fut).await,
+      .await;
+    observe_request("WS", &op.to_string(), start.elapsed());
+    to_json_string(&op, &res?)
   }
+  .instrument(span);
+
+  let endpoint = op2.to_string();
+  let rate_limited = match op2 {
+    UserOperation::Register => rate_limiter.register(),
+    UserOperation::ResendVerificationEmail => rate_limiter.register(),
+    UserOperation::CreatePost => rate_limiter.post(),
+    UserOperation::CreateCommunity => rate_limiter.register(),
+    UserOperation::CreatePostReport => rate_limiter.report(),
+    UserOperation::CreateCommentReport => rate_limiter.report(),
+    _ => rate_limiter.message(),
+  };
+  rate_limited.wrap(ip, endpoint, user_id, fut).await
 }
 
 /// Make actor from `ChatServer`
@@ -557,6 +1169,13 @@ impl Actor for ChatServer {
   /// We are going to use simple Context, we just need ability to communicate
   /// with other actors.
   type Context = Context<Self>;
+
+  fn started(&mut self, ctx: &mut Self::Context) {
+    if let Some(pubsub) = self.pubsub.clone() {
+      actix_rt::spawn(pubsub.listen(ctx.address()));
+    }
+    ctx.run_interval(USERS_ONLINE_INTERVAL, |act, _| act.send_users_online());
+  }
 }
 
 /// Handler for Connect message.
@@ -574,9 +1193,13 @@ impl Handler<Connect> for ChatServer {
       id,
       SessionInfo {
         addr: msg.addr,
+        close: msg.close,
         ip: msg.ip,
+        rooms: HashSet::new(),
+        user: None,
       },
     );
+    set_websocket_connections(self.sessions.len());
 
     id
   }
@@ -587,20 +1210,13 @@ impl Handler<Disconnect> for ChatServer {
   type Result = ();
 
   fn handle(&mut self, msg: Disconnect, _: &mut Context<Self>) {
-    // Remove connections from sessions and all 3 scopes
-    if self.sessions.remove(&msg.id).is_some() {
-      for sessions in self.user_rooms.values_mut() {
-        sessions.remove(&msg.id);
-      }
-
-      for sessions in self.post_rooms.values_mut() {
-        sessions.remove(&msg.id);
-      }
-
-      for sessions in self.community_rooms.values_mut() {
-        sessions.remove(&msg.id);
+    // Remove connections from sessions and the rooms they were in
+    if let Some(info) = self.sessions.remove(&msg.id) {
+      for room in info.rooms {
+        self.remove_from_room(room, msg.id);
       }
     }
+    set_websocket_connections(self.sessions.len());
   }
 }
 
@@ -618,7 +1234,7 @@ impl Handler<StandardMessage> for ChatServer {
         }
         Err(e) => {
           error!("Error during message handling {}", e);
-          Ok(e.to_string())
+          Ok(serde_json::to_string(&e.to_response()).unwrap_or_default())
         }
       }
     })
@@ -672,6 +1288,16 @@ impl Handler<SendPost> for ChatServer {
   }
 }
 
+impl Handler<SendPostToSubscribers> for ChatServer {
+  type Result = ();
+
+  fn handle(&mut self, msg: SendPostToSubscribers, _: &mut Context<Self>) {
+    self
+      .send_post_to_subscribers(&msg.op, &msg.post, &msg.subscriber_ids, msg.my_id)
+      .unwrap();
+  }
+}
+
 impl Handler<SendComment> for ChatServer {
   type Result = ();
 
@@ -680,11 +1306,43 @@ impl Handler<SendComment> for ChatServer {
   }
 }
 
+impl Handler<SendPrivateMessage> for ChatServer {
+  type Result = ();
+
+  fn handle(&mut self, msg: SendPrivateMessage, _: &mut Context<Self>) {
+    self
+      .send_private_message(&msg.op, &msg.message, msg.notify_recipient, msg.my_id)
+      .unwrap();
+  }
+}
+
+impl Handler<Shutdown> for ChatServer {
+  type Result = ();
+
+  fn handle(&mut self, _: Shutdown, _: &mut Context<Self>) {
+    for info in self.sessions.values() {
+      let _ = info.close.do_send(CloseSession);
+    }
+  }
+}
+
+impl Handler<Broadcast> for ChatServer {
+  type Result = ();
+
+  fn handle(&mut self, msg: Broadcast, _: &mut Context<Self>) {
+    self.send_to_target(&msg.target, &msg.message, None);
+  }
+}
+
 impl Handler<JoinUserRoom> for ChatServer {
   type Result = ();
 
   fn handle(&mut self, msg: JoinUserRoom, _: &mut Context<Self>) {
-    self.join_user_room(msg.user_id, msg.id);
+    let user = SessionUser {
+      id: msg.user_id,
+      hide_presence: msg.hide_presence,
+    };
+    self.join_user_room(user, msg.id);
   }
 }
 
@@ -704,6 +1362,56 @@ impl Handler<JoinPostRoom> for ChatServer {
   }
 }
 
+impl Handler<JoinConversationRoom> for ChatServer {
+  type Result = ();
+
+  fn handle(&mut self, msg: JoinConversationRoom, _: &mut Context<Self>) {
+    self.join_conversation_room(msg.conversation_id, msg.id);
+  }
+}
+
+impl Handler<ChangeSubscriptions> for ChatServer {
+  type Result = Result<Vec<Room>, RoomLimitReached>;
+
+  fn handle(&mut self, msg: ChangeSubscriptions, _: &mut Context<Self>) -> Self::Result {
+    self.change_subscriptions(msg)
+  }
+}
+
+impl Handler<ChangeSessionUser> for ChatServer {
+  type Result = ();
+
+  fn handle(&mut self, msg: ChangeSessionUser, _: &mut Context<Self>) {
+    match msg.user {
+      Some(user) => self.join_user_room(user, msg.id),
+      None => self.leave_user_room(msg.id),
+    }
+  }
+}
+
+impl Handler<SendUnreadCounts> for ChatServer {
+  type Result = ();
+
+  /// Counted outside of the actor, which goes on with the other messages in the meantime
+  fn handle(&mut self, msg: SendUnreadCounts, ctx: &mut Context<Self>) {
+    let pool = self.pool.clone();
+    let addr = ctx.address();
+    actix_rt::spawn(async move {
+      for user_id in msg.user_ids {
+        match unread_count(&pool, user_id).await {
+          Ok(response) => addr.do_send(SendUserRoomMessage {
+            op: UserOperation::GetUnreadCount,
+            response,
+            recipient_id: user_id,
+            my_id: None,
+          }),
+          Err(e) => error!("Couldn't count the unread items of user {}: {}", user_id, e),
+        }
+      }
+    });
+  }
+}
+
 impl Handler<GetUsersOnline> for ChatServer {
   type Result = usize;
 
@@ -716,11 +1424,7 @@ impl Handler<GetPostUsersOnline> for ChatServer {
   type Result = usize;
 
   fn handle(&mut self, msg: GetPostUsersOnline, _: &mut Context<Self>) -> Self::Result {
-    if let Some(users) = self.post_rooms.get(&msg.post_id) {
-      users.len()
-    } else {
-      0
-    }
+    self.count_users_online(Room::Post(msg.post_id))
   }
 }
 
@@ -728,11 +1432,7 @@ impl Handler<GetCommunityUsersOnline> for ChatServer {
   type Result = usize;
 
   fn handle(&mut self, msg: GetCommunityUsersOnline, _: &mut Context<Self>) -> Self::Result {
-    if let Some(users) = self.community_rooms.get(&msg.community_id) {
-      users.len()
-    } else {
-      0
-    }
+    self.count_users_online(Room::Community(msg.community_id))
   }
 }
 